@@ -0,0 +1,194 @@
+//! Throughput benchmarks of pool operations, run with `cargo bench`. Names given after
+//! `--` filter benchmarks by substring, e.g. `cargo bench -- swap`.
+//!
+//! Every operation is measured on the fixed-point `LpPool` and on the floating-point
+//! `ReferencePool`, the only other backend of the pool math, so the cost of the
+//! fixed-point design is measured against unrounded `f64` math. Benchmarks run batches
+//! of operations on fresh pools for about a second and report the median time of a
+//! single operation, which keeps runs on the same machine comparable.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use invariant_task::*;
+
+/// operations applied to a single fresh pool
+const BATCH: usize = 1_000;
+/// how long every benchmark runs
+const TARGET: Duration = Duration::from_secs(1);
+
+struct Bencher {
+    filters: Vec<String>,
+}
+
+impl Bencher {
+    fn from_args() -> Self {
+        Self {
+            // cargo passes `--bench` to benchmarks with harness disabled
+            filters: std::env::args()
+                .skip(1)
+                .filter(|arg| !arg.starts_with("--"))
+                .collect(),
+        }
+    }
+
+    /// Measures `routine` applied `BATCH` times to state created by `setup`, setup
+    /// itself isn't measured
+    fn bench<T>(&self, name: &str, mut setup: impl FnMut() -> T, mut routine: impl FnMut(&mut T)) {
+        if !self.filters.is_empty() && !self.filters.iter().any(|filter| name.contains(filter)) {
+            return;
+        }
+        let mut samples = Vec::new();
+        let start = Instant::now();
+        while start.elapsed() < TARGET || samples.len() < 5 {
+            let mut state = setup();
+            let batch = Instant::now();
+            for _ in 0..BATCH {
+                routine(black_box(&mut state));
+            }
+            samples.push(batch.elapsed().as_nanos() as f64 / BATCH as f64);
+            black_box(state);
+        }
+        samples.sort_by(f64::total_cmp);
+        let median = samples[samples.len() / 2];
+        println!(
+            "{name:<32} {median:>10.1} ns/op {:>12.0} ops/s",
+            1e9 / median
+        );
+    }
+}
+
+fn config() -> PoolConfig {
+    PoolConfig::new(
+        1.1.into(),
+        PoolParams {
+            min_fee: 0.001.into(),
+            max_fee: 0.09.into(),
+            liquidity_target: 1_000_000.into(),
+        },
+    )
+}
+
+/// liquidity of benchmarked pools, big enough that a whole batch of swaps succeeds
+fn deposit() -> PoolOp {
+    PoolOp::AddLiquidity {
+        account: None,
+        amount: 2_000_000.into(),
+    }
+}
+
+fn fixed_pool() -> LpPool {
+    let mut pool = config().build();
+    pool.apply(&deposit()).unwrap();
+    pool
+}
+
+/// pool below its liquidity target, where fees are interpolated along the curve
+fn draining_pool() -> LpPool {
+    let mut pool = config().build();
+    pool.apply(&PoolOp::AddLiquidity {
+        account: None,
+        amount: 500_000.into(),
+    })
+    .unwrap();
+    pool
+}
+
+fn reference_pool() -> ReferencePool {
+    let mut pool = ReferencePool::from_config(&config()).expect("linear config is covered");
+    pool.apply(&deposit());
+    pool
+}
+
+fn main() {
+    let bencher = Bencher::from_args();
+    let swap = PoolOp::Swap {
+        account: None,
+        amount: 10.into(),
+    };
+    let add = PoolOp::AddLiquidity {
+        account: None,
+        amount: 10.into(),
+    };
+    let remove = PoolOp::RemoveLiquidity {
+        account: None,
+        lp_amount: 10.into(),
+    };
+
+    for (name, op) in [
+        ("swap", swap),
+        ("add_liquidity", add),
+        ("remove_liquidity", remove),
+    ] {
+        bencher.bench(&format!("{name}/fixed"), fixed_pool, |pool| {
+            black_box(pool.apply(&op)).unwrap();
+        });
+        bencher.bench(&format!("{name}/reference"), reference_pool, |pool| {
+            black_box(pool.apply(&op));
+        });
+    }
+
+    bencher.bench("quote/fixed", fixed_pool, |pool| {
+        black_box(pool.amount_for_swap(black_box(10.into()))).unwrap();
+    });
+    bencher.bench("quote_with_fee/fixed", fixed_pool, |pool| {
+        black_box(pool.fee_for_swap(black_box(10.into()))).unwrap();
+    });
+    let candidates: Vec<StakedTokenAmount> = (1..=64).map(StakedTokenAmount::from).collect();
+    bencher.bench("quote_swaps_64/fixed", fixed_pool, |pool| {
+        black_box(pool.quote_swaps(black_box(&candidates)));
+    });
+    // volatility surcharge is added to the fee curve and rounded together with it
+    let volatile_pool = || {
+        let mut pool = draining_pool();
+        pool.set_fee_policy(FeePolicy::VolatilitySensitive {
+            sensitivity: 2.0.into(),
+            max_surcharge: 0.05.into(),
+        });
+        for price in [1.2, 1.05, 1.15] {
+            pool.set_price(price.into()).unwrap();
+        }
+        pool
+    };
+    bencher.bench("quote_volatile/fixed", volatile_pool, |pool| {
+        black_box(pool.amount_for_swap(black_box(10.into()))).unwrap();
+    });
+    // above the target the fee curve is flat, below it the fee is interpolated
+    bencher.bench("quote_below_target/fixed", draining_pool, |pool| {
+        black_box(pool.amount_for_swap(black_box(10.into()))).unwrap();
+    });
+    bencher.bench("current_fee/fixed", fixed_pool, |pool| {
+        black_box(pool.current_fee());
+    });
+    bencher.bench("current_fee_below_target/fixed", draining_pool, |pool| {
+        black_box(pool.current_fee());
+    });
+
+    // fixed-point primitive behind every conversion, against always widening to u128
+    let operands = |a: Uint| move || (a, 1_500_000 as Uint, SCALE);
+    for (name, a) in [("small", 1_000_000_000), ("wide", Uint::MAX / 2)] {
+        bencher.bench(&format!("mul_div/{name}"), operands(a), |&mut (a, b, c)| {
+            black_box(mul_div(black_box(a), black_box(b), black_box(c)));
+        });
+        bencher.bench(
+            &format!("mul_div_u128/{name}"),
+            operands(a),
+            |&mut (a, b, c)| {
+                let quotient = black_box(a) as u128 * black_box(b) as u128 / black_box(c) as u128;
+                black_box(Uint::try_from(quotient).ok());
+            },
+        );
+    }
+
+    // operations of a realistic mix, including rejected ones
+    let ops = random_ops(&mut Rng::new(0), BATCH);
+    let mut index = 0;
+    bencher.bench(
+        "mixed/fixed",
+        || config().build(),
+        |pool| {
+            let _ = black_box(pool.apply(&ops[index % ops.len()]));
+            index += 1;
+        },
+    );
+}
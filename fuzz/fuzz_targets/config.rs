@@ -0,0 +1,6 @@
+//! Builds pools from decoded configurations and checks their snapshot round trip.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| invariant_task::fuzz_config(data));
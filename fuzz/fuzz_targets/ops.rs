@@ -0,0 +1,7 @@
+//! Applies decoded operation sequences to a pool built from a decoded configuration and
+//! checks conservation, snapshot round trips and replay of the operation log.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| invariant_task::fuzz_ops(data));
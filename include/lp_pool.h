@@ -0,0 +1,59 @@
+/* C interface of the invariant-task liquidity pool, built with the `ffi` feature.
+ * Amounts, prices and fees are raw fixed-point values with 6 decimals, e.g. 1500000
+ * is 1.5. Fallible functions return one of the LP_POOL_* codes, output pointers may be
+ * NULL when the value isn't needed. */
+
+#ifndef LP_POOL_H
+#define LP_POOL_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define LP_POOL_OK 0
+#define LP_POOL_NULL_POINTER 1
+#define LP_POOL_ZERO_AMOUNT 2
+#define LP_POOL_NOT_ENOUGH_LIQUIDITY 3
+#define LP_POOL_NOT_ENOUGH_LP_TOKENS 4
+#define LP_POOL_OVERFLOW 5
+#define LP_POOL_STALE_PRICE 6
+#define LP_POOL_ORACLE_ERROR 7
+#define LP_POOL_PRICE_REJECTED 8
+#define LP_POOL_PANIC 9
+#define LP_POOL_HOOK_REJECTED 10
+#define LP_POOL_UNREPLAYABLE 11
+
+/* opaque pool handle */
+typedef struct LpPool LpPool;
+
+/* creates pool, the handle has to be released with lp_pool_free */
+LpPool *lp_pool_new(uint64_t price, uint64_t min_fee, uint64_t max_fee, uint64_t liquidity_target);
+
+/* releases pool, NULL is ignored */
+void lp_pool_free(LpPool *pool);
+
+int32_t lp_pool_add_liquidity(LpPool *pool, uint64_t amount, uint64_t *lp_amount_out);
+
+int32_t lp_pool_remove_liquidity(LpPool *pool, uint64_t lp_amount, uint64_t *token_amount_out,
+                                 uint64_t *st_token_amount_out);
+
+int32_t lp_pool_swap(LpPool *pool, uint64_t amount, uint64_t *token_amount_out);
+
+/* writes outcome of a swap without modifying the pool */
+int32_t lp_pool_quote_swap(LpPool *pool, uint64_t amount, uint64_t *token_amount_out,
+                           uint64_t *fee_out);
+
+int32_t lp_pool_set_price(LpPool *pool, uint64_t price);
+
+int32_t lp_pool_advance_epoch(LpPool *pool, uint64_t epochs);
+
+int32_t lp_pool_balances(LpPool *pool, uint64_t *token_amount_out, uint64_t *st_token_amount_out,
+                         uint64_t *lp_token_amount_out, uint64_t *price_out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* LP_POOL_H */
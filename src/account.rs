@@ -0,0 +1,277 @@
+//! Borsh compatible account layout of the pool, shared with an on-chain program.
+//!
+//! Fields are encoded in declaration order of `PoolAccount` without any padding between
+//! them: integers as little endian, `bool` as a single `0`/`1` byte and `Option<T>` as
+//! a `0`/`1` tag byte followed by the value when present. The encoded struct is followed
+//! by zero bytes up to `POOL_ACCOUNT_SPACE`, reserved for fields added in later versions.
+//! Neither the order nor the meaning of existing fields may change within a version.
+
+use crate::error::AccountLayoutError;
+use crate::types::*;
+
+/// version of the account layout, stored in the first byte of the account
+pub const POOL_ACCOUNT_VERSION: u8 = 1;
+
+/// size of the encoded account including the reserved zero padding
+pub const POOL_ACCOUNT_SPACE: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Pool state kept in the on-chain account. Amounts are raw fixed-point values.
+pub struct PoolAccount {
+    pub price: Uint,
+    pub price_updated_at: Epoch,
+    pub token_amount: Uint,
+    pub st_token_amount: Uint,
+    pub lp_token_amount: Uint,
+    pub liquidity_target: Uint,
+    pub min_fee: Uint,
+    pub max_fee: Uint,
+    pub treasury_cut: Uint,
+    pub epoch: Epoch,
+    pub max_price_age: Option<Epoch>,
+    pub max_price_deviation: Option<Uint>,
+    pub max_fee_change: Option<Uint>,
+    pub monotonic_price: bool,
+    pub total_fees: Uint,
+    pub lp_fees: Uint,
+    pub treasury_fees: Uint,
+    pub claimable_treasury_fees: Uint,
+}
+
+impl PoolAccount {
+    /// Encodes account into its borsh representation, without the reserved padding
+    pub fn to_borsh(&self) -> Vec<u8> {
+        let mut bytes = vec![POOL_ACCOUNT_VERSION];
+        for value in [
+            self.price,
+            self.price_updated_at,
+            self.token_amount,
+            self.st_token_amount,
+            self.lp_token_amount,
+            self.liquidity_target,
+            self.min_fee,
+            self.max_fee,
+            self.treasury_cut,
+            self.epoch,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [
+            self.max_price_age,
+            self.max_price_deviation,
+            self.max_fee_change,
+        ] {
+            match value {
+                Some(value) => {
+                    bytes.push(1);
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+                None => bytes.push(0),
+            }
+        }
+        bytes.push(self.monotonic_price as u8);
+        for value in [
+            self.total_fees,
+            self.lp_fees,
+            self.treasury_fees,
+            self.claimable_treasury_fees,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Encodes account data of `POOL_ACCOUNT_SPACE` bytes
+    pub fn to_account_data(&self) -> Vec<u8> {
+        let mut bytes = self.to_borsh();
+        bytes.resize(POOL_ACCOUNT_SPACE, 0);
+        bytes
+    }
+
+    /// Decodes account from its borsh representation, trailing bytes have to be zero
+    /// padding
+    pub fn from_borsh(bytes: &[u8]) -> Result<Self, AccountLayoutError> {
+        let mut reader = Reader { bytes };
+        let version = reader.byte()?;
+        if version != POOL_ACCOUNT_VERSION {
+            return Err(AccountLayoutError::UnsupportedVersion(version));
+        }
+
+        let account = PoolAccount {
+            price: reader.u64()?,
+            price_updated_at: reader.u64()?,
+            token_amount: reader.u64()?,
+            st_token_amount: reader.u64()?,
+            lp_token_amount: reader.u64()?,
+            liquidity_target: reader.u64()?,
+            min_fee: reader.u64()?,
+            max_fee: reader.u64()?,
+            treasury_cut: reader.u64()?,
+            epoch: reader.u64()?,
+            max_price_age: reader.option()?,
+            max_price_deviation: reader.option()?,
+            max_fee_change: reader.option()?,
+            monotonic_price: reader.bool()?,
+            total_fees: reader.u64()?,
+            lp_fees: reader.u64()?,
+            treasury_fees: reader.u64()?,
+            claimable_treasury_fees: reader.u64()?,
+        };
+        if reader.bytes.iter().any(|byte| *byte != 0) {
+            return Err(AccountLayoutError::NonZeroPadding);
+        }
+        if account.price_updated_at > account.epoch {
+            return Err(AccountLayoutError::PriceUpdatedInFuture {
+                price_updated_at: account.price_updated_at,
+                epoch: account.epoch,
+            });
+        }
+        Ok(account)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], AccountLayoutError> {
+        let (taken, rest) = self
+            .bytes
+            .split_first_chunk::<N>()
+            .ok_or(AccountLayoutError::UnexpectedEnd)?;
+        self.bytes = rest;
+        Ok(*taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, AccountLayoutError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, AccountLayoutError> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn bool(&mut self) -> Result<bool, AccountLayoutError> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            byte => Err(AccountLayoutError::InvalidBool(byte)),
+        }
+    }
+
+    fn option(&mut self) -> Result<Option<u64>, AccountLayoutError> {
+        match self.byte()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.u64()?)),
+            byte => Err(AccountLayoutError::InvalidOptionTag(byte)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account() -> PoolAccount {
+        PoolAccount {
+            price: 1_500_000,
+            price_updated_at: 2,
+            token_amount: 100_000_000,
+            st_token_amount: 6_000_000,
+            lp_token_amount: 100_000_000,
+            liquidity_target: 90_000_000,
+            min_fee: 1_000,
+            max_fee: 90_000,
+            treasury_cut: 0,
+            epoch: 3,
+            max_price_age: Some(5),
+            max_price_deviation: None,
+            max_fee_change: Some(10_000),
+            monotonic_price: true,
+            total_fees: 9_000,
+            lp_fees: 9_000,
+            treasury_fees: 0,
+            claimable_treasury_fees: 0,
+        }
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn layout_is_stable() {
+        let expected = [
+            "01",
+            "60e3160000000000",   // price
+            "0200000000000000",   // price_updated_at
+            "00e1f50500000000",   // token_amount
+            "808d5b0000000000",   // st_token_amount
+            "00e1f50500000000",   // lp_token_amount
+            "804a5d0500000000",   // liquidity_target
+            "e803000000000000",   // min_fee
+            "905f010000000000",   // max_fee
+            "0000000000000000",   // treasury_cut
+            "0300000000000000",   // epoch
+            "010500000000000000", // max_price_age
+            "00",                 // max_price_deviation
+            "011027000000000000", // max_fee_change
+            "01",                 // monotonic_price
+            "2823000000000000",   // total_fees
+            "2823000000000000",   // lp_fees
+            "0000000000000000",   // treasury_fees
+            "0000000000000000",   // claimable_treasury_fees
+        ]
+        .concat();
+
+        assert_eq!(hex(&account().to_borsh()), expected);
+        assert_eq!(account().to_account_data().len(), POOL_ACCOUNT_SPACE);
+    }
+
+    #[test]
+    fn account_round_trips() {
+        assert_eq!(
+            PoolAccount::from_borsh(&account().to_borsh()),
+            Ok(account())
+        );
+        assert_eq!(
+            PoolAccount::from_borsh(&account().to_account_data()),
+            Ok(account())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        let mut data = account().to_account_data();
+        assert_eq!(
+            PoolAccount::from_borsh(&data[..30]),
+            Err(AccountLayoutError::UnexpectedEnd)
+        );
+
+        let future_price = PoolAccount {
+            price_updated_at: 11,
+            epoch: 10,
+            ..account()
+        };
+        assert_eq!(
+            PoolAccount::from_borsh(&future_price.to_borsh()),
+            Err(AccountLayoutError::PriceUpdatedInFuture {
+                price_updated_at: 11,
+                epoch: 10
+            })
+        );
+
+        data[POOL_ACCOUNT_SPACE - 1] = 1;
+        assert_eq!(
+            PoolAccount::from_borsh(&data),
+            Err(AccountLayoutError::NonZeroPadding)
+        );
+
+        data[0] = 2;
+        assert_eq!(
+            PoolAccount::from_borsh(&data),
+            Err(AccountLayoutError::UnsupportedVersion(2))
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! Pool owned by an actor thread consuming typed commands from a mailbox. Concurrent users
+//! send commands through cloned handles and wait for the answer on a oneshot channel, the
+//! actor handles commands one at a time in the order they arrived, so users never see or
+//! share a lock. The actor stops once every handle was dropped.
+
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender};
+
+use crate::conservation::Balances;
+use crate::error::{AddLiquidityError, RemoveLiquidityError, SwapError};
+use crate::lp_pool::LpPool;
+use crate::types::*;
+
+/// Sending half of a oneshot channel
+type Respond<T> = SyncSender<T>;
+
+#[derive(Debug)]
+/// Command handled by the actor, `reply` receives its result
+pub enum Command {
+    Swap {
+        amount: StakedTokenAmount,
+        reply: Respond<Result<TokenAmount, SwapError>>,
+    },
+    AddLiquidity {
+        account: Option<AccountId>,
+        amount: TokenAmount,
+        reply: Respond<Result<LpTokenAmount, AddLiquidityError>>,
+    },
+    RemoveLiquidity {
+        account: Option<AccountId>,
+        lp_amount: LpTokenAmount,
+        reply: Respond<Result<(TokenAmount, StakedTokenAmount), RemoveLiquidityError>>,
+    },
+    /// quotes swap without changing the pool
+    QuoteSwap {
+        amount: StakedTokenAmount,
+        reply: Respond<Result<TokenAmount, SwapError>>,
+    },
+    CurrentFee {
+        reply: Respond<Percentage>,
+    },
+    Balances {
+        reply: Respond<Balances>,
+    },
+}
+
+impl Command {
+    fn handle(self, pool: &mut LpPool) {
+        // the caller may have stopped waiting for the reply, which is fine
+        match self {
+            Command::Swap { amount, reply } => {
+                let _ = reply.send(pool.swap(amount));
+            }
+            Command::AddLiquidity {
+                account: Some(account),
+                amount,
+                reply,
+            } => {
+                let _ = reply.send(pool.add_liquidity_for(account, amount));
+            }
+            Command::AddLiquidity {
+                account: None,
+                amount,
+                reply,
+            } => {
+                let _ = reply.send(pool.add_liquidity(amount));
+            }
+            Command::RemoveLiquidity {
+                account: Some(account),
+                lp_amount,
+                reply,
+            } => {
+                let _ = reply.send(pool.remove_liquidity_for(account, lp_amount));
+            }
+            Command::RemoveLiquidity {
+                account: None,
+                lp_amount,
+                reply,
+            } => {
+                let _ = reply.send(pool.remove_liquidity(lp_amount));
+            }
+            Command::QuoteSwap { amount, reply } => {
+                let _ = reply.send(pool.amount_for_swap(amount));
+            }
+            Command::CurrentFee { reply } => {
+                let _ = reply.send(pool.current_fee());
+            }
+            Command::Balances { reply } => {
+                let _ = reply.send(pool.balances());
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+#[must_use = "reply has to be waited for to get the result"]
+/// Receiving half of a oneshot channel answering a command
+pub struct Reply<T>(Receiver<T>);
+
+impl<T> Reply<T> {
+    /// Creates the channel, the sender goes into the command
+    pub fn channel() -> (Respond<T>, Self) {
+        let (sender, receiver) = sync_channel(1);
+        (sender, Self(receiver))
+    }
+
+    /// Blocks until the actor answered the command
+    ///
+    /// # Panics
+    ///
+    /// If the actor panicked while handling this or an earlier command
+    pub fn wait(self) -> T {
+        self.0.recv().expect("pool actor stopped")
+    }
+
+    /// Returns the answer if it already arrived
+    pub fn try_wait(&self) -> Option<T> {
+        self.0.try_recv().ok()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Handle of the actor's mailbox, clones send to the same actor
+pub struct PoolActor {
+    mailbox: Sender<Command>,
+}
+
+impl PoolActor {
+    /// Moves the pool to a newly spawned actor thread
+    pub fn spawn(pool: LpPool) -> Self {
+        let (mailbox, commands) = channel::<Command>();
+        std::thread::Builder::new()
+            .name("pool-actor".into())
+            .spawn(move || {
+                let mut pool = pool;
+                for command in commands {
+                    command.handle(&mut pool);
+                }
+            })
+            .expect("pool actor thread spawns");
+        Self { mailbox }
+    }
+
+    /// Puts command into the mailbox, commands sent after the actor panicked are dropped
+    /// and their replies panic when waited for
+    pub fn send(&self, command: Command) {
+        let _ = self.mailbox.send(command);
+    }
+
+    fn request<T>(&self, command: impl FnOnce(Respond<T>) -> Command) -> Reply<T> {
+        let (reply, receiver) = Reply::channel();
+        self.send(command(reply));
+        receiver
+    }
+
+    pub fn swap(&self, amount: StakedTokenAmount) -> Reply<Result<TokenAmount, SwapError>> {
+        self.request(|reply| Command::Swap { amount, reply })
+    }
+
+    pub fn add_liquidity(
+        &self,
+        account: Option<AccountId>,
+        amount: TokenAmount,
+    ) -> Reply<Result<LpTokenAmount, AddLiquidityError>> {
+        self.request(|reply| Command::AddLiquidity {
+            account,
+            amount,
+            reply,
+        })
+    }
+
+    pub fn remove_liquidity(
+        &self,
+        account: Option<AccountId>,
+        lp_amount: LpTokenAmount,
+    ) -> Reply<Result<(TokenAmount, StakedTokenAmount), RemoveLiquidityError>> {
+        self.request(|reply| Command::RemoveLiquidity {
+            account,
+            lp_amount,
+            reply,
+        })
+    }
+
+    pub fn quote_swap(&self, amount: StakedTokenAmount) -> Reply<Result<TokenAmount, SwapError>> {
+        self.request(|reply| Command::QuoteSwap { amount, reply })
+    }
+
+    pub fn current_fee(&self) -> Reply<Percentage> {
+        self.request(|reply| Command::CurrentFee { reply })
+    }
+
+    pub fn balances(&self) -> Reply<Balances> {
+        self.request(|reply| Command::Balances { reply })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixture_config;
+
+    #[test]
+    fn answers_commands_in_order() {
+        let actor = PoolActor::spawn(fixture_config().build());
+        let deposit = actor.add_liquidity(Some(1), 100.into());
+        let quote = actor.quote_swap(6.into());
+        let swap = actor.swap(6.into());
+        let failed = actor.swap(0.into());
+        assert_eq!(deposit.wait().unwrap(), 100.into());
+        assert_eq!(swap.wait().unwrap(), quote.wait().unwrap());
+        assert!(matches!(
+            failed.wait(),
+            Err(SwapError::ZeroTokensAsArgument)
+        ));
+        let (tokens, staked) = actor.remove_liquidity(Some(1), 50.into()).wait().unwrap();
+        assert_eq!(staked, 3.into());
+        assert!(tokens > 45.into());
+        assert_eq!(actor.balances().wait().staked_tokens, 3.into());
+        assert!(actor.current_fee().wait() < 0.09.into());
+    }
+
+    #[test]
+    fn serializes_commands_of_all_handles() {
+        let actor = PoolActor::spawn(fixture_config().build());
+        actor.add_liquidity(None, 1_000.into()).wait().unwrap();
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let actor = actor.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        actor.swap(1.into()).wait().unwrap();
+                    }
+                })
+            })
+            .collect();
+        threads
+            .into_iter()
+            .for_each(|thread| thread.join().unwrap());
+        assert_eq!(actor.balances().wait().staked_tokens, 40.into());
+    }
+
+    #[test]
+    fn accepts_hand_built_commands() {
+        let actor = PoolActor::spawn(fixture_config().build());
+        let (reply, receiver) = Reply::channel();
+        actor.send(Command::CurrentFee { reply });
+        assert_eq!(receiver.wait(), 0.09.into());
+        // replies nobody waits for don't stop the actor
+        drop(actor.balances());
+        assert_eq!(actor.balances().wait().tokens, 0.into());
+    }
+}
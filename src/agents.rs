@@ -0,0 +1,402 @@
+//! Agent based simulation. Every tick each agent observes the pool, in order, and decides
+//! operations applied to it, so dynamics emerge from agents reacting to each other, e.g.
+//! liquidity providers leaving a drained pool which drives fees up and liquidity further
+//! down. Staked tokens trade at a discount to the pool price on the outside market,
+//! which is what arbitrageurs react to.
+//!
+//! The simulation is as reproducible as the agents, the built-in ones draw randomness
+//! only from the simulation's `Rng`.
+
+use crate::backtest::liquidity_ratio;
+use crate::lp_pool::LpPool;
+use crate::ops::PoolOp;
+use crate::replay::PoolConfig;
+use crate::rng::Rng;
+use crate::simulator::{Distribution, PriceDrift, Recorder, Simulation};
+use crate::types::*;
+
+/// State of the simulation an agent decides from
+pub struct Observation<'a> {
+    pub tick: usize,
+    pub pool: &'a LpPool,
+    /// price of staked tokens on the outside market
+    pub market_price: Price,
+}
+
+impl Observation<'_> {
+    /// Returns ratio of pool tokens to the liquidity target
+    pub fn liquidity_ratio(&self) -> Percentage {
+        liquidity_ratio(self.pool)
+    }
+}
+
+/// Participant of the simulation
+pub trait Agent {
+    fn name(&self) -> &str;
+
+    /// Returns operations the agent applies this tick, in order
+    fn act(&mut self, observation: &Observation<'_>, rng: &mut Rng) -> Vec<PoolOp>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Unstakes small amounts regardless of the fee
+pub struct RetailUnstaker {
+    /// probability of a swap in a single tick
+    pub probability: f64,
+    /// distribution of swapped staked tokens
+    pub size: Distribution,
+}
+
+impl Agent for RetailUnstaker {
+    fn name(&self) -> &str {
+        "retail"
+    }
+
+    fn act(&mut self, _: &Observation<'_>, rng: &mut Rng) -> Vec<PoolOp> {
+        match rng.chance(self.probability) {
+            true => vec![PoolOp::Swap {
+                account: None,
+                amount: StakedTokenAmount::from_raw_amount(self.size.sample(rng)),
+            }],
+            false => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Unstakes large amounts, only when the fee of the whole swap is acceptable
+pub struct Whale {
+    /// probability of considering a swap in a single tick
+    pub probability: f64,
+    /// distribution of swapped staked tokens
+    pub size: Distribution,
+    /// highest fee the whale pays
+    pub max_fee: Percentage,
+}
+
+impl Agent for Whale {
+    fn name(&self) -> &str {
+        "whale"
+    }
+
+    fn act(&mut self, observation: &Observation<'_>, rng: &mut Rng) -> Vec<PoolOp> {
+        if !rng.chance(self.probability) {
+            return Vec::new();
+        }
+        let amount = StakedTokenAmount::from_raw_amount(self.size.sample(rng));
+        match observation.pool.fee_for_swap(amount) {
+            Ok(fee) if fee <= self.max_fee => vec![PoolOp::Swap {
+                account: None,
+                amount,
+            }],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Deposits while fees are attractive and withdraws its whole position once liquidity
+/// falls below its comfort level
+pub struct LiquidityProvider {
+    pub account: AccountId,
+    /// probability of considering a deposit in a single tick
+    pub probability: f64,
+    /// distribution of deposited tokens
+    pub deposit: Distribution,
+    /// lowest marginal fee the provider deposits at
+    pub min_fee: Percentage,
+    /// liquidity ratio below which the provider exits
+    pub exit_ratio: Percentage,
+}
+
+impl Agent for LiquidityProvider {
+    fn name(&self) -> &str {
+        "lp"
+    }
+
+    fn act(&mut self, observation: &Observation<'_>, rng: &mut Rng) -> Vec<PoolOp> {
+        let lp_tokens = observation
+            .pool
+            .position(self.account)
+            .map(|position| position.lp_tokens)
+            .unwrap_or_default();
+        if observation.liquidity_ratio() < self.exit_ratio {
+            return match lp_tokens.raw() {
+                0 => Vec::new(),
+                _ => vec![PoolOp::RemoveLiquidity {
+                    account: Some(self.account),
+                    lp_amount: lp_tokens,
+                }],
+            };
+        }
+        match rng.chance(self.probability) && observation.pool.current_fee() >= self.min_fee {
+            true => vec![PoolOp::AddLiquidity {
+                account: Some(self.account),
+                amount: TokenAmount::from_raw_amount(self.deposit.sample(rng)),
+            }],
+            false => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Buys staked tokens on the market and unstakes them in the pool, as much as stays
+/// profitable after the fee
+pub struct Arbitrageur {
+    /// biggest amount of staked tokens swapped in a single tick
+    pub max_amount: StakedTokenAmount,
+}
+
+impl Agent for Arbitrageur {
+    fn name(&self) -> &str {
+        "arbitrageur"
+    }
+
+    fn act(&mut self, observation: &Observation<'_>, _: &mut Rng) -> Vec<PoolOp> {
+        let pool = observation.pool;
+        let (pool_price, market_price) = (pool.price().raw(), observation.market_price.raw());
+        if market_price >= pool_price {
+            return Vec::new();
+        }
+        // swap is profitable while `fee < 1 - market_price / pool_price`
+        let edge = (pool_price - market_price) as u128 * SCALE as u128 / pool_price as u128;
+        let profitable = |amount: Uint| {
+            pool.fee_for_swap(StakedTokenAmount::from_raw_amount(amount))
+                .is_ok_and(|fee| (fee.raw() as u128) < edge)
+        };
+
+        // fee grows with the amount, so the biggest profitable amount is found by bisection
+        let (mut low, mut high) = (0, self.max_amount.raw());
+        while low < high {
+            let middle = low + (high - low).div_ceil(2);
+            match profitable(middle) {
+                true => low = middle,
+                false => high = middle - 1,
+            }
+        }
+        match low {
+            0 => Vec::new(),
+            amount => vec![PoolOp::Swap {
+                account: None,
+                amount: StakedTokenAmount::from_raw_amount(amount),
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Configuration of an agent based simulation run
+pub struct AgentSimulationConfig {
+    pub pool: PoolConfig,
+    pub seed: u64,
+    pub ticks: usize,
+    /// liquidity deposited before the first tick
+    pub initial_liquidity: TokenAmount,
+    /// number of ticks in a single epoch
+    pub epoch_length: usize,
+    pub price_drift: PriceDrift,
+    /// biggest discount of the market price to the pool price, drawn uniformly every tick
+    pub max_market_discount: f64,
+}
+
+impl AgentSimulationConfig {
+    /// Creates simulation of a thousand ticks with a pool funded up to its liquidity
+    /// target, a slowly rising price and market discount of up to two percent
+    pub fn new(pool: PoolConfig, seed: u64) -> Self {
+        Self {
+            initial_liquidity: pool.params.liquidity_target,
+            pool,
+            seed,
+            ticks: 1_000,
+            epoch_length: 100,
+            price_drift: PriceDrift {
+                drift: 0.0002,
+                volatility: 0.0001,
+            },
+            max_market_discount: 0.02,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Operations of a single agent
+pub struct AgentStats {
+    pub name: String,
+    /// operations accepted by the pool
+    pub ops: usize,
+    /// operations rejected by the pool
+    pub failed_ops: usize,
+}
+
+#[derive(Debug)]
+/// Result of an agent based simulation run
+pub struct AgentSimulation {
+    pub simulation: Simulation,
+    /// statistics of every agent, in the order agents were given
+    pub agents: Vec<AgentStats>,
+}
+
+/// Runs simulation, agents act every tick in the given order
+pub fn simulate_agents(
+    config: &AgentSimulationConfig,
+    agents: &mut [Box<dyn Agent>],
+) -> AgentSimulation {
+    let mut rng = Rng::new(config.seed);
+    let mut pool = config.pool.build();
+    let mut recorder = Recorder::new(&pool);
+    let mut stats: Vec<_> = agents
+        .iter()
+        .map(|agent| AgentStats {
+            name: agent.name().into(),
+            ops: 0,
+            failed_ops: 0,
+        })
+        .collect();
+
+    recorder.run(
+        &mut pool,
+        PoolOp::AddLiquidity {
+            account: None,
+            amount: config.initial_liquidity,
+        },
+    );
+    for tick in 1..=config.ticks {
+        let discount = config.max_market_discount * rng.unit();
+        let market_price =
+            Price::from_raw_amount((pool.price().raw() as f64 * (1.0 - discount)) as Uint);
+        for (agent, stats) in agents.iter_mut().zip(&mut stats) {
+            let observation = Observation {
+                tick,
+                pool: &pool,
+                market_price,
+            };
+            for op in agent.act(&observation, &mut rng) {
+                match recorder.run(&mut pool, op) {
+                    true => stats.ops += 1,
+                    false => stats.failed_ops += 1,
+                }
+            }
+        }
+
+        if config.epoch_length > 0 && tick % config.epoch_length == 0 {
+            recorder.end_epoch(&mut pool, &config.price_drift, &mut rng);
+        }
+    }
+
+    AgentSimulation {
+        simulation: recorder.finish(pool),
+        agents: stats,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::governance::PoolParams;
+
+    fn config(seed: u64) -> AgentSimulationConfig {
+        let pool = PoolConfig::new(
+            1.1.into(),
+            PoolParams {
+                min_fee: 0.001.into(),
+                max_fee: 0.09.into(),
+                liquidity_target: 10_000.into(),
+            },
+        );
+        AgentSimulationConfig::new(pool, seed)
+    }
+
+    fn agents(exit_ratio: f64) -> Vec<Box<dyn Agent>> {
+        let provider = |account| LiquidityProvider {
+            account,
+            probability: 0.05,
+            deposit: Distribution::Uniform {
+                low: 100 * SCALE,
+                high: 500 * SCALE,
+            },
+            min_fee: 0.005.into(),
+            exit_ratio: exit_ratio.into(),
+        };
+        vec![
+            Box::new(RetailUnstaker {
+                probability: 0.5,
+                size: Distribution::LogUniform {
+                    low: SCALE / 100,
+                    high: 20 * SCALE,
+                },
+            }),
+            Box::new(Whale {
+                probability: 0.02,
+                size: Distribution::Uniform {
+                    low: 500 * SCALE,
+                    high: 2_000 * SCALE,
+                },
+                max_fee: 0.05.into(),
+            }),
+            Box::new(provider(0)),
+            Box::new(provider(1)),
+            Box::new(Arbitrageur {
+                max_amount: 1_000.into(),
+            }),
+        ]
+    }
+
+    #[test]
+    fn is_reproducible_from_seed() {
+        let first = simulate_agents(&config(3), &mut agents(0.2));
+        let second = simulate_agents(&config(3), &mut agents(0.2));
+        assert_eq!(first.simulation.ops, second.simulation.ops);
+        assert_eq!(first.agents, second.agents);
+        assert_eq!(
+            first.simulation.pool.state_hash(),
+            second.simulation.pool.state_hash()
+        );
+        assert!(first.agents.iter().all(|agent| agent.ops > 0), "{first:?}");
+    }
+
+    #[test]
+    fn providers_leaving_drain_the_pool() {
+        let calm = simulate_agents(&config(0), &mut agents(0.0))
+            .simulation
+            .stats;
+        let panicky = simulate_agents(&config(0), &mut agents(0.7));
+        let stats = panicky.simulation.stats;
+        assert!(stats.withdrawals > 0, "{stats:?}");
+        assert_eq!(calm.withdrawals, 0);
+        assert!(
+            stats.min_liquidity_ratio < calm.min_liquidity_ratio,
+            "{stats:?}"
+        );
+    }
+
+    #[test]
+    fn arbitrageur_swaps_only_profitable_amounts() {
+        let mut config = config(0);
+        config.max_market_discount = 0.0;
+        let mut arbitrageur: Vec<Box<dyn Agent>> = vec![Box::new(Arbitrageur {
+            max_amount: 1_000.into(),
+        })];
+        let run = simulate_agents(&config, &mut arbitrageur);
+        assert_eq!(run.agents[0].ops, 0, "no discount leaves no profit");
+
+        let mut pool = config.pool.build();
+        pool.add_liquidity(10_000.into()).unwrap();
+        let observation = Observation {
+            tick: 0,
+            pool: &pool,
+            market_price: 1.05.into(),
+        };
+        let ops = Arbitrageur {
+            max_amount: 100_000.into(),
+        }
+        .act(&observation, &mut Rng::new(0));
+        let [PoolOp::Swap { amount, .. }] = ops[..] else {
+            panic!("expected a single swap, got {ops:?}");
+        };
+        // (1.1 - 1.05) / 1.1
+        let edge = Percentage::from_raw_amount(45_454);
+        assert!(pool.fee_for_swap(amount).unwrap() < edge);
+        let more = amount + StakedTokenAmount::from_raw_amount(1);
+        assert!(pool.fee_for_swap(more).map_or(true, |fee| fee >= edge));
+    }
+}
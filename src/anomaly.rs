@@ -0,0 +1,384 @@
+//! Detection of suspicious pool activity for monitoring integrations. The detector is a
+//! `PoolHook` registered with `LpPool::add_hook` which inspects every successful
+//! operation and flags:
+//!
+//! * swaps draining more than a share of the tokens in the pool at once,
+//! * liquidity withdrawn by an account within a few epochs of depositing it, anonymous
+//!   liquidity can't be attributed and isn't tracked,
+//! * epochs whose fee revenue is a multiple of the average of the epochs before them.
+//!
+//! Clones of the detector share its state, so a clone kept outside the pool collects or
+//! subscribes to the flagged anomalies.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::conservation::Balances;
+use crate::error::{HookRejection, OpError};
+use crate::hooks::PoolHook;
+use crate::json::Value;
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::replay::PoolConfig;
+use crate::schema::ToJson;
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Thresholds of the detector
+pub struct AnomalyConfig {
+    /// biggest share of pool tokens a single swap may pay out unflagged
+    pub max_swap_share: Percentage,
+    /// withdrawals at most this many epochs after a deposit of the same account are flagged
+    pub cycle_epochs: Epoch,
+    /// multiple of the average epoch fee revenue flagging an epoch
+    pub fee_spike_multiple: Uint,
+    /// amount of past epochs averaged, spikes are flagged once that many epochs passed
+    pub fee_window: usize,
+}
+
+impl Default for AnomalyConfig {
+    /// Flags swaps paying out over 20% of the pool, liquidity withdrawn in the epoch it
+    /// was deposited in or the next one and epochs earning 5 times the average of the
+    /// last 10 epochs
+    fn default() -> Self {
+        Self {
+            max_swap_share: 0.2.into(),
+            cycle_epochs: 1,
+            fee_spike_multiple: 5,
+            fee_window: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Suspicious activity flagged by the detector
+pub enum Anomaly {
+    /// single swap paid out `drained` share of the tokens in the pool
+    LargeSwap {
+        epoch: Epoch,
+        amount: StakedTokenAmount,
+        drained: Percentage,
+    },
+    /// account withdrew liquidity shortly after depositing it
+    LiquidityCycle {
+        epoch: Epoch,
+        account: AccountId,
+        deposited_at: Epoch,
+        lp_amount: LpTokenAmount,
+    },
+    /// fee revenue of the finished epoch exceeded the configured multiple of the average
+    FeeSpike {
+        epoch: Epoch,
+        fees: TokenAmount,
+        average: TokenAmount,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+/// State of the pool captured before the operation in flight
+struct Before {
+    balances: Balances,
+    fees: TokenAmount,
+    epoch: Epoch,
+}
+
+#[derive(Debug, Default)]
+struct DetectorState {
+    anomalies: Vec<Anomaly>,
+    subscribers: Vec<Sender<Anomaly>>,
+    pending: Option<Before>,
+    /// epoch of the last deposit of every account
+    deposits: HashMap<AccountId, Epoch>,
+    /// total fee revenue when the current epoch started
+    epoch_start_fees: Option<TokenAmount>,
+    epoch_fees: VecDeque<TokenAmount>,
+}
+
+impl DetectorState {
+    fn flag(&mut self, anomaly: Anomaly) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(anomaly).is_ok());
+        self.anomalies.push(anomaly);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Shared handle of an anomaly detector
+pub struct AnomalyDetector {
+    config: AnomalyConfig,
+    state: Arc<Mutex<DetectorState>>,
+}
+
+impl AnomalyDetector {
+    pub fn new(config: AnomalyConfig) -> Self {
+        Self {
+            config,
+            state: Arc::default(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, DetectorState> {
+        self.state.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    pub fn config(&self) -> AnomalyConfig {
+        self.config
+    }
+
+    /// Returns all anomalies flagged so far except drained ones, in order
+    pub fn anomalies(&self) -> Vec<Anomaly> {
+        self.state().anomalies.clone()
+    }
+
+    /// Returns and forgets anomalies flagged since the last call
+    pub fn drain(&self) -> Vec<Anomaly> {
+        std::mem::take(&mut self.state().anomalies)
+    }
+
+    /// Returns receiver of every anomaly flagged from now on
+    pub fn subscribe(&self) -> Receiver<Anomaly> {
+        let (sender, receiver) = channel();
+        self.state().subscribers.push(sender);
+        receiver
+    }
+
+    fn inspect(&self, state: &mut DetectorState, before: Before, pool: &LpPool, op: &PoolOp) {
+        // the epoch the detector was registered in is measured from its first operation
+        let start_fees = *state.epoch_start_fees.get_or_insert(before.fees);
+        match *op {
+            PoolOp::Swap { amount, .. } => {
+                let tokens = before.balances.tokens.raw();
+                let drained = tokens.saturating_sub(pool.token_amount().raw());
+                let share = match tokens {
+                    0 => 0,
+                    tokens => (drained as u128 * SCALE as u128 / tokens as u128) as Uint,
+                };
+                if share > self.config.max_swap_share.raw() {
+                    state.flag(Anomaly::LargeSwap {
+                        epoch: pool.epoch(),
+                        amount,
+                        drained: Percentage::from_raw_amount(share),
+                    });
+                }
+            }
+            PoolOp::AddLiquidity {
+                account: Some(account),
+                ..
+            } => {
+                state.deposits.insert(account, pool.epoch());
+            }
+            PoolOp::RemoveLiquidity {
+                account: Some(account),
+                lp_amount,
+            } => {
+                if let Some(&deposited_at) = state.deposits.get(&account) {
+                    if pool.epoch() - deposited_at <= self.config.cycle_epochs {
+                        state.flag(Anomaly::LiquidityCycle {
+                            epoch: pool.epoch(),
+                            account,
+                            deposited_at,
+                            lp_amount,
+                        });
+                    }
+                }
+            }
+            PoolOp::AdvanceEpoch { .. } => {
+                let total = pool.fee_revenue().total;
+                let fees = total - start_fees;
+                let window = &state.epoch_fees;
+                let sum: u128 = window.iter().map(|fees| fees.raw() as u128).sum();
+                let average = sum / window.len().max(1) as u128;
+                if window.len() >= self.config.fee_window
+                    && self.config.fee_window > 0
+                    && average > 0
+                    && fees.raw() as u128 > average * self.config.fee_spike_multiple as u128
+                {
+                    state.flag(Anomaly::FeeSpike {
+                        epoch: before.epoch,
+                        fees,
+                        average: TokenAmount::from_raw_amount(average as Uint),
+                    });
+                }
+                state.epoch_fees.push_back(fees);
+                if state.epoch_fees.len() > self.config.fee_window {
+                    state.epoch_fees.pop_front();
+                }
+                state.epoch_start_fees = Some(total);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl PoolHook for AnomalyDetector {
+    fn before_op(&mut self, pool: &LpPool, _op: &PoolOp) -> Result<(), HookRejection> {
+        self.state().pending = Some(Before {
+            balances: pool.balances(),
+            fees: pool.fee_revenue().total,
+            epoch: pool.epoch(),
+        });
+        Ok(())
+    }
+
+    fn after_op(&mut self, pool: &LpPool, op: &PoolOp, result: &Result<OpOutcome, OpError>) {
+        let mut state = self.state();
+        let (Some(before), Ok(_)) = (state.pending.take(), result) else {
+            return;
+        };
+        self.inspect(&mut state, before, pool, op);
+    }
+}
+
+/// Replays operations on a pool built from the configuration and returns anomalies
+/// flagged by a detector with the thresholds
+pub fn detect_anomalies(
+    config: &PoolConfig,
+    ops: &[PoolOp],
+    thresholds: AnomalyConfig,
+) -> Vec<Anomaly> {
+    let detector = AnomalyDetector::new(thresholds);
+    let mut pool = config.build();
+    pool.add_hook(detector.clone());
+    for op in ops {
+        let _ = pool.apply(op);
+    }
+    detector.drain()
+}
+
+impl ToJson for Anomaly {
+    fn to_json(&self) -> Value {
+        match *self {
+            Anomaly::LargeSwap {
+                epoch,
+                amount,
+                drained,
+            } => Value::object([
+                ("type", "large_swap".into()),
+                ("epoch", epoch.to_json()),
+                ("amount", amount.to_json()),
+                ("drained", drained.to_json()),
+            ]),
+            Anomaly::LiquidityCycle {
+                epoch,
+                account,
+                deposited_at,
+                lp_amount,
+            } => Value::object([
+                ("type", "liquidity_cycle".into()),
+                ("epoch", epoch.to_json()),
+                ("account", account.to_json()),
+                ("deposited_at", deposited_at.to_json()),
+                ("lp_amount", lp_amount.to_json()),
+            ]),
+            Anomaly::FeeSpike {
+                epoch,
+                fees,
+                average,
+            } => Value::object([
+                ("type", "fee_spike".into()),
+                ("epoch", epoch.to_json()),
+                ("fees", fees.to_json()),
+                ("average", average.to_json()),
+            ]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixture_config;
+
+    fn deposit(account: Option<AccountId>, amount: f64) -> PoolOp {
+        PoolOp::AddLiquidity {
+            account,
+            amount: amount.into(),
+        }
+    }
+
+    #[test]
+    fn flags_draining_swaps_and_cycles() {
+        let ops = [
+            deposit(Some(1), 100.0),
+            PoolOp::Swap {
+                account: None,
+                amount: 6.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 20.into(),
+            },
+            deposit(Some(2), 10.0),
+            PoolOp::AdvanceEpoch { epochs: 1 },
+            PoolOp::RemoveLiquidity {
+                account: Some(2),
+                lp_amount: 1.into(),
+            },
+            PoolOp::AdvanceEpoch { epochs: 2 },
+            PoolOp::RemoveLiquidity {
+                account: Some(2),
+                lp_amount: 1.into(),
+            },
+        ];
+        let anomalies = detect_anomalies(&fixture_config(), &ops, AnomalyConfig::default());
+        assert_eq!(anomalies.len(), 2);
+        let Anomaly::LargeSwap {
+            epoch: 0, drained, ..
+        } = anomalies[0]
+        else {
+            panic!("expected large swap, got {:?}", anomalies[0]);
+        };
+        assert!(drained > 0.2.into() && drained < 0.5.into());
+        assert_eq!(
+            anomalies[1],
+            Anomaly::LiquidityCycle {
+                epoch: 1,
+                account: 2,
+                deposited_at: 0,
+                lp_amount: 1.into(),
+            }
+        );
+    }
+
+    #[test]
+    fn flags_fee_spikes() {
+        let mut ops = vec![deposit(None, 1_000.0)];
+        for swap in [1.0, 1.0, 1.0, 30.0, 1.0] {
+            ops.push(PoolOp::Swap {
+                account: None,
+                amount: swap.into(),
+            });
+            ops.push(PoolOp::AdvanceEpoch { epochs: 1 });
+        }
+        let thresholds = AnomalyConfig {
+            fee_window: 3,
+            ..AnomalyConfig::default()
+        };
+
+        let detector = AnomalyDetector::new(thresholds);
+        let receiver = detector.subscribe();
+        let mut pool = fixture_config().build();
+        pool.add_hook(detector.clone());
+        for op in &ops {
+            pool.apply(op).unwrap();
+        }
+        let anomalies = detector.drain();
+        assert_eq!(anomalies.len(), 1);
+        let Anomaly::FeeSpike {
+            epoch: 3,
+            fees,
+            average,
+        } = anomalies[0]
+        else {
+            panic!("expected fee spike, got {:?}", anomalies[0]);
+        };
+        assert!(fees.raw() > 5 * average.raw());
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), anomalies);
+        assert!(detector.anomalies().is_empty());
+        assert_eq!(
+            anomalies[0].to_json().get("type"),
+            Some(&Value::from("fee_spike"))
+        );
+    }
+}
@@ -0,0 +1,392 @@
+//! JSON REST API exposing a shared pool, so the crate can run as a standalone quoting
+//! service. Amounts are sent as decimal strings, e.g. `"1.5"`, to avoid precision loss of
+//! JSON numbers. Failed requests return `{"error": {"kind": ..., "message": ...}}`.
+//!
+//! | method | path                | body                                     |
+//! |--------|---------------------|------------------------------------------|
+//! | GET    | `/stats`            |                                          |
+//! | GET    | `/quote?amount=6.5` |                                          |
+//! | GET    | `/history`          |                                          |
+//! | POST   | `/swap`             | `{"amount": "6.5"}`                      |
+//! | POST   | `/liquidity/add`    | `{"amount": "100", "account": 1}`        |
+//! | POST   | `/liquidity/remove` | `{"lp_amount": "10", "account": 1}`      |
+
+use std::io::{self, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::str::FromStr;
+
+use crate::error::{AddLiquidityError, RemoveLiquidityError, SwapError};
+use crate::http::{read_request, write_response};
+use crate::json::Value;
+use crate::shared::SharedPool;
+use crate::types::*;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Response of the API before it's written to the connection
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Failed request, encoded into the response body
+struct ApiError {
+    status: u16,
+    kind: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, kind: &'static str, message: impl ToString) -> Self {
+        Self {
+            status,
+            kind,
+            message: message.to_string(),
+        }
+    }
+
+    fn bad_request(message: impl ToString) -> Self {
+        Self::new(400, "bad_request", message)
+    }
+}
+
+impl From<SwapError> for ApiError {
+    fn from(error: SwapError) -> Self {
+        let (status, kind) = match error {
+            SwapError::ZeroTokensAsArgument => (400, "zero_amount"),
+            SwapError::AmountTooBig => (400, "amount_too_big"),
+            SwapError::PoolNotEnoughTokens { .. } => (409, "not_enough_liquidity"),
+            SwapError::StalePrice { .. } => (409, "stale_price"),
+            SwapError::Oracle(_) => (503, "oracle_unavailable"),
+        };
+        ApiError::new(status, kind, error)
+    }
+}
+
+impl From<AddLiquidityError> for ApiError {
+    fn from(error: AddLiquidityError) -> Self {
+        let (status, kind) = match error {
+            AddLiquidityError::NoTokensProvided => (400, "zero_amount"),
+            AddLiquidityError::TokenAmountTooBig => (400, "amount_too_big"),
+            AddLiquidityError::PoolWithoutValue => (409, "pool_without_value"),
+            AddLiquidityError::Oracle(_) => (503, "oracle_unavailable"),
+        };
+        ApiError::new(status, kind, error)
+    }
+}
+
+impl From<RemoveLiquidityError> for ApiError {
+    fn from(error: RemoveLiquidityError) -> Self {
+        let (status, kind) = match error {
+            RemoveLiquidityError::NotEnoughTokens { .. }
+            | RemoveLiquidityError::PositionNotEnoughTokens { .. } => (409, "not_enough_lp_tokens"),
+            RemoveLiquidityError::WithdrawCalculationOverflow => (400, "amount_too_big"),
+        };
+        ApiError::new(status, kind, error)
+    }
+}
+
+impl From<ApiError> for ApiResponse {
+    fn from(error: ApiError) -> Self {
+        ApiResponse {
+            status: error.status,
+            body: Value::object([(
+                "error",
+                Value::object([
+                    ("kind", Value::String(error.kind.into())),
+                    ("message", Value::String(error.message)),
+                ]),
+            )]),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// REST API over a shared pool
+pub struct Api {
+    pool: SharedPool,
+}
+
+impl Api {
+    pub fn new(pool: SharedPool) -> Self {
+        Self { pool }
+    }
+
+    /// Handles single request, `target` is the request path including the query string
+    pub fn handle(&self, method: &str, target: &str, body: &str) -> ApiResponse {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let result = match (method, path) {
+            ("GET", "/stats") => Ok(self.stats()),
+            ("GET", "/quote") => self.quote(query),
+            ("GET", "/history") => Ok(self.history()),
+            ("POST", "/swap") => parse_body(body).and_then(|body| self.swap(&body)),
+            ("POST", "/liquidity/add") => {
+                parse_body(body).and_then(|body| self.add_liquidity(&body))
+            }
+            ("POST", "/liquidity/remove") => {
+                parse_body(body).and_then(|body| self.remove_liquidity(&body))
+            }
+            (
+                _,
+                "/stats" | "/quote" | "/history" | "/swap" | "/liquidity/add" | "/liquidity/remove",
+            ) => Err(ApiError::new(
+                405,
+                "method_not_allowed",
+                format!("{method} is not allowed on {path}"),
+            )),
+            _ => Err(ApiError::new(
+                404,
+                "not_found",
+                format!("no route for {path}"),
+            )),
+        };
+        match result {
+            Ok(body) => ApiResponse { status: 200, body },
+            Err(error) => error.into(),
+        }
+    }
+
+    /// Serves requests from the listener until accepting a connection fails. Every
+    /// connection is handled on its own thread and closed after a single request.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            let api = self.clone();
+            std::thread::spawn(move || api.handle_connection(stream));
+        }
+    }
+
+    fn handle_connection(&self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let response = match read_request(&mut reader) {
+            Ok((method, target, body)) => self.handle(&method, &target, &body),
+            Err(error) => ApiError::bad_request(error).into(),
+        };
+        write_response(stream, response.status, &response.body)
+    }
+
+    fn stats(&self) -> Value {
+        self.pool.with(|pool| {
+            let params = pool.params();
+            let fees = pool.fee_revenue();
+            Value::object([
+                ("price", decimal(pool.price())),
+                ("epoch", Value::number(pool.epoch())),
+                ("token_amount", decimal(pool.token_amount())),
+                ("st_token_amount", decimal(pool.st_token_amount())),
+                ("lp_token_amount", decimal(pool.lp_token_amount())),
+                ("total_value", decimal(pool.total_value())),
+                ("liquidity_target", decimal(params.liquidity_target)),
+                ("min_fee", decimal(params.min_fee)),
+                ("max_fee", decimal(params.max_fee)),
+                ("current_fee", decimal(pool.current_fee())),
+                ("total_fees", decimal(fees.total)),
+                ("lp_fees", decimal(fees.lp)),
+                ("treasury_fees", decimal(fees.treasury)),
+            ])
+        })
+    }
+
+    fn quote(&self, query: &str) -> Result<Value, ApiError> {
+        let amount = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("amount="))
+            .ok_or_else(|| ApiError::bad_request("missing `amount` query parameter"))?;
+        let amount = parse_amount("amount", amount)?;
+        self.pool.with(|pool| {
+            Ok(Value::object([
+                ("amount_out", decimal(pool.amount_for_swap(amount)?)),
+                ("fee", decimal(pool.fee_for_swap(amount)?)),
+            ]))
+        })
+    }
+
+    fn history(&self) -> Value {
+        self.pool.with(|pool| {
+            Value::object([(
+                "prices",
+                Value::Array(
+                    pool.price_history()
+                        .iter()
+                        .map(|point| {
+                            Value::object([
+                                ("epoch", Value::number(point.epoch)),
+                                ("price", decimal(point.price)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            )])
+        })
+    }
+
+    fn swap(&self, body: &Value) -> Result<Value, ApiError> {
+        let amount = amount_field(body, "amount")?;
+        let amount_out = self.pool.with(|pool| pool.swap(amount))?;
+        Ok(Value::object([("amount_out", decimal(amount_out))]))
+    }
+
+    fn add_liquidity(&self, body: &Value) -> Result<Value, ApiError> {
+        let amount = amount_field(body, "amount")?;
+        let account = account_field(body)?;
+        let lp_tokens = self.pool.with(|pool| match account {
+            Some(account) => pool.add_liquidity_for(account, amount),
+            None => pool.add_liquidity(amount),
+        })?;
+        Ok(Value::object([("lp_amount", decimal(lp_tokens))]))
+    }
+
+    fn remove_liquidity(&self, body: &Value) -> Result<Value, ApiError> {
+        let lp_amount = amount_field(body, "lp_amount")?;
+        let account = account_field(body)?;
+        let (tokens, staked_tokens) = self.pool.with(|pool| match account {
+            Some(account) => pool.remove_liquidity_for(account, lp_amount),
+            None => pool.remove_liquidity(lp_amount),
+        })?;
+        Ok(Value::object([
+            ("token_amount", decimal(tokens)),
+            ("st_token_amount", decimal(staked_tokens)),
+        ]))
+    }
+}
+
+fn decimal(amount: impl ToString) -> Value {
+    Value::String(amount.to_string())
+}
+
+fn parse_body(body: &str) -> Result<Value, ApiError> {
+    Value::parse(body).map_err(ApiError::bad_request)
+}
+
+fn parse_amount<T: FromStr>(key: &str, literal: &str) -> Result<T, ApiError> {
+    literal
+        .parse()
+        .map_err(|_| ApiError::bad_request(format!("`{key}` has to be a decimal string")))
+}
+
+fn amount_field<T: FromStr>(body: &Value, key: &str) -> Result<T, ApiError> {
+    let literal = body
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| ApiError::bad_request(format!("missing `{key}` decimal string")))?;
+    parse_amount(key, literal)
+}
+
+fn account_field(body: &Value) -> Result<Option<AccountId>, ApiError> {
+    match body.get("account") {
+        None | Some(Value::Null) => Ok(None),
+        Some(account) => account
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| ApiError::bad_request("`account` has to be an integer")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::*;
+    use crate::lp_pool::LpPool;
+
+    fn api() -> Api {
+        let pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        Api::new(SharedPool::new(pool))
+    }
+
+    fn field<'a>(response: &'a ApiResponse, key: &str) -> &'a str {
+        response.body.get(key).and_then(Value::as_str).unwrap()
+    }
+
+    #[test]
+    fn handles_pool_operations() {
+        let api = api();
+        let added = api.handle(
+            "POST",
+            "/liquidity/add",
+            r#"{"amount": "100", "account": 1}"#,
+        );
+        assert_eq!(added.status, 200);
+        assert_eq!(field(&added, "lp_amount"), "100.000000");
+
+        let quote = api.handle("GET", "/quote?amount=6", "");
+        let swapped = api.handle("POST", "/swap", r#"{"amount": "6"}"#);
+        assert_eq!(field(&quote, "amount_out"), field(&swapped, "amount_out"));
+        assert_eq!(field(&quote, "fee"), "0.001000");
+
+        let stats = api.handle("GET", "/stats", "");
+        assert_eq!(field(&stats, "st_token_amount"), "6.000000");
+
+        let removed = api.handle(
+            "POST",
+            "/liquidity/remove",
+            r#"{"lp_amount": "10", "account": 1}"#,
+        );
+        assert_eq!(field(&removed, "st_token_amount"), "0.600000");
+
+        let history = api.handle("GET", "/history", "");
+        assert_eq!(
+            history
+                .body
+                .get("prices")
+                .and_then(Value::as_array)
+                .map(<[_]>::len),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn encodes_errors() {
+        let api = api();
+        let error = |response: ApiResponse| {
+            let kind = response
+                .body
+                .get("error")
+                .and_then(|error| error.get("kind"));
+            (
+                response.status,
+                kind.and_then(Value::as_str).unwrap().to_string(),
+            )
+        };
+
+        assert_eq!(
+            error(api.handle("POST", "/swap", r#"{"amount": "6"}"#)),
+            (409, "not_enough_liquidity".into())
+        );
+        assert_eq!(
+            error(api.handle("POST", "/swap", r#"{"amount": 6}"#)),
+            (400, "bad_request".into())
+        );
+        assert_eq!(
+            error(api.handle("POST", "/swap", "{")),
+            (400, "bad_request".into())
+        );
+        assert_eq!(
+            error(api.handle("GET", "/swap", "")),
+            (405, "method_not_allowed".into())
+        );
+        assert_eq!(error(api.handle("GET", "/", "")), (404, "not_found".into()));
+    }
+
+    #[test]
+    fn serves_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let api = api();
+        std::thread::spawn(move || api.serve(listener));
+
+        let body = r#"{"amount": "100"}"#;
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "POST /liquidity/add HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(r#"{"lp_amount":"100.000000"}"#));
+    }
+}
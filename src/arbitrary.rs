@@ -0,0 +1,510 @@
+//! Decoding of arbitrary bytes into pool configurations and operation sequences, used by
+//! the fuzz targets in `fuzz/`. `Unstructured` and `Arbitrary` mirror the API of the
+//! `arbitrary` crate, every byte sequence decodes into a valid value and exhausted input
+//! decodes into zeros, so fuzzers can mutate inputs freely. Targets are run with
+//! `cargo fuzz run ops` or `cargo fuzz run config` from the repository root.
+//! `Structured` and `Encode` are their inverse, the `fuzz-corpus` binary uses them to
+//! seed `fuzz/corpus` with inputs replaying scenarios, simulations and recorded flows.
+//!
+//! Amounts are limited to `MAX_ARBITRARY_AMOUNT` and prices to `MAX_ARBITRARY_PRICE`,
+//! the range the pool math supports for pools built from short operation sequences.
+
+use duplicate::duplicate_item;
+
+use crate::conservation::check_ops;
+use crate::flows::{replay_flows, FlowReplay, UnstakeFlow};
+use crate::governance::PoolParams;
+use crate::lp_pool::LpPool;
+use crate::ops::PoolOp;
+use crate::replay::PoolConfig;
+use crate::scenarios::canonical_scenarios;
+use crate::shrinking::{shrink, unit_test};
+use crate::simulator::{simulate, SimulationConfig};
+use crate::types::*;
+
+/// biggest raw amount of a single operation, a million whole tokens
+pub const MAX_ARBITRARY_AMOUNT: Uint = 1_000_000 * SCALE;
+/// biggest raw price
+pub const MAX_ARBITRARY_PRICE: Uint = 10 * SCALE;
+/// longest decoded operation sequence
+pub const MAX_ARBITRARY_OPS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Source of raw bytes values are decoded from
+pub struct Unstructured<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Unstructured<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Returns true if every byte was consumed
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns next byte, `0` once the input is exhausted
+    pub fn byte(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((byte, rest)) => {
+                self.data = rest;
+                *byte
+            }
+            None => 0,
+        }
+    }
+
+    pub fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(std::array::from_fn(|_| self.byte()))
+    }
+
+    /// Returns value in `low..=high`
+    pub fn int_in_range(&mut self, low: u64, high: u64) -> u64 {
+        match (high - low).checked_add(1) {
+            Some(bound) => low + self.u64() % bound,
+            None => self.u64(),
+        }
+    }
+
+    pub fn bool(&mut self) -> bool {
+        self.byte() & 1 == 1
+    }
+
+    /// Returns raw amount in `0..=MAX_ARBITRARY_AMOUNT` spread evenly across magnitudes,
+    /// so dust and large amounts are as likely as medium ones
+    fn amount(&mut self) -> Uint {
+        let magnitude = 10u64.pow(self.int_in_range(0, 12) as u32);
+        self.int_in_range(0, magnitude).min(MAX_ARBITRARY_AMOUNT)
+    }
+}
+
+/// Type which can be decoded from arbitrary bytes
+pub trait Arbitrary: Sized {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Self;
+}
+
+/// Decodes value from the given bytes
+pub fn from_bytes<T: Arbitrary>(data: &[u8]) -> T {
+    T::arbitrary(&mut Unstructured::new(data))
+}
+
+impl<T: Arbitrary> Arbitrary for Option<T> {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Self {
+        u.bool().then(|| T::arbitrary(u))
+    }
+}
+
+impl Arbitrary for TokenAmount {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Self {
+        TokenAmount::from_raw_amount(u.amount())
+    }
+}
+
+impl Arbitrary for StakedTokenAmount {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Self {
+        StakedTokenAmount::from_raw_amount(u.amount())
+    }
+}
+
+impl Arbitrary for LpTokenAmount {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Self {
+        LpTokenAmount::from_raw_amount(u.amount())
+    }
+}
+
+impl Arbitrary for Price {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Self {
+        Price::from_raw_amount(u.int_in_range(1, MAX_ARBITRARY_PRICE))
+    }
+}
+
+impl Arbitrary for Percentage {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Self {
+        Percentage::from_raw_amount(u.int_in_range(0, SCALE))
+    }
+}
+
+impl Arbitrary for PoolParams {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Self {
+        let (first, second) = (
+            Percentage::arbitrary(u).raw(),
+            Percentage::arbitrary(u).raw(),
+        );
+        PoolParams {
+            min_fee: Percentage::from_raw_amount(first.min(second)),
+            max_fee: Percentage::from_raw_amount(first.max(second)),
+            liquidity_target: TokenAmount::from_raw_amount(u.amount().max(1)),
+        }
+    }
+}
+
+impl Arbitrary for PoolConfig {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Self {
+        let mut config = PoolConfig::new(Price::arbitrary(u), PoolParams::arbitrary(u));
+        config.treasury_cut = Percentage::arbitrary(u);
+        config.max_price_age = u.bool().then(|| u.int_in_range(0, 8));
+        config.max_price_deviation = Option::arbitrary(u);
+        config.monotonic_price = u.bool();
+        config
+    }
+}
+
+impl Arbitrary for PoolOp {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Self {
+        let account = |u: &mut Unstructured<'_>| u.bool().then(|| u.int_in_range(0, 3));
+        match u.byte() % 5 {
+            0 => PoolOp::AddLiquidity {
+                account: account(u),
+                amount: TokenAmount::arbitrary(u),
+            },
+            1 => PoolOp::RemoveLiquidity {
+                account: account(u),
+                lp_amount: LpTokenAmount::arbitrary(u),
+            },
+            2 => PoolOp::Swap {
+                account: None,
+                amount: StakedTokenAmount::arbitrary(u),
+            },
+            3 => PoolOp::SetPrice {
+                price: Price::arbitrary(u),
+            },
+            _ => PoolOp::AdvanceEpoch {
+                epochs: u.int_in_range(0, 4),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Configuration together with operations applied to a pool built from it
+pub struct FuzzInput {
+    pub config: PoolConfig,
+    pub ops: Vec<PoolOp>,
+}
+
+impl Arbitrary for FuzzInput {
+    fn arbitrary(u: &mut Unstructured<'_>) -> Self {
+        let config = PoolConfig::arbitrary(u);
+        let mut ops = Vec::new();
+        while !u.is_empty() && ops.len() < MAX_ARBITRARY_OPS {
+            ops.push(PoolOp::arbitrary(u));
+        }
+        Self { config, ops }
+    }
+}
+
+/// Sink of raw bytes, the inverse of `Unstructured`. Values written by `Encode` decode
+/// back into themselves as long as they're in the range `Arbitrary` produces, values
+/// outside of it are clamped into it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Structured {
+    data: Vec<u8>,
+}
+
+impl Structured {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn byte(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    /// Writes value decoded by `Unstructured::int_in_range(low, high)`
+    pub fn int_in_range(&mut self, low: u64, high: u64, value: u64) {
+        self.u64(value.clamp(low, high) - low);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.byte(value as u8);
+    }
+
+    fn amount(&mut self, value: Uint) {
+        let value = value.min(MAX_ARBITRARY_AMOUNT);
+        let magnitude = (0..12)
+            .find(|&power| 10u64.pow(power) >= value)
+            .unwrap_or(12);
+        self.int_in_range(0, 12, magnitude as u64);
+        self.int_in_range(0, 10u64.pow(magnitude), value);
+    }
+}
+
+/// Type which can be encoded into bytes `Arbitrary` decodes it from
+pub trait Encode {
+    fn encode(&self, s: &mut Structured);
+}
+
+/// Encodes value into bytes it's decoded from by `from_bytes`
+pub fn to_bytes<T: Encode>(value: &T) -> Vec<u8> {
+    let mut s = Structured::new();
+    value.encode(&mut s);
+    s.into_bytes()
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, s: &mut Structured) {
+        s.bool(self.is_some());
+        if let Some(value) = self {
+            value.encode(s);
+        }
+    }
+}
+
+#[duplicate_item(ImplName; [TokenAmount]; [StakedTokenAmount]; [LpTokenAmount])]
+impl Encode for ImplName {
+    fn encode(&self, s: &mut Structured) {
+        s.amount(self.raw());
+    }
+}
+
+impl Encode for Price {
+    fn encode(&self, s: &mut Structured) {
+        s.int_in_range(1, MAX_ARBITRARY_PRICE, self.raw());
+    }
+}
+
+impl Encode for Percentage {
+    fn encode(&self, s: &mut Structured) {
+        s.int_in_range(0, SCALE, self.raw());
+    }
+}
+
+impl Encode for PoolParams {
+    fn encode(&self, s: &mut Structured) {
+        self.min_fee.encode(s);
+        self.max_fee.encode(s);
+        s.amount(self.liquidity_target.raw().max(1));
+    }
+}
+
+/// Fee policy, price smoothing and surcharge aren't decoded, so they're dropped
+impl Encode for PoolConfig {
+    fn encode(&self, s: &mut Structured) {
+        self.price.encode(s);
+        self.params.encode(s);
+        self.treasury_cut.encode(s);
+        s.bool(self.max_price_age.is_some());
+        if let Some(age) = self.max_price_age {
+            s.int_in_range(0, 8, age);
+        }
+        self.max_price_deviation.encode(s);
+        s.bool(self.monotonic_price);
+    }
+}
+
+impl Encode for PoolOp {
+    fn encode(&self, s: &mut Structured) {
+        let account = |s: &mut Structured, account: Option<AccountId>| {
+            s.bool(account.is_some());
+            if let Some(account) = account {
+                s.int_in_range(0, 3, account);
+            }
+        };
+        match *self {
+            PoolOp::AddLiquidity {
+                account: id,
+                amount,
+            } => {
+                s.byte(0);
+                account(s, id);
+                amount.encode(s);
+            }
+            PoolOp::RemoveLiquidity {
+                account: id,
+                lp_amount,
+            } => {
+                s.byte(1);
+                account(s, id);
+                lp_amount.encode(s);
+            }
+            PoolOp::Swap { amount, .. } => {
+                s.byte(2);
+                amount.encode(s);
+            }
+            PoolOp::SetPrice { price } => {
+                s.byte(3);
+                price.encode(s);
+            }
+            PoolOp::AdvanceEpoch { epochs } => {
+                s.byte(4);
+                s.int_in_range(0, 4, epochs);
+            }
+            // never generated, so there's no input producing it
+            PoolOp::Unreplayable => {}
+        }
+    }
+}
+
+/// Operations beyond `MAX_ARBITRARY_OPS` are dropped
+impl Encode for FuzzInput {
+    fn encode(&self, s: &mut Structured) {
+        self.config.encode(s);
+        for op in self.ops.iter().take(MAX_ARBITRARY_OPS) {
+            op.encode(s);
+        }
+    }
+}
+
+/// Returns inputs of the `ops` target replaying the canonical scenarios and the first
+/// operations of seeded simulations, named after their source, so fuzzing starts from
+/// pools in meaningful states
+pub fn corpus_seeds() -> Vec<(String, FuzzInput)> {
+    let scenarios = canonical_scenarios().into_iter().map(|scenario| {
+        let input = FuzzInput {
+            config: scenario.config,
+            ops: scenario.ops,
+        };
+        (format!("scenario-{}", scenario.name), input)
+    });
+    let simulations = (0..4).map(|seed| {
+        let mut config = SimulationConfig::new(scenario_config(), seed);
+        config.steps = MAX_ARBITRARY_OPS;
+        let ops = simulate(&config).ops;
+        (
+            format!("simulation-{seed}"),
+            FuzzInput {
+                config: config.pool,
+                ops,
+            },
+        )
+    });
+    scenarios.chain(simulations).collect()
+}
+
+/// Returns input of the `ops` target replaying recorded flows through a pool funded up
+/// to its liquidity target, which is set to the sum of unstaked amounts
+pub fn flow_seed(flows: &[UnstakeFlow]) -> FuzzInput {
+    let unstaked = flows
+        .iter()
+        .fold(0, |sum: Uint, flow| sum.saturating_add(flow.amount.raw()));
+    let mut config = scenario_config();
+    config.params.liquidity_target = TokenAmount::from_raw_amount(unstaked.max(1));
+    let replay = FlowReplay::new(config.params.liquidity_target);
+    let ops = replay_flows(&config, flows, &replay).ops;
+    FuzzInput { config, ops }
+}
+
+fn scenario_config() -> PoolConfig {
+    PoolConfig::new(
+        1.1.into(),
+        PoolParams {
+            min_fee: 0.001.into(),
+            max_fee: 0.09.into(),
+            liquidity_target: 10_000.into(),
+        },
+    )
+}
+
+/// Body of the `ops` fuzz target. Decodes input, applies operations and panics if any
+/// of them violates conservation or if the pool can't be restored from its snapshot or
+/// its operation log. Overflows panic when built with debug assertions. Violating
+/// sequences are shrunk and printed as a unit test reproducing the violation.
+pub fn fuzz_ops(data: &[u8]) {
+    let FuzzInput { config, ops } = from_bytes(data);
+    let mut pool = config.build();
+    if check_ops(&mut pool, &ops).is_err() {
+        let ops = shrink(ops, |ops| check_ops(&mut config.build(), ops).is_err());
+        let error = check_ops(&mut config.build(), &ops).expect_err("shrunk sequence still fails");
+        let check = "check_ops(&mut pool, &ops).unwrap();".to_string();
+        panic!(
+            "{error}, reproduced by\n{}",
+            unit_test("fuzz_case", &config, &ops, &[check])
+        );
+    }
+
+    let restored = LpPool::from_snapshot(&pool.to_snapshot()).expect("snapshot restores");
+    assert_eq!(restored.state_hash(), pool.state_hash());
+    let replayed = LpPool::replay(&config, pool.op_log()).expect("op log replays");
+    assert_eq!(replayed.to_account(), pool.to_account());
+}
+
+/// Body of the `config` fuzz target. Decodes configuration and checks it survives
+/// building a pool and a snapshot round trip.
+pub fn fuzz_config(data: &[u8]) {
+    let config: PoolConfig = from_bytes(data);
+    config.params.validate().expect("decoded params are valid");
+    let pool = config.build();
+    assert_eq!(pool.params(), config.params);
+    let restored = LpPool::from_snapshot(&pool.to_snapshot()).expect("snapshot restores");
+    assert_eq!(restored.to_account(), pool.to_account());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    #[test]
+    fn decodes_every_input() {
+        assert_eq!(
+            from_bytes::<FuzzInput>(&[]).ops,
+            Vec::<PoolOp>::new(),
+            "empty input decodes into defaults"
+        );
+        let input: FuzzInput = from_bytes(&[7; 200]);
+        assert!(!input.ops.is_empty() && input.ops.len() <= MAX_ARBITRARY_OPS);
+        assert!(input.config.params.validate().is_ok());
+    }
+
+    #[test]
+    fn encodes_decoded_inputs() {
+        let mut rng = Rng::new(1);
+        for _ in 0..500 {
+            let data: Vec<u8> = (0..rng.range(0, 400))
+                .map(|_| rng.next_u64() as u8)
+                .collect();
+            let input: FuzzInput = from_bytes(&data);
+            assert_eq!(from_bytes::<FuzzInput>(&to_bytes(&input)), input);
+            assert_eq!(
+                from_bytes::<PoolConfig>(&to_bytes(&input.config)),
+                input.config
+            );
+        }
+    }
+
+    #[test]
+    fn seeds_decode_into_their_sources() {
+        let seeds = corpus_seeds();
+        assert!(seeds.len() >= 8);
+        for (name, input) in seeds {
+            let decoded: FuzzInput = from_bytes(&to_bytes(&input));
+            assert_eq!(
+                decoded.ops.len(),
+                input.ops.len().min(MAX_ARBITRARY_OPS),
+                "{name}"
+            );
+            // scenarios of the basic kind use settings the decoder covers
+            if name == "scenario-basic_unstake" {
+                assert_eq!(decoded, input);
+            }
+            fuzz_ops(&to_bytes(&input));
+        }
+
+        let flows = UnstakeFlow::from_csv("timestamp,amount\n1,5\n600000,2.5").unwrap();
+        let input = flow_seed(&flows);
+        assert_eq!(input.ops.len(), 4, "{:?}", input.ops);
+        assert_eq!(from_bytes::<FuzzInput>(&to_bytes(&input)), input);
+    }
+
+    #[test]
+    fn smoke_runs_targets() {
+        let mut rng = Rng::new(0);
+        for _ in 0..500 {
+            let data: Vec<u8> = (0..rng.range(0, 400))
+                .map(|_| rng.next_u64() as u8)
+                .collect();
+            fuzz_config(&data);
+            fuzz_ops(&data);
+        }
+    }
+}
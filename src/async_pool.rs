@@ -0,0 +1,256 @@
+//! Async facade over a pool owned by a dedicated worker thread. Calls are sent to the
+//! worker as messages and answered through a future, so async services (tokio,
+//! async-std, smol...) await pool operations without ever blocking an executor thread on
+//! a lock. The worker applies calls one at a time in the order they were sent and stops
+//! once every handle was dropped.
+//!
+//! A panic inside a call doesn't stop the worker, it's caught and resumed in the task
+//! which awaited the call.
+
+use std::any::Any;
+use std::future::Future;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+use crate::conservation::Balances;
+use crate::error::{AddLiquidityError, OpError, PriceUpdateError, RemoveLiquidityError, SwapError};
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::types::*;
+
+type Call = Box<dyn FnOnce(&mut LpPool) + Send>;
+
+#[derive(Debug)]
+/// Outcome of a call, `Err` holds the payload of a panic
+struct Slot<R> {
+    result: Option<Result<R, Box<dyn Any + Send>>>,
+    waker: Option<Waker>,
+}
+
+/// Future resolving once the worker answered the call
+struct Reply<R>(Arc<Mutex<Slot<R>>>);
+
+impl<R> Reply<R> {
+    fn lock(&self) -> MutexGuard<'_, Slot<R>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<R> Future for Reply<R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<R> {
+        let mut slot = self.lock();
+        match slot.result.take() {
+            Some(Ok(result)) => Poll::Ready(result),
+            Some(Err(panic)) => resume_unwind(panic),
+            None => {
+                slot.waker = Some(context.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Handle of a pool owned by a worker thread, clones share the pool
+pub struct AsyncLpPool {
+    calls: Sender<Call>,
+}
+
+impl AsyncLpPool {
+    /// Moves the pool to a newly spawned worker thread
+    pub fn spawn(pool: LpPool) -> Self {
+        let (calls, receiver) = channel::<Call>();
+        std::thread::Builder::new()
+            .name("lp-pool".into())
+            .spawn(move || {
+                let mut pool = pool;
+                for call in receiver {
+                    call(&mut pool);
+                }
+            })
+            .expect("pool worker thread spawns");
+        Self { calls }
+    }
+
+    /// Runs closure on the worker with exclusive access to the pool
+    pub fn with<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut LpPool) -> R + Send + 'static,
+    ) -> impl Future<Output = R> + Send {
+        let slot = Arc::new(Mutex::new(Slot {
+            result: None,
+            waker: None,
+        }));
+        let reply = Reply(slot.clone());
+        let call: Call = Box::new(move |pool| {
+            let result = catch_unwind(AssertUnwindSafe(|| f(pool)));
+            let mut slot = slot.lock().unwrap_or_else(PoisonError::into_inner);
+            slot.result = Some(result);
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        });
+        // the worker only stops once every handle was dropped
+        self.calls
+            .send(call)
+            .unwrap_or_else(|_| unreachable!("pool worker outlives its handles"));
+        reply
+    }
+
+    pub async fn apply(&self, op: PoolOp) -> Result<OpOutcome, OpError> {
+        self.with(move |pool| pool.apply(&op)).await
+    }
+
+    pub async fn add_liquidity(
+        &self,
+        token_amount_in: TokenAmount,
+    ) -> Result<LpTokenAmount, AddLiquidityError> {
+        self.with(move |pool| pool.add_liquidity(token_amount_in))
+            .await
+    }
+
+    pub async fn add_liquidity_for(
+        &self,
+        account: AccountId,
+        token_amount_in: TokenAmount,
+    ) -> Result<LpTokenAmount, AddLiquidityError> {
+        self.with(move |pool| pool.add_liquidity_for(account, token_amount_in))
+            .await
+    }
+
+    pub async fn remove_liquidity(
+        &self,
+        lp_amount_out: LpTokenAmount,
+    ) -> Result<(TokenAmount, StakedTokenAmount), RemoveLiquidityError> {
+        self.with(move |pool| pool.remove_liquidity(lp_amount_out))
+            .await
+    }
+
+    pub async fn remove_liquidity_for(
+        &self,
+        account: AccountId,
+        lp_amount_out: LpTokenAmount,
+    ) -> Result<(TokenAmount, StakedTokenAmount), RemoveLiquidityError> {
+        self.with(move |pool| pool.remove_liquidity_for(account, lp_amount_out))
+            .await
+    }
+
+    pub async fn swap(&self, swap_amount: StakedTokenAmount) -> Result<TokenAmount, SwapError> {
+        self.with(move |pool| pool.swap(swap_amount)).await
+    }
+
+    pub async fn set_price(&self, price: Price) -> Result<(), PriceUpdateError> {
+        self.with(move |pool| pool.set_price(price)).await
+    }
+
+    pub async fn advance_epoch(&self, epochs: Epoch) {
+        self.with(move |pool| pool.advance_epoch(epochs)).await
+    }
+
+    pub async fn amount_for_swap(
+        &self,
+        swap_amount: StakedTokenAmount,
+    ) -> Result<TokenAmount, SwapError> {
+        self.with(move |pool| pool.amount_for_swap(swap_amount))
+            .await
+    }
+
+    pub async fn current_fee(&self) -> Percentage {
+        self.with(|pool| pool.current_fee()).await
+    }
+
+    pub async fn balances(&self) -> Balances {
+        self.with(|pool| pool.balances()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::task::Wake;
+    use std::thread::Thread;
+
+    use super::*;
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Polls the future on the current thread, parking it until woken
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut context = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    fn pool() -> AsyncLpPool {
+        AsyncLpPool::spawn(LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap())
+    }
+
+    #[test]
+    fn awaits_operations_applied_by_the_worker() {
+        let pool = pool();
+        block_on(async {
+            assert_eq!(pool.add_liquidity(100.into()).await.unwrap(), 100.into());
+            let quote = pool.amount_for_swap(6.into()).await.unwrap();
+            assert_eq!(pool.swap(6.into()).await.unwrap(), quote);
+            pool.advance_epoch(1).await;
+            assert!(matches!(
+                pool.apply(PoolOp::Swap {
+                    account: None,
+                    amount: 0.into()
+                })
+                .await,
+                Err(OpError::Swap(SwapError::ZeroTokensAsArgument))
+            ));
+            assert_eq!(pool.balances().await.tokens, 91.009.into());
+            assert_eq!(pool.with(|pool| pool.epoch()).await, 1);
+        });
+    }
+
+    #[test]
+    fn serializes_calls_of_all_handles() {
+        let pool = pool();
+        block_on(pool.add_liquidity(1_000.into())).unwrap();
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let pool = pool.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        block_on(pool.swap(1.into())).unwrap();
+                    }
+                })
+            })
+            .collect();
+        threads
+            .into_iter()
+            .for_each(|thread| thread.join().unwrap());
+        assert_eq!(block_on(pool.balances()).staked_tokens, 40.into());
+    }
+
+    #[test]
+    fn resumes_panics_in_the_caller() {
+        let pool = pool();
+        let panicked = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            block_on(pool.with(|_| -> () { panic!("call failed") }))
+        }));
+        assert!(panicked.is_err());
+        // the worker keeps serving calls
+        assert_eq!(block_on(pool.current_fee()), 0.09.into());
+    }
+}
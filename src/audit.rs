@@ -0,0 +1,320 @@
+//! Append-only audit log of pool mutations for compliance-style reviews of simulation
+//! runs. The log is a `PoolHook`, so it's registered once with `LpPool::add_hook` and
+//! records every successful operation applied with `LpPool::apply`, rejected operations
+//! don't mutate the pool and aren't recorded. Clones of the log share its entries, so a
+//! clone kept outside the pool is used to query and export them.
+//!
+//! Entries carry a sequence number increasing by one from `0`, unix timestamp of the log
+//! clock, the actor the mutation is attributed to and pool balances around it.
+
+use std::fmt::Debug;
+use std::io::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::conservation::Balances;
+use crate::csv::decimal;
+use crate::error::{HookRejection, OpError};
+use crate::hooks::PoolHook;
+use crate::json::Value;
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::oracle::unix_now;
+use crate::schema::ToJson;
+use crate::types::*;
+
+/// columns of the exported file
+pub const AUDIT_CSV_HEADER: &str = "sequence,timestamp,actor,op,amount,epoch,\
+    tokens_before,staked_tokens_before,lp_tokens_before,price_before,treasury_fees_before,\
+    tokens_after,staked_tokens_after,lp_tokens_after,price_after,treasury_fees_after";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Single recorded mutation
+pub struct AuditEntry {
+    pub sequence: u64,
+    /// unix timestamp of the log clock when the mutation was applied
+    pub timestamp: i64,
+    /// actor set with `AuditLog::set_actor`, otherwise the account of the operation
+    pub actor: Option<AccountId>,
+    pub op: PoolOp,
+    /// epoch of the pool after the mutation
+    pub epoch: Epoch,
+    pub before: Balances,
+    pub after: Balances,
+}
+
+#[derive(Debug, Default)]
+struct AuditState {
+    entries: Vec<AuditEntry>,
+    actor: Option<AccountId>,
+    /// balances captured before the operation in flight
+    pending: Option<Balances>,
+}
+
+#[derive(Clone)]
+/// Shared handle of an audit log
+pub struct AuditLog {
+    state: Arc<Mutex<AuditState>>,
+    clock: Arc<dyn Fn() -> i64 + Send + Sync>,
+}
+
+impl AuditLog {
+    /// Creates empty log timestamping entries with system time
+    pub fn new() -> Self {
+        Self::with_clock(unix_now)
+    }
+
+    /// Creates empty log using custom clock returning current unix timestamp, e.g. to
+    /// timestamp replayed simulations deterministically
+    pub fn with_clock(clock: impl Fn() -> i64 + Send + Sync + 'static) -> Self {
+        Self {
+            state: Arc::default(),
+            clock: Arc::new(clock),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, AuditState> {
+        self.state.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Attributes following mutations to the actor, `None` attributes them to the account
+    /// of the operation again
+    pub fn set_actor(&self, actor: Option<AccountId>) {
+        self.state().actor = actor;
+    }
+
+    pub fn len(&self) -> usize {
+        self.state().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state().entries.is_empty()
+    }
+
+    /// Returns copy of all entries in sequence order
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        self.state().entries.clone()
+    }
+
+    /// Returns entries matching the predicate in sequence order
+    pub fn query(&self, predicate: impl Fn(&AuditEntry) -> bool) -> Vec<AuditEntry> {
+        let state = self.state();
+        state
+            .entries
+            .iter()
+            .filter(|entry| predicate(entry))
+            .copied()
+            .collect()
+    }
+
+    /// Returns entries with sequence number of at least `sequence`
+    pub fn since(&self, sequence: u64) -> Vec<AuditEntry> {
+        let state = self.state();
+        let start = state
+            .entries
+            .partition_point(|entry| entry.sequence < sequence);
+        state.entries[start..].to_vec()
+    }
+
+    /// Returns entries attributed to the actor
+    pub fn by_actor(&self, actor: AccountId) -> Vec<AuditEntry> {
+        self.query(|entry| entry.actor == Some(actor))
+    }
+
+    /// Writes entries as CSV with one row per entry, anonymous actors are left empty
+    pub fn export_csv(&self, mut writer: impl Write) -> std::io::Result<()> {
+        writeln!(writer, "{AUDIT_CSV_HEADER}")?;
+        for entry in self.entries() {
+            let amount = match entry.op {
+                PoolOp::AdvanceEpoch { epochs } => epochs.to_string(),
+                op => decimal(op.raw_amount()),
+            };
+            let actor = entry
+                .actor
+                .map(|actor| actor.to_string())
+                .unwrap_or_default();
+            writeln!(
+                writer,
+                "{},{},{actor},{},{amount},{},{},{}",
+                entry.sequence,
+                entry.timestamp,
+                entry.op.name(),
+                entry.epoch,
+                balances(&entry.before),
+                balances(&entry.after),
+            )?;
+        }
+        writer.flush()
+    }
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for AuditLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("entries", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+fn balances(balances: &Balances) -> String {
+    format!(
+        "{},{},{},{},{}",
+        decimal(balances.tokens.raw()),
+        decimal(balances.staked_tokens.raw()),
+        decimal(balances.lp_tokens.raw()),
+        decimal(balances.price.raw()),
+        decimal(balances.treasury_fees.raw())
+    )
+}
+
+impl PoolHook for AuditLog {
+    fn before_op(&mut self, pool: &LpPool, _op: &PoolOp) -> Result<(), HookRejection> {
+        self.state().pending = Some(pool.balances());
+        Ok(())
+    }
+
+    fn after_op(&mut self, pool: &LpPool, op: &PoolOp, result: &Result<OpOutcome, OpError>) {
+        let timestamp = (self.clock)();
+        let mut state = self.state();
+        let before = state.pending.take();
+        // rejections by earlier hooks skip `before_op`, nothing was mutated either way
+        let (Some(before), Ok(_)) = (before, result) else {
+            return;
+        };
+        let entry = AuditEntry {
+            sequence: state.entries.len() as u64,
+            timestamp,
+            actor: state.actor.or(op.account()),
+            op: *op,
+            epoch: pool.epoch(),
+            before,
+            after: pool.balances(),
+        };
+        state.entries.push(entry);
+    }
+}
+
+impl ToJson for AuditEntry {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("sequence", self.sequence.to_json()),
+            ("timestamp", self.timestamp.to_json()),
+            ("actor", self.actor.to_json()),
+            ("op", Value::from(self.op.name())),
+            ("amount", self.op.raw_amount().to_json()),
+            ("epoch", self.epoch.to_json()),
+            ("before", self.before.to_json()),
+            ("after", self.after.to_json()),
+        ])
+    }
+}
+
+impl ToJson for AuditLog {
+    fn to_json(&self) -> Value {
+        self.entries().to_json()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    use super::*;
+
+    /// Returns log ticking a minute with every operation and a pool recording into it
+    fn audited_pool() -> (AuditLog, LpPool) {
+        let clock = AtomicI64::new(1_700_000_000);
+        let log = AuditLog::with_clock(move || clock.fetch_add(60, Ordering::Relaxed));
+        let mut pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        pool.add_hook(log.clone());
+        (log, pool)
+    }
+
+    #[test]
+    fn records_mutations_in_sequence() {
+        let (log, mut pool) = audited_pool();
+        pool.apply(&PoolOp::AddLiquidity {
+            account: Some(7),
+            amount: 100.into(),
+        })
+        .unwrap();
+        pool.apply(&PoolOp::Swap {
+            account: None,
+            amount: 100.into(),
+        })
+        .unwrap_err();
+        log.set_actor(Some(3));
+        pool.apply(&PoolOp::Swap {
+            account: None,
+            amount: 6.into(),
+        })
+        .unwrap();
+        pool.apply(&PoolOp::AdvanceEpoch { epochs: 2 }).unwrap();
+
+        let entries = log.entries();
+        let sequences: Vec<_> = entries.iter().map(|entry| entry.sequence).collect();
+        assert_eq!(sequences, [0, 1, 2]);
+        let timestamps: Vec<_> = entries.iter().map(|entry| entry.timestamp).collect();
+        // the rejected swap still ticks the clock
+        assert_eq!(timestamps, [1_700_000_000, 1_700_000_120, 1_700_000_180]);
+        assert_eq!(entries[0].actor, Some(7));
+        assert_eq!(
+            entries[0].before,
+            Balances {
+                price: 1.5.into(),
+                ..Balances::default()
+            }
+        );
+        assert_eq!(entries[1].before, entries[0].after);
+        assert_eq!(entries[1].after.tokens, 91.009.into());
+        assert_eq!(entries[2].epoch, 2);
+
+        assert_eq!(log.by_actor(3).len(), 2);
+        assert_eq!(log.since(2), &entries[2..]);
+        assert!(log.since(3).is_empty());
+        let swaps = log.query(|entry| matches!(entry.op, PoolOp::Swap { .. }));
+        assert_eq!(swaps, &entries[1..2]);
+    }
+
+    #[test]
+    fn exports_entries() {
+        let (log, mut pool) = audited_pool();
+        pool.apply(&PoolOp::AddLiquidity {
+            account: Some(7),
+            amount: 100.into(),
+        })
+        .unwrap();
+        pool.apply(&PoolOp::Swap {
+            account: None,
+            amount: 6.into(),
+        })
+        .unwrap();
+
+        let mut csv = Vec::new();
+        log.export_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], AUDIT_CSV_HEADER);
+        assert_eq!(
+            lines[1],
+            "0,1700000000,7,add_liquidity,100.000000,0,\
+            0.000000,0.000000,0.000000,1.500000,0.000000,\
+            100.000000,0.000000,100.000000,1.500000,0.000000"
+        );
+        assert!(lines[2].starts_with("1,1700000060,,swap,6.000000,0,100.000000,"));
+
+        let json = log.to_json();
+        let entries = json.as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].get("op"), Some(&Value::from("swap")));
+        assert_eq!(
+            entries[1].get("after").unwrap().get("tokens"),
+            Some(&TokenAmount::from(91.009).to_json())
+        );
+    }
+}
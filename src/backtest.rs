@@ -0,0 +1,153 @@
+use crate::fee_policy::FeePolicy;
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Results of replaying an operation stream with a single fee policy
+pub struct PolicyReport {
+    pub policy: FeePolicy,
+    /// all fees charged by swaps
+    pub revenue: TokenAmount,
+    /// mean share of swap value lost to fees over successful swaps
+    pub average_slippage: Percentage,
+    pub successful_swaps: usize,
+    /// operations rejected by the pool
+    pub failed_ops: usize,
+    /// lowest observed ratio of pool tokens to the liquidity target
+    pub min_liquidity_ratio: Percentage,
+    /// ratio of pool tokens to the liquidity target after the last operation
+    pub final_liquidity_ratio: Percentage,
+}
+
+/// Replays the operation stream against a fresh pool for every policy and returns
+/// one report per policy, in the same order as `policies`.
+///
+/// # Arguments
+///
+/// * `make_pool` - creates pool in its initial state, called once per policy
+/// * `ops` - recorded operation stream
+/// * `policies` - fee policies to compare
+pub fn backtest(
+    make_pool: impl Fn() -> LpPool,
+    ops: &[PoolOp],
+    policies: &[FeePolicy],
+) -> Vec<PolicyReport> {
+    policies
+        .iter()
+        .map(|policy| {
+            let mut pool = make_pool();
+            pool.set_fee_policy(*policy);
+            replay(pool, ops)
+        })
+        .collect()
+}
+
+/// Replays the operation stream against the pool using its current fee policy
+pub(crate) fn replay(mut pool: LpPool, ops: &[PoolOp]) -> PolicyReport {
+    let mut total_slippage: u128 = 0;
+    let mut successful_swaps = 0;
+    let mut failed_ops = 0;
+    let mut min_liquidity_ratio = liquidity_ratio(&pool);
+
+    for op in ops {
+        let value_before_fees = match op {
+            PoolOp::Swap { amount, .. } => amount.checked_into_token_amount(pool.price()),
+            _ => None,
+        };
+
+        match pool.apply(op) {
+            Ok(OpOutcome::Swapped(amount_out)) => {
+                let value = value_before_fees.expect("swap op yields swap outcome");
+                if value.raw() > 0 {
+                    total_slippage +=
+                        (value - amount_out).raw() as u128 * SCALE as u128 / value.raw() as u128;
+                }
+                successful_swaps += 1;
+            }
+            Ok(_) => {}
+            Err(_) => failed_ops += 1,
+        }
+
+        let ratio = liquidity_ratio(&pool);
+        if ratio < min_liquidity_ratio {
+            min_liquidity_ratio = ratio;
+        }
+    }
+
+    let average_slippage = match successful_swaps {
+        0 => 0,
+        swaps => (total_slippage / swaps as u128) as Uint,
+    };
+
+    PolicyReport {
+        policy: pool.fee_policy(),
+        revenue: pool.fee_revenue().total,
+        average_slippage: Percentage::from_raw_amount(average_slippage),
+        successful_swaps,
+        failed_ops,
+        min_liquidity_ratio,
+        final_liquidity_ratio: liquidity_ratio(&pool),
+    }
+}
+
+/// Returns ratio of pool tokens to the liquidity target
+pub(crate) fn liquidity_ratio(pool: &LpPool) -> Percentage {
+    let target = pool.params().liquidity_target.raw().max(1) as u128;
+    let ratio = pool.token_amount().raw() as u128 * SCALE as u128 / target;
+    Percentage::from_raw_amount(ratio.min(Uint::MAX as u128) as Uint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_pool() -> LpPool {
+        LpPool::init(1.into(), 0.01.into(), 0.1.into(), 100.into()).unwrap()
+    }
+
+    fn ops() -> Vec<PoolOp> {
+        vec![
+            PoolOp::AddLiquidity {
+                account: None,
+                amount: 100.into(),
+            },
+            PoolOp::SetPrice { price: 1.2.into() },
+            PoolOp::SetPrice { price: 1.into() },
+            PoolOp::Swap {
+                account: None,
+                amount: 10.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 1000.into(),
+            },
+        ]
+    }
+
+    #[test]
+    fn compares_policies() {
+        let volatile = FeePolicy::VolatilitySensitive {
+            sensitivity: 1.0.into(),
+            max_surcharge: 0.5.into(),
+        };
+        let reports = backtest(make_pool, &ops(), &[FeePolicy::Linear, volatile]);
+
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].policy, FeePolicy::Linear);
+        assert_eq!(reports[0].successful_swaps, 1);
+        assert_eq!(reports[0].failed_ops, 1);
+        assert!(reports[1].revenue > reports[0].revenue);
+        assert!(reports[1].average_slippage > reports[0].average_slippage);
+    }
+
+    #[test]
+    fn tracks_liquidity_health() {
+        let reports = backtest(make_pool, &ops(), &[FeePolicy::Linear]);
+        let report = reports[0];
+
+        assert_eq!(report.min_liquidity_ratio, Percentage::from(0.0));
+        assert!(report.final_liquidity_ratio < Percentage::from(1.0));
+        assert!(report.final_liquidity_ratio > Percentage::from(0.9));
+    }
+}
@@ -0,0 +1,53 @@
+//! Writes seed inputs of the fuzz targets into `fuzz/corpus`, replaying the canonical
+//! scenarios, seeded simulations and recorded unstake flows given as arguments.
+//!
+//! ```text
+//! fuzz-corpus [flows.csv|flows.json]...
+//! ```
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+use invariant_task::{corpus_seeds, flow_seed, to_bytes, UnstakeFlow};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(written) => {
+            println!("wrote {written} corpus files");
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(flow_files: &[String]) -> Result<usize, Box<dyn Error>> {
+    let mut seeds = corpus_seeds();
+    for path in flow_files {
+        let name = Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        seeds.push((
+            format!("flows-{name}"),
+            flow_seed(&UnstakeFlow::load(path)?),
+        ));
+    }
+
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fuzz")
+        .join("corpus");
+    let (ops, config) = (corpus.join("ops"), corpus.join("config"));
+    fs::create_dir_all(&ops)?;
+    fs::create_dir_all(&config)?;
+    for (name, input) in &seeds {
+        fs::write(ops.join(name), to_bytes(input))?;
+        fs::write(config.join(name), to_bytes(&input.config))?;
+    }
+    Ok(2 * seeds.len())
+}
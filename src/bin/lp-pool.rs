@@ -0,0 +1,147 @@
+//! Command line tool managing a pool persisted in a snapshot file.
+//!
+//! ```text
+//! lp-pool <state-file> init <config.toml|config.yaml>
+//! lp-pool <state-file> add <token-amount>
+//! lp-pool <state-file> remove <lp-token-amount>
+//! lp-pool <state-file> swap <staked-token-amount>
+//! lp-pool <state-file> stats
+//! ```
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+use invariant_task::{LpPool, PoolConfig};
+
+const USAGE: &str = "usage: lp-pool <state-file> <init <config>|add <amount>|remove <lp-amount>|swap <amount>|stats>";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match run(&args) {
+        Ok(output) => {
+            println!("{output}");
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let [state, command, rest @ ..] = args else {
+        return Err(USAGE.into());
+    };
+    let state = Path::new(state);
+
+    match (*command, rest) {
+        ("init", [config]) => {
+            if state.exists() {
+                return Err(format!("state file {} already exists", state.display()).into());
+            }
+            let pool = PoolConfig::load(config)?.build();
+            save(state, &pool)?;
+            Ok(stats(&pool))
+        }
+        ("add", [amount]) => {
+            let mut pool = load(state)?;
+            let minted = pool.add_liquidity(amount.parse()?)?;
+            save(state, &pool)?;
+            Ok(format!("minted {minted} lp tokens"))
+        }
+        ("remove", [lp_amount]) => {
+            let mut pool = load(state)?;
+            let (tokens, staked_tokens) = pool.remove_liquidity(lp_amount.parse()?)?;
+            save(state, &pool)?;
+            Ok(format!(
+                "received {tokens} tokens and {staked_tokens} staked tokens"
+            ))
+        }
+        ("swap", [amount]) => {
+            let mut pool = load(state)?;
+            let received = pool.swap(amount.parse()?)?;
+            save(state, &pool)?;
+            Ok(format!("received {received} tokens"))
+        }
+        ("stats", []) => Ok(stats(&load(state)?)),
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn load(state: &Path) -> Result<LpPool, Box<dyn Error>> {
+    let bytes = fs::read(state)
+        .map_err(|error| format!("can't read state file {}: {error}", state.display()))?;
+    Ok(LpPool::from_snapshot(&bytes)?)
+}
+
+/// Writes snapshot next to the state file first, so a failed write never corrupts it
+fn save(state: &Path, pool: &LpPool) -> Result<(), Box<dyn Error>> {
+    let temporary = state.with_extension("tmp");
+    fs::write(&temporary, pool.to_snapshot())?;
+    fs::rename(&temporary, state)?;
+    Ok(())
+}
+
+fn stats(pool: &LpPool) -> String {
+    let params = pool.params();
+    let fees = pool.fee_revenue();
+    [
+        format!("price:            {}", pool.price()),
+        format!("epoch:            {}", pool.epoch()),
+        format!("tokens:           {}", pool.token_amount()),
+        format!("staked tokens:    {}", pool.st_token_amount()),
+        format!("lp tokens:        {}", pool.lp_token_amount()),
+        format!("total value:      {}", pool.total_value()),
+        format!("liquidity target: {}", params.liquidity_target),
+        format!("fees:             {} - {}", params.min_fee, params.max_fee),
+        format!(
+            "fees collected:   {} ({} lp, {} treasury)",
+            fees.total, fees.lp, fees.treasury
+        ),
+    ]
+    .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manages_persisted_pool() {
+        let directory = std::env::temp_dir().join(format!("lp-pool-cli-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let config = directory.join("pool.toml");
+        fs::write(
+            &config,
+            "price = 1.5\nliquidity_target = 90\nmin_fee_bps = 10\nmax_fee_bps = 900\n",
+        )
+        .unwrap();
+        let state = directory.join("pool.bin");
+        let (state, config) = (state.to_str().unwrap(), config.to_str().unwrap());
+
+        assert!(run(&[state, "init", config])
+            .unwrap()
+            .contains("price:            1.500000"));
+        assert!(run(&[state, "init", config]).is_err());
+        assert_eq!(
+            run(&[state, "add", "100"]).unwrap(),
+            "minted 100.000000 lp tokens"
+        );
+        assert_eq!(
+            run(&[state, "swap", "6"]).unwrap(),
+            "received 8.991000 tokens"
+        );
+        assert!(run(&[state, "stats"])
+            .unwrap()
+            .contains("staked tokens:    6.000000"));
+        assert!(run(&[state, "remove", "1000"]).is_err());
+        assert!(run(&[state, "swap", "six"]).is_err());
+        assert!(run(&[state, "burn"]).is_err());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
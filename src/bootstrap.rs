@@ -0,0 +1,112 @@
+//! Liquidity bootstrapping mode modelled after liquidity bootstrapping pools. Right after
+//! it's enabled the pool charges a high surcharge on top of the regular swap fee, which
+//! decays linearly to zero over the configured amount of epochs. Early swappers pay for
+//! draining the thin initial liquidity, while LPs keep joining at regular terms.
+
+use crate::error::SchemaError;
+use crate::json::Value;
+use crate::schema::{field, FromJson, ToJson};
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Configuration of the bootstrapping surcharge
+pub struct BootstrapConfig {
+    /// fee added on top of the regular fee when bootstrapping starts
+    pub surcharge: Percentage,
+    /// epochs over which the surcharge decays to zero
+    pub epochs: Epoch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Bootstrapping surcharge started at a given epoch
+pub struct LiquidityBootstrap {
+    config: BootstrapConfig,
+    started_at: Epoch,
+}
+
+impl LiquidityBootstrap {
+    pub fn new(config: BootstrapConfig, started_at: Epoch) -> Self {
+        Self { config, started_at }
+    }
+
+    pub fn config(&self) -> &BootstrapConfig {
+        &self.config
+    }
+
+    pub fn started_at(&self) -> Epoch {
+        self.started_at
+    }
+
+    /// Returns epoch from which on no surcharge is charged
+    pub fn ends_at(&self) -> Epoch {
+        self.started_at.saturating_add(self.config.epochs)
+    }
+
+    /// Returns raw surcharge charged in the epoch multiplied by `SCALE`, unrounded
+    pub(crate) fn scaled_surcharge(&self, epoch: Epoch) -> u128 {
+        let remaining = self.ends_at().saturating_sub(epoch.max(self.started_at));
+        match self.config.epochs {
+            0 => 0,
+            epochs => {
+                self.config.surcharge.raw() as u128 * SCALE as u128 * remaining as u128
+                    / epochs as u128
+            }
+        }
+    }
+
+    /// Returns surcharge charged in the epoch, rounded up
+    pub fn surcharge(&self, epoch: Epoch) -> Percentage {
+        Percentage::from_raw_amount(
+            self.scaled_surcharge(epoch)
+                .div_ceil(SCALE as u128)
+                .min(Uint::MAX as u128) as Uint,
+        )
+    }
+}
+
+impl ToJson for LiquidityBootstrap {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("surcharge", self.config.surcharge.to_json()),
+            ("epochs", self.config.epochs.to_json()),
+            ("started_at", self.started_at.to_json()),
+        ])
+    }
+}
+
+impl FromJson for LiquidityBootstrap {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        Ok(Self {
+            config: BootstrapConfig {
+                surcharge: field(value, "surcharge")?,
+                epochs: field(value, "epochs")?,
+            },
+            started_at: field(value, "started_at")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decays_linearly_to_zero() {
+        let bootstrap = LiquidityBootstrap::new(
+            BootstrapConfig {
+                surcharge: 0.1.into(),
+                epochs: 4,
+            },
+            2,
+        );
+        let surcharges: Vec<_> = (0..8).map(|epoch| bootstrap.surcharge(epoch)).collect();
+        assert_eq!(
+            surcharges,
+            [0.1, 0.1, 0.1, 0.075, 0.05, 0.025, 0.0, 0.0].map(Percentage::from)
+        );
+        assert_eq!(bootstrap.ends_at(), 6);
+
+        let restored = LiquidityBootstrap::from_json(&bootstrap.to_json()).unwrap();
+        assert_eq!(restored, bootstrap);
+    }
+}
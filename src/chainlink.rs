@@ -0,0 +1,221 @@
+use std::fmt::Debug;
+use std::sync::Mutex;
+
+use crate::error::OracleError;
+use crate::oracle::{unix_now, PriceOracle};
+use crate::types::*;
+
+/// length of ABI encoded `latestRoundData` return value, five 32 byte words
+pub const ROUND_DATA_ABI_LEN: usize = 160;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Round data returned by Chainlink aggregator's `latestRoundData`
+pub struct ChainlinkRound {
+    pub round_id: u128,
+    pub answer: i128,
+    pub started_at: u64,
+    /// unix timestamp of the answer
+    pub updated_at: u64,
+    pub answered_in_round: u128,
+}
+
+impl ChainlinkRound {
+    /// Decodes ABI encoded return value of `latestRoundData`
+    /// (`uint80, int256, uint256, uint256, uint80`)
+    pub fn from_abi(data: &[u8]) -> Result<Self, OracleError> {
+        if data.len() != ROUND_DATA_ABI_LEN {
+            return Err(invalid("round data has to be five ABI words"));
+        }
+        let word = |index: usize| &data[index * 32..(index + 1) * 32];
+
+        Ok(Self {
+            round_id: read_uint(word(0))?,
+            answer: read_int(word(1))?,
+            started_at: u64::try_from(read_uint(word(2))?)
+                .map_err(|_| invalid("startedAt overflow"))?,
+            updated_at: u64::try_from(read_uint(word(3))?)
+                .map_err(|_| invalid("updatedAt overflow"))?,
+            answered_in_round: read_uint(word(4))?,
+        })
+    }
+
+    /// Converts the answer into the crate's `Price` after checking the round is complete
+    /// and fresh.
+    ///
+    /// # Arguments
+    ///
+    /// * `decimals` - decimals of the aggregator answer
+    /// * `max_age_secs` - max age of the answer in seconds
+    /// * `now` - current unix timestamp
+    pub fn to_price(
+        &self,
+        decimals: u8,
+        max_age_secs: u64,
+        now: i64,
+    ) -> Result<Price, OracleError> {
+        if self.updated_at == 0 || self.answered_in_round < self.round_id {
+            return Err(invalid("round is incomplete"));
+        }
+        if self.answer <= 0 {
+            return Err(OracleError::ZeroPrice);
+        }
+
+        let age = (now.max(0) as u64).saturating_sub(self.updated_at);
+        if age > max_age_secs {
+            return Err(OracleError::StalePrice {
+                age,
+                max_age: max_age_secs,
+            });
+        }
+
+        let answer = u64::try_from(self.answer).map_err(|_| invalid("answer overflow"))?;
+        Price::from_decimal(answer, -(decimals as i32))
+            .ok_or_else(|| invalid("price doesn't fit into fixed-point representation"))
+    }
+}
+
+/// Oracle reading prices from the latest Chainlink round pushed into it
+pub struct ChainlinkOracle {
+    round: Mutex<Option<ChainlinkRound>>,
+    decimals: u8,
+    max_age_secs: u64,
+    clock: Box<dyn Fn() -> i64 + Send + Sync>,
+}
+
+impl ChainlinkOracle {
+    /// Creates oracle using system time to check answer staleness
+    pub fn new(decimals: u8, max_age_secs: u64) -> Self {
+        Self::with_clock(decimals, max_age_secs, unix_now)
+    }
+
+    /// Creates oracle using custom clock returning current unix timestamp
+    pub fn with_clock(
+        decimals: u8,
+        max_age_secs: u64,
+        clock: impl Fn() -> i64 + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            round: Mutex::new(None),
+            decimals,
+            max_age_secs,
+            clock: Box::new(clock),
+        }
+    }
+
+    /// Replaces stored round with freshly fetched one
+    pub fn update_round(&self, round: ChainlinkRound) {
+        *self.round.lock().unwrap_or_else(|p| p.into_inner()) = Some(round);
+    }
+}
+
+impl Debug for ChainlinkOracle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChainlinkOracle")
+            .field("decimals", &self.decimals)
+            .field("max_age_secs", &self.max_age_secs)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PriceOracle for ChainlinkOracle {
+    fn price(&self) -> Result<Price, OracleError> {
+        let round = *self.round.lock().unwrap_or_else(|p| p.into_inner());
+        let round = round.ok_or_else(|| OracleError::Unavailable("no round data".into()))?;
+        round.to_price(self.decimals, self.max_age_secs, (self.clock)())
+    }
+}
+
+fn invalid(reason: &str) -> OracleError {
+    OracleError::InvalidData(reason.into())
+}
+
+/// Reads big endian ABI word that has to fit into `u128`
+fn read_uint(word: &[u8]) -> Result<u128, OracleError> {
+    if word[..16].iter().any(|byte| *byte != 0) {
+        return Err(invalid("value doesn't fit into 128 bits"));
+    }
+    Ok(u128::from_be_bytes(
+        word[16..].try_into().expect("word is 32 bytes"),
+    ))
+}
+
+/// Reads big endian two's complement ABI word that has to fit into `i128`
+fn read_int(word: &[u8]) -> Result<i128, OracleError> {
+    let sign_extension = if word[16] & 0x80 == 0 { 0x00 } else { 0xff };
+    if word[..16].iter().any(|byte| *byte != sign_extension) {
+        return Err(invalid("value doesn't fit into 128 bits"));
+    }
+    Ok(i128::from_be_bytes(
+        word[16..].try_into().expect("word is 32 bytes"),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn round(answer: i128, updated_at: u64) -> ChainlinkRound {
+        ChainlinkRound {
+            round_id: 7,
+            answer,
+            started_at: updated_at,
+            updated_at,
+            answered_in_round: 7,
+        }
+    }
+
+    fn encode(round: &ChainlinkRound) -> Vec<u8> {
+        let mut data = Vec::with_capacity(ROUND_DATA_ABI_LEN);
+        let mut push_uint = |value: u128| {
+            data.extend_from_slice(&[0u8; 16]);
+            data.extend_from_slice(&value.to_be_bytes());
+        };
+        push_uint(round.round_id);
+        push_uint(0);
+        push_uint(round.started_at as u128);
+        push_uint(round.updated_at as u128);
+        push_uint(round.answered_in_round);
+
+        let sign = if round.answer < 0 { 0xff } else { 0x00 };
+        data[32..48].copy_from_slice(&[sign; 16]);
+        data[48..64].copy_from_slice(&round.answer.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn decodes_abi_round_data() {
+        let expected = round(-5, NOW as u64);
+        assert_eq!(ChainlinkRound::from_abi(&encode(&expected)), Ok(expected));
+        assert!(ChainlinkRound::from_abi(&[0u8; 10]).is_err());
+    }
+
+    #[test]
+    fn converts_answer_using_decimals() {
+        let oracle = ChainlinkOracle::with_clock(18, 3600, || NOW);
+        oracle.update_round(round(1_150_000_000_000_000_000, NOW as u64 - 10));
+        assert_eq!(oracle.price(), Ok(Price::from(1.15)));
+    }
+
+    #[test]
+    fn rejects_stale_and_incomplete_rounds() {
+        let oracle = ChainlinkOracle::with_clock(8, 3600, || NOW);
+        assert!(matches!(oracle.price(), Err(OracleError::Unavailable(_))));
+
+        oracle.update_round(round(115_000_000, NOW as u64 - 3601));
+        assert!(matches!(
+            oracle.price(),
+            Err(OracleError::StalePrice { .. })
+        ));
+
+        oracle.update_round(ChainlinkRound {
+            answered_in_round: 6,
+            ..round(115_000_000, NOW as u64)
+        });
+        assert!(matches!(oracle.price(), Err(OracleError::InvalidData(_))));
+
+        oracle.update_round(round(0, NOW as u64));
+        assert_eq!(oracle.price(), Err(OracleError::ZeroPrice));
+    }
+}
@@ -0,0 +1,256 @@
+//! Chaos layer of the simulator. It runs the regular simulation and after randomly
+//! chosen steps injects price shocks, oracle outages, during which the price isn't
+//! refreshed at the end of an epoch, and bursts of swaps big enough to drain the pool.
+//!
+//! Chaos is drawn from its own random stream, so a run without chaos generates exactly
+//! the operations of `simulate`. After the run the pool has to be consistent, so the
+//! operations conserve value when replayed and the end state restores from its snapshot,
+//! and recoverable, so it accepts a fresh price, a deposit, a swap and a full withdrawal.
+
+use crate::conservation::check_ops;
+use crate::error::ChaosError;
+use crate::lp_pool::LpPool;
+use crate::ops::PoolOp;
+use crate::rng::Rng;
+use crate::simulator::{Recorder, Simulation, SimulationConfig};
+use crate::types::*;
+
+/// mixed into the seed of the simulation to seed the chaos stream
+const CHAOS_STREAM: u64 = 0xc4a0_5c4a_05c4_a05c;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Rates and sizes of injected events
+pub struct ChaosConfig {
+    /// probability that a step is followed by an event
+    pub rate: f64,
+    /// biggest relative price change of a shock, prices move up or down
+    pub max_shock: f64,
+    /// longest outage in epochs
+    pub max_outage: Epoch,
+    /// biggest amount of swaps in a burst
+    pub max_burst: usize,
+    /// biggest value of a burst swap relative to tokens in the pool
+    pub burst_size: f64,
+}
+
+impl ChaosConfig {
+    /// Creates chaos of the rate with shocks of up to 50%, outages of up to 5 epochs and
+    /// bursts of up to 10 swaps worth up to twice the pool liquidity each
+    pub fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            max_shock: 0.5,
+            max_outage: 5,
+            max_burst: 10,
+            burst_size: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Event injected into a simulation
+pub enum ChaosEvent {
+    PriceShock { price: Price },
+    OracleOutage { epochs: Epoch },
+    SwapBurst { swaps: usize },
+}
+
+#[derive(Debug)]
+/// Result of a chaos run
+pub struct ChaosSimulation {
+    pub simulation: Simulation,
+    /// injected events with the step they followed
+    pub events: Vec<(usize, ChaosEvent)>,
+}
+
+/// Runs simulation with injected events and checks that it ends in a consistent and
+/// recoverable state
+pub fn simulate_chaos(
+    config: &SimulationConfig,
+    chaos: &ChaosConfig,
+) -> Result<ChaosSimulation, ChaosError> {
+    let mut rng = Rng::new(config.seed);
+    let mut chaos_rng = Rng::new(config.seed ^ CHAOS_STREAM);
+    let mut pool = config.pool.build();
+    let mut recorder = Recorder::new(&pool);
+    let mut events = Vec::new();
+    let mut outage: Epoch = 0;
+
+    recorder.run(
+        &mut pool,
+        PoolOp::AddLiquidity {
+            account: None,
+            amount: config.initial_liquidity,
+        },
+    );
+    for step in 1..=config.steps {
+        let op = match rng.chance(config.deposit_probability) {
+            true => PoolOp::AddLiquidity {
+                account: None,
+                amount: TokenAmount::from_raw_amount(config.deposit_size.sample(&mut rng)),
+            },
+            false => PoolOp::Swap {
+                account: None,
+                amount: StakedTokenAmount::from_raw_amount(config.swap_size.sample(&mut rng)),
+            },
+        };
+        recorder.run(&mut pool, op);
+
+        if chaos_rng.chance(chaos.rate) {
+            let event = inject(&mut pool, &mut recorder, chaos, &mut chaos_rng);
+            if let ChaosEvent::OracleOutage { epochs } = event {
+                outage += epochs;
+            }
+            events.push((step, event));
+        }
+
+        if config.epoch_length > 0 && step % config.epoch_length == 0 {
+            recorder.run(&mut pool, PoolOp::AdvanceEpoch { epochs: 1 });
+            // the price is drawn even during outages to keep the simulation stream intact
+            let price = config.price_drift.next(pool.price(), &mut rng);
+            match outage {
+                0 => {
+                    recorder.run(&mut pool, PoolOp::SetPrice { price });
+                }
+                _ => outage -= 1,
+            }
+        }
+    }
+    let simulation = recorder.finish(pool);
+
+    check_ops(&mut config.pool.build(), &simulation.ops)?;
+    let restored = LpPool::from_snapshot(&simulation.pool.to_snapshot())
+        .map_err(|_| ChaosError::NotRestorable)?;
+    if restored.state_hash() != simulation.pool.state_hash() {
+        return Err(ChaosError::NotRestorable);
+    }
+    recover(restored, config.initial_liquidity)?;
+    Ok(ChaosSimulation { simulation, events })
+}
+
+/// Applies a random event to the pool
+fn inject(
+    pool: &mut LpPool,
+    recorder: &mut Recorder,
+    chaos: &ChaosConfig,
+    rng: &mut Rng,
+) -> ChaosEvent {
+    match rng.below(3) {
+        0 => {
+            let change = 1.0 + chaos.max_shock * (2.0 * rng.unit() - 1.0);
+            let price =
+                Price::from_raw_amount(((pool.price().raw() as f64 * change) as Uint).max(1));
+            recorder.run(pool, PoolOp::SetPrice { price });
+            ChaosEvent::PriceShock { price }
+        }
+        1 => ChaosEvent::OracleOutage {
+            epochs: rng.range(1, chaos.max_outage.max(1)),
+        },
+        _ => {
+            let swaps = rng.range(1, chaos.max_burst.max(1) as u64) as usize;
+            for _ in 0..swaps {
+                let value = pool.token_amount().raw() as f64 * chaos.burst_size * rng.unit();
+                let amount = value * SCALE as f64 / pool.price().raw() as f64;
+                let amount = StakedTokenAmount::from_raw_amount((amount as Uint).max(1));
+                recorder.run(
+                    pool,
+                    PoolOp::Swap {
+                        account: None,
+                        amount,
+                    },
+                );
+            }
+            ChaosEvent::SwapBurst { swaps }
+        }
+    }
+}
+
+/// Checks that the pool accepts a fresh price, a deposit, a swap of a hundredth of the
+/// deposit and withdrawal of all liquidity
+fn recover(mut pool: LpPool, deposit: TokenAmount) -> Result<(), ChaosError> {
+    let price = pool.price();
+    let swap = deposit.raw() as u128 * SCALE as u128 / price.raw() as u128 / 100;
+    let apply = |pool: &mut LpPool, op: PoolOp| {
+        pool.apply(&op)
+            .map(|_| ())
+            .map_err(|error| ChaosError::NotRecovered { op, error })
+    };
+    apply(&mut pool, PoolOp::SetPrice { price })?;
+    apply(
+        &mut pool,
+        PoolOp::AddLiquidity {
+            account: None,
+            amount: deposit,
+        },
+    )?;
+    apply(
+        &mut pool,
+        PoolOp::Swap {
+            account: None,
+            amount: StakedTokenAmount::from_raw_amount((swap as Uint).max(1)),
+        },
+    )?;
+    let lp_amount = pool.lp_token_amount();
+    apply(
+        &mut pool,
+        PoolOp::RemoveLiquidity {
+            account: None,
+            lp_amount,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::governance::PoolParams;
+    use crate::replay::PoolConfig;
+    use crate::simulator::simulate;
+
+    fn config(seed: u64) -> SimulationConfig {
+        let mut pool = PoolConfig::new(
+            1.1.into(),
+            PoolParams {
+                min_fee: 0.001.into(),
+                max_fee: 0.09.into(),
+                liquidity_target: 10_000.into(),
+            },
+        );
+        pool.treasury_cut = 0.2.into();
+        pool.max_price_age = Some(2);
+        pool.max_price_deviation = Some(0.3.into());
+        SimulationConfig::new(pool, seed)
+    }
+
+    #[test]
+    fn runs_plain_simulation_without_chaos() {
+        let chaos = simulate_chaos(&config(3), &ChaosConfig::new(0.0)).unwrap();
+        assert!(chaos.events.is_empty());
+        let plain = simulate(&config(3));
+        assert_eq!(chaos.simulation.ops, plain.ops);
+        assert_eq!(chaos.simulation.pool.state_hash(), plain.pool.state_hash());
+    }
+
+    #[test]
+    fn recovers_from_injected_events() {
+        for seed in 0..5 {
+            let config = SimulationConfig {
+                epoch_length: 20,
+                ..config(seed)
+            };
+            let chaos = simulate_chaos(&config, &ChaosConfig::new(0.05)).unwrap();
+            let count = |matches: fn(&ChaosEvent) -> bool| {
+                chaos
+                    .events
+                    .iter()
+                    .filter(|(_, event)| matches(event))
+                    .count()
+            };
+            assert!(count(|event| matches!(event, ChaosEvent::PriceShock { .. })) > 0);
+            assert!(count(|event| matches!(event, ChaosEvent::OracleOutage { .. })) > 0);
+            assert!(count(|event| matches!(event, ChaosEvent::SwapBurst { .. })) > 0);
+            // bursts drain the pool and outages leave the price stale
+            assert!(chaos.simulation.stats.failed_ops > 0);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::error::RestoreError;
+use crate::hashing::StateHash;
+use crate::lp_pool::LpPool;
+use crate::ops::PoolOp;
+use crate::replay::PoolConfig;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Snapshot of the pool taken after a given amount of logged operations
+pub struct Checkpoint {
+    /// amount of operations applied before the snapshot was taken
+    pub op_index: usize,
+    pub state_hash: StateHash,
+    snapshot: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+/// Operation log with periodic checkpoints. Restoring starts from the latest checkpoint,
+/// and compaction drops everything before it, so replay time stays bounded however long
+/// the simulation runs.
+pub struct CheckpointLog {
+    config: PoolConfig,
+    /// amount of operations between checkpoints
+    interval: usize,
+    checkpoints: Vec<Checkpoint>,
+    /// operations following `first_op_index`
+    ops: Vec<PoolOp>,
+    first_op_index: usize,
+}
+
+impl CheckpointLog {
+    /// Creates log of a pool built from `config`, checkpointing every `interval` operations
+    pub fn new(config: PoolConfig, interval: usize) -> Self {
+        Self {
+            config,
+            interval: interval.max(1),
+            checkpoints: Vec::new(),
+            ops: Vec::new(),
+            first_op_index: 0,
+        }
+    }
+
+    /// Moves operations recorded by the pool into the log and takes a checkpoint when
+    /// `interval` operations passed since the last one
+    pub fn record(&mut self, pool: &mut LpPool) {
+        self.ops.extend(pool.take_op_log());
+        let last_checkpoint = self
+            .checkpoints
+            .last()
+            .map_or(0, |checkpoint| checkpoint.op_index);
+        if self.op_count() - last_checkpoint >= self.interval {
+            self.checkpoint(pool);
+        }
+    }
+
+    /// Takes checkpoint of the pool, the pool's operation log has to be recorded first
+    pub fn checkpoint(&mut self, pool: &LpPool) {
+        self.checkpoints.push(Checkpoint {
+            op_index: self.op_count(),
+            state_hash: pool.state_hash(),
+            snapshot: pool.to_snapshot(),
+        });
+    }
+
+    /// Drops checkpoints and operations preceding the latest checkpoint
+    pub fn compact(&mut self) {
+        let Some(latest) = self.checkpoints.pop() else {
+            return;
+        };
+        self.ops.drain(..latest.op_index - self.first_op_index);
+        self.first_op_index = latest.op_index;
+        self.checkpoints = vec![latest];
+    }
+
+    /// Restores the pool from the latest checkpoint and operations recorded after it
+    pub fn restore(&self) -> Result<LpPool, RestoreError> {
+        let (mut pool, first_op_index) = match self.checkpoints.last() {
+            Some(checkpoint) => {
+                let mut pool = LpPool::from_snapshot(&checkpoint.snapshot)?;
+                pool.enable_op_log();
+                (pool, checkpoint.op_index)
+            }
+            None => (self.config.build(), 0),
+        };
+        pool.apply_logged(&self.ops[first_op_index - self.first_op_index..])?;
+        pool.take_op_log();
+        Ok(pool)
+    }
+
+    /// Returns total amount of operations recorded, including compacted ones
+    pub fn op_count(&self) -> usize {
+        self.first_op_index + self.ops.len()
+    }
+
+    /// Returns operations kept in the log
+    pub fn ops(&self) -> &[PoolOp] {
+        &self.ops
+    }
+
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::governance::PoolParams;
+    use crate::types::*;
+
+    fn config() -> PoolConfig {
+        PoolConfig::new(
+            1.5.into(),
+            PoolParams {
+                min_fee: 0.001.into(),
+                max_fee: 0.09.into(),
+                liquidity_target: 90.into(),
+            },
+        )
+    }
+
+    fn simulate(pool: &mut LpPool, log: &mut CheckpointLog, steps: u64) {
+        for step in 0..steps {
+            pool.swap(StakedTokenAmount::from_raw_amount(100_000 + step))
+                .unwrap();
+            pool.advance_epoch(1);
+            log.record(pool);
+        }
+    }
+
+    #[test]
+    fn restores_from_latest_checkpoint() {
+        let mut log = CheckpointLog::new(config(), 10);
+        let mut pool = config().build();
+        pool.add_liquidity(1000.into()).unwrap();
+        log.record(&mut pool);
+        simulate(&mut pool, &mut log, 12);
+
+        assert_eq!(log.op_count(), 25);
+        assert_eq!(log.checkpoints().len(), 2);
+        assert_eq!(log.restore().unwrap().state_hash(), pool.state_hash());
+    }
+
+    #[test]
+    fn compaction_keeps_state() {
+        let mut log = CheckpointLog::new(config(), 8);
+        let mut pool = config().build();
+        pool.add_liquidity(1000.into()).unwrap();
+        log.record(&mut pool);
+        simulate(&mut pool, &mut log, 9);
+
+        log.compact();
+        assert_eq!(log.checkpoints().len(), 1);
+        // checkpoints were taken after 9 and 17 of 19 operations
+        assert_eq!(log.checkpoints()[0].op_index, 17);
+        assert_eq!(log.ops().len(), 2);
+        assert_eq!(log.restore().unwrap().state_hash(), pool.state_hash());
+
+        simulate(&mut pool, &mut log, 3);
+        log.compact();
+        assert_eq!(log.restore().unwrap().state_hash(), pool.state_hash());
+    }
+}
@@ -0,0 +1,163 @@
+//! Columnar export of an operation log and per-step metrics for large simulation runs.
+//! Every column is a plain vector of raw fixed-point values, matching one-to-one the
+//! `UInt64` arrays of an Arrow record batch, so the data can be handed to an Arrow or
+//! Parquet writer without any per-row conversion.
+
+use crate::error::ReplayError;
+use crate::ops::PoolOp;
+use crate::replay::PoolConfig;
+use crate::types::*;
+
+/// names of the columns in the order they are returned by `OpColumns::columns`
+pub const COLUMN_NAMES: [&str; 10] = [
+    "step",
+    "op",
+    "account",
+    "amount",
+    "price",
+    "epoch",
+    "token_amount",
+    "st_token_amount",
+    "lp_token_amount",
+    "total_fees",
+];
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// Operations and pool metrics after every step stored column by column
+pub struct OpColumns {
+    pub step: Vec<u64>,
+    /// operation names, dictionary encoded into `&'static str`
+    pub op: Vec<&'static str>,
+    pub account: Vec<Option<AccountId>>,
+    /// raw argument of the operation, see `PoolOp::raw_amount`
+    pub amount: Vec<Uint>,
+    pub price: Vec<Uint>,
+    pub epoch: Vec<Epoch>,
+    pub token_amount: Vec<Uint>,
+    pub st_token_amount: Vec<Uint>,
+    pub lp_token_amount: Vec<Uint>,
+    pub total_fees: Vec<Uint>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Single column of `OpColumns`
+pub enum Column<'a> {
+    UInt64(&'a [u64]),
+    NullableUInt64(&'a [Option<u64>]),
+    Utf8(&'a [&'static str]),
+}
+
+impl OpColumns {
+    /// Replays operations on a pool built from the configuration and collects one row
+    /// per operation with metrics after it was applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - configuration the logged pool was created with
+    /// * `ops` - operations recorded by the pool's operation log
+    pub fn collect(config: &PoolConfig, ops: &[PoolOp]) -> Result<Self, ReplayError> {
+        let mut pool = config.build();
+        let mut columns = Self::with_capacity(ops.len());
+
+        for (index, op) in ops.iter().enumerate() {
+            pool.apply(op)
+                .map_err(|error| ReplayError { index, error })?;
+            columns.step.push(index as u64 + 1);
+            columns.op.push(op.name());
+            columns.account.push(op.account());
+            columns.amount.push(op.raw_amount());
+            columns.price.push(pool.price().raw());
+            columns.epoch.push(pool.epoch());
+            columns.token_amount.push(pool.token_amount().raw());
+            columns.st_token_amount.push(pool.st_token_amount().raw());
+            columns.lp_token_amount.push(pool.lp_token_amount().raw());
+            columns.total_fees.push(pool.fee_revenue().total.raw());
+        }
+        Ok(columns)
+    }
+
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            step: Vec::with_capacity(capacity),
+            op: Vec::with_capacity(capacity),
+            account: Vec::with_capacity(capacity),
+            amount: Vec::with_capacity(capacity),
+            price: Vec::with_capacity(capacity),
+            epoch: Vec::with_capacity(capacity),
+            token_amount: Vec::with_capacity(capacity),
+            st_token_amount: Vec::with_capacity(capacity),
+            lp_token_amount: Vec::with_capacity(capacity),
+            total_fees: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns number of rows
+    pub fn len(&self) -> usize {
+        self.step.len()
+    }
+
+    /// Returns true if there are no rows
+    pub fn is_empty(&self) -> bool {
+        self.step.is_empty()
+    }
+
+    /// Returns columns in the order of `COLUMN_NAMES`
+    pub fn columns(&self) -> [Column<'_>; 10] {
+        [
+            Column::UInt64(&self.step),
+            Column::Utf8(&self.op),
+            Column::NullableUInt64(&self.account),
+            Column::UInt64(&self.amount),
+            Column::UInt64(&self.price),
+            Column::UInt64(&self.epoch),
+            Column::UInt64(&self.token_amount),
+            Column::UInt64(&self.st_token_amount),
+            Column::UInt64(&self.lp_token_amount),
+            Column::UInt64(&self.total_fees),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::governance::PoolParams;
+
+    #[test]
+    fn collects_columns() {
+        let config = PoolConfig::new(
+            1.5.into(),
+            PoolParams {
+                min_fee: 0.001.into(),
+                max_fee: 0.09.into(),
+                liquidity_target: 90.into(),
+            },
+        );
+        let ops = [
+            PoolOp::AddLiquidity {
+                account: Some(7),
+                amount: 100.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 6.into(),
+            },
+            PoolOp::AdvanceEpoch { epochs: 2 },
+        ];
+        let columns = OpColumns::collect(&config, &ops).unwrap();
+
+        assert_eq!(columns.len(), 3);
+        assert_eq!(columns.op, vec!["add_liquidity", "swap", "advance_epoch"]);
+        assert_eq!(columns.account, vec![Some(7), None, None]);
+        assert_eq!(columns.amount, vec![100_000_000, 6_000_000, 2]);
+        assert_eq!(columns.epoch, vec![0, 0, 2]);
+        assert_eq!(columns.token_amount[1], 91_009_000);
+        assert!(columns.columns().iter().all(|column| match column {
+            Column::UInt64(values) => values.len() == 3,
+            Column::NullableUInt64(values) => values.len() == 3,
+            Column::Utf8(values) => values.len() == 3,
+        }));
+
+        assert_eq!(OpColumns::collect(&config, &ops[1..]).unwrap_err().index, 0);
+    }
+}
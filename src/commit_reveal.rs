@@ -0,0 +1,188 @@
+//! Two-phase swaps for studying front-running mitigations. A swapper first commits to the
+//! BLAKE3 hash of the swap amount and a salt and reveals both once `delay` epochs passed,
+//! only then the swap is executed with the regular pool math. Between the phases other
+//! operations, front-running ones included, can be applied to the same pool.
+
+use std::collections::BTreeMap;
+
+use crate::blake3;
+use crate::error::{CommitRevealError, OpError};
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::types::*;
+
+/// Returns commitment to a swap of `amount` staked tokens, hash of the raw amount as 8 byte
+/// little endian followed by the salt
+pub fn swap_commitment(amount: StakedTokenAmount, salt: &[u8]) -> [u8; 32] {
+    let mut bytes = amount.raw().to_le_bytes().to_vec();
+    bytes.extend_from_slice(salt);
+    blake3::hash(&bytes)
+}
+
+#[derive(Debug)]
+/// Pool executing swaps committed to at least `delay` epochs before they're revealed
+pub struct CommitRevealPool {
+    pool: LpPool,
+    delay: Epoch,
+    /// epochs at which unrevealed commitments were made
+    commitments: BTreeMap<[u8; 32], Epoch>,
+}
+
+impl CommitRevealPool {
+    pub fn new(pool: LpPool, delay: Epoch) -> Self {
+        Self {
+            pool,
+            delay,
+            commitments: BTreeMap::new(),
+        }
+    }
+
+    pub fn pool(&self) -> &LpPool {
+        &self.pool
+    }
+
+    /// Returns the wrapped pool, dropping unrevealed commitments
+    pub fn into_inner(self) -> LpPool {
+        self.pool
+    }
+
+    pub fn delay(&self) -> Epoch {
+        self.delay
+    }
+
+    /// Returns epoch at which the commitment was made, if it wasn't revealed yet
+    pub fn committed_at(&self, hash: &[u8; 32]) -> Option<Epoch> {
+        self.commitments.get(hash).copied()
+    }
+
+    /// Applies operation to the pool without a commitment, e.g. a front-running swap
+    pub fn apply(&mut self, op: &PoolOp) -> Result<OpOutcome, OpError> {
+        self.pool.apply(op)
+    }
+
+    /// Records commitment created with `swap_commitment` and returns epoch from which on
+    /// it can be revealed, capped at `Epoch::MAX` like the pool clock
+    pub fn commit(&mut self, hash: [u8; 32]) -> Result<Epoch, CommitRevealError> {
+        if self.commitments.contains_key(&hash) {
+            return Err(CommitRevealError::DuplicateCommitment);
+        }
+        let epoch = self.pool.epoch();
+        self.commitments.insert(hash, epoch);
+        Ok(epoch.saturating_add(self.delay))
+    }
+
+    /// Executes committed swap and returns tokens granted to the caller. Commitments of
+    /// failed swaps are kept, so they can be revealed again.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - amount of staked tokens committed to
+    /// * `salt` - salt the commitment was created with
+    pub fn reveal(
+        &mut self,
+        amount: StakedTokenAmount,
+        salt: &[u8],
+    ) -> Result<TokenAmount, CommitRevealError> {
+        let hash = swap_commitment(amount, salt);
+        let committed_at = self
+            .committed_at(&hash)
+            .ok_or(CommitRevealError::UnknownCommitment)?;
+        let epoch = self.pool.epoch();
+        let revealable_at = committed_at.saturating_add(self.delay);
+        if epoch < revealable_at {
+            return Err(CommitRevealError::TooEarly {
+                revealable_at,
+                epoch,
+            });
+        }
+        let amount_out = self.pool.swap(amount)?;
+        self.commitments.remove(&hash);
+        Ok(amount_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::SwapError;
+
+    fn pool() -> LpPool {
+        let mut pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        pool.add_liquidity(100.into()).unwrap();
+        pool
+    }
+
+    #[test]
+    fn reveals_swaps_after_delay() {
+        let mut pool = CommitRevealPool::new(pool(), 2);
+        let hash = swap_commitment(6.into(), b"salt");
+        assert_eq!(pool.commit(hash).unwrap(), 2);
+        assert!(matches!(
+            pool.commit(hash),
+            Err(CommitRevealError::DuplicateCommitment)
+        ));
+        assert!(matches!(
+            pool.reveal(6.into(), b"salt"),
+            Err(CommitRevealError::TooEarly {
+                revealable_at: 2,
+                epoch: 0
+            })
+        ));
+
+        pool.apply(&PoolOp::AdvanceEpoch { epochs: 2 }).unwrap();
+        assert!(matches!(
+            pool.reveal(6.into(), b"pepper"),
+            Err(CommitRevealError::UnknownCommitment)
+        ));
+        assert!(matches!(
+            pool.reveal(7.into(), b"salt"),
+            Err(CommitRevealError::UnknownCommitment)
+        ));
+        assert_eq!(pool.reveal(6.into(), b"salt").unwrap(), 8.991.into());
+        assert_eq!(pool.committed_at(&hash), None);
+    }
+
+    #[test]
+    fn reveals_commitments_near_end_of_clock() {
+        let mut pool = CommitRevealPool::new(pool(), 3);
+        pool.apply(&PoolOp::AdvanceEpoch {
+            epochs: Epoch::MAX - 1,
+        })
+        .unwrap();
+        let hash = swap_commitment(6.into(), b"salt");
+        assert_eq!(pool.commit(hash).unwrap(), Epoch::MAX);
+        assert!(matches!(
+            pool.reveal(6.into(), b"salt"),
+            Err(CommitRevealError::TooEarly {
+                revealable_at: Epoch::MAX,
+                ..
+            })
+        ));
+
+        // the clock stops at the last epoch, which reveals everything committed before
+        pool.apply(&PoolOp::AdvanceEpoch { epochs: 5 }).unwrap();
+        assert_eq!(pool.pool().epoch(), Epoch::MAX);
+        assert!(pool.reveal(6.into(), b"salt").is_ok());
+    }
+
+    #[test]
+    fn keeps_commitment_of_failed_reveal() {
+        let mut pool = CommitRevealPool::new(pool(), 0);
+        pool.commit(swap_commitment(60.into(), b"salt")).unwrap();
+        // front-running swap leaves too little liquidity for the committed one
+        pool.apply(&PoolOp::Swap {
+            account: None,
+            amount: 60.into(),
+        })
+        .unwrap();
+        assert!(matches!(
+            pool.reveal(60.into(), b"salt"),
+            Err(CommitRevealError::Swap(
+                SwapError::PoolNotEnoughTokens { .. }
+            ))
+        ));
+        assert!(pool
+            .committed_at(&swap_commitment(60.into(), b"salt"))
+            .is_some());
+    }
+}
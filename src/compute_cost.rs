@@ -0,0 +1,328 @@
+//! Approximate on-chain execution cost of pool operations, so pool designs can be
+//! compared by what they cost to run on Solana and not only economically. Every operation
+//! is mapped to the compute units its instruction would consume and to the accounts it
+//! would lock. Costs are built from per-step estimates of a `CostModel`: the instruction
+//! overhead, token program CPIs, fixed-point arithmetic and the work of optional features
+//! like fee policies, price smoothing or swap surcharges.
+//!
+//! Default estimates are ballpark figures of an Anchor program, they are meant for
+//! relative comparison of variants, calibrate them against a deployed program for
+//! absolute numbers.
+
+use crate::fee_policy::FeePolicy;
+use crate::json::Value;
+use crate::lp_pool::LpPool;
+use crate::ops::PoolOp;
+use crate::price_smoothing::PriceSmoothing;
+use crate::replay::PoolConfig;
+use crate::schema::ToJson;
+
+/// compute units a single instruction may consume without requesting a higher limit
+pub const DEFAULT_COMPUTE_UNIT_LIMIT: u64 = 200_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Execution cost of a single instruction
+pub struct ComputeCost {
+    pub compute_units: u64,
+    /// accounts passed to the instruction, programs included
+    pub accounts: usize,
+    /// accounts locked for writing, they serialize transactions touching the pool
+    pub writable_accounts: usize,
+}
+
+impl ComputeCost {
+    fn add(&mut self, compute_units: u64, accounts: usize, writable_accounts: usize) {
+        self.compute_units += compute_units;
+        self.accounts += accounts;
+        self.writable_accounts += writable_accounts;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Compute units of the steps operations are built from
+pub struct CostModel {
+    /// instruction dispatch, account deserialization and validation of the pool account
+    pub instruction: u64,
+    /// serialization of the modified pool account
+    pub pool_write: u64,
+    /// single token transfer, mint or burn CPI into the token program
+    pub token_cpi: u64,
+    /// single widened fixed-point multiplication or division
+    pub fixed_point_op: u64,
+    /// deserialization and staleness check of the oracle price account
+    pub oracle_read: u64,
+    /// volatility computation per price history sample
+    pub volatility_sample: u64,
+    /// update of a liquidity position account
+    pub position_update: u64,
+    /// write of a price history and TWAP checkpoint
+    pub checkpoint: u64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            instruction: 6_000,
+            pool_write: 1_500,
+            token_cpi: 4_500,
+            fixed_point_op: 120,
+            oracle_read: 2_500,
+            volatility_sample: 350,
+            position_update: 3_000,
+            checkpoint: 1_200,
+        }
+    }
+}
+
+impl CostModel {
+    /// Returns cost of applying the operation to the pool configured by `config`, whether
+    /// it would succeed or not. Pool state sizes the work of state dependent steps like
+    /// the volatility computation.
+    pub fn estimate(&self, config: &PoolConfig, pool: &LpPool, op: &PoolOp) -> ComputeCost {
+        let mut cost = ComputeCost::default();
+        // pool account and the signer paying for the transaction
+        cost.add(self.instruction + self.pool_write, 2, 2);
+        let math = |ops: u64| ops * self.fixed_point_op;
+        match *op {
+            PoolOp::AddLiquidity { account, .. } => {
+                // transfer in and mint of lp tokens, user token, vault, lp mint, user lp
+                // accounts and the token program
+                cost.add(2 * self.token_cpi + math(4), 5, 4);
+                if account.is_some() {
+                    cost.add(self.position_update + math(2), 1, 1);
+                }
+            }
+            PoolOp::RemoveLiquidity { account, .. } => {
+                // burn of lp tokens and transfers of both legs, lp mint, user lp, both
+                // vaults, both user accounts and the token program
+                cost.add(3 * self.token_cpi + math(6), 7, 6);
+                if account.is_some() {
+                    cost.add(self.position_update + math(4), 1, 1);
+                }
+            }
+            PoolOp::Swap { .. } => {
+                // transfers of both legs, both vaults, both user accounts, token program
+                // and oracle
+                cost.add(2 * self.token_cpi + self.oracle_read + math(10), 6, 4);
+                if config.treasury_cut.raw() > 0 {
+                    cost.add(self.token_cpi + math(2), 1, 1);
+                }
+                if let FeePolicy::VolatilitySensitive { .. } = config.fee_policy {
+                    let samples = pool.price_history().len() as u64;
+                    cost.add(self.volatility_sample * samples + math(2), 0, 0);
+                }
+                if config.surcharge.is_some() {
+                    cost.add(math(6), 0, 0);
+                }
+                cost.add(self.checkpoint, 0, 0);
+            }
+            PoolOp::SetPrice { .. } => {
+                cost.add(self.oracle_read + self.checkpoint, 1, 0);
+                if config.max_price_deviation.is_some() {
+                    cost.add(math(2), 0, 0);
+                }
+                if let PriceSmoothing::Ema { .. } = config.price_smoothing {
+                    cost.add(math(3), 0, 0);
+                }
+            }
+            PoolOp::AdvanceEpoch { .. } => {
+                // clock sysvar read and TWAP accumulation
+                cost.add(self.checkpoint + math(2), 1, 0);
+            }
+            // marks a mutation done outside operations, no instruction replays it
+            PoolOp::Unreplayable => {}
+        }
+        cost
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// Distribution of costs of a single kind of operation
+pub struct OpCostSummary {
+    pub count: usize,
+    pub total_compute_units: u64,
+    pub max_compute_units: u64,
+    /// most accounts locked for writing by a single operation
+    pub max_writable_accounts: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Costs of an operation stream
+pub struct CostReport {
+    /// cost of every operation in order
+    pub costs: Vec<ComputeCost>,
+    pub total_compute_units: u64,
+    /// operations exceeding `DEFAULT_COMPUTE_UNIT_LIMIT`
+    pub over_limit: usize,
+    /// summaries keyed by operation name, in order of first occurrence
+    pub by_op: Vec<(&'static str, OpCostSummary)>,
+}
+
+/// Replays operations on a pool built from the configuration and estimates cost of every
+/// one of them with the model
+pub fn estimate_costs(model: &CostModel, config: &PoolConfig, ops: &[PoolOp]) -> CostReport {
+    let mut pool = config.build();
+    let mut report = CostReport {
+        costs: Vec::with_capacity(ops.len()),
+        total_compute_units: 0,
+        over_limit: 0,
+        by_op: Vec::new(),
+    };
+    for op in ops {
+        let cost = model.estimate(config, &pool, op);
+        let _ = pool.apply(op);
+
+        report.total_compute_units += cost.compute_units;
+        if cost.compute_units > DEFAULT_COMPUTE_UNIT_LIMIT {
+            report.over_limit += 1;
+        }
+        let index = match report.by_op.iter().position(|(name, _)| *name == op.name()) {
+            Some(index) => index,
+            None => {
+                report.by_op.push((op.name(), OpCostSummary::default()));
+                report.by_op.len() - 1
+            }
+        };
+        let summary = &mut report.by_op[index].1;
+        summary.count += 1;
+        summary.total_compute_units += cost.compute_units;
+        summary.max_compute_units = summary.max_compute_units.max(cost.compute_units);
+        summary.max_writable_accounts = summary.max_writable_accounts.max(cost.writable_accounts);
+        report.costs.push(cost);
+    }
+    report
+}
+
+impl ToJson for OpCostSummary {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("count", self.count.to_json()),
+            ("total_compute_units", self.total_compute_units.to_json()),
+            ("max_compute_units", self.max_compute_units.to_json()),
+            (
+                "max_writable_accounts",
+                self.max_writable_accounts.to_json(),
+            ),
+        ])
+    }
+}
+
+impl ToJson for CostReport {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("ops", self.costs.len().to_json()),
+            ("total_compute_units", self.total_compute_units.to_json()),
+            ("over_limit", self.over_limit.to_json()),
+            (
+                "by_op",
+                Value::object(
+                    self.by_op
+                        .iter()
+                        .map(|(name, summary)| (*name, summary.to_json())),
+                ),
+            ),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixture_config;
+
+    fn ops() -> Vec<PoolOp> {
+        let mut ops = vec![PoolOp::AddLiquidity {
+            account: Some(1),
+            amount: 100.into(),
+        }];
+        for price in [1.5, 1.6, 1.4, 1.55] {
+            ops.push(PoolOp::SetPrice {
+                price: price.into(),
+            });
+            ops.push(PoolOp::Swap {
+                account: None,
+                amount: 2.into(),
+            });
+        }
+        ops.push(PoolOp::RemoveLiquidity {
+            account: None,
+            lp_amount: 10.into(),
+        });
+        ops
+    }
+
+    #[test]
+    fn estimates_operations() {
+        let model = CostModel::default();
+        let config = fixture_config();
+        let pool = config.build();
+        let deposit = PoolOp::AddLiquidity {
+            account: None,
+            amount: 1.into(),
+        };
+        let anonymous = model.estimate(&config, &pool, &deposit);
+        let tracked = model.estimate(
+            &config,
+            &pool,
+            &PoolOp::AddLiquidity {
+                account: Some(1),
+                amount: 1.into(),
+            },
+        );
+        assert_eq!(tracked.accounts, anonymous.accounts + 1);
+        assert!(tracked.compute_units > anonymous.compute_units);
+
+        let swap = model.estimate(
+            &config,
+            &pool,
+            &PoolOp::Swap {
+                account: None,
+                amount: 1.into(),
+            },
+        );
+        let epoch = model.estimate(&config, &pool, &PoolOp::AdvanceEpoch { epochs: 1 });
+        assert!(swap.compute_units > epoch.compute_units);
+        assert!(swap.compute_units < DEFAULT_COMPUTE_UNIT_LIMIT);
+    }
+
+    #[test]
+    fn compares_variants() {
+        let model = CostModel::default();
+        let linear = fixture_config();
+        let mut volatile = fixture_config();
+        volatile.fee_policy = FeePolicy::VolatilitySensitive {
+            sensitivity: 2.0.into(),
+            max_surcharge: 0.05.into(),
+        };
+        volatile.treasury_cut = 0.1.into();
+
+        let (linear, volatile) = (
+            estimate_costs(&model, &linear, &ops()),
+            estimate_costs(&model, &volatile, &ops()),
+        );
+        assert_eq!(linear.costs.len(), 10);
+        assert_eq!(linear.over_limit, 0);
+        assert!(volatile.total_compute_units > linear.total_compute_units);
+
+        let names: Vec<_> = linear.by_op.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            ["add_liquidity", "set_price", "swap", "remove_liquidity"]
+        );
+        let swaps = |report: &CostReport| report.by_op[2].1.clone();
+        assert_eq!(swaps(&linear).count, 4);
+        assert_eq!(
+            swaps(&linear).max_compute_units * 4,
+            swaps(&linear).total_compute_units
+        );
+        // volatility is computed over a growing price history
+        assert!(volatile.costs[8].compute_units > volatile.costs[2].compute_units);
+        assert_eq!(
+            swaps(&volatile).max_writable_accounts,
+            swaps(&linear).max_writable_accounts + 1
+        );
+
+        let json = volatile.to_json();
+        assert!(json.get("by_op").unwrap().get("swap").is_some());
+    }
+}
@@ -0,0 +1,280 @@
+//! Loading of `PoolConfig` from TOML or YAML files, so simulation setups can live next to
+//! the data instead of in code. Only flat documents of scalar `key = value` (TOML) or
+//! `key: value` (YAML) pairs are supported:
+//!
+//! ```toml
+//! price = 1.25
+//! # amounts below are given in native units with 9 decimals
+//! decimals = 9
+//! liquidity_target = 90_000_000_000
+//! min_fee_bps = 10
+//! max_fee_bps = 900
+//! treasury_cut_bps = 2500
+//! insurance_cut_bps = 500
+//! max_price_age = 5
+//! max_price_deviation_bps = 1000
+//! monotonic_price = false
+//! ```
+//!
+//! Only `price`, `liquidity_target`, `min_fee_bps` and `max_fee_bps` are required.
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::ConfigError;
+use crate::governance::PoolParams;
+use crate::replay::PoolConfig;
+use crate::types::*;
+
+/// fees are configured in basis points
+const BASIS_POINTS: Uint = 10_000;
+/// biggest supported amount of decimals of configured amounts
+const MAX_DECIMALS: i32 = 18;
+
+/// Scalar value of a configuration key together with the line it was read from
+struct Entry {
+    line: usize,
+    key: String,
+    value: String,
+}
+
+impl PoolConfig {
+    /// Parses and validates configuration in the TOML format
+    pub fn from_toml(input: &str) -> Result<PoolConfig, ConfigError> {
+        Self::from_entries(parse(input, '=')?)
+    }
+
+    /// Parses and validates configuration in the YAML format
+    pub fn from_yaml(input: &str) -> Result<PoolConfig, ConfigError> {
+        Self::from_entries(parse(input, ':')?)
+    }
+
+    /// Loads configuration from a `.toml`, `.yaml` or `.yml` file
+    pub fn load(path: impl AsRef<Path>) -> Result<PoolConfig, ConfigError> {
+        let path = path.as_ref();
+        let input = fs::read_to_string(path)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&input),
+            Some("yaml" | "yml") => Self::from_yaml(&input),
+            _ => Err(ConfigError::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+
+    fn from_entries(entries: Vec<Entry>) -> Result<PoolConfig, ConfigError> {
+        let mut decimals = 0;
+        if let Some(entry) = entries.iter().find(|entry| entry.key == "decimals") {
+            decimals = integer(entry)?;
+            if decimals > MAX_DECIMALS as u64 {
+                return Err(invalid(entry, "too many decimals"));
+            }
+        }
+        let get = |key: &str| entries.iter().find(|entry| entry.key == key);
+        let require = |key: &str| get(key).ok_or_else(|| ConfigError::MissingKey(key.into()));
+
+        let price_entry = require("price")?;
+        let price = Price::from_raw_amount(fixed(price_entry, 0)?);
+        if price.raw() == 0 {
+            return Err(invalid(price_entry, "price has to be greater than zero"));
+        }
+        let params = PoolParams {
+            min_fee: bps(require("min_fee_bps")?)?,
+            max_fee: bps(require("max_fee_bps")?)?,
+            liquidity_target: TokenAmount::from_raw_amount(fixed(
+                require("liquidity_target")?,
+                decimals as i32,
+            )?),
+        };
+        params.validate()?;
+
+        let mut config = PoolConfig::new(price, params);
+        for entry in &entries {
+            match entry.key.as_str() {
+                "price" | "decimals" | "liquidity_target" | "min_fee_bps" | "max_fee_bps" => {}
+                "treasury_cut_bps" => config.treasury_cut = bps(entry)?,
+                "insurance_cut_bps" => config.insurance_cut = bps(entry)?,
+                "max_price_age" => config.max_price_age = Some(integer(entry)?),
+                "max_price_deviation_bps" => config.max_price_deviation = Some(bps(entry)?),
+                "monotonic_price" => {
+                    config.monotonic_price = match entry.value.as_str() {
+                        "true" => true,
+                        "false" => false,
+                        _ => return Err(invalid(entry, "expected bool")),
+                    }
+                }
+                _ => return Err(ConfigError::UnknownKey(entry.key.clone())),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Splits document into scalar entries, `separator` is `=` for TOML and `:` for YAML
+fn parse(input: &str, separator: char) -> Result<Vec<Entry>, ConfigError> {
+    let mut entries: Vec<Entry> = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        let line_number = index + 1;
+        let syntax = |reason: &str| ConfigError::Syntax {
+            line: line_number,
+            reason: reason.into(),
+        };
+        let line = strip_comment(line).trim();
+        if line.is_empty() || (separator == ':' && line == "---") {
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once(separator)
+            .ok_or_else(|| syntax("expected key and value"))?;
+        let key = key.trim();
+        let key = key
+            .strip_prefix('"')
+            .and_then(|key| key.strip_suffix('"'))
+            .unwrap_or(key);
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || char == '_')
+        {
+            return Err(syntax("invalid key"));
+        }
+        let value = value.trim();
+        let value = match value.strip_prefix('"') {
+            Some(quoted) => quoted
+                .strip_suffix('"')
+                .ok_or_else(|| syntax("unterminated string"))?,
+            None if value.is_empty() => return Err(syntax("missing value")),
+            None => value,
+        };
+        if entries.iter().any(|entry| entry.key == key) {
+            return Err(syntax("duplicate key"));
+        }
+        entries.push(Entry {
+            line: line_number,
+            key: key.into(),
+            value: value.into(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Removes `#` comment, unless it's inside of a quoted string
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (index, char) in line.char_indices() {
+        match char {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn invalid(entry: &Entry, reason: &str) -> ConfigError {
+    ConfigError::InvalidValue {
+        line: entry.line,
+        key: entry.key.clone(),
+        reason: reason.into(),
+    }
+}
+
+fn integer(entry: &Entry) -> Result<u64, ConfigError> {
+    entry
+        .value
+        .replace('_', "")
+        .parse()
+        .map_err(|_| invalid(entry, "expected integer"))
+}
+
+/// Parses decimal literal given with `decimals` decimals into raw fixed-point value
+fn fixed(entry: &Entry, decimals: i32) -> Result<Uint, ConfigError> {
+    let literal = entry.value.replace('_', "");
+    let (whole, fraction) = literal.split_once('.').unwrap_or((&literal, ""));
+    let digits = format!("{whole}{fraction}");
+    if digits.is_empty() || !digits.chars().all(|char| char.is_ascii_digit()) {
+        return Err(invalid(entry, "expected decimal number"));
+    }
+    let mantissa = digits
+        .parse()
+        .map_err(|_| invalid(entry, "number is too big"))?;
+    Price::from_decimal(mantissa, -(fraction.len() as i32) - decimals)
+        .map(|price| price.raw())
+        .ok_or_else(|| invalid(entry, "number is too big"))
+}
+
+fn bps(entry: &Entry) -> Result<Percentage, ConfigError> {
+    let bps = integer(entry)?;
+    if bps > BASIS_POINTS {
+        return Err(invalid(entry, "basis points can't exceed 10000"));
+    }
+    Ok(Percentage::from_raw_amount(bps * SCALE / BASIS_POINTS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"
+        # mainnet like setup
+        price = 1.25
+        decimals = 9
+        liquidity_target = 90_000_000_000
+        min_fee_bps = 10
+        max_fee_bps = 900
+        treasury_cut_bps = 2500 # a quarter
+        insurance_cut_bps = 500
+        max_price_age = 5
+        "monotonic_price" = true
+    "#;
+
+    #[test]
+    fn loads_toml_and_yaml() {
+        let config = PoolConfig::from_toml(TOML).unwrap();
+        assert_eq!(config.price, Price::from(1.25));
+        assert_eq!(config.params.liquidity_target, TokenAmount::from(90));
+        assert_eq!(config.params.min_fee, Percentage::from(0.001));
+        assert_eq!(config.params.max_fee, Percentage::from(0.09));
+        assert_eq!(config.treasury_cut, Percentage::from(0.25));
+        assert_eq!(config.insurance_cut, Percentage::from(0.05));
+        assert_eq!(config.max_price_age, Some(5));
+        assert!(config.monotonic_price);
+        assert_eq!(config.max_price_deviation, None);
+
+        let yaml = TOML.replace(" = ", ": ").replace("\"", "");
+        assert_eq!(
+            PoolConfig::from_yaml(&format!("---\n{yaml}")).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn validates_config() {
+        let without = |key: &str| {
+            TOML.lines()
+                .filter(|line| !line.contains(key))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        assert!(matches!(
+            PoolConfig::from_toml(&without("min_fee_bps")),
+            Err(ConfigError::MissingKey(key)) if key == "min_fee_bps"
+        ));
+        assert!(matches!(
+            PoolConfig::from_toml(&format!("{TOML}\nfee = 1")),
+            Err(ConfigError::UnknownKey(key)) if key == "fee"
+        ));
+        assert!(matches!(
+            PoolConfig::from_toml(&TOML.replace("= 900", "= 1")),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            PoolConfig::from_toml(&TOML.replace("= 1.25", "= 1.2.5")),
+            Err(ConfigError::InvalidValue { line: 3, .. })
+        ));
+        assert!(matches!(
+            PoolConfig::from_toml("price 1"),
+            Err(ConfigError::Syntax { line: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,339 @@
+use crate::error::{ConservationError, OpError};
+use crate::json::Value;
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::schema::ToJson;
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Balances of the pool relevant for value conservation
+pub struct Balances {
+    pub tokens: TokenAmount,
+    pub staked_tokens: StakedTokenAmount,
+    pub lp_tokens: LpTokenAmount,
+    pub price: Price,
+    /// treasury and insurance fees ever removed from pool liquidity
+    pub treasury_fees: TokenAmount,
+}
+
+impl Balances {
+    /// Returns raw total value of tokens and staked tokens, widened so it never overflows
+    pub fn total_value(&self) -> u128 {
+        self.tokens.raw() as u128
+            + self.staked_tokens.raw() as u128 * self.price.raw() as u128 / SCALE as u128
+    }
+}
+
+impl LpPool {
+    /// Returns balances used by the conservation validator
+    pub fn balances(&self) -> Balances {
+        Balances {
+            tokens: self.token_amount(),
+            staked_tokens: self.st_token_amount(),
+            lp_tokens: self.lp_token_amount(),
+            price: self.price(),
+            treasury_fees: self.fee_revenue().treasury + self.fee_revenue().insurance,
+        }
+    }
+}
+
+impl ToJson for Balances {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("tokens", self.tokens.to_json()),
+            ("staked_tokens", self.staked_tokens.to_json()),
+            ("lp_tokens", self.lp_tokens.to_json()),
+            ("price", self.price.to_json()),
+            ("treasury_fees", self.treasury_fees.to_json()),
+        ])
+    }
+}
+
+/// Checks that a single operation conserved value. Tokens leaving the pool plus tokens
+/// retained by it have to match what was in the pool plus what came in, failed operations
+/// can't change any balance, and value of existing lp tokens can only grow via LP fees
+/// (price changes are treated as yield). Rounding gains of at most one raw lp token worth
+/// of value are tolerated.
+///
+/// # Arguments
+///
+/// * `step` - index of the operation, reported in errors
+/// * `before` - balances before the operation
+/// * `op` - applied operation
+/// * `outcome` - result returned by the pool
+/// * `after` - balances after the operation
+pub fn check_step(
+    step: usize,
+    before: &Balances,
+    op: &PoolOp,
+    outcome: &Result<OpOutcome, OpError>,
+    after: &Balances,
+) -> Result<(), ConservationError> {
+    let Ok(outcome) = outcome else {
+        // operations refresh price from the oracle before they can fail
+        if *before
+            != (Balances {
+                price: before.price,
+                ..*after
+            })
+        {
+            return Err(ConservationError::FailedOpChangedState { step });
+        }
+        return Ok(());
+    };
+
+    let expect = |holds: bool, error: ConservationError| match holds {
+        true => Ok(()),
+        false => Err(error),
+    };
+    // expected balances are `None` when they would have to be negative
+    let tokens = |expected: Option<u128>| {
+        expect(
+            Some(after.tokens.raw() as u128) == expected,
+            ConservationError::TokensNotConserved {
+                step,
+                expected: TokenAmount::from_raw_amount(
+                    expected.unwrap_or_default().min(Uint::MAX as u128) as Uint,
+                ),
+                actual: after.tokens,
+            },
+        )
+    };
+    let staked = |expected: Option<u128>| {
+        expect(
+            Some(after.staked_tokens.raw() as u128) == expected,
+            ConservationError::StakedTokensNotConserved { step },
+        )
+    };
+    let lp = |expected: Option<u128>| {
+        expect(
+            Some(after.lp_tokens.raw() as u128) == expected,
+            ConservationError::LpTokensNotConserved { step },
+        )
+    };
+    let Some(treasury_delta) = after
+        .treasury_fees
+        .raw()
+        .checked_sub(before.treasury_fees.raw())
+        .map(u128::from)
+    else {
+        return Err(ConservationError::UnexpectedStateChange { step });
+    };
+    let raw = |value: Uint| value as u128;
+
+    match (*op, *outcome) {
+        (PoolOp::AddLiquidity { amount, .. }, OpOutcome::LiquidityAdded(minted)) => {
+            tokens(Some(raw(before.tokens.raw()) + raw(amount.raw())))?;
+            staked(Some(raw(before.staked_tokens.raw())))?;
+            lp(Some(raw(before.lp_tokens.raw()) + raw(minted.raw())))?;
+            check_existing_lp_value(step, before, after, before.lp_tokens.raw() as u128, 0)
+        }
+        (PoolOp::RemoveLiquidity { lp_amount, .. }, OpOutcome::LiquidityRemoved(t, s)) => {
+            tokens(raw(before.tokens.raw()).checked_sub(raw(t.raw())))?;
+            staked(raw(before.staked_tokens.raw()).checked_sub(raw(s.raw())))?;
+            lp(raw(before.lp_tokens.raw()).checked_sub(raw(lp_amount.raw())))?;
+
+            let share = share_of(
+                before.total_value(),
+                lp_amount.raw() as u128,
+                (before.lp_tokens.raw() as u128).max(1),
+            );
+            let withdrawn =
+                t.raw() as u128 + s.raw() as u128 * before.price.raw() as u128 / SCALE as u128;
+            expect(
+                withdrawn <= share + 1,
+                ConservationError::ExcessWithdrawal { step },
+            )?;
+            check_existing_lp_value(step, before, after, after.lp_tokens.raw() as u128, 0)
+        }
+        (PoolOp::Swap { amount, .. }, OpOutcome::Swapped(amount_out)) => {
+            staked(Some(raw(before.staked_tokens.raw()) + raw(amount.raw())))?;
+            tokens(
+                raw(before.tokens.raw())
+                    .checked_sub(raw(amount_out.raw()))
+                    .and_then(|tokens| tokens.checked_sub(treasury_delta)),
+            )?;
+            lp(Some(raw(before.lp_tokens.raw())))?;
+
+            // swap could have refreshed the price, price change itself is yield so the
+            // state before the swap is valued at the price used by the swap
+            let before = Balances {
+                price: after.price,
+                ..*before
+            };
+            let value_in = raw(amount.into_token_amount(after.price).raw());
+            let Some(lp_fee) = value_in
+                .checked_sub(raw(amount_out.raw()))
+                .and_then(|fee| fee.checked_sub(treasury_delta))
+            else {
+                return Err(ConservationError::NegativeFee { step });
+            };
+            check_existing_lp_value(step, &before, after, before.lp_tokens.raw() as u128, lp_fee)
+        }
+        (PoolOp::SetPrice { price }, OpOutcome::PriceSet) => {
+            expect(
+                after.price == price && before.tokens == after.tokens,
+                ConservationError::UnexpectedStateChange { step },
+            )?;
+            staked(Some(raw(before.staked_tokens.raw())))?;
+            lp(Some(raw(before.lp_tokens.raw())))
+        }
+        (PoolOp::AdvanceEpoch { .. }, OpOutcome::EpochAdvanced) => expect(
+            before == after,
+            ConservationError::UnexpectedStateChange { step },
+        ),
+        _ => Err(ConservationError::MismatchedOutcome { step }),
+    }
+}
+
+/// Checks that value held by lp tokens existing both before and after the operation
+/// didn't grow by more than `allowed_gain` plus rounding
+fn check_existing_lp_value(
+    step: usize,
+    before: &Balances,
+    after: &Balances,
+    existing_lp: u128,
+    allowed_gain: u128,
+) -> Result<(), ConservationError> {
+    let before_lp = before.lp_tokens.raw() as u128;
+    let after_lp = after.lp_tokens.raw() as u128;
+    if existing_lp == 0 || before_lp == 0 || after_lp == 0 {
+        return Ok(());
+    }
+
+    let value_before = share_of(before.total_value(), existing_lp, before_lp);
+    let value_after = share_of(after.total_value(), existing_lp, after_lp);
+    // floor divisions in pool math can leave at most one raw lp token worth of dust, and
+    // floored withdrawals leave behind less than a raw token plus a raw staked token
+    let rounding = after.total_value().div_ceil(after_lp)
+        + (after.price.raw() as u128).div_ceil(SCALE as u128)
+        + 1;
+
+    if value_after > value_before + allowed_gain + rounding {
+        return Err(ConservationError::LpValueIncreased {
+            step,
+            before: TokenAmount::from_raw_amount(value_before.min(Uint::MAX as u128) as Uint),
+            after: TokenAmount::from_raw_amount(value_after.min(Uint::MAX as u128) as Uint),
+        });
+    }
+    Ok(())
+}
+
+/// Returns `value * part / whole` rounded down, split so it can't overflow for
+/// `part <= whole`
+fn share_of(value: u128, part: u128, whole: u128) -> u128 {
+    (value / whole).saturating_mul(part) + value % whole * part / whole
+}
+
+/// Applies operations to the pool one by one and checks conservation of every step.
+/// Returns outcomes of all operations if every step conserved value.
+///
+/// # Arguments
+///
+/// * `pool` - pool the operations are applied to
+/// * `ops` - operations to apply
+pub fn check_ops(
+    pool: &mut LpPool,
+    ops: &[PoolOp],
+) -> Result<Vec<Result<OpOutcome, OpError>>, ConservationError> {
+    let mut outcomes = Vec::with_capacity(ops.len());
+    for (step, op) in ops.iter().enumerate() {
+        let before = pool.balances();
+        let outcome = pool.apply(op);
+        check_step(step, &before, op, &outcome, &pool.balances())?;
+        outcomes.push(outcome);
+    }
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool() -> LpPool {
+        let mut pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        pool.set_treasury_cut(0.3.into());
+        pool
+    }
+
+    #[test]
+    fn story_example_conserves_value() {
+        let ops = [
+            PoolOp::AddLiquidity {
+                account: None,
+                amount: 100.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 6.into(),
+            },
+            PoolOp::AddLiquidity {
+                account: None,
+                amount: 10.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 30.into(),
+            },
+            PoolOp::SetPrice { price: 1.6.into() },
+            PoolOp::Swap {
+                account: None,
+                amount: 1000.into(),
+            },
+            PoolOp::RemoveLiquidity {
+                account: None,
+                lp_amount: 50.into(),
+            },
+        ];
+
+        let outcomes = check_ops(&mut pool(), &ops).unwrap();
+        assert!(outcomes[5].is_err(), "huge swap should fail");
+    }
+
+    #[test]
+    fn detects_created_tokens() {
+        let mut pool = pool();
+        let before = pool.balances();
+        let op = PoolOp::AddLiquidity {
+            account: None,
+            amount: 10.into(),
+        };
+        let outcome = pool.apply(&op);
+        let mut after = pool.balances();
+        after.tokens = after.tokens + TokenAmount::from_raw_amount(1);
+
+        assert!(matches!(
+            check_step(0, &before, &op, &outcome, &after),
+            Err(ConservationError::TokensNotConserved { .. })
+        ));
+    }
+
+    #[test]
+    fn detects_lp_value_increase() {
+        let mut pool = pool();
+        pool.add_liquidity(100.into()).unwrap();
+        let before = pool.balances();
+        let op = PoolOp::AddLiquidity {
+            account: None,
+            amount: 100.into(),
+        };
+        let fair = Ok(OpOutcome::LiquidityAdded(100.into()));
+        let after = Balances {
+            tokens: before.tokens + 100.into(),
+            lp_tokens: before.lp_tokens + 100.into(),
+            ..before
+        };
+        assert!(check_step(0, &before, &op, &fair, &after).is_ok());
+
+        // depositor gets only half of the fair lp amount, existing LPs gain value
+        let unfair = Ok(OpOutcome::LiquidityAdded(50.into()));
+        let after = Balances {
+            lp_tokens: before.lp_tokens + 50.into(),
+            ..after
+        };
+        assert!(matches!(
+            check_step(0, &before, &op, &unfair, &after),
+            Err(ConservationError::LpValueIncreased { .. })
+        ));
+    }
+}
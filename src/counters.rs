@@ -0,0 +1,134 @@
+use crate::error::SchemaError;
+use crate::json::Value;
+use crate::schema::{field, FromJson, ToJson};
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Lifetime operation counters and volumes of the pool, only successful operations are
+/// counted. Volumes saturate instead of overflowing.
+pub struct PoolCounters {
+    pub swaps: u64,
+    /// staked tokens ever swapped into the pool
+    pub swap_volume: StakedTokenAmount,
+    /// tokens ever paid out by swaps, after fees
+    pub swap_payout: TokenAmount,
+    /// all fees ever charged by swaps
+    pub fees: TokenAmount,
+    pub deposits: u64,
+    /// tokens ever deposited
+    pub deposited: TokenAmount,
+    pub withdrawals: u64,
+    /// tokens ever withdrawn
+    pub withdrawn_tokens: TokenAmount,
+    /// staked tokens ever withdrawn
+    pub withdrawn_staked_tokens: StakedTokenAmount,
+}
+
+fn add_tokens(a: TokenAmount, b: TokenAmount) -> TokenAmount {
+    TokenAmount::from_raw_amount(a.raw().saturating_add(b.raw()))
+}
+
+fn add_staked_tokens(a: StakedTokenAmount, b: StakedTokenAmount) -> StakedTokenAmount {
+    StakedTokenAmount::from_raw_amount(a.raw().saturating_add(b.raw()))
+}
+
+impl PoolCounters {
+    /// Records swap of `amount` staked tokens paying out `amount_out` tokens after
+    /// charging `fee_amount`
+    pub fn record_swap(
+        &mut self,
+        amount: StakedTokenAmount,
+        amount_out: TokenAmount,
+        fee_amount: TokenAmount,
+    ) {
+        self.swaps += 1;
+        self.swap_volume = add_staked_tokens(self.swap_volume, amount);
+        self.swap_payout = add_tokens(self.swap_payout, amount_out);
+        self.fees = add_tokens(self.fees, fee_amount);
+    }
+
+    /// Records deposit of `amount` tokens
+    pub fn record_deposit(&mut self, amount: TokenAmount) {
+        self.deposits += 1;
+        self.deposited = add_tokens(self.deposited, amount);
+    }
+
+    /// Records withdrawal paying out `tokens` and `staked_tokens`
+    pub fn record_withdrawal(&mut self, tokens: TokenAmount, staked_tokens: StakedTokenAmount) {
+        self.withdrawals += 1;
+        self.withdrawn_tokens = add_tokens(self.withdrawn_tokens, tokens);
+        self.withdrawn_staked_tokens =
+            add_staked_tokens(self.withdrawn_staked_tokens, staked_tokens);
+    }
+}
+
+impl ToJson for PoolCounters {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("swaps", self.swaps.to_json()),
+            ("swap_volume", self.swap_volume.to_json()),
+            ("swap_payout", self.swap_payout.to_json()),
+            ("fees", self.fees.to_json()),
+            ("deposits", self.deposits.to_json()),
+            ("deposited", self.deposited.to_json()),
+            ("withdrawals", self.withdrawals.to_json()),
+            ("withdrawn_tokens", self.withdrawn_tokens.to_json()),
+            (
+                "withdrawn_staked_tokens",
+                self.withdrawn_staked_tokens.to_json(),
+            ),
+        ])
+    }
+}
+
+impl FromJson for PoolCounters {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        Ok(Self {
+            swaps: field(value, "swaps")?,
+            swap_volume: field(value, "swap_volume")?,
+            swap_payout: field(value, "swap_payout")?,
+            fees: field(value, "fees")?,
+            deposits: field(value, "deposits")?,
+            deposited: field(value, "deposited")?,
+            withdrawals: field(value, "withdrawals")?,
+            withdrawn_tokens: field(value, "withdrawn_tokens")?,
+            withdrawn_staked_tokens: field(value, "withdrawn_staked_tokens")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulates_operations() {
+        let mut counters = PoolCounters::default();
+        counters.record_deposit(100.into());
+        counters.record_swap(6.into(), 8.991.into(), 0.009.into());
+        counters.record_swap(1.into(), 1.into(), 0.into());
+        counters.record_withdrawal(50.into(), 3.into());
+
+        assert_eq!(
+            (counters.swaps, counters.deposits, counters.withdrawals),
+            (2, 1, 1)
+        );
+        assert_eq!(counters.swap_volume, StakedTokenAmount::from(7));
+        assert_eq!(counters.swap_payout, TokenAmount::from(9.991));
+        assert_eq!(counters.fees, TokenAmount::from(0.009));
+        assert_eq!(counters.withdrawn_staked_tokens, StakedTokenAmount::from(3));
+        assert_eq!(
+            PoolCounters::from_json(&counters.to_json()).unwrap(),
+            counters
+        );
+    }
+
+    #[test]
+    fn volumes_saturate() {
+        let mut counters = PoolCounters::default();
+        counters.record_deposit(TokenAmount::from_raw_amount(Uint::MAX));
+        counters.record_deposit(1.into());
+        assert_eq!(counters.deposited.raw(), Uint::MAX);
+        assert_eq!(counters.deposits, 2);
+    }
+}
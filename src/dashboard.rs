@@ -0,0 +1,191 @@
+//! Terminal dashboard rendering live pool state: balances, position on the fee curve,
+//! recent operations and value of a single lp token over time. Frames are plain text
+//! with ANSI escapes, so they can be drawn by a simulation loop or a service on every
+//! update without any terminal library.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
+use crate::error::OpError;
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::types::*;
+
+/// clears screen and moves cursor to the top left corner
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// width of the fee curve gauge in characters
+const GAUGE_WIDTH: usize = 40;
+
+#[derive(Debug, Clone)]
+/// Dashboard keeping bounded history of operations and lp token values
+pub struct Dashboard {
+    capacity: usize,
+    recent_ops: VecDeque<(PoolOp, bool)>,
+    lp_values: VecDeque<Uint>,
+}
+
+impl Dashboard {
+    /// Creates dashboard showing up to `capacity` recent operations and lp values
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            recent_ops: VecDeque::new(),
+            lp_values: VecDeque::new(),
+        }
+    }
+
+    /// Records operation applied to the pool together with the pool state after it
+    pub fn record(&mut self, pool: &LpPool, op: &PoolOp, outcome: &Result<OpOutcome, OpError>) {
+        if self.recent_ops.len() == self.capacity {
+            self.recent_ops.pop_front();
+        }
+        self.recent_ops.push_back((*op, outcome.is_ok()));
+        self.observe(pool);
+    }
+
+    /// Records lp token value of the pool without any operation, e.g. on a timer
+    pub fn observe(&mut self, pool: &LpPool) {
+        if self.lp_values.len() == self.capacity {
+            self.lp_values.pop_front();
+        }
+        self.lp_values.push_back(lp_value(pool));
+    }
+
+    /// Renders single frame of the dashboard
+    pub fn render(&self, pool: &LpPool) -> String {
+        let params = pool.params();
+        let fee = pool.current_fee();
+        let fees = pool.fee_revenue();
+        let mut frame = vec![
+            format!("LP POOL — epoch {}   price {}", pool.epoch(), pool.price()),
+            String::new(),
+            format!("tokens         {}", pool.token_amount()),
+            format!("staked tokens  {}", pool.st_token_amount()),
+            format!("lp tokens      {}", pool.lp_token_amount()),
+            format!("total value    {}", pool.total_value()),
+            format!(
+                "liquidity      {} / {}",
+                pool.token_amount(),
+                params.liquidity_target
+            ),
+            format!(
+                "fee            {} [{}] {} – {}",
+                fee,
+                gauge(fee, params.min_fee, params.max_fee),
+                params.min_fee,
+                params.max_fee
+            ),
+            format!(
+                "fees collected {} ({} lp, {} treasury)",
+                fees.total, fees.lp, fees.treasury
+            ),
+            String::new(),
+            format!(
+                "lp value       {} {}",
+                TokenAmount::from_raw_amount(lp_value(pool)),
+                sparkline(&self.lp_values)
+            ),
+            String::new(),
+            "recent operations".to_string(),
+        ];
+        frame.extend(self.recent_ops.iter().rev().map(|(op, succeeded)| {
+            let status = match succeeded {
+                true => "ok",
+                false => "failed",
+            };
+            format!("  {:<17}{:>20}  {status}", op.name(), op.raw_amount())
+        }));
+        frame.join("\n")
+    }
+
+    /// Clears the terminal and draws a frame
+    pub fn draw(&self, pool: &LpPool, mut out: impl Write) -> io::Result<()> {
+        writeln!(out, "{CLEAR_SCREEN}{}", self.render(pool))?;
+        out.flush()
+    }
+}
+
+/// Returns raw token value of a single lp token
+fn lp_value(pool: &LpPool) -> Uint {
+    match pool.lp_token_amount().raw() {
+        0 => SCALE,
+        lp_tokens => (pool.total_value().raw() as u128 * SCALE as u128 / lp_tokens as u128) as Uint,
+    }
+}
+
+/// Returns bar showing where the fee is between min and max fee
+fn gauge(fee: Percentage, min_fee: Percentage, max_fee: Percentage) -> String {
+    let range = max_fee.raw().saturating_sub(min_fee.raw()).max(1);
+    let filled = (fee.raw().saturating_sub(min_fee.raw()) as u128 * GAUGE_WIDTH as u128
+        / range as u128)
+        .min(GAUGE_WIDTH as u128) as usize;
+    format!("{}{}", "#".repeat(filled), ".".repeat(GAUGE_WIDTH - filled))
+}
+
+/// Returns sparkline of values scaled between their minimum and maximum
+fn sparkline(values: &VecDeque<Uint>) -> String {
+    let (Some(min), Some(max)) = (values.iter().min(), values.iter().max()) else {
+        return String::new();
+    };
+    let range = (max - min).max(1) as u128;
+    values
+        .iter()
+        .map(|value| {
+            let level = (value - min) as u128 * (SPARK_LEVELS.len() - 1) as u128 / range;
+            SPARK_LEVELS[level as usize]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_pool_state() {
+        let mut pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        let mut dashboard = Dashboard::new(2);
+        for op in [
+            PoolOp::AddLiquidity {
+                account: None,
+                amount: 100.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 0.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 6.into(),
+            },
+        ] {
+            let outcome = pool.apply(&op);
+            dashboard.record(&pool, &op, &outcome);
+        }
+
+        let frame = dashboard.render(&pool);
+        assert!(frame.contains("staked tokens  6.000000"));
+        assert!(frame.contains(&format!("fee            {}", pool.current_fee())));
+        assert!(frame.contains("swap"));
+        assert!(frame.contains("failed"));
+        // capacity keeps only the two most recent operations
+        assert!(!frame.contains("add_liquidity"));
+        assert_eq!(sparkline(&dashboard.lp_values).chars().count(), 2);
+
+        let mut out = Vec::new();
+        dashboard.draw(&pool, &mut out).unwrap();
+        assert!(out.starts_with(CLEAR_SCREEN.as_bytes()));
+    }
+
+    #[test]
+    fn gauge_tracks_fee_curve() {
+        let (min, max) = (Percentage::from(0.01), Percentage::from(0.09));
+        assert_eq!(gauge(min, min, max), ".".repeat(GAUGE_WIDTH));
+        assert_eq!(gauge(max, min, max), "#".repeat(GAUGE_WIDTH));
+        assert_eq!(
+            gauge(0.05.into(), min, max).matches('#').count(),
+            GAUGE_WIDTH / 2
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Time-travel debugger over a sequence of operations. The debugger applies operations
+//! to a copy of the pool once, recording state after every one, so failing simulations
+//! can be walked through in both directions without replaying them. Position `0` is the
+//! state before the first operation and position `n` the state after the `n`-th.
+
+use crate::diff::{PoolDiff, PoolSnapshot};
+use crate::error::OpError;
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+
+#[derive(Debug)]
+/// Operation applied by the debugger with its result and the state it left
+pub struct Step {
+    pub op: PoolOp,
+    pub outcome: Result<OpOutcome, OpError>,
+    pub snapshot: PoolSnapshot,
+    /// full state encoded by `LpPool::to_snapshot`
+    state: Vec<u8>,
+}
+
+#[derive(Debug)]
+/// Recorded states of a pool after every operation with a cursor moving between them
+pub struct Debugger {
+    initial: (PoolSnapshot, Vec<u8>),
+    steps: Vec<Step>,
+    position: usize,
+}
+
+impl Debugger {
+    /// Applies operations to a copy of the pool, the pool itself is left untouched.
+    /// Simulations are debugged with `Debugger::new(&config.pool.build(), &simulation.ops)`.
+    pub fn new(pool: &LpPool, ops: &[PoolOp]) -> Self {
+        let state = pool.to_snapshot();
+        let mut pool = LpPool::from_snapshot(&state).expect("snapshot of a live pool restores");
+        let initial = (pool.snapshot(), state);
+        let steps = ops
+            .iter()
+            .map(|op| {
+                let outcome = pool.apply(op);
+                Step {
+                    op: *op,
+                    outcome,
+                    snapshot: pool.snapshot(),
+                    state: pool.to_snapshot(),
+                }
+            })
+            .collect();
+        Self {
+            initial,
+            steps,
+            position: 0,
+        }
+    }
+
+    /// Returns amount of recorded operations, the last position
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns step which led to the position, `None` at position `0`
+    pub fn step(&self) -> Option<&Step> {
+        self.position.checked_sub(1).map(|index| &self.steps[index])
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Returns balances and parameters of the pool at the position
+    pub fn snapshot(&self) -> &PoolSnapshot {
+        self.snapshot_at(self.position)
+            .expect("position is always recorded")
+    }
+
+    /// Returns pool restored in the state of the position, so it can be queried or
+    /// operations can be tried on it
+    pub fn pool(&self) -> LpPool {
+        let state = match self.step() {
+            Some(step) => &step.state,
+            None => &self.initial.1,
+        };
+        LpPool::from_snapshot(state).expect("recorded state restores")
+    }
+
+    fn snapshot_at(&self, position: usize) -> Option<&PoolSnapshot> {
+        match position {
+            0 => Some(&self.initial.0),
+            _ => self.steps.get(position - 1).map(|step| &step.snapshot),
+        }
+    }
+
+    /// Returns changes leading from position `from` to position `to`, `None` if either
+    /// of them wasn't recorded
+    pub fn diff(&self, from: usize, to: usize) -> Option<PoolDiff> {
+        Some(self.snapshot_at(from)?.diff(self.snapshot_at(to)?))
+    }
+
+    /// Moves to position `position` and returns changes made by the move, `None` if the
+    /// position wasn't recorded, the cursor doesn't move then
+    pub fn goto(&mut self, position: usize) -> Option<PoolDiff> {
+        let diff = self.diff(self.position, position)?;
+        self.position = position;
+        Some(diff)
+    }
+
+    /// Applies the next operation, `None` at the last position
+    pub fn step_forward(&mut self) -> Option<PoolDiff> {
+        self.goto(self.position + 1)
+    }
+
+    /// Reverts the last operation, `None` at position `0`
+    pub fn step_back(&mut self) -> Option<PoolDiff> {
+        self.goto(self.position.checked_sub(1)?)
+    }
+
+    /// Returns position after the first recorded step matching the predicate, e.g. the
+    /// first rejected operation
+    pub fn find(&self, predicate: impl Fn(&Step) -> bool) -> Option<usize> {
+        self.steps.iter().position(predicate).map(|index| index + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::ToJson;
+    use crate::simulator::{simulate, SimulationConfig};
+    use crate::test_utils::fixture_config;
+
+    fn debugger() -> Debugger {
+        let pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        let ops = [
+            PoolOp::AddLiquidity {
+                account: None,
+                amount: 100.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 6.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 100.into(),
+            },
+            PoolOp::AdvanceEpoch { epochs: 1 },
+        ];
+        Debugger::new(&pool, &ops)
+    }
+
+    #[test]
+    fn travels_between_steps() {
+        let mut debugger = debugger();
+        assert_eq!(debugger.len(), 4);
+        assert!(debugger.step().is_none());
+        assert!(debugger.step_back().is_none());
+
+        let deposit = debugger.step_forward().unwrap();
+        assert_eq!(deposit.token_amount, 100_000_000);
+        let swap = debugger.step_forward().unwrap();
+        assert_eq!(swap.token_amount, -8_991_000);
+        assert_eq!(debugger.pool().token_amount(), 91.009.into());
+
+        assert_eq!(debugger.goto(4).unwrap().epoch, 1);
+        assert!(debugger.step_forward().is_none());
+        assert!(debugger.goto(5).is_none());
+        assert_eq!(debugger.position(), 4);
+
+        let reverted = debugger.step_back().unwrap();
+        assert_eq!(reverted.epoch, -1);
+        assert!(debugger.goto(2).unwrap().is_empty());
+        assert_eq!(debugger.diff(1, 2), Some(swap));
+        assert!(debugger.goto(0).unwrap().token_amount < 0);
+    }
+
+    #[test]
+    fn finds_failing_steps() {
+        let mut debugger = debugger();
+        let rejected = debugger.find(|step| step.outcome.is_err()).unwrap();
+        assert_eq!(rejected, 3);
+        debugger.goto(rejected).unwrap();
+        assert!(matches!(debugger.step().unwrap().op, PoolOp::Swap { .. }));
+        assert!(debugger.diff(rejected - 1, rejected).unwrap().is_empty());
+    }
+
+    #[test]
+    fn replays_simulations() {
+        let config = SimulationConfig::new(fixture_config(), 3);
+        let simulation = simulate(&config);
+        let mut debugger = Debugger::new(&config.pool.build(), &simulation.ops);
+        debugger.goto(debugger.len()).unwrap();
+        assert_eq!(debugger.pool().to_json(), simulation.pool.to_json());
+    }
+}
@@ -0,0 +1,146 @@
+use crate::account::PoolAccount;
+use crate::error::SnapshotError;
+use crate::lp_pool::LpPool;
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Balances and parameters of the pool captured at a single point in time
+pub struct PoolSnapshot {
+    account: PoolAccount,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Value of a setting which can't be expressed as a signed delta
+pub struct Change<T> {
+    pub before: T,
+    pub after: T,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Difference between two snapshots. Amounts, prices and percentages are signed deltas
+/// of raw fixed-point values, optional settings are reported only when they changed.
+pub struct PoolDiff {
+    pub price: Int,
+    pub price_updated_at: Int,
+    pub token_amount: Int,
+    pub st_token_amount: Int,
+    pub lp_token_amount: Int,
+    pub liquidity_target: Int,
+    pub min_fee: Int,
+    pub max_fee: Int,
+    pub treasury_cut: Int,
+    pub epoch: Int,
+    pub total_fees: Int,
+    pub lp_fees: Int,
+    pub treasury_fees: Int,
+    pub claimable_treasury_fees: Int,
+    pub max_price_age: Option<Change<Option<Epoch>>>,
+    pub max_price_deviation: Option<Change<Option<Percentage>>>,
+    pub max_fee_change: Option<Change<Option<Percentage>>>,
+    pub monotonic_price: Option<Change<bool>>,
+}
+
+impl PoolDiff {
+    /// Returns true if both snapshots hold identical state
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl PoolSnapshot {
+    /// Decodes snapshot from the binary format produced by `LpPool::to_snapshot`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        Ok(LpPool::from_snapshot(bytes)?.snapshot())
+    }
+
+    /// Returns captured state in the on-chain account layout
+    pub fn account(&self) -> &PoolAccount {
+        &self.account
+    }
+
+    /// Returns changes leading from this snapshot to `other`
+    pub fn diff(&self, other: &PoolSnapshot) -> PoolDiff {
+        let (before, after) = (&self.account, &other.account);
+        let delta = |before: Uint, after: Uint| after as Int - before as Int;
+        let percentage = |value: Option<Uint>| value.map(Percentage::from_raw_amount);
+
+        PoolDiff {
+            price: delta(before.price, after.price),
+            price_updated_at: delta(before.price_updated_at, after.price_updated_at),
+            token_amount: delta(before.token_amount, after.token_amount),
+            st_token_amount: delta(before.st_token_amount, after.st_token_amount),
+            lp_token_amount: delta(before.lp_token_amount, after.lp_token_amount),
+            liquidity_target: delta(before.liquidity_target, after.liquidity_target),
+            min_fee: delta(before.min_fee, after.min_fee),
+            max_fee: delta(before.max_fee, after.max_fee),
+            treasury_cut: delta(before.treasury_cut, after.treasury_cut),
+            epoch: delta(before.epoch, after.epoch),
+            total_fees: delta(before.total_fees, after.total_fees),
+            lp_fees: delta(before.lp_fees, after.lp_fees),
+            treasury_fees: delta(before.treasury_fees, after.treasury_fees),
+            claimable_treasury_fees: delta(
+                before.claimable_treasury_fees,
+                after.claimable_treasury_fees,
+            ),
+            max_price_age: change(before.max_price_age, after.max_price_age),
+            max_price_deviation: change(
+                percentage(before.max_price_deviation),
+                percentage(after.max_price_deviation),
+            ),
+            max_fee_change: change(
+                percentage(before.max_fee_change),
+                percentage(after.max_fee_change),
+            ),
+            monotonic_price: change(before.monotonic_price, after.monotonic_price),
+        }
+    }
+}
+
+fn change<T: PartialEq>(before: T, after: T) -> Option<Change<T>> {
+    (before != after).then_some(Change { before, after })
+}
+
+impl LpPool {
+    /// Captures current balances and parameters of the pool
+    pub fn snapshot(&self) -> PoolSnapshot {
+        PoolSnapshot {
+            account: self.to_account(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_signed_deltas() {
+        let mut pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        pool.add_liquidity(100.into()).unwrap();
+        let before = pool.snapshot();
+        assert!(before.diff(&before).is_empty());
+
+        pool.swap(6.into()).unwrap();
+        pool.set_max_price_age(Some(5));
+        let diff = before.diff(&pool.snapshot());
+
+        assert_eq!(
+            diff.st_token_amount,
+            StakedTokenAmount::from(6).raw() as Int
+        );
+        assert!(diff.token_amount < 0);
+        assert_eq!(diff.lp_token_amount, 0);
+        assert_eq!(diff.total_fees, 9_000_000 + diff.token_amount);
+        assert_eq!(
+            diff.max_price_age,
+            Some(Change {
+                before: None,
+                after: Some(5)
+            })
+        );
+        assert_eq!(diff.monotonic_price, None);
+
+        let restored = PoolSnapshot::from_bytes(&pool.to_snapshot()).unwrap();
+        assert!(pool.snapshot().diff(&restored).is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+//! Swap fee discounts for accounts providing liquidity or swapping a lot. Tiers are
+//! resolved at swap time from the positions ledger and the swap volume recorded per
+//! account; an account qualifying for several tiers gets the largest discount. Discounted
+//! fees are rounded up in favor of LPs.
+
+use std::collections::BTreeMap;
+
+use crate::error::SchemaError;
+use crate::json::Value;
+use crate::schema::{field, FromJson, ToJson};
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Requirement an account has to meet to qualify for a discount tier
+pub enum DiscountCriterion {
+    /// account's position holds at least this share of lp tokens in circulation
+    LiquidityShare(Percentage),
+    /// account swapped tokens worth at least this much, valued before fees
+    Volume(TokenAmount),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Discount taken off the swap fee of accounts meeting the criterion
+pub struct DiscountTier {
+    pub criterion: DiscountCriterion,
+    /// share of the fee waived, capped at 100%
+    pub discount: Percentage,
+}
+
+impl DiscountTier {
+    pub fn new(criterion: DiscountCriterion, discount: Percentage) -> Self {
+        Self {
+            criterion,
+            discount,
+        }
+    }
+
+    /// Returns whether the account qualifies for the tier
+    ///
+    /// # Arguments
+    ///
+    /// * `lp_tokens` - lp tokens held by the account's position
+    /// * `lp_supply` - lp tokens in circulation
+    /// * `volume` - swap volume of the account
+    pub fn qualifies(
+        &self,
+        lp_tokens: LpTokenAmount,
+        lp_supply: LpTokenAmount,
+        volume: TokenAmount,
+    ) -> bool {
+        match self.criterion {
+            DiscountCriterion::LiquidityShare(share) => {
+                lp_supply.raw() > 0
+                    && lp_tokens.raw() as u128 * SCALE as u128
+                        >= share.raw() as u128 * lp_supply.raw() as u128
+            }
+            DiscountCriterion::Volume(threshold) => volume >= threshold,
+        }
+    }
+
+    /// Returns `fee` with the discount taken off, rounded up
+    pub fn apply(&self, fee: Percentage) -> Percentage {
+        let retained = SCALE.saturating_sub(self.discount.raw()) as u128;
+        Percentage::from_raw_amount((fee.raw() as u128 * retained).div_ceil(SCALE as u128) as Uint)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// Configured discount tiers together with swap volume of every account that swapped
+pub struct FeeDiscounts {
+    tiers: Vec<DiscountTier>,
+    volumes: BTreeMap<AccountId, TokenAmount>,
+}
+
+impl FeeDiscounts {
+    pub fn tiers(&self) -> &[DiscountTier] {
+        &self.tiers
+    }
+
+    pub(crate) fn set_tiers(&mut self, tiers: Vec<DiscountTier>) {
+        self.tiers = tiers;
+    }
+
+    /// Returns swap volume of the account, valued before fees
+    pub fn volume(&self, account: AccountId) -> TokenAmount {
+        self.volumes.get(&account).copied().unwrap_or_default()
+    }
+
+    /// Records swap of the account, volume is recorded even without any tiers so that
+    /// tiers configured later take earlier swaps into account
+    pub(crate) fn record_swap(&mut self, account: AccountId, value: TokenAmount) {
+        let volume = self.volumes.entry(account).or_default();
+        *volume = volume.saturating_add(value);
+    }
+
+    /// Returns tier with the largest discount the account qualifies for, the first one
+    /// out of equal discounts
+    pub(crate) fn resolve(
+        &self,
+        account: AccountId,
+        lp_tokens: LpTokenAmount,
+        lp_supply: LpTokenAmount,
+    ) -> Option<DiscountTier> {
+        let volume = self.volume(account);
+        self.tiers
+            .iter()
+            .filter(|tier| tier.qualifies(lp_tokens, lp_supply, volume))
+            .fold(None, |best: Option<DiscountTier>, tier| match best {
+                Some(best) if best.discount >= tier.discount => Some(best),
+                _ => Some(*tier),
+            })
+    }
+}
+
+impl ToJson for DiscountTier {
+    fn to_json(&self) -> Value {
+        let (criterion, threshold) = match self.criterion {
+            DiscountCriterion::LiquidityShare(share) => ("liquidity_share", share.to_json()),
+            DiscountCriterion::Volume(volume) => ("volume", volume.to_json()),
+        };
+        Value::object([
+            ("criterion", criterion.into()),
+            ("threshold", threshold),
+            ("discount", self.discount.to_json()),
+        ])
+    }
+}
+
+impl FromJson for DiscountTier {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        let criterion = match field::<String>(value, "criterion")?.as_str() {
+            "liquidity_share" => DiscountCriterion::LiquidityShare(field(value, "threshold")?),
+            "volume" => DiscountCriterion::Volume(field(value, "threshold")?),
+            other => {
+                return Err(SchemaError::InvalidValue {
+                    path: "criterion".into(),
+                    reason: format!("unknown variant `{other}`"),
+                })
+            }
+        };
+        Ok(Self {
+            criterion,
+            discount: field(value, "discount")?,
+        })
+    }
+}
+
+impl ToJson for FeeDiscounts {
+    fn to_json(&self) -> Value {
+        let volumes = self
+            .volumes
+            .iter()
+            .map(|(account, volume)| {
+                Value::object([("account", account.to_json()), ("volume", volume.to_json())])
+            })
+            .collect();
+        Value::object([
+            ("tiers", self.tiers.to_json()),
+            ("volumes", Value::Array(volumes)),
+        ])
+    }
+}
+
+impl FromJson for FeeDiscounts {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        let volumes = field::<Vec<Value>>(value, "volumes")?
+            .iter()
+            .map(|entry| Ok((field(entry, "account")?, field(entry, "volume")?)))
+            .collect::<Result<_, SchemaError>>()
+            .map_err(|error| error.in_field("volumes"))?;
+        Ok(Self {
+            tiers: field(value, "tiers")?,
+            volumes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: AccountId = 1;
+
+    #[test]
+    fn resolves_largest_qualifying_discount() {
+        let mut discounts = FeeDiscounts::default();
+        discounts.set_tiers(vec![
+            DiscountTier::new(DiscountCriterion::LiquidityShare(0.01.into()), 0.2.into()),
+            DiscountTier::new(DiscountCriterion::Volume(100.into()), 0.1.into()),
+            DiscountTier::new(DiscountCriterion::Volume(1_000.into()), 0.5.into()),
+        ]);
+
+        assert_eq!(discounts.resolve(ALICE, 0.into(), 100.into()), None);
+        assert_eq!(
+            discounts
+                .resolve(ALICE, 1.into(), 100.into())
+                .map(|tier| tier.discount),
+            Some(0.2.into())
+        );
+        assert_eq!(discounts.resolve(ALICE, 1.into(), 0.into()), None);
+
+        discounts.record_swap(ALICE, 100.into());
+        assert_eq!(
+            discounts
+                .resolve(ALICE, 0.into(), 100.into())
+                .map(|tier| tier.discount),
+            Some(0.1.into())
+        );
+        discounts.record_swap(ALICE, 900.into());
+        assert_eq!(discounts.volume(ALICE), 1_000.into());
+        assert_eq!(
+            discounts
+                .resolve(ALICE, 1.into(), 100.into())
+                .map(|tier| tier.discount),
+            Some(0.5.into())
+        );
+
+        let restored = FeeDiscounts::from_json(&discounts.to_json()).unwrap();
+        assert_eq!(restored, discounts);
+    }
+
+    #[test]
+    fn rounds_discounted_fee_up() {
+        let tier = DiscountTier::new(DiscountCriterion::Volume(0.into()), 0.2.into());
+        assert_eq!(tier.apply(0.05.into()), 0.04.into());
+        assert_eq!(
+            tier.apply(Percentage::from_raw_amount(3)),
+            Percentage::from_raw_amount(3)
+        );
+        let free = DiscountTier::new(DiscountCriterion::Volume(0.into()), 1.5.into());
+        assert_eq!(free.apply(0.05.into()), 0.0.into());
+    }
+}
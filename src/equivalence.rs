@@ -0,0 +1,99 @@
+//! Equivalence check of two pools fed the same operation stream, meant for refactorings
+//! of the pool math and for comparing configurations which should behave the same. Pools
+//! are compared by what they expose: the outcome of every operation and the balances
+//! after it. Settings themselves can differ, so configurations using e.g. another
+//! rounding policy are equivalent as long as no operation observes the difference.
+
+use crate::error::EquivalenceError;
+use crate::ops::PoolOp;
+use crate::replay::PoolConfig;
+use crate::schema::ToJson;
+
+/// Applies operations to pools built from both configurations and returns the first step
+/// whose outcome or resulting balances differ, with the state of both pools around it
+pub fn check_equivalent(
+    config_a: &PoolConfig,
+    config_b: &PoolConfig,
+    ops: &[PoolOp],
+) -> Result<(), Box<EquivalenceError>> {
+    let (mut pool_a, mut pool_b) = (config_a.build(), config_b.build());
+    for (step, op) in ops.iter().enumerate() {
+        let (before_a, before_b) = (pool_a.to_json(), pool_b.to_json());
+        let before = (pool_a.snapshot(), pool_b.snapshot());
+        let outcome_a = format!("{:?}", pool_a.apply(op));
+        let outcome_b = format!("{:?}", pool_b.apply(op));
+        if outcome_a != outcome_b || pool_a.balances() != pool_b.balances() {
+            return Err(Box::new(EquivalenceError {
+                step,
+                op: *op,
+                outcome_a,
+                outcome_b,
+                changes_a: before.0.diff(&pool_a.snapshot()),
+                changes_b: before.1.diff(&pool_b.snapshot()),
+                before_a,
+                before_b,
+                after_a: pool_a.to_json(),
+                after_b: pool_b.to_json(),
+            }));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixture_config;
+
+    fn ops() -> Vec<PoolOp> {
+        vec![
+            PoolOp::AddLiquidity {
+                account: None,
+                amount: 100.into(),
+            },
+            PoolOp::AdvanceEpoch { epochs: 1 },
+            PoolOp::Swap {
+                account: None,
+                amount: 6.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 30.into(),
+            },
+        ]
+    }
+
+    #[test]
+    fn accepts_settings_no_operation_observes() {
+        let mut config = fixture_config();
+        config.max_price_age = Some(10);
+        assert_eq!(check_equivalent(&fixture_config(), &config, &ops()), Ok(()));
+    }
+
+    #[test]
+    fn reports_first_divergence() {
+        let mut config = fixture_config();
+        config.treasury_cut = 0.5.into();
+        let error = check_equivalent(&fixture_config(), &config, &ops()).unwrap_err();
+        assert_eq!(error.step, 2);
+        assert_eq!(
+            error.op,
+            PoolOp::Swap {
+                account: None,
+                amount: 6.into()
+            }
+        );
+        // both pools pay out the same, the treasury cut leaves the second pool with less
+        assert_eq!(error.outcome_a, error.outcome_b);
+        assert_eq!(error.changes_a.treasury_fees, 0);
+        assert_eq!(error.changes_b.treasury_fees, 4_500);
+        assert_eq!(
+            error.before_a.get("token_amount"),
+            error.before_b.get("token_amount")
+        );
+        assert_ne!(
+            error.after_a.get("token_amount"),
+            error.after_b.get("token_amount")
+        );
+    }
+}
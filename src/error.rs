@@ -1,6 +1,14 @@
 use thiserror::Error;
 
-use crate::types::{LpTokenAmount, TokenAmount};
+use crate::account::PoolAccount;
+use crate::diff::PoolDiff;
+use crate::governance::PoolParams;
+use crate::json::{JsonError, Value};
+use crate::ops::PoolOp;
+use crate::roles::Role;
+use crate::types::{
+    AccountId, Epoch, LpTokenAmount, Percentage, Price, StakedTokenAmount, TokenAmount,
+};
 
 #[derive(Error, Debug)]
 /// enum holding common errors
@@ -13,6 +21,10 @@ pub enum AddLiquidityError {
     NoTokensProvided,
     #[error("Provided token amount was too big and would cause overflow")]
     TokenAmountTooBig,
+    #[error("Pool has lp tokens in circulation but doesn't hold any value")]
+    PoolWithoutValue,
+    #[error(transparent)]
+    Oracle(#[from] OracleError),
 }
 
 #[derive(Error, Debug)]
@@ -25,6 +37,11 @@ pub enum RemoveLiquidityError {
     },
     #[error("Calculating withdraw amount caused overflow, try using smaller withdraw amount")]
     WithdrawCalculationOverflow,
+    #[error("Account wanted to withdraw {withdraw_amount:?} tokens but its position only holds {position_amount:?}")]
+    PositionNotEnoughTokens {
+        withdraw_amount: LpTokenAmount,
+        position_amount: LpTokenAmount,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -39,4 +56,582 @@ pub enum SwapError {
     },
     #[error("Zero tokens were passed as swap argument")]
     ZeroTokensAsArgument,
+    #[error("Swapped amount was too big and would cause overflow")]
+    AmountTooBig,
+    #[error(transparent)]
+    Oracle(#[from] OracleError),
+    #[error("Price was last updated {age} epochs ago, max allowed age is {max_age} epochs")]
+    StalePrice { age: Epoch, max_age: Epoch },
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+/// enum holding errors that can happen when querying price oracle
+pub enum OracleError {
+    #[error("Oracle is unavailable: {0}")]
+    Unavailable(String),
+    #[error("Oracle returned zero price")]
+    ZeroPrice,
+    #[error("Oracle data is invalid: {0}")]
+    InvalidData(String),
+    #[error("Oracle price is {age}s old, max allowed age is {max_age}s")]
+    StalePrice { age: u64, max_age: u64 },
+    #[error("Price {price:?} deviates by {deviation:?} from last accepted price, max allowed deviation is {max_deviation:?}")]
+    DeviationTooLarge {
+        price: Price,
+        deviation: Percentage,
+        max_deviation: Percentage,
+    },
+    #[error("Price {price:?} is lower than last accepted price {last_price:?} and no slashing was signalled")]
+    PriceDecreased { price: Price, last_price: Price },
+    #[error("Only {responses} oracle sources responded, quorum is {quorum}")]
+    QuorumNotReached { responses: usize, quorum: usize },
+    #[error("Oracle confidence interval {confidence:?} is wider than allowed {max_confidence:?}")]
+    ConfidenceTooWide {
+        confidence: Percentage,
+        max_confidence: Percentage,
+    },
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors that can happen when updating price
+pub enum PriceUpdateError {
+    #[error("Price has to be greater than zero")]
+    ZeroPrice,
+    #[error("Price {price:?} deviates by {deviation:?} from last accepted price, max allowed deviation is {max_deviation:?}")]
+    DeviationTooLarge {
+        price: Price,
+        deviation: Percentage,
+        max_deviation: Percentage,
+    },
+    #[error("Price {price:?} is lower than last accepted price {last_price:?} and no slashing was signalled")]
+    PriceDecreased { price: Price, last_price: Price },
+    #[error("Price override requires a reason")]
+    MissingReason,
+    #[error("There is no rejected price waiting for approval")]
+    NoRejectedPrice,
+    #[error(transparent)]
+    Oracle(#[from] OracleError),
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors that can happen when claiming treasury fees
+pub enum ClaimError {
+    #[error("There are no treasury fees to claim")]
+    NothingToClaim,
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors that can happen when changing pool parameters
+pub enum GovernanceError {
+    #[error(
+        "Min fee {min_fee:?} can't be bigger than max fee {max_fee:?} and fees can't exceed 100%"
+    )]
+    InvalidFees {
+        min_fee: Percentage,
+        max_fee: Percentage,
+    },
+    #[error("Liquidity target has to be greater than zero")]
+    ZeroLiquidityTarget,
+    #[error("Fee change of {change:?} exceeds max allowed change of {max_change:?} per update")]
+    FeeChangeTooBig {
+        change: Percentage,
+        max_change: Percentage,
+    },
+    #[error("Another parameter update is already pending")]
+    UpdateAlreadyPending,
+    #[error("There is no pending parameter update")]
+    NoPendingUpdate,
+    #[error("Pending update can only be applied at epoch {executable_at}, current epoch is {current_epoch}")]
+    TimelockNotPassed {
+        current_epoch: Epoch,
+        executable_at: Epoch,
+    },
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors that can happen when applying `PoolOp`
+pub enum OpError {
+    #[error(transparent)]
+    AddLiquidity(#[from] AddLiquidityError),
+    #[error(transparent)]
+    RemoveLiquidity(#[from] RemoveLiquidityError),
+    #[error(transparent)]
+    Swap(#[from] SwapError),
+    #[error(transparent)]
+    PriceUpdate(#[from] PriceUpdateError),
+    #[error(transparent)]
+    Hook(#[from] HookRejection),
+    #[error("Mutation can't be replayed from the operation log")]
+    Unreplayable,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("Operation rejected by hook: {reason}")]
+/// Rejection of an operation by a `PoolHook`
+pub struct HookRejection {
+    pub reason: String,
+}
+
+#[derive(Error, Debug, PartialEq)]
+/// enum holding conservation violations found by the conservation validator
+pub enum ConservationError {
+    #[error("Step {step}: pool should hold {expected:?} tokens but holds {actual:?}")]
+    TokensNotConserved {
+        step: usize,
+        expected: TokenAmount,
+        actual: TokenAmount,
+    },
+    #[error("Step {step}: staked tokens were not conserved")]
+    StakedTokensNotConserved { step: usize },
+    #[error("Step {step}: lp tokens were not conserved")]
+    LpTokensNotConserved { step: usize },
+    #[error("Step {step}: value of existing lp tokens increased from {before:?} to {after:?} without fees")]
+    LpValueIncreased {
+        step: usize,
+        before: TokenAmount,
+        after: TokenAmount,
+    },
+    #[error("Step {step}: withdrawal returned more than the proportional share of the pool")]
+    ExcessWithdrawal { step: usize },
+    #[error("Step {step}: swap paid out more than the value of swapped tokens")]
+    NegativeFee { step: usize },
+    #[error("Step {step}: failed operation changed pool state")]
+    FailedOpChangedState { step: usize },
+    #[error("Step {step}: operation changed state it shouldn't touch")]
+    UnexpectedStateChange { step: usize },
+    #[error("Step {step}: outcome doesn't match the operation")]
+    MismatchedOutcome { step: usize },
+    #[error("Step {step}: rounding drifted pool value by {drift}, allowed drift is {bound}")]
+    DriftOutOfBound { step: usize, drift: f64, bound: f64 },
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors that can happen when importing pool state from external sources
+pub enum ImportError {
+    #[error("Transport failed: {0}")]
+    Transport(String),
+    #[error("RPC node returned error: {0}")]
+    Rpc(String),
+    #[error("RPC response is invalid: {0}")]
+    InvalidResponse(String),
+    #[error("Account data is invalid: {0}")]
+    InvalidAccount(String),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+/// enum holding errors returned when restoring serialized pool state
+pub enum SchemaError {
+    #[error("Schema version {version} is not supported, newest supported version is {supported}")]
+    UnsupportedVersion { version: u64, supported: u64 },
+    #[error("Missing field `{path}`")]
+    MissingField { path: String },
+    #[error("Invalid value of `{path}`: {reason}")]
+    InvalidValue { path: String, reason: String },
+}
+
+#[cfg(feature = "json-files")]
+#[derive(Error, Debug)]
+/// enum holding errors that can happen when saving or loading pool state files
+pub enum PersistError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] crate::json::JsonError),
+    #[error(transparent)]
+    Schema(#[from] SchemaError),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+/// enum holding errors returned when decoding binary snapshots
+pub enum SnapshotError {
+    #[error("Snapshot header is invalid")]
+    InvalidHeader,
+    #[error("Snapshot format version {0} is not supported")]
+    UnsupportedVersion(u8),
+    #[error("Snapshot ended unexpectedly")]
+    UnexpectedEnd,
+    #[error("Snapshot contains bytes after the encoded data")]
+    TrailingBytes,
+    #[error("Snapshot contains invalid tag {0}")]
+    InvalidTag(u8),
+    #[error("Snapshot references unknown key {0}")]
+    InvalidKeyIndex(u128),
+    #[error("Snapshot contains invalid varint")]
+    VarintOverflow,
+    #[error("Snapshot contains invalid UTF-8 string")]
+    InvalidUtf8,
+    #[error("Snapshot is nested too deeply")]
+    TooDeep,
+    #[error(transparent)]
+    Schema(#[from] SchemaError),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+/// enum holding errors returned when decoding on-chain pool accounts
+pub enum AccountLayoutError {
+    #[error("Account layout version {0} is not supported")]
+    UnsupportedVersion(u8),
+    #[error("Account data ended unexpectedly")]
+    UnexpectedEnd,
+    #[error("Invalid bool value {0}")]
+    InvalidBool(u8),
+    #[error("Invalid option tag {0}")]
+    InvalidOptionTag(u8),
+    #[error("Reserved account space is not zeroed")]
+    NonZeroPadding,
+    #[error("Price updated at epoch {price_updated_at} is ahead of epoch {epoch}")]
+    PriceUpdatedInFuture {
+        price_updated_at: Epoch,
+        epoch: Epoch,
+    },
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors returned by pool stores
+pub enum StoreError {
+    #[error("Invalid pool id `{0}`")]
+    InvalidId(String),
+    #[error("Pool `{0}` was never saved")]
+    UnknownPool(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Snapshot(#[from] SnapshotError),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+/// enum holding errors returned when drawing on the insurance fund
+pub enum InsuranceError {
+    #[error("Zero tokens were requested from the insurance fund")]
+    ZeroAmount,
+    #[error("Insurance fund holds {balance:?} tokens, {requested:?} were requested")]
+    InsufficientFunds {
+        requested: TokenAmount,
+        balance: TokenAmount,
+    },
+    #[error("Covering {amount:?} tokens would overflow pool liquidity")]
+    LiquidityOverflow { amount: TokenAmount },
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+/// enum holding errors returned by timelock queues
+pub enum TimelockError {
+    #[error("There is no queued action {0}")]
+    UnknownAction(u64),
+    #[error("Action {id} can only be executed at epoch {executable_at}, current epoch is {current_epoch}")]
+    NotReady {
+        id: u64,
+        current_epoch: Epoch,
+        executable_at: Epoch,
+    },
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors returned by operations of a pool with roles
+pub enum RoleError {
+    #[error("Account {account} doesn't have the {role:?} role")]
+    Unauthorized { account: AccountId, role: Role },
+    #[error("Pool is paused")]
+    Paused,
+    #[error("Emergency withdrawals are only allowed while the pool is paused")]
+    NotPaused,
+    #[error("Account {0} is the last admin and can't lose the role")]
+    LastAdmin(AccountId),
+    #[error("Account {caller} can't operate on behalf of account {account}")]
+    NotAccountOwner {
+        caller: AccountId,
+        account: AccountId,
+    },
+    #[error(transparent)]
+    Governance(#[from] GovernanceError),
+    #[error(transparent)]
+    Timelock(#[from] TimelockError),
+    #[error(transparent)]
+    RemoveLiquidity(#[from] RemoveLiquidityError),
+    #[error(transparent)]
+    Insurance(#[from] InsuranceError),
+    #[error(transparent)]
+    PriceUpdate(#[from] PriceUpdateError),
+    #[error(transparent)]
+    Op(#[from] OpError),
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors returned by liquidity migrations between pools
+pub enum MigrateLiquidityError {
+    #[error(transparent)]
+    RemoveLiquidity(#[from] RemoveLiquidityError),
+    #[error(transparent)]
+    AddLiquidity(#[from] AddLiquidityError),
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors returned by two-phase swaps
+pub enum CommitRevealError {
+    #[error("Swap was already committed to")]
+    DuplicateCommitment,
+    #[error("Revealed swap doesn't match any commitment")]
+    UnknownCommitment,
+    #[error("Commitment can be revealed from epoch {revealable_at}, current epoch is {epoch}")]
+    TooEarly { revealable_at: Epoch, epoch: Epoch },
+    #[error(transparent)]
+    Swap(#[from] SwapError),
+}
+
+#[derive(Error, Debug, PartialEq)]
+/// enum holding errors returned by the limit order book
+pub enum OrderError {
+    #[error("Order has to swap a non-zero amount")]
+    ZeroAmount,
+    #[error("Order expiring at epoch {expires_at} can't be placed at epoch {epoch}")]
+    Expired { expires_at: Epoch, epoch: Epoch },
+    #[error("There is no unfilled order {0}")]
+    UnknownOrder(u64),
+    #[error("Account {caller} doesn't own order {id}")]
+    NotOwner { id: u64, caller: AccountId },
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors returned by the pool registry
+pub enum RegistryError {
+    #[error("Invalid tenant or pool id `{0}`")]
+    InvalidId(String),
+    #[error("Tenant `{tenant}` has no pool `{id}`")]
+    UnknownPool { tenant: String, id: String },
+    #[error("Tenant `{tenant}` already has pool `{id}`")]
+    PoolExists { tenant: String, id: String },
+    #[error("Tenant `{tenant}` reached its limit of {max_pools} pools")]
+    PoolLimit { tenant: String, max_pools: usize },
+    #[error("Pool would hold {tokens:?} tokens, tenant limit is {max_tokens:?}")]
+    TokenLimit {
+        tokens: TokenAmount,
+        max_tokens: TokenAmount,
+    },
+    #[error(transparent)]
+    Op(#[from] OpError),
+}
+
+#[derive(Error, Debug)]
+#[error("Replay failed at operation {index}: {error}")]
+/// error returned when a logged operation can't be replayed
+pub struct ReplayError {
+    /// index of the failed operation in the log
+    pub index: usize,
+    pub error: OpError,
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors returned when restoring a pool from checkpoints
+pub enum RestoreError {
+    #[error(transparent)]
+    Snapshot(#[from] SnapshotError),
+    #[error(transparent)]
+    Replay(#[from] ReplayError),
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors returned by swaps bounded by a minimal payout
+pub enum SlippageError {
+    #[error(transparent)]
+    Swap(#[from] SwapError),
+    #[error("Swap would pay out {amount_out:?}, less than the required {min_amount_out:?}")]
+    Exceeded {
+        amount_out: TokenAmount,
+        min_amount_out: TokenAmount,
+    },
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors returned when exporting simulation results
+pub enum ExportError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Replay(#[from] ReplayError),
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors returned when loading pool configuration
+pub enum ConfigError {
+    #[error("Syntax error at line {line}: {reason}")]
+    Syntax { line: usize, reason: String },
+    #[error("Missing required key `{0}`")]
+    MissingKey(String),
+    #[error("Unknown key `{0}`")]
+    UnknownKey(String),
+    #[error("Invalid value of `{key}` at line {line}: {reason}")]
+    InvalidValue {
+        line: usize,
+        key: String,
+        reason: String,
+    },
+    #[error(transparent)]
+    Invalid(#[from] GovernanceError),
+    #[error("Unsupported configuration format of `{0}`, expected .toml, .yaml or .yml")]
+    UnsupportedFormat(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("`{0}` is not a valid decimal amount")]
+/// error returned when parsing amount from a decimal literal
+pub struct ParseAmountError(pub String);
+
+#[derive(Error, Debug, PartialEq)]
+/// enum holding violations of pool properties found by randomized checks
+pub enum PropertyError {
+    #[error(transparent)]
+    Conservation(#[from] ConservationError),
+    #[error("Step {step}: swap decreased value of lp tokens")]
+    LpValueDecreased { step: usize },
+    #[error("Removing freshly added liquidity failed")]
+    RoundTripFailed,
+    #[error("Round trip of {deposited:?} with {fees:?} LP fees withdrew {withdrawn:?}")]
+    RoundTripProfit {
+        deposited: TokenAmount,
+        fees: TokenAmount,
+        withdrawn: TokenAmount,
+    },
+}
+
+#[derive(Error, Debug, PartialEq)]
+/// enum holding violations found by the exhaustive check of small pools
+pub enum ModelCheckError {
+    #[error("{op:?} panicked on pool {account:?}: {message}")]
+    Panicked {
+        account: Box<PoolAccount>,
+        op: PoolOp,
+        message: String,
+    },
+    #[error("{op:?} applied to pool {account:?} violates a property: {error}")]
+    Violated {
+        account: Box<PoolAccount>,
+        op: PoolOp,
+        error: PropertyError,
+    },
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Property violated by case with seed {seed}: {error}")]
+/// Randomized case violating a property, `ops` is the shrunk failing sequence
+pub struct PropertyFailure {
+    pub seed: u64,
+    pub ops: Vec<PoolOp>,
+    /// deposit of the round trip check run after the operations
+    pub deposit: TokenAmount,
+    /// swaps of the round trip check
+    pub swaps: Vec<StakedTokenAmount>,
+    pub error: PropertyError,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+/// enum holding differences between the fixed-point pool and its floating-point reference
+pub enum DivergenceError {
+    #[error("Configuration uses settings the reference model doesn't cover")]
+    Unsupported,
+    #[error(
+        "Step {step}: {quantity} is {fixed} but exact value is {exact}, allowed error is {bound}"
+    )]
+    OutOfBound {
+        step: usize,
+        quantity: &'static str,
+        fixed: u64,
+        exact: f64,
+        bound: f64,
+    },
+    #[error("Step {step}: outcome doesn't match the operation")]
+    MismatchedOutcome { step: usize },
+    #[error("Step {step}: rounding drifted pool value by {drift}, allowed drift is {bound}")]
+    DriftOutOfBound { step: usize, drift: f64, bound: f64 },
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+/// enum holding errors of the ported Marinade liquid unstake
+pub enum MarinadeUnstakeError {
+    #[error("Zero mSOL were passed as unstake argument")]
+    ZeroAmount,
+    #[error("Unstake value doesn't fit into u64")]
+    CalculationFailure,
+    #[error("Unstake would require {requested} lamports but pool can only provide {available}")]
+    InsufficientLiquidity { requested: u64, available: u64 },
+}
+
+#[derive(Error, Debug, PartialEq)]
+/// enum holding failures found by the extreme value stress checks
+pub enum StressError {
+    #[error("Step {step}: {op:?} panicked: {message}")]
+    Panicked {
+        step: usize,
+        op: Option<PoolOp>,
+        message: String,
+    },
+    #[error("Invalid parameters {0:?} passed validation")]
+    InvalidConfigAccepted(PoolParams),
+    #[error(transparent)]
+    Conservation(#[from] ConservationError),
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error(
+    "Step {step}: pools diverged on {op:?}, outcomes are {outcome_a} and {outcome_b}, \
+    changes are {changes_a:?} and {changes_b:?}"
+)]
+/// First operation whose outcome or resulting balances differ between two pools, states
+/// are JSON documents of both pools before and after the operation
+pub struct EquivalenceError {
+    pub step: usize,
+    pub op: PoolOp,
+    pub outcome_a: String,
+    pub outcome_b: String,
+    pub changes_a: PoolDiff,
+    pub changes_b: PoolDiff,
+    pub before_a: Value,
+    pub before_b: Value,
+    pub after_a: Value,
+    pub after_b: Value,
+}
+
+#[derive(Error, Debug)]
+/// enum holding inconsistencies found after a simulation with injected chaos
+pub enum ChaosError {
+    #[error(transparent)]
+    Conservation(#[from] ConservationError),
+    #[error("End state doesn't restore from its snapshot")]
+    NotRestorable,
+    #[error("Pool rejected {op:?} after the run: {error}")]
+    NotRecovered { op: PoolOp, error: OpError },
+}
+
+#[derive(Error, Debug)]
+/// enum holding errors returned when loading recorded unstake flows
+pub enum FlowError {
+    #[error("Syntax error in record {record}: {reason}")]
+    Syntax { record: usize, reason: String },
+    #[error("Record {record} is older than the record before it")]
+    Unordered { record: usize },
+    #[error(transparent)]
+    Json(#[from] JsonError),
+    #[error("Unsupported flow format of `{0}`, expected .csv or .json")]
+    UnsupportedFormat(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl SchemaError {
+    /// Prefixes path of the error with the name of the field containing it
+    pub(crate) fn in_field(self, key: &str) -> Self {
+        let prefix = |path: String| match path.is_empty() {
+            true => key.to_string(),
+            false => format!("{key}.{path}"),
+        };
+        match self {
+            SchemaError::MissingField { path } => SchemaError::MissingField { path: prefix(path) },
+            SchemaError::InvalidValue { path, reason } => SchemaError::InvalidValue {
+                path: prefix(path),
+                reason,
+            },
+            error => error,
+        }
+    }
 }
@@ -0,0 +1,231 @@
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::mpsc::Sender;
+
+use crate::error::SchemaError;
+use crate::governance::PoolParams;
+use crate::json::Value;
+use crate::roles::{PrivilegedAction, Role};
+use crate::schema::{field, FromJson, ToJson};
+use crate::types::*;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Events emitted by every mutation of the pool, retrievable with `LpPool::drain_events`
+/// or received by a sink registered with `LpPool::set_event_sink`
+pub enum PoolEvent {
+    /// liquidity was deposited, `account` is `None` for anonymous liquidity
+    LiquidityAdded {
+        account: Option<AccountId>,
+        amount: TokenAmount,
+        lp_amount: LpTokenAmount,
+    },
+    /// liquidity was withdrawn, `account` is `None` for anonymous liquidity
+    LiquidityRemoved {
+        account: Option<AccountId>,
+        lp_amount: LpTokenAmount,
+        tokens: TokenAmount,
+        staked_tokens: StakedTokenAmount,
+    },
+    /// liquidity was withdrawn to be deposited into another pool, reported together with
+    /// `LiquidityRemoved` and paired with `LiquidityMigratedIn` of the other pool
+    LiquidityMigratedOut {
+        account: Option<AccountId>,
+        lp_amount: LpTokenAmount,
+        tokens: TokenAmount,
+        staked_tokens: StakedTokenAmount,
+    },
+    /// tokens withdrawn from another pool were deposited, reported together with
+    /// `LiquidityAdded` and paired with `LiquidityMigratedOut` of the other pool
+    LiquidityMigratedIn {
+        account: Option<AccountId>,
+        amount: TokenAmount,
+        lp_amount: LpTokenAmount,
+    },
+    /// staked tokens were swapped, the fee is reported by `FeesCollected` as well
+    Swapped {
+        /// account the swap is attributed to, `None` for anonymous swaps
+        account: Option<AccountId>,
+        amount: StakedTokenAmount,
+        amount_out: TokenAmount,
+        fee_amount: TokenAmount,
+    },
+    /// price used by swaps changed, whether it was set, refreshed from the oracle, forced
+    /// or approved
+    PriceUpdated { previous_price: Price, price: Price },
+    /// pool clock moved forward to `epoch`
+    EpochAdvanced { epochs: Epoch, epoch: Epoch },
+    /// swap fee was charged and split between LPs, the treasury and the insurance fund
+    FeesCollected {
+        lp_portion: TokenAmount,
+        treasury_portion: TokenAmount,
+        insurance_portion: TokenAmount,
+    },
+    /// accumulated treasury fees were claimed
+    TreasuryFeesClaimed { amount: TokenAmount },
+    /// tokens were moved from the insurance fund to pool liquidity to cover a shortfall
+    InsuranceDrawn { amount: TokenAmount },
+    /// parameter change was queued behind the governance timelock
+    ParamsUpdateProposed {
+        params: PoolParams,
+        executable_at: Epoch,
+    },
+    /// queued parameter change was applied
+    ParamsUpdated { params: PoolParams },
+    /// queued parameter change was cancelled
+    ParamsUpdateCancelled { params: PoolParams },
+    /// price deviating too much from the last accepted one was rejected by the circuit breaker
+    PriceRejected {
+        price: Price,
+        last_price: Price,
+        deviation: Percentage,
+    },
+    /// rejected price was approved and accepted
+    RejectedPriceApproved { price: Price },
+    /// slashing was signalled, allowing the price to decrease in monotonic mode
+    SlashingSignalled { last_price: Price },
+    /// price was forced by an authority, bypassing oracle and price checks
+    PriceOverridden(PriceOverride),
+    /// admin `by` granted the role to the account
+    RoleGranted {
+        account: AccountId,
+        role: Role,
+        by: AccountId,
+    },
+    /// admin `by` revoked the role of the account
+    RoleRevoked {
+        account: AccountId,
+        role: Role,
+        by: AccountId,
+    },
+    /// deposits, withdrawals and swaps were paused by the account
+    Paused { by: AccountId },
+    /// paused pool was resumed once the scheduled `PrivilegedAction::Unpause` executed
+    Unpaused,
+    /// privileged action was queued behind the timelock
+    ActionScheduled {
+        id: u64,
+        action: PrivilegedAction,
+        executable_at: Epoch,
+    },
+    /// scheduled privileged action was dropped by the admin
+    ActionCancelled { id: u64, by: AccountId },
+    /// limit order was queued by the owner
+    OrderPlaced {
+        id: u64,
+        owner: AccountId,
+        amount: StakedTokenAmount,
+        min_rate: Price,
+    },
+    /// limit order was filled in part or in whole, the swap is reported by `Swapped` as well
+    OrderFilled {
+        id: u64,
+        amount: StakedTokenAmount,
+        amount_out: TokenAmount,
+    },
+    /// limit order was cancelled by the owner
+    OrderCancelled {
+        id: u64,
+        unfilled: StakedTokenAmount,
+    },
+    /// limit order reached its expiry before it was filled
+    OrderExpired {
+        id: u64,
+        unfilled: StakedTokenAmount,
+    },
+}
+
+/// events kept by the queue of a pool with `bounded-events`, older ones are dropped
+#[cfg(feature = "bounded-events")]
+pub const EVENT_QUEUE_CAPACITY: usize = 1024;
+
+#[derive(Debug, Default)]
+/// Events waiting for `LpPool::drain_events`. With `bounded-events` the queue is
+/// allocated once with room for `EVENT_QUEUE_CAPACITY` events and drops the oldest event
+/// when full, so emitting never allocates.
+pub(crate) struct EventQueue {
+    events: VecDeque<PoolEvent>,
+    #[cfg(feature = "bounded-events")]
+    dropped: u64,
+}
+
+impl EventQueue {
+    pub(crate) fn new() -> Self {
+        Self {
+            #[cfg(feature = "bounded-events")]
+            events: VecDeque::with_capacity(EVENT_QUEUE_CAPACITY),
+            #[cfg(not(feature = "bounded-events"))]
+            events: VecDeque::new(),
+            #[cfg(feature = "bounded-events")]
+            dropped: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, event: PoolEvent) {
+        #[cfg(feature = "bounded-events")]
+        if self.events.len() == EVENT_QUEUE_CAPACITY {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+        self.events.push_back(event);
+    }
+
+    /// Returns queued events, keeping the allocation of the queue for further events
+    #[cfg(feature = "bounded-events")]
+    pub(crate) fn drain(&mut self) -> Vec<PoolEvent> {
+        self.events.drain(..).collect()
+    }
+
+    #[cfg(not(feature = "bounded-events"))]
+    pub(crate) fn drain(&mut self) -> Vec<PoolEvent> {
+        Vec::from(std::mem::take(&mut self.events))
+    }
+
+    /// Returns amount of events dropped because the queue was full
+    #[cfg(feature = "bounded-events")]
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+/// Receiver of pool events, called for every event as it's emitted
+pub trait EventSink: Debug + Send + Sync {
+    fn on_event(&mut self, event: &PoolEvent);
+}
+
+/// Forwards events to the channel, events emitted after the receiver was dropped are lost
+impl EventSink for Sender<PoolEvent> {
+    fn on_event(&mut self, event: &PoolEvent) {
+        let _ = self.send(event.clone());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Audit record of a manual price override
+pub struct PriceOverride {
+    pub epoch: Epoch,
+    pub previous_price: Price,
+    pub price: Price,
+    pub reason: String,
+}
+
+impl ToJson for PriceOverride {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("epoch", self.epoch.to_json()),
+            ("previous_price", self.previous_price.to_json()),
+            ("price", self.price.to_json()),
+            ("reason", self.reason.to_json()),
+        ])
+    }
+}
+
+impl FromJson for PriceOverride {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        Ok(Self {
+            epoch: field(value, "epoch")?,
+            previous_price: field(value, "previous_price")?,
+            price: field(value, "price")?,
+            reason: field(value, "reason")?,
+        })
+    }
+}
@@ -0,0 +1,185 @@
+//! Linear fee curve with the values derived from its parameters computed once, so that
+//! quoting a fee on the hot path doesn't recompute them. Pools at or above their
+//! liquidity target, and curves which are flat because their fees are inverted, equal
+//! or lack a target, charge min fee without any division.
+//!
+//! Surcharges added on top of the curve are passed unrounded, scaled by `SCALE`, and the
+//! whole fee is rounded once, up in favor of LPs, instead of rounding the base fee and
+//! every surcharge separately.
+
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Base fee curve `max_fee - (max_fee - min_fee) * amount_after / target`, capped at min
+/// fee
+pub(crate) struct FeeCurve {
+    min_fee: Uint,
+    max_fee: Uint,
+    target: Uint,
+    /// `max_fee - min_fee`, zero for inverted fees
+    spread: Uint,
+    /// token amount from which on min fee is charged, zero if the curve is flat
+    flat_from: Uint,
+}
+
+impl FeeCurve {
+    pub(crate) fn new(
+        min_fee: Percentage,
+        max_fee: Percentage,
+        liquidity_target: TokenAmount,
+    ) -> Self {
+        let (min_fee, max_fee) = (min_fee.raw(), max_fee.raw());
+        let spread = max_fee.saturating_sub(min_fee);
+        let target = liquidity_target.raw();
+        // `spread * amount / target` reaches the spread at the target, so the fee bottoms
+        // out there; without a spread or a target the fee is min fee everywhere
+        let flat_from = match spread {
+            0 => 0,
+            _ => target,
+        };
+        Self {
+            min_fee,
+            max_fee,
+            target,
+            spread,
+            flat_from,
+        }
+    }
+
+    /// Returns base fee plus `surcharge / SCALE` rounded up, capped at 100%
+    ///
+    /// # Arguments
+    ///
+    /// * `amount_after` - token amount left in the pool after the operation
+    /// * `surcharge` - raw percentage added on top of the base fee, multiplied by `SCALE`
+    #[inline]
+    pub(crate) fn fee_with_surcharge(
+        &self,
+        amount_after: TokenAmount,
+        surcharge: Uint,
+    ) -> Percentage {
+        let amount = amount_after.raw();
+        if surcharge > 0 {
+            return Percentage::from_raw_amount(self.surcharged_fee(amount, surcharge));
+        }
+        if amount >= self.flat_from {
+            return Percentage::from_raw_amount(self.min_fee.min(SCALE));
+        }
+        // rounding `max_fee - spread * amount / target` up is truncating the subtrahend,
+        // which stays below the spread, so the product usually fits into `Uint`
+        let rhs = mul_div(self.spread, amount, self.target).unwrap_or(self.spread);
+        Percentage::from_raw_amount((self.max_fee - rhs).min(SCALE))
+    }
+
+    #[inline(never)]
+    fn surcharged_fee(&self, amount: Uint, surcharge: Uint) -> Uint {
+        let scale = SCALE as u128;
+        // surcharges above 100% are capped anyway, bounding the products below
+        let surcharge = (surcharge as u128).min(scale * scale);
+        let fee = match amount >= self.flat_from {
+            true => self.min_fee as u128 + surcharge.div_ceil(scale),
+            false => {
+                // fee = (max_fee * target - spread * amount) / target + surcharge / SCALE
+                let target = self.target as u128;
+                let base = self.max_fee as u128 * target - self.spread as u128 * amount as u128;
+                match base
+                    .checked_mul(scale)
+                    .and_then(|base| base.checked_add(surcharge * target))
+                {
+                    Some(fee) => fee.div_ceil(target * scale),
+                    // fees far above 100% round the base fee on its own
+                    None => base.div_ceil(target) + surcharge.div_ceil(scale),
+                }
+            }
+        };
+        fee.min(scale) as Uint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    /// Formula the curve replaces, evaluated from scratch
+    fn uncached(min_fee: Uint, max_fee: Uint, target: Uint, amount: Uint) -> Uint {
+        let spread = max_fee.saturating_sub(min_fee) as u128;
+        let rhs = match target {
+            0 => max_fee as u128,
+            target => spread * amount as u128 / target as u128,
+        };
+        let rhs = rhs.min(max_fee as u128) as Uint;
+        (max_fee - rhs).max(min_fee)
+    }
+
+    #[test]
+    fn matches_uncached_formula_without_surcharge() {
+        let mut rng = Rng::new(11);
+        let edges = [0, 1, 1_000, SCALE / 2, SCALE, SCALE + 1, Uint::MAX];
+        let pick = |rng: &mut Rng| match rng.chance(0.5) {
+            true => edges[rng.below(edges.len() as u64) as usize],
+            false => rng.range(0, 2 * SCALE),
+        };
+        for _ in 0..10_000 {
+            let (min_fee, max_fee) = (pick(&mut rng), pick(&mut rng));
+            let target = rng.range(0, 1_000 * SCALE);
+            let curve = FeeCurve::new(
+                Percentage::from_raw_amount(min_fee),
+                Percentage::from_raw_amount(max_fee),
+                TokenAmount::from_raw_amount(target),
+            );
+            for amount in [
+                0,
+                1,
+                target / 3,
+                target.saturating_sub(1),
+                target,
+                Uint::MAX,
+            ] {
+                assert_eq!(
+                    curve
+                        .fee_with_surcharge(TokenAmount::from_raw_amount(amount), 0)
+                        .raw(),
+                    uncached(min_fee, max_fee, target, amount).min(SCALE),
+                    "{min_fee} {max_fee} {target} {amount}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rounds_surcharged_fee_once() {
+        let mut rng = Rng::new(12);
+        for _ in 0..10_000 {
+            let min_fee = rng.range(0, SCALE / 10);
+            let max_fee = rng.range(min_fee, SCALE);
+            let target = rng.range(1, 1_000 * SCALE);
+            let curve = FeeCurve::new(
+                Percentage::from_raw_amount(min_fee),
+                Percentage::from_raw_amount(max_fee),
+                TokenAmount::from_raw_amount(target),
+            );
+            let surcharge = rng.range(0, SCALE * SCALE / 5);
+            for amount in [0, 1, target / 3, target.saturating_sub(1), target] {
+                let exact = (max_fee as f64
+                    - (max_fee - min_fee) as f64 * (amount.min(target) as f64 / target as f64))
+                    + surcharge as f64 / SCALE as f64;
+                let fee = curve
+                    .fee_with_surcharge(TokenAmount::from_raw_amount(amount), surcharge)
+                    .raw();
+                // rounding each term separately could end up a unit lower, never higher
+                let stepwise =
+                    (uncached(min_fee, max_fee, target, amount) + surcharge / SCALE).min(SCALE);
+                assert!(fee == stepwise || fee == stepwise + 1, "{fee} {stepwise}");
+                assert!(
+                    fee as f64 >= exact.min(SCALE as f64) - 1e-3,
+                    "{fee} {exact}"
+                );
+                assert!(fee as f64 - exact < 1.0 + 1e-3, "{fee} {exact}");
+                if surcharge.is_multiple_of(SCALE) {
+                    assert_eq!(fee, stepwise);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::error::SchemaError;
+use crate::json::Value;
+use crate::price_history::PriceHistory;
+use crate::schema::{field, FromJson, ToJson};
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Policy deciding how the base (liquidity based) fee is adjusted before it's charged
+pub enum FeePolicy {
+    /// base fee is charged as is
+    #[default]
+    Linear,
+    /// base fee is widened proportionally to the volatility of recent price updates,
+    /// protecting LPs during depeg-like events
+    VolatilitySensitive {
+        /// multiplier applied to the measured volatility, `2.0` doubles it
+        sensitivity: Percentage,
+        /// upper bound of the fee added on top of the base fee
+        max_surcharge: Percentage,
+    },
+}
+
+impl FeePolicy {
+    /// Returns fee that should be charged given the base fee and recent price history.
+    /// Returned fee never exceeds 100%.
+    pub fn apply(&self, base_fee: Percentage, price_history: &PriceHistory) -> Percentage {
+        let surcharge = self.scaled_surcharge(price_history) / SCALE as u128;
+        let fee = base_fee.raw() as u128 + surcharge;
+        Percentage::from_raw_amount(fee.min(SCALE as u128) as Uint)
+    }
+
+    /// Returns raw fee added on top of the base fee multiplied by `SCALE`, i.e. before
+    /// rounding. It doesn't depend on the base fee, so it can be computed once for many
+    /// quotes.
+    pub fn scaled_surcharge(&self, price_history: &PriceHistory) -> u128 {
+        match self {
+            FeePolicy::Linear => 0,
+            FeePolicy::VolatilitySensitive {
+                sensitivity,
+                max_surcharge,
+            } => {
+                let volatility = price_history.volatility().raw() as u128;
+                (volatility * sensitivity.raw() as u128)
+                    .min(max_surcharge.raw() as u128 * SCALE as u128)
+            }
+        }
+    }
+}
+
+impl ToJson for FeePolicy {
+    fn to_json(&self) -> Value {
+        match self {
+            FeePolicy::Linear => Value::object([("type", "linear".into())]),
+            FeePolicy::VolatilitySensitive {
+                sensitivity,
+                max_surcharge,
+            } => Value::object([
+                ("type", "volatility_sensitive".into()),
+                ("sensitivity", sensitivity.to_json()),
+                ("max_surcharge", max_surcharge.to_json()),
+            ]),
+        }
+    }
+}
+
+impl FromJson for FeePolicy {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        match field::<String>(value, "type")?.as_str() {
+            "linear" => Ok(FeePolicy::Linear),
+            "volatility_sensitive" => Ok(FeePolicy::VolatilitySensitive {
+                sensitivity: field(value, "sensitivity")?,
+                max_surcharge: field(value, "max_surcharge")?,
+            }),
+            other => Err(SchemaError::InvalidValue {
+                path: "type".into(),
+                reason: format!("unknown variant `{other}`"),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volatile_history() -> PriceHistory {
+        let mut history = PriceHistory::default();
+        history.push(0, 1.into());
+        history.push(0, 1.1.into());
+        history.push(0, 0.99.into());
+        history
+    }
+
+    #[test]
+    fn linear_policy_keeps_base_fee() {
+        let fee = FeePolicy::Linear.apply(0.05.into(), &volatile_history());
+        assert_eq!(fee, Percentage::from(0.05));
+    }
+
+    #[test]
+    fn volatility_widens_fee() {
+        let policy = FeePolicy::VolatilitySensitive {
+            sensitivity: 0.5.into(),
+            max_surcharge: 1.0.into(),
+        };
+        let fee = policy.apply(0.01.into(), &volatile_history());
+        assert_eq!(fee, Percentage::from(0.06));
+    }
+
+    #[test]
+    fn surcharge_is_capped() {
+        let policy = FeePolicy::VolatilitySensitive {
+            sensitivity: 10.0.into(),
+            max_surcharge: 0.02.into(),
+        };
+        let fee = policy.apply(0.01.into(), &volatile_history());
+        assert_eq!(fee, Percentage::from(0.03));
+    }
+}
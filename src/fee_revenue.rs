@@ -0,0 +1,142 @@
+use crate::error::SchemaError;
+use crate::json::Value;
+use crate::schema::{field, field_or_default, FromJson, ToJson};
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Cumulative fee revenue of the pool split between LPs, the treasury and the insurance
+/// fund
+pub struct FeeRevenue {
+    /// all fees ever charged by the pool
+    pub total: TokenAmount,
+    /// portion of fees left in the pool, reflected in LP token value
+    pub lp: TokenAmount,
+    /// portion of fees set aside for the treasury, claimed or not
+    pub treasury: TokenAmount,
+    /// treasury fees that were not claimed yet
+    pub claimable_treasury: TokenAmount,
+    /// portion of fees paid into the insurance fund
+    pub insurance: TokenAmount,
+}
+
+impl FeeRevenue {
+    /// Records charged fee and returns treasury portion of it.
+    ///
+    /// # Arguments
+    ///
+    /// * `fee_amount` - total fee charged by the operation
+    /// * `treasury_cut` - share of the fee that belongs to the treasury
+    pub fn record(&mut self, fee_amount: TokenAmount, treasury_cut: Percentage) -> TokenAmount {
+        self.record_split(fee_amount, treasury_cut, Percentage::from_raw_amount(0))
+            .0
+    }
+
+    /// Records charged fee and returns its treasury and insurance portions. Both cuts are
+    /// taken from the whole fee, the insurance portion is capped at what the treasury left.
+    ///
+    /// # Arguments
+    ///
+    /// * `fee_amount` - total fee charged by the operation
+    /// * `treasury_cut` - share of the fee that belongs to the treasury
+    /// * `insurance_cut` - share of the fee paid into the insurance fund
+    pub fn record_split(
+        &mut self,
+        fee_amount: TokenAmount,
+        treasury_cut: Percentage,
+        insurance_cut: Percentage,
+    ) -> (TokenAmount, TokenAmount) {
+        let portion = |cut: Percentage| {
+            TokenAmount::from_raw_amount(
+                (fee_amount.raw() as u128 * cut.raw().min(SCALE) as u128 / SCALE as u128) as Uint,
+            )
+        };
+        let treasury_portion = portion(treasury_cut);
+        let insurance_portion = TokenAmount::from_raw_amount(
+            portion(insurance_cut)
+                .raw()
+                .min((fee_amount - treasury_portion).raw()),
+        );
+        let lp_portion = fee_amount - treasury_portion - insurance_portion;
+
+        self.total = self.total + fee_amount;
+        self.lp = self.lp + lp_portion;
+        self.treasury = self.treasury + treasury_portion;
+        self.claimable_treasury = self.claimable_treasury + treasury_portion;
+        self.insurance = self.insurance + insurance_portion;
+
+        (treasury_portion, insurance_portion)
+    }
+
+    /// Marks all claimable treasury fees as claimed and returns their amount
+    pub fn claim_treasury(&mut self) -> TokenAmount {
+        std::mem::replace(
+            &mut self.claimable_treasury,
+            TokenAmount::from_raw_amount(0),
+        )
+    }
+}
+
+impl ToJson for FeeRevenue {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("total", self.total.to_json()),
+            ("lp", self.lp.to_json()),
+            ("treasury", self.treasury.to_json()),
+            ("claimable_treasury", self.claimable_treasury.to_json()),
+            ("insurance", self.insurance.to_json()),
+        ])
+    }
+}
+
+impl FromJson for FeeRevenue {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        Ok(Self {
+            total: field(value, "total")?,
+            lp: field(value, "lp")?,
+            treasury: field(value, "treasury")?,
+            claimable_treasury: field(value, "claimable_treasury")?,
+            insurance: field_or_default(value, "insurance")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_fees_between_lp_and_treasury() {
+        let mut revenue = FeeRevenue::default();
+        let treasury = revenue.record(10.into(), 0.25.into());
+
+        assert_eq!(treasury, TokenAmount::from(2.5));
+        assert_eq!(revenue.total, TokenAmount::from(10));
+        assert_eq!(revenue.lp, TokenAmount::from(7.5));
+        assert_eq!(revenue.claimable_treasury, TokenAmount::from(2.5));
+    }
+
+    #[test]
+    fn claim_resets_claimable_fees() {
+        let mut revenue = FeeRevenue::default();
+        revenue.record(10.into(), 0.5.into());
+
+        assert_eq!(revenue.claim_treasury(), TokenAmount::from(5));
+        assert_eq!(revenue.claimable_treasury, TokenAmount::from(0));
+        assert_eq!(revenue.treasury, TokenAmount::from(5));
+    }
+
+    #[test]
+    fn caps_insurance_at_fee_left_by_treasury() {
+        let mut revenue = FeeRevenue::default();
+        assert_eq!(
+            revenue.record_split(10.into(), 0.25.into(), 0.1.into()),
+            (2.5.into(), 1.into())
+        );
+        assert_eq!(
+            revenue.record_split(10.into(), 0.75.into(), 0.5.into()),
+            (7.5.into(), 2.5.into())
+        );
+        assert_eq!(revenue.lp, TokenAmount::from(6.5));
+        assert_eq!(revenue.insurance, TokenAmount::from(3.5));
+    }
+}
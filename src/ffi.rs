@@ -0,0 +1,323 @@
+//! C interface of the pool. Pools are opaque handles created with `lp_pool_new` and
+//! released with `lp_pool_free`, amounts are raw fixed-point `uint64_t` values with 6
+//! decimals and every fallible function returns one of the `LP_POOL_*` codes. The
+//! matching declarations are kept in `include/lp_pool.h`. Build a linkable library with
+//! `cargo rustc --release --features ffi --crate-type staticlib` (or `cdylib`).
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::error::*;
+use crate::lp_pool::LpPool;
+use crate::types::*;
+
+pub const LP_POOL_OK: i32 = 0;
+pub const LP_POOL_NULL_POINTER: i32 = 1;
+pub const LP_POOL_ZERO_AMOUNT: i32 = 2;
+pub const LP_POOL_NOT_ENOUGH_LIQUIDITY: i32 = 3;
+pub const LP_POOL_NOT_ENOUGH_LP_TOKENS: i32 = 4;
+pub const LP_POOL_OVERFLOW: i32 = 5;
+pub const LP_POOL_STALE_PRICE: i32 = 6;
+pub const LP_POOL_ORACLE_ERROR: i32 = 7;
+pub const LP_POOL_PRICE_REJECTED: i32 = 8;
+pub const LP_POOL_PANIC: i32 = 9;
+pub const LP_POOL_HOOK_REJECTED: i32 = 10;
+pub const LP_POOL_UNREPLAYABLE: i32 = 11;
+
+fn error_code(error: OpError) -> i32 {
+    match error {
+        OpError::AddLiquidity(AddLiquidityError::NoTokensProvided)
+        | OpError::Swap(SwapError::ZeroTokensAsArgument)
+        | OpError::PriceUpdate(PriceUpdateError::ZeroPrice) => LP_POOL_ZERO_AMOUNT,
+        OpError::AddLiquidity(AddLiquidityError::TokenAmountTooBig)
+        | OpError::Swap(SwapError::AmountTooBig)
+        | OpError::RemoveLiquidity(RemoveLiquidityError::WithdrawCalculationOverflow) => {
+            LP_POOL_OVERFLOW
+        }
+        OpError::RemoveLiquidity(_) => LP_POOL_NOT_ENOUGH_LP_TOKENS,
+        OpError::AddLiquidity(AddLiquidityError::PoolWithoutValue)
+        | OpError::Swap(SwapError::PoolNotEnoughTokens { .. }) => LP_POOL_NOT_ENOUGH_LIQUIDITY,
+        OpError::Swap(SwapError::StalePrice { .. }) => LP_POOL_STALE_PRICE,
+        OpError::AddLiquidity(AddLiquidityError::Oracle(_))
+        | OpError::Swap(SwapError::Oracle(_))
+        | OpError::PriceUpdate(PriceUpdateError::Oracle(_)) => LP_POOL_ORACLE_ERROR,
+        OpError::PriceUpdate(_) => LP_POOL_PRICE_REJECTED,
+        OpError::Hook(_) => LP_POOL_HOOK_REJECTED,
+        OpError::Unreplayable => LP_POOL_UNREPLAYABLE,
+    }
+}
+
+/// Runs operation on the pool behind the handle, panics never cross the C boundary
+///
+/// # Safety
+///
+/// `pool` has to be null or a live handle returned by `lp_pool_new`
+unsafe fn with_pool(pool: *mut LpPool, f: impl FnOnce(&mut LpPool) -> Result<(), OpError>) -> i32 {
+    let Some(pool) = pool.as_mut() else {
+        return LP_POOL_NULL_POINTER;
+    };
+    match catch_unwind(AssertUnwindSafe(|| f(pool))) {
+        Ok(Ok(())) => LP_POOL_OK,
+        Ok(Err(error)) => error_code(error),
+        Err(_) => LP_POOL_PANIC,
+    }
+}
+
+/// Writes value to the output pointer, null outputs are skipped
+///
+/// # Safety
+///
+/// `out` has to be null or valid for writes
+unsafe fn write(out: *mut Uint, value: Uint) {
+    if let Some(out) = out.as_mut() {
+        *out = value;
+    }
+}
+
+/// Creates pool, the handle has to be released with `lp_pool_free`
+#[no_mangle]
+pub extern "C" fn lp_pool_new(
+    price: Uint,
+    min_fee: Uint,
+    max_fee: Uint,
+    liquidity_target: Uint,
+) -> *mut LpPool {
+    let pool = LpPool::init(
+        Price::from_raw_amount(price),
+        Percentage::from_raw_amount(min_fee),
+        Percentage::from_raw_amount(max_fee),
+        TokenAmount::from_raw_amount(liquidity_target),
+    )
+    .unwrap_or_else(|never| match never {});
+    Box::into_raw(Box::new(pool))
+}
+
+/// Releases pool
+///
+/// # Safety
+///
+/// `pool` has to be null or a handle returned by `lp_pool_new` which wasn't freed yet
+#[no_mangle]
+pub unsafe extern "C" fn lp_pool_free(pool: *mut LpPool) {
+    if !pool.is_null() {
+        drop(Box::from_raw(pool));
+    }
+}
+
+/// Adds liquidity and writes minted lp tokens to `lp_amount_out`
+///
+/// # Safety
+///
+/// `pool` has to be a live handle, `lp_amount_out` null or valid for writes
+#[no_mangle]
+pub unsafe extern "C" fn lp_pool_add_liquidity(
+    pool: *mut LpPool,
+    amount: Uint,
+    lp_amount_out: *mut Uint,
+) -> i32 {
+    with_pool(pool, |pool| {
+        let lp_amount = pool.add_liquidity(TokenAmount::from_raw_amount(amount))?;
+        write(lp_amount_out, lp_amount.raw());
+        Ok(())
+    })
+}
+
+/// Removes liquidity and writes withdrawn tokens and staked tokens to the outputs
+///
+/// # Safety
+///
+/// `pool` has to be a live handle, outputs null or valid for writes
+#[no_mangle]
+pub unsafe extern "C" fn lp_pool_remove_liquidity(
+    pool: *mut LpPool,
+    lp_amount: Uint,
+    token_amount_out: *mut Uint,
+    st_token_amount_out: *mut Uint,
+) -> i32 {
+    with_pool(pool, |pool| {
+        let (tokens, staked_tokens) =
+            pool.remove_liquidity(LpTokenAmount::from_raw_amount(lp_amount))?;
+        write(token_amount_out, tokens.raw());
+        write(st_token_amount_out, staked_tokens.raw());
+        Ok(())
+    })
+}
+
+/// Swaps staked tokens and writes received tokens to `token_amount_out`
+///
+/// # Safety
+///
+/// `pool` has to be a live handle, `token_amount_out` null or valid for writes
+#[no_mangle]
+pub unsafe extern "C" fn lp_pool_swap(
+    pool: *mut LpPool,
+    amount: Uint,
+    token_amount_out: *mut Uint,
+) -> i32 {
+    with_pool(pool, |pool| {
+        let tokens = pool.swap(StakedTokenAmount::from_raw_amount(amount))?;
+        write(token_amount_out, tokens.raw());
+        Ok(())
+    })
+}
+
+/// Writes tokens and fee a swap would result in, without modifying the pool
+///
+/// # Safety
+///
+/// `pool` has to be a live handle, outputs null or valid for writes
+#[no_mangle]
+pub unsafe extern "C" fn lp_pool_quote_swap(
+    pool: *mut LpPool,
+    amount: Uint,
+    token_amount_out: *mut Uint,
+    fee_out: *mut Uint,
+) -> i32 {
+    with_pool(pool, |pool| {
+        let amount = StakedTokenAmount::from_raw_amount(amount);
+        write(token_amount_out, pool.amount_for_swap(amount)?.raw());
+        write(fee_out, pool.fee_for_swap(amount)?.raw());
+        Ok(())
+    })
+}
+
+/// Sets price of staked tokens
+///
+/// # Safety
+///
+/// `pool` has to be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn lp_pool_set_price(pool: *mut LpPool, price: Uint) -> i32 {
+    with_pool(pool, |pool| {
+        Ok(pool.set_price(Price::from_raw_amount(price))?)
+    })
+}
+
+/// Advances pool clock by the given amount of epochs
+///
+/// # Safety
+///
+/// `pool` has to be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn lp_pool_advance_epoch(pool: *mut LpPool, epochs: Epoch) -> i32 {
+    with_pool(pool, |pool| {
+        pool.advance_epoch(epochs);
+        Ok(())
+    })
+}
+
+/// Writes pool balances to the outputs
+///
+/// # Safety
+///
+/// `pool` has to be a live handle, outputs null or valid for writes
+#[no_mangle]
+pub unsafe extern "C" fn lp_pool_balances(
+    pool: *mut LpPool,
+    token_amount_out: *mut Uint,
+    st_token_amount_out: *mut Uint,
+    lp_token_amount_out: *mut Uint,
+    price_out: *mut Uint,
+) -> i32 {
+    with_pool(pool, |pool| {
+        write(token_amount_out, pool.token_amount().raw());
+        write(st_token_amount_out, pool.st_token_amount().raw());
+        write(lp_token_amount_out, pool.lp_token_amount().raw());
+        write(price_out, pool.price().raw());
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::null_mut;
+
+    use super::*;
+
+    #[test]
+    fn drives_pool_through_c_interface() {
+        let pool = lp_pool_new(1_500_000, 1_000, 90_000, 90_000_000);
+        let (mut first, mut second) = (0, 0);
+        unsafe {
+            assert_eq!(
+                lp_pool_add_liquidity(pool, 100_000_000, &mut first),
+                LP_POOL_OK
+            );
+            assert_eq!(first, 100_000_000);
+
+            assert_eq!(
+                lp_pool_quote_swap(pool, 6_000_000, &mut first, &mut second),
+                LP_POOL_OK
+            );
+            assert_eq!((first, second), (8_991_000, 1_000));
+            assert_eq!(lp_pool_swap(pool, 6_000_000, &mut first), LP_POOL_OK);
+            assert_eq!(first, 8_991_000);
+
+            assert_eq!(lp_pool_swap(pool, 0, null_mut()), LP_POOL_ZERO_AMOUNT);
+            assert_eq!(
+                lp_pool_swap(pool, 1_000_000_000, null_mut()),
+                LP_POOL_NOT_ENOUGH_LIQUIDITY
+            );
+            assert_eq!(
+                lp_pool_remove_liquidity(pool, 1_000_000_000, null_mut(), null_mut()),
+                LP_POOL_NOT_ENOUGH_LP_TOKENS
+            );
+            assert_eq!(lp_pool_set_price(pool, 0), LP_POOL_ZERO_AMOUNT);
+            assert_eq!(lp_pool_advance_epoch(pool, 1), LP_POOL_OK);
+
+            let mut staked = 0;
+            assert_eq!(
+                lp_pool_balances(pool, null_mut(), &mut staked, null_mut(), null_mut()),
+                LP_POOL_OK
+            );
+            assert_eq!(staked, 6_000_000);
+
+            assert_eq!(
+                lp_pool_swap(null_mut(), 1, null_mut()),
+                LP_POOL_NULL_POINTER
+            );
+            lp_pool_free(pool);
+            lp_pool_free(null_mut());
+        }
+    }
+
+    #[test]
+    fn header_declares_every_function() {
+        let header = include_str!("../include/lp_pool.h");
+        for function in [
+            "lp_pool_new",
+            "lp_pool_free",
+            "lp_pool_add_liquidity",
+            "lp_pool_remove_liquidity",
+            "lp_pool_swap",
+            "lp_pool_quote_swap",
+            "lp_pool_set_price",
+            "lp_pool_advance_epoch",
+            "lp_pool_balances",
+        ] {
+            assert!(
+                header.contains(&format!(" {function}("))
+                    || header.contains(&format!("*{function}(")),
+                "{function}"
+            );
+        }
+        for (name, code) in [
+            ("LP_POOL_OK", LP_POOL_OK),
+            ("LP_POOL_NULL_POINTER", LP_POOL_NULL_POINTER),
+            ("LP_POOL_ZERO_AMOUNT", LP_POOL_ZERO_AMOUNT),
+            ("LP_POOL_NOT_ENOUGH_LIQUIDITY", LP_POOL_NOT_ENOUGH_LIQUIDITY),
+            ("LP_POOL_NOT_ENOUGH_LP_TOKENS", LP_POOL_NOT_ENOUGH_LP_TOKENS),
+            ("LP_POOL_OVERFLOW", LP_POOL_OVERFLOW),
+            ("LP_POOL_STALE_PRICE", LP_POOL_STALE_PRICE),
+            ("LP_POOL_ORACLE_ERROR", LP_POOL_ORACLE_ERROR),
+            ("LP_POOL_PRICE_REJECTED", LP_POOL_PRICE_REJECTED),
+            ("LP_POOL_PANIC", LP_POOL_PANIC),
+            ("LP_POOL_HOOK_REJECTED", LP_POOL_HOOK_REJECTED),
+            ("LP_POOL_UNREPLAYABLE", LP_POOL_UNREPLAYABLE),
+        ] {
+            assert!(
+                header.contains(&format!("#define {name} {code}\n")),
+                "{name}"
+            );
+        }
+    }
+}
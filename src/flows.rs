@@ -0,0 +1,289 @@
+//! Loading of recorded unstake flows and their replay through the pool, so fee curves and
+//! liquidity targets can be evaluated against real demand instead of generated one.
+//! Flows are stored as CSV with a `timestamp,amount[,price]` header
+//!
+//! ```csv
+//! timestamp,amount,price
+//! 1700000000,12.5,1.143021
+//! 1700003600,0.25,
+//! ```
+//!
+//! or as a JSON array of `{"timestamp": 1700000000, "amount": "12.5", "price": "1.143021"}`
+//! objects, where amounts and prices can be strings or numbers. Timestamps are unix
+//! seconds and have to be non-decreasing, amounts are staked tokens unstaked by a single
+//! transaction and the optional price is the exchange rate at the time of the unstake.
+//! Digits below the crate's precision, e.g. of 9 decimal mSOL amounts, are truncated.
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::FlowError;
+use crate::json::Value;
+use crate::ops::PoolOp;
+use crate::replay::PoolConfig;
+use crate::simulator::{Recorder, Simulation};
+use crate::types::*;
+
+/// length of a Solana epoch in seconds, roughly two days
+pub const SECONDS_PER_EPOCH: u64 = 2 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Single recorded unstake
+pub struct UnstakeFlow {
+    /// unix timestamp in seconds
+    pub timestamp: u64,
+    pub amount: StakedTokenAmount,
+    /// exchange rate at the time of the unstake
+    pub price: Option<Price>,
+}
+
+impl UnstakeFlow {
+    /// Parses flows in the CSV format
+    pub fn from_csv(input: &str) -> Result<Vec<UnstakeFlow>, FlowError> {
+        let mut lines = input
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        let (_, header) = lines.next().ok_or(FlowError::Syntax {
+            record: 1,
+            reason: "missing header".into(),
+        })?;
+        let columns: Vec<_> = header.split(',').map(str::trim).collect();
+        if columns != ["timestamp", "amount"] && columns != ["timestamp", "amount", "price"] {
+            return Err(FlowError::Syntax {
+                record: 1,
+                reason: format!("expected `timestamp,amount[,price]` header, got `{header}`"),
+            });
+        }
+
+        let flows = lines
+            .map(|(line, text)| {
+                let fields: Vec<_> = text.split(',').map(str::trim).collect();
+                if fields.len() != columns.len() {
+                    return Err(syntax(line, "wrong number of fields"));
+                }
+                let price = fields.get(2).filter(|price| !price.is_empty());
+                Ok(UnstakeFlow {
+                    timestamp: fields[0]
+                        .parse()
+                        .map_err(|_| syntax(line, "expected integer timestamp"))?,
+                    amount: decimal(line, fields[1])?,
+                    price: price.map(|price| decimal(line, price)).transpose()?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        check_order(&flows, |index| index + 2)?;
+        Ok(flows)
+    }
+
+    /// Parses flows in the JSON format
+    pub fn from_json(input: &str) -> Result<Vec<UnstakeFlow>, FlowError> {
+        let value = Value::parse(input)?;
+        let records = value
+            .as_array()
+            .ok_or_else(|| syntax(0, "expected array of flows"))?;
+        let flows = records
+            .iter()
+            .enumerate()
+            .map(|(index, record)| {
+                let scalar = |key: &str| match record.get(key) {
+                    Some(Value::Number(literal) | Value::String(literal)) => Some(literal.as_str()),
+                    _ => None,
+                };
+                Ok::<_, FlowError>(UnstakeFlow {
+                    timestamp: record
+                        .get("timestamp")
+                        .and_then(Value::as_u64)
+                        .ok_or_else(|| syntax(index, "expected integer `timestamp`"))?,
+                    amount: decimal(
+                        index,
+                        scalar("amount").ok_or_else(|| syntax(index, "missing `amount`"))?,
+                    )?,
+                    price: scalar("price")
+                        .map(|price| decimal(index, price))
+                        .transpose()?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        check_order(&flows, |index| index)?;
+        Ok(flows)
+    }
+
+    /// Loads flows from a `.csv` or `.json` file
+    pub fn load(path: impl AsRef<Path>) -> Result<Vec<UnstakeFlow>, FlowError> {
+        let path = path.as_ref();
+        let input = fs::read_to_string(path)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => Self::from_csv(&input),
+            Some("json") => Self::from_json(&input),
+            _ => Err(FlowError::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+}
+
+fn syntax(record: usize, reason: &str) -> FlowError {
+    FlowError::Syntax {
+        record,
+        reason: reason.into(),
+    }
+}
+
+fn decimal<T: std::str::FromStr>(record: usize, literal: &str) -> Result<T, FlowError> {
+    literal
+        .parse()
+        .map_err(|_| syntax(record, &format!("`{literal}` is not a valid decimal")))
+}
+
+/// Checks timestamps don't decrease, `record` maps index of a flow to its record number
+fn check_order(flows: &[UnstakeFlow], record: impl Fn(usize) -> usize) -> Result<(), FlowError> {
+    match flows
+        .windows(2)
+        .position(|pair| pair[1].timestamp < pair[0].timestamp)
+    {
+        Some(index) => Err(FlowError::Unordered {
+            record: record(index + 1),
+        }),
+        None => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Settings of a flow replay
+pub struct FlowReplay {
+    /// liquidity deposited before the first flow
+    pub initial_liquidity: TokenAmount,
+    /// seconds in a single pool epoch
+    pub epoch_seconds: u64,
+}
+
+impl FlowReplay {
+    pub fn new(initial_liquidity: TokenAmount) -> Self {
+        Self {
+            initial_liquidity,
+            epoch_seconds: SECONDS_PER_EPOCH,
+        }
+    }
+}
+
+/// Replays recorded flows through a pool built from the configuration. Epoch of the
+/// first flow is the pool's first epoch, later flows advance the clock by the epochs
+/// passed since then, and recorded prices are set before the unstake they belong to.
+/// Unstakes the pool can't serve are counted as failed operations.
+pub fn replay_flows(config: &PoolConfig, flows: &[UnstakeFlow], replay: &FlowReplay) -> Simulation {
+    let mut pool = config.build();
+    let mut recorder = Recorder::new(&pool);
+    recorder.run(
+        &mut pool,
+        PoolOp::AddLiquidity {
+            account: None,
+            amount: replay.initial_liquidity,
+        },
+    );
+
+    let start = flows.first().map(|flow| flow.timestamp).unwrap_or_default();
+    for flow in flows {
+        let epoch = (flow.timestamp - start) / replay.epoch_seconds.max(1);
+        if epoch > pool.epoch() {
+            let epochs = epoch - pool.epoch();
+            recorder.run(&mut pool, PoolOp::AdvanceEpoch { epochs });
+        }
+        if let Some(price) = flow.price.filter(|price| *price != pool.price()) {
+            recorder.run(&mut pool, PoolOp::SetPrice { price });
+        }
+        recorder.run(
+            &mut pool,
+            PoolOp::Swap {
+                account: None,
+                amount: flow.amount,
+            },
+        );
+    }
+    recorder.finish(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::governance::PoolParams;
+
+    const CSV: &str = "timestamp,amount,price
+        1700000000,12.5,1.1
+        1700003600,0.25,
+        1700200000,40.123456789,1.2
+        1700400000,70,
+    ";
+
+    fn config(liquidity_target: u64) -> PoolConfig {
+        PoolConfig::new(
+            1.1.into(),
+            PoolParams {
+                min_fee: 0.001.into(),
+                max_fee: 0.09.into(),
+                liquidity_target: liquidity_target.into(),
+            },
+        )
+    }
+
+    #[test]
+    fn loads_csv_and_json() {
+        let flows = UnstakeFlow::from_csv(CSV).unwrap();
+        assert_eq!(flows.len(), 4);
+        assert_eq!(
+            flows[2],
+            UnstakeFlow {
+                timestamp: 1_700_200_000,
+                amount: StakedTokenAmount::from_raw_amount(40_123_456),
+                price: Some(1.2.into()),
+            }
+        );
+        assert_eq!(flows[1].price, None);
+
+        let json = r#"[
+            {"timestamp": 1700000000, "amount": "12.5", "price": 1.1},
+            {"timestamp": 1700003600, "amount": 0.25},
+            {"timestamp": 1700200000, "amount": "40.123456789", "price": "1.2"},
+            {"timestamp": 1700400000, "amount": 70}
+        ]"#;
+        assert_eq!(UnstakeFlow::from_json(json).unwrap(), flows);
+    }
+
+    #[test]
+    fn rejects_invalid_flows() {
+        assert!(matches!(
+            UnstakeFlow::from_csv("time,amount\n1,2"),
+            Err(FlowError::Syntax { record: 1, .. })
+        ));
+        assert!(matches!(
+            UnstakeFlow::from_csv("timestamp,amount\n1,2\n3,x"),
+            Err(FlowError::Syntax { record: 3, .. })
+        ));
+        assert!(matches!(
+            UnstakeFlow::from_csv("timestamp,amount\n5,2\n3,1"),
+            Err(FlowError::Unordered { record: 3 })
+        ));
+        assert!(matches!(
+            UnstakeFlow::from_json(r#"[{"timestamp": 1}]"#),
+            Err(FlowError::Syntax { record: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn replays_flows_against_configs() {
+        let flows = UnstakeFlow::from_csv(CSV).unwrap();
+        let replay = FlowReplay::new(100.into());
+        let Simulation { pool, ops, stats } = replay_flows(&config(100), &flows, &replay);
+
+        assert_eq!(stats.successful_swaps, 3, "last unstake exceeds liquidity");
+        assert_eq!(stats.failed_ops, 1);
+        assert_eq!(pool.epoch(), 2);
+        assert_eq!(pool.price(), 1.2.into());
+        // deposit, swap, swap, epoch, price, swap, epoch, swap
+        assert_eq!(ops.len(), 8);
+
+        // a smaller target keeps fees lower for the same demand
+        let cheaper = replay_flows(&config(50), &flows, &replay).stats;
+        assert!(cheaper.fee_revenue < stats.fee_revenue);
+        assert!(cheaper.swap_volume > stats.swap_volume);
+    }
+}
@@ -0,0 +1,274 @@
+use crate::error::{GovernanceError, SchemaError, TimelockError};
+use crate::json::Value;
+use crate::schema::{field, FromJson, ToJson};
+use crate::timelock::{Queued, TimelockQueue};
+use crate::types::*;
+
+/// default amount of epochs a proposed parameter change has to wait before being applied
+pub const DEFAULT_GOVERNANCE_DELAY: Epoch = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Pool parameters that can be changed through governance
+pub struct PoolParams {
+    pub min_fee: Percentage,
+    pub max_fee: Percentage,
+    pub liquidity_target: TokenAmount,
+}
+
+impl PoolParams {
+    /// Checks if parameters describe a valid fee curve
+    pub fn validate(&self) -> Result<(), GovernanceError> {
+        if self.min_fee > self.max_fee || self.max_fee.raw() > SCALE {
+            return Err(GovernanceError::InvalidFees {
+                min_fee: self.min_fee,
+                max_fee: self.max_fee,
+            });
+        }
+        if self.liquidity_target.raw() == 0 {
+            return Err(GovernanceError::ZeroLiquidityTarget);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Parameter change waiting for its timelock to pass
+pub struct PendingUpdate {
+    pub params: PoolParams,
+    pub proposed_at: Epoch,
+    pub executable_at: Epoch,
+}
+
+impl From<Queued<PoolParams>> for PendingUpdate {
+    fn from(queued: Queued<PoolParams>) -> Self {
+        Self {
+            params: queued.action,
+            proposed_at: queued.queued_at,
+            executable_at: queued.executable_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Timelock queue holding at most one parameter change
+pub struct Governance {
+    queue: TimelockQueue<PoolParams>,
+}
+
+impl Governance {
+    pub fn new(delay: Epoch) -> Self {
+        Self {
+            queue: TimelockQueue::new(delay),
+        }
+    }
+
+    pub fn delay(&self) -> Epoch {
+        self.queue.delay()
+    }
+
+    pub fn set_delay(&mut self, delay: Epoch) {
+        self.queue.set_delay(delay);
+    }
+
+    pub fn pending(&self) -> Option<PendingUpdate> {
+        self.queue.queued().first().cloned().map(Into::into)
+    }
+
+    /// Records new pending update, fails if another update is already pending
+    pub fn propose(
+        &mut self,
+        params: PoolParams,
+        current_epoch: Epoch,
+    ) -> Result<PendingUpdate, GovernanceError> {
+        params.validate()?;
+        if !self.queue.queued().is_empty() {
+            return Err(GovernanceError::UpdateAlreadyPending);
+        }
+        Ok(self.queue.queue(params, current_epoch).clone().into())
+    }
+
+    /// Removes pending update if its timelock already passed
+    pub fn take_executable(
+        &mut self,
+        current_epoch: Epoch,
+    ) -> Result<PendingUpdate, GovernanceError> {
+        let id = self.pending_id()?;
+        match self.queue.execute(id, current_epoch) {
+            Ok(queued) => Ok(queued.into()),
+            Err(TimelockError::NotReady { executable_at, .. }) => {
+                Err(GovernanceError::TimelockNotPassed {
+                    current_epoch,
+                    executable_at,
+                })
+            }
+            Err(TimelockError::UnknownAction(_)) => Err(GovernanceError::NoPendingUpdate),
+        }
+    }
+
+    /// Removes pending update without applying it
+    pub fn cancel(&mut self) -> Result<PendingUpdate, GovernanceError> {
+        let id = self.pending_id()?;
+        self.queue
+            .cancel(id)
+            .map(Into::into)
+            .map_err(|_| GovernanceError::NoPendingUpdate)
+    }
+
+    fn pending_id(&self) -> Result<u64, GovernanceError> {
+        self.queue
+            .queued()
+            .first()
+            .map(|queued| queued.id)
+            .ok_or(GovernanceError::NoPendingUpdate)
+    }
+}
+
+impl Default for Governance {
+    fn default() -> Self {
+        Self::new(DEFAULT_GOVERNANCE_DELAY)
+    }
+}
+
+impl ToJson for PoolParams {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("min_fee", self.min_fee.to_json()),
+            ("max_fee", self.max_fee.to_json()),
+            ("liquidity_target", self.liquidity_target.to_json()),
+        ])
+    }
+}
+
+impl FromJson for PoolParams {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        Ok(Self {
+            min_fee: field(value, "min_fee")?,
+            max_fee: field(value, "max_fee")?,
+            liquidity_target: field(value, "liquidity_target")?,
+        })
+    }
+}
+
+impl ToJson for PendingUpdate {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("params", self.params.to_json()),
+            ("proposed_at", self.proposed_at.to_json()),
+            ("executable_at", self.executable_at.to_json()),
+        ])
+    }
+}
+
+impl FromJson for PendingUpdate {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        Ok(Self {
+            params: field(value, "params")?,
+            proposed_at: field(value, "proposed_at")?,
+            executable_at: field(value, "executable_at")?,
+        })
+    }
+}
+
+impl ToJson for Governance {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("delay", self.delay().to_json()),
+            ("pending", self.pending().to_json()),
+        ])
+    }
+}
+
+impl FromJson for Governance {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        let mut governance = Self::new(field(value, "delay")?);
+        let pending: Option<PendingUpdate> = field(value, "pending")?;
+        if let Some(update) = pending {
+            // `apply_pending` installs the parameters as they are, so they're validated
+            // like proposals are
+            update
+                .params
+                .validate()
+                .map_err(|error| SchemaError::InvalidValue {
+                    path: "pending.params".into(),
+                    reason: error.to_string(),
+                })?;
+            governance.queue.push(Queued {
+                id: 0,
+                action: update.params,
+                queued_at: update.proposed_at,
+                executable_at: update.executable_at,
+            });
+        }
+        Ok(governance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> PoolParams {
+        PoolParams {
+            min_fee: 0.01.into(),
+            max_fee: 0.05.into(),
+            liquidity_target: 100.into(),
+        }
+    }
+
+    #[test]
+    fn update_waits_for_timelock() {
+        let mut governance = Governance::new(3);
+        let update = governance.propose(params(), 10).unwrap();
+        assert_eq!(update.executable_at, 13);
+
+        assert!(governance.take_executable(12).is_err());
+        assert_eq!(governance.take_executable(13).unwrap(), update);
+        assert!(governance.pending().is_none());
+    }
+
+    #[test]
+    fn only_one_update_can_be_pending() {
+        let mut governance = Governance::default();
+        governance.propose(params(), 0).unwrap();
+        assert!(governance.propose(params(), 0).is_err());
+
+        governance.cancel().unwrap();
+        assert!(governance.propose(params(), 0).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_params() {
+        let mut governance = Governance::default();
+        let invalid = PoolParams {
+            min_fee: 0.1.into(),
+            ..params()
+        };
+        assert!(governance.propose(invalid, 0).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_pending_params_on_load() {
+        let mut governance = Governance::default();
+        governance.propose(params(), 0).unwrap();
+        assert_eq!(
+            Governance::from_json(&governance.to_json())
+                .unwrap()
+                .pending(),
+            governance.pending()
+        );
+
+        let invalid = PendingUpdate {
+            params: PoolParams {
+                min_fee: 0.1.into(),
+                ..params()
+            },
+            proposed_at: 0,
+            executable_at: 0,
+        };
+        let document = Value::object([("delay", 0u64.to_json()), ("pending", invalid.to_json())]);
+        assert!(matches!(
+            Governance::from_json(&document),
+            Err(SchemaError::InvalidValue { path, .. }) if path == "pending.params"
+        ));
+    }
+}
@@ -0,0 +1,185 @@
+//! Canonical state hashing. Pool state is encoded into a canonical little-endian byte
+//! representation of its schema document and hashed with BLAKE3, so replicas running on
+//! different machines can prove they hold identical state. Operation logs are hashed as
+//! a chain, every hash committing to the whole history before it.
+
+use std::fmt;
+
+use crate::blake3;
+use crate::json::Value;
+use crate::lp_pool::LpPool;
+use crate::ops::PoolOp;
+use crate::schema::ToJson;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+/// BLAKE3 hash of a pool state or an operation chain
+pub struct StateHash(pub [u8; 32]);
+
+impl fmt::Display for StateHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+impl fmt::Debug for StateHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StateHash({self})")
+    }
+}
+
+impl StateHash {
+    /// Returns hash of the operation chain extended with `op`
+    pub fn chain(&self, op: &PoolOp) -> StateHash {
+        let mut bytes = self.0.to_vec();
+        encode_op(op, &mut bytes);
+        StateHash(blake3::hash(&bytes))
+    }
+}
+
+impl LpPool {
+    /// Returns canonical encoding of the whole pool state. Every value is written as a
+    /// tag byte followed by its payload: integers as 16 byte little endian, strings and
+    /// collections prefixed with their length as 8 byte little endian, object fields in
+    /// schema order.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        encode_value(&self.to_json(), &mut bytes);
+        bytes
+    }
+
+    /// Returns hash of the pool state, pools in identical state have identical hashes
+    /// regardless of platform or crate build
+    pub fn state_hash(&self) -> StateHash {
+        StateHash(blake3::hash(&self.canonical_bytes()))
+    }
+
+    /// Returns hash chained over every operation in the pool's operation log, starting
+    /// from the zero hash
+    pub fn op_log_hash(&self) -> StateHash {
+        op_chain_hash(StateHash::default(), self.op_log())
+    }
+}
+
+/// Extends hash of an operation chain with operations in order
+pub fn op_chain_hash(start: StateHash, ops: &[PoolOp]) -> StateHash {
+    ops.iter().fold(start, |hash, op| hash.chain(op))
+}
+
+fn encode_len(len: usize, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&(len as u64).to_le_bytes());
+}
+
+fn encode_value(value: &Value, bytes: &mut Vec<u8>) {
+    match value {
+        Value::Null => bytes.push(0),
+        Value::Bool(value) => bytes.extend_from_slice(&[1, *value as u8]),
+        Value::Number(literal) => match (literal.parse::<u128>(), literal.parse::<i128>()) {
+            (Ok(number), _) => {
+                bytes.push(2);
+                bytes.extend_from_slice(&number.to_le_bytes());
+            }
+            (_, Ok(number)) => {
+                bytes.push(3);
+                bytes.extend_from_slice(&number.to_le_bytes());
+            }
+            // schema writes only integers, other literals are kept verbatim
+            _ => {
+                bytes.push(4);
+                encode_str(literal, bytes);
+            }
+        },
+        Value::String(value) => {
+            bytes.push(5);
+            encode_str(value, bytes);
+        }
+        Value::Array(items) => {
+            bytes.push(6);
+            encode_len(items.len(), bytes);
+            items.iter().for_each(|item| encode_value(item, bytes));
+        }
+        Value::Object(fields) => {
+            bytes.push(7);
+            encode_len(fields.len(), bytes);
+            for (key, value) in fields {
+                encode_str(key, bytes);
+                encode_value(value, bytes);
+            }
+        }
+    }
+}
+
+fn encode_str(value: &str, bytes: &mut Vec<u8>) {
+    encode_len(value.len(), bytes);
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+fn encode_op(op: &PoolOp, bytes: &mut Vec<u8>) {
+    let tag: u8 = match op {
+        PoolOp::AddLiquidity { .. } => 0,
+        PoolOp::RemoveLiquidity { .. } => 1,
+        PoolOp::Swap { .. } => 2,
+        PoolOp::SetPrice { .. } => 3,
+        PoolOp::AdvanceEpoch { .. } => 4,
+        PoolOp::Unreplayable => 5,
+    };
+    bytes.push(tag);
+    match op.account() {
+        Some(account) => {
+            bytes.push(1);
+            bytes.extend_from_slice(&account.to_le_bytes());
+        }
+        None => bytes.push(0),
+    }
+    bytes.extend_from_slice(&op.raw_amount().to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool() -> LpPool {
+        let mut pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        pool.enable_op_log();
+        pool.add_liquidity_for(1, 100.into()).unwrap();
+        pool.swap(6.into()).unwrap();
+        pool
+    }
+
+    #[test]
+    fn identical_states_have_identical_hashes() {
+        let (mut first, second) = (pool(), pool());
+        assert_eq!(first.state_hash(), second.state_hash());
+        assert_eq!(first.op_log_hash(), second.op_log_hash());
+        assert_eq!(
+            LpPool::from_snapshot(&first.to_snapshot())
+                .unwrap()
+                .state_hash(),
+            first.state_hash()
+        );
+
+        first.advance_epoch(1);
+        assert_ne!(first.state_hash(), second.state_hash());
+        assert_ne!(first.op_log_hash(), second.op_log_hash());
+        assert_eq!(
+            op_chain_hash(second.op_log_hash(), &[PoolOp::AdvanceEpoch { epochs: 1 }]),
+            first.op_log_hash()
+        );
+    }
+
+    #[test]
+    fn chain_depends_on_order() {
+        let ops = [
+            PoolOp::Swap {
+                account: None,
+                amount: 1.into(),
+            },
+            PoolOp::AdvanceEpoch { epochs: 1 },
+        ];
+        let reversed = [ops[1], ops[0]];
+        assert_ne!(
+            op_chain_hash(StateHash::default(), &ops),
+            op_chain_hash(StateHash::default(), &reversed)
+        );
+        assert_eq!(StateHash::default().to_string(), "0".repeat(64));
+    }
+}
@@ -0,0 +1,178 @@
+//! Middleware around operations applied with `LpPool::apply`. Hooks registered with
+//! `LpPool::add_hook` are called in registration order before every operation, when any
+//! of them rejects it the pool is left untouched and the operation fails with
+//! `OpError::Hook`, and after every operation with its result, rejected ones included.
+//! Cross-cutting concerns like logging, limits or custom accounting are layered this way
+//! without touching the pool math. Direct calls of pool methods bypass hooks.
+
+use std::fmt::Debug;
+
+use crate::error::{HookRejection, OpError};
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+
+/// Callbacks run around every operation applied with `LpPool::apply`
+pub trait PoolHook: Debug + Send + Sync {
+    /// Called with the pool before the operation, rejecting it skips the remaining hooks
+    fn before_op(&mut self, _pool: &LpPool, _op: &PoolOp) -> Result<(), HookRejection> {
+        Ok(())
+    }
+
+    /// Called with the pool after the operation
+    fn after_op(&mut self, _pool: &LpPool, _op: &PoolOp, _result: &Result<OpOutcome, OpError>) {}
+}
+
+impl LpPool {
+    /// Registers hook called around operations after the already registered ones
+    pub fn add_hook(&mut self, hook: impl PoolHook + 'static) {
+        self.hooks_mut().push(Box::new(hook));
+    }
+
+    /// Removes every registered hook and returns them in registration order
+    pub fn take_hooks(&mut self) -> Vec<Box<dyn PoolHook>> {
+        std::mem::take(self.hooks_mut())
+    }
+
+    /// Runs `apply` between the hooks, which are detached from the pool meanwhile so they
+    /// can observe it
+    pub(crate) fn with_hooks(
+        &mut self,
+        op: &PoolOp,
+        apply: impl FnOnce(&mut LpPool) -> Result<OpOutcome, OpError>,
+    ) -> Result<OpOutcome, OpError> {
+        let mut hooks = std::mem::take(self.hooks_mut());
+        let result = hooks
+            .iter_mut()
+            .try_for_each(|hook| hook.before_op(self, op))
+            .map_err(OpError::from)
+            .and_then(|()| apply(self));
+        for hook in &mut hooks {
+            hook.after_op(self, op, &result);
+        }
+        *self.hooks_mut() = hooks;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::types::*;
+
+    #[derive(Debug, Default)]
+    struct Logger {
+        lines: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl PoolHook for Logger {
+        fn before_op(&mut self, pool: &LpPool, op: &PoolOp) -> Result<(), HookRejection> {
+            let line = format!("{} at {}", op.name(), pool.token_amount().raw());
+            self.lines.lock().unwrap().push(line);
+            Ok(())
+        }
+
+        fn after_op(&mut self, pool: &LpPool, op: &PoolOp, result: &Result<OpOutcome, OpError>) {
+            let line = format!(
+                "{} {} {}",
+                op.name(),
+                result.is_ok(),
+                pool.token_amount().raw()
+            );
+            self.lines.lock().unwrap().push(line);
+        }
+    }
+
+    #[derive(Debug)]
+    /// Rejects swaps paying out more than a share of pool tokens
+    struct SwapLimit {
+        max_share: Percentage,
+    }
+
+    impl PoolHook for SwapLimit {
+        fn before_op(&mut self, pool: &LpPool, op: &PoolOp) -> Result<(), HookRejection> {
+            let PoolOp::Swap { amount, .. } = op else {
+                return Ok(());
+            };
+            let value = amount.into_token_amount(pool.price());
+            match value.raw() as u128 * SCALE as u128
+                > pool.token_amount().raw() as u128 * self.max_share.raw() as u128
+            {
+                true => Err(HookRejection {
+                    reason: format!("swap worth {value:?} exceeds the limit"),
+                }),
+                false => Ok(()),
+            }
+        }
+    }
+
+    fn pool() -> LpPool {
+        LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap()
+    }
+
+    #[test]
+    fn hooks_observe_operations() {
+        let mut pool = pool();
+        let logger = Logger::default();
+        let lines = logger.lines.clone();
+        pool.add_hook(logger);
+        pool.apply(&PoolOp::AddLiquidity {
+            account: None,
+            amount: 1.into(),
+        })
+        .unwrap();
+        pool.apply(&PoolOp::Swap {
+            account: None,
+            amount: 0.into(),
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            *lines.lock().unwrap(),
+            [
+                "add_liquidity at 0",
+                "add_liquidity true 1000000",
+                "swap at 1000000",
+                "swap false 1000000",
+            ]
+        );
+        assert_eq!(pool.take_hooks().len(), 1);
+        pool.apply(&PoolOp::AdvanceEpoch { epochs: 1 }).unwrap();
+        assert_eq!(lines.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn hooks_reject_operations() {
+        let mut pool = pool();
+        pool.add_hook(SwapLimit {
+            max_share: 0.1.into(),
+        });
+        let logger = Logger::default();
+        let lines = logger.lines.clone();
+        pool.add_hook(logger);
+        pool.apply(&PoolOp::AddLiquidity {
+            account: None,
+            amount: 100.into(),
+        })
+        .unwrap();
+
+        let before = pool.balances();
+        let rejected = pool.apply(&PoolOp::Swap {
+            account: None,
+            amount: 10.into(),
+        });
+        assert!(matches!(rejected, Err(OpError::Hook(HookRejection { .. }))));
+        assert_eq!(pool.balances(), before);
+        pool.apply(&PoolOp::Swap {
+            account: None,
+            amount: 6.into(),
+        })
+        .unwrap();
+
+        // rejections skip later hooks but are reported to all of them
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[2], "swap false 100000000");
+    }
+}
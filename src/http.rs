@@ -0,0 +1,74 @@
+//! Minimal HTTP/1.1 handling shared by the servers exposing a pool over the network.
+//! Every connection carries a single request with an optional `Content-Length` body and
+//! is answered with a JSON document.
+
+use std::io::{self, BufRead, Write};
+
+use crate::json::Value;
+
+/// biggest accepted request body
+const MAX_BODY_LEN: usize = 64 * 1024;
+
+/// Reads request line, headers and body of a HTTP/1.1 request
+pub(crate) fn read_request(reader: &mut impl BufRead) -> Result<(String, String, String), String> {
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|error| error.to_string())?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err("invalid request line".into());
+    };
+    let (method, target) = (method.to_string(), target.to_string());
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        reader
+            .read_line(&mut line)
+            .map_err(|error| error.to_string())?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| "invalid Content-Length".to_string())?;
+            }
+        }
+    }
+    if content_length > MAX_BODY_LEN {
+        return Err("request body is too large".into());
+    }
+
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|error| error.to_string())?;
+    let body = String::from_utf8(body).map_err(|_| "body is not valid UTF-8".to_string())?;
+    Ok((method, target, body))
+}
+
+/// Writes JSON response and closes the connection
+pub(crate) fn write_response(mut stream: impl Write, status: u16, body: &Value) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        503 => "Service Unavailable",
+        _ => "Error",
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        status,
+        body.len()
+    )?;
+    stream.flush()
+}
@@ -0,0 +1,143 @@
+//! Insurance fund backing LPs against losses of the staked tokens held by the pool. The
+//! fund takes a configurable cut of every swap fee out of pool liquidity and an authority
+//! draws on it to cover shortfalls after a slashing or depeg, returning the drawn tokens
+//! to pool liquidity. `InsuranceStats` relate the fund to the value it insures.
+
+use crate::error::{InsuranceError, SchemaError};
+use crate::json::Value;
+use crate::schema::{field, FromJson, ToJson};
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Balance and configuration of the insurance fund
+pub struct InsuranceFund {
+    /// share of every swap fee paid into the fund
+    pub cut: Percentage,
+    /// tokens held by the fund
+    pub balance: TokenAmount,
+    /// tokens ever drawn to cover shortfalls
+    pub drawn: TokenAmount,
+}
+
+impl InsuranceFund {
+    pub(crate) fn deposit(&mut self, amount: TokenAmount) {
+        self.balance = self.balance.saturating_add(amount);
+    }
+
+    /// Takes tokens out of the fund
+    pub(crate) fn draw(&mut self, amount: TokenAmount) -> Result<(), InsuranceError> {
+        if amount.raw() == 0 {
+            return Err(InsuranceError::ZeroAmount);
+        }
+        let Some(balance) = self.balance.checked_sub(amount) else {
+            return Err(InsuranceError::InsufficientFunds {
+                requested: amount,
+                balance: self.balance,
+            });
+        };
+        self.balance = balance;
+        self.drawn = self.drawn.saturating_add(amount);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Insurance fund compared to the value it insures
+pub struct InsuranceStats {
+    pub balance: TokenAmount,
+    /// value of staked tokens held by the pool, lost by LPs if they slash or depeg
+    pub exposure: TokenAmount,
+    /// share of pool value that is exposed
+    pub exposure_ratio: Percentage,
+    /// fund balance relative to the exposure, 100% and above covers complete loss of the
+    /// staked tokens
+    pub coverage_ratio: Percentage,
+}
+
+impl InsuranceStats {
+    /// # Arguments
+    ///
+    /// * `fund` - insurance fund of the pool
+    /// * `exposure` - raw value of staked tokens held by the pool
+    /// * `total_value` - raw total value of the pool
+    pub(crate) fn new(fund: &InsuranceFund, exposure: u128, total_value: u128) -> Self {
+        let ratio = |part: u128, whole: u128| match whole {
+            // nothing exposed is fully covered
+            0 => Percentage::from_raw_amount(SCALE),
+            whole => Percentage::from_raw_amount(
+                (part * SCALE as u128 / whole).min(Uint::MAX as u128) as Uint,
+            ),
+        };
+        Self {
+            balance: fund.balance,
+            exposure: TokenAmount::from_raw_amount(exposure.min(Uint::MAX as u128) as Uint),
+            exposure_ratio: match total_value {
+                0 => Percentage::from_raw_amount(0),
+                total_value => ratio(exposure, total_value),
+            },
+            coverage_ratio: ratio(fund.balance.raw() as u128, exposure),
+        }
+    }
+}
+
+impl ToJson for InsuranceFund {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("cut", self.cut.to_json()),
+            ("balance", self.balance.to_json()),
+            ("drawn", self.drawn.to_json()),
+        ])
+    }
+}
+
+impl FromJson for InsuranceFund {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        Ok(Self {
+            cut: field(value, "cut")?,
+            balance: field(value, "balance")?,
+            drawn: field(value, "drawn")?,
+        })
+    }
+}
+
+impl ToJson for InsuranceStats {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("balance", self.balance.to_json()),
+            ("exposure", self.exposure.to_json()),
+            ("exposure_ratio", self.exposure_ratio.to_json()),
+            ("coverage_ratio", self.coverage_ratio.to_json()),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_only_held_tokens() {
+        let mut fund = InsuranceFund::default();
+        fund.deposit(5.into());
+        assert!(matches!(
+            fund.draw(6.into()),
+            Err(InsuranceError::InsufficientFunds { .. })
+        ));
+        assert!(matches!(
+            fund.draw(0.into()),
+            Err(InsuranceError::ZeroAmount)
+        ));
+        fund.draw(2.into()).unwrap();
+        assert_eq!(fund.balance, 3.into());
+        assert_eq!(fund.drawn, 2.into());
+
+        let stats = InsuranceStats::new(&fund, 12 * SCALE as u128, 48 * SCALE as u128);
+        assert_eq!(stats.exposure, 12.into());
+        assert_eq!(stats.exposure_ratio, 0.25.into());
+        assert_eq!(stats.coverage_ratio, 0.25.into());
+        assert_eq!(
+            InsuranceStats::new(&fund, 0, 0).coverage_ratio,
+            Percentage::from(1.0)
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! Queryable view of the operation log. Queries are built by chaining filters on
+//! `LpPool::journal`, e.g. `pool.journal().swaps().since(epoch)`, and yield typed records
+//! with the position and epoch of every operation, so consumers don't have to scan the
+//! raw log and track epochs themselves.
+
+use crate::lp_pool::LpPool;
+use crate::ops::PoolOp;
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Operation of the log with its position and the epoch it was applied at
+pub struct JournalRecord {
+    /// position of the operation in the log
+    pub index: usize,
+    /// epoch the operation was applied at, for epoch advances the epoch they started from
+    pub epoch: Epoch,
+    pub op: PoolOp,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Query over the operation log, every filter narrows down the previous ones
+pub struct Journal<'a> {
+    ops: &'a [PoolOp],
+    start: Epoch,
+    kind: Option<fn(&PoolOp) -> bool>,
+    account: Option<AccountId>,
+    since: Epoch,
+    before: Option<Epoch>,
+}
+
+impl LpPool {
+    /// Returns query matching every operation of the operation log
+    pub fn journal(&self) -> Journal<'_> {
+        Journal::new(self.op_log(), self.op_log_epoch())
+    }
+}
+
+impl<'a> Journal<'a> {
+    /// Creates query over operations applied from epoch `start` on
+    pub fn new(ops: &'a [PoolOp], start: Epoch) -> Self {
+        Self {
+            ops,
+            start,
+            kind: None,
+            account: None,
+            since: 0,
+            before: None,
+        }
+    }
+
+    fn of_kind(self, kind: fn(&PoolOp) -> bool) -> Self {
+        Self {
+            kind: Some(kind),
+            ..self
+        }
+    }
+
+    pub fn deposits(self) -> Self {
+        self.of_kind(|op| matches!(op, PoolOp::AddLiquidity { .. }))
+    }
+
+    pub fn withdrawals(self) -> Self {
+        self.of_kind(|op| matches!(op, PoolOp::RemoveLiquidity { .. }))
+    }
+
+    pub fn swaps(self) -> Self {
+        self.of_kind(|op| matches!(op, PoolOp::Swap { .. }))
+    }
+
+    pub fn price_updates(self) -> Self {
+        self.of_kind(|op| matches!(op, PoolOp::SetPrice { .. }))
+    }
+
+    pub fn epoch_advances(self) -> Self {
+        self.of_kind(|op| matches!(op, PoolOp::AdvanceEpoch { .. }))
+    }
+
+    /// Keeps operations attributed to the account
+    pub fn account(self, account: AccountId) -> Self {
+        Self {
+            account: Some(account),
+            ..self
+        }
+    }
+
+    /// Keeps operations applied at or after the epoch
+    pub fn since(self, epoch: Epoch) -> Self {
+        Self {
+            since: self.since.max(epoch),
+            ..self
+        }
+    }
+
+    /// Keeps operations applied before the epoch
+    pub fn before(self, epoch: Epoch) -> Self {
+        Self {
+            before: Some(self.before.map_or(epoch, |before| before.min(epoch))),
+            ..self
+        }
+    }
+
+    /// Returns matching records in log order
+    pub fn iter(&self) -> impl Iterator<Item = JournalRecord> + 'a {
+        let query = *self;
+        let mut epoch = self.start;
+        self.ops
+            .iter()
+            .enumerate()
+            .map(move |(index, op)| {
+                let record = JournalRecord {
+                    index,
+                    epoch,
+                    op: *op,
+                };
+                if let PoolOp::AdvanceEpoch { epochs } = op {
+                    epoch = epoch.saturating_add(*epochs);
+                }
+                record
+            })
+            .filter(move |record| query.matches(record))
+    }
+
+    fn matches(&self, record: &JournalRecord) -> bool {
+        self.kind.is_none_or(|kind| kind(&record.op))
+            && (self.account.is_none() || record.op.account() == self.account)
+            && record.epoch >= self.since
+            && self.before.is_none_or(|before| record.epoch < before)
+    }
+
+    pub fn records(&self) -> Vec<JournalRecord> {
+        self.iter().collect()
+    }
+
+    pub fn count(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn first(&self) -> Option<JournalRecord> {
+        self.iter().next()
+    }
+
+    pub fn last(&self) -> Option<JournalRecord> {
+        self.iter().last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixture_config;
+
+    fn pool() -> LpPool {
+        let mut pool = fixture_config().build();
+        let ops = [
+            PoolOp::AddLiquidity {
+                account: Some(1),
+                amount: 100.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 6.into(),
+            },
+            PoolOp::AdvanceEpoch { epochs: 2 },
+            PoolOp::Swap {
+                account: None,
+                amount: 2.into(),
+            },
+            PoolOp::AddLiquidity {
+                account: Some(2),
+                amount: 10.into(),
+            },
+            PoolOp::AdvanceEpoch { epochs: 1 },
+            PoolOp::Swap {
+                account: None,
+                amount: 1.into(),
+            },
+        ];
+        for op in &ops {
+            pool.apply(op).unwrap();
+        }
+        pool
+    }
+
+    #[test]
+    fn filters_by_kind_time_and_account() {
+        let pool = pool();
+        let swaps = pool.journal().swaps();
+        assert_eq!(swaps.count(), 3);
+        let late: Vec<_> = swaps
+            .since(2)
+            .iter()
+            .map(|record| (record.index, record.epoch))
+            .collect();
+        assert_eq!(late, [(3, 2), (6, 3)]);
+        assert_eq!(swaps.since(1).before(3).records().len(), 1);
+        assert_eq!(
+            swaps.last().unwrap().op,
+            PoolOp::Swap {
+                account: None,
+                amount: 1.into()
+            }
+        );
+
+        let deposits = pool.journal().deposits();
+        assert_eq!(deposits.account(2).first().unwrap().index, 4);
+        assert_eq!(deposits.account(3).count(), 0);
+        // epoch advances carry the epoch they started from
+        let advances: Vec<_> = pool
+            .journal()
+            .epoch_advances()
+            .iter()
+            .map(|record| record.epoch)
+            .collect();
+        assert_eq!(advances, [0, 2]);
+        assert_eq!(pool.journal().before(0).count(), 0);
+    }
+
+    #[test]
+    fn starts_at_epoch_of_the_log() {
+        let mut pool = pool();
+        pool.take_op_log();
+        pool.apply(&PoolOp::Swap {
+            account: None,
+            amount: 1.into(),
+        })
+        .unwrap();
+        assert_eq!(pool.op_log_epoch(), 3);
+        assert_eq!(pool.journal().swaps().since(3).count(), 1);
+    }
+}
@@ -0,0 +1,61 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::PersistError;
+use crate::json::Value;
+use crate::lp_pool::LpPool;
+use crate::schema::{FromJson, ToJson};
+
+impl LpPool {
+    /// Saves pool state to a pretty printed JSON file. The state is written to a temporary
+    /// file first and then moved in place, so an interrupted save never leaves a truncated
+    /// file behind.
+    pub fn save_json(&self, path: impl AsRef<Path>) -> Result<(), PersistError> {
+        let path = path.as_ref();
+        let temporary = temporary_path(path);
+        fs::write(&temporary, self.to_json().to_pretty_string())?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    /// Loads pool state saved with `save_json`
+    pub fn load_json(path: impl AsRef<Path>) -> Result<LpPool, PersistError> {
+        let document = Value::parse(&fs::read_to_string(path)?)?;
+        Ok(LpPool::from_json(&document)?)
+    }
+}
+
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::*;
+
+    #[test]
+    fn pool_survives_save_and_load() {
+        let path = std::env::temp_dir().join(format!("lp-pool-{}.json", std::process::id()));
+        let mut pool = LpPool::init(1.5.into(), 0.1.into(), 0.9.into(), 100.into()).unwrap();
+        pool.add_liquidity(100.into()).unwrap();
+        pool.swap(StakedTokenAmount::from(6)).unwrap();
+
+        pool.save_json(&path).unwrap();
+        let mut restored = LpPool::load_json(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(restored.to_json(), pool.to_json());
+        restored.swap(StakedTokenAmount::from(1)).unwrap();
+        pool.swap(StakedTokenAmount::from(1)).unwrap();
+        assert_eq!(restored.token_amount(), pool.token_amount());
+    }
+
+    #[test]
+    fn loading_missing_file_fails() {
+        let path = std::env::temp_dir().join("lp-pool-missing.json");
+        assert!(matches!(LpPool::load_json(path), Err(PersistError::Io(_))));
+    }
+}
@@ -1,7 +1,204 @@
+mod account;
+mod actor;
+mod agents;
+#[cfg(test)]
+mod allocations;
+mod anomaly;
+#[cfg(feature = "api")]
+mod api;
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(feature = "async-pool")]
+mod async_pool;
+mod audit;
+mod backtest;
+mod blake3;
+mod bootstrap;
+#[cfg(feature = "chainlink")]
+mod chainlink;
+mod chaos;
+mod checkpoint;
+#[cfg(feature = "arrow")]
+mod columnar;
+mod commit_reveal;
+mod compute_cost;
+mod config;
+mod conservation;
+mod counters;
+mod csv;
+#[cfg(feature = "tui")]
+mod dashboard;
+mod debugger;
+mod diff;
+mod discounts;
+#[cfg(any(feature = "marinade-rpc", feature = "json-rpc"))]
+mod encoding;
+mod equivalence;
 mod error;
+mod events;
+mod fee_curve;
+mod fee_policy;
+mod fee_revenue;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod flows;
+mod governance;
+mod hashing;
+mod hooks;
+#[cfg(any(feature = "api", feature = "json-rpc"))]
+mod http;
+mod insurance;
+mod journal;
+pub mod json;
+#[cfg(feature = "json-files")]
+mod json_files;
+mod liquidity_migration;
+#[cfg(all(test, loom))]
+mod loom;
 mod lp_pool;
+#[cfg(feature = "marinade-rpc")]
+mod marinade;
+mod marinade_account;
+mod marinade_unstake;
+mod migration;
+#[cfg(feature = "uniffi")]
+mod mobile;
+mod model_check;
+#[cfg(feature = "node")]
+mod node;
+mod ops;
+mod optimizer;
+mod oracle;
+mod orders;
+mod positions;
+mod price_history;
+mod price_smoothing;
+mod properties;
+#[cfg(feature = "pyth")]
+mod pyth;
+mod rational;
+mod reference;
+mod registry;
+mod replay;
+mod report;
+mod rewards;
+mod rng;
+mod roles;
+#[cfg(feature = "json-rpc")]
+mod rpc;
+mod scenarios;
+mod schema;
+#[cfg(feature = "grpc")]
+mod service;
+mod shared;
+mod shrinking;
+mod simulator;
+mod snapshot;
+mod store;
+mod stress;
+mod subscriptions;
+mod surcharge;
+#[cfg(any(test, feature = "test-utils"))]
+mod test_utils;
+mod timelock;
+mod twap;
 mod types;
+mod volume;
+#[cfg(feature = "wasm")]
+mod wasm;
 
+pub use account::*;
+pub use actor::*;
+pub use agents::*;
+pub use anomaly::*;
+#[cfg(feature = "api")]
+pub use api::*;
+#[cfg(feature = "arbitrary")]
+pub use arbitrary::*;
+#[cfg(feature = "async-pool")]
+pub use async_pool::*;
+pub use audit::*;
+pub use backtest::*;
+pub use bootstrap::*;
+#[cfg(feature = "chainlink")]
+pub use chainlink::*;
+pub use chaos::*;
+pub use checkpoint::*;
+#[cfg(feature = "arrow")]
+pub use columnar::*;
+pub use commit_reveal::*;
+pub use compute_cost::*;
+pub use conservation::*;
+pub use counters::*;
+pub use csv::*;
+#[cfg(feature = "tui")]
+pub use dashboard::*;
+pub use debugger::*;
+pub use diff::*;
+pub use discounts::*;
+pub use equivalence::*;
 pub use error::*;
+pub use events::{EventSink, PoolEvent, PriceOverride};
+pub use fee_policy::FeePolicy;
+pub use fee_revenue::FeeRevenue;
+#[cfg(feature = "ffi")]
+pub use ffi::*;
+pub use flows::*;
+pub use governance::*;
+pub use hashing::*;
+pub use hooks::PoolHook;
+pub use insurance::*;
+pub use journal::*;
+pub use liquidity_migration::*;
 pub use lp_pool::LpPool;
+#[cfg(feature = "marinade-rpc")]
+pub use marinade::*;
+pub use marinade_account::*;
+pub use marinade_unstake::*;
+pub use migration::*;
+#[cfg(feature = "uniffi")]
+pub use mobile::*;
+pub use model_check::*;
+#[cfg(feature = "node")]
+pub use node::*;
+pub use ops::*;
+pub use optimizer::*;
+pub use oracle::*;
+pub use orders::*;
+pub use positions::{Position, PositionPnl, Positions};
+pub use price_history::*;
+pub use price_smoothing::PriceSmoothing;
+pub use properties::*;
+#[cfg(feature = "pyth")]
+pub use pyth::*;
+pub use rational::*;
+pub use reference::*;
+pub use registry::*;
+pub use replay::*;
+pub use report::*;
+pub use rewards::*;
+pub use rng::Rng;
+pub use roles::*;
+#[cfg(feature = "json-rpc")]
+pub use rpc::*;
+pub use scenarios::*;
+pub use schema::*;
+#[cfg(feature = "grpc")]
+pub use service::*;
+pub use shared::*;
+pub use shrinking::*;
+pub use simulator::*;
+pub use snapshot::*;
+pub use store::*;
+pub use stress::*;
+#[cfg(feature = "async-events")]
+pub use subscriptions::AsyncEventReceiver;
+pub use surcharge::*;
+#[cfg(any(test, feature = "test-utils"))]
+pub use test_utils::*;
+pub use timelock::*;
+pub use twap::*;
 pub use types::*;
+pub use volume::*;
+#[cfg(feature = "wasm")]
+pub use wasm::*;
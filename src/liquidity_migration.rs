@@ -0,0 +1,199 @@
+//! Moving liquidity between pools. Withdrawn tokens are deposited into the target pool
+//! while withdrawn staked tokens, which pools only accept through swaps, are handed back
+//! to the caller. The target pool's oracle is queried once upfront, then both legs run on
+//! staged copies of the pools which replace their state only once both legs succeeded, so
+//! a migration failing in either of them leaves liquidity of both pools untouched.
+
+use crate::error::{AddLiquidityError, MigrateLiquidityError};
+use crate::events::PoolEvent;
+use crate::lp_pool::LpPool;
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Result of a migration, value withdrawn from the source pool is `tokens` plus value of
+/// `staked_tokens`
+pub struct LiquidityMigration {
+    /// lp tokens of the source pool burned
+    pub lp_burned: LpTokenAmount,
+    /// tokens withdrawn from the source pool and deposited into the target pool
+    pub tokens: TokenAmount,
+    /// staked tokens withdrawn from the source pool and returned to the caller
+    pub staked_tokens: StakedTokenAmount,
+    /// lp tokens of the target pool minted
+    pub lp_minted: LpTokenAmount,
+}
+
+/// Withdraws `lp_amount` of anonymous liquidity from `from` and deposits the withdrawn
+/// tokens into `to`
+pub fn migrate_liquidity(
+    from: &mut LpPool,
+    to: &mut LpPool,
+    lp_amount: LpTokenAmount,
+) -> Result<LiquidityMigration, MigrateLiquidityError> {
+    migrate(from, to, None, lp_amount)
+}
+
+/// Same as `migrate_liquidity` but moves liquidity from the account's position in `from`
+/// to its position in `to`
+pub fn migrate_liquidity_for(
+    from: &mut LpPool,
+    to: &mut LpPool,
+    account: AccountId,
+    lp_amount: LpTokenAmount,
+) -> Result<LiquidityMigration, MigrateLiquidityError> {
+    migrate(from, to, Some(account), lp_amount)
+}
+
+fn migrate(
+    from: &mut LpPool,
+    to: &mut LpPool,
+    account: Option<AccountId>,
+    lp_amount: LpTokenAmount,
+) -> Result<LiquidityMigration, MigrateLiquidityError> {
+    // the copies price deposits at the refreshed price without querying the oracle again
+    to.refresh_price().map_err(AddLiquidityError::from)?;
+    let (mut staged_from, mut staged_to) = (from.staged(), to.staged());
+    let (tokens, staked_tokens) = withdraw(&mut staged_from, account, lp_amount)?;
+    let lp_minted = deposit(&mut staged_to, account, tokens)?;
+    staged_from.emit(PoolEvent::LiquidityMigratedOut {
+        account,
+        lp_amount,
+        tokens,
+        staked_tokens,
+    });
+    staged_to.emit(PoolEvent::LiquidityMigratedIn {
+        account,
+        amount: tokens,
+        lp_amount: lp_minted,
+    });
+    from.commit_staged(staged_from);
+    to.commit_staged(staged_to);
+    Ok(LiquidityMigration {
+        lp_burned: lp_amount,
+        tokens,
+        staked_tokens,
+        lp_minted,
+    })
+}
+
+fn withdraw(
+    pool: &mut LpPool,
+    account: Option<AccountId>,
+    lp_amount: LpTokenAmount,
+) -> Result<(TokenAmount, StakedTokenAmount), MigrateLiquidityError> {
+    Ok(match account {
+        Some(account) => pool.remove_liquidity_for(account, lp_amount)?,
+        None => pool.remove_liquidity(lp_amount)?,
+    })
+}
+
+fn deposit(
+    pool: &mut LpPool,
+    account: Option<AccountId>,
+    tokens: TokenAmount,
+) -> Result<LpTokenAmount, MigrateLiquidityError> {
+    Ok(match account {
+        Some(account) => pool.add_liquidity_for(account, tokens)?,
+        None => pool.add_liquidity(tokens)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::OracleError;
+    use crate::ops::PoolOp;
+    use crate::oracle::MockOracle;
+    use crate::schema::ToJson;
+
+    const ALICE: AccountId = 1;
+
+    fn pool() -> LpPool {
+        LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap()
+    }
+
+    #[test]
+    fn moves_value_between_pools() {
+        let (mut from, mut to) = (pool(), pool());
+        from.add_liquidity_for(ALICE, 100.into()).unwrap();
+        from.swap(6.into()).unwrap();
+        from.drain_events();
+        let value_before = from.total_value();
+
+        let migration = migrate_liquidity_for(&mut from, &mut to, ALICE, 50.into()).unwrap();
+        let withdrawn = migration.tokens + migration.staked_tokens.into_token_amount(from.price());
+        assert_eq!(value_before - from.total_value(), withdrawn);
+        assert_eq!(to.total_value(), migration.tokens);
+        assert_eq!(from.position(ALICE).unwrap().lp_tokens, 50.into());
+        assert_eq!(to.position(ALICE).unwrap().lp_tokens, migration.lp_minted);
+        assert_eq!(
+            from.drain_events().last(),
+            Some(&PoolEvent::LiquidityMigratedOut {
+                account: Some(ALICE),
+                lp_amount: 50.into(),
+                tokens: migration.tokens,
+                staked_tokens: migration.staked_tokens,
+            })
+        );
+        assert_eq!(
+            to.drain_events().last(),
+            Some(&PoolEvent::LiquidityMigratedIn {
+                account: Some(ALICE),
+                amount: migration.tokens,
+                lp_amount: migration.lp_minted,
+            })
+        );
+    }
+
+    #[test]
+    fn queries_target_oracle_once() {
+        let (mut from, mut to) = (pool(), pool());
+        from.add_liquidity(100.into()).unwrap();
+        from.enable_op_log();
+        to.add_liquidity(10.into()).unwrap();
+        let oracle = MockOracle::new([1.5.into()]);
+        oracle.push_failure(OracleError::Unavailable("outage".into()));
+        to.set_oracle(oracle.clone());
+
+        let migration = migrate_liquidity(&mut from, &mut to, 50.into()).unwrap();
+        assert_eq!(oracle.calls(), 1);
+        assert_eq!(from.lp_token_amount(), 50.into());
+        assert_eq!(to.total_value(), TokenAmount::from(10) + migration.tokens);
+        assert_eq!(
+            from.op_log(),
+            [PoolOp::RemoveLiquidity {
+                account: None,
+                lp_amount: 50.into(),
+            }]
+        );
+
+        // with the oracle down the migration fails before any liquidity moves
+        let state = from.to_json();
+        assert!(matches!(
+            migrate_liquidity(&mut from, &mut to, 50.into()),
+            Err(MigrateLiquidityError::AddLiquidity(
+                AddLiquidityError::Oracle(OracleError::Unavailable(_))
+            ))
+        ));
+        assert_eq!(from.to_json(), state);
+        assert_eq!(to.total_value(), TokenAmount::from(10) + migration.tokens);
+    }
+
+    #[test]
+    fn failed_deposit_leaves_pools_untouched() {
+        let (mut from, mut to) = (pool(), pool());
+        from.add_liquidity(100.into()).unwrap();
+        // pool holding only staked tokens withdraws no tokens to deposit
+        from.set_balances(0.into(), 100.into(), 100.into());
+        let state = from.to_json();
+
+        assert!(matches!(
+            migrate_liquidity(&mut from, &mut to, 50.into()),
+            Err(MigrateLiquidityError::AddLiquidity(
+                AddLiquidityError::NoTokensProvided
+            ))
+        ));
+        assert_eq!(from.to_json(), state);
+        assert_eq!(to.lp_token_amount(), 0.into());
+    }
+}
@@ -0,0 +1,323 @@
+//! Minimal model checker mirroring the API of the `loom` crate, used by tests of the
+//! shared pools. Like `loom` it's only compiled with `--cfg loom`, which also swaps the
+//! locks and atomics of `shared` for the ones below:
+//!
+//! ```sh
+//! RUSTFLAGS="--cfg loom" cargo test --lib shared
+//! ```
+//!
+//! `model` runs the closure once for every possible interleaving of the threads it spawns
+//! with `thread::spawn`. Only one thread of a model runs at a time and threads are
+//! switched at yield points, acquisitions of `sync::Mutex` and `sync::RwLock`, accesses of
+//! `sync::atomic::AtomicU64` and joins, so every order in which threads take locks and
+//! touch atomics is explored exactly once.
+//!
+//! Guards mustn't be held across yield points, e.g. by locking another mutex inside a
+//! locked closure. Outside of `model` the types behave like their `std` counterparts.
+
+use std::cell::RefCell;
+use std::sync::{Arc, Condvar, Mutex as StdMutex};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Runnable,
+    /// waiting for the thread with the index to finish
+    Joining(usize),
+    Finished,
+}
+
+#[derive(Debug)]
+struct State {
+    /// index of the only thread allowed to run
+    active: usize,
+    threads: Vec<Status>,
+    /// chosen option and number of options of every scheduling decision, decisions
+    /// recorded by the previous execution are replayed by the next one
+    path: Vec<(usize, usize)>,
+    decision: usize,
+}
+
+impl State {
+    fn runnable(&self) -> Vec<usize> {
+        (0..self.threads.len())
+            .filter(|&thread| match self.threads[thread] {
+                Status::Runnable => true,
+                Status::Joining(other) => self.threads[other] == Status::Finished,
+                Status::Finished => false,
+            })
+            .collect()
+    }
+
+    /// Picks thread which runs next, following the recorded path as long as it lasts
+    fn schedule(&mut self) {
+        let runnable = self.runnable();
+        if runnable.is_empty() {
+            assert!(
+                self.threads
+                    .iter()
+                    .all(|status| *status == Status::Finished),
+                "deadlock, no thread can run: {:?}",
+                self.threads
+            );
+            return;
+        }
+        let choice = match self.path.get(self.decision) {
+            Some(&(choice, _)) => choice,
+            None => {
+                self.path.push((0, runnable.len()));
+                0
+            }
+        };
+        self.decision += 1;
+        self.active = runnable[choice];
+    }
+}
+
+#[derive(Debug)]
+struct Execution {
+    state: StdMutex<State>,
+    wake: Condvar,
+}
+
+impl Execution {
+    /// Hands control over to the scheduler and blocks until `thread` is picked again
+    fn switch(&self, thread: usize, status: Status) {
+        let mut state = self.state.lock().unwrap();
+        state.threads[thread] = status;
+        state.schedule();
+        self.wake.notify_all();
+        if status == Status::Finished {
+            return;
+        }
+        while state.active != thread {
+            state = self.wake.wait(state).unwrap();
+        }
+        state.threads[thread] = Status::Runnable;
+    }
+
+    /// Blocks until `thread` is picked for the first time
+    fn wait_for_turn(&self, thread: usize) {
+        let mut state = self.state.lock().unwrap();
+        while state.active != thread {
+            state = self.wake.wait(state).unwrap();
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<(Arc<Execution>, usize)>> = const { RefCell::new(None) };
+}
+
+fn current() -> Option<(Arc<Execution>, usize)> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+fn set_current(current: Option<(Arc<Execution>, usize)>) {
+    CURRENT.with(|cell| *cell.borrow_mut() = current);
+}
+
+/// Lets other threads of the model run, no-op outside of `model`
+fn yield_point(status: Status) {
+    if let Some((execution, thread)) = current() {
+        execution.switch(thread, status);
+    }
+}
+
+/// Runs `f` once for every interleaving of the threads it spawns and returns the number
+/// of explored executions. Panics of `f` and of joined threads fail the model.
+pub fn model(f: impl Fn()) -> usize {
+    let mut path = Vec::new();
+    let mut executions = 0;
+    loop {
+        let execution = Arc::new(Execution {
+            state: StdMutex::new(State {
+                active: 0,
+                threads: vec![Status::Runnable],
+                path,
+                decision: 0,
+            }),
+            wake: Condvar::new(),
+        });
+        set_current(Some((execution.clone(), 0)));
+        f();
+        execution.switch(0, Status::Finished);
+        set_current(None);
+
+        let mut state = execution.state.lock().unwrap();
+        while state
+            .threads
+            .iter()
+            .any(|status| *status != Status::Finished)
+        {
+            state = execution.wake.wait(state).unwrap();
+        }
+        executions += 1;
+
+        // next execution takes the next option of the last decision which has one left
+        path = std::mem::take(&mut state.path);
+        while let Some((choice, options)) = path.pop() {
+            if choice + 1 < options {
+                path.push((choice + 1, options));
+                break;
+            }
+        }
+        if path.is_empty() {
+            return executions;
+        }
+    }
+}
+
+pub mod thread {
+    use std::panic::{self, AssertUnwindSafe};
+
+    use super::*;
+
+    #[derive(Debug)]
+    pub struct JoinHandle<T> {
+        thread: usize,
+        handle: std::thread::JoinHandle<T>,
+    }
+
+    impl<T> JoinHandle<T> {
+        pub fn join(self) -> std::thread::Result<T> {
+            yield_point(Status::Joining(self.thread));
+            self.handle.join()
+        }
+    }
+
+    /// Spawns thread of the current model, it starts once the scheduler picks it
+    pub fn spawn<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> JoinHandle<T> {
+        let (execution, _) = current().expect("loom threads are spawned inside `model`");
+        let thread = {
+            let mut state = execution.state.lock().unwrap();
+            state.threads.push(Status::Runnable);
+            state.threads.len() - 1
+        };
+        let handle = std::thread::spawn(move || {
+            execution.wait_for_turn(thread);
+            set_current(Some((execution.clone(), thread)));
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+            execution.switch(thread, Status::Finished);
+            result.unwrap_or_else(|payload| panic::resume_unwind(payload))
+        });
+        JoinHandle { thread, handle }
+    }
+}
+
+pub mod sync {
+    use std::sync::{LockResult, MutexGuard, RwLock as StdRwLock};
+    use std::sync::{RwLockReadGuard, RwLockWriteGuard};
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    /// Mutex whose every acquisition is a yield point of the model
+    pub struct Mutex<T> {
+        inner: StdMutex<T>,
+    }
+
+    impl<T> Mutex<T> {
+        pub fn new(value: T) -> Self {
+            Self {
+                inner: StdMutex::new(value),
+            }
+        }
+
+        pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+            yield_point(Status::Runnable);
+            self.inner.lock()
+        }
+    }
+
+    #[derive(Debug, Default)]
+    /// Read-write lock whose every acquisition is a yield point of the model
+    pub struct RwLock<T> {
+        inner: StdRwLock<T>,
+    }
+
+    impl<T> RwLock<T> {
+        pub fn new(value: T) -> Self {
+            Self {
+                inner: StdRwLock::new(value),
+            }
+        }
+
+        pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+            yield_point(Status::Runnable);
+            self.inner.read()
+        }
+
+        pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+            yield_point(Status::Runnable);
+            self.inner.write()
+        }
+    }
+
+    pub mod atomic {
+        pub use std::sync::atomic::Ordering;
+
+        use super::super::*;
+
+        #[derive(Debug, Default)]
+        /// Atomic whose every access is a yield point of the model. Threads of a model
+        /// never run in parallel, so every access observes the latest value regardless of
+        /// the ordering.
+        pub struct AtomicU64 {
+            inner: std::sync::atomic::AtomicU64,
+        }
+
+        impl AtomicU64 {
+            pub fn new(value: u64) -> Self {
+                Self {
+                    inner: std::sync::atomic::AtomicU64::new(value),
+                }
+            }
+
+            pub fn load(&self, ordering: Ordering) -> u64 {
+                yield_point(Status::Runnable);
+                self.inner.load(ordering)
+            }
+
+            pub fn fetch_add(&self, value: u64, ordering: Ordering) -> u64 {
+                yield_point(Status::Runnable);
+                self.inner.fetch_add(value, ordering)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sync::Mutex;
+    use super::*;
+
+    #[test]
+    fn explores_every_interleaving() {
+        let orders = StdMutex::new(Vec::new());
+        let executions = model(|| {
+            let log = Arc::new(Mutex::new(Vec::new()));
+            let threads: Vec<_> = (1..=2)
+                .map(|thread| {
+                    let log = log.clone();
+                    thread::spawn(move || {
+                        for step in 0..2 {
+                            log.lock().unwrap().push((thread, step));
+                        }
+                    })
+                })
+                .collect();
+            log.lock().unwrap().push((0, 0));
+            threads
+                .into_iter()
+                .for_each(|thread| thread.join().unwrap());
+            orders.lock().unwrap().push(log.lock().unwrap().clone());
+        });
+
+        let mut orders = orders.into_inner().unwrap();
+        assert_eq!(orders.len(), executions);
+        orders.sort();
+        orders.dedup();
+        // orders of five locks, where both locks of every spawned thread keep their order
+        assert_eq!(orders.len(), 5 * 4 * 3 * 2 / (2 * 2));
+    }
+}
@@ -1,18 +1,95 @@
 use std::convert::Infallible;
+use std::num::NonZeroU64;
 
+use crate::account::PoolAccount;
+use crate::bootstrap::{BootstrapConfig, LiquidityBootstrap};
+use crate::counters::PoolCounters;
+use crate::discounts::{DiscountTier, FeeDiscounts};
 use crate::error::*;
+use crate::events::{EventQueue, EventSink, PoolEvent, PriceOverride};
+use crate::fee_curve::FeeCurve;
+use crate::fee_policy::FeePolicy;
+use crate::fee_revenue::FeeRevenue;
+use crate::governance::{Governance, PendingUpdate, PoolParams};
+use crate::hooks::PoolHook;
+use crate::insurance::{InsuranceFund, InsuranceStats};
+use crate::json::Value;
+use crate::ops::{PoolOp, SwapOutcome};
+use crate::oracle::{FixedOracle, PriceOracle};
+use crate::positions::{Position, PositionPnl, Positions};
+use crate::price_history::PriceHistory;
+use crate::price_smoothing::PriceSmoothing;
+use crate::schema::{field, field_or_default, FromJson, ToJson, SCHEMA_VERSION};
+use crate::subscriptions::Subscribers;
+use crate::surcharge::{SurchargeConfig, SwapSurcharge};
+use crate::twap::{TwapAccumulator, DEFAULT_TWAP_CAPACITY};
 use crate::types::*;
+use crate::volume::{VolumeHistory, EPOCHS_PER_YEAR};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Outcome of a swap calculated before it's applied to the pool
+struct SwapQuote {
+    /// value of staked tokens before fees are applied
+    amount_out_before_fees: TokenAmount,
+    /// fee percentage charged by the swap
+    fee: Percentage,
+    /// tokens granted to the caller
+    amount_out: TokenAmount,
+    /// discount tier applied to the fee
+    tier: Option<DiscountTier>,
+}
 
 #[derive(Debug)]
 /// Unstake Liquidity Pool following marinade protocol
 pub struct LpPool {
+    /// last price accepted from the oracle
     price: Price,
+    /// epoch at which the price was last changed or explicitly set
+    price_updated_at: Epoch,
+    max_price_age: Option<Epoch>,
+    max_price_deviation: Option<Percentage>,
+    /// last price rejected by the deviation circuit breaker
+    rejected_price: Option<Price>,
+    /// rejects price decreases unless slashing was signalled
+    monotonic_price: bool,
+    slashing_signalled: bool,
+    price_overrides: Vec<PriceOverride>,
+    oracle: Box<dyn PriceOracle>,
     token_amount: TokenAmount,
     st_token_amount: StakedTokenAmount,
     lp_token_amount: LpTokenAmount,
     liquidity_target: TokenAmount,
     min_fee: Percentage,
     max_fee: Percentage,
+    /// derived from min fee, max fee and liquidity target, refreshed whenever they change
+    fee_curve: FeeCurve,
+    fee_policy: FeePolicy,
+    price_smoothing: PriceSmoothing,
+    price_history: PriceHistory,
+    twap: TwapAccumulator,
+    surcharge: Option<SwapSurcharge>,
+    bootstrap: Option<LiquidityBootstrap>,
+    epoch: Epoch,
+    treasury_cut: Percentage,
+    fee_revenue: FeeRevenue,
+    insurance: InsuranceFund,
+    fee_discounts: FeeDiscounts,
+    counters: PoolCounters,
+    events: EventQueue,
+    /// receives events instead of `events` when registered
+    event_sink: Option<Box<dyn EventSink>>,
+    volume_history: VolumeHistory,
+    positions: Positions,
+    governance: Governance,
+    max_fee_change: Option<Percentage>,
+    /// successful operations recorded for replay, `None` when recording is disabled
+    op_log: Option<Vec<PoolOp>>,
+    /// epoch of the pool when the first operation of the log was recorded
+    op_log_epoch: Epoch,
+    /// called around operations applied with `apply`
+    hooks: Vec<Box<dyn PoolHook>>,
+    /// receive copies of events in addition to the sink or queue
+    subscribers: Subscribers,
 }
 
 impl LpPool {
@@ -25,280 +102,1801 @@ impl LpPool {
         max_fee: Percentage,
         liquidity_target: TokenAmount,
     ) -> Result<Self, Infallible> {
+        let mut price_history = PriceHistory::default();
+        price_history.push(0, price);
+
         Ok(Self {
             price,
+            price_updated_at: 0,
+            max_price_age: None,
+            max_price_deviation: None,
+            rejected_price: None,
+            monotonic_price: false,
+            slashing_signalled: false,
+            price_overrides: Vec::new(),
+            oracle: Box::new(FixedOracle::new(price)),
             token_amount: TokenAmount::from(0),
             st_token_amount: StakedTokenAmount::from(0),
             lp_token_amount: LpTokenAmount::from(0),
             min_fee,
             max_fee,
             liquidity_target,
+            fee_curve: FeeCurve::new(min_fee, max_fee, liquidity_target),
+            fee_policy: FeePolicy::default(),
+            price_smoothing: PriceSmoothing::default(),
+            price_history,
+            twap: TwapAccumulator::new(0, price, DEFAULT_TWAP_CAPACITY),
+            surcharge: None,
+            bootstrap: None,
+            epoch: 0,
+            treasury_cut: Percentage::from_raw_amount(0),
+            fee_revenue: FeeRevenue::default(),
+            insurance: InsuranceFund::default(),
+            fee_discounts: FeeDiscounts::default(),
+            counters: PoolCounters::default(),
+            events: EventQueue::new(),
+            event_sink: None,
+            volume_history: VolumeHistory::default(),
+            positions: Positions::default(),
+            governance: Governance::default(),
+            max_fee_change: None,
+            op_log: None,
+            op_log_epoch: 0,
+            hooks: Vec::new(),
+            subscribers: Subscribers::default(),
         })
     }
 
-    /// Returns Amount of LP tokens granted to the caller.
+    /// Returns price of StakedToken in respect to Token used by swaps
+    pub fn price(&self) -> Price {
+        self.price
+    }
+
+    /// Returns amount of 'unstaked' tokens held by the pool
+    pub fn token_amount(&self) -> TokenAmount {
+        self.token_amount
+    }
+
+    /// Returns amount of staked tokens held by the pool
+    pub fn st_token_amount(&self) -> StakedTokenAmount {
+        self.st_token_amount
+    }
+
+    /// Returns amount of lp tokens in circulation
+    pub fn lp_token_amount(&self) -> LpTokenAmount {
+        self.lp_token_amount
+    }
+
+    /// Returns total value stored inside the pool (tokens + staked tokens) as `TokenAmount`,
+    /// saturated at `Uint::MAX` if a price update made it unrepresentable
+    pub fn total_value(&self) -> TokenAmount {
+        TokenAmount::from_raw_amount(self.total_val().min(Uint::MAX as u128) as Uint)
+    }
+
+    /// Returns parameters describing the fee curve
+    pub fn params(&self) -> PoolParams {
+        PoolParams {
+            min_fee: self.min_fee,
+            max_fee: self.max_fee,
+            liquidity_target: self.liquidity_target,
+        }
+    }
+
+    /// Sets amount of epochs proposed parameter changes have to wait before being applied
+    pub fn set_governance_delay(&mut self, delay: Epoch) {
+        self.governance.set_delay(delay);
+        self.log_op(PoolOp::Unreplayable);
+    }
+
+    /// Returns parameter change waiting for its timelock
+    pub fn pending_update(&self) -> Option<PendingUpdate> {
+        self.governance.pending()
+    }
+
+    /// Queues parameter change that can be applied with `apply_pending` once
+    /// the governance delay passes. Returns the queued update.
     ///
     /// # Arguments
     ///
-    /// * `token_amount_in` - amount of 'unstaked' tokens provided by the caller
-    pub fn add_liquidity(
-        &mut self,
-        token_amount_in: TokenAmount,
-    ) -> Result<LpTokenAmount, AddLiquidityError> {
-        if token_amount_in.raw() == 0 {
-            return Err(AddLiquidityError::NoTokensProvided);
-        }
+    /// * `params` - new fee curve parameters
+    pub fn propose_update(&mut self, params: PoolParams) -> Result<PendingUpdate, GovernanceError> {
+        let update = self.governance.propose(params, self.epoch)?;
+        self.emit(PoolEvent::ParamsUpdateProposed {
+            params,
+            executable_at: update.executable_at,
+        });
+        self.log_op(PoolOp::Unreplayable);
+        Ok(update)
+    }
 
-        let lp_tokens_raw_amount = match self.lp_token_amount.raw() {
-            0 => token_amount_in.raw(),
-            lp_amount => {
-                let Some(checked_mul) = lp_amount.checked_mul(token_amount_in.raw()) else {
-                    return Err(AddLiquidityError::TokenAmountTooBig);
-                };
-                checked_mul / self.total_val().raw()
-            }
-        };
-        let lp_amount = LpTokenAmount::from_raw_amount(lp_tokens_raw_amount);
+    /// Applies pending parameter change if its timelock passed and returns applied parameters
+    pub fn apply_pending(&mut self) -> Result<PoolParams, GovernanceError> {
+        let PendingUpdate { params, .. } = self.governance.take_executable(self.epoch)?;
+        self.min_fee = params.min_fee;
+        self.max_fee = params.max_fee;
+        self.liquidity_target = params.liquidity_target;
+        self.refresh_fee_curve();
+        self.emit(PoolEvent::ParamsUpdated { params });
+        self.log_op(PoolOp::Unreplayable);
+        Ok(params)
+    }
 
-        self.token_amount = self.token_amount + token_amount_in;
-        self.lp_token_amount = self.lp_token_amount + lp_amount;
+    /// Cancels pending parameter change and returns its parameters
+    pub fn cancel_pending(&mut self) -> Result<PoolParams, GovernanceError> {
+        let PendingUpdate { params, .. } = self.governance.cancel()?;
+        self.emit(PoolEvent::ParamsUpdateCancelled { params });
+        self.log_op(PoolOp::Unreplayable);
+        Ok(params)
+    }
 
-        Ok(lp_amount)
+    /// Limits how much a single `update_fees` call can move min and max fee,
+    /// `None` removes the limit
+    pub fn set_max_fee_change(&mut self, max_fee_change: Option<Percentage>) {
+        self.max_fee_change = max_fee_change;
+        self.log_op(PoolOp::Unreplayable);
     }
 
-    /// Returns tuple consisting of unstaked and staked token amounts withdrawn from the pool.
+    /// Immediately updates min and max fee. Fails if either fee moves by more than
+    /// the configured max fee change.
     ///
     /// # Arguments
     ///
-    /// * `lp_amount_out` - lp token amount that the caller wants to withdraw from the pool
-    pub fn remove_liquidity(
+    /// * `min_fee` - new min fee
+    /// * `max_fee` - new max fee
+    pub fn update_fees(
         &mut self,
-        lp_amount_out: LpTokenAmount,
-    ) -> Result<(TokenAmount, StakedTokenAmount), RemoveLiquidityError> {
-        if lp_amount_out > self.lp_token_amount {
-            return Err(RemoveLiquidityError::NotEnoughTokens {
-                withdraw_amount: lp_amount_out,
-                pool_capacity: self.lp_token_amount,
-            });
+        min_fee: Percentage,
+        max_fee: Percentage,
+    ) -> Result<(), GovernanceError> {
+        let params = PoolParams {
+            min_fee,
+            max_fee,
+            ..self.params()
+        };
+        params.validate()?;
+
+        if let Some(max_change) = self.max_fee_change {
+            let change = self
+                .min_fee
+                .raw()
+                .abs_diff(min_fee.raw())
+                .max(self.max_fee.raw().abs_diff(max_fee.raw()));
+            if change > max_change.raw() {
+                return Err(GovernanceError::FeeChangeTooBig {
+                    change: Percentage::from_raw_amount(change),
+                    max_change,
+                });
+            }
         }
 
-        let calculate_raw_out = |raw_amount: Uint| {
-            let Some(checked_mul) = raw_amount.checked_mul(lp_amount_out.raw()) else {
-                return Err(RemoveLiquidityError::WithdrawCalculationOverflow);
-            };
-            Ok(checked_mul / self.lp_token_amount.raw())
-        };
+        self.min_fee = min_fee;
+        self.max_fee = max_fee;
+        self.refresh_fee_curve();
+        self.emit(PoolEvent::ParamsUpdated { params });
+        self.log_op(PoolOp::Unreplayable);
+        Ok(())
+    }
 
-        let token_out = TokenAmount::from_raw_amount(calculate_raw_out(self.token_amount.raw())?);
-        let staked_out =
-            StakedTokenAmount::from_raw_amount(calculate_raw_out(self.st_token_amount.raw())?);
+    fn refresh_fee_curve(&mut self) {
+        self.fee_curve = FeeCurve::new(self.min_fee, self.max_fee, self.liquidity_target);
+    }
 
-        self.token_amount = self.token_amount - token_out;
-        self.st_token_amount = self.st_token_amount - staked_out;
-        self.lp_token_amount = self.lp_token_amount - lp_amount_out;
+    /// Sets share of swap fees that goes to the treasury instead of LPs
+    pub fn set_treasury_cut(&mut self, treasury_cut: Percentage) {
+        self.treasury_cut = treasury_cut;
+        self.log_op(PoolOp::Unreplayable);
+    }
 
-        Ok((token_out, staked_out))
+    /// Returns cumulative fee revenue of the pool
+    pub fn fee_revenue(&self) -> &FeeRevenue {
+        &self.fee_revenue
     }
 
-    /// Returns amount of tokens granted to the person executing swap.
+    /// Returns lifetime operation counters and volumes
+    pub fn counters(&self) -> &PoolCounters {
+        &self.counters
+    }
+
+    /// Returns treasury fees that can be claimed right now
+    pub fn claimable_treasury_fees(&self) -> TokenAmount {
+        self.fee_revenue.claimable_treasury
+    }
+
+    /// Returns amount of treasury fees transferred to the caller
+    pub fn claim(&mut self) -> Result<TokenAmount, ClaimError> {
+        if self.fee_revenue.claimable_treasury.raw() == 0 {
+            return Err(ClaimError::NothingToClaim);
+        }
+
+        let amount = self.fee_revenue.claim_treasury();
+        self.emit(PoolEvent::TreasuryFeesClaimed { amount });
+        self.log_op(PoolOp::Unreplayable);
+
+        Ok(amount)
+    }
+
+    /// Sets share of swap fees paid into the insurance fund instead of LPs, taken from
+    /// what's left after the treasury cut
+    pub fn set_insurance_cut(&mut self, insurance_cut: Percentage) {
+        self.insurance.cut = insurance_cut;
+        self.log_op(PoolOp::Unreplayable);
+    }
+
+    /// Returns insurance fund cut, balance and tokens drawn from it so far
+    pub fn insurance_fund(&self) -> &InsuranceFund {
+        &self.insurance
+    }
+
+    /// Returns insurance fund balance compared to value of the staked tokens it insures
+    pub fn insurance_stats(&self) -> InsuranceStats {
+        let exposure =
+            self.st_token_amount.raw() as u128 * self.price.raw() as u128 / SCALE as u128;
+        InsuranceStats::new(&self.insurance, exposure, self.total_val())
+    }
+
+    /// Moves tokens from the insurance fund to pool liquidity, raising LP token value to
+    /// cover a shortfall after a slashing or depeg. Meant to be called by an authority,
+    /// `PermissionedPool` restricts it to admins.
     ///
     /// # Arguments
     ///
-    /// * `swap_amount` - amount of staked tokens in incoming swap
-    pub fn swap(&mut self, swap_amount: StakedTokenAmount) -> Result<TokenAmount, SwapError> {
-        if swap_amount.raw() == 0 {
-            return Err(SwapError::ZeroTokensAsArgument);
+    /// * `amount` - tokens drawn from the fund
+    pub fn cover_shortfall(&mut self, amount: TokenAmount) -> Result<(), InsuranceError> {
+        let Some(token_amount) = self.token_amount.checked_add(amount) else {
+            return Err(InsuranceError::LiquidityOverflow { amount });
+        };
+        self.insurance.draw(amount)?;
+        self.token_amount = token_amount;
+        self.emit(PoolEvent::InsuranceDrawn { amount });
+        self.log_op(PoolOp::Unreplayable);
+        Ok(())
+    }
+
+    /// Returns and clears events emitted since the last call
+    pub fn drain_events(&mut self) -> Vec<PoolEvent> {
+        self.events.drain()
+    }
+
+    /// Returns amount of events dropped from the full queue since the pool was created
+    #[cfg(feature = "bounded-events")]
+    pub fn dropped_events(&self) -> u64 {
+        self.events.dropped()
+    }
+
+    /// Registers sink receiving every event as it's emitted, `None` queues events for
+    /// `drain_events` again. Events queued before the sink was registered stay queued.
+    pub fn set_event_sink(&mut self, sink: Option<Box<dyn EventSink>>) {
+        self.event_sink = sink;
+    }
+
+    pub(crate) fn emit(&mut self, event: PoolEvent) {
+        self.subscribers.publish(&event);
+        match &mut self.event_sink {
+            Some(sink) => sink.on_event(&event),
+            None => self.events.push(event),
         }
+    }
 
-        let amount_out_before_fees = swap_amount.into_token_amount(self.price);
-        if amount_out_before_fees > self.token_amount {
-            return Err(SwapError::PoolNotEnoughTokens {
-                token_amount: amount_out_before_fees,
-                pool_capacity: self.token_amount,
-            });
+    /// Returns current epoch of the pool
+    pub fn epoch(&self) -> Epoch {
+        self.epoch
+    }
+
+    /// Moves pool clock forward by given amount of epochs, the clock stops at `Epoch::MAX`
+    pub fn advance_epoch(&mut self, epochs: Epoch) {
+        self.epoch = self.epoch.saturating_add(epochs);
+        if let Some(surcharge) = &mut self.surcharge {
+            surcharge.on_epochs(epochs);
         }
+        self.emit(PoolEvent::EpochAdvanced {
+            epochs,
+            epoch: self.epoch,
+        });
+        self.log_op(PoolOp::AdvanceEpoch { epochs });
+    }
 
-        let fee = self.fee(self.token_amount - amount_out_before_fees);
+    /// Enables (or disables with `None`) temporary fee surcharge raised after large swaps
+    pub fn set_swap_surcharge(&mut self, config: Option<SurchargeConfig>) {
+        self.surcharge = config.map(SwapSurcharge::new);
+        self.log_op(PoolOp::Unreplayable);
+    }
 
-        let amount_out = amount_out_before_fees.apply_fee(fee);
+    /// Returns surcharge currently added on top of swap fees
+    pub fn active_surcharge(&self) -> Percentage {
+        self.surcharge
+            .map(|surcharge| surcharge.active())
+            .unwrap_or(Percentage::from_raw_amount(0))
+    }
 
-        self.token_amount = self.token_amount - amount_out;
-        self.st_token_amount = self.st_token_amount + swap_amount;
+    /// Enables (or disables with `None`) liquidity bootstrapping mode, starting the decay
+    /// of its surcharge at the current epoch
+    pub fn set_bootstrap(&mut self, config: Option<BootstrapConfig>) {
+        self.bootstrap = config.map(|config| LiquidityBootstrap::new(config, self.epoch));
+        self.log_op(PoolOp::Unreplayable);
+    }
+
+    /// Restores liquidity bootstrapping mode started at an arbitrary epoch
+    pub(crate) fn restore_bootstrap(&mut self, bootstrap: Option<LiquidityBootstrap>) {
+        self.bootstrap = bootstrap;
+        self.log_op(PoolOp::Unreplayable);
+    }
 
-        Ok(amount_out)
+    /// Returns liquidity bootstrapping mode together with the epoch it started at, `None` if
+    /// it's disabled
+    pub fn bootstrap(&self) -> Option<&LiquidityBootstrap> {
+        self.bootstrap.as_ref()
     }
 
-    /// Returns total value stored inside the pool (tokens + staked tokens) as `TokenAmount`
-    fn total_val(&self) -> TokenAmount {
-        let staked_value =
-            TokenAmount::from_raw_amount(self.st_token_amount.raw() * self.price.raw() / SCALE);
-        self.token_amount + staked_value
+    /// Returns surcharge currently added on top of swap fees by liquidity bootstrapping
+    pub fn bootstrap_surcharge(&self) -> Percentage {
+        self.bootstrap
+            .map(|bootstrap| bootstrap.surcharge(self.epoch))
+            .unwrap_or(Percentage::from_raw_amount(0))
     }
 
-    /// Returns pool swap percentage fee.
+    /// Returns policy used to adjust swap fees
+    pub fn fee_policy(&self) -> FeePolicy {
+        self.fee_policy
+    }
+
+    /// Selects policy used to adjust swap fees
+    pub fn set_fee_policy(&mut self, fee_policy: FeePolicy) {
+        self.fee_policy = fee_policy;
+        self.log_op(PoolOp::Unreplayable);
+    }
+
+    /// Injects oracle that drives the pool price. Price is refreshed from the oracle
+    /// before every operation depending on it.
+    pub fn set_oracle(&mut self, oracle: impl PriceOracle + 'static) {
+        self.oracle = Box::new(oracle);
+    }
+
+    /// Pins price of StakedToken in respect to Token, replacing injected oracle with
+    /// a `FixedOracle`, and records it in the price history.
     ///
     /// # Arguments
     ///
-    /// * `amount_after` - Token amount after operation
-    fn fee(&self, amount_after: TokenAmount) -> Percentage {
-        // FEE FORMULA
-        // fee = max_fee - (max_fee - min_fee) * amount_after / target
-        let rhs =
-            (self.max_fee - self.min_fee).raw() * amount_after.raw() / self.liquidity_target.raw();
-        let rhs = rhs.min(self.max_fee.raw());
+    /// * `price` - new price, has to be non-zero
+    pub fn set_price(&mut self, price: Price) -> Result<(), PriceUpdateError> {
+        if price.raw() == 0 {
+            return Err(PriceUpdateError::ZeroPrice);
+        }
+        if !self.is_price_direction_allowed(price) {
+            return Err(PriceUpdateError::PriceDecreased {
+                price,
+                last_price: self.price,
+            });
+        }
+        if let Err((deviation, max_deviation)) = self.check_deviation(price) {
+            return Err(PriceUpdateError::DeviationTooLarge {
+                price,
+                deviation,
+                max_deviation,
+            });
+        }
 
-        // we're capping rhs to max_fee so there's no need to check if current_percentage is over it later on
-        // and we avoid overflows
-        let current_percentage = (self.max_fee.raw() - rhs).max(self.min_fee.raw());
-        Percentage::from_raw_amount(current_percentage)
-    }
-}
+        self.oracle = Box::new(FixedOracle::new(price));
+        self.accept_price(price);
+        self.log_op(PoolOp::SetPrice { price });
 
-#[cfg(test)]
-mod tests {
-    use std::error::Error;
+        Ok(())
+    }
 
-    use rstest::{fixture, rstest};
+    /// Queries the oracle and accepts its price, smoothed according to the pool's
+    /// `PriceSmoothing`, if it changed. Returns current price.
+    pub fn refresh_price(&mut self) -> Result<Price, OracleError> {
+        let price = self.oracle.price()?;
+        if price.raw() == 0 {
+            return Err(OracleError::ZeroPrice);
+        }
+        if price == self.price {
+            return Ok(price);
+        }
+        if !self.is_price_direction_allowed(price) {
+            return Err(OracleError::PriceDecreased {
+                price,
+                last_price: self.price,
+            });
+        }
 
-    use super::*;
+        if let Err((deviation, max_deviation)) = self.check_deviation(price) {
+            if self.rejected_price != Some(price) {
+                self.rejected_price = Some(price);
+                self.emit(PoolEvent::PriceRejected {
+                    price,
+                    last_price: self.price,
+                    deviation,
+                });
+                self.log_op(PoolOp::Unreplayable);
+            }
+            return Err(OracleError::DeviationTooLarge {
+                price,
+                deviation,
+                max_deviation,
+            });
+        }
 
-    #[fixture]
-    fn story_example_pool() -> LpPool {
-        LpPool {
-            price: 1.5.into(),
-            token_amount: 0.into(),
-            st_token_amount: 0.into(),
-            lp_token_amount: 0.into(),
-            liquidity_target: 90.into(),
-            min_fee: 0.001.into(),
-            max_fee: 0.09.into(),
+        let price = self.price_smoothing.apply(self.price, price);
+        if price != self.price {
+            self.accept_price(price);
+            self.log_op(PoolOp::SetPrice { price });
         }
+        Ok(price)
     }
 
-    #[fixture]
-    fn empty_pool() -> LpPool {
-        LpPool {
-            price: 2.into(),
-            token_amount: 0.into(),
-            st_token_amount: 0.into(),
-            lp_token_amount: 0.into(),
-            liquidity_target: 100.into(),
-            min_fee: 0.0.into(),
-            max_fee: 0.09.into(),
+    /// Selects smoothing applied to oracle prices before they're used in swap math.
+    /// Explicitly set prices aren't smoothed.
+    pub fn set_price_smoothing(&mut self, price_smoothing: PriceSmoothing) {
+        self.price_smoothing = price_smoothing;
+        self.log_op(PoolOp::Unreplayable);
+    }
+
+    /// Makes the pool reject oracle prices deviating from the last accepted price by more
+    /// than `max_price_deviation`, `None` disables the circuit breaker
+    pub fn set_max_price_deviation(&mut self, max_price_deviation: Option<Percentage>) {
+        self.max_price_deviation = max_price_deviation;
+        self.log_op(PoolOp::Unreplayable);
+    }
+
+    /// Returns last oracle price rejected by the circuit breaker
+    pub fn rejected_price(&self) -> Option<Price> {
+        self.rejected_price
+    }
+
+    /// Forces the price during incident response, bypassing deviation and monotonicity
+    /// checks. The oracle is replaced with a `FixedOracle` and the override is recorded in
+    /// the audit log and emitted as an event.
+    ///
+    /// # Arguments
+    ///
+    /// * `price` - new price, has to be non-zero
+    /// * `reason` - mandatory explanation of the override
+    pub fn force_price(
+        &mut self,
+        price: Price,
+        reason: impl Into<String>,
+    ) -> Result<(), PriceUpdateError> {
+        let reason = reason.into();
+        if price.raw() == 0 {
+            return Err(PriceUpdateError::ZeroPrice);
+        }
+        if reason.trim().is_empty() {
+            return Err(PriceUpdateError::MissingReason);
         }
+
+        let record = PriceOverride {
+            epoch: self.epoch,
+            previous_price: self.price,
+            price,
+            reason,
+        };
+        self.oracle = Box::new(FixedOracle::new(price));
+        self.accept_price(price);
+        self.emit(PoolEvent::PriceOverridden(record.clone()));
+        self.price_overrides.push(record);
+        self.log_op(PoolOp::Unreplayable);
+
+        Ok(())
     }
 
-    #[fixture]
-    fn non_empty_pool() -> LpPool {
-        LpPool {
-            price: 5.into(),
-            token_amount: (2 as Uint).pow(20).into(),
-            st_token_amount: 30.into(),
-            lp_token_amount: 250.into(),
-            liquidity_target: 100.into(),
-            min_fee: 0.1.into(),
-            max_fee: 0.2.into(),
+    /// Returns audit log of manual price overrides, from the oldest to the newest
+    pub fn price_overrides(&self) -> &[PriceOverride] {
+        &self.price_overrides
+    }
+
+    /// Admin override accepting the last price rejected by the circuit breaker
+    pub fn approve_rejected_price(&mut self) -> Result<Price, PriceUpdateError> {
+        let price = self
+            .rejected_price
+            .take()
+            .ok_or(PriceUpdateError::NoRejectedPrice)?;
+        if !self.is_price_direction_allowed(price) {
+            self.rejected_price = Some(price);
+            return Err(PriceUpdateError::PriceDecreased {
+                price,
+                last_price: self.price,
+            });
         }
+        self.accept_price(price);
+        self.emit(PoolEvent::RejectedPriceApproved { price });
+        self.log_op(PoolOp::Unreplayable);
+        Ok(price)
     }
 
-    #[rstest]
-    fn can_calculate_fees(empty_pool: LpPool, non_empty_pool: LpPool) {
-        assert_eq!(empty_pool.fee(0.into()).raw(), Percentage::from(0.09).raw());
-        assert_eq!(
-            empty_pool.fee(100.into()).raw(),
-            Percentage::from(0.0).raw()
-        );
-        assert_eq!(
-            empty_pool.fee(50.into()).raw(),
-            Percentage::from(0.045).raw()
-        );
+    /// Makes the pool reject price decreases (staking exchange rates only grow absent
+    /// slashing) unless slashing is signalled with `signal_slashing`
+    pub fn set_monotonic_price(&mut self, monotonic_price: bool) {
+        self.monotonic_price = monotonic_price;
+        self.log_op(PoolOp::Unreplayable);
+    }
 
-        assert_eq!(
-            non_empty_pool.fee(0.into()).raw(),
-            Percentage::from(0.2).raw()
-        );
-        assert_eq!(
-            non_empty_pool.fee(100.into()).raw(),
-            Percentage::from(0.1).raw()
-        );
-        assert_eq!(
-            non_empty_pool.fee(50.into()).raw(),
-            Percentage::from(0.15).raw()
-        );
+    /// Signals slashing of the staked tokens, allowing the next price update in monotonic
+    /// mode to decrease the price
+    pub fn signal_slashing(&mut self) {
+        self.slashing_signalled = true;
+        self.emit(PoolEvent::SlashingSignalled {
+            last_price: self.price,
+        });
+        self.log_op(PoolOp::Unreplayable);
     }
 
-    #[rstest]
-    fn can_add_liquidity(mut empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
-        let added = empty_pool.add_liquidity(TokenAmount::from(20))?;
-        assert_eq!(
-            added,
-            LpTokenAmount::from(20),
-            "initial liquidity added should match token amount added"
-        );
+    fn is_price_direction_allowed(&self, price: Price) -> bool {
+        !self.monotonic_price || self.slashing_signalled || price >= self.price
+    }
 
-        Ok(())
+    /// Returns deviation and max deviation if price deviates too much from the last one
+    fn check_deviation(&self, price: Price) -> Result<(), (Percentage, Percentage)> {
+        let Some(max_deviation) = self.max_price_deviation else {
+            return Ok(());
+        };
+        let deviation = price.raw().abs_diff(self.price.raw()) as u128 * SCALE as u128
+            / self.price.raw().max(1) as u128;
+        let deviation = Percentage::from_raw_amount(deviation.min(Uint::MAX as u128) as Uint);
+        match deviation > max_deviation {
+            true => Err((deviation, max_deviation)),
+            false => Ok(()),
+        }
     }
 
-    #[rstest]
-    fn errors_on_empty_add_liquidity(mut story_example_pool: LpPool) {
-        assert!(
-            story_example_pool
-                .add_liquidity(TokenAmount::from_raw_amount(0))
-                .is_err(),
-            "adding zero liquidity should fail"
-        )
+    /// Overwrites pool balances, used when mirroring externally held state
+    pub(crate) fn set_balances(
+        &mut self,
+        token_amount: TokenAmount,
+        st_token_amount: StakedTokenAmount,
+        lp_token_amount: LpTokenAmount,
+    ) {
+        self.token_amount = token_amount;
+        self.st_token_amount = st_token_amount;
+        self.lp_token_amount = lp_token_amount;
     }
 
-    #[rstest]
-    fn can_remove_liquidity(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
-        let res = non_empty_pool.remove_liquidity(LpTokenAmount::from(10))?;
-        assert_ne!(res.0, TokenAmount::from(0), "removing liquidity from the pool consisting of both assets should not yield zero value");
-        assert_ne!(res.1, StakedTokenAmount::from(0), "removing liquidity from the pool consisting of both assets should not yield zero value");
-        Ok(())
+    /// Returns epoch at which the price was last changed or explicitly set
+    pub fn price_updated_at(&self) -> Epoch {
+        self.price_updated_at
     }
 
-    #[rstest]
-    fn errors_on_remove_liquidity_bigger_than_pool(mut empty_pool: LpPool) {
-        let res = empty_pool.remove_liquidity(LpTokenAmount::from(1000));
-        assert!(res.is_err());
+    /// Makes swaps fail when the price wasn't updated for more than `max_price_age` epochs,
+    /// `None` disables the check. Oracles repeating the same price don't count as updates
+    /// so a dead feed is detected as well.
+    pub fn set_max_price_age(&mut self, max_price_age: Option<Epoch>) {
+        self.max_price_age = max_price_age;
+        self.log_op(PoolOp::Unreplayable);
     }
 
-    #[rstest]
-    fn can_execute_swap(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
-        let swap_result = non_empty_pool.swap(StakedTokenAmount::from(3))?;
-        assert_ne!(
-            swap_result,
-            TokenAmount::from(0),
-            "successful swap should result in non-zero token amount granted to the caller"
-        );
-        Ok(())
+    fn accept_price(&mut self, price: Price) {
+        if price < self.price {
+            self.slashing_signalled = false;
+        }
+        if self.rejected_price == Some(price) {
+            self.rejected_price = None;
+        }
+        self.emit(PoolEvent::PriceUpdated {
+            previous_price: self.price,
+            price,
+        });
+        self.price = price;
+        self.price_updated_at = self.epoch;
+        self.price_history.push(self.epoch, price);
+        // price only changes here, so observing accepted prices is enough for the accumulator
+        self.twap.observe(self.epoch, price);
     }
 
-    #[rstest]
-    fn swap_errors_on_not_enough_tokens(mut empty_pool: LpPool) {
-        let swap_result = empty_pool.swap(StakedTokenAmount::from(3));
-        assert!(
-            swap_result.is_err(),
-            "swap on empty pool should always yield err"
-        );
+    /// Returns recently accepted prices
+    pub fn price_history(&self) -> &PriceHistory {
+        &self.price_history
     }
 
-    #[rstest]
-    fn swap_errors_on_zero_token_argument(mut empty_pool: LpPool) {
-        let swap_result = empty_pool.swap(StakedTokenAmount::from(0));
-        assert!(
-            swap_result.is_err(),
-            "swap on empty pool should always yield err"
-        );
+    /// Returns lowest and highest price active during the last `window` epochs
+    pub fn min_max(&self, window: Epoch) -> Option<(Price, Price)> {
+        self.price_history.min_max(self.epoch, window)
     }
 
-    #[rstest]
-    fn story_example(mut story_example_pool: LpPool) -> Result<(), Box<dyn Error>> {
-        assert_eq!(
-            story_example_pool.add_liquidity(TokenAmount::from(100))?,
-            LpTokenAmount::from(100),
-            "initial add liquidity"
-        );
+    /// Returns volatility of prices active during the last `window` epochs
+    pub fn volatility(&self, window: Epoch) -> Percentage {
+        self.price_history.volatility_in_window(self.epoch, window)
+    }
+
+    /// Returns time weighted average price over the last `window` epochs, `None` if the
+    /// window reaches before the oldest kept checkpoint
+    pub fn twap(&self, window: Epoch) -> Option<Price> {
+        self.twap.twap(self.epoch, window)
+    }
+
+    /// Returns Amount of LP tokens granted to the caller.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_amount_in` - amount of 'unstaked' tokens provided by the caller
+    pub fn add_liquidity(
+        &mut self,
+        token_amount_in: TokenAmount,
+    ) -> Result<LpTokenAmount, AddLiquidityError> {
+        let lp_amount = self.deposit(token_amount_in)?;
+        self.emit(PoolEvent::LiquidityAdded {
+            account: None,
+            amount: token_amount_in,
+            lp_amount,
+        });
+        self.log_op(PoolOp::AddLiquidity {
+            account: None,
+            amount: token_amount_in,
+        });
+        Ok(lp_amount)
+    }
+
+    fn deposit(
+        &mut self,
+        token_amount_in: TokenAmount,
+    ) -> Result<LpTokenAmount, AddLiquidityError> {
+        if token_amount_in.raw() == 0 {
+            return Err(AddLiquidityError::NoTokensProvided);
+        }
+        self.refresh_price()?;
+
+        let lp_tokens_raw_amount = match (self.lp_token_amount.raw(), self.total_val()) {
+            (0, _) => token_amount_in.raw(),
+            // lp tokens of a pool without any value can't be priced
+            (_, 0) => return Err(AddLiquidityError::PoolWithoutValue),
+            (lp_amount, total_value) => {
+                Uint::try_from(lp_amount as u128 * token_amount_in.raw() as u128 / total_value)
+                    .map_err(|_| AddLiquidityError::TokenAmountTooBig)?
+            }
+        };
+        let (Some(token_amount), Some(lp_token_amount)) = (
+            self.token_amount.raw().checked_add(token_amount_in.raw()),
+            self.lp_token_amount.raw().checked_add(lp_tokens_raw_amount),
+        ) else {
+            return Err(AddLiquidityError::TokenAmountTooBig);
+        };
+        let lp_amount = LpTokenAmount::from_raw_amount(lp_tokens_raw_amount);
+
+        self.token_amount = TokenAmount::from_raw_amount(token_amount);
+        self.lp_token_amount = LpTokenAmount::from_raw_amount(lp_token_amount);
+        self.counters.record_deposit(token_amount_in);
+        self.on_operation();
+
+        Ok(lp_amount)
+    }
+
+    /// Same as `add_liquidity` but records minted lp tokens in the account's position.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - account providing liquidity
+    /// * `token_amount_in` - amount of 'unstaked' tokens provided by the caller
+    pub fn add_liquidity_for(
+        &mut self,
+        account: AccountId,
+        token_amount_in: TokenAmount,
+    ) -> Result<LpTokenAmount, AddLiquidityError> {
+        let lp_amount = self.deposit(token_amount_in)?;
+        self.positions.deposit(account, token_amount_in, lp_amount);
+        self.emit(PoolEvent::LiquidityAdded {
+            account: Some(account),
+            amount: token_amount_in,
+            lp_amount,
+        });
+        self.log_op(PoolOp::AddLiquidity {
+            account: Some(account),
+            amount: token_amount_in,
+        });
+        Ok(lp_amount)
+    }
+
+    /// Same as `remove_liquidity` but withdraws lp tokens from the account's position.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - account withdrawing liquidity
+    /// * `lp_amount_out` - lp token amount that the caller wants to withdraw from the pool
+    pub fn remove_liquidity_for(
+        &mut self,
+        account: AccountId,
+        lp_amount_out: LpTokenAmount,
+    ) -> Result<(TokenAmount, StakedTokenAmount), RemoveLiquidityError> {
+        let position_amount = self
+            .positions
+            .get(account)
+            .map(|position| position.lp_tokens)
+            .unwrap_or_default();
+        if lp_amount_out > position_amount {
+            return Err(RemoveLiquidityError::PositionNotEnoughTokens {
+                withdraw_amount: lp_amount_out,
+                position_amount,
+            });
+        }
+
+        // withdrawn value never exceeds value of the lp tokens, so it fits once that does
+        if self.lp_tokens_value_raw(lp_amount_out) > Uint::MAX as u128 {
+            return Err(RemoveLiquidityError::WithdrawCalculationOverflow);
+        }
+        let (token_out, staked_out) = self.withdraw(lp_amount_out)?;
+        let value_out = token_out + staked_out.into_token_amount(self.price);
+        self.positions.withdraw(account, lp_amount_out, value_out);
+        self.emit(PoolEvent::LiquidityRemoved {
+            account: Some(account),
+            lp_amount: lp_amount_out,
+            tokens: token_out,
+            staked_tokens: staked_out,
+        });
+        self.log_op(PoolOp::RemoveLiquidity {
+            account: Some(account),
+            lp_amount: lp_amount_out,
+        });
+
+        Ok((token_out, staked_out))
+    }
+
+    /// Returns ledger of all tracked liquidity positions
+    pub fn positions(&self) -> &Positions {
+        &self.positions
+    }
+
+    /// Returns liquidity position of the account
+    pub fn position(&self, account: AccountId) -> Option<&Position> {
+        self.positions.get(account)
+    }
+
+    /// Returns fees earned, value compared to holding and realized/unrealized profit and loss
+    /// of the account's position
+    pub fn position_pnl(&self, account: AccountId) -> Option<PositionPnl> {
+        let position = self.positions.get(account)?;
+        self.positions
+            .pnl(account, self.lp_tokens_value(position.lp_tokens))
+    }
+
+    /// Returns value of given lp tokens as `TokenAmount`, saturated at `Uint::MAX` if a
+    /// price update made it unrepresentable
+    pub fn lp_tokens_value(&self, lp_tokens: LpTokenAmount) -> TokenAmount {
+        TokenAmount::from_raw_amount(
+            self.lp_tokens_value_raw(lp_tokens).min(Uint::MAX as u128) as Uint
+        )
+    }
+
+    fn lp_tokens_value_raw(&self, lp_tokens: LpTokenAmount) -> u128 {
+        match self.lp_token_amount.raw() {
+            0 => 0,
+            lp_amount => {
+                // split division keeps products of value and lp tokens within u128
+                let (lp_amount, lp_tokens) = (lp_amount as u128, lp_tokens.raw() as u128);
+                let total_value = self.total_val();
+                (total_value / lp_amount)
+                    .saturating_mul(lp_tokens)
+                    .saturating_add(total_value % lp_amount * lp_tokens / lp_amount)
+            }
+        }
+    }
+
+    /// Returns tuple consisting of unstaked and staked token amounts withdrawn from the pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `lp_amount_out` - lp token amount that the caller wants to withdraw from the pool
+    pub fn remove_liquidity(
+        &mut self,
+        lp_amount_out: LpTokenAmount,
+    ) -> Result<(TokenAmount, StakedTokenAmount), RemoveLiquidityError> {
+        let (tokens, staked_tokens) = self.withdraw(lp_amount_out)?;
+        self.emit(PoolEvent::LiquidityRemoved {
+            account: None,
+            lp_amount: lp_amount_out,
+            tokens,
+            staked_tokens,
+        });
+        self.log_op(PoolOp::RemoveLiquidity {
+            account: None,
+            lp_amount: lp_amount_out,
+        });
+        Ok((tokens, staked_tokens))
+    }
+
+    fn withdraw(
+        &mut self,
+        lp_amount_out: LpTokenAmount,
+    ) -> Result<(TokenAmount, StakedTokenAmount), RemoveLiquidityError> {
+        if lp_amount_out > self.lp_token_amount {
+            return Err(RemoveLiquidityError::NotEnoughTokens {
+                withdraw_amount: lp_amount_out,
+                pool_capacity: self.lp_token_amount,
+            });
+        }
+
+        let calculate_raw_out = |raw_amount: Uint| match self.lp_token_amount.raw() {
+            // pool without lp tokens can only be asked to withdraw zero lp tokens
+            0 => Ok(0),
+            lp_amount => mul_div(raw_amount, lp_amount_out.raw(), lp_amount)
+                .ok_or(RemoveLiquidityError::WithdrawCalculationOverflow),
+        };
+
+        let token_out = TokenAmount::from_raw_amount(calculate_raw_out(self.token_amount.raw())?);
+        let staked_out =
+            StakedTokenAmount::from_raw_amount(calculate_raw_out(self.st_token_amount.raw())?);
+
+        self.token_amount = self.token_amount - token_out;
+        self.st_token_amount = self.st_token_amount - staked_out;
+        self.lp_token_amount = self.lp_token_amount - lp_amount_out;
+        self.counters.record_withdrawal(token_out, staked_out);
+        self.on_operation();
+
+        Ok((token_out, staked_out))
+    }
+
+    /// Returns amount of tokens granted to the person executing swap.
+    ///
+    /// # Arguments
+    ///
+    /// * `swap_amount` - amount of staked tokens in incoming swap
+    pub fn swap(&mut self, swap_amount: StakedTokenAmount) -> Result<TokenAmount, SwapError> {
+        Ok(self.execute_swap(None, swap_amount)?.amount_out)
+    }
+
+    /// Same as `swap` but charges the fee discounted by the tier the account qualifies for
+    /// and records the swap in the account's volume.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - account executing the swap
+    /// * `swap_amount` - amount of staked tokens in incoming swap
+    pub fn swap_for(
+        &mut self,
+        account: AccountId,
+        swap_amount: StakedTokenAmount,
+    ) -> Result<SwapOutcome, SwapError> {
+        self.execute_swap(Some(account), swap_amount)
+    }
+
+    /// Same as `swap` but swaps only as many staked tokens as the pool has tokens for,
+    /// instead of failing with `PoolNotEnoughTokens`. Returns tokens granted to the caller
+    /// together with the unfilled remainder of the swap amount, pools without tokens fill
+    /// nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `swap_amount` - amount of staked tokens in incoming swap
+    pub fn swap_partial(
+        &mut self,
+        swap_amount: StakedTokenAmount,
+    ) -> Result<(TokenAmount, StakedTokenAmount), SwapError> {
+        if swap_amount.raw() == 0 {
+            return Err(SwapError::ZeroTokensAsArgument);
+        }
+        self.refresh_price()?;
+
+        let filled =
+            StakedTokenAmount::from_raw_amount(swap_amount.raw().min(self.max_swap_amount().raw()));
+        if filled.raw() == 0 {
+            return Ok((TokenAmount::from_raw_amount(0), swap_amount));
+        }
+        let outcome = self.fill_swap(None, filled)?;
+        Ok((outcome.amount_out, swap_amount - filled))
+    }
+
+    /// Returns largest staked token amount whose value at the last accepted price doesn't
+    /// exceed tokens held by the pool
+    fn max_swap_amount(&self) -> StakedTokenAmount {
+        // value of `amount` is truncated, so it stays within tokens held by the pool as long
+        // as `amount * price < (token_amount + 1) * SCALE`
+        let capacity = (self.token_amount.raw() as u128 + 1) * SCALE as u128 - 1;
+        let amount = capacity
+            .checked_div(self.price.raw() as u128)
+            .unwrap_or(u128::MAX);
+        StakedTokenAmount::from_raw_amount(amount.min(Uint::MAX as u128) as Uint)
+    }
+
+    fn execute_swap(
+        &mut self,
+        account: Option<AccountId>,
+        swap_amount: StakedTokenAmount,
+    ) -> Result<SwapOutcome, SwapError> {
+        if swap_amount.raw() == 0 {
+            return Err(SwapError::ZeroTokensAsArgument);
+        }
+        self.refresh_price()?;
+        self.fill_swap(account, swap_amount)
+    }
+
+    /// Executes swap at the already refreshed price
+    fn fill_swap(
+        &mut self,
+        account: Option<AccountId>,
+        swap_amount: StakedTokenAmount,
+    ) -> Result<SwapOutcome, SwapError> {
+        let SwapQuote {
+            amount_out_before_fees,
+            fee,
+            amount_out,
+            tier,
+        } = self.quote_swap_with(swap_amount, self.fee_surcharge(), account)?;
+
+        let fee_amount = amount_out_before_fees - amount_out;
+        let (treasury_portion, insurance_portion) =
+            self.fee_revenue
+                .record_split(fee_amount, self.treasury_cut, self.insurance.cut);
+        self.insurance.deposit(insurance_portion);
+        let lp_portion = fee_amount - treasury_portion - insurance_portion;
+        self.positions
+            .record_lp_fees(lp_portion, self.lp_token_amount);
+        self.emit(PoolEvent::FeesCollected {
+            lp_portion,
+            treasury_portion,
+            insurance_portion,
+        });
+
+        let pool_tokens_before = self.token_amount;
+        // treasury and insurance portions leave pool liquidity so that they don't increase
+        // LP token value
+        self.token_amount = self.token_amount - amount_out - treasury_portion - insurance_portion;
+        self.st_token_amount = self.st_token_amount + swap_amount;
+        self.volume_history
+            .record_swap(self.epoch, swap_amount, amount_out_before_fees);
+        if let Some(account) = account {
+            self.fee_discounts
+                .record_swap(account, amount_out_before_fees);
+        }
+        self.counters
+            .record_swap(swap_amount, amount_out, fee_amount);
+        self.on_operation();
+        if let Some(surcharge) = &mut self.surcharge {
+            surcharge.on_swap(amount_out_before_fees, pool_tokens_before);
+        }
+        self.emit(PoolEvent::Swapped {
+            account,
+            amount: swap_amount,
+            amount_out,
+            fee_amount,
+        });
+        self.log_op(PoolOp::Swap {
+            account,
+            amount: swap_amount,
+        });
+
+        Ok(SwapOutcome {
+            amount_out,
+            fee,
+            fee_amount,
+            tier,
+        })
+    }
+
+    /// Returns outcome `swap_for` would have for the given account and swap amount at the
+    /// last accepted price, without modifying the pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - account executing the swap
+    /// * `swap_amount` - amount of staked tokens in incoming swap
+    pub fn quote_swap_for(
+        &self,
+        account: AccountId,
+        swap_amount: StakedTokenAmount,
+    ) -> Result<SwapOutcome, SwapError> {
+        let quote = self.quote_swap_with(swap_amount, self.fee_surcharge(), Some(account))?;
+        Ok(SwapOutcome {
+            amount_out: quote.amount_out,
+            fee: quote.fee,
+            fee_amount: quote.amount_out_before_fees - quote.amount_out,
+            tier: quote.tier,
+        })
+    }
+
+    /// Replaces swap fee discount tiers, accounts qualifying for several tiers get the
+    /// largest discount
+    pub fn set_fee_discounts(&mut self, tiers: Vec<DiscountTier>) {
+        self.fee_discounts.set_tiers(tiers);
+        self.log_op(PoolOp::Unreplayable);
+    }
+
+    /// Returns discount tiers and swap volume recorded per account
+    pub fn fee_discounts(&self) -> &FeeDiscounts {
+        &self.fee_discounts
+    }
+
+    /// Returns tier with the largest discount the account currently qualifies for
+    pub fn discount_tier(&self, account: AccountId) -> Option<DiscountTier> {
+        let lp_tokens = self
+            .positions
+            .get(account)
+            .map(|position| position.lp_tokens)
+            .unwrap_or_default();
+        self.fee_discounts
+            .resolve(account, lp_tokens, self.lp_token_amount)
+    }
+
+    /// Returns fee percentage that `swap` would charge for the given swap amount
+    /// at the last accepted price, without modifying the pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `swap_amount` - amount of staked tokens in incoming swap
+    pub fn fee_for_swap(&self, swap_amount: StakedTokenAmount) -> Result<Percentage, SwapError> {
+        Ok(self.quote_swap(swap_amount)?.fee)
+    }
+
+    /// Returns tokens that `swap` would grant for the given swap amount at the last
+    /// accepted price, without modifying the pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `swap_amount` - amount of staked tokens in incoming swap
+    pub fn amount_for_swap(
+        &self,
+        swap_amount: StakedTokenAmount,
+    ) -> Result<TokenAmount, SwapError> {
+        Ok(self.quote_swap(swap_amount)?.amount_out)
+    }
+
+    /// Returns tokens that `swap` would grant for every swap amount, equal to calling
+    /// `amount_for_swap` for each of them. Work independent of the amount, like the
+    /// volatility measured by the fee policy, is done once for the whole batch, so routers
+    /// can evaluate many candidate sizes at the cost of a few arithmetic operations each.
+    ///
+    /// # Arguments
+    ///
+    /// * `swap_amounts` - amounts of staked tokens of candidate swaps
+    pub fn quote_swaps(
+        &self,
+        swap_amounts: &[StakedTokenAmount],
+    ) -> Vec<Result<TokenAmount, SwapError>> {
+        let surcharge = self.fee_surcharge();
+        swap_amounts
+            .iter()
+            .map(|&swap_amount| {
+                self.quote_swap_with(swap_amount, surcharge, None)
+                    .map(|quote| quote.amount_out)
+            })
+            .collect()
+    }
+
+    /// Returns fee charged for a marginal swap at the current liquidity, i.e. the position
+    /// on the fee curve
+    pub fn current_fee(&self) -> Percentage {
+        self.fee(self.token_amount)
+    }
+
+    /// Calculates swap outcome without modifying the pool. Shared by every swap related
+    /// method so that quotes can't diverge from executed swaps.
+    fn quote_swap(&self, swap_amount: StakedTokenAmount) -> Result<SwapQuote, SwapError> {
+        self.quote_swap_with(swap_amount, self.fee_surcharge(), None)
+    }
+
+    /// Calculates swap outcome with the fee surcharge computed upfront by `fee_surcharge`,
+    /// discounted by the tier of the account executing the swap
+    #[inline]
+    fn quote_swap_with(
+        &self,
+        swap_amount: StakedTokenAmount,
+        surcharge: Uint,
+        account: Option<AccountId>,
+    ) -> Result<SwapQuote, SwapError> {
+        if swap_amount.raw() == 0 {
+            return Err(SwapError::ZeroTokensAsArgument);
+        }
+        if let Some(max_age) = self.max_price_age {
+            let age = self.epoch.saturating_sub(self.price_updated_at);
+            if age > max_age {
+                return Err(SwapError::StalePrice { age, max_age });
+            }
+        }
+
+        let Some(amount_out_before_fees) = swap_amount.checked_into_token_amount(self.price) else {
+            return Err(SwapError::AmountTooBig);
+        };
+        if amount_out_before_fees > self.token_amount {
+            return Err(SwapError::PoolNotEnoughTokens {
+                token_amount: amount_out_before_fees,
+                pool_capacity: self.token_amount,
+            });
+        }
+        if self
+            .st_token_amount
+            .raw()
+            .checked_add(swap_amount.raw())
+            .is_none()
+        {
+            return Err(SwapError::AmountTooBig);
+        }
+
+        let fee = self.fee_with_surcharge(self.token_amount - amount_out_before_fees, surcharge);
+        let tier = account.and_then(|account| self.discount_tier(account));
+        let fee = tier.map_or(fee, |tier| tier.apply(fee));
+        let amount_out = amount_out_before_fees.apply_fee(fee);
+        // fee revenue bounds every other fee counter, treasury fees are owed so they can't
+        // saturate
+        let fee_amount = amount_out_before_fees - amount_out;
+        if self
+            .fee_revenue
+            .total
+            .raw()
+            .checked_add(fee_amount.raw())
+            .is_none()
+        {
+            return Err(SwapError::AmountTooBig);
+        }
+
+        Ok(SwapQuote {
+            amount_out_before_fees,
+            fee,
+            amount_out,
+            tier,
+        })
+    }
+
+    /// Starts recording every successful mutation as a `PoolOp`, prices accepted from the
+    /// oracle are recorded as `PoolOp::SetPrice`. Mutations outside of the operation set,
+    /// like configuration changes, governance updates, claims, insurance draws, forced,
+    /// rejected or approved prices and slashing signals, are recorded as
+    /// `PoolOp::Unreplayable`, so replaying such a log fails at the first of them.
+    /// The log grows with every recorded mutation, so operations of a pool recording it
+    /// allocate even with `bounded-events`.
+    pub fn enable_op_log(&mut self) {
+        if self.op_log.is_none() {
+            self.op_log = Some(Vec::new());
+            self.op_log_epoch = self.epoch;
+        }
+    }
+
+    /// Returns operations recorded since the log was enabled or last taken
+    pub fn op_log(&self) -> &[PoolOp] {
+        self.op_log.as_deref().unwrap_or_default()
+    }
+
+    /// Returns recorded operations, leaving the log empty but enabled
+    pub fn take_op_log(&mut self) -> Vec<PoolOp> {
+        self.op_log_epoch = self.epoch;
+        self.op_log.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Returns epoch the operation log starts at
+    pub fn op_log_epoch(&self) -> Epoch {
+        self.op_log_epoch
+    }
+
+    pub(crate) fn hooks_mut(&mut self) -> &mut Vec<Box<dyn PoolHook>> {
+        &mut self.hooks
+    }
+
+    pub(crate) fn subscribers(&self) -> &Subscribers {
+        &self.subscribers
+    }
+
+    pub(crate) fn subscribers_mut(&mut self) -> &mut Subscribers {
+        &mut self.subscribers
+    }
+
+    fn log_op(&mut self, op: PoolOp) {
+        if let Some(op_log) = &mut self.op_log {
+            op_log.push(op);
+        }
+    }
+
+    /// Bookkeeping shared by every successful mutating operation
+    fn on_operation(&mut self) {
+        if let Some(surcharge) = &mut self.surcharge {
+            surcharge.on_operation();
+        }
+    }
+
+    /// Returns swap volume recorded per epoch
+    pub fn volume_history(&self) -> &VolumeHistory {
+        &self.volume_history
+    }
+
+    /// Returns annualized yield of a single LP token estimated from the swap volume recorded
+    /// over the last `window` epochs and the current fee schedule. Returns `None` if the
+    /// window is empty or the pool doesn't hold any value.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - amount of most recent epochs (including current one) taken into account
+    pub fn estimate_lp_apy(&self, window: Epoch) -> Option<Percentage> {
+        let total_value = self.total_val();
+        if window == 0 || total_value == 0 {
+            return None;
+        }
+
+        let volume = self
+            .volume_history
+            .in_window(self.epoch, window)
+            .token_volume;
+        let fee = self.fee(self.token_amount).raw() as u128;
+        let lp_share = SCALE
+            .saturating_sub(self.treasury_cut.raw())
+            .saturating_sub(self.insurance.cut.raw()) as u128;
+        let scale = SCALE as u128;
+
+        let lp_fees = volume.raw() as u128 * fee / scale * lp_share / scale;
+        let apy = lp_fees * scale / total_value * EPOCHS_PER_YEAR as u128 / window as u128;
+
+        Some(Percentage::from_raw_amount(
+            apy.min(Uint::MAX as u128) as Uint
+        ))
+    }
+
+    /// Returns `n` evenly spaced points of the active fee curve, starting with an empty pool
+    /// and ending at the liquidity target. Each point pairs token amount left in the pool
+    /// with the fee that would be charged.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - amount of points to sample
+    pub fn fee_curve_points(&self, n: usize) -> Vec<(TokenAmount, Percentage)> {
+        let target = self.liquidity_target.raw() as u128;
+        let last = n.saturating_sub(1).max(1) as u128;
+
+        (0..n as u128)
+            .map(|i| {
+                let amount = TokenAmount::from_raw_amount((target * i / last) as Uint);
+                (amount, self.fee(amount))
+            })
+            .collect()
+    }
+
+    /// Returns raw total value stored inside the pool (tokens + staked tokens), widened so
+    /// it never overflows
+    fn total_val(&self) -> u128 {
+        self.token_amount.raw() as u128
+            + self.st_token_amount.raw() as u128 * self.price.raw() as u128 / SCALE as u128
+    }
+
+    /// Returns pool swap percentage fee adjusted by the selected fee policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount_after` - Token amount after operation
+    fn fee(&self, amount_after: TokenAmount) -> Percentage {
+        self.fee_with_surcharge(amount_after, self.fee_surcharge())
+    }
+
+    /// Returns raw fee added on top of the base fee by the fee policy, the swap surcharge
+    /// and liquidity bootstrapping, multiplied by `SCALE` and independent of the liquidity
+    /// left after a swap
+    fn fee_surcharge(&self) -> Uint {
+        // surcharges beyond 100% are capped anyway
+        let cap = SCALE as u128 * SCALE as u128;
+        let policy = self.fee_policy.scaled_surcharge(&self.price_history);
+        let policy = policy.min(cap) as Uint;
+        let bootstrap = self
+            .bootstrap
+            .map(|bootstrap| bootstrap.scaled_surcharge(self.epoch).min(cap) as Uint)
+            .unwrap_or(0);
+        policy
+            .saturating_add(bootstrap)
+            .saturating_add(self.active_surcharge().raw().saturating_mul(SCALE))
+    }
+
+    /// Returns fee charged when `amount_after` tokens are left in the pool, rounded up once
+    /// in favor of LPs.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount_after` - Token amount after operation
+    /// * `surcharge` - surcharge returned by `fee_surcharge`
+    fn fee_with_surcharge(&self, amount_after: TokenAmount, surcharge: Uint) -> Percentage {
+        // FEE FORMULA
+        // fee = max_fee - (max_fee - min_fee) * amount_after / target + surcharges
+        // with the liquidity part capped at min fee, pools initialized with inverted fees
+        // or without a target charge min fee
+        self.fee_curve.fee_with_surcharge(amount_after, surcharge)
+    }
+}
+
+/// Pool state is serialized together with `SCHEMA_VERSION`. The oracle can't be persisted,
+/// so restored pools use a `FixedOracle` returning the last accepted price until another
+/// one is set. Undrained events aren't part of the state either.
+impl ToJson for LpPool {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("version", SCHEMA_VERSION.to_json()),
+            ("price", self.price.to_json()),
+            ("price_updated_at", self.price_updated_at.to_json()),
+            ("max_price_age", self.max_price_age.to_json()),
+            ("max_price_deviation", self.max_price_deviation.to_json()),
+            ("rejected_price", self.rejected_price.to_json()),
+            ("monotonic_price", self.monotonic_price.to_json()),
+            ("slashing_signalled", self.slashing_signalled.to_json()),
+            ("price_overrides", self.price_overrides.to_json()),
+            ("token_amount", self.token_amount.to_json()),
+            ("st_token_amount", self.st_token_amount.to_json()),
+            ("lp_token_amount", self.lp_token_amount.to_json()),
+            ("liquidity_target", self.liquidity_target.to_json()),
+            ("min_fee", self.min_fee.to_json()),
+            ("max_fee", self.max_fee.to_json()),
+            ("fee_policy", self.fee_policy.to_json()),
+            ("price_smoothing", self.price_smoothing.to_json()),
+            ("price_history", self.price_history.to_json()),
+            ("twap", self.twap.to_json()),
+            ("surcharge", self.surcharge.to_json()),
+            ("bootstrap", self.bootstrap.to_json()),
+            ("epoch", self.epoch.to_json()),
+            ("treasury_cut", self.treasury_cut.to_json()),
+            ("fee_revenue", self.fee_revenue.to_json()),
+            ("insurance", self.insurance.to_json()),
+            ("fee_discounts", self.fee_discounts.to_json()),
+            ("counters", self.counters.to_json()),
+            ("volume_history", self.volume_history.to_json()),
+            ("positions", self.positions.to_json()),
+            ("governance", self.governance.to_json()),
+            ("max_fee_change", self.max_fee_change.to_json()),
+        ])
+    }
+}
+
+impl FromJson for LpPool {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        let version: u64 = field(value, "version")?;
+        if version > SCHEMA_VERSION {
+            return Err(SchemaError::UnsupportedVersion {
+                version,
+                supported: SCHEMA_VERSION,
+            });
+        }
+        let price: Price = field(value, "price")?;
+        if price.raw() == 0 {
+            return Err(SchemaError::InvalidValue {
+                path: "price".into(),
+                reason: "price has to be greater than zero".into(),
+            });
+        }
+
+        let mut pool = LpPool::init(
+            price,
+            field(value, "min_fee")?,
+            field(value, "max_fee")?,
+            field(value, "liquidity_target")?,
+        )
+        .unwrap_or_else(|never| match never {});
+        pool.price_updated_at = field(value, "price_updated_at")?;
+        pool.max_price_age = field_or_default(value, "max_price_age")?;
+        pool.max_price_deviation = field_or_default(value, "max_price_deviation")?;
+        pool.rejected_price = field_or_default(value, "rejected_price")?;
+        pool.monotonic_price = field_or_default(value, "monotonic_price")?;
+        pool.slashing_signalled = field_or_default(value, "slashing_signalled")?;
+        pool.price_overrides = field_or_default(value, "price_overrides")?;
+        pool.token_amount = field(value, "token_amount")?;
+        pool.st_token_amount = field(value, "st_token_amount")?;
+        pool.lp_token_amount = field(value, "lp_token_amount")?;
+        pool.fee_policy = field_or_default(value, "fee_policy")?;
+        pool.price_smoothing = field_or_default(value, "price_smoothing")?;
+        pool.price_history = field_or_default(value, "price_history")?;
+        pool.twap = field(value, "twap")?;
+        pool.surcharge = field_or_default(value, "surcharge")?;
+        pool.bootstrap = field_or_default(value, "bootstrap")?;
+        pool.epoch = field(value, "epoch")?;
+        if pool.price_updated_at > pool.epoch {
+            return Err(SchemaError::InvalidValue {
+                path: "price_updated_at".into(),
+                reason: format!("price can't be updated after epoch {}", pool.epoch),
+            });
+        }
+        pool.treasury_cut = field_or_default(value, "treasury_cut")?;
+        pool.fee_revenue = field_or_default(value, "fee_revenue")?;
+        pool.insurance = field_or_default(value, "insurance")?;
+        pool.fee_discounts = field_or_default(value, "fee_discounts")?;
+        pool.counters = field_or_default(value, "counters")?;
+        pool.volume_history = field_or_default(value, "volume_history")?;
+        pool.positions = field_or_default(value, "positions")?;
+        pool.governance = field_or_default(value, "governance")?;
+        pool.max_fee_change = field_or_default(value, "max_fee_change")?;
+        Ok(pool)
+    }
+}
+
+impl LpPool {
+    /// Returns state kept in the on-chain pool account
+    pub fn to_account(&self) -> PoolAccount {
+        PoolAccount {
+            price: self.price.raw(),
+            price_updated_at: self.price_updated_at,
+            token_amount: self.token_amount.raw(),
+            st_token_amount: self.st_token_amount.raw(),
+            lp_token_amount: self.lp_token_amount.raw(),
+            liquidity_target: self.liquidity_target.raw(),
+            min_fee: self.min_fee.raw(),
+            max_fee: self.max_fee.raw(),
+            treasury_cut: self.treasury_cut.raw(),
+            epoch: self.epoch,
+            max_price_age: self.max_price_age,
+            max_price_deviation: self.max_price_deviation.map(|deviation| deviation.raw()),
+            max_fee_change: self.max_fee_change.map(|change| change.raw()),
+            monotonic_price: self.monotonic_price,
+            total_fees: self.fee_revenue.total.raw(),
+            lp_fees: self.fee_revenue.lp.raw(),
+            treasury_fees: self.fee_revenue.treasury.raw(),
+            claimable_treasury_fees: self.fee_revenue.claimable_treasury.raw(),
+        }
+    }
+
+    /// Creates pool mirroring the on-chain account. State not kept on-chain (histories,
+    /// positions, governance queue, counters, insurance fund) starts empty.
+    pub fn from_account(account: &PoolAccount) -> Self {
+        let price = Price::from_raw_amount(account.price);
+        let mut pool = LpPool::init(
+            price,
+            Percentage::from_raw_amount(account.min_fee),
+            Percentage::from_raw_amount(account.max_fee),
+            TokenAmount::from_raw_amount(account.liquidity_target),
+        )
+        .unwrap_or_else(|never| match never {});
+        pool.price_updated_at = account.price_updated_at;
+        pool.token_amount = TokenAmount::from_raw_amount(account.token_amount);
+        pool.st_token_amount = StakedTokenAmount::from_raw_amount(account.st_token_amount);
+        pool.lp_token_amount = LpTokenAmount::from_raw_amount(account.lp_token_amount);
+        pool.treasury_cut = Percentage::from_raw_amount(account.treasury_cut);
+        pool.epoch = account.epoch;
+        pool.price_history = PriceHistory::default();
+        pool.price_history.push(account.price_updated_at, price);
+        pool.twap = TwapAccumulator::new(account.epoch, price, DEFAULT_TWAP_CAPACITY);
+        pool.max_price_age = account.max_price_age;
+        pool.max_price_deviation = account.max_price_deviation.map(Percentage::from_raw_amount);
+        pool.max_fee_change = account.max_fee_change.map(Percentage::from_raw_amount);
+        pool.monotonic_price = account.monotonic_price;
+        pool.fee_revenue = FeeRevenue {
+            total: TokenAmount::from_raw_amount(account.total_fees),
+            lp: TokenAmount::from_raw_amount(account.lp_fees),
+            treasury: TokenAmount::from_raw_amount(account.treasury_fees),
+            claimable_treasury: TokenAmount::from_raw_amount(account.claimable_treasury_fees),
+            insurance: TokenAmount::from_raw_amount(0),
+        };
+        pool
+    }
+
+    /// Returns copy of the pool state answering every query like the pool itself. The
+    /// copy uses a `FixedOracle` returning the last accepted price and has no events,
+    /// event sink, operation log, hooks or subscribers.
+    pub fn detached(&self) -> Self {
+        Self {
+            price: self.price,
+            price_updated_at: self.price_updated_at,
+            max_price_age: self.max_price_age,
+            max_price_deviation: self.max_price_deviation,
+            rejected_price: self.rejected_price,
+            monotonic_price: self.monotonic_price,
+            slashing_signalled: self.slashing_signalled,
+            price_overrides: self.price_overrides.clone(),
+            oracle: Box::new(FixedOracle::new(self.price)),
+            token_amount: self.token_amount,
+            st_token_amount: self.st_token_amount,
+            lp_token_amount: self.lp_token_amount,
+            liquidity_target: self.liquidity_target,
+            min_fee: self.min_fee,
+            max_fee: self.max_fee,
+            fee_curve: self.fee_curve,
+            fee_policy: self.fee_policy,
+            price_smoothing: self.price_smoothing,
+            price_history: self.price_history.clone(),
+            twap: self.twap.clone(),
+            surcharge: self.surcharge,
+            bootstrap: self.bootstrap,
+            epoch: self.epoch,
+            treasury_cut: self.treasury_cut,
+            fee_revenue: self.fee_revenue,
+            insurance: self.insurance,
+            fee_discounts: self.fee_discounts.clone(),
+            counters: self.counters,
+            events: EventQueue::new(),
+            event_sink: None,
+            volume_history: self.volume_history.clone(),
+            positions: self.positions.clone(),
+            governance: self.governance.clone(),
+            max_fee_change: self.max_fee_change,
+            op_log: None,
+            op_log_epoch: self.epoch,
+            hooks: Vec::new(),
+            subscribers: Subscribers::default(),
+        }
+    }
+
+    /// Returns detached copy of the pool recording its operations. Changes made to the copy
+    /// are moved into the pool by `commit_staged`, so changes spanning several pools can
+    /// be applied only once all of them succeeded.
+    pub(crate) fn staged(&self) -> Self {
+        let mut staged = self.detached();
+        staged.enable_op_log();
+        staged
+    }
+
+    /// Replaces pool state by the state of a copy returned by `staged`, emitting events and
+    /// recording operations of the copy as if they happened on the pool. Oracle, event
+    /// sink, operation log, hooks and subscribers of the pool are kept.
+    pub(crate) fn commit_staged(&mut self, mut staged: LpPool) {
+        let events = staged.drain_events();
+        let ops = staged.take_op_log();
+        let pool = std::mem::replace(self, staged);
+        self.oracle = pool.oracle;
+        self.events = pool.events;
+        self.event_sink = pool.event_sink;
+        self.op_log = pool.op_log;
+        self.op_log_epoch = pool.op_log_epoch;
+        self.hooks = pool.hooks;
+        self.subscribers = pool.subscribers;
+        for event in events {
+            self.emit(event);
+        }
+        for op in ops {
+            self.log_op(op);
+        }
+    }
+
+    /// Returns detached copy of the pool with balances and liquidity target divided by
+    /// `factor`, keeping price, fees and ratios between balances. Swaps `factor` times
+    /// smaller are charged the same fee and granted `factor` times less tokens, up to
+    /// rounding. Accounting that doesn't divide with the balances (positions, fee revenue,
+    /// counters, insurance fund balance and recorded volume) starts empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `factor` - divisor of every balance
+    pub fn scaled_clone(&self, factor: NonZeroU64) -> Self {
+        let factor = factor.get();
+        let mut pool = self.detached();
+        pool.token_amount = TokenAmount::from_raw_amount(self.token_amount.raw() / factor);
+        pool.st_token_amount =
+            StakedTokenAmount::from_raw_amount(self.st_token_amount.raw() / factor);
+        pool.lp_token_amount = LpTokenAmount::from_raw_amount(self.lp_token_amount.raw() / factor);
+        pool.liquidity_target = TokenAmount::from_raw_amount(self.liquidity_target.raw() / factor);
+        pool.refresh_fee_curve();
+        pool.fee_revenue = FeeRevenue::default();
+        pool.counters = PoolCounters::default();
+        pool.insurance = InsuranceFund {
+            cut: self.insurance.cut,
+            ..Default::default()
+        };
+        pool.positions = Positions::default();
+        pool.volume_history = VolumeHistory::default();
+        pool.fee_discounts = FeeDiscounts::default();
+        pool.fee_discounts
+            .set_tiers(self.fee_discounts.tiers().to_vec());
+        pool
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use rstest::{fixture, rstest};
+
+    use super::*;
+    use crate::discounts::DiscountCriterion;
+    use crate::surcharge::SurchargeDecay;
+
+    #[fixture]
+    fn story_example_pool() -> LpPool {
+        LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap()
+    }
+
+    #[fixture]
+    fn empty_pool() -> LpPool {
+        LpPool::init(2.into(), 0.0.into(), 0.09.into(), 100.into()).unwrap()
+    }
+
+    #[fixture]
+    fn non_empty_pool() -> LpPool {
+        let mut pool = LpPool::init(5.into(), 0.1.into(), 0.2.into(), 100.into()).unwrap();
+        pool.token_amount = (2 as Uint).pow(20).into();
+        pool.st_token_amount = 30.into();
+        pool.lp_token_amount = 250.into();
+        pool
+    }
+
+    #[rstest]
+    fn can_calculate_fees(empty_pool: LpPool, non_empty_pool: LpPool) {
+        assert_eq!(empty_pool.fee(0.into()).raw(), Percentage::from(0.09).raw());
+        assert_eq!(
+            empty_pool.fee(100.into()).raw(),
+            Percentage::from(0.0).raw()
+        );
+        assert_eq!(
+            empty_pool.fee(50.into()).raw(),
+            Percentage::from(0.045).raw()
+        );
+
+        assert_eq!(
+            non_empty_pool.fee(0.into()).raw(),
+            Percentage::from(0.2).raw()
+        );
+        assert_eq!(
+            non_empty_pool.fee(100.into()).raw(),
+            Percentage::from(0.1).raw()
+        );
+        assert_eq!(
+            non_empty_pool.fee(50.into()).raw(),
+            Percentage::from(0.15).raw()
+        );
+    }
+
+    #[rstest]
+    fn can_add_liquidity(mut empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        let added = empty_pool.add_liquidity(TokenAmount::from(20))?;
+        assert_eq!(
+            added,
+            LpTokenAmount::from(20),
+            "initial liquidity added should match token amount added"
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn errors_on_empty_add_liquidity(mut story_example_pool: LpPool) {
+        assert!(
+            story_example_pool
+                .add_liquidity(TokenAmount::from_raw_amount(0))
+                .is_err(),
+            "adding zero liquidity should fail"
+        )
+    }
+
+    #[rstest]
+    fn can_remove_liquidity(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        let res = non_empty_pool.remove_liquidity(LpTokenAmount::from(10))?;
+        assert_ne!(res.0, TokenAmount::from(0), "removing liquidity from the pool consisting of both assets should not yield zero value");
+        assert_ne!(res.1, StakedTokenAmount::from(0), "removing liquidity from the pool consisting of both assets should not yield zero value");
+        Ok(())
+    }
+
+    #[rstest]
+    fn errors_on_remove_liquidity_bigger_than_pool(mut empty_pool: LpPool) {
+        let res = empty_pool.remove_liquidity(LpTokenAmount::from(1000));
+        assert!(res.is_err());
+    }
+
+    #[rstest]
+    fn can_remove_zero_liquidity_from_empty_pool(
+        mut empty_pool: LpPool,
+    ) -> Result<(), Box<dyn Error>> {
+        let res = empty_pool.remove_liquidity(LpTokenAmount::from(0))?;
+        assert_eq!(res, (TokenAmount::from(0), StakedTokenAmount::from(0)));
+        Ok(())
+    }
+
+    #[rstest]
+    fn can_execute_swap(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        let swap_result = non_empty_pool.swap(StakedTokenAmount::from(3))?;
+        assert_ne!(
+            swap_result,
+            TokenAmount::from(0),
+            "successful swap should result in non-zero token amount granted to the caller"
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    fn discounts_swap_fee_of_qualifying_accounts(
+        mut story_example_pool: LpPool,
+    ) -> Result<(), Box<dyn Error>> {
+        let tier = DiscountTier::new(DiscountCriterion::LiquidityShare(0.01.into()), 0.2.into());
+        story_example_pool.set_fee_discounts(vec![tier]);
+        story_example_pool.enable_op_log();
+        story_example_pool.add_liquidity(99.into())?;
+        story_example_pool.add_liquidity_for(1, 1.into())?;
+
+        let quote = story_example_pool.quote_swap_for(1, 6.into())?;
+        assert_eq!(story_example_pool.quote_swap_for(2, 6.into())?.tier, None);
+        let outcome = story_example_pool.swap_for(1, 6.into())?;
+        assert_eq!(outcome, quote);
+        assert_eq!(
+            outcome,
+            SwapOutcome {
+                amount_out: 8.9928.into(),
+                fee: 0.0008.into(),
+                fee_amount: 0.0072.into(),
+                tier: Some(tier),
+            }
+        );
+        assert_eq!(story_example_pool.fee_discounts().volume(1), 9.into());
+        assert_eq!(
+            story_example_pool.op_log().last(),
+            Some(&PoolOp::Swap {
+                account: Some(1),
+                amount: 6.into(),
+            })
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    fn fills_swaps_partially(mut story_example_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        story_example_pool.add_liquidity(100.into())?;
+        let filled = StakedTokenAmount::from_raw_amount(66_666_667);
+        let expected = story_example_pool.detached().amount_for_swap(filled)?;
+
+        let (amount_out, unfilled) = story_example_pool.swap_partial(100.into())?;
+        assert_eq!(amount_out, expected);
+        assert_eq!(unfilled, StakedTokenAmount::from_raw_amount(33_333_333));
+        assert_eq!(story_example_pool.st_token_amount(), filled);
+        // LP portion of the fee is all that's left to fill following swaps with
+        assert_eq!(story_example_pool.token_amount(), 9.into());
+        assert_eq!(story_example_pool.swap_partial(7.into())?.1, 1.into());
+
+        let mut empty = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into())?;
+        assert_eq!(
+            empty.swap_partial(5.into())?,
+            (TokenAmount::from_raw_amount(0), 5.into())
+        );
+        assert!(matches!(
+            empty.swap_partial(0.into()),
+            Err(SwapError::ZeroTokensAsArgument)
+        ));
+        Ok(())
+    }
+
+    #[rstest]
+    fn bootstrapping_surcharge_decays(
+        mut story_example_pool: LpPool,
+    ) -> Result<(), Box<dyn Error>> {
+        story_example_pool.add_liquidity(100.into())?;
+        story_example_pool.advance_epoch(3);
+        story_example_pool.set_bootstrap(Some(BootstrapConfig {
+            surcharge: 0.05.into(),
+            epochs: 10,
+        }));
+        assert_eq!(story_example_pool.fee_for_swap(6.into())?, 0.051.into());
+
+        story_example_pool.advance_epoch(4);
+        assert_eq!(story_example_pool.bootstrap_surcharge(), 0.03.into());
+        assert_eq!(story_example_pool.fee_for_swap(6.into())?, 0.031.into());
+
+        story_example_pool.advance_epoch(6);
+        assert_eq!(story_example_pool.fee_for_swap(6.into())?, 0.001.into());
+        Ok(())
+    }
+
+    #[rstest]
+    fn scaled_clone_quotes_proportionally(
+        mut story_example_pool: LpPool,
+    ) -> Result<(), Box<dyn Error>> {
+        story_example_pool.add_liquidity_for(1, 90_000.into())?;
+        story_example_pool.swap(30_000.into())?;
+        let factor = NonZeroU64::new(1_000).unwrap();
+        let scaled = story_example_pool.scaled_clone(factor);
+
+        assert_eq!(scaled.price(), story_example_pool.price());
+        // the fee stays in the pool, so tokens aren't a round number
+        assert_eq!(scaled.token_amount(), 45.045.into());
+        assert_eq!(scaled.st_token_amount(), 30.into());
+        assert_eq!(scaled.lp_token_amount(), 90.into());
+        assert_eq!(scaled.current_fee(), story_example_pool.current_fee());
+        assert!(scaled.positions().iter().next().is_none());
+        for amount in [1, 1_000, 10_000, 29_000] {
+            let amount = StakedTokenAmount::from(amount);
+            let scaled_amount = StakedTokenAmount::from_raw_amount(amount.raw() / factor.get());
+            let full = story_example_pool.amount_for_swap(amount)?;
+            let small = scaled.amount_for_swap(scaled_amount)?;
+            assert_eq!(
+                scaled.fee_for_swap(scaled_amount)?,
+                story_example_pool.fee_for_swap(amount)?
+            );
+            assert!(full.raw().abs_diff(small.raw() * factor.get()) <= factor.get());
+        }
+        Ok(())
+    }
+
+    #[rstest]
+    fn swap_errors_on_not_enough_tokens(mut empty_pool: LpPool) {
+        let swap_result = empty_pool.swap(StakedTokenAmount::from(3));
+        assert!(
+            swap_result.is_err(),
+            "swap on empty pool should always yield err"
+        );
+    }
+
+    #[rstest]
+    fn swap_errors_on_zero_token_argument(mut empty_pool: LpPool) {
+        let swap_result = empty_pool.swap(StakedTokenAmount::from(0));
+        assert!(
+            swap_result.is_err(),
+            "swap on empty pool should always yield err"
+        );
+    }
+
+    #[rstest]
+    fn story_example(mut story_example_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        assert_eq!(
+            story_example_pool.add_liquidity(TokenAmount::from(100))?,
+            LpTokenAmount::from(100),
+            "initial add liquidity"
+        );
         assert_eq!(
             story_example_pool.swap(StakedTokenAmount::from(6))?,
             TokenAmount::from(8.991),
@@ -329,4 +1927,724 @@ mod tests {
         );
         Ok(())
     }
+
+    #[rstest]
+    fn set_price_rejects_zero(mut empty_pool: LpPool) {
+        assert!(empty_pool.set_price(Price::from_raw_amount(0)).is_err());
+        assert_eq!(empty_pool.price_history().len(), 1);
+    }
+
+    #[rstest]
+    fn batch_quotes_match_single_quotes(
+        mut story_example_pool: LpPool,
+    ) -> Result<(), Box<dyn Error>> {
+        story_example_pool.add_liquidity(100.into())?;
+        story_example_pool.set_fee_policy(FeePolicy::VolatilitySensitive {
+            sensitivity: 1.0.into(),
+            max_surcharge: 0.05.into(),
+        });
+        for price in [1.6, 1.4, 1.55] {
+            story_example_pool.set_price(price.into())?;
+        }
+        let amounts: Vec<StakedTokenAmount> = [0.0, 0.5, 6.0, 30.0, 80.0, 1e12]
+            .into_iter()
+            .map(StakedTokenAmount::from)
+            .collect();
+        let quote = |pool: &LpPool| -> Vec<_> {
+            let single = amounts.iter().map(|amount| pool.amount_for_swap(*amount));
+            pool.quote_swaps(&amounts)
+                .into_iter()
+                .zip(single)
+                .map(|(batch, single)| (format!("{batch:?}"), format!("{single:?}")))
+                .collect()
+        };
+        for (batch, single) in quote(&story_example_pool) {
+            assert_eq!(batch, single);
+        }
+        assert!(story_example_pool.quote_swaps(&amounts)[2].is_ok());
+
+        story_example_pool.set_max_price_age(Some(1));
+        story_example_pool.advance_epoch(2);
+        for (batch, single) in quote(&story_example_pool) {
+            assert_eq!(batch, single);
+        }
+        assert!(quote(&story_example_pool)[2].0.contains("StalePrice"));
+        Ok(())
+    }
+
+    #[rstest]
+    fn volatile_prices_widen_swap_fee(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        let calm_fee = non_empty_pool.fee(50.into());
+
+        non_empty_pool.set_fee_policy(FeePolicy::VolatilitySensitive {
+            sensitivity: 1.0.into(),
+            max_surcharge: 0.5.into(),
+        });
+        assert_eq!(
+            non_empty_pool.fee(50.into()),
+            calm_fee,
+            "no price updates yet"
+        );
+
+        non_empty_pool.set_price(5.5.into())?;
+        non_empty_pool.set_price(5.into())?;
+        assert!(
+            non_empty_pool.fee(50.into()) > calm_fee,
+            "price swings should widen the fee"
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    fn large_swap_raises_fee_for_following_swaps(
+        mut non_empty_pool: LpPool,
+    ) -> Result<(), Box<dyn Error>> {
+        non_empty_pool.set_swap_surcharge(Some(SurchargeConfig {
+            large_swap_threshold: 0.0001.into(),
+            surcharge: 0.05.into(),
+            decay_rate: 1.0.into(),
+            decay: SurchargeDecay::PerEpoch,
+        }));
+        let fee_before = non_empty_pool.fee(50.into());
+
+        non_empty_pool.swap(StakedTokenAmount::from(30))?;
+        assert_eq!(non_empty_pool.active_surcharge(), Percentage::from(0.05));
+        assert_eq!(
+            non_empty_pool.fee(50.into()).raw(),
+            fee_before.raw() + Percentage::from(0.05).raw()
+        );
+
+        non_empty_pool.advance_epoch(1);
+        assert_eq!(
+            non_empty_pool.fee(50.into()),
+            fee_before,
+            "surcharge decayed"
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    fn insurance_fund_covers_shortfalls(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        non_empty_pool.set_treasury_cut(0.2.into());
+        non_empty_pool.set_insurance_cut(0.3.into());
+        let tokens_before = non_empty_pool.token_amount;
+
+        let amount_out = non_empty_pool.swap(StakedTokenAmount::from(30))?;
+        let revenue = *non_empty_pool.fee_revenue();
+        let fund = *non_empty_pool.insurance_fund();
+        assert_eq!(fund.balance, revenue.insurance);
+        assert_eq!(
+            revenue.lp + revenue.treasury + revenue.insurance,
+            revenue.total
+        );
+        assert_eq!(
+            non_empty_pool.token_amount,
+            tokens_before - amount_out - revenue.treasury - revenue.insurance,
+            "insurance fees should not stay in pool liquidity"
+        );
+        let stats = non_empty_pool.insurance_stats();
+        assert_eq!(stats.exposure, TokenAmount::from(300));
+        assert_eq!(stats.coverage_ratio.raw(), fund.balance.raw() / 300);
+
+        let value_before = non_empty_pool.total_value();
+        assert_eq!(
+            non_empty_pool.cover_shortfall(fund.balance + 1.into()),
+            Err(InsuranceError::InsufficientFunds {
+                requested: fund.balance + 1.into(),
+                balance: fund.balance
+            })
+        );
+        non_empty_pool.cover_shortfall(fund.balance)?;
+        assert_eq!(non_empty_pool.total_value(), value_before + fund.balance);
+        assert_eq!(non_empty_pool.insurance_fund().drawn, fund.balance);
+        assert_eq!(non_empty_pool.insurance_stats().balance, 0.into());
+
+        non_empty_pool.insurance.deposit(1.into());
+        non_empty_pool.token_amount = TokenAmount::from_raw_amount(Uint::MAX);
+        assert_eq!(
+            non_empty_pool.cover_shortfall(1.into()),
+            Err(InsuranceError::LiquidityOverflow { amount: 1.into() })
+        );
+        assert_eq!(non_empty_pool.insurance_fund().balance, 1.into());
+        Ok(())
+    }
+
+    #[rstest]
+    fn splits_swap_fees_with_treasury(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        non_empty_pool.set_treasury_cut(0.5.into());
+        let tokens_before = non_empty_pool.token_amount;
+
+        let amount_out = non_empty_pool.swap(StakedTokenAmount::from(3))?;
+        let fee_amount = TokenAmount::from(15) - amount_out;
+        let revenue = *non_empty_pool.fee_revenue();
+        assert_eq!(revenue.total, fee_amount);
+        assert_eq!(revenue.lp + revenue.treasury, fee_amount);
+        assert_eq!(
+            non_empty_pool.token_amount,
+            tokens_before - amount_out - revenue.treasury,
+            "treasury fees should not stay in pool liquidity"
+        );
+
+        let claimed = non_empty_pool.claim()?;
+        assert_eq!(claimed, revenue.treasury);
+        assert_eq!(
+            non_empty_pool.claimable_treasury_fees(),
+            TokenAmount::from(0)
+        );
+        assert!(non_empty_pool.claim().is_err(), "nothing left to claim");
+        assert!(non_empty_pool
+            .drain_events()
+            .contains(&PoolEvent::TreasuryFeesClaimed { amount: claimed }));
+        Ok(())
+    }
+
+    #[rstest]
+    fn can_sample_fee_curve(empty_pool: LpPool) {
+        let points = empty_pool.fee_curve_points(3);
+        assert_eq!(
+            points,
+            vec![
+                (TokenAmount::from(0), Percentage::from(0.09)),
+                (TokenAmount::from(50), Percentage::from(0.045)),
+                (TokenAmount::from(100), Percentage::from(0.0)),
+            ]
+        );
+        assert!(empty_pool.fee_curve_points(0).is_empty());
+        assert_eq!(empty_pool.fee_curve_points(1).len(), 1);
+    }
+
+    #[rstest]
+    fn fee_for_swap_matches_executed_swap(
+        mut non_empty_pool: LpPool,
+    ) -> Result<(), Box<dyn Error>> {
+        let swap_amount = StakedTokenAmount::from(3);
+        let fee = non_empty_pool.fee_for_swap(swap_amount)?;
+        let value = swap_amount.into_token_amount(non_empty_pool.price);
+
+        let amount_out = non_empty_pool.swap(swap_amount)?;
+        assert_eq!(amount_out, value.apply_fee(fee));
+        assert!(non_empty_pool
+            .fee_for_swap(StakedTokenAmount::from(0))
+            .is_err());
+        Ok(())
+    }
+
+    #[rstest]
+    fn can_estimate_lp_apy(mut empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        empty_pool.add_liquidity(200.into())?;
+        assert_eq!(empty_pool.estimate_lp_apy(1), Some(Percentage::from(0.0)));
+        assert_eq!(empty_pool.estimate_lp_apy(0), None);
+
+        // 20 tokens of volume, pool is above target so the fee is min_fee (0%)
+        empty_pool.swap(StakedTokenAmount::from(10))?;
+        assert_eq!(empty_pool.estimate_lp_apy(1), Some(Percentage::from(0.0)));
+
+        empty_pool.update_fees(0.01.into(), empty_pool.max_fee)?;
+        let apy = empty_pool.estimate_lp_apy(1).expect("pool holds value");
+        // 20 volume * 1% fee / 200 value * 182 epochs
+        assert_eq!(apy, Percentage::from(0.182));
+        Ok(())
+    }
+
+    #[rstest]
+    fn tracks_position_pnl(mut story_example_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        const ALICE: AccountId = 1;
+        story_example_pool.add_liquidity_for(ALICE, 100.into())?;
+        story_example_pool.swap(StakedTokenAmount::from(6))?;
+
+        let pnl = story_example_pool
+            .position_pnl(ALICE)
+            .expect("position exists");
+        assert_eq!(pnl.hold_value, TokenAmount::from(100));
+        assert_eq!(pnl.fees_earned, TokenAmount::from(0.009));
+        assert_eq!(
+            pnl.unrealized_pnl,
+            SignedTokenAmount::difference(pnl.current_value, 100.into())
+        );
+
+        assert!(story_example_pool
+            .remove_liquidity_for(ALICE, 101.into())
+            .is_err());
+        story_example_pool.remove_liquidity_for(ALICE, 100.into())?;
+        let pnl = story_example_pool
+            .position_pnl(ALICE)
+            .expect("position exists");
+        assert_eq!(pnl.current_value, TokenAmount::from(0));
+        assert_eq!(
+            pnl.realized_pnl,
+            SignedTokenAmount::from(TokenAmount::from(0.009))
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    fn governance_updates_are_timelocked(mut empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        let params = PoolParams {
+            min_fee: 0.01.into(),
+            ..empty_pool.params()
+        };
+        empty_pool.set_governance_delay(2);
+        empty_pool.propose_update(params)?;
+
+        empty_pool.advance_epoch(1);
+        assert!(
+            empty_pool.apply_pending().is_err(),
+            "timelock didn't pass yet"
+        );
+        assert_eq!(empty_pool.min_fee, Percentage::from(0.0));
+
+        empty_pool.advance_epoch(1);
+        assert_eq!(empty_pool.apply_pending()?, params);
+        assert_eq!(empty_pool.params(), params);
+        assert!(
+            empty_pool.cancel_pending().is_err(),
+            "nothing left to cancel"
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    fn fee_updates_are_bounded(mut empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        empty_pool.set_max_fee_change(Some(0.01.into()));
+
+        assert!(
+            empty_pool.update_fees(0.0.into(), 0.2.into()).is_err(),
+            "max fee moved by 11%"
+        );
+        empty_pool.update_fees(0.01.into(), 0.08.into())?;
+        assert_eq!(empty_pool.min_fee, Percentage::from(0.01));
+        assert_eq!(empty_pool.max_fee, Percentage::from(0.08));
+
+        empty_pool.set_max_fee_change(None);
+        empty_pool.update_fees(0.0.into(), 0.5.into())?;
+        assert!(empty_pool.update_fees(0.6.into(), 0.5.into()).is_err());
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    struct SharedOracle(std::sync::Arc<std::sync::Mutex<Price>>);
+
+    impl PriceOracle for SharedOracle {
+        fn price(&self) -> Result<Price, OracleError> {
+            Ok(*self.0.lock().unwrap())
+        }
+    }
+
+    #[rstest]
+    fn swaps_use_oracle_price(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        let price = std::sync::Arc::new(std::sync::Mutex::new(Price::from(5)));
+        non_empty_pool.set_oracle(SharedOracle(price.clone()));
+
+        *price.lock().unwrap() = 6.into();
+        non_empty_pool.swap(StakedTokenAmount::from(1))?;
+        assert_eq!(non_empty_pool.price(), Price::from(6));
+        assert_eq!(
+            non_empty_pool.price_history().latest(),
+            Some(Price::from(6))
+        );
+
+        *price.lock().unwrap() = Price::from_raw_amount(0);
+        assert!(matches!(
+            non_empty_pool.swap(StakedTokenAmount::from(1)),
+            Err(SwapError::Oracle(OracleError::ZeroPrice))
+        ));
+
+        non_empty_pool.set_price(7.into())?;
+        assert_eq!(
+            non_empty_pool.refresh_price()?,
+            Price::from(7),
+            "set_price pins the price"
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    fn swaps_fail_on_stale_price(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        non_empty_pool.set_max_price_age(Some(2));
+        non_empty_pool.advance_epoch(2);
+        non_empty_pool.swap(StakedTokenAmount::from(1))?;
+
+        non_empty_pool.advance_epoch(1);
+        assert!(matches!(
+            non_empty_pool.swap(StakedTokenAmount::from(1)),
+            Err(SwapError::StalePrice { age: 3, max_age: 2 })
+        ));
+        assert!(non_empty_pool
+            .fee_for_swap(StakedTokenAmount::from(1))
+            .is_err());
+
+        non_empty_pool.set_price(5.into())?;
+        assert_eq!(non_empty_pool.price_updated_at(), 3);
+        non_empty_pool.swap(StakedTokenAmount::from(1))?;
+        Ok(())
+    }
+
+    #[rstest]
+    fn circuit_breaker_rejects_bad_prints(
+        mut non_empty_pool: LpPool,
+    ) -> Result<(), Box<dyn Error>> {
+        let oracle = crate::oracle::MockOracle::new([5.2.into()]);
+        non_empty_pool.set_oracle(oracle.clone());
+        non_empty_pool.set_max_price_deviation(Some(0.05.into()));
+        non_empty_pool.swap(StakedTokenAmount::from(1))?;
+
+        oracle.push_price(10.into());
+        assert!(matches!(
+            non_empty_pool.swap(StakedTokenAmount::from(1)),
+            Err(SwapError::Oracle(OracleError::DeviationTooLarge { .. }))
+        ));
+        assert_eq!(non_empty_pool.price(), Price::from(5.2));
+        assert_eq!(non_empty_pool.rejected_price(), Some(Price::from(10)));
+        assert!(non_empty_pool
+            .drain_events()
+            .iter()
+            .any(|event| matches!(event, PoolEvent::PriceRejected { .. })));
+
+        assert!(non_empty_pool.set_price(1.into()).is_err());
+        assert_eq!(non_empty_pool.approve_rejected_price()?, Price::from(10));
+        non_empty_pool.swap(StakedTokenAmount::from(1))?;
+        assert_eq!(non_empty_pool.price(), Price::from(10));
+        Ok(())
+    }
+
+    #[rstest]
+    fn can_query_twap(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        non_empty_pool.advance_epoch(3);
+        non_empty_pool.set_price(6.into())?;
+        non_empty_pool.advance_epoch(1);
+
+        // 3 epochs at 5 and 1 epoch at 6
+        assert_eq!(non_empty_pool.twap(4), Some(Price::from(5.25)));
+        assert_eq!(non_empty_pool.twap(0), Some(Price::from(6)));
+        assert_eq!(non_empty_pool.twap(5), None);
+        Ok(())
+    }
+
+    #[rstest]
+    fn ema_smooths_oracle_prices(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        non_empty_pool.set_oracle(FixedOracle::new(6.into()));
+        non_empty_pool.set_price_smoothing(PriceSmoothing::Ema { alpha: 0.5.into() });
+
+        assert_eq!(non_empty_pool.refresh_price()?, Price::from(5.5));
+        non_empty_pool.swap(StakedTokenAmount::from(1))?;
+        assert_eq!(non_empty_pool.price(), Price::from(5.75));
+        Ok(())
+    }
+
+    #[rstest]
+    fn can_query_price_window(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        non_empty_pool.advance_epoch(1);
+        non_empty_pool.set_price(5.5.into())?;
+        non_empty_pool.advance_epoch(1);
+        non_empty_pool.set_price(4.4.into())?;
+
+        assert_eq!(
+            non_empty_pool.min_max(2),
+            Some((Price::from(4.4), Price::from(5.5)))
+        );
+        assert_eq!(non_empty_pool.volatility(1), Percentage::from(0.2));
+        assert_eq!(
+            non_empty_pool
+                .price_history()
+                .iter()
+                .last()
+                .map(|point| point.epoch),
+            Some(2)
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    fn monotonic_price_requires_slashing_signal(
+        mut non_empty_pool: LpPool,
+    ) -> Result<(), Box<dyn Error>> {
+        non_empty_pool.set_monotonic_price(true);
+        non_empty_pool.set_price(5.5.into())?;
+        assert!(matches!(
+            non_empty_pool.set_price(5.into()),
+            Err(PriceUpdateError::PriceDecreased { .. })
+        ));
+
+        non_empty_pool.set_oracle(FixedOracle::new(5.into()));
+        assert!(matches!(
+            non_empty_pool.swap(StakedTokenAmount::from(1)),
+            Err(SwapError::Oracle(OracleError::PriceDecreased { .. }))
+        ));
+
+        non_empty_pool.signal_slashing();
+        non_empty_pool.swap(StakedTokenAmount::from(1))?;
+        assert_eq!(non_empty_pool.price(), Price::from(5));
+        // signal is consumed by the decrease
+        assert!(non_empty_pool.set_price(4.into()).is_err());
+        Ok(())
+    }
+
+    #[rstest]
+    fn price_override_is_audited(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        non_empty_pool.set_monotonic_price(true);
+        non_empty_pool.set_max_price_deviation(Some(0.01.into()));
+        assert!(matches!(
+            non_empty_pool.force_price(1.into(), " "),
+            Err(PriceUpdateError::MissingReason)
+        ));
+
+        non_empty_pool.force_price(1.into(), "oracle outage")?;
+        let record = PriceOverride {
+            epoch: 0,
+            previous_price: 5.into(),
+            price: 1.into(),
+            reason: "oracle outage".into(),
+        };
+        assert_eq!(non_empty_pool.price(), Price::from(1));
+        assert_eq!(
+            non_empty_pool.price_overrides(),
+            std::slice::from_ref(&record)
+        );
+        assert_eq!(
+            non_empty_pool.drain_events(),
+            vec![
+                PoolEvent::PriceUpdated {
+                    previous_price: 5.into(),
+                    price: 1.into(),
+                },
+                PoolEvent::PriceOverridden(record)
+            ]
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    fn mutations_emit_events(mut story_example_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        story_example_pool.add_liquidity_for(1, 100.into())?;
+        story_example_pool.swap(6.into())?;
+        story_example_pool.set_price(2.into())?;
+        story_example_pool.advance_epoch(2);
+        story_example_pool.remove_liquidity_for(1, 10.into())?;
+        let events = story_example_pool.drain_events();
+        assert_eq!(
+            events[..4],
+            [
+                PoolEvent::LiquidityAdded {
+                    account: Some(1),
+                    amount: 100.into(),
+                    lp_amount: 100.into(),
+                },
+                PoolEvent::FeesCollected {
+                    lp_portion: 0.009.into(),
+                    treasury_portion: 0.into(),
+                    insurance_portion: 0.into(),
+                },
+                PoolEvent::Swapped {
+                    account: None,
+                    amount: 6.into(),
+                    amount_out: 8.991.into(),
+                    fee_amount: 0.009.into(),
+                },
+                PoolEvent::PriceUpdated {
+                    previous_price: 1.5.into(),
+                    price: 2.into(),
+                },
+            ]
+        );
+        assert_eq!(
+            events[4],
+            PoolEvent::EpochAdvanced {
+                epochs: 2,
+                epoch: 2
+            }
+        );
+        assert!(matches!(
+            events[5],
+            PoolEvent::LiquidityRemoved {
+                account: Some(1),
+                ..
+            }
+        ));
+        assert_eq!(events.len(), 6);
+        Ok(())
+    }
+
+    #[rstest]
+    fn sink_receives_events(mut story_example_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        story_example_pool.add_liquidity(50.into())?;
+        let (sender, receiver) = std::sync::mpsc::channel();
+        story_example_pool.set_event_sink(Some(Box::new(sender)));
+        story_example_pool.add_liquidity(10.into())?;
+        story_example_pool.remove_liquidity(10.into())?;
+
+        let received: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(received.len(), 2);
+        assert!(matches!(
+            received[1],
+            PoolEvent::LiquidityRemoved { account: None, .. }
+        ));
+        // events emitted before the sink was registered stay queued
+        assert_eq!(story_example_pool.drain_events().len(), 1);
+
+        story_example_pool.set_event_sink(None);
+        story_example_pool.advance_epoch(1);
+        assert_eq!(story_example_pool.drain_events().len(), 1);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "bounded-events"))]
+    #[derive(Debug)]
+    struct DiscardEvents;
+
+    #[cfg(not(feature = "bounded-events"))]
+    impl EventSink for DiscardEvents {
+        fn on_event(&mut self, _event: &PoolEvent) {}
+    }
+
+    #[rstest]
+    fn core_operations_dont_allocate(mut story_example_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        // without the bounded queue undrained events grow it, so they're discarded instead
+        #[cfg(not(feature = "bounded-events"))]
+        story_example_pool.set_event_sink(Some(Box::new(DiscardEvents)));
+        let price = std::sync::Arc::new(std::sync::Mutex::new(story_example_pool.price()));
+        story_example_pool.set_oracle(SharedOracle(price.clone()));
+        story_example_pool.add_liquidity(100.into())?;
+        // positions of accounts are only allocated by their first deposit
+        story_example_pool.add_liquidity_for(7, 10.into())?;
+        // the first swap of an epoch may open its volume entry
+        story_example_pool.swap(1.into())?;
+
+        let (result, allocations) = crate::allocations::count_allocations(|| {
+            (0..100).try_for_each(|_| {
+                // the swap refreshes the price from the oracle
+                let next_price = Price::from_raw_amount(price.lock().unwrap().raw() + 1);
+                *price.lock().unwrap() = next_price;
+                story_example_pool.add_liquidity(10.into())?;
+                story_example_pool.swap(1.into())?;
+                story_example_pool.remove_liquidity(5.into())?;
+                story_example_pool.add_liquidity_for(7, 10.into())?;
+                story_example_pool.remove_liquidity_for(7, 5.into())?;
+                Ok::<_, Box<dyn Error>>(())
+            })
+        });
+        result?;
+        assert_eq!(allocations, 0);
+        assert_eq!(story_example_pool.price(), *price.lock().unwrap());
+        Ok(())
+    }
+
+    #[cfg(feature = "bounded-events")]
+    #[rstest]
+    fn drops_oldest_events_of_full_queue(
+        mut story_example_pool: LpPool,
+    ) -> Result<(), Box<dyn Error>> {
+        use crate::events::EVENT_QUEUE_CAPACITY;
+
+        story_example_pool.add_liquidity(100.into())?;
+        for _ in 0..EVENT_QUEUE_CAPACITY {
+            story_example_pool.advance_epoch(1);
+        }
+        assert_eq!(story_example_pool.dropped_events(), 1);
+        let events = story_example_pool.drain_events();
+        assert_eq!(events.len(), EVENT_QUEUE_CAPACITY);
+        assert_eq!(
+            events[0],
+            PoolEvent::EpochAdvanced {
+                epochs: 1,
+                epoch: 1
+            }
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    fn counts_successful_operations(mut story_example_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        story_example_pool.add_liquidity(100.into())?;
+        story_example_pool.swap(6.into())?;
+        assert!(story_example_pool.swap(100.into()).is_err());
+        let lp_amount = story_example_pool.add_liquidity_for(7, 10.into())?;
+        story_example_pool.remove_liquidity_for(7, lp_amount)?;
+
+        let counters = *story_example_pool.counters();
+        assert_eq!(
+            (counters.swaps, counters.deposits, counters.withdrawals),
+            (1, 2, 1)
+        );
+        assert_eq!(counters.swap_volume, 6.into());
+        assert_eq!(counters.swap_payout, 8.991.into());
+        assert_eq!(counters.fees, story_example_pool.fee_revenue().total);
+        assert_eq!(counters.deposited, 110.into());
+        assert_eq!(
+            LpPool::from_json(&story_example_pool.to_json())?.counters(),
+            &counters
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    fn state_round_trips_through_json(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        non_empty_pool.add_liquidity_for(7, 10.into())?;
+        non_empty_pool.set_treasury_cut(0.1.into());
+        non_empty_pool.swap(StakedTokenAmount::from(3))?;
+        non_empty_pool.advance_epoch(2);
+        non_empty_pool.force_price(5.5.into(), "test")?;
+        non_empty_pool.propose_update(PoolParams {
+            min_fee: 0.05.into(),
+            max_fee: 0.3.into(),
+            liquidity_target: 200.into(),
+        })?;
+
+        let document = non_empty_pool.to_json();
+        let restored = LpPool::from_json(&Value::parse(&document.to_string())?)?;
+        assert_eq!(restored.to_json(), document);
+        assert_eq!(restored.price(), Price::from(5.5));
+        assert_eq!(restored.position(7), non_empty_pool.position(7));
+        Ok(())
+    }
+
+    #[rstest]
+    fn rejects_price_updated_after_current_epoch(non_empty_pool: LpPool) {
+        let Value::Object(mut fields) = non_empty_pool.to_json() else {
+            unreachable!()
+        };
+        let field = fields
+            .iter_mut()
+            .find(|(key, _)| key == "price_updated_at")
+            .unwrap();
+        field.1 = (non_empty_pool.epoch() + 1).to_json();
+        assert!(matches!(
+            LpPool::from_json(&Value::Object(fields)),
+            Err(SchemaError::InvalidValue { path, .. }) if path == "price_updated_at"
+        ));
+
+        // accounts built in code aren't decoded, their prices just aren't stale
+        let mut account = non_empty_pool.to_account();
+        account.price_updated_at = account.epoch + 1;
+        let mut pool = LpPool::from_account(&account);
+        pool.set_max_price_age(Some(0));
+        assert!(pool.fee_for_swap(1.into()).is_ok());
+    }
+
+    #[rstest]
+    fn rejects_newer_schema(non_empty_pool: LpPool) {
+        let Value::Object(mut fields) = non_empty_pool.to_json() else {
+            unreachable!()
+        };
+        fields[0].1 = (SCHEMA_VERSION + 1).to_json();
+
+        assert!(matches!(
+            LpPool::from_json(&Value::Object(fields)),
+            Err(SchemaError::UnsupportedVersion { .. })
+        ));
+    }
+
+    #[rstest]
+    fn pool_round_trips_through_account(mut non_empty_pool: LpPool) -> Result<(), Box<dyn Error>> {
+        non_empty_pool.set_treasury_cut(0.2.into());
+        non_empty_pool.set_max_price_age(Some(3));
+        non_empty_pool.swap(StakedTokenAmount::from(2))?;
+        non_empty_pool.advance_epoch(1);
+
+        let account = non_empty_pool.to_account();
+        let restored = LpPool::from_account(&PoolAccount::from_borsh(&account.to_account_data())?);
+        assert_eq!(restored.to_account(), account);
+        assert_eq!(restored.fee_revenue(), non_empty_pool.fee_revenue());
+        assert_eq!(restored.total_value(), non_empty_pool.total_value());
+        Ok(())
+    }
 }
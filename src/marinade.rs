@@ -0,0 +1,182 @@
+//! Importer constructing `LpPool` mirroring the live Marinade liquidity pool.
+//! HTTP is left to the caller through the `RpcTransport` trait so any async client
+//! (reqwest, hyper, surf...) can be plugged in.
+
+use std::future::Future;
+
+use crate::encoding::{base58_encode, base64_decode};
+use crate::error::ImportError;
+use crate::json::Value;
+use crate::lp_pool::LpPool;
+use crate::marinade_account::MarinadeState;
+
+/// address of the Marinade state account on Solana mainnet
+pub const MARINADE_STATE_ADDRESS: &str = "8szGkuLTAux9XMgZ2vtY39jVSowEcpBfFfD8hXSEqdGC";
+
+/// HTTP transport used to send JSON-RPC requests to a Solana node
+pub trait RpcTransport {
+    /// Posts JSON request body and returns response body
+    fn post(&self, body: String) -> impl Future<Output = Result<String, ImportError>> + Send;
+}
+
+#[derive(Debug, Clone)]
+/// Client fetching Marinade liquidity pool state from Solana RPC
+pub struct MarinadeRpcClient<T> {
+    transport: T,
+    state_address: String,
+    sol_leg_address: String,
+}
+
+impl<T: RpcTransport> MarinadeRpcClient<T> {
+    /// Creates client reading mainnet state account.
+    ///
+    /// # Arguments
+    ///
+    /// * `transport` - HTTP transport connected to the RPC node
+    /// * `sol_leg_address` - address of the liquidity pool SOL leg account
+    pub fn new(transport: T, sol_leg_address: impl Into<String>) -> Self {
+        Self {
+            transport,
+            state_address: MARINADE_STATE_ADDRESS.into(),
+            sol_leg_address: sol_leg_address.into(),
+        }
+    }
+
+    /// Reads state from a different state account, e.g. on devnet
+    pub fn with_state_address(mut self, state_address: impl Into<String>) -> Self {
+        self.state_address = state_address.into();
+        self
+    }
+
+    /// Fetches and decodes the Marinade state account
+    pub async fn fetch_state(&self) -> Result<MarinadeState, ImportError> {
+        let params = format!(r#"["{}",{{"encoding":"base64"}}]"#, self.state_address);
+        let result = self.call("getAccountInfo", &params).await?;
+        let data = result
+            .get("value")
+            .and_then(|value| value.get("data"))
+            .and_then(|data| data.index(0))
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid_response("missing account data"))?;
+        let data = base64_decode(data).ok_or_else(|| invalid_response("invalid base64"))?;
+
+        MarinadeState::decode(&data)
+    }
+
+    /// Fetches state and leg balances and builds pool mirroring mainnet
+    pub async fn fetch_pool(&self) -> Result<LpPool, ImportError> {
+        let state = self.fetch_state().await?;
+
+        let sol_leg = self
+            .call("getBalance", &format!(r#"["{}"]"#, self.sol_leg_address))
+            .await?
+            .get("value")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| invalid_response("missing SOL leg balance"))?;
+        let msol_leg = self
+            .call(
+                "getTokenAccountBalance",
+                &format!(r#"["{}"]"#, base58_encode(&state.msol_leg)),
+            )
+            .await?
+            .get("value")
+            .and_then(|value| value.get("amount"))
+            .and_then(Value::as_u64)
+            .ok_or_else(|| invalid_response("missing mSOL leg balance"))?;
+
+        Ok(state.into_pool(sol_leg, msol_leg))
+    }
+
+    async fn call(&self, method: &str, params: &str) -> Result<Value, ImportError> {
+        let body = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"{method}","params":{params}}}"#);
+        let response = self.transport.post(body).await?;
+        let mut response =
+            Value::parse(&response).map_err(|error| invalid_response(&error.to_string()))?;
+
+        if let Some(error) = response.get("error") {
+            return Err(ImportError::Rpc(error.to_string()));
+        }
+        match &mut response {
+            Value::Object(fields) => fields
+                .iter()
+                .position(|(key, _)| key == "result")
+                .map(|index| fields.swap_remove(index).1)
+                .ok_or_else(|| invalid_response("missing result")),
+            _ => Err(invalid_response("response is not an object")),
+        }
+    }
+}
+
+fn invalid_response(reason: &str) -> ImportError {
+    ImportError::InvalidResponse(reason.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::ready;
+    use std::pin::pin;
+    use std::sync::Mutex;
+    use std::task::{Context, Poll, Waker};
+
+    use super::*;
+    use crate::encoding::base64_encode;
+    use crate::marinade_account::tests::state_account;
+    use crate::types::*;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("mock transport is always ready"),
+        }
+    }
+
+    #[derive(Default)]
+    struct MockTransport {
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl RpcTransport for MockTransport {
+        fn post(&self, body: String) -> impl Future<Output = Result<String, ImportError>> + Send {
+            let response = if body.contains("getAccountInfo") {
+                format!(
+                    r#"{{"jsonrpc":"2.0","id":1,"result":{{"context":{{"slot":1}},"value":{{"data":["{}","base64"]}}}}}}"#,
+                    base64_encode(&state_account())
+                )
+            } else if body.contains("getBalance") {
+                r#"{"jsonrpc":"2.0","id":1,"result":{"value":1000002039280}}"#.into()
+            } else if body.contains("getTokenAccountBalance") {
+                r#"{"jsonrpc":"2.0","id":1,"result":{"value":{"amount":"200000000000","decimals":9}}}"#.into()
+            } else {
+                r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"Method not found"}}"#
+                    .into()
+            };
+            self.requests.lock().unwrap().push(body);
+            ready(Ok(response))
+        }
+    }
+
+    #[test]
+    fn fetches_pool_over_rpc() {
+        let client = MarinadeRpcClient::new(MockTransport::default(), "SoLLeg1111");
+        let pool = block_on(client.fetch_pool()).unwrap();
+
+        assert_eq!(pool.price(), Price::from(1.25));
+        assert_eq!(pool.token_amount(), TokenAmount::from(1_000));
+        assert_eq!(pool.st_token_amount(), StakedTokenAmount::from(200));
+        assert_eq!(pool.lp_token_amount(), LpTokenAmount::from(5_000));
+
+        let requests = client.transport.requests.lock().unwrap();
+        assert!(requests[0].contains(MARINADE_STATE_ADDRESS));
+        assert!(requests[1].contains("SoLLeg1111"));
+        assert!(requests[2].contains("11111111111111111111111111111111"));
+    }
+
+    #[test]
+    fn surfaces_rpc_errors() {
+        let client = MarinadeRpcClient::new(MockTransport::default(), "SoLLeg1111");
+        let result = block_on(client.call("unknownMethod", "[]"));
+        assert!(matches!(result, Err(ImportError::Rpc(_))));
+    }
+}
@@ -0,0 +1,214 @@
+//! Decoding of raw Marinade account data, so simulations can start from pool state
+//! captured on mainnet without any RPC access.
+
+use crate::error::ImportError;
+use crate::lp_pool::LpPool;
+use crate::types::*;
+
+/// Marinade stores mSOL price as fixed-point number with this denominator
+const MSOL_PRICE_DENOMINATOR: u128 = 0x1_0000_0000;
+/// SOL, mSOL and LP tokens all use 9 decimals
+const TOKEN_DECIMALS: u32 = 9;
+/// Marinade fees are expressed in basis points
+const BASIS_POINTS: u128 = 10_000;
+
+// offsets inside the Marinade `State` account (anchor discriminator included)
+const RENT_EXEMPT_OFFSET: usize = 138;
+const MSOL_LEG_OFFSET: usize = 420;
+const LIQUIDITY_TARGET_OFFSET: usize = 452;
+const MAX_FEE_OFFSET: usize = 460;
+const MIN_FEE_OFFSET: usize = 464;
+const TREASURY_CUT_OFFSET: usize = 468;
+const LP_SUPPLY_OFFSET: usize = 472;
+const MSOL_PRICE_OFFSET: usize = 512;
+/// minimal length of the account holding all fields read by the decoder
+const MIN_STATE_LEN: usize = MSOL_PRICE_OFFSET + 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Liquidity pool related fields of the Marinade state account
+pub struct MarinadeState {
+    pub msol_price: Price,
+    pub liquidity_target: TokenAmount,
+    pub min_fee: Percentage,
+    pub max_fee: Percentage,
+    pub treasury_cut: Percentage,
+    pub lp_supply: LpTokenAmount,
+    /// lamports kept in the SOL leg to keep it rent exempt
+    pub rent_exempt_lamports: u64,
+    /// address of the token account holding the pool's mSOL
+    pub msol_leg: [u8; 32],
+}
+
+impl MarinadeState {
+    /// Decodes raw data of the Marinade state account
+    pub fn decode(data: &[u8]) -> Result<Self, ImportError> {
+        if data.len() < MIN_STATE_LEN {
+            return Err(ImportError::InvalidAccount(format!(
+                "state account has {} bytes, expected at least {MIN_STATE_LEN}",
+                data.len()
+            )));
+        }
+        let u64_at = |offset: usize| {
+            u64::from_le_bytes(data[offset..offset + 8].try_into().expect("length checked"))
+        };
+        let bps_at = |offset: usize| {
+            let bps =
+                u32::from_le_bytes(data[offset..offset + 4].try_into().expect("length checked"));
+            Percentage::from_raw_amount((bps as u128 * SCALE as u128 / BASIS_POINTS) as Uint)
+        };
+
+        let msol_price = u64_at(MSOL_PRICE_OFFSET) as u128 * SCALE as u128 / MSOL_PRICE_DENOMINATOR;
+
+        Ok(Self {
+            msol_price: Price::from_raw_amount(
+                Uint::try_from(msol_price)
+                    .map_err(|_| ImportError::InvalidAccount("mSOL price overflow".into()))?,
+            ),
+            liquidity_target: TokenAmount::from_raw_amount(from_native(u64_at(
+                LIQUIDITY_TARGET_OFFSET,
+            ))),
+            min_fee: bps_at(MIN_FEE_OFFSET),
+            max_fee: bps_at(MAX_FEE_OFFSET),
+            treasury_cut: bps_at(TREASURY_CUT_OFFSET),
+            lp_supply: LpTokenAmount::from_raw_amount(from_native(u64_at(LP_SUPPLY_OFFSET))),
+            rent_exempt_lamports: u64_at(RENT_EXEMPT_OFFSET),
+            msol_leg: data[MSOL_LEG_OFFSET..MSOL_LEG_OFFSET + 32]
+                .try_into()
+                .expect("length checked"),
+        })
+    }
+
+    /// Builds pool mirroring the Marinade liquidity pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `sol_leg_lamports` - lamports held by the pool's SOL leg account
+    /// * `msol_leg_amount` - raw mSOL amount held by the pool's mSOL leg account
+    pub fn into_pool(self, sol_leg_lamports: u64, msol_leg_amount: u64) -> LpPool {
+        let mut pool = LpPool::init(
+            self.msol_price,
+            self.min_fee,
+            self.max_fee,
+            self.liquidity_target,
+        )
+        .expect("pool init is infallible");
+        pool.set_treasury_cut(self.treasury_cut);
+        pool.set_balances(
+            TokenAmount::from_raw_amount(from_native(
+                sol_leg_lamports.saturating_sub(self.rent_exempt_lamports),
+            )),
+            StakedTokenAmount::from_raw_amount(from_native(msol_leg_amount)),
+            self.lp_supply,
+        );
+        pool
+    }
+}
+
+/// Converts amount with 9 decimals into crate's fixed-point precision
+fn from_native(amount: u64) -> Uint {
+    (amount as u128 * SCALE as u128 / 10u128.pow(TOKEN_DECIMALS)) as Uint
+}
+
+/// size of the SPL token account data
+const TOKEN_ACCOUNT_LEN: usize = 165;
+/// offset of the token amount inside the SPL token account, after mint and owner
+const TOKEN_AMOUNT_OFFSET: usize = 64;
+
+/// Decodes raw token amount held by an SPL token account
+pub fn decode_token_amount(data: &[u8]) -> Result<u64, ImportError> {
+    if data.len() != TOKEN_ACCOUNT_LEN {
+        return Err(ImportError::InvalidAccount(format!(
+            "token account has {} bytes, expected {TOKEN_ACCOUNT_LEN}",
+            data.len()
+        )));
+    }
+    Ok(u64::from_le_bytes(
+        data[TOKEN_AMOUNT_OFFSET..TOKEN_AMOUNT_OFFSET + 8]
+            .try_into()
+            .expect("length checked"),
+    ))
+}
+
+impl LpPool {
+    /// Builds pool from raw accounts of the Marinade liquidity pool, e.g. dumped with
+    /// `solana account --output json`.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - data of the Marinade state account
+    /// * `sol_leg_lamports` - lamports held by the pool's SOL leg account
+    /// * `msol_leg` - data of the SPL token account holding the pool's mSOL
+    pub fn from_marinade_accounts(
+        state: &[u8],
+        sol_leg_lamports: u64,
+        msol_leg: &[u8],
+    ) -> Result<LpPool, ImportError> {
+        let state = MarinadeState::decode(state)?;
+        Ok(state.into_pool(sol_leg_lamports, decode_token_amount(msol_leg)?))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn state_account() -> Vec<u8> {
+        let mut data = vec![0u8; MIN_STATE_LEN];
+        let mut put_u64 = |offset: usize, value: u64| {
+            data[offset..offset + 8].copy_from_slice(&value.to_le_bytes())
+        };
+        put_u64(RENT_EXEMPT_OFFSET, 2_039_280);
+        put_u64(LIQUIDITY_TARGET_OFFSET, 10_000_000_000_000);
+        put_u64(LP_SUPPLY_OFFSET, 5_000_000_000_000);
+        // 1.25 SOL per mSOL
+        put_u64(MSOL_PRICE_OFFSET, 0x1_4000_0000);
+        for (offset, bps) in [
+            (MAX_FEE_OFFSET, 300u32),
+            (MIN_FEE_OFFSET, 30),
+            (TREASURY_CUT_OFFSET, 2500),
+        ] {
+            data[offset..offset + 4].copy_from_slice(&bps.to_le_bytes());
+        }
+        data[MSOL_LEG_OFFSET..MSOL_LEG_OFFSET + 32].copy_from_slice(&[0u8; 32]);
+        data
+    }
+
+    fn token_account(amount: u64) -> Vec<u8> {
+        let mut data = vec![0u8; TOKEN_ACCOUNT_LEN];
+        data[TOKEN_AMOUNT_OFFSET..TOKEN_AMOUNT_OFFSET + 8].copy_from_slice(&amount.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn decodes_state_account() {
+        let state = MarinadeState::decode(&state_account()).unwrap();
+        assert_eq!(state.msol_price, Price::from(1.25));
+        assert_eq!(state.min_fee, Percentage::from(0.003));
+        assert_eq!(state.max_fee, Percentage::from(0.03));
+        assert_eq!(state.treasury_cut, Percentage::from(0.25));
+        assert_eq!(state.liquidity_target, TokenAmount::from(10_000));
+        assert_eq!(state.lp_supply, LpTokenAmount::from(5_000));
+
+        assert!(MarinadeState::decode(&[0u8; 10]).is_err());
+    }
+
+    #[test]
+    fn builds_pool_from_raw_accounts() {
+        let pool = LpPool::from_marinade_accounts(
+            &state_account(),
+            1_000_002_039_280,
+            &token_account(200_000_000_000),
+        )
+        .unwrap();
+
+        assert_eq!(pool.price(), Price::from(1.25));
+        assert_eq!(pool.token_amount(), TokenAmount::from(1_000));
+        assert_eq!(pool.st_token_amount(), StakedTokenAmount::from(200));
+        assert_eq!(pool.lp_token_amount(), LpTokenAmount::from(5_000));
+
+        assert!(matches!(
+            LpPool::from_marinade_accounts(&state_account(), 0, &[0u8; 64]),
+            Err(ImportError::InvalidAccount(_))
+        ));
+    }
+}
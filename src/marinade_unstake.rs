@@ -0,0 +1,303 @@
+//! Port of the liquid unstake computation of Marinade's on-chain program, kept as a test
+//! oracle for this crate's swap. It follows `state/liq_pool.rs` and
+//! `instructions/liq_pool/liquid_unstake.rs` of `marinade-finance/liquid-staking-program`
+//! operation by operation, including its integer rounding, with account transfers left
+//! out. Amounts are lamports and raw mSOL.
+//!
+//! The crate intentionally differs from the program in the ways listed by `Deviation`,
+//! `MarinadeUnstakeState::to_pool` builds the pool the program's state corresponds to.
+
+use crate::error::MarinadeUnstakeError;
+use crate::lp_pool::LpPool;
+use crate::types::*;
+
+/// Marinade fees are expressed in basis points
+const BASIS_POINTS: u64 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Fee in basis points, `Fee` of the program
+pub struct Fee {
+    pub basis_points: u32,
+}
+
+impl Fee {
+    /// Returns fee charged from the amount, rounded down
+    pub fn apply(&self, lamports: u64) -> u64 {
+        (lamports as u128 * self.basis_points as u128 / BASIS_POINTS as u128) as u64
+    }
+
+    /// Returns the fee in crate's fixed-point precision
+    pub fn to_percentage(self) -> Percentage {
+        Percentage::from_raw_amount(self.basis_points as u64 * SCALE / BASIS_POINTS)
+    }
+}
+
+/// Returns `amount * numerator / denominator` rounded down, `amount` when the denominator
+/// is zero, the program's `proportional`
+pub fn proportional(amount: u64, numerator: u64, denominator: u64) -> Option<u64> {
+    if denominator == 0 {
+        return Some(amount);
+    }
+    u64::try_from(amount as u128 * numerator as u128 / denominator as u128).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Fee curve of the liquidity pool, `LiqPool` of the program
+pub struct MarinadeLiqPool {
+    pub lp_liquidity_target: u64,
+    pub lp_max_fee: Fee,
+    pub lp_min_fee: Fee,
+    pub treasury_cut: Fee,
+}
+
+impl MarinadeLiqPool {
+    /// Returns fee of an unstake leaving `lamports` in the pool
+    pub fn linear_fee(&self, lamports: u64) -> Fee {
+        if lamports >= self.lp_liquidity_target {
+            return self.lp_min_fee;
+        }
+        let delta = (self.lp_max_fee.basis_points - self.lp_min_fee.basis_points) as u64;
+        let rhs = proportional(delta, lamports, self.lp_liquidity_target)
+            .expect("lamports are below the target, so the result is below delta");
+        Fee {
+            basis_points: self.lp_max_fee.basis_points - rhs as u32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Parts of the program's `State` the liquid unstake depends on
+pub struct MarinadeUnstakeState {
+    pub liq_pool: MarinadeLiqPool,
+    pub total_virtual_staked_lamports: u64,
+    pub msol_supply: u64,
+    /// lamports kept in the SOL leg to keep it rent exempt
+    pub rent_exempt_for_token_acc: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Result of a liquid unstake
+pub struct LiquidUnstake {
+    /// fee charged by the pool
+    pub fee: Fee,
+    /// fee kept as mSOL, including the treasury cut
+    pub msol_fee: u64,
+    /// lamports transferred from the SOL leg to the user
+    pub lamports_out: u64,
+    /// mSOL transferred to the treasury
+    pub treasury_msol_cut: u64,
+}
+
+impl MarinadeUnstakeState {
+    pub fn calc_lamports_from_msol_amount(&self, msol_amount: u64) -> Option<u64> {
+        proportional(
+            msol_amount,
+            self.total_virtual_staked_lamports,
+            self.msol_supply,
+        )
+    }
+
+    /// Computes liquid unstake of `msol_amount` from a pool whose SOL leg holds
+    /// `sol_leg_lamports`
+    pub fn liquid_unstake(
+        &self,
+        sol_leg_lamports: u64,
+        msol_amount: u64,
+    ) -> Result<LiquidUnstake, MarinadeUnstakeError> {
+        if msol_amount == 0 {
+            return Err(MarinadeUnstakeError::ZeroAmount);
+        }
+        let max_lamports = sol_leg_lamports.saturating_sub(self.rent_exempt_for_token_acc);
+
+        let user_remove_lamports = self
+            .calc_lamports_from_msol_amount(msol_amount)
+            .ok_or(MarinadeUnstakeError::CalculationFailure)?;
+        let fee = if user_remove_lamports >= max_lamports {
+            self.liq_pool.lp_min_fee
+        } else {
+            self.liq_pool
+                .linear_fee(max_lamports - user_remove_lamports)
+        };
+
+        let msol_fee = fee.apply(msol_amount);
+        let working_lamports_value = self
+            .calc_lamports_from_msol_amount(msol_amount - msol_fee)
+            .ok_or(MarinadeUnstakeError::CalculationFailure)?;
+        if working_lamports_value.saturating_add(self.rent_exempt_for_token_acc) > sol_leg_lamports
+        {
+            return Err(MarinadeUnstakeError::InsufficientLiquidity {
+                requested: working_lamports_value,
+                available: max_lamports,
+            });
+        }
+
+        Ok(LiquidUnstake {
+            fee,
+            msol_fee,
+            lamports_out: working_lamports_value,
+            treasury_msol_cut: self.liq_pool.treasury_cut.apply(msol_fee),
+        })
+    }
+
+    /// Builds pool corresponding to the program state, with lamports and raw mSOL used
+    /// as raw token amounts. Rent exempt reserve isn't part of pool liquidity and price is
+    /// rounded to crate's precision, see `Deviation`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sol_leg_lamports` - lamports held by the pool's SOL leg account
+    /// * `msol_leg_amount` - raw mSOL amount held by the pool's mSOL leg account
+    /// * `lp_supply` - raw amount of minted LP tokens
+    pub fn to_pool(&self, sol_leg_lamports: u64, msol_leg_amount: u64, lp_supply: u64) -> LpPool {
+        let price = proportional(SCALE, self.total_virtual_staked_lamports, self.msol_supply)
+            .expect("mSOL price fits into u64");
+        let mut pool = LpPool::init(
+            Price::from_raw_amount(price),
+            self.liq_pool.lp_min_fee.to_percentage(),
+            self.liq_pool.lp_max_fee.to_percentage(),
+            TokenAmount::from_raw_amount(self.liq_pool.lp_liquidity_target),
+        )
+        .expect("pool init is infallible");
+        pool.set_treasury_cut(self.liq_pool.treasury_cut.to_percentage());
+        pool.set_balances(
+            TokenAmount::from_raw_amount(
+                sol_leg_lamports.saturating_sub(self.rent_exempt_for_token_acc),
+            ),
+            StakedTokenAmount::from_raw_amount(msol_leg_amount),
+            LpTokenAmount::from_raw_amount(lp_supply),
+        );
+        pool
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Intentional differences between `LpPool::swap` and the program's liquid unstake
+pub enum Deviation {
+    /// fees are resolved to 1e-6 instead of whole basis points, so the crate charges up
+    /// to one basis point less than the program
+    FeeResolution,
+    /// the program keeps fees as mSOL in the pool and pays out the remaining mSOL value,
+    /// the crate charges fees from paid out tokens, so treasury cut is paid in tokens
+    FeeAsset,
+    /// the program derives SOL value from total staked lamports and mSOL supply, the
+    /// crate uses price with 6 decimals, valuing every mSOL up to 1e-6 lamports lower
+    PriceResolution,
+    /// liquidity is checked against value before fees, so the crate rejects unstakes
+    /// which would fit into the pool only after fees, for which the program also charges
+    /// the minimal fee
+    OversizedSwap,
+}
+
+/// every deviation of the crate from the program
+pub const DEVIATIONS: [Deviation; 4] = [
+    Deviation::FeeResolution,
+    Deviation::FeeAsset,
+    Deviation::PriceResolution,
+    Deviation::OversizedSwap,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn random_state(rng: &mut Rng) -> (MarinadeUnstakeState, u64) {
+        let min_fee = rng.range(0, 100) as u32;
+        let max_fee = min_fee + rng.range(0, 1_000) as u32;
+        let msol_supply = rng.range(1_000_000_000, 10_000_000_000_000_000);
+        let state = MarinadeUnstakeState {
+            liq_pool: MarinadeLiqPool {
+                lp_liquidity_target: rng.range(1_000_000_000, 10_000_000_000_000),
+                lp_max_fee: Fee {
+                    basis_points: max_fee,
+                },
+                lp_min_fee: Fee {
+                    basis_points: min_fee,
+                },
+                treasury_cut: Fee {
+                    basis_points: rng.range(0, 10_000) as u32,
+                },
+            },
+            total_virtual_staked_lamports: msol_supply / 100 * rng.range(100, 130),
+            msol_supply,
+            rent_exempt_for_token_acc: 2_039_280,
+        };
+        // pool math multiplies raw amounts by raw price in u64, so liquidity stays below
+        // ten thousand SOL
+        (state, rng.range(0, 10_000_000_000_000))
+    }
+
+    #[test]
+    fn matches_program_fee_curve() {
+        let liq_pool = MarinadeLiqPool {
+            lp_liquidity_target: 10_000,
+            lp_max_fee: Fee { basis_points: 300 },
+            lp_min_fee: Fee { basis_points: 30 },
+            treasury_cut: Fee {
+                basis_points: 2_500,
+            },
+        };
+        assert_eq!(liq_pool.linear_fee(0).basis_points, 300);
+        assert_eq!(liq_pool.linear_fee(5_000).basis_points, 165);
+        assert_eq!(liq_pool.linear_fee(10_000).basis_points, 30);
+        assert_eq!(Fee { basis_points: 165 }.apply(1_000_001), 16_500);
+    }
+
+    #[test]
+    fn swap_matches_program_within_deviations() {
+        let mut rng = Rng::new(0);
+        let mut compared = 0;
+        for _ in 0..2_000 {
+            let (state, sol_leg) = random_state(&mut rng);
+            let msol_amount = rng.range(1, sol_leg.max(1));
+            let mut pool = state.to_pool(sol_leg, 0, sol_leg);
+
+            let fee = pool.fee_for_swap(StakedTokenAmount::from_raw_amount(msol_amount));
+            let Ok(amount_out) = pool.swap(StakedTokenAmount::from_raw_amount(msol_amount)) else {
+                continue;
+            };
+            let program = state
+                .liquid_unstake(sol_leg, msol_amount)
+                .expect("crate checks liquidity more strictly, see OversizedSwap");
+            compared += 1;
+
+            // FeeResolution: at most one basis point less, plus the fee change caused by
+            // the lower value of PriceResolution
+            let fee = fee.unwrap().raw();
+            let program_fee = program.fee.to_percentage().raw();
+            let liq_pool = state.liq_pool;
+            let slope = (liq_pool.lp_max_fee.basis_points - liq_pool.lp_min_fee.basis_points)
+                as f64
+                * 100.0
+                / liq_pool.lp_liquidity_target as f64;
+            let fee_tolerance = 100.0 + slope * (msol_amount as f64 / SCALE as f64 + 2.0) + 1.0;
+            assert!(fee <= program_fee + 1, "{fee} > {program_fee}");
+            assert!((program_fee - fee.min(program_fee)) as f64 <= fee_tolerance);
+
+            // FeeAsset and PriceResolution: same value up to the fee difference
+            let value = amount_out.raw() as f64 / (1.0 - fee as f64 / SCALE as f64);
+            let tolerance = value * (fee_tolerance + 1.0) / SCALE as f64
+                + msol_amount as f64 / SCALE as f64
+                + 3.0;
+            let difference = (amount_out.raw() as f64 - program.lamports_out as f64).abs();
+            assert!(difference <= tolerance, "{difference} > {tolerance}");
+        }
+        assert!(compared > 1_000, "only {compared} swaps compared");
+    }
+
+    #[test]
+    fn program_charges_min_fee_for_oversized_unstakes() {
+        let (state, _) = random_state(&mut Rng::new(1));
+        let sol_leg = state.rent_exempt_for_token_acc + 1_000;
+        let unstake = state.liquid_unstake(sol_leg, 1_000_000);
+        assert!(matches!(
+            unstake,
+            Err(MarinadeUnstakeError::InsufficientLiquidity { .. })
+        ));
+        assert!(state
+            .to_pool(sol_leg, 0, sol_leg)
+            .swap(StakedTokenAmount::from_raw_amount(1_000_000))
+            .is_err());
+        assert_eq!(DEVIATIONS.len(), 4);
+    }
+}
@@ -0,0 +1,103 @@
+//! Migrations of pool snapshots written by older crate versions. Every migration upgrades
+//! the state document by exactly one schema version, so a snapshot of any version is
+//! brought up to `SCHEMA_VERSION` by applying the chain starting at its own version.
+//! Fields added without changing the version are defaulted by `FromJson` itself.
+
+use crate::error::{SchemaError, SnapshotError};
+use crate::json::Value;
+use crate::lp_pool::LpPool;
+use crate::schema::{field, FromJson, SCHEMA_VERSION};
+use crate::snapshot::decode_value;
+
+/// first schema version, every document written by the crate carries its version
+const FIRST_SCHEMA_VERSION: u64 = 1;
+
+/// Upgrades state document from version `index + 1` to version `index + 2`
+type Migration = fn(&mut Value) -> Result<(), SchemaError>;
+
+/// no version was superseded yet, migrations are added whenever `SCHEMA_VERSION` is bumped
+const MIGRATIONS: [Migration; (SCHEMA_VERSION - FIRST_SCHEMA_VERSION) as usize] = [];
+
+/// Restores pool from a snapshot written with an older schema version.
+///
+/// # Arguments
+///
+/// * `bytes` - binary snapshot created with `LpPool::to_snapshot`
+/// * `from_version` - schema version the snapshot was written with, has to match version
+///   stored in the snapshot if there is one
+pub fn migrate(bytes: &[u8], from_version: u64) -> Result<LpPool, SnapshotError> {
+    if from_version > SCHEMA_VERSION {
+        return Err(SchemaError::UnsupportedVersion {
+            version: from_version,
+            supported: SCHEMA_VERSION,
+        }
+        .into());
+    }
+    if from_version < FIRST_SCHEMA_VERSION {
+        return Err(SchemaError::InvalidValue {
+            path: "version".into(),
+            reason: format!("schema versions start at {FIRST_SCHEMA_VERSION}"),
+        }
+        .into());
+    }
+    let mut value = decode_value(bytes)?;
+    let stored: u64 = field(&value, "version")?;
+    if stored != from_version {
+        return Err(SchemaError::InvalidValue {
+            path: "version".into(),
+            reason: format!("snapshot has version {stored}, expected {from_version}"),
+        }
+        .into());
+    }
+
+    for migration in &MIGRATIONS[(from_version - FIRST_SCHEMA_VERSION) as usize..] {
+        migration(&mut value)?;
+    }
+    Ok(LpPool::from_json(&value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::ToJson;
+    use crate::snapshot::encode_value;
+
+    fn pool() -> LpPool {
+        let mut pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        pool.add_liquidity_for(1, 100.into()).unwrap();
+        pool.swap(6.into()).unwrap();
+        pool
+    }
+
+    #[test]
+    fn current_version_round_trips() {
+        let pool = pool();
+        let migrated = migrate(&pool.to_snapshot(), SCHEMA_VERSION).unwrap();
+        assert_eq!(migrated.to_json(), pool.to_json());
+        assert_eq!(migrated.state_hash(), pool.state_hash());
+    }
+
+    #[test]
+    fn rejects_wrong_versions() {
+        let snapshot = pool().to_snapshot();
+        assert!(matches!(
+            migrate(&snapshot, 0),
+            Err(SnapshotError::Schema(SchemaError::InvalidValue { .. }))
+        ));
+        let mut unversioned = pool().to_json();
+        let Value::Object(fields) = &mut unversioned else {
+            unreachable!()
+        };
+        fields.retain(|(key, _)| key != "version");
+        assert!(matches!(
+            migrate(&encode_value(&unversioned), SCHEMA_VERSION),
+            Err(SnapshotError::Schema(SchemaError::MissingField { .. }))
+        ));
+        assert!(matches!(
+            migrate(&snapshot, SCHEMA_VERSION + 1),
+            Err(SnapshotError::Schema(
+                SchemaError::UnsupportedVersion { .. }
+            ))
+        ));
+    }
+}
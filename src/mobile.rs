@@ -0,0 +1,181 @@
+//! Pool API matching the UniFFI interface in `uniffi/lp_pool.udl`, so Swift and Kotlin
+//! wallets can quote swaps locally with the exact fixed-point math. UniFFI objects are
+//! shared behind `Arc`, therefore every method takes `&self` and the pool is locked.
+
+use thiserror::Error;
+
+use crate::error::ParseAmountError;
+use crate::lp_pool::LpPool;
+use crate::shared::SharedPool;
+use crate::types::*;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+/// Flat error exported to Swift and Kotlin, carrying message of the underlying error
+pub enum MobileError {
+    #[error("Invalid amount: {0}")]
+    InvalidAmount(String),
+    #[error("Operation rejected: {0}")]
+    Rejected(String),
+}
+
+impl From<ParseAmountError> for MobileError {
+    fn from(error: ParseAmountError) -> Self {
+        Self::InvalidAmount(error.0)
+    }
+}
+
+fn rejected(error: impl std::error::Error) -> MobileError {
+    MobileError::Rejected(error.to_string())
+}
+
+/// Formats raw fixed-point value as a decimal string
+pub fn format_amount(raw: Uint) -> String {
+    TokenAmount::from_raw_amount(raw).to_string()
+}
+
+/// Parses decimal string into raw fixed-point value
+pub fn parse_amount(amount: &str) -> Result<Uint, MobileError> {
+    Ok(amount.parse::<TokenAmount>()?.raw())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Outcome of a swap, amounts are raw fixed-point values
+pub struct MobileQuote {
+    pub amount_out: Uint,
+    pub fee: Uint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Pool state, amounts are raw fixed-point values
+pub struct MobilePoolStats {
+    pub price: Uint,
+    pub epoch: Epoch,
+    pub token_amount: Uint,
+    pub st_token_amount: Uint,
+    pub lp_token_amount: Uint,
+    pub current_fee: Uint,
+}
+
+#[derive(Debug)]
+/// Pool exported to Swift and Kotlin
+pub struct MobilePool {
+    pool: SharedPool,
+}
+
+impl MobilePool {
+    /// Creates pool from raw fixed-point values
+    pub fn new(price: Uint, min_fee: Uint, max_fee: Uint, liquidity_target: Uint) -> Self {
+        let pool = LpPool::init(
+            Price::from_raw_amount(price),
+            Percentage::from_raw_amount(min_fee),
+            Percentage::from_raw_amount(max_fee),
+            TokenAmount::from_raw_amount(liquidity_target),
+        )
+        .unwrap_or_else(|never| match never {});
+        Self {
+            pool: SharedPool::new(pool),
+        }
+    }
+
+    /// Returns outcome of swapping staked tokens without modifying the pool
+    pub fn quote(&self, amount: Uint) -> Result<MobileQuote, MobileError> {
+        let amount = StakedTokenAmount::from_raw_amount(amount);
+        self.pool.with(|pool| {
+            Ok(MobileQuote {
+                amount_out: pool.amount_for_swap(amount).map_err(rejected)?.raw(),
+                fee: pool.fee_for_swap(amount).map_err(rejected)?.raw(),
+            })
+        })
+    }
+
+    pub fn swap(&self, amount: Uint) -> Result<Uint, MobileError> {
+        self.pool.with(|pool| {
+            pool.swap(StakedTokenAmount::from_raw_amount(amount))
+                .map(|amount| amount.raw())
+                .map_err(rejected)
+        })
+    }
+
+    pub fn add_liquidity(&self, amount: Uint) -> Result<Uint, MobileError> {
+        self.pool.with(|pool| {
+            pool.add_liquidity(TokenAmount::from_raw_amount(amount))
+                .map(|lp_amount| lp_amount.raw())
+                .map_err(rejected)
+        })
+    }
+
+    /// Returns withdrawn raw tokens and staked tokens
+    pub fn remove_liquidity(&self, lp_amount: Uint) -> Result<Vec<Uint>, MobileError> {
+        self.pool.with(|pool| {
+            pool.remove_liquidity(LpTokenAmount::from_raw_amount(lp_amount))
+                .map(|(tokens, staked_tokens)| vec![tokens.raw(), staked_tokens.raw()])
+                .map_err(rejected)
+        })
+    }
+
+    pub fn set_price(&self, price: Uint) -> Result<(), MobileError> {
+        self.pool.with(|pool| {
+            pool.set_price(Price::from_raw_amount(price))
+                .map_err(rejected)
+        })
+    }
+
+    pub fn advance_epoch(&self, epochs: Epoch) {
+        self.pool.with(|pool| pool.advance_epoch(epochs));
+    }
+
+    pub fn stats(&self) -> MobilePoolStats {
+        self.pool.with(|pool| MobilePoolStats {
+            price: pool.price().raw(),
+            epoch: pool.epoch(),
+            token_amount: pool.token_amount().raw(),
+            st_token_amount: pool.st_token_amount().raw(),
+            lp_token_amount: pool.lp_token_amount().raw(),
+            current_fee: pool.current_fee().raw(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_with_exact_amounts() {
+        let pool = MobilePool::new(1_500_000, 1_000, 90_000, 90_000_000);
+        assert_eq!(
+            pool.add_liquidity(parse_amount("100").unwrap()),
+            Ok(100_000_000)
+        );
+
+        let quote = pool.quote(6_000_000).unwrap();
+        assert_eq!(format_amount(quote.amount_out), "8.991000");
+        assert_eq!(pool.swap(6_000_000), Ok(quote.amount_out));
+        assert_eq!(pool.stats().st_token_amount, 6_000_000);
+
+        assert!(matches!(pool.swap(0), Err(MobileError::Rejected(_))));
+        assert!(matches!(
+            parse_amount("1e3"),
+            Err(MobileError::InvalidAmount(_))
+        ));
+    }
+
+    #[test]
+    fn udl_declares_every_method() {
+        let udl = include_str!("../uniffi/lp_pool.udl");
+        for declaration in [
+            "string format_amount(u64 raw);",
+            "u64 parse_amount([ByRef] string amount);",
+            "constructor(u64 price, u64 min_fee, u64 max_fee, u64 liquidity_target);",
+            "MobileQuote quote(u64 amount);",
+            "u64 swap(u64 amount);",
+            "u64 add_liquidity(u64 amount);",
+            "sequence<u64> remove_liquidity(u64 lp_amount);",
+            "void set_price(u64 price);",
+            "void advance_epoch(u64 epochs);",
+            "MobilePoolStats stats();",
+        ] {
+            assert!(udl.contains(declaration), "{declaration}");
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Bounded model check of the pool math. Instead of sampling random sequences it builds
+//! every pool whose balances and liquidity target are at most a few raw units, for every
+//! combination of the listed prices, fees and treasury cuts, and applies every operation
+//! with amounts in the same range to each of them. States are built from on-chain
+//! accounts, so unreachable ones, e.g. lp tokens backed by dust or by nothing, are
+//! checked too.
+//!
+//! Every operation has to keep the invariants of `check_invariants` and must not panic,
+//! and every deposit into a pool with lp tokens has to pass the round trip of
+//! `check_round_trip`.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::account::PoolAccount;
+use crate::error::{ModelCheckError, PropertyError};
+use crate::lp_pool::LpPool;
+use crate::ops::PoolOp;
+use crate::properties::{check_invariants, check_round_trip};
+use crate::types::*;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Values enumerated by `check_exhaustive`
+pub struct StateSpace {
+    /// balances, liquidity targets and amounts of operations range over
+    /// `0..=max_amount` raw units
+    pub max_amount: Uint,
+    pub prices: Vec<Price>,
+    /// minimal and maximal fee range over every pair of these, inverted pairs included
+    pub fees: Vec<Percentage>,
+    pub treasury_cuts: Vec<Percentage>,
+}
+
+impl StateSpace {
+    /// Creates space of amounts up to `max_amount` raw units with the smallest price, a
+    /// price below one, the even price and the price of the task description, fees of
+    /// zero, 0.1%, 9% and 100% and treasury cuts of zero and a quarter
+    pub fn new(max_amount: Uint) -> Self {
+        Self {
+            max_amount,
+            prices: vec![
+                Price::from_raw_amount(1),
+                0.5.into(),
+                1.0.into(),
+                1.5.into(),
+            ],
+            fees: vec![
+                Percentage::from_raw_amount(0),
+                0.001.into(),
+                0.09.into(),
+                1.0.into(),
+            ],
+            treasury_cuts: vec![Percentage::from_raw_amount(0), 0.25.into()],
+        }
+    }
+
+    /// Returns every pool account of the space
+    pub fn accounts(&self) -> Vec<PoolAccount> {
+        let amounts = 0..=self.max_amount;
+        let mut accounts = Vec::new();
+        for price in &self.prices {
+            for min_fee in &self.fees {
+                for max_fee in &self.fees {
+                    for treasury_cut in &self.treasury_cuts {
+                        for liquidity_target in amounts.clone() {
+                            for token_amount in amounts.clone() {
+                                for st_token_amount in amounts.clone() {
+                                    for lp_token_amount in amounts.clone() {
+                                        accounts.push(PoolAccount {
+                                            price: price.raw(),
+                                            token_amount,
+                                            st_token_amount,
+                                            lp_token_amount,
+                                            liquidity_target,
+                                            min_fee: min_fee.raw(),
+                                            max_fee: max_fee.raw(),
+                                            treasury_cut: treasury_cut.raw(),
+                                            ..PoolAccount::default()
+                                        });
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        accounts
+    }
+
+    /// Returns every operation of the space. Operations of accounts aren't included, as
+    /// on-chain accounts don't keep positions.
+    pub fn ops(&self) -> Vec<PoolOp> {
+        let amounts = 0..=self.max_amount;
+        let mut ops = Vec::new();
+        for amount in amounts.clone() {
+            ops.push(PoolOp::AddLiquidity {
+                account: None,
+                amount: TokenAmount::from_raw_amount(amount),
+            });
+            ops.push(PoolOp::RemoveLiquidity {
+                account: None,
+                lp_amount: LpTokenAmount::from_raw_amount(amount),
+            });
+            ops.push(PoolOp::Swap {
+                account: None,
+                amount: StakedTokenAmount::from_raw_amount(amount),
+            });
+        }
+        ops.extend(self.prices.iter().map(|&price| PoolOp::SetPrice { price }));
+        ops.extend((0..=1).map(|epochs| PoolOp::AdvanceEpoch { epochs }));
+        ops
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Summary of an exhaustive check
+pub struct ModelReport {
+    /// amount of checked pools
+    pub states: usize,
+    /// amount of operations applied, accepted or not
+    pub applied: usize,
+    /// amount of operations which changed state kept in the account
+    pub changed: usize,
+}
+
+/// Returns message of a caught panic
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or_else(
+            || "unknown panic".to_string(),
+            |message| message.to_string(),
+        ),
+    }
+}
+
+/// Runs `f`, turning its panic into an error of the checked state and operation
+fn guarded<T>(
+    account: &PoolAccount,
+    op: &PoolOp,
+    f: impl FnOnce() -> T,
+) -> Result<T, ModelCheckError> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| ModelCheckError::Panicked {
+        account: Box::new(*account),
+        op: *op,
+        message: panic_message(payload),
+    })
+}
+
+/// Applies every operation of the space to every pool of the space, checking the
+/// invariants of every step. Returns the first violation.
+pub fn check_exhaustive(space: &StateSpace) -> Result<ModelReport, ModelCheckError> {
+    let ops = space.ops();
+    let mut report = ModelReport::default();
+    for account in space.accounts() {
+        report.states += 1;
+        let pool = LpPool::from_account(&account);
+        for op in &ops {
+            let violated = |error: PropertyError| ModelCheckError::Violated {
+                account: Box::new(account),
+                op: *op,
+                error,
+            };
+            let mut copy = LpPool::from_account(&account);
+            guarded(&account, op, || check_invariants(&mut copy, &[*op]))?.map_err(violated)?;
+            report.applied += 1;
+            report.changed += usize::from(copy.to_account() != pool.to_account());
+
+            // the first deposit takes over value nobody owns, so only deposits into pools
+            // with lp tokens are round trips
+            if let (PoolOp::AddLiquidity { amount, .. }, 1..) = (*op, account.lp_token_amount) {
+                guarded(&account, op, || check_round_trip(&pool, amount, &[]))?
+                    .map_err(violated)?;
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enumerates_space() {
+        let space = StateSpace::new(2);
+        assert_eq!(space.accounts().len(), 4 * 16 * 2 * 3usize.pow(4));
+        assert_eq!(space.ops().len(), 3 * 3 + 4 + 2);
+    }
+
+    #[test]
+    fn keeps_invariants_of_small_pools() {
+        let space = StateSpace::new(2);
+        let report = check_exhaustive(&space).unwrap();
+        assert_eq!(report.states, space.accounts().len());
+        assert_eq!(report.applied, report.states * space.ops().len());
+        assert!(report.changed > 0);
+    }
+
+    #[test]
+    fn reports_panics() {
+        let op = PoolOp::Swap {
+            account: None,
+            amount: 1.into(),
+        };
+        let error = guarded(&PoolAccount::default(), &op, || {
+            let divisor = LpPool::from_account(&PoolAccount::default()).lp_token_amount();
+            1 / divisor.raw()
+        })
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            ModelCheckError::Panicked { message, .. } if message.contains("divide by zero")
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+//! Pool API shaped for napi-rs exports. Amounts cross the boundary as raw fixed-point
+//! integers, mapped to JavaScript `BigInt`, or as decimal strings where TypeScript code
+//! prefers them. Errors are returned as messages napi turns into JavaScript errors.
+
+use crate::error::{ParseAmountError, SwapError};
+use crate::lp_pool::LpPool;
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Swap quote exported as a plain object, amounts are raw `BigInt` values
+pub struct NodeQuote {
+    pub amount_out: Uint,
+    pub fee: Uint,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// Pool state exported as a plain object with decimal string amounts
+pub struct NodePoolStats {
+    pub price: String,
+    pub epoch: Epoch,
+    pub token_amount: String,
+    pub st_token_amount: String,
+    pub lp_token_amount: String,
+    pub total_value: String,
+    pub current_fee: String,
+    pub total_fees: String,
+}
+
+#[derive(Debug)]
+/// Pool exported to Node.js
+pub struct NodePool {
+    pool: LpPool,
+}
+
+impl NodePool {
+    /// Creates pool from raw fixed-point values
+    pub fn new(price: Uint, min_fee: Uint, max_fee: Uint, liquidity_target: Uint) -> Self {
+        let pool = LpPool::init(
+            Price::from_raw_amount(price),
+            Percentage::from_raw_amount(min_fee),
+            Percentage::from_raw_amount(max_fee),
+            TokenAmount::from_raw_amount(liquidity_target),
+        )
+        .unwrap_or_else(|never| match never {});
+        Self { pool }
+    }
+
+    /// Returns outcome of swapping raw staked token amount without modifying the pool
+    pub fn quote(&self, amount: Uint) -> Result<NodeQuote, String> {
+        let amount = StakedTokenAmount::from_raw_amount(amount);
+        let quote = || {
+            Ok(NodeQuote {
+                amount_out: self.pool.amount_for_swap(amount)?.raw(),
+                fee: self.pool.fee_for_swap(amount)?.raw(),
+            })
+        };
+        quote().map_err(|error: SwapError| error.to_string())
+    }
+
+    /// Same as `quote`, taking decimal string amount
+    pub fn quote_decimal(&self, amount: &str) -> Result<NodeQuote, String> {
+        let amount: StakedTokenAmount = amount
+            .parse()
+            .map_err(|error: ParseAmountError| error.to_string())?;
+        self.quote(amount.raw())
+    }
+
+    pub fn swap(&mut self, amount: Uint) -> Result<Uint, String> {
+        self.pool
+            .swap(StakedTokenAmount::from_raw_amount(amount))
+            .map(|amount| amount.raw())
+            .map_err(|error| error.to_string())
+    }
+
+    pub fn add_liquidity(&mut self, amount: Uint) -> Result<Uint, String> {
+        self.pool
+            .add_liquidity(TokenAmount::from_raw_amount(amount))
+            .map(|lp_amount| lp_amount.raw())
+            .map_err(|error| error.to_string())
+    }
+
+    /// Returns withdrawn raw tokens and staked tokens
+    pub fn remove_liquidity(&mut self, lp_amount: Uint) -> Result<Vec<Uint>, String> {
+        self.pool
+            .remove_liquidity(LpTokenAmount::from_raw_amount(lp_amount))
+            .map(|(tokens, staked_tokens)| vec![tokens.raw(), staked_tokens.raw()])
+            .map_err(|error| error.to_string())
+    }
+
+    pub fn set_price(&mut self, price: Uint) -> Result<(), String> {
+        self.pool
+            .set_price(Price::from_raw_amount(price))
+            .map_err(|error| error.to_string())
+    }
+
+    pub fn advance_epoch(&mut self, epochs: Epoch) {
+        self.pool.advance_epoch(epochs);
+    }
+
+    pub fn stats(&self) -> NodePoolStats {
+        NodePoolStats {
+            price: self.pool.price().to_string(),
+            epoch: self.pool.epoch(),
+            token_amount: self.pool.token_amount().to_string(),
+            st_token_amount: self.pool.st_token_amount().to_string(),
+            lp_token_amount: self.pool.lp_token_amount().to_string(),
+            total_value: self.pool.total_value().to_string(),
+            current_fee: self.pool.current_fee().to_string(),
+            total_fees: self.pool.fee_revenue().total.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exposes_raw_and_decimal_amounts() {
+        let mut pool = NodePool::new(1_500_000, 1_000, 90_000, 90_000_000);
+        assert_eq!(pool.add_liquidity(100_000_000), Ok(100_000_000));
+
+        let quote = pool.quote_decimal("6").unwrap();
+        assert_eq!(quote, pool.quote(6_000_000).unwrap());
+        assert_eq!(pool.swap(6_000_000), Ok(quote.amount_out));
+        assert_eq!(quote.fee, 1_000);
+
+        let stats = pool.stats();
+        assert_eq!(stats.st_token_amount, "6.000000");
+        assert_eq!(stats.total_fees, "0.009000");
+
+        assert!(pool.quote(0).is_err());
+        assert!(pool.quote_decimal("six").is_err());
+        assert!(pool.remove_liquidity(1_000_000_000).is_err());
+    }
+}
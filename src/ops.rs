@@ -0,0 +1,178 @@
+use crate::discounts::DiscountTier;
+use crate::error::*;
+use crate::lp_pool::LpPool;
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Single mutating pool operation, used to record and replay operation streams
+pub enum PoolOp {
+    AddLiquidity {
+        /// account the liquidity is attributed to, `None` for anonymous deposits
+        account: Option<AccountId>,
+        amount: TokenAmount,
+    },
+    RemoveLiquidity {
+        /// account the liquidity is withdrawn from, `None` for anonymous withdrawals
+        account: Option<AccountId>,
+        lp_amount: LpTokenAmount,
+    },
+    Swap {
+        /// account the swap is attributed to, resolves its fee discount tier, `None` for
+        /// anonymous swaps
+        account: Option<AccountId>,
+        amount: StakedTokenAmount,
+    },
+    SetPrice {
+        price: Price,
+    },
+    AdvanceEpoch {
+        epochs: Epoch,
+    },
+    /// Mutation outside of the operation set, e.g. a forced price or a configuration
+    /// change, recorded so that replaying the log fails instead of diverging
+    Unreplayable,
+}
+
+impl PoolOp {
+    /// Returns snake case name of the operation, used by exporters
+    pub fn name(&self) -> &'static str {
+        match self {
+            PoolOp::AddLiquidity { .. } => "add_liquidity",
+            PoolOp::RemoveLiquidity { .. } => "remove_liquidity",
+            PoolOp::Swap { .. } => "swap",
+            PoolOp::SetPrice { .. } => "set_price",
+            PoolOp::AdvanceEpoch { .. } => "advance_epoch",
+            PoolOp::Unreplayable => "unreplayable",
+        }
+    }
+
+    /// Returns account the operation is attributed to
+    pub fn account(&self) -> Option<AccountId> {
+        match *self {
+            PoolOp::AddLiquidity { account, .. }
+            | PoolOp::RemoveLiquidity { account, .. }
+            | PoolOp::Swap { account, .. } => account,
+            _ => None,
+        }
+    }
+
+    /// Returns raw value of the operation's argument, e.g. swapped amount or number of
+    /// epochs
+    pub fn raw_amount(&self) -> Uint {
+        match *self {
+            PoolOp::AddLiquidity { amount, .. } => amount.raw(),
+            PoolOp::RemoveLiquidity { lp_amount, .. } => lp_amount.raw(),
+            PoolOp::Swap { amount, .. } => amount.raw(),
+            PoolOp::SetPrice { price } => price.raw(),
+            PoolOp::AdvanceEpoch { epochs } => epochs,
+            PoolOp::Unreplayable => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Result of successfully applied `PoolOp`
+pub enum OpOutcome {
+    LiquidityAdded(LpTokenAmount),
+    LiquidityRemoved(TokenAmount, StakedTokenAmount),
+    Swapped(TokenAmount),
+    PriceSet,
+    EpochAdvanced,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Result of a swap attributed to an account
+pub struct SwapOutcome {
+    /// tokens granted to the caller
+    pub amount_out: TokenAmount,
+    /// fee percentage charged, with the discount taken off
+    pub fee: Percentage,
+    pub fee_amount: TokenAmount,
+    /// discount tier the account qualified for when the swap was quoted
+    pub tier: Option<DiscountTier>,
+}
+
+impl LpPool {
+    /// Applies single operation to the pool by dispatching to the matching method, between
+    /// the registered hooks.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - operation to apply
+    pub fn apply(&mut self, op: &PoolOp) -> Result<OpOutcome, OpError> {
+        self.with_hooks(op, |pool| pool.dispatch(op))
+    }
+
+    fn dispatch(&mut self, op: &PoolOp) -> Result<OpOutcome, OpError> {
+        let outcome = match *op {
+            PoolOp::AddLiquidity { account, amount } => OpOutcome::LiquidityAdded(match account {
+                Some(account) => self.add_liquidity_for(account, amount)?,
+                None => self.add_liquidity(amount)?,
+            }),
+            PoolOp::RemoveLiquidity { account, lp_amount } => {
+                let (tokens, staked) = match account {
+                    Some(account) => self.remove_liquidity_for(account, lp_amount)?,
+                    None => self.remove_liquidity(lp_amount)?,
+                };
+                OpOutcome::LiquidityRemoved(tokens, staked)
+            }
+            PoolOp::Swap { account, amount } => OpOutcome::Swapped(match account {
+                Some(account) => self.swap_for(account, amount)?.amount_out,
+                None => self.swap(amount)?,
+            }),
+            PoolOp::SetPrice { price } => {
+                self.set_price(price)?;
+                OpOutcome::PriceSet
+            }
+            PoolOp::AdvanceEpoch { epochs } => {
+                self.advance_epoch(epochs);
+                OpOutcome::EpochAdvanced
+            }
+            PoolOp::Unreplayable => return Err(OpError::Unreplayable),
+        };
+
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_operations() {
+        let mut pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        let ops = [
+            PoolOp::AddLiquidity {
+                account: Some(1),
+                amount: 100.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 6.into(),
+            },
+            PoolOp::AdvanceEpoch { epochs: 1 },
+        ];
+
+        let outcomes: Vec<_> = ops.iter().map(|op| pool.apply(op).unwrap()).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                OpOutcome::LiquidityAdded(100.into()),
+                OpOutcome::Swapped(8.991.into()),
+                OpOutcome::EpochAdvanced,
+            ]
+        );
+        assert_eq!(pool.epoch(), 1);
+    }
+
+    #[test]
+    fn surfaces_operation_errors() {
+        let mut pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        let result = pool.apply(&PoolOp::Swap {
+            account: None,
+            amount: 6.into(),
+        });
+        assert!(matches!(result, Err(OpError::Swap(_))));
+    }
+}
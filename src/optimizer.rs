@@ -0,0 +1,141 @@
+use crate::backtest::{replay, PolicyReport};
+use crate::governance::PoolParams;
+use crate::lp_pool::LpPool;
+use crate::ops::PoolOp;
+use crate::types::*;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// Candidate values searched by `optimize`, every combination is evaluated
+pub struct ParamGrid {
+    pub min_fees: Vec<Percentage>,
+    pub max_fees: Vec<Percentage>,
+    pub liquidity_targets: Vec<TokenAmount>,
+}
+
+impl ParamGrid {
+    /// Returns all valid parameter combinations of the grid
+    pub fn combinations(&self) -> impl Iterator<Item = PoolParams> + '_ {
+        self.min_fees.iter().flat_map(move |min_fee| {
+            self.max_fees.iter().flat_map(move |max_fee| {
+                self.liquidity_targets
+                    .iter()
+                    .map(move |liquidity_target| PoolParams {
+                        min_fee: *min_fee,
+                        max_fee: *max_fee,
+                        liquidity_target: *liquidity_target,
+                    })
+                    .filter(|params| params.validate().is_ok())
+            })
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Best configuration found by `optimize`
+pub struct Optimum<S> {
+    pub params: PoolParams,
+    pub report: PolicyReport,
+    pub score: S,
+}
+
+/// Grid searches pool parameters maximizing the objective over the operation stream.
+/// Returns `None` if the objective rejected every configuration. When several
+/// configurations score the same the first one in grid order wins.
+///
+/// # Arguments
+///
+/// * `make_pool` - creates pool in its initial state with given parameters
+/// * `ops` - operation stream every configuration is evaluated against
+/// * `grid` - searched parameter values
+/// * `objective` - scores a report, `None` marks configuration as infeasible
+///   (e.g. because its slippage is too high)
+pub fn optimize<S: PartialOrd>(
+    make_pool: impl Fn(PoolParams) -> LpPool,
+    ops: &[PoolOp],
+    grid: &ParamGrid,
+    objective: impl Fn(&PolicyReport) -> Option<S>,
+) -> Option<Optimum<S>> {
+    let mut best: Option<Optimum<S>> = None;
+
+    for params in grid.combinations() {
+        let report = replay(make_pool(params), ops);
+        let Some(score) = objective(&report) else {
+            continue;
+        };
+        if best.as_ref().is_none_or(|best| score > best.score) {
+            best = Some(Optimum {
+                params,
+                report,
+                score,
+            });
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_pool(params: PoolParams) -> LpPool {
+        LpPool::init(
+            1.into(),
+            params.min_fee,
+            params.max_fee,
+            params.liquidity_target,
+        )
+        .unwrap()
+    }
+
+    fn ops() -> Vec<PoolOp> {
+        let mut ops = vec![PoolOp::AddLiquidity {
+            account: None,
+            amount: 100.into(),
+        }];
+        ops.extend((0..5).map(|_| PoolOp::Swap {
+            account: None,
+            amount: 10.into(),
+        }));
+        ops
+    }
+
+    fn grid() -> ParamGrid {
+        ParamGrid {
+            min_fees: vec![0.001.into(), 0.01.into()],
+            max_fees: vec![0.05.into(), 0.1.into()],
+            liquidity_targets: vec![50.into(), 100.into()],
+        }
+    }
+
+    #[test]
+    fn skips_invalid_combinations() {
+        let grid = ParamGrid {
+            min_fees: vec![0.2.into()],
+            ..grid()
+        };
+        assert_eq!(grid.combinations().count(), 0);
+        assert_eq!(self::grid().combinations().count(), 8);
+    }
+
+    #[test]
+    fn maximizes_revenue() {
+        let best = optimize(make_pool, &ops(), &grid(), |report| Some(report.revenue)).unwrap();
+        assert_eq!(best.params.min_fee, Percentage::from(0.01));
+        assert_eq!(best.params.max_fee, Percentage::from(0.1));
+        assert_eq!(best.params.liquidity_target, TokenAmount::from(100));
+    }
+
+    #[test]
+    fn respects_constraints() {
+        let max_slippage = Percentage::from(0.02);
+        let best = optimize(make_pool, &ops(), &grid(), |report| {
+            (report.average_slippage <= max_slippage).then_some(report.revenue)
+        })
+        .unwrap();
+        assert!(best.report.average_slippage <= max_slippage);
+
+        let none = optimize(make_pool, &ops(), &grid(), |_| None::<TokenAmount>);
+        assert!(none.is_none());
+    }
+}
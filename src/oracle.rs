@@ -0,0 +1,240 @@
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use crate::error::OracleError;
+use crate::types::*;
+
+/// Source of the StakedToken price used by the pool. Pool queries the oracle before
+/// every operation that depends on the price.
+pub trait PriceOracle: Debug + Send + Sync {
+    /// Returns current price of StakedToken in respect to Token
+    fn price(&self) -> Result<Price, OracleError>;
+}
+
+/// Returns current unix timestamp in seconds, used by oracles checking staleness and to
+/// timestamp audit entries
+pub(crate) fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Oracle always returning the same price, used by default
+pub struct FixedOracle(Price);
+
+impl FixedOracle {
+    pub fn new(price: Price) -> Self {
+        Self(price)
+    }
+}
+
+impl PriceOracle for FixedOracle {
+    fn price(&self) -> Result<Price, OracleError> {
+        Ok(self.0)
+    }
+}
+
+impl<T: PriceOracle + ?Sized> PriceOracle for Box<T> {
+    fn price(&self) -> Result<Price, OracleError> {
+        (**self).price()
+    }
+}
+
+#[derive(Debug)]
+/// Oracle aggregating several sources and returning the median of their prices. Failing
+/// sources are tolerated as long as at least `quorum` of them respond.
+pub struct MedianOracle {
+    sources: Vec<Box<dyn PriceOracle>>,
+    quorum: usize,
+}
+
+impl MedianOracle {
+    /// Creates oracle requiring a majority of `sources` to respond
+    pub fn new(sources: Vec<Box<dyn PriceOracle>>) -> Self {
+        let quorum = sources.len() / 2 + 1;
+        Self { sources, quorum }
+    }
+
+    /// Sets minimal amount of sources that have to respond, clamped to at least one
+    pub fn with_quorum(mut self, quorum: usize) -> Self {
+        self.quorum = quorum.max(1);
+        self
+    }
+
+    pub fn quorum(&self) -> usize {
+        self.quorum
+    }
+}
+
+impl PriceOracle for MedianOracle {
+    fn price(&self) -> Result<Price, OracleError> {
+        let mut prices: Vec<Uint> = self
+            .sources
+            .iter()
+            .filter_map(|source| source.price().ok())
+            .filter(|price| price.raw() != 0)
+            .map(|price| price.raw())
+            .collect();
+        if prices.len() < self.quorum {
+            return Err(OracleError::QuorumNotReached {
+                responses: prices.len(),
+                quorum: self.quorum,
+            });
+        }
+
+        prices.sort_unstable();
+        let middle = prices.len() / 2;
+        let median = match prices.len() % 2 {
+            0 => ((prices[middle - 1] as u128 + prices[middle] as u128) / 2) as Uint,
+            _ => prices[middle],
+        };
+        Ok(Price::from_raw_amount(median))
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    script: VecDeque<Result<Price, OracleError>>,
+    last: Option<Result<Price, OracleError>>,
+    calls: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+/// Deterministic oracle returning scripted responses one by one. Once the script runs out
+/// the last response is repeated. Clones share the script, so responses can be added
+/// after the oracle was handed over to the pool.
+pub struct MockOracle {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockOracle {
+    /// Creates oracle scripted to return given prices in order
+    pub fn new(prices: impl IntoIterator<Item = Price>) -> Self {
+        let oracle = Self::default();
+        for price in prices {
+            oracle.push_price(price);
+        }
+        oracle
+    }
+
+    /// Appends price to the script
+    pub fn push_price(&self, price: Price) {
+        self.push(Ok(price));
+    }
+
+    /// Appends failure to the script, simulating oracle outage
+    pub fn push_failure(&self, error: OracleError) {
+        self.push(Err(error));
+    }
+
+    /// Returns how many times the oracle was queried
+    pub fn calls(&self) -> usize {
+        self.lock().calls
+    }
+
+    fn push(&self, response: Result<Price, OracleError>) {
+        self.lock().script.push_back(response);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        // state is never left inconsistent so a poisoned lock can be reused
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl PriceOracle for MockOracle {
+    fn price(&self) -> Result<Price, OracleError> {
+        let mut state = self.lock();
+        state.calls += 1;
+        if let Some(response) = state.script.pop_front() {
+            state.last = Some(response);
+        }
+        state
+            .last
+            .clone()
+            .unwrap_or_else(|| Err(OracleError::Unavailable("mock oracle has no script".into())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::SwapError;
+    use crate::lp_pool::LpPool;
+
+    #[test]
+    fn fixed_oracle_returns_its_price() {
+        let oracle = FixedOracle::new(1.5.into());
+        assert_eq!(oracle.price().unwrap(), Price::from(1.5));
+    }
+
+    #[test]
+    fn mock_oracle_follows_script() {
+        let oracle = MockOracle::new([1.into(), 2.into()]);
+        oracle.push_failure(OracleError::Unavailable("outage".into()));
+
+        assert_eq!(oracle.price(), Ok(Price::from(1)));
+        assert_eq!(oracle.price(), Ok(Price::from(2)));
+        assert!(oracle.price().is_err());
+        assert!(oracle.price().is_err(), "last response is repeated");
+        assert_eq!(oracle.calls(), 4);
+        assert!(MockOracle::default().price().is_err());
+    }
+
+    #[test]
+    fn pool_survives_oracle_outage() {
+        let oracle = MockOracle::new([1.into()]);
+        let mut pool = LpPool::init(1.into(), 0.01.into(), 0.1.into(), 100.into()).unwrap();
+        pool.set_oracle(oracle.clone());
+        pool.add_liquidity(100.into()).unwrap();
+
+        oracle.push_failure(OracleError::Unavailable("outage".into()));
+        assert!(matches!(
+            pool.swap(10.into()),
+            Err(SwapError::Oracle(OracleError::Unavailable(_)))
+        ));
+        assert_eq!(pool.token_amount(), TokenAmount::from(100));
+
+        oracle.push_price(1.1.into());
+        pool.swap(10.into()).unwrap();
+        assert_eq!(pool.price(), Price::from(1.1));
+    }
+
+    #[test]
+    fn median_oracle_tolerates_failures() {
+        let failing = MockOracle::default();
+        let oracle = MedianOracle::new(vec![
+            Box::new(FixedOracle::new(1.into())),
+            Box::new(FixedOracle::new(3.into())),
+            Box::new(FixedOracle::new(1.2.into())),
+            Box::new(failing.clone()),
+        ]);
+        assert_eq!(oracle.quorum(), 3);
+        assert_eq!(oracle.price(), Ok(Price::from(1.2)));
+
+        failing.push_price(2.into());
+        // median of even amount of prices is the mean of the middle ones
+        assert_eq!(oracle.price(), Ok(Price::from(1.6)));
+    }
+
+    #[test]
+    fn median_oracle_requires_quorum() {
+        let oracle = MedianOracle::new(vec![
+            Box::new(FixedOracle::new(1.into())),
+            Box::new(MockOracle::default()),
+        ])
+        .with_quorum(2);
+
+        assert_eq!(
+            oracle.price(),
+            Err(OracleError::QuorumNotReached {
+                responses: 1,
+                quorum: 2
+            })
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Limit unstake orders matched against pool liquidity. An order swaps staked tokens only
+//! while the pool grants at least its minimum rate, fees and discounts of the owner
+//! included. Orders are matched in placement order whenever they're placed and after
+//! every operation applied through the book; an order the pool can only serve in part is
+//! filled as far as its rate allows and keeps the remainder. Orders can be cancelled by
+//! their owner and expire once the pool clock reaches their expiry.
+
+use crate::error::{OpError, OrderError};
+use crate::events::PoolEvent;
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Unfilled part of a limit unstake order
+pub struct LimitOrder {
+    pub id: u64,
+    pub owner: AccountId,
+    /// staked tokens left to swap
+    pub amount: StakedTokenAmount,
+    /// least tokens granted per staked token
+    pub min_rate: Price,
+    /// epoch from which on the order can't be filled, `None` for orders that don't expire
+    pub expires_at: Option<Epoch>,
+}
+
+impl LimitOrder {
+    fn is_expired(&self, epoch: Epoch) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| epoch >= expires_at)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Swap executed for an order
+pub struct OrderFill {
+    pub id: u64,
+    pub owner: AccountId,
+    pub amount: StakedTokenAmount,
+    pub amount_out: TokenAmount,
+}
+
+#[derive(Debug)]
+/// Pool with a queue of limit unstake orders
+pub struct OrderBook {
+    pool: LpPool,
+    orders: Vec<LimitOrder>,
+    next_id: u64,
+}
+
+impl OrderBook {
+    pub fn new(pool: LpPool) -> Self {
+        Self {
+            pool,
+            orders: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn pool(&self) -> &LpPool {
+        &self.pool
+    }
+
+    /// Returns the wrapped pool, dropping unfilled orders
+    pub fn into_inner(self) -> LpPool {
+        self.pool
+    }
+
+    /// Returns unfilled orders in matching order
+    pub fn orders(&self) -> &[LimitOrder] {
+        &self.orders
+    }
+
+    pub fn order(&self, id: u64) -> Option<&LimitOrder> {
+        self.orders.iter().find(|order| order.id == id)
+    }
+
+    /// Queues the order and matches the book. Returns id of the order together with fills
+    /// of the matching, the order itself may be filled right away.
+    ///
+    /// # Arguments
+    ///
+    /// * `owner` - account swapping staked tokens
+    /// * `amount` - staked tokens to swap
+    /// * `min_rate` - least tokens granted per staked token
+    /// * `expires_at` - epoch from which on the order can't be filled
+    pub fn place(
+        &mut self,
+        owner: AccountId,
+        amount: StakedTokenAmount,
+        min_rate: Price,
+        expires_at: Option<Epoch>,
+    ) -> Result<(u64, Vec<OrderFill>), OrderError> {
+        if amount.raw() == 0 {
+            return Err(OrderError::ZeroAmount);
+        }
+        let epoch = self.pool.epoch();
+        if let Some(expires_at) = expires_at.filter(|&expires_at| epoch >= expires_at) {
+            return Err(OrderError::Expired { expires_at, epoch });
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.orders.push(LimitOrder {
+            id,
+            owner,
+            amount,
+            min_rate,
+            expires_at,
+        });
+        self.pool.emit(PoolEvent::OrderPlaced {
+            id,
+            owner,
+            amount,
+            min_rate,
+        });
+        Ok((id, self.match_orders()))
+    }
+
+    /// Removes unfilled order of the caller
+    pub fn cancel(&mut self, caller: AccountId, id: u64) -> Result<LimitOrder, OrderError> {
+        let index = self
+            .orders
+            .iter()
+            .position(|order| order.id == id)
+            .ok_or(OrderError::UnknownOrder(id))?;
+        if self.orders[index].owner != caller {
+            return Err(OrderError::NotOwner { id, caller });
+        }
+        let order = self.orders.remove(index);
+        self.pool.emit(PoolEvent::OrderCancelled {
+            id,
+            unfilled: order.amount,
+        });
+        Ok(order)
+    }
+
+    /// Applies the operation to the pool and matches the book once it succeeded
+    pub fn apply(&mut self, op: &PoolOp) -> Result<(OpOutcome, Vec<OrderFill>), OpError> {
+        let outcome = self.pool.apply(op)?;
+        Ok((outcome, self.match_orders()))
+    }
+
+    /// Drops expired orders and fills the remaining ones in placement order as far as the
+    /// pool grants their rate at the price refreshed from the oracle. Nothing is filled
+    /// while the price can't be refreshed.
+    pub fn match_orders(&mut self) -> Vec<OrderFill> {
+        let epoch = self.pool.epoch();
+        let (expired, orders) = std::mem::take(&mut self.orders)
+            .into_iter()
+            .partition::<Vec<_>, _>(|order| order.is_expired(epoch));
+        self.orders = orders;
+        for order in expired {
+            self.pool.emit(PoolEvent::OrderExpired {
+                id: order.id,
+                unfilled: order.amount,
+            });
+        }
+        if self.pool.refresh_price().is_err() {
+            return Vec::new();
+        }
+
+        let mut fills = Vec::new();
+        let mut index = 0;
+        while index < self.orders.len() {
+            let order = self.orders[index];
+            let fill = self.fillable(&order).and_then(|amount| {
+                match self.pool.swap_for(order.owner, amount) {
+                    Ok(outcome) => Some(OrderFill {
+                        id: order.id,
+                        owner: order.owner,
+                        amount,
+                        amount_out: outcome.amount_out,
+                    }),
+                    Err(_) => None,
+                }
+            });
+            let Some(fill) = fill else {
+                index += 1;
+                continue;
+            };
+            self.pool.emit(PoolEvent::OrderFilled {
+                id: fill.id,
+                amount: fill.amount,
+                amount_out: fill.amount_out,
+            });
+            fills.push(fill);
+            let remaining = order.amount - fill.amount;
+            match remaining.raw() {
+                0 => {
+                    self.orders.remove(index);
+                }
+                _ => {
+                    self.orders[index].amount = remaining;
+                    index += 1;
+                }
+            }
+        }
+        fills
+    }
+
+    /// Returns largest part of the order the pool fills at its rate. Larger swaps pay
+    /// higher fees, so the rate only falls with the amount.
+    fn fillable(&self, order: &LimitOrder) -> Option<StakedTokenAmount> {
+        let fills = |amount: Uint| {
+            let amount = StakedTokenAmount::from_raw_amount(amount);
+            self.pool
+                .quote_swap_for(order.owner, amount)
+                .is_ok_and(|outcome| {
+                    outcome.amount_out.raw() as u128 * SCALE as u128
+                        >= order.min_rate.raw() as u128 * amount.raw() as u128
+                })
+        };
+        let (mut low, mut high) = (0, order.amount.raw());
+        if fills(high) {
+            return Some(order.amount);
+        }
+        // `low` always fills, apart from the zero amount it starts with
+        while high - low > 1 {
+            let middle = low + (high - low) / 2;
+            match fills(middle) {
+                true => low = middle,
+                false => high = middle,
+            }
+        }
+        (low > 0).then(|| StakedTokenAmount::from_raw_amount(low))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: AccountId = 1;
+    const BOB: AccountId = 2;
+
+    fn book() -> OrderBook {
+        let mut pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        pool.add_liquidity(100.into()).unwrap();
+        OrderBook::new(pool)
+    }
+
+    #[test]
+    fn fills_orders_as_rate_allows() {
+        let mut book = book();
+        let (id, fills) = book.place(ALICE, 6.into(), 1.49.into(), None).unwrap();
+        assert_eq!(
+            fills,
+            [OrderFill {
+                id,
+                owner: ALICE,
+                amount: 6.into(),
+                amount_out: 8.991.into(),
+            }]
+        );
+        assert!(book.orders().is_empty());
+
+        // draining the pool raises the fee, so only part of the order fills
+        let (id, fills) = book.place(BOB, 60.into(), 1.4.into(), None).unwrap();
+        let [fill] = fills[..] else {
+            panic!("expected single fill, got {fills:?}");
+        };
+        assert!(fill.amount.raw() > 0 && fill.amount < 60.into());
+        assert!(
+            fill.amount_out.raw() as u128 * SCALE as u128 >= 1_400_000 * fill.amount.raw() as u128
+        );
+        let remaining = *book.order(id).unwrap();
+        assert_eq!(remaining.amount, StakedTokenAmount::from(60) - fill.amount);
+
+        // fresh liquidity lowers the fee again and the remainder is matched
+        let (_, fills) = book
+            .apply(&PoolOp::AddLiquidity {
+                account: None,
+                amount: 1_000.into(),
+            })
+            .unwrap();
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].amount, remaining.amount);
+        assert!(book.orders().is_empty());
+    }
+
+    #[test]
+    fn cancels_and_expires_orders() {
+        let mut book = book();
+        let (first, _) = book.place(ALICE, 6.into(), 2.into(), Some(2)).unwrap();
+        let (second, _) = book.place(ALICE, 6.into(), 2.into(), None).unwrap();
+        assert!(matches!(
+            book.cancel(BOB, second),
+            Err(OrderError::NotOwner { .. })
+        ));
+        assert_eq!(book.cancel(ALICE, second).unwrap().amount, 6.into());
+        assert!(matches!(
+            book.cancel(ALICE, second),
+            Err(OrderError::UnknownOrder(_))
+        ));
+
+        book.apply(&PoolOp::AdvanceEpoch { epochs: 2 }).unwrap();
+        assert!(book.order(first).is_none());
+        assert!(matches!(
+            book.place(ALICE, 6.into(), 2.into(), Some(2)),
+            Err(OrderError::Expired { .. })
+        ));
+        assert!(matches!(
+            book.place(ALICE, 0.into(), 2.into(), None),
+            Err(OrderError::ZeroAmount)
+        ));
+        assert!(book.pool.drain_events().contains(&PoolEvent::OrderExpired {
+            id: first,
+            unfilled: 6.into(),
+        }));
+    }
+}
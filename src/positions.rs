@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+
+use crate::error::SchemaError;
+use crate::json::Value;
+use crate::schema::{field, FromJson, ToJson};
+use crate::types::*;
+
+/// extra precision of the fee growth accumulator, keeps dust fees from being lost
+const FEE_GROWTH_PRECISION: u128 = SCALE as u128 * SCALE as u128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Liquidity position of a single account
+pub struct Position {
+    /// lp tokens currently held
+    pub lp_tokens: LpTokenAmount,
+    /// tokens deposited for currently held lp tokens
+    pub cost_basis: TokenAmount,
+    /// profit or loss realized by previous withdrawals
+    pub realized_pnl: SignedTokenAmount,
+    /// LP fees accrued by the position up to the last checkpoint
+    fees_earned: TokenAmount,
+    /// value of fee growth accumulator when fees were last accrued
+    fee_growth_checkpoint: u128,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Profit and loss report of a single position
+pub struct PositionPnl {
+    /// LP fees earned by the position since it was opened
+    pub fees_earned: TokenAmount,
+    /// value of currently held lp tokens
+    pub current_value: TokenAmount,
+    /// value the deposited tokens would have if they were held instead
+    pub hold_value: TokenAmount,
+    /// `current_value - hold_value`, negative values mean impermanent loss
+    pub value_vs_hold: SignedTokenAmount,
+    pub realized_pnl: SignedTokenAmount,
+    pub unrealized_pnl: SignedTokenAmount,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// Ledger tracking liquidity positions of accounts together with LP fee growth
+pub struct Positions {
+    accounts: BTreeMap<AccountId, Position>,
+    /// LP fees earned by one raw lp token unit, scaled by `FEE_GROWTH_PRECISION`
+    fee_growth: u128,
+}
+
+impl Positions {
+    pub fn get(&self, account: AccountId) -> Option<&Position> {
+        self.accounts.get(&account)
+    }
+
+    /// Returns positions ordered by account id
+    pub fn iter(&self) -> impl Iterator<Item = (&AccountId, &Position)> {
+        self.accounts.iter()
+    }
+
+    /// Distributes LP fees between all lp tokens in circulation
+    pub fn record_lp_fees(&mut self, lp_fees: TokenAmount, lp_supply: LpTokenAmount) {
+        if lp_supply.raw() == 0 {
+            return;
+        }
+        self.fee_growth = self
+            .fee_growth
+            .saturating_add(lp_fees.raw() as u128 * FEE_GROWTH_PRECISION / lp_supply.raw() as u128);
+    }
+
+    /// Records deposit of tokens that minted lp tokens for the account
+    pub fn deposit(
+        &mut self,
+        account: AccountId,
+        tokens_in: TokenAmount,
+        lp_minted: LpTokenAmount,
+    ) {
+        let fee_growth = self.fee_growth;
+        let position = self.accounts.entry(account).or_insert(Position {
+            fee_growth_checkpoint: fee_growth,
+            ..Default::default()
+        });
+        position.accrue_fees(fee_growth);
+        // positions can only outgrow the lp supply when withdrawals without an account burn
+        // tokens attributed to positions, so the ledger saturates instead of rejecting
+        position.lp_tokens = position.lp_tokens.saturating_add(lp_minted);
+        position.cost_basis = position.cost_basis.saturating_add(tokens_in);
+    }
+
+    /// Records withdrawal of lp tokens worth `value_out` tokens
+    pub fn withdraw(
+        &mut self,
+        account: AccountId,
+        lp_burned: LpTokenAmount,
+        value_out: TokenAmount,
+    ) {
+        let fee_growth = self.fee_growth;
+        let Some(position) = self.accounts.get_mut(&account) else {
+            return;
+        };
+        position.accrue_fees(fee_growth);
+
+        let basis_out = TokenAmount::from_raw_amount(
+            (position.cost_basis.raw() as u128 * lp_burned.raw() as u128
+                / position.lp_tokens.raw().max(1) as u128) as Uint,
+        );
+        position.realized_pnl =
+            position.realized_pnl + SignedTokenAmount::difference(value_out, basis_out);
+        position.cost_basis = position.cost_basis - basis_out;
+        position.lp_tokens = position.lp_tokens - lp_burned;
+    }
+
+    /// Returns profit and loss report of the account's position
+    ///
+    /// # Arguments
+    ///
+    /// * `current_value` - current value of lp tokens held by the position
+    pub fn pnl(&self, account: AccountId, current_value: TokenAmount) -> Option<PositionPnl> {
+        let position = self.accounts.get(&account)?;
+        let unrealized_pnl = SignedTokenAmount::difference(current_value, position.cost_basis);
+
+        Some(PositionPnl {
+            fees_earned: position
+                .fees_earned
+                .saturating_add(position.pending_fees(self.fee_growth)),
+            current_value,
+            hold_value: position.cost_basis,
+            value_vs_hold: unrealized_pnl,
+            realized_pnl: position.realized_pnl,
+            unrealized_pnl,
+        })
+    }
+}
+
+impl Position {
+    /// Returns fees accrued since the checkpoint, fee statistics saturate at `Uint::MAX`
+    fn pending_fees(&self, fee_growth: u128) -> TokenAmount {
+        let growth = fee_growth - self.fee_growth_checkpoint;
+        let fees = (self.lp_tokens.raw() as u128).saturating_mul(growth) / FEE_GROWTH_PRECISION;
+        TokenAmount::from_raw_amount(fees.min(Uint::MAX as u128) as Uint)
+    }
+
+    fn accrue_fees(&mut self, fee_growth: u128) {
+        self.fees_earned = self
+            .fees_earned
+            .saturating_add(self.pending_fees(fee_growth));
+        self.fee_growth_checkpoint = fee_growth;
+    }
+}
+
+impl ToJson for Position {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("lp_tokens", self.lp_tokens.to_json()),
+            ("cost_basis", self.cost_basis.to_json()),
+            ("realized_pnl", self.realized_pnl.to_json()),
+            ("fees_earned", self.fees_earned.to_json()),
+            (
+                "fee_growth_checkpoint",
+                self.fee_growth_checkpoint.to_json(),
+            ),
+        ])
+    }
+}
+
+impl FromJson for Position {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        Ok(Self {
+            lp_tokens: field(value, "lp_tokens")?,
+            cost_basis: field(value, "cost_basis")?,
+            realized_pnl: field(value, "realized_pnl")?,
+            fees_earned: field(value, "fees_earned")?,
+            fee_growth_checkpoint: field(value, "fee_growth_checkpoint")?,
+        })
+    }
+}
+
+impl ToJson for Positions {
+    fn to_json(&self) -> Value {
+        let accounts = self
+            .accounts
+            .iter()
+            .map(|(account, position)| {
+                Value::object([
+                    ("account", account.to_json()),
+                    ("position", position.to_json()),
+                ])
+            })
+            .collect();
+        Value::object([
+            ("fee_growth", self.fee_growth.to_json()),
+            ("accounts", Value::Array(accounts)),
+        ])
+    }
+}
+
+impl FromJson for Positions {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        let accounts = field::<Vec<Value>>(value, "accounts")?
+            .iter()
+            .map(|entry| Ok((field(entry, "account")?, field(entry, "position")?)))
+            .collect::<Result<_, SchemaError>>()
+            .map_err(|error| error.in_field("accounts"))?;
+        Ok(Self {
+            accounts,
+            fee_growth: field(value, "fee_growth")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: AccountId = 1;
+    const BOB: AccountId = 2;
+
+    #[test]
+    fn distributes_fees_proportionally() {
+        let mut positions = Positions::default();
+        positions.deposit(ALICE, 30.into(), 30.into());
+        positions.deposit(BOB, 10.into(), 10.into());
+        positions.record_lp_fees(4.into(), 40.into());
+
+        let alice = positions.pnl(ALICE, 33.into()).unwrap();
+        let bob = positions.pnl(BOB, 11.into()).unwrap();
+        assert_eq!(alice.fees_earned, TokenAmount::from(3));
+        assert_eq!(bob.fees_earned, TokenAmount::from(1));
+    }
+
+    #[test]
+    fn late_depositor_doesnt_earn_past_fees() {
+        let mut positions = Positions::default();
+        positions.deposit(ALICE, 10.into(), 10.into());
+        positions.record_lp_fees(1.into(), 10.into());
+        positions.deposit(BOB, 10.into(), 10.into());
+
+        assert_eq!(
+            positions.pnl(BOB, 10.into()).unwrap().fees_earned,
+            TokenAmount::from(0)
+        );
+    }
+
+    #[test]
+    fn withdrawal_realizes_pnl() {
+        let mut positions = Positions::default();
+        positions.deposit(ALICE, 10.into(), 10.into());
+        positions.withdraw(ALICE, 5.into(), 4.into());
+
+        let pnl = positions.pnl(ALICE, 4.into()).unwrap();
+        assert_eq!(
+            pnl.realized_pnl,
+            SignedTokenAmount::difference(4.into(), 5.into())
+        );
+        assert_eq!(pnl.hold_value, TokenAmount::from(5));
+        assert_eq!(
+            pnl.unrealized_pnl,
+            SignedTokenAmount::difference(4.into(), 5.into())
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::VecDeque;
+
+use crate::error::SchemaError;
+use crate::json::Value;
+use crate::schema::{field, FromJson, ToJson};
+use crate::types::*;
+
+/// default amount of prices kept by the pool
+pub const DEFAULT_PRICE_HISTORY_CAPACITY: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Price accepted by the pool together with the epoch it was accepted at
+pub struct PricePoint {
+    pub epoch: Epoch,
+    pub price: Price,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Fixed capacity ring buffer holding the most recent prices accepted by the pool.
+/// When the buffer is full the oldest entry is overwritten.
+pub struct PriceHistory {
+    entries: VecDeque<PricePoint>,
+    capacity: usize,
+}
+
+impl PriceHistory {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Appends price accepted at `epoch` to the buffer, evicting the oldest entry if the
+    /// buffer is full
+    pub fn push(&mut self, epoch: Epoch, price: Price) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(PricePoint { epoch, price });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns prices from the oldest to the newest
+    pub fn iter(&self) -> impl Iterator<Item = &PricePoint> {
+        self.entries.iter()
+    }
+
+    /// Returns the most recently accepted price
+    pub fn latest(&self) -> Option<Price> {
+        self.entries.back().map(|point| point.price)
+    }
+
+    /// Returns prices active during `window` epochs ending with `current_epoch` (inclusive):
+    /// the price active when the window started followed by prices accepted inside it
+    pub fn in_window(
+        &self,
+        current_epoch: Epoch,
+        window: Epoch,
+    ) -> impl Iterator<Item = &PricePoint> + Clone {
+        let first_epoch = current_epoch.saturating_add(1).saturating_sub(window);
+        let skip = self
+            .entries
+            .iter()
+            .rposition(|point| point.epoch < first_epoch)
+            .unwrap_or(0);
+        self.entries
+            .iter()
+            .skip(skip)
+            .filter(move |point| point.epoch <= current_epoch)
+    }
+
+    /// Returns lowest and highest price active during `window` epochs ending with
+    /// `current_epoch`, `None` if the buffer is empty
+    pub fn min_max(&self, current_epoch: Epoch, window: Epoch) -> Option<(Price, Price)> {
+        self.in_window(current_epoch, window)
+            .fold(None, |min_max, point| match min_max {
+                None => Some((point.price, point.price)),
+                Some((min, max)) => Some((
+                    Price::from_raw_amount(min.raw().min(point.price.raw())),
+                    Price::from_raw_amount(max.raw().max(point.price.raw())),
+                )),
+            })
+    }
+
+    /// Returns volatility of all buffered prices, see `volatility_in_window`
+    pub fn volatility(&self) -> Percentage {
+        Self::mean_change(self.entries.iter())
+    }
+
+    /// Returns volatility of prices active during `window` epochs ending with
+    /// `current_epoch` as mean absolute relative change between consecutive updates.
+    /// Less than two prices have zero volatility.
+    pub fn volatility_in_window(&self, current_epoch: Epoch, window: Epoch) -> Percentage {
+        Self::mean_change(self.in_window(current_epoch, window))
+    }
+
+    fn mean_change<'a>(points: impl Iterator<Item = &'a PricePoint> + Clone) -> Percentage {
+        // u128 is used so that big prices can't overflow after scaling
+        let (total_change, changes) = points
+            .clone()
+            .zip(points.skip(1))
+            .map(|(previous, next)| {
+                let change = previous.price.raw().abs_diff(next.price.raw()) as u128;
+                change * SCALE as u128 / (previous.price.raw() as u128).max(1)
+            })
+            .fold((0u128, 0u128), |(total, count), change| {
+                (total + change, count + 1)
+            });
+        if changes == 0 {
+            return Percentage::from_raw_amount(0);
+        }
+        let mean_change = total_change / changes;
+
+        Percentage::from_raw_amount(mean_change.min(Uint::MAX as u128) as Uint)
+    }
+}
+
+impl Default for PriceHistory {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_PRICE_HISTORY_CAPACITY)
+    }
+}
+
+impl ToJson for PricePoint {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("epoch", self.epoch.to_json()),
+            ("price", self.price.to_json()),
+        ])
+    }
+}
+
+impl FromJson for PricePoint {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        Ok(Self {
+            epoch: field(value, "epoch")?,
+            price: field(value, "price")?,
+        })
+    }
+}
+
+impl ToJson for PriceHistory {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("capacity", self.capacity.to_json()),
+            (
+                "entries",
+                self.entries.iter().copied().collect::<Vec<_>>().to_json(),
+            ),
+        ])
+    }
+}
+
+impl FromJson for PriceHistory {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        let mut history = Self::with_capacity(field(value, "capacity")?);
+        for point in field::<Vec<PricePoint>>(value, "entries")? {
+            history.push(point.epoch, point.price);
+        }
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_oldest_price_when_full() {
+        let mut history = PriceHistory::with_capacity(2);
+        history.push(0, 1.into());
+        history.push(0, 2.into());
+        history.push(0, 3.into());
+
+        let prices: Vec<Price> = history.iter().map(|point| point.price).collect();
+        assert_eq!(prices, vec![Price::from(2), Price::from(3)]);
+    }
+
+    #[test]
+    fn constant_prices_have_no_volatility() {
+        let mut history = PriceHistory::default();
+        history.push(0, 1.5.into());
+        history.push(0, 1.5.into());
+        history.push(0, 1.5.into());
+
+        assert_eq!(history.volatility(), Percentage::from_raw_amount(0));
+    }
+
+    #[test]
+    fn can_calculate_volatility() {
+        let mut history = PriceHistory::default();
+        // +10% followed by -10%
+        history.push(0, 1.into());
+        history.push(0, 1.1.into());
+        history.push(0, 0.99.into());
+
+        assert_eq!(history.volatility(), Percentage::from(0.1));
+    }
+
+    #[test]
+    fn can_query_window() {
+        let mut history = PriceHistory::default();
+        history.push(0, 1.into());
+        history.push(2, 1.2.into());
+        history.push(4, 0.9.into());
+        history.push(5, 1.1.into());
+
+        // price 1.2 was still active at the start of the window
+        assert_eq!(
+            history.min_max(5, 2),
+            Some((Price::from(0.9), Price::from(1.2)))
+        );
+        assert_eq!(
+            history.min_max(5, 1),
+            Some((Price::from(0.9), Price::from(1.1)))
+        );
+        assert_eq!(
+            history.min_max(1, 1),
+            Some((Price::from(1), Price::from(1)))
+        );
+        assert_eq!(history.volatility_in_window(3, 2), Percentage::from(0.2));
+    }
+}
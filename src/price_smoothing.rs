@@ -0,0 +1,98 @@
+use crate::error::SchemaError;
+use crate::json::Value;
+use crate::schema::{field, FromJson, ToJson};
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Smoothing applied to oracle prices before they're used in swap math
+pub enum PriceSmoothing {
+    /// oracle prices are used as is
+    #[default]
+    Raw,
+    /// exponential moving average of oracle prices, every refresh moves the pool price
+    /// by `alpha` of the distance to the oracle price
+    Ema {
+        /// weight of the newest oracle price, `1.0` disables smoothing
+        alpha: Percentage,
+    },
+}
+
+impl PriceSmoothing {
+    /// Returns price that should be used by the pool given the previously used price
+    /// and the newest oracle price
+    pub fn apply(&self, previous: Price, oracle_price: Price) -> Price {
+        match self {
+            PriceSmoothing::Raw => oracle_price,
+            PriceSmoothing::Ema { alpha } => {
+                let distance = previous.raw().abs_diff(oracle_price.raw());
+                let step =
+                    (distance as u128 * alpha.raw().min(SCALE) as u128 / SCALE as u128) as Uint;
+                // moving by at least one unit makes sure the price eventually converges
+                let step = match alpha.raw() > 0 {
+                    true => step.max(distance.min(1)),
+                    false => step,
+                };
+                match oracle_price > previous {
+                    true => Price::from_raw_amount(previous.raw() + step),
+                    false => Price::from_raw_amount(previous.raw() - step),
+                }
+            }
+        }
+    }
+}
+
+impl ToJson for PriceSmoothing {
+    fn to_json(&self) -> Value {
+        match self {
+            PriceSmoothing::Raw => Value::object([("type", "raw".into())]),
+            PriceSmoothing::Ema { alpha } => {
+                Value::object([("type", "ema".into()), ("alpha", alpha.to_json())])
+            }
+        }
+    }
+}
+
+impl FromJson for PriceSmoothing {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        match field::<String>(value, "type")?.as_str() {
+            "raw" => Ok(PriceSmoothing::Raw),
+            "ema" => Ok(PriceSmoothing::Ema {
+                alpha: field(value, "alpha")?,
+            }),
+            other => Err(SchemaError::InvalidValue {
+                path: "type".into(),
+                reason: format!("unknown variant `{other}`"),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_smoothing_returns_oracle_price() {
+        let price = PriceSmoothing::Raw.apply(1.into(), 2.into());
+        assert_eq!(price, Price::from(2));
+    }
+
+    #[test]
+    fn ema_moves_towards_oracle_price() {
+        let smoothing = PriceSmoothing::Ema { alpha: 0.25.into() };
+
+        assert_eq!(smoothing.apply(1.into(), 2.into()), Price::from(1.25));
+        assert_eq!(smoothing.apply(2.into(), 1.into()), Price::from(1.75));
+    }
+
+    #[test]
+    fn ema_converges() {
+        let smoothing = PriceSmoothing::Ema { alpha: 0.5.into() };
+        let mut price = Price::from(1);
+        for _ in 0..64 {
+            price = smoothing.apply(price, 2.into());
+        }
+
+        assert_eq!(price, Price::from(2));
+    }
+}
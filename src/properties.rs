@@ -0,0 +1,277 @@
+//! Property checks of global pool invariants over randomly generated operation
+//! sequences. Sequences are generated from a seed, so every failure is reproducible, and
+//! failing sequences are shrunk to a minimal set of operations before they are reported.
+//! The checks are public, so integration tests and fuzz targets can run them on their
+//! own configurations and inputs.
+//!
+//! Checked properties:
+//!
+//! * value is conserved by every operation, see `check_step`
+//! * swaps never decrease value held by lp tokens
+//! * removing liquidity right after adding it, with only swaps in between, never returns
+//!   more than the deposit plus LP fees earned in the meantime
+
+use crate::conservation::{check_step, Balances};
+use crate::error::{OpError, PropertyError, PropertyFailure, RemoveLiquidityError};
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::replay::PoolConfig;
+use crate::rng::Rng;
+use crate::shrinking::{raw_code, shrink, unit_test};
+use crate::types::*;
+
+/// number of accounts liquidity operations are attributed to
+const ACCOUNTS: u64 = 4;
+
+/// Generates random operation sequence. Amounts span from dust to amounts bigger than
+/// usual pools, so sequences exercise both rounding and insufficient liquidity paths.
+pub fn random_ops(rng: &mut Rng, len: usize) -> Vec<PoolOp> {
+    (0..len)
+        .map(|_| match rng.below(10) {
+            0..=2 => PoolOp::AddLiquidity {
+                account: random_account(rng),
+                amount: TokenAmount::from_raw_amount(random_amount(rng)),
+            },
+            3..=4 => PoolOp::RemoveLiquidity {
+                account: random_account(rng),
+                lp_amount: LpTokenAmount::from_raw_amount(random_amount(rng)),
+            },
+            5..=7 => PoolOp::Swap {
+                account: None,
+                amount: StakedTokenAmount::from_raw_amount(random_amount(rng)),
+            },
+            8 => PoolOp::SetPrice {
+                price: Price::from_raw_amount(rng.range(SCALE / 2, 3 * SCALE)),
+            },
+            _ => PoolOp::AdvanceEpoch {
+                epochs: rng.range(0, 3),
+            },
+        })
+        .collect()
+}
+
+fn random_account(rng: &mut Rng) -> Option<AccountId> {
+    rng.chance(0.5).then(|| rng.below(ACCOUNTS))
+}
+
+/// Returns raw amount of random magnitude, up to a million whole tokens
+fn random_amount(rng: &mut Rng) -> Uint {
+    let magnitude = 10u64.pow(rng.range(0, 12) as u32);
+    rng.range(0, magnitude)
+}
+
+/// Applies operations to the pool, checking conservation of every step and that swaps
+/// never decrease value of lp tokens
+pub fn check_invariants(pool: &mut LpPool, ops: &[PoolOp]) -> Result<(), PropertyError> {
+    for (step, op) in ops.iter().enumerate() {
+        let before = pool.balances();
+        let outcome = pool.apply(op);
+        let after = pool.balances();
+        check_step(step, &before, op, &outcome, &after)?;
+
+        if let (PoolOp::Swap { .. }, Ok(_)) = (op, &outcome) {
+            // swaps which refreshed the price are compared at the new price
+            let before = Balances {
+                price: after.price,
+                ..before
+            };
+            if after.total_value() < before.total_value() {
+                return Err(PropertyError::LpValueDecreased { step });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds liquidity to a copy of the pool, applies swaps and removes all minted lp tokens.
+/// Checks that the withdrawn value doesn't exceed deposit plus LP fees earned by the
+/// swaps, the pool itself is left untouched.
+pub fn check_round_trip(
+    pool: &LpPool,
+    amount: TokenAmount,
+    swaps: &[StakedTokenAmount],
+) -> Result<(), PropertyError> {
+    let mut pool =
+        LpPool::from_snapshot(&pool.to_snapshot()).expect("snapshot of a live pool restores");
+    let Ok(OpOutcome::LiquidityAdded(minted)) = pool.apply(&PoolOp::AddLiquidity {
+        account: None,
+        amount,
+    }) else {
+        return Ok(());
+    };
+
+    let lp_fees_before = pool.fee_revenue().lp;
+    for amount in swaps {
+        // failed swaps don't change the pool
+        let _ = pool.apply(&PoolOp::Swap {
+            account: None,
+            amount: *amount,
+        });
+    }
+    let fees = pool.fee_revenue().lp - lp_fees_before;
+    let price = pool.price();
+
+    let (tokens, staked_tokens) = match pool.apply(&PoolOp::RemoveLiquidity {
+        account: None,
+        lp_amount: minted,
+    }) {
+        Ok(OpOutcome::LiquidityRemoved(tokens, staked_tokens)) => (tokens, staked_tokens),
+        // withdrawals from big pools overflow intermediate products, which is reported
+        // to the caller instead of paying out a wrong amount
+        Err(OpError::RemoveLiquidity(RemoveLiquidityError::WithdrawCalculationOverflow)) => {
+            return Ok(())
+        }
+        _ => return Err(PropertyError::RoundTripFailed),
+    };
+    let withdrawn =
+        tokens.raw() as u128 + staked_tokens.raw() as u128 * price.raw() as u128 / SCALE as u128;
+    // fees are accounted per swap on floored values while withdrawn staked tokens are
+    // valued at once, which can add one raw token of valuation rounding per swap
+    let rounding = swaps.len() as u128;
+    if withdrawn > amount.raw() as u128 + fees.raw() as u128 + rounding {
+        return Err(PropertyError::RoundTripProfit {
+            deposited: amount,
+            fees,
+            withdrawn: TokenAmount::from_raw_amount(withdrawn.min(Uint::MAX as u128) as Uint),
+        });
+    }
+    Ok(())
+}
+
+/// Checks every property on `cases` random sequences of up to `max_len` operations
+/// applied to pools built from the configuration. Case `n` is generated from seed
+/// `seed + n`, the returned failure holds the shrunk sequence.
+pub fn check_properties(
+    config: &PoolConfig,
+    seed: u64,
+    cases: u64,
+    max_len: usize,
+) -> Result<(), Box<PropertyFailure>> {
+    for case in 0..cases {
+        let case_seed = seed.wrapping_add(case);
+        let mut rng = Rng::new(case_seed);
+        let len = rng.range(1, max_len as u64) as usize;
+        let ops = random_ops(&mut rng, len);
+        let round_trip = (
+            TokenAmount::from_raw_amount(random_amount(&mut rng)),
+            (0..rng.range(0, 8))
+                .map(|_| StakedTokenAmount::from_raw_amount(random_amount(&mut rng)))
+                .collect::<Vec<_>>(),
+        );
+
+        let (deposit, swaps) = round_trip;
+        let check = |ops: &[PoolOp]| {
+            let mut pool = config.build();
+            check_invariants(&mut pool, ops)?;
+            check_round_trip(&pool, deposit, &swaps)
+        };
+        if check(&ops).is_err() {
+            let ops = shrink(ops, |ops| check(ops).is_err());
+            let error = check(&ops).expect_err("shrunk sequence still fails");
+            return Err(Box::new(PropertyFailure {
+                seed: case_seed,
+                ops,
+                deposit,
+                swaps,
+                error,
+            }));
+        }
+    }
+    Ok(())
+}
+
+impl PropertyFailure {
+    /// Prints the failing case as a unit test checking every property on pools built
+    /// from `config`, which has to be the configuration the case was generated for
+    pub fn unit_test(&self, config: &PoolConfig) -> String {
+        let swaps: Vec<_> = self
+            .swaps
+            .iter()
+            .map(|amount| raw_code("StakedTokenAmount", amount.raw()))
+            .collect();
+        unit_test(
+            &format!("property_case_{}", self.seed),
+            config,
+            &self.ops,
+            &[
+                "check_invariants(&mut pool, &ops).unwrap();".to_string(),
+                format!(
+                    "check_round_trip(&pool, {}, &[{}]).unwrap();",
+                    raw_code("TokenAmount", self.deposit.raw()),
+                    swaps.join(", ")
+                ),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::governance::PoolParams;
+    use crate::schema::ToJson;
+
+    fn config() -> PoolConfig {
+        let mut config = PoolConfig::new(
+            1.5.into(),
+            PoolParams {
+                min_fee: 0.001.into(),
+                max_fee: 0.09.into(),
+                liquidity_target: 90.into(),
+            },
+        );
+        config.treasury_cut = 0.25.into();
+        config
+    }
+
+    #[test]
+    fn random_sequences_keep_invariants() {
+        check_properties(&config(), 0, 300, 40).unwrap();
+
+        let mut config = config();
+        config.params.liquidity_target = 1_000_000.into();
+        config.treasury_cut = 0.0.into();
+        check_properties(&config, 1_000, 100, 80).unwrap();
+    }
+
+    #[test]
+    fn round_trip_is_never_profitable() {
+        let mut pool = config().build();
+        pool.add_liquidity(100.into()).unwrap();
+        pool.swap(30.into()).unwrap();
+        let state = pool.to_json();
+        check_round_trip(&pool, 50.into(), &[10.into(), 5.into(), 1_000.into()]).unwrap();
+        // the pool itself stays untouched
+        assert_eq!(pool.to_json(), state);
+    }
+
+    #[test]
+    fn prints_failure_as_unit_test() {
+        let failure = PropertyFailure {
+            seed: 12,
+            ops: vec![PoolOp::Swap {
+                account: None,
+                amount: 1.into(),
+            }],
+            deposit: 5.into(),
+            swaps: vec![2.into(), 3.into()],
+            error: PropertyError::RoundTripFailed,
+        };
+        let test = failure.unit_test(&config());
+        assert!(test.starts_with("#[test]\nfn property_case_12() {\n    let mut config"));
+        assert!(test.contains("config.treasury_cut = Percentage::from_raw_amount(250000);"));
+        assert!(test.contains("check_round_trip(&pool, TokenAmount::from_raw_amount(5000000), &[StakedTokenAmount::from_raw_amount(2000000), StakedTokenAmount::from_raw_amount(3000000)]).unwrap();"));
+    }
+
+    #[test]
+    fn shrinks_failing_sequence() {
+        let mut rng = Rng::new(7);
+        let ops = random_ops(&mut rng, 50);
+        let is_swap = |op: &PoolOp| matches!(op, PoolOp::Swap { .. });
+        let shrunk = shrink(ops.clone(), |ops| {
+            ops.iter().filter(|op| is_swap(op)).count() >= 2
+        });
+        assert_eq!(shrunk.len(), 2);
+        assert!(shrunk.iter().all(is_swap));
+    }
+}
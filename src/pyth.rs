@@ -0,0 +1,235 @@
+use std::fmt::Debug;
+use std::sync::Mutex;
+
+use crate::error::OracleError;
+use crate::oracle::{unix_now, PriceOracle};
+use crate::types::*;
+
+/// magic number starting every Pyth account
+const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+/// account type of Pyth price accounts
+const PRICE_ACCOUNT_TYPE: u32 = 3;
+/// aggregate status of a price that is currently trading
+const STATUS_TRADING: u32 = 1;
+
+// offsets inside the Pyth v2 price account
+const MAGIC_OFFSET: usize = 0;
+const ACCOUNT_TYPE_OFFSET: usize = 8;
+const EXPONENT_OFFSET: usize = 20;
+const TIMESTAMP_OFFSET: usize = 96;
+const AGG_PRICE_OFFSET: usize = 208;
+const AGG_CONF_OFFSET: usize = 216;
+const AGG_STATUS_OFFSET: usize = 224;
+/// minimal length of the account holding all fields read by the parser
+const MIN_ACCOUNT_LEN: usize = 240;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Aggregate price read from a Pyth price account
+pub struct PythPrice {
+    pub price: i64,
+    pub confidence: u64,
+    pub exponent: i32,
+    pub status: u32,
+    /// unix timestamp of the aggregate price
+    pub publish_time: i64,
+}
+
+impl PythPrice {
+    /// Parses aggregate price out of raw Pyth v2 price account data
+    pub fn parse(data: &[u8]) -> Result<Self, OracleError> {
+        if data.len() < MIN_ACCOUNT_LEN {
+            return Err(invalid("account data too short"));
+        }
+        if read_u32(data, MAGIC_OFFSET) != PYTH_MAGIC {
+            return Err(invalid("not a Pyth account"));
+        }
+        if read_u32(data, ACCOUNT_TYPE_OFFSET) != PRICE_ACCOUNT_TYPE {
+            return Err(invalid("not a Pyth price account"));
+        }
+
+        Ok(Self {
+            price: read_u64(data, AGG_PRICE_OFFSET) as i64,
+            confidence: read_u64(data, AGG_CONF_OFFSET),
+            exponent: read_u32(data, EXPONENT_OFFSET) as i32,
+            status: read_u32(data, AGG_STATUS_OFFSET),
+            publish_time: read_u64(data, TIMESTAMP_OFFSET) as i64,
+        })
+    }
+
+    /// Converts the price into the crate's `Price` after checking its status, age and
+    /// confidence interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - staleness and confidence limits
+    /// * `now` - current unix timestamp
+    pub fn to_price(&self, config: &PythConfig, now: i64) -> Result<Price, OracleError> {
+        if self.status != STATUS_TRADING {
+            return Err(invalid("price is not trading"));
+        }
+        if self.price <= 0 {
+            return Err(OracleError::ZeroPrice);
+        }
+
+        let age = now.saturating_sub(self.publish_time).max(0) as u64;
+        if age > config.max_age_secs {
+            return Err(OracleError::StalePrice {
+                age,
+                max_age: config.max_age_secs,
+            });
+        }
+
+        let confidence = Percentage::from_raw_amount(
+            (self.confidence as u128 * SCALE as u128 / self.price as u128).min(Uint::MAX as u128)
+                as Uint,
+        );
+        if confidence > config.max_confidence {
+            return Err(OracleError::ConfidenceTooWide {
+                confidence,
+                max_confidence: config.max_confidence,
+            });
+        }
+
+        Price::from_decimal(self.price as u64, self.exponent)
+            .ok_or_else(|| invalid("price doesn't fit into fixed-point representation"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Limits applied to Pyth prices before they are accepted
+pub struct PythConfig {
+    /// max age of the aggregate price in seconds
+    pub max_age_secs: u64,
+    /// max width of the confidence interval relative to the price
+    pub max_confidence: Percentage,
+}
+
+impl Default for PythConfig {
+    fn default() -> Self {
+        Self {
+            max_age_secs: 60,
+            max_confidence: Percentage::from_raw_amount(SCALE / 100),
+        }
+    }
+}
+
+/// Oracle reading prices from the latest Pyth price account data pushed into it
+pub struct PythOracle {
+    account: Mutex<Vec<u8>>,
+    config: PythConfig,
+    clock: Box<dyn Fn() -> i64 + Send + Sync>,
+}
+
+impl PythOracle {
+    /// Creates oracle using system time to check price staleness
+    pub fn new(config: PythConfig) -> Self {
+        Self::with_clock(config, unix_now)
+    }
+
+    /// Creates oracle using custom clock returning current unix timestamp
+    pub fn with_clock(config: PythConfig, clock: impl Fn() -> i64 + Send + Sync + 'static) -> Self {
+        Self {
+            account: Mutex::new(Vec::new()),
+            config,
+            clock: Box::new(clock),
+        }
+    }
+
+    /// Replaces stored price account data with freshly fetched one
+    pub fn update_account(&self, data: &[u8]) {
+        let mut account = self.account.lock().unwrap_or_else(|p| p.into_inner());
+        account.clear();
+        account.extend_from_slice(data);
+    }
+}
+
+impl Debug for PythOracle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PythOracle")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PriceOracle for PythOracle {
+    fn price(&self) -> Result<Price, OracleError> {
+        let account = self.account.lock().unwrap_or_else(|p| p.into_inner());
+        PythPrice::parse(&account)?.to_price(&self.config, (self.clock)())
+    }
+}
+
+fn invalid(reason: &str) -> OracleError {
+    OracleError::InvalidData(reason.into())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().expect("length checked"))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().expect("length checked"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn account(price: i64, confidence: u64, exponent: i32, publish_time: i64) -> Vec<u8> {
+        let mut data = vec![0u8; MIN_ACCOUNT_LEN];
+        data[MAGIC_OFFSET..][..4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+        data[ACCOUNT_TYPE_OFFSET..][..4].copy_from_slice(&PRICE_ACCOUNT_TYPE.to_le_bytes());
+        data[EXPONENT_OFFSET..][..4].copy_from_slice(&exponent.to_le_bytes());
+        data[TIMESTAMP_OFFSET..][..8].copy_from_slice(&publish_time.to_le_bytes());
+        data[AGG_PRICE_OFFSET..][..8].copy_from_slice(&price.to_le_bytes());
+        data[AGG_CONF_OFFSET..][..8].copy_from_slice(&confidence.to_le_bytes());
+        data[AGG_STATUS_OFFSET..][..4].copy_from_slice(&STATUS_TRADING.to_le_bytes());
+        data
+    }
+
+    fn oracle() -> PythOracle {
+        PythOracle::with_clock(PythConfig::default(), || NOW)
+    }
+
+    #[test]
+    fn converts_pyth_price() {
+        let oracle = oracle();
+        oracle.update_account(&account(150_000_000, 10_000, -8, NOW - 5));
+        assert_eq!(oracle.price(), Ok(Price::from(1.5)));
+    }
+
+    #[test]
+    fn rejects_stale_price() {
+        let oracle = oracle();
+        oracle.update_account(&account(150_000_000, 10_000, -8, NOW - 61));
+        assert_eq!(
+            oracle.price(),
+            Err(OracleError::StalePrice {
+                age: 61,
+                max_age: 60
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_wide_confidence() {
+        let oracle = oracle();
+        oracle.update_account(&account(150_000_000, 3_000_000, -8, NOW));
+        assert!(matches!(
+            oracle.price(),
+            Err(OracleError::ConfidenceTooWide { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_accounts() {
+        let oracle = oracle();
+        assert!(oracle.price().is_err(), "no account data yet");
+
+        let mut data = account(150_000_000, 10_000, -8, NOW);
+        data[MAGIC_OFFSET] = 0;
+        oracle.update_account(&data);
+        assert!(matches!(oracle.price(), Err(OracleError::InvalidData(_))));
+    }
+}
@@ -0,0 +1,653 @@
+//! Exact rational backend of the pool math and an automated comparison bounding rounding
+//! of the fixed-point pool. `RationalPool` follows the formulas of `ReferencePool` on
+//! arbitrary precision fractions, so unlike the floating-point model its results are
+//! exact for amounts of any magnitude.
+//!
+//! `check_rounding` syncs the model to the fixed-point pool before every operation and
+//! checks every result against the bounds documented in `reference`. On top of that it
+//! values the fixed-point balances exactly after every operation and checks that they
+//! differ from the exact balances by no more than the rounding of the amounts paid out,
+//! `1 + price / S` per withdrawal and `2 * (1 + swap bound)` per swap, which covers the
+//! floored treasury fee. Differences are summed into the cumulative drift, which is
+//! checked against the sum of the bounds, so long runs expose rounding which accumulates
+//! in one direction.
+//!
+//! Exact swap values can exceed tokens left in the pool by less than a raw unit where the
+//! floored value doesn't, such swaps are charged the fee of an empty pool by the model.
+
+use std::cmp::Ordering;
+use std::ops::{Add, Div, Mul};
+
+use crate::error::DivergenceError;
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::replay::PoolConfig;
+use crate::types::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// Unsigned integer of arbitrary size, little endian limbs without trailing zeros
+struct BigUint {
+    limbs: Vec<u64>,
+}
+
+impl BigUint {
+    fn from_u128(value: u128) -> Self {
+        let mut limbs = vec![value as u64, (value >> 64) as u64];
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        Self { limbs }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    fn bits(&self) -> usize {
+        match self.limbs.last() {
+            Some(last) => self.limbs.len() * 64 - last.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    fn bit(&self, index: usize) -> bool {
+        self.limbs
+            .get(index / 64)
+            .is_some_and(|limb| limb >> (index % 64) & 1 == 1)
+    }
+
+    fn normalized(mut self) -> Self {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        self
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        let mut limbs = Vec::with_capacity(self.limbs.len().max(other.limbs.len()) + 1);
+        let mut carry = 0u128;
+        for i in 0..self.limbs.len().max(other.limbs.len()) {
+            let sum = *self.limbs.get(i).unwrap_or(&0) as u128
+                + *other.limbs.get(i).unwrap_or(&0) as u128
+                + carry;
+            limbs.push(sum as u64);
+            carry = sum >> 64;
+        }
+        limbs.push(carry as u64);
+        Self { limbs }.normalized()
+    }
+
+    /// Returns `self - other`, which mustn't be negative
+    fn sub(&self, other: &Self) -> Self {
+        debug_assert!(*self >= *other);
+        let mut limbs = Vec::with_capacity(self.limbs.len());
+        let mut borrow = false;
+        for (i, limb) in self.limbs.iter().enumerate() {
+            let (difference, first) = limb.overflowing_sub(*other.limbs.get(i).unwrap_or(&0));
+            let (difference, second) = difference.overflowing_sub(borrow as u64);
+            limbs.push(difference);
+            borrow = first || second;
+        }
+        Self { limbs }.normalized()
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        let mut limbs = vec![0u64; self.limbs.len() + other.limbs.len()];
+        for (i, a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u128;
+            for (j, b) in other.limbs.iter().enumerate() {
+                let product = *a as u128 * *b as u128 + limbs[i + j] as u128 + carry;
+                limbs[i + j] = product as u64;
+                carry = product >> 64;
+            }
+            limbs[i + other.limbs.len()] = carry as u64;
+        }
+        Self { limbs }.normalized()
+    }
+
+    fn shl1(&self, bit: bool) -> Self {
+        let mut limbs = Vec::with_capacity(self.limbs.len() + 1);
+        let mut carry = bit as u64;
+        for limb in &self.limbs {
+            limbs.push(limb << 1 | carry);
+            carry = limb >> 63;
+        }
+        limbs.push(carry);
+        Self { limbs }.normalized()
+    }
+
+    /// Returns quotient and remainder of the division, `divisor` mustn't be zero
+    fn div_rem(&self, divisor: &Self) -> (Self, Self) {
+        assert!(!divisor.is_zero(), "division by zero");
+        let mut quotient = vec![0u64; self.limbs.len()];
+        let mut remainder = BigUint::default();
+        for index in (0..self.bits()).rev() {
+            remainder = remainder.shl1(self.bit(index));
+            if remainder >= *divisor {
+                remainder = remainder.sub(divisor);
+                quotient[index / 64] |= 1 << (index % 64);
+            }
+        }
+        (Self { limbs: quotient }.normalized(), remainder)
+    }
+
+    fn to_f64(&self) -> f64 {
+        self.limbs
+            .iter()
+            .rev()
+            .fold(0.0, |value, limb| value * 2f64.powi(64) + *limb as f64)
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Non-negative fraction of arbitrary precision
+pub struct Ratio {
+    numerator: BigUint,
+    denominator: BigUint,
+}
+
+impl Ratio {
+    pub fn integer(value: u128) -> Self {
+        Self {
+            numerator: BigUint::from_u128(value),
+            denominator: BigUint::from_u128(1),
+        }
+    }
+
+    /// Returns `numerator / denominator`, `denominator` mustn't be zero
+    pub fn new(numerator: u128, denominator: u128) -> Self {
+        assert!(denominator > 0, "zero denominator");
+        Self {
+            numerator: BigUint::from_u128(numerator),
+            denominator: BigUint::from_u128(denominator),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.numerator.is_zero()
+    }
+
+    /// Returns `self - other`, or zero if `other` is bigger
+    pub fn saturating_sub(&self, other: &Self) -> Self {
+        let left = self.numerator.mul(&other.denominator);
+        let right = other.numerator.mul(&self.denominator);
+        Self {
+            numerator: match left > right {
+                true => left.sub(&right),
+                false => BigUint::default(),
+            },
+            denominator: self.denominator.mul(&other.denominator),
+        }
+    }
+
+    pub fn abs_diff(&self, other: &Self) -> Self {
+        match self > other {
+            true => self.saturating_sub(other),
+            false => other.saturating_sub(self),
+        }
+    }
+
+    pub fn min(self, other: Self) -> Self {
+        match self <= other {
+            true => self,
+            false => other,
+        }
+    }
+
+    pub fn max(self, other: Self) -> Self {
+        match self >= other {
+            true => self,
+            false => other,
+        }
+    }
+
+    /// Returns value rounded towards zero, saturated at `u128::MAX`
+    pub fn floor(&self) -> u128 {
+        let (quotient, _) = self.numerator.div_rem(&self.denominator);
+        match quotient.limbs.len() {
+            0..=2 => quotient
+                .limbs
+                .iter()
+                .rev()
+                .fold(0, |value, limb| value << 64 | *limb as u128),
+            _ => u128::MAX,
+        }
+    }
+
+    /// Returns the closest floating-point value, only used for reports
+    pub fn to_f64(&self) -> f64 {
+        let (quotient, remainder) = self.numerator.div_rem(&self.denominator);
+        let scale = self.denominator.bits().saturating_sub(60) as i32;
+        let fraction =
+            remainder.to_f64() / 2f64.powi(scale) / (self.denominator.to_f64() / 2f64.powi(scale));
+        quotient.to_f64() + fraction
+    }
+}
+
+impl PartialEq for Ratio {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ratio {}
+
+impl Ord for Ratio {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.numerator
+            .mul(&other.denominator)
+            .cmp(&other.numerator.mul(&self.denominator))
+    }
+}
+
+impl PartialOrd for Ratio {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for &Ratio {
+    type Output = Ratio;
+
+    fn add(self, other: &Ratio) -> Ratio {
+        Ratio {
+            numerator: self
+                .numerator
+                .mul(&other.denominator)
+                .add(&other.numerator.mul(&self.denominator)),
+            denominator: self.denominator.mul(&other.denominator),
+        }
+    }
+}
+
+impl Mul for &Ratio {
+    type Output = Ratio;
+
+    fn mul(self, other: &Ratio) -> Ratio {
+        Ratio {
+            numerator: self.numerator.mul(&other.numerator),
+            denominator: self.denominator.mul(&other.denominator),
+        }
+    }
+}
+
+/// Division by zero panics
+impl Div for &Ratio {
+    type Output = Ratio;
+
+    fn div(self, other: &Ratio) -> Ratio {
+        assert!(!other.is_zero(), "division by zero");
+        Ratio {
+            numerator: self.numerator.mul(&other.denominator),
+            denominator: self.denominator.mul(&other.numerator),
+        }
+    }
+}
+
+fn raw(value: Uint) -> Ratio {
+    Ratio::integer(value as u128)
+}
+
+/// Returns exact value of tokens and staked tokens at the price
+fn value_of(tokens: &Ratio, staked_tokens: &Ratio, price: &Ratio) -> Ratio {
+    tokens + &(&(staked_tokens * price) / &raw(SCALE))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Pool model computing exact results in rational numbers, amounts are raw units
+pub struct RationalPool {
+    pub price: Ratio,
+    pub token_amount: Ratio,
+    pub st_token_amount: Ratio,
+    pub lp_token_amount: Ratio,
+    min_fee: Ratio,
+    max_fee: Ratio,
+    liquidity_target: Ratio,
+    treasury_cut: Ratio,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Exact result of an operation, amounts are raw units
+pub enum RationalOutcome {
+    LiquidityAdded(Ratio),
+    LiquidityRemoved(Ratio, Ratio),
+    Swapped { amount_out: Ratio, treasury: Ratio },
+    PriceSet,
+    EpochAdvanced,
+}
+
+impl RationalPool {
+    /// Creates empty model of a pool built from the configuration, returns `None` if the
+    /// configuration uses settings the model doesn't cover, see `ReferencePool`
+    pub fn from_config(config: &PoolConfig) -> Option<Self> {
+        crate::reference::ReferencePool::from_config(config)?;
+        Some(Self {
+            price: raw(config.price.raw()),
+            token_amount: raw(0),
+            st_token_amount: raw(0),
+            lp_token_amount: raw(0),
+            min_fee: raw(config.params.min_fee.raw()),
+            max_fee: raw(config.params.max_fee.raw()),
+            liquidity_target: raw(config.params.liquidity_target.raw()),
+            treasury_cut: raw(config.treasury_cut.raw().min(SCALE)),
+        })
+    }
+
+    /// Copies price and balances of the pool
+    pub fn sync(&mut self, pool: &LpPool) {
+        self.price = raw(pool.price().raw());
+        self.token_amount = raw(pool.token_amount().raw());
+        self.st_token_amount = raw(pool.st_token_amount().raw());
+        self.lp_token_amount = raw(pool.lp_token_amount().raw());
+    }
+
+    pub fn total_value(&self) -> Ratio {
+        value_of(&self.token_amount, &self.st_token_amount, &self.price)
+    }
+
+    /// Returns exact fee in raw units for a swap leaving `amount_after` tokens in the pool
+    fn fee(&self, amount_after: &Ratio) -> Ratio {
+        let spread = self.max_fee.saturating_sub(&self.min_fee);
+        let rhs = match self.liquidity_target.is_zero() {
+            true => self.max_fee.clone(),
+            false => (&(&spread * amount_after) / &self.liquidity_target).min(self.max_fee.clone()),
+        };
+        self.max_fee
+            .saturating_sub(&rhs)
+            .max(self.min_fee.clone())
+            .min(raw(SCALE))
+    }
+
+    /// Returns bound of the fee error in raw units, see `reference`
+    fn fee_bound(&self) -> Ratio {
+        match self.liquidity_target.is_zero() {
+            true => raw(1),
+            false => {
+                &raw(1) + &(&self.max_fee.saturating_sub(&self.min_fee) / &self.liquidity_target)
+            }
+        }
+    }
+
+    /// Applies operation without any validation, so it should only be applied when the
+    /// fixed-point pool accepted it
+    pub fn apply(&mut self, op: &PoolOp) -> RationalOutcome {
+        let scale = raw(SCALE);
+        match *op {
+            PoolOp::AddLiquidity { amount, .. } => {
+                let amount = raw(amount.raw());
+                let minted = match self.lp_token_amount.is_zero() {
+                    true => amount.clone(),
+                    false => &(&self.lp_token_amount * &amount) / &self.total_value(),
+                };
+                self.token_amount = &self.token_amount + &amount;
+                self.lp_token_amount = &self.lp_token_amount + &minted;
+                RationalOutcome::LiquidityAdded(minted)
+            }
+            PoolOp::RemoveLiquidity { lp_amount, .. } => {
+                let lp_amount = raw(lp_amount.raw());
+                let (tokens, staked) = match self.lp_token_amount.is_zero() {
+                    true => (raw(0), raw(0)),
+                    false => (
+                        &(&self.token_amount * &lp_amount) / &self.lp_token_amount,
+                        &(&self.st_token_amount * &lp_amount) / &self.lp_token_amount,
+                    ),
+                };
+                self.token_amount = self.token_amount.saturating_sub(&tokens);
+                self.st_token_amount = self.st_token_amount.saturating_sub(&staked);
+                self.lp_token_amount = self.lp_token_amount.saturating_sub(&lp_amount);
+                RationalOutcome::LiquidityRemoved(tokens, staked)
+            }
+            PoolOp::Swap { amount, .. } => {
+                let amount = raw(amount.raw());
+                let value = &(&amount * &self.price) / &scale;
+                let fee = self.fee(&self.token_amount.saturating_sub(&value));
+                let amount_out = &(&value * &scale.saturating_sub(&fee)) / &scale;
+                let treasury = &(&value.saturating_sub(&amount_out) * &self.treasury_cut) / &scale;
+                self.token_amount = self.token_amount.saturating_sub(&(&amount_out + &treasury));
+                self.st_token_amount = &self.st_token_amount + &amount;
+                RationalOutcome::Swapped {
+                    amount_out,
+                    treasury,
+                }
+            }
+            PoolOp::SetPrice { price } => {
+                self.price = raw(price.raw());
+                RationalOutcome::PriceSet
+            }
+            PoolOp::AdvanceEpoch { .. } => RationalOutcome::EpochAdvanced,
+            PoolOp::Unreplayable => unreachable!("unreplayable operations are never accepted"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Summary of a rounding check
+pub struct RoundingReport {
+    /// amount of successful operations whose results were compared
+    pub compared: usize,
+    /// biggest difference between fixed-point and exact result in raw units
+    pub max_error: f64,
+    /// sum of differences between exactly valued fixed-point balances and exact
+    /// balances after every operation, positive if rounding kept value in the pool
+    pub drift: f64,
+    /// bound of the absolute drift, sum of per operation bounds
+    pub drift_bound: f64,
+}
+
+/// Replays operations on a pool built from the configuration and on the exact model,
+/// checking rounding of every result and of the pool value and the cumulative drift of
+/// the value. Failed operations are skipped by the model.
+pub fn check_rounding(
+    config: &PoolConfig,
+    ops: &[PoolOp],
+) -> Result<RoundingReport, DivergenceError> {
+    let mut model = RationalPool::from_config(config).ok_or(DivergenceError::Unsupported)?;
+    let mut pool = config.build();
+    let mut report = RoundingReport::default();
+
+    for (step, op) in ops.iter().enumerate() {
+        model.sync(&pool);
+        let before = model.clone();
+        let Ok(outcome) = pool.apply(op) else {
+            continue;
+        };
+        let expected = model.apply(op);
+        report.compared += 1;
+
+        let mut compare = |quantity: &'static str, fixed: &Ratio, exact: &Ratio, bound: &Ratio| {
+            let error = fixed.abs_diff(exact);
+            report.max_error = report.max_error.max(error.to_f64());
+            match error <= *bound {
+                true => Ok(()),
+                false => Err(DivergenceError::OutOfBound {
+                    step,
+                    quantity,
+                    fixed: fixed.floor() as Uint,
+                    exact: exact.to_f64(),
+                    bound: bound.to_f64(),
+                }),
+            }
+        };
+        let value_bound = match (outcome, &expected) {
+            (OpOutcome::LiquidityAdded(minted), RationalOutcome::LiquidityAdded(exact)) => {
+                let total = before.total_value();
+                // the bound is infinite when the floored total value is zero
+                let bound = match before.lp_token_amount.is_zero() {
+                    true => Some(raw(0)),
+                    false => (total > raw(1))
+                        .then(|| &raw(1) + &(exact / &total.saturating_sub(&raw(1)))),
+                };
+                if let Some(bound) = bound {
+                    compare("minted lp tokens", &raw(minted.raw()), exact, &bound)?;
+                }
+                raw(0)
+            }
+            (
+                OpOutcome::LiquidityRemoved(tokens, staked),
+                RationalOutcome::LiquidityRemoved(exact_tokens, exact_staked),
+            ) => {
+                compare(
+                    "withdrawn tokens",
+                    &raw(tokens.raw()),
+                    exact_tokens,
+                    &raw(1),
+                )?;
+                compare(
+                    "withdrawn staked tokens",
+                    &raw(staked.raw()),
+                    exact_staked,
+                    &raw(1),
+                )?;
+                &raw(1) + &(&before.price / &raw(SCALE))
+            }
+            (
+                OpOutcome::Swapped(amount_out),
+                RationalOutcome::Swapped {
+                    amount_out: exact, ..
+                },
+            ) => {
+                let value = &(&raw(op.raw_amount()) * &before.price) / &raw(SCALE);
+                let bound = &raw(2) + &(&(&value * &before.fee_bound()) / &raw(SCALE));
+                compare("swapped tokens", &raw(amount_out.raw()), exact, &bound)?;
+                &raw(2) * &(&raw(1) + &bound)
+            }
+            (OpOutcome::PriceSet, RationalOutcome::PriceSet)
+            | (OpOutcome::EpochAdvanced, RationalOutcome::EpochAdvanced) => raw(0),
+            _ => return Err(DivergenceError::MismatchedOutcome { step }),
+        };
+
+        let fixed_value = value_of(
+            &raw(pool.token_amount().raw()),
+            &raw(pool.st_token_amount().raw()),
+            &raw(pool.price().raw()),
+        );
+        let exact_value = model.total_value();
+        compare("pool value", &fixed_value, &exact_value, &value_bound)?;
+        // differences of a single step are small, so they're summed in floating point
+        // instead of fractions whose denominators would grow with every step
+        report.drift += match fixed_value >= exact_value {
+            true => fixed_value.saturating_sub(&exact_value).to_f64(),
+            false => -exact_value.saturating_sub(&fixed_value).to_f64(),
+        };
+        report.drift_bound += value_bound.to_f64();
+        if report.drift.abs() > report.drift_bound {
+            return Err(DivergenceError::DriftOutOfBound {
+                step,
+                drift: report.drift,
+                bound: report.drift_bound,
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::governance::PoolParams;
+    use crate::properties::random_ops;
+    use crate::rng::Rng;
+
+    fn config(liquidity_target: u64) -> PoolConfig {
+        let mut config = PoolConfig::new(
+            1.5.into(),
+            PoolParams {
+                min_fee: 0.001.into(),
+                max_fee: 0.09.into(),
+                liquidity_target: liquidity_target.into(),
+            },
+        );
+        config.treasury_cut = 0.25.into();
+        config
+    }
+
+    #[test]
+    fn computes_exactly() {
+        let big = BigUint::from_u128(u128::MAX).mul(&BigUint::from_u128(u128::MAX));
+        let (quotient, remainder) = big
+            .add(&BigUint::from_u128(5))
+            .div_rem(&BigUint::from_u128(u128::MAX));
+        assert_eq!(quotient, BigUint::from_u128(u128::MAX));
+        assert_eq!(remainder, BigUint::from_u128(5));
+        assert_eq!(big.sub(&big), BigUint::default());
+
+        let third = Ratio::new(1, 3);
+        assert_eq!(&(&third + &third) + &third, Ratio::integer(1));
+        assert_eq!((&Ratio::integer(10) / &Ratio::new(3, 1)).floor(), 3);
+        assert!((Ratio::new(2, 3).to_f64() - 2.0 / 3.0).abs() < 1e-15);
+        assert!(Ratio::new(1, 3).saturating_sub(&Ratio::new(1, 2)).is_zero());
+    }
+
+    #[test]
+    fn models_story_example_exactly() {
+        let mut model = RationalPool::from_config(&config(90)).unwrap();
+        model.apply(&PoolOp::AddLiquidity {
+            account: None,
+            amount: 100.into(),
+        });
+        let RationalOutcome::Swapped { amount_out, .. } = model.apply(&PoolOp::Swap {
+            account: None,
+            amount: 6.into(),
+        }) else {
+            panic!("expected swap outcome");
+        };
+        // value of 9 tokens at the minimal fee of 0.1%
+        assert_eq!(amount_out, Ratio::integer(8_991_000));
+    }
+
+    #[test]
+    fn bounds_rounding_of_long_runs() {
+        for (seed, liquidity_target) in [(0, 90), (1, 1_000_000), (2, 1)] {
+            let ops = random_ops(&mut Rng::new(seed), 3_000);
+            let report = check_rounding(&config(liquidity_target), &ops).unwrap();
+            assert!(report.compared > 1_000, "{report:?}");
+            assert!(report.drift.abs() <= report.drift_bound, "{report:?}");
+        }
+    }
+
+    #[test]
+    fn covers_amounts_beyond_float_precision() {
+        let ops = [
+            PoolOp::AddLiquidity {
+                account: None,
+                amount: TokenAmount::from_raw_amount(Uint::MAX / 4),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: StakedTokenAmount::from_raw_amount(Uint::MAX / 8 - 12_345),
+            },
+            PoolOp::RemoveLiquidity {
+                account: None,
+                lp_amount: LpTokenAmount::from_raw_amount(Uint::MAX / 5 + 3),
+            },
+        ];
+        let report = check_rounding(&config(1_000_000), &ops).unwrap();
+        assert_eq!(report.compared, 3);
+    }
+
+    #[test]
+    fn rejects_unsupported_configs() {
+        let mut config = config(90);
+        config.monotonic_price = true;
+        assert!(matches!(
+            check_rounding(&config, &[]),
+            Err(DivergenceError::Unsupported)
+        ));
+    }
+}
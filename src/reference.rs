@@ -0,0 +1,304 @@
+//! Floating-point reference model of the pool math and a differential harness comparing
+//! it against the fixed-point pool. The model follows the formulas of `LpPool` without
+//! any rounding, amounts are raw values stored as `f64`, so it's the exact result the
+//! fixed-point math approximates.
+//!
+//! Before every operation the model is synced to the state of the fixed-point pool, so
+//! every compared value carries rounding of a single operation only. Its error is bounded
+//! by the floor divisions involved, with `S = SCALE`, `D = max_fee - min_fee` and
+//! `G = liquidity_target` in raw units:
+//!
+//! * minted lp tokens: `1 + minted / (total_value - 1)`, total value is floored before
+//!   it's used as divisor
+//! * withdrawn tokens and staked tokens: `1` each
+//! * swap fee, which enters the bound below: `1 + D / G`, the fee is linear in the
+//!   floored swap value
+//! * tokens granted by a swap: `2 + value * (1 + D / G) / S`, floors of the swap value
+//!   and of the fee application plus the fee error applied to the swap value
+//!
+//! The model covers the linear fee curve only, configurations with other fee policies,
+//! surcharges, price smoothing or price guards aren't supported.
+
+use crate::error::DivergenceError;
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::replay::PoolConfig;
+use crate::types::*;
+
+const S: f64 = SCALE as f64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Pool model computing exact results in floating point, amounts are raw units
+pub struct ReferencePool {
+    pub price: f64,
+    pub token_amount: f64,
+    pub st_token_amount: f64,
+    pub lp_token_amount: f64,
+    min_fee: f64,
+    max_fee: f64,
+    liquidity_target: f64,
+    treasury_cut: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Exact result of an operation, amounts are raw units
+pub enum ReferenceOutcome {
+    LiquidityAdded(f64),
+    LiquidityRemoved(f64, f64),
+    Swapped { amount_out: f64, fee: f64 },
+    PriceSet,
+    EpochAdvanced,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Summary of a differential run
+pub struct DifferentialReport {
+    /// amount of successful operations whose results were compared
+    pub compared: usize,
+    /// biggest difference between fixed-point and exact result in raw units
+    pub max_error: f64,
+    /// relative difference of total value after replaying the whole stream on a model
+    /// which was never synced, shows how rounding accumulates
+    pub value_drift: f64,
+}
+
+impl ReferencePool {
+    /// Creates empty model of a pool built from the configuration, returns `None` if the
+    /// configuration uses settings the model doesn't cover
+    pub fn from_config(config: &PoolConfig) -> Option<Self> {
+        let defaults = PoolConfig {
+            price: config.price,
+            params: config.params,
+            treasury_cut: config.treasury_cut,
+            ..PoolConfig::new(config.price, config.params)
+        };
+        if *config != defaults {
+            return None;
+        }
+        Some(Self {
+            price: config.price.raw() as f64,
+            token_amount: 0.0,
+            st_token_amount: 0.0,
+            lp_token_amount: 0.0,
+            min_fee: config.params.min_fee.raw() as f64,
+            max_fee: config.params.max_fee.raw() as f64,
+            liquidity_target: config.params.liquidity_target.raw() as f64,
+            treasury_cut: config.treasury_cut.raw().min(SCALE) as f64,
+        })
+    }
+
+    /// Copies price and balances of the pool
+    pub fn sync(&mut self, pool: &LpPool) {
+        self.price = pool.price().raw() as f64;
+        self.token_amount = pool.token_amount().raw() as f64;
+        self.st_token_amount = pool.st_token_amount().raw() as f64;
+        self.lp_token_amount = pool.lp_token_amount().raw() as f64;
+    }
+
+    pub fn total_value(&self) -> f64 {
+        self.token_amount + self.st_token_amount * self.price / S
+    }
+
+    /// Returns exact fee in raw units for a swap leaving `amount_after` tokens in the pool
+    fn fee(&self, amount_after: f64) -> f64 {
+        let rhs = ((self.max_fee - self.min_fee) * amount_after / self.liquidity_target)
+            .min(self.max_fee);
+        (self.max_fee - rhs).max(self.min_fee).min(S)
+    }
+
+    /// Applies operation without any validation, so it should only be applied when the
+    /// fixed-point pool accepted it
+    pub fn apply(&mut self, op: &PoolOp) -> ReferenceOutcome {
+        match *op {
+            PoolOp::AddLiquidity { amount, .. } => {
+                let amount = amount.raw() as f64;
+                let minted = match self.lp_token_amount {
+                    0.0 => amount,
+                    lp => lp * amount / self.total_value(),
+                };
+                self.token_amount += amount;
+                self.lp_token_amount += minted;
+                ReferenceOutcome::LiquidityAdded(minted)
+            }
+            PoolOp::RemoveLiquidity { lp_amount, .. } => {
+                let share = match self.lp_token_amount {
+                    0.0 => 0.0,
+                    lp => lp_amount.raw() as f64 / lp,
+                };
+                let (tokens, staked) = (self.token_amount * share, self.st_token_amount * share);
+                self.token_amount -= tokens;
+                self.st_token_amount -= staked;
+                self.lp_token_amount -= lp_amount.raw() as f64;
+                ReferenceOutcome::LiquidityRemoved(tokens, staked)
+            }
+            PoolOp::Swap { amount, .. } => {
+                let amount = amount.raw() as f64;
+                let value = amount * self.price / S;
+                let fee = self.fee(self.token_amount - value);
+                let amount_out = value * (S - fee) / S;
+                let treasury = (value - amount_out) * self.treasury_cut / S;
+                self.token_amount -= amount_out + treasury;
+                self.st_token_amount += amount;
+                ReferenceOutcome::Swapped { amount_out, fee }
+            }
+            PoolOp::SetPrice { price } => {
+                self.price = price.raw() as f64;
+                ReferenceOutcome::PriceSet
+            }
+            PoolOp::AdvanceEpoch { .. } => ReferenceOutcome::EpochAdvanced,
+            PoolOp::Unreplayable => unreachable!("unreplayable operations are never accepted"),
+        }
+    }
+
+    /// Returns bound of the fee error in raw units, see module documentation
+    fn fee_bound(&self) -> f64 {
+        1.0 + (self.max_fee - self.min_fee) / self.liquidity_target
+    }
+}
+
+/// Replays operations on a pool built from the configuration and on the reference model,
+/// checking that every result of a successful operation is within the error bound of
+/// its exact value. Failed operations are skipped by the model.
+pub fn check_differential(
+    config: &PoolConfig,
+    ops: &[PoolOp],
+) -> Result<DifferentialReport, DivergenceError> {
+    let mut free = ReferencePool::from_config(config).ok_or(DivergenceError::Unsupported)?;
+    let mut synced = free;
+    let mut pool = config.build();
+    let mut report = DifferentialReport::default();
+
+    for (step, op) in ops.iter().enumerate() {
+        synced.sync(&pool);
+        let before = synced;
+        let Ok(outcome) = pool.apply(op) else {
+            continue;
+        };
+        let expected = synced.apply(op);
+        free.apply(op);
+        report.compared += 1;
+
+        let mut compare = |quantity: &'static str, fixed: Uint, exact: f64, bound: f64| {
+            let error = (fixed as f64 - exact).abs();
+            report.max_error = report.max_error.max(error);
+            match error <= bound {
+                true => Ok(()),
+                false => Err(DivergenceError::OutOfBound {
+                    step,
+                    quantity,
+                    fixed,
+                    exact,
+                    bound,
+                }),
+            }
+        };
+        match (outcome, expected) {
+            (OpOutcome::LiquidityAdded(minted), ReferenceOutcome::LiquidityAdded(exact)) => {
+                let bound = match before.lp_token_amount {
+                    0.0 => 0.0,
+                    _ => 1.0 + exact / (before.total_value() - 1.0).max(0.0),
+                };
+                compare("minted lp tokens", minted.raw(), exact, bound)?;
+            }
+            (
+                OpOutcome::LiquidityRemoved(tokens, staked),
+                ReferenceOutcome::LiquidityRemoved(exact_tokens, exact_staked),
+            ) => {
+                compare("withdrawn tokens", tokens.raw(), exact_tokens, 1.0)?;
+                compare("withdrawn staked tokens", staked.raw(), exact_staked, 1.0)?;
+            }
+            (
+                OpOutcome::Swapped(amount_out),
+                ReferenceOutcome::Swapped {
+                    amount_out: exact, ..
+                },
+            ) => {
+                let value = op.raw_amount() as f64 * before.price / S;
+                let bound = 2.0 + value * before.fee_bound() / S;
+                compare("swapped tokens", amount_out.raw(), exact, bound)?;
+            }
+            (OpOutcome::PriceSet, ReferenceOutcome::PriceSet)
+            | (OpOutcome::EpochAdvanced, ReferenceOutcome::EpochAdvanced) => {}
+            _ => return Err(DivergenceError::MismatchedOutcome { step }),
+        }
+    }
+
+    let value = pool.total_value().raw() as f64;
+    report.value_drift = (value - free.total_value()).abs() / free.total_value().max(1.0);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::governance::PoolParams;
+    use crate::properties::random_ops;
+    use crate::rng::Rng;
+
+    fn config() -> PoolConfig {
+        let mut config = PoolConfig::new(
+            1.5.into(),
+            PoolParams {
+                min_fee: 0.001.into(),
+                max_fee: 0.09.into(),
+                liquidity_target: 90.into(),
+            },
+        );
+        config.treasury_cut = 0.25.into();
+        config
+    }
+
+    #[test]
+    fn models_story_example_exactly() {
+        let mut model = ReferencePool::from_config(&config()).unwrap();
+        model.apply(&PoolOp::AddLiquidity {
+            account: None,
+            amount: 100.into(),
+        });
+        let ReferenceOutcome::Swapped { amount_out, fee } = model.apply(&PoolOp::Swap {
+            account: None,
+            amount: 6.into(),
+        }) else {
+            panic!("expected swap outcome");
+        };
+        assert!((amount_out - 8_991_000.0).abs() < 1e-6);
+        assert!((fee - 1_000.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fixed_point_stays_within_bound() {
+        for seed in 0..200 {
+            let ops = random_ops(&mut Rng::new(seed), 60);
+            check_differential(&config(), &ops).unwrap();
+        }
+
+        // with realistic amounts rounding doesn't accumulate into a visible drift
+        let mut rng = Rng::new(0);
+        let mut ops = vec![PoolOp::AddLiquidity {
+            account: None,
+            amount: 1_000.into(),
+        }];
+        ops.extend((0..200).map(|_| PoolOp::Swap {
+            account: None,
+            amount: StakedTokenAmount::from_raw_amount(rng.range(SCALE / 10, 5 * SCALE)),
+        }));
+        ops.push(PoolOp::RemoveLiquidity {
+            account: None,
+            lp_amount: 500.into(),
+        });
+        let report = check_differential(&config(), &ops).unwrap();
+        assert_eq!(report.compared, ops.len());
+        assert!(report.max_error <= 2.0, "{report:?}");
+        assert!(report.value_drift < 1e-6, "{report:?}");
+    }
+
+    #[test]
+    fn rejects_unsupported_configs() {
+        let mut config = config();
+        config.monotonic_price = true;
+        assert_eq!(
+            check_differential(&config, &[]),
+            Err(DivergenceError::Unsupported)
+        );
+    }
+}
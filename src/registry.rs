@@ -0,0 +1,280 @@
+//! Registry hosting pools of many tenants, for services running a sandbox per user. Pools
+//! are addressed by tenant and pool id, ids of different tenants never clash and every
+//! call is scoped to a single tenant, so one tenant can't reach pools of another. Every
+//! tenant is bounded by its limits, the registry's default ones unless set otherwise.
+
+use std::collections::BTreeMap;
+
+use crate::error::RegistryError;
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::replay::PoolConfig;
+use crate::store::is_valid_id;
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Resources a single tenant can use
+pub struct TenantLimits {
+    /// biggest amount of pools the tenant can hold at once
+    pub max_pools: usize,
+    /// biggest amount of tokens deposits can grow a pool of the tenant to, `None` if
+    /// unbounded
+    pub max_pool_tokens: Option<TokenAmount>,
+}
+
+impl Default for TenantLimits {
+    /// 16 pools of any size
+    fn default() -> Self {
+        Self {
+            max_pools: 16,
+            max_pool_tokens: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Tenant {
+    limits: Option<TenantLimits>,
+    pools: BTreeMap<String, LpPool>,
+}
+
+#[derive(Debug, Default)]
+/// Pools grouped by tenant, tenant -> pool id -> pool
+pub struct PoolRegistry {
+    default_limits: TenantLimits,
+    tenants: BTreeMap<String, Tenant>,
+}
+
+fn validate_id(id: &str) -> Result<(), RegistryError> {
+    match is_valid_id(id) {
+        true => Ok(()),
+        false => Err(RegistryError::InvalidId(id.into())),
+    }
+}
+
+impl PoolRegistry {
+    /// Creates registry applying the limits to tenants without limits of their own
+    pub fn new(default_limits: TenantLimits) -> Self {
+        Self {
+            default_limits,
+            tenants: BTreeMap::new(),
+        }
+    }
+
+    /// Returns limits the tenant is bound by
+    pub fn limits(&self, tenant: &str) -> TenantLimits {
+        self.tenants
+            .get(tenant)
+            .and_then(|tenant| tenant.limits)
+            .unwrap_or(self.default_limits)
+    }
+
+    /// Overrides limits of the tenant, existing pools above the new limits are kept but
+    /// no new pools or deposits beyond them are accepted
+    pub fn set_limits(&mut self, tenant: &str, limits: TenantLimits) -> Result<(), RegistryError> {
+        validate_id(tenant)?;
+        self.tenants.entry(tenant.into()).or_default().limits = Some(limits);
+        Ok(())
+    }
+
+    /// Builds a new pool of the tenant from the configuration
+    pub fn create(
+        &mut self,
+        tenant: &str,
+        id: &str,
+        config: &PoolConfig,
+    ) -> Result<&mut LpPool, RegistryError> {
+        validate_id(tenant)?;
+        validate_id(id)?;
+        let limits = self.limits(tenant);
+        let pools = self.tenants.get(tenant).map(|tenant| &tenant.pools);
+        if pools.is_some_and(|pools| pools.contains_key(id)) {
+            return Err(RegistryError::PoolExists {
+                tenant: tenant.into(),
+                id: id.into(),
+            });
+        }
+        if pools.map_or(0, BTreeMap::len) >= limits.max_pools {
+            return Err(RegistryError::PoolLimit {
+                tenant: tenant.into(),
+                max_pools: limits.max_pools,
+            });
+        }
+        // the tenant is only registered once the pool is certain to be created
+        let pools = &mut self.tenants.entry(tenant.into()).or_default().pools;
+        Ok(pools.entry(id.into()).or_insert(config.build()))
+    }
+
+    pub fn pool(&self, tenant: &str, id: &str) -> Option<&LpPool> {
+        self.tenants.get(tenant)?.pools.get(id)
+    }
+
+    fn pool_mut(&mut self, tenant: &str, id: &str) -> Result<&mut LpPool, RegistryError> {
+        self.tenants
+            .get_mut(tenant)
+            .and_then(|pools| pools.pools.get_mut(id))
+            .ok_or_else(|| RegistryError::UnknownPool {
+                tenant: tenant.into(),
+                id: id.into(),
+            })
+    }
+
+    /// Applies operation to the pool of the tenant, deposits growing the pool beyond the
+    /// tenant's token limit are rejected without touching the pool
+    pub fn apply(
+        &mut self,
+        tenant: &str,
+        id: &str,
+        op: &PoolOp,
+    ) -> Result<OpOutcome, RegistryError> {
+        let max_tokens = self.limits(tenant).max_pool_tokens;
+        let pool = self.pool_mut(tenant, id)?;
+        if let (PoolOp::AddLiquidity { amount, .. }, Some(max_tokens)) = (op, max_tokens) {
+            let tokens = pool.token_amount().saturating_add(*amount);
+            if tokens > max_tokens {
+                return Err(RegistryError::TokenLimit { tokens, max_tokens });
+            }
+        }
+        Ok(pool.apply(op)?)
+    }
+
+    /// Removes the pool of the tenant and returns it
+    pub fn remove(&mut self, tenant: &str, id: &str) -> Option<LpPool> {
+        self.tenants.get_mut(tenant)?.pools.remove(id)
+    }
+
+    /// Removes the tenant with all of its pools and its limits
+    pub fn remove_tenant(&mut self, tenant: &str) -> BTreeMap<String, LpPool> {
+        self.tenants
+            .remove(tenant)
+            .map(|tenant| tenant.pools)
+            .unwrap_or_default()
+    }
+
+    /// Returns ids of the tenant's pools in ascending order
+    pub fn pools(&self, tenant: &str) -> Vec<&str> {
+        self.tenants
+            .get(tenant)
+            .map(|tenant| tenant.pools.keys().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns tenants holding pools or limits of their own in ascending order
+    pub fn tenants(&self) -> Vec<&str> {
+        self.tenants.keys().map(String::as_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{OpError, SwapError};
+    use crate::test_utils::fixture_config;
+
+    fn deposit(amount: f64) -> PoolOp {
+        PoolOp::AddLiquidity {
+            account: None,
+            amount: amount.into(),
+        }
+    }
+
+    #[test]
+    fn isolates_pools_of_tenants() {
+        let mut registry = PoolRegistry::default();
+        registry.create("alice", "main", &fixture_config()).unwrap();
+        registry.create("bob", "main", &fixture_config()).unwrap();
+        registry.apply("alice", "main", &deposit(100.0)).unwrap();
+        registry
+            .apply(
+                "alice",
+                "main",
+                &PoolOp::Swap {
+                    account: None,
+                    amount: 6.into(),
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            registry.pool("bob", "main").unwrap().token_amount(),
+            0.into()
+        );
+        assert!(matches!(
+            registry.apply(
+                "bob",
+                "main",
+                &PoolOp::Swap {
+                    account: None,
+                    amount: 6.into()
+                }
+            ),
+            Err(RegistryError::Op(OpError::Swap(
+                SwapError::PoolNotEnoughTokens { .. }
+            )))
+        ));
+        assert!(matches!(
+            registry.apply("bob", "other", &deposit(1.0)),
+            Err(RegistryError::UnknownPool { .. })
+        ));
+        assert!(matches!(
+            registry.create("alice", "main", &fixture_config()),
+            Err(RegistryError::PoolExists { .. })
+        ));
+        assert!(matches!(
+            registry.create("alice", "../bob", &fixture_config()),
+            Err(RegistryError::InvalidId(_))
+        ));
+
+        assert_eq!(registry.tenants(), ["alice", "bob"]);
+        assert_eq!(registry.remove_tenant("bob").len(), 1);
+        assert_eq!(registry.pools("alice"), ["main"]);
+        assert!(registry.pools("bob").is_empty());
+    }
+
+    #[test]
+    fn enforces_tenant_limits() {
+        let mut registry = PoolRegistry::new(TenantLimits {
+            max_pools: 1,
+            max_pool_tokens: Some(100.into()),
+        });
+        registry.create("alice", "a", &fixture_config()).unwrap();
+        assert!(matches!(
+            registry.create("alice", "b", &fixture_config()),
+            Err(RegistryError::PoolLimit { max_pools: 1, .. })
+        ));
+        registry.apply("alice", "a", &deposit(100.0)).unwrap();
+        assert!(matches!(
+            registry.apply("alice", "a", &deposit(1.0)),
+            Err(RegistryError::TokenLimit { .. })
+        ));
+        assert_eq!(
+            registry.pool("alice", "a").unwrap().token_amount(),
+            100.into()
+        );
+
+        let unlimited = TenantLimits {
+            max_pools: 2,
+            max_pool_tokens: None,
+        };
+        registry.set_limits("alice", unlimited).unwrap();
+        registry.create("alice", "b", &fixture_config()).unwrap();
+        registry.apply("alice", "a", &deposit(1.0)).unwrap();
+        // limits of other tenants are untouched
+        assert_eq!(registry.limits("bob").max_pools, 1);
+        registry.remove("alice", "a").unwrap();
+        assert_eq!(registry.pools("alice"), ["b"]);
+    }
+
+    #[test]
+    fn rejected_create_doesnt_register_tenant() {
+        let mut registry = PoolRegistry::new(TenantLimits {
+            max_pools: 0,
+            max_pool_tokens: None,
+        });
+        assert!(matches!(
+            registry.create("alice", "main", &fixture_config()),
+            Err(RegistryError::PoolLimit { max_pools: 0, .. })
+        ));
+        assert!(registry.tenants().is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+use crate::bootstrap::LiquidityBootstrap;
+use crate::discounts::DiscountTier;
+use crate::error::ReplayError;
+use crate::fee_policy::FeePolicy;
+use crate::governance::PoolParams;
+use crate::lp_pool::LpPool;
+use crate::ops::PoolOp;
+use crate::price_smoothing::PriceSmoothing;
+use crate::surcharge::SurchargeConfig;
+use crate::types::*;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Configuration a pool was created with, together with the operation log it's enough
+/// to reconstruct the pool
+pub struct PoolConfig {
+    pub price: Price,
+    pub params: PoolParams,
+    pub treasury_cut: Percentage,
+    pub insurance_cut: Percentage,
+    pub fee_policy: FeePolicy,
+    pub price_smoothing: PriceSmoothing,
+    pub surcharge: Option<SurchargeConfig>,
+    /// liquidity bootstrapping mode together with the epoch it started at
+    pub bootstrap: Option<LiquidityBootstrap>,
+    pub max_price_age: Option<Epoch>,
+    pub max_price_deviation: Option<Percentage>,
+    pub monotonic_price: bool,
+    pub fee_discounts: Vec<DiscountTier>,
+}
+
+impl PoolConfig {
+    /// Creates configuration with default values of every optional setting
+    pub const fn new(price: Price, params: PoolParams) -> Self {
+        Self {
+            price,
+            params,
+            treasury_cut: Percentage::from_raw_amount(0),
+            insurance_cut: Percentage::from_raw_amount(0),
+            fee_policy: FeePolicy::Linear,
+            price_smoothing: PriceSmoothing::Raw,
+            surcharge: None,
+            bootstrap: None,
+            max_price_age: None,
+            max_price_deviation: None,
+            monotonic_price: false,
+            fee_discounts: Vec::new(),
+        }
+    }
+
+    /// Creates pool with this configuration and enabled operation log
+    pub fn build(&self) -> LpPool {
+        let mut pool = LpPool::init(
+            self.price,
+            self.params.min_fee,
+            self.params.max_fee,
+            self.params.liquidity_target,
+        )
+        .unwrap_or_else(|never| match never {});
+        pool.set_treasury_cut(self.treasury_cut);
+        pool.set_insurance_cut(self.insurance_cut);
+        pool.set_fee_policy(self.fee_policy);
+        pool.set_price_smoothing(self.price_smoothing);
+        pool.set_swap_surcharge(self.surcharge);
+        pool.restore_bootstrap(self.bootstrap);
+        pool.set_max_price_age(self.max_price_age);
+        pool.set_max_price_deviation(self.max_price_deviation);
+        pool.set_monotonic_price(self.monotonic_price);
+        pool.set_fee_discounts(self.fee_discounts.clone());
+        pool.enable_op_log();
+        pool
+    }
+}
+
+impl LpPool {
+    /// Reconstructs pool by applying logged operations to a freshly configured pool.
+    /// Logs contain only successful operations, so any failure means the log doesn't
+    /// belong to the configuration or the logged pool was mutated outside of operations.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - configuration the logged pool was created with
+    /// * `ops` - operations recorded by the pool's operation log
+    pub fn replay(config: &PoolConfig, ops: &[PoolOp]) -> Result<LpPool, ReplayError> {
+        let mut pool = config.build();
+        pool.apply_logged(ops)?;
+        Ok(pool)
+    }
+
+    /// Applies logged operations on top of the current state
+    pub(crate) fn apply_logged(&mut self, ops: &[PoolOp]) -> Result<(), ReplayError> {
+        for (index, op) in ops.iter().enumerate() {
+            self.apply(op)
+                .map_err(|error| ReplayError { index, error })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bootstrap::BootstrapConfig;
+    use crate::discounts::DiscountCriterion;
+    use crate::error::OpError;
+    use crate::oracle::MockOracle;
+
+    fn config() -> PoolConfig {
+        let mut config = PoolConfig::new(
+            1.5.into(),
+            PoolParams {
+                min_fee: 0.001.into(),
+                max_fee: 0.09.into(),
+                liquidity_target: 90.into(),
+            },
+        );
+        config.treasury_cut = 0.1.into();
+        config.insurance_cut = 0.2.into();
+        config.fee_discounts = vec![DiscountTier::new(
+            DiscountCriterion::LiquidityShare(0.5.into()),
+            0.2.into(),
+        )];
+        let bootstrap = BootstrapConfig {
+            surcharge: 0.05.into(),
+            epochs: 10,
+        };
+        config.bootstrap = Some(LiquidityBootstrap::new(bootstrap, 2));
+        config
+    }
+
+    #[test]
+    fn replay_reconstructs_identical_state() {
+        let mut pool = config().build();
+        let oracle = MockOracle::new([1.5.into(), 1.6.into()]);
+        pool.add_liquidity_for(1, 100.into()).unwrap();
+        pool.set_oracle(oracle.clone());
+        pool.swap(6.into()).unwrap();
+        pool.swap(0.into()).unwrap_err();
+        pool.swap(2.into()).unwrap();
+        assert!(pool.swap_for(1, 1.into()).unwrap().tier.is_some());
+        pool.advance_epoch(1);
+        pool.remove_liquidity_for(1, 10.into()).unwrap();
+
+        let replayed = LpPool::replay(&config(), pool.op_log()).unwrap();
+        assert_eq!(replayed.state_hash(), pool.state_hash());
+        assert_eq!(replayed.op_log(), pool.op_log());
+        assert!(replayed.insurance_fund().balance.raw() > 0);
+        assert_eq!(replayed.bootstrap(), pool.bootstrap());
+        assert!(replayed.bootstrap_surcharge().raw() > 0);
+        assert!(pool
+            .op_log()
+            .contains(&PoolOp::SetPrice { price: 1.6.into() }));
+    }
+
+    #[test]
+    fn replay_fails_on_unlogged_mutations() {
+        let mut pool = config().build();
+        pool.add_liquidity(100.into()).unwrap();
+        pool.swap(6.into()).unwrap();
+        pool.claim().unwrap();
+        pool.force_price(1.4.into(), "depeg").unwrap();
+        assert_eq!(
+            pool.op_log()
+                .iter()
+                .filter(|op| **op == PoolOp::Unreplayable)
+                .count(),
+            2
+        );
+
+        let error = LpPool::replay(&config(), pool.op_log()).unwrap_err();
+        assert_eq!(error.index, 2);
+        assert!(matches!(error.error, OpError::Unreplayable));
+    }
+
+    #[test]
+    fn replay_reports_failing_op() {
+        let ops = [
+            PoolOp::AddLiquidity {
+                account: None,
+                amount: 10.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 100.into(),
+            },
+        ];
+        let error = LpPool::replay(&config(), &ops).unwrap_err();
+        assert_eq!(error.index, 1);
+    }
+}
@@ -0,0 +1,265 @@
+//! Statistical report of a simulation run. The operation log is replayed against a pool
+//! built from the configuration, collecting slippage of every successful swap, fees and
+//! liquidity utilization, the share of the liquidity target missing from the pool, after
+//! every operation. Distributions are summarized by their mean, median, 99th percentile
+//! and maximum, and utilization and fees are also reported per epoch.
+
+use crate::backtest::liquidity_ratio;
+use crate::json::Value;
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::replay::PoolConfig;
+use crate::schema::ToJson;
+use crate::simulator::Simulation;
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Summary of a distribution of percentages, all zero for an empty distribution
+pub struct Percentiles {
+    pub mean: Percentage,
+    pub median: Percentage,
+    pub p99: Percentage,
+    pub max: Percentage,
+}
+
+impl Percentiles {
+    /// Summarizes raw values, percentiles are taken by the nearest rank
+    pub fn from_raw(mut values: Vec<Uint>) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        values.sort_unstable();
+        let rank = |percentile: usize| {
+            let rank = (values.len() * percentile).div_ceil(100).max(1);
+            Percentage::from_raw_amount(values[rank - 1])
+        };
+        let sum: u128 = values.iter().map(|value| *value as u128).sum();
+        Self {
+            mean: Percentage::from_raw_amount((sum / values.len() as u128) as Uint),
+            median: rank(50),
+            p99: rank(99),
+            max: Percentage::from_raw_amount(*values.last().expect("values aren't empty")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Activity of a single epoch
+pub struct EpochReport {
+    pub epoch: Epoch,
+    pub successful_swaps: usize,
+    /// all fees charged by swaps of the epoch
+    pub fee_revenue: TokenAmount,
+    /// utilization after the last operation of the epoch
+    pub utilization: Percentage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Distributions and totals of a replayed operation log
+pub struct SimulationReport {
+    pub ops: usize,
+    /// operations rejected by the pool
+    pub failed_ops: usize,
+    pub successful_swaps: usize,
+    /// share of swap value lost to fees over successful swaps
+    pub slippage: Percentiles,
+    /// tokens paid out by swaps
+    pub swap_volume: TokenAmount,
+    /// all fees charged by swaps
+    pub fee_revenue: TokenAmount,
+    /// share of the fees kept by liquidity providers
+    pub lp_fee_revenue: TokenAmount,
+    pub treasury_fee_revenue: TokenAmount,
+    /// utilization after every operation
+    pub utilization: Percentiles,
+    /// epochs the pool passed through in order, including epochs without any swaps
+    pub epochs: Vec<EpochReport>,
+}
+
+/// Returns share of the liquidity target missing from the pool
+fn utilization(pool: &LpPool) -> Uint {
+    SCALE.saturating_sub(liquidity_ratio(pool).raw())
+}
+
+impl SimulationReport {
+    /// Replays operations on a pool built from the configuration and reports them
+    pub fn from_ops(config: &PoolConfig, ops: &[PoolOp]) -> Self {
+        let mut pool = config.build();
+        let mut slippage = Vec::new();
+        let mut utilizations = Vec::with_capacity(ops.len());
+        let (mut failed_ops, mut successful_swaps) = (0, 0);
+        let mut swap_volume = TokenAmount::from_raw_amount(0);
+        let mut epochs = Vec::new();
+        let mut epoch = EpochReport {
+            epoch: pool.epoch(),
+            successful_swaps: 0,
+            fee_revenue: 0.into(),
+            utilization: Percentage::from_raw_amount(utilization(&pool)),
+        };
+
+        for op in ops {
+            let value_before_fees = match op {
+                PoolOp::Swap { amount, .. } => amount.checked_into_token_amount(pool.price()),
+                _ => None,
+            };
+            let fees_before = pool.fee_revenue().total;
+            match pool.apply(op) {
+                Ok(OpOutcome::Swapped(amount_out)) => {
+                    let value = value_before_fees.expect("swap op yields swap outcome");
+                    if value.raw() > 0 {
+                        let lost = (value - amount_out).raw() as u128 * SCALE as u128;
+                        slippage.push((lost / value.raw() as u128) as Uint);
+                    }
+                    swap_volume = swap_volume + amount_out;
+                    successful_swaps += 1;
+                    epoch.successful_swaps += 1;
+                }
+                Ok(_) => {}
+                Err(_) => failed_ops += 1,
+            }
+            epoch.fee_revenue = epoch.fee_revenue + (pool.fee_revenue().total - fees_before);
+            utilizations.push(utilization(&pool));
+
+            if pool.epoch() != epoch.epoch {
+                epochs.push(epoch);
+                epoch = EpochReport {
+                    epoch: pool.epoch(),
+                    successful_swaps: 0,
+                    fee_revenue: 0.into(),
+                    utilization: Percentage::from_raw_amount(0),
+                };
+            }
+            epoch.utilization = Percentage::from_raw_amount(utilization(&pool));
+        }
+        epochs.push(epoch);
+
+        let fees = pool.fee_revenue();
+        Self {
+            ops: ops.len(),
+            failed_ops,
+            successful_swaps,
+            slippage: Percentiles::from_raw(slippage),
+            swap_volume,
+            fee_revenue: fees.total,
+            lp_fee_revenue: fees.lp,
+            treasury_fee_revenue: fees.treasury,
+            utilization: Percentiles::from_raw(utilizations),
+            epochs,
+        }
+    }
+
+    /// Reports every operation generated by the simulation, rejected ones included
+    pub fn from_simulation(config: &PoolConfig, simulation: &Simulation) -> Self {
+        Self::from_ops(config, &simulation.ops)
+    }
+}
+
+impl ToJson for Percentiles {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("mean", self.mean.to_json()),
+            ("median", self.median.to_json()),
+            ("p99", self.p99.to_json()),
+            ("max", self.max.to_json()),
+        ])
+    }
+}
+
+impl ToJson for EpochReport {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("epoch", self.epoch.to_json()),
+            ("successful_swaps", self.successful_swaps.to_json()),
+            ("fee_revenue", self.fee_revenue.to_json()),
+            ("utilization", self.utilization.to_json()),
+        ])
+    }
+}
+
+impl ToJson for SimulationReport {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("ops", self.ops.to_json()),
+            ("failed_ops", self.failed_ops.to_json()),
+            ("successful_swaps", self.successful_swaps.to_json()),
+            ("slippage", self.slippage.to_json()),
+            ("swap_volume", self.swap_volume.to_json()),
+            ("fee_revenue", self.fee_revenue.to_json()),
+            ("lp_fee_revenue", self.lp_fee_revenue.to_json()),
+            ("treasury_fee_revenue", self.treasury_fee_revenue.to_json()),
+            ("utilization", self.utilization.to_json()),
+            ("epochs", self.epochs.to_json()),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::{simulate, SimulationConfig};
+    use crate::test_utils::fixture_config;
+
+    #[test]
+    fn summarizes_percentiles() {
+        assert_eq!(Percentiles::from_raw(Vec::new()), Percentiles::default());
+        let summary = Percentiles::from_raw((1..=200).rev().collect());
+        assert_eq!(summary.mean.raw(), 100);
+        assert_eq!(summary.median.raw(), 100);
+        assert_eq!(summary.p99.raw(), 198);
+        assert_eq!(summary.max.raw(), 200);
+    }
+
+    #[test]
+    fn reports_story_example() {
+        let ops = [
+            PoolOp::AddLiquidity {
+                account: None,
+                amount: 100.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 6.into(),
+            },
+            PoolOp::AdvanceEpoch { epochs: 1 },
+            PoolOp::Swap {
+                account: None,
+                amount: 100.into(),
+            },
+        ];
+        let report = SimulationReport::from_ops(&fixture_config(), &ops);
+        assert_eq!(
+            (report.ops, report.failed_ops, report.successful_swaps),
+            (4, 1, 1)
+        );
+        assert_eq!(report.slippage.median, 0.001.into());
+        assert_eq!(report.swap_volume, 8.991.into());
+        assert_eq!(report.fee_revenue, 0.009.into());
+        // the pool never drops below the target of 90 tokens
+        assert_eq!(report.utilization.max, 0.0.into());
+
+        let epochs: Vec<_> = report.epochs.iter().map(|epoch| epoch.epoch).collect();
+        assert_eq!(epochs, [0, 1]);
+        assert_eq!(report.epochs[0].fee_revenue, 0.009.into());
+        assert_eq!(report.epochs[1].successful_swaps, 0);
+        assert_eq!(report.epochs[1].utilization, 0.0.into());
+    }
+
+    #[test]
+    fn reports_simulations() {
+        let config = SimulationConfig::new(fixture_config(), 2);
+        let simulation = simulate(&config);
+        let report = SimulationReport::from_simulation(&config.pool, &simulation);
+        assert_eq!(report.successful_swaps, simulation.stats.successful_swaps);
+        assert_eq!(report.fee_revenue, simulation.stats.fee_revenue);
+        assert_eq!(report.swap_volume, simulation.stats.swap_volume);
+        assert_eq!(report.epochs.len(), config.steps / config.epoch_length + 1);
+        assert!(report.slippage.median <= report.slippage.p99);
+
+        let json = report.to_json();
+        assert_eq!(
+            json.get("epochs").unwrap().as_array().unwrap().len(),
+            report.epochs.len()
+        );
+        assert!(json.to_pretty_string().contains("\"p99\""));
+    }
+}
@@ -0,0 +1,456 @@
+//! Incentive rewards for liquidity providers. `LpRewards` is a `PoolHook` registered with
+//! `LpPool::add_hook` which emits a fixed amount of reward tokens per epoch and splits it
+//! between tracked positions pro rata to their lp tokens, anonymous liquidity earns
+//! nothing. Clones of the program share its state, so a clone kept outside the pool is
+//! used to query and claim rewards.
+//!
+//! Rewards optionally vest: nothing is claimable before the cliff passes, afterwards the
+//! rewards of every epoch are released linearly until the end of the vesting duration.
+//! Withdrawing liquidity before rewards fully vest forfeits unvested rewards according to
+//! the configured `Forfeiture` rule, what already vested always stays claimable.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::error::{HookRejection, OpError};
+use crate::hooks::PoolHook;
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Release schedule of rewards, counted from the epoch they were earned in
+pub struct VestingSchedule {
+    /// epochs during which nothing is released
+    pub cliff: Epoch,
+    /// epochs until rewards are released completely, releases are linear over the whole
+    /// duration, so reaching the cliff releases `cliff / duration` of them at once
+    pub duration: Epoch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// What happens to unvested rewards of a position withdrawing liquidity
+pub enum Forfeiture {
+    /// unvested rewards keep vesting
+    Keep,
+    /// share of unvested rewards matching the withdrawn share of lp tokens is forfeited
+    #[default]
+    Proportional,
+    /// any withdrawal forfeits all unvested rewards
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RewardsConfig {
+    /// reward tokens split between positions every epoch, emission stops once `Uint::MAX`
+    /// raw tokens were distributed
+    pub reward_per_epoch: TokenAmount,
+    /// `None` makes rewards claimable right away
+    pub vesting: Option<VestingSchedule>,
+    pub forfeiture: Forfeiture,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Rewards earned in one epoch, `base` is released at the cliff and `amount` linearly
+/// from `linear_from` to `end`
+struct Grant {
+    base: Uint,
+    amount: Uint,
+    cliff_end: Epoch,
+    linear_from: Epoch,
+    end: Epoch,
+}
+
+impl Grant {
+    fn new(amount: Uint, epoch: Epoch, vesting: Option<VestingSchedule>) -> Self {
+        let vesting = vesting.unwrap_or(VestingSchedule {
+            cliff: 0,
+            duration: 0,
+        });
+        Self {
+            base: 0,
+            amount,
+            cliff_end: epoch.saturating_add(vesting.cliff),
+            linear_from: epoch,
+            end: epoch.saturating_add(vesting.duration),
+        }
+    }
+
+    fn total(&self) -> Uint {
+        self.base + self.amount
+    }
+
+    fn vested(&self, epoch: Epoch) -> Uint {
+        if epoch < self.cliff_end {
+            return 0;
+        }
+        let linear = match epoch >= self.end {
+            true => self.amount,
+            false => {
+                let elapsed = (epoch - self.linear_from) as u128;
+                let duration = (self.end - self.linear_from) as u128;
+                (self.amount as u128 * elapsed / duration) as Uint
+            }
+        };
+        self.base + linear
+    }
+
+    /// Forfeits `share` of the unvested rewards at the epoch, the rest keeps vesting until
+    /// the original end. Returns the forfeited amount.
+    fn forfeit(&mut self, share: Percentage, epoch: Epoch) -> Uint {
+        let vested = self.vested(epoch);
+        let unvested = self.total() - vested;
+        let forfeited = mul_div(unvested, share.raw().min(SCALE), SCALE).unwrap_or(unvested);
+        let kept = unvested - forfeited;
+        // before the cliff nothing vested and the kept rewards follow the original schedule
+        if epoch >= self.cliff_end {
+            self.base = vested;
+            self.linear_from = epoch;
+        }
+        self.amount = kept;
+        forfeited
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Account {
+    /// grants that didn't vest completely yet
+    grants: Vec<Grant>,
+    /// rewards of completely vested grants
+    vested: Uint,
+    claimed: Uint,
+}
+
+impl Account {
+    fn vested(&self, epoch: Epoch) -> Uint {
+        self.grants
+            .iter()
+            .map(|grant| grant.vested(epoch))
+            .fold(self.vested, Uint::saturating_add)
+    }
+
+    fn unvested(&self, epoch: Epoch) -> Uint {
+        self.grants
+            .iter()
+            .map(|grant| grant.total() - grant.vested(epoch))
+            .fold(0, Uint::saturating_add)
+    }
+
+    /// Folds completely vested grants into `vested`
+    fn compact(&mut self, epoch: Epoch) {
+        let vested = &mut self.vested;
+        self.grants.retain(|grant| {
+            let done = grant.vested(epoch) == grant.total();
+            if done {
+                *vested = vested.saturating_add(grant.total());
+            }
+            !done
+        });
+    }
+}
+
+#[derive(Debug, Default)]
+struct RewardsState {
+    epoch: Epoch,
+    accounts: BTreeMap<AccountId, Account>,
+    /// lp tokens of the withdrawing account captured before the operation in flight
+    pending_lp_tokens: Option<LpTokenAmount>,
+    distributed: Uint,
+    forfeited: Uint,
+}
+
+#[derive(Debug, Clone)]
+/// Shared handle of an LP rewards program
+pub struct LpRewards {
+    config: RewardsConfig,
+    state: Arc<Mutex<RewardsState>>,
+}
+
+impl LpRewards {
+    pub fn new(config: RewardsConfig) -> Self {
+        Self {
+            config,
+            state: Arc::default(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, RewardsState> {
+        self.state.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    pub fn config(&self) -> RewardsConfig {
+        self.config
+    }
+
+    /// Returns epoch of the pool after the last observed operation
+    pub fn epoch(&self) -> Epoch {
+        self.state().epoch
+    }
+
+    /// Returns rewards of the account vested and not claimed yet
+    pub fn claimable_now(&self, account: AccountId) -> TokenAmount {
+        let state = self.state();
+        let claimable = state
+            .accounts
+            .get(&account)
+            .map(|rewards| rewards.vested(state.epoch) - rewards.claimed)
+            .unwrap_or_default();
+        TokenAmount::from_raw_amount(claimable)
+    }
+
+    /// Returns rewards of the account that didn't vest yet
+    pub fn unvested(&self, account: AccountId) -> TokenAmount {
+        let state = self.state();
+        let unvested = state
+            .accounts
+            .get(&account)
+            .map(|rewards| rewards.unvested(state.epoch))
+            .unwrap_or_default();
+        TokenAmount::from_raw_amount(unvested)
+    }
+
+    /// Marks claimable rewards of the account as claimed and returns their amount
+    pub fn claim(&self, account: AccountId) -> TokenAmount {
+        let mut state = self.state();
+        let epoch = state.epoch;
+        let Some(rewards) = state.accounts.get_mut(&account) else {
+            return TokenAmount::from_raw_amount(0);
+        };
+        let claimable = rewards.vested(epoch) - rewards.claimed;
+        rewards.claimed += claimable;
+        TokenAmount::from_raw_amount(claimable)
+    }
+
+    /// Returns all rewards ever split between positions
+    pub fn distributed(&self) -> TokenAmount {
+        TokenAmount::from_raw_amount(self.state().distributed)
+    }
+
+    /// Returns all rewards forfeited by early withdrawals
+    pub fn forfeited(&self) -> TokenAmount {
+        TokenAmount::from_raw_amount(self.state().forfeited)
+    }
+
+    fn distribute(&self, state: &mut RewardsState, pool: &LpPool, epochs: Epoch) {
+        // rewards beyond what's left of `Uint` aren't emitted, so neither shares nor the
+        // distributed total can overflow
+        let reward = (self.config.reward_per_epoch.raw() as u128 * epochs as u128)
+            .min((Uint::MAX - state.distributed) as u128);
+        let positions = pool.positions();
+        let lp_tokens: u128 = positions
+            .iter()
+            .map(|(_, position)| position.lp_tokens.raw() as u128)
+            .sum();
+        if reward == 0 || lp_tokens == 0 {
+            return;
+        }
+        for (&account, position) in positions.iter() {
+            let share = (reward * position.lp_tokens.raw() as u128 / lp_tokens) as Uint;
+            if share == 0 {
+                continue;
+            }
+            let rewards = state.accounts.entry(account).or_default();
+            rewards
+                .grants
+                .push(Grant::new(share, state.epoch, self.config.vesting));
+            rewards.compact(state.epoch);
+            state.distributed += share;
+        }
+    }
+
+    fn forfeit(&self, state: &mut RewardsState, account: AccountId, share: Percentage) {
+        let share = match self.config.forfeiture {
+            Forfeiture::Keep => return,
+            Forfeiture::Proportional => share,
+            Forfeiture::All => Percentage::from_raw_amount(SCALE),
+        };
+        let epoch = state.epoch;
+        let Some(rewards) = state.accounts.get_mut(&account) else {
+            return;
+        };
+        let forfeited: Uint = rewards
+            .grants
+            .iter_mut()
+            .map(|grant| grant.forfeit(share, epoch))
+            .sum();
+        rewards.compact(epoch);
+        state.forfeited += forfeited;
+    }
+}
+
+impl PoolHook for LpRewards {
+    fn before_op(&mut self, pool: &LpPool, op: &PoolOp) -> Result<(), HookRejection> {
+        if let PoolOp::RemoveLiquidity {
+            account: Some(account),
+            ..
+        } = *op
+        {
+            self.state().pending_lp_tokens =
+                pool.position(account).map(|position| position.lp_tokens);
+        }
+        Ok(())
+    }
+
+    fn after_op(&mut self, pool: &LpPool, op: &PoolOp, result: &Result<OpOutcome, OpError>) {
+        let mut state = self.state();
+        let lp_tokens_before = state.pending_lp_tokens.take();
+        state.epoch = pool.epoch();
+        if result.is_err() {
+            return;
+        }
+        match *op {
+            PoolOp::AdvanceEpoch { epochs } => self.distribute(&mut state, pool, epochs),
+            PoolOp::RemoveLiquidity {
+                account: Some(account),
+                lp_amount,
+            } => {
+                let Some(lp_tokens) = lp_tokens_before.filter(|lp| lp.raw() > 0) else {
+                    return;
+                };
+                let share = mul_div(lp_amount.raw(), SCALE, lp_tokens.raw()).unwrap_or(SCALE);
+                self.forfeit(&mut state, account, Percentage::from_raw_amount(share));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixture_config;
+
+    const ALICE: AccountId = 1;
+    const BOB: AccountId = 2;
+
+    fn pool(config: RewardsConfig) -> (LpPool, LpRewards) {
+        let rewards = LpRewards::new(config);
+        let mut pool = fixture_config().build();
+        pool.add_hook(rewards.clone());
+        for (account, amount) in [(ALICE, 300), (BOB, 100)] {
+            pool.apply(&PoolOp::AddLiquidity {
+                account: Some(account),
+                amount: amount.into(),
+            })
+            .unwrap();
+        }
+        (pool, rewards)
+    }
+
+    fn vesting(forfeiture: Forfeiture) -> RewardsConfig {
+        RewardsConfig {
+            reward_per_epoch: 40.into(),
+            vesting: Some(VestingSchedule {
+                cliff: 2,
+                duration: 4,
+            }),
+            forfeiture,
+        }
+    }
+
+    fn advance(pool: &mut LpPool, epochs: Epoch) {
+        pool.apply(&PoolOp::AdvanceEpoch { epochs }).unwrap();
+    }
+
+    #[test]
+    fn releases_rewards_after_cliff() {
+        let (mut pool, rewards) = pool(vesting(Forfeiture::Keep));
+        advance(&mut pool, 1);
+        assert_eq!(rewards.unvested(ALICE), 30.into());
+        assert_eq!(rewards.unvested(BOB), 10.into());
+        advance(&mut pool, 0);
+        advance(&mut pool, 1);
+        // the reward earned in epoch 1 passed its cliff in epoch 3, the one of epoch 2
+        // hasn't yet
+        advance(&mut pool, 1);
+        assert_eq!(rewards.claimable_now(ALICE), 15.into());
+        assert_eq!(rewards.claim(ALICE), 15.into());
+        assert_eq!(rewards.claimable_now(ALICE), 0.into());
+        advance(&mut pool, 10);
+        assert_eq!(rewards.claim(ALICE), 75.into());
+        assert_eq!(rewards.unvested(ALICE), 300.into());
+        assert_eq!(rewards.distributed(), 520.into());
+    }
+
+    #[test]
+    fn early_exit_forfeits_unvested_rewards() {
+        let (mut pool, rewards) = pool(vesting(Forfeiture::Proportional));
+        advance(&mut pool, 1);
+        advance(&mut pool, 2);
+        // 15 of the 30 earned in epoch 1 vested, none of the 60 earned in epoch 3,
+        // withdrawing half of the lp tokens forfeits half of the unvested 75
+        pool.apply(&PoolOp::RemoveLiquidity {
+            account: Some(ALICE),
+            lp_amount: 150.into(),
+        })
+        .unwrap();
+        assert_eq!(rewards.claimable_now(ALICE), 15.into());
+        assert_eq!(rewards.unvested(ALICE), 37.5.into());
+        assert_eq!(rewards.forfeited(), 37.5.into());
+        // kept rewards vest linearly until the original end, the ones before their cliff
+        // keep waiting for it
+        advance(&mut pool, 1);
+        assert_eq!(rewards.claimable_now(ALICE), 18.75.into());
+        advance(&mut pool, 1);
+        assert_eq!(rewards.claimable_now(ALICE), 37.5.into());
+
+        let (mut pool, rewards) = pool_with_all_forfeited();
+        pool.apply(&PoolOp::RemoveLiquidity {
+            account: Some(BOB),
+            lp_amount: 1.into(),
+        })
+        .unwrap();
+        assert_eq!(rewards.unvested(BOB), 0.into());
+        assert_eq!(rewards.forfeited(), 10.into());
+    }
+
+    fn pool_with_all_forfeited() -> (LpPool, LpRewards) {
+        let (mut pool, rewards) = pool(vesting(Forfeiture::All));
+        advance(&mut pool, 1);
+        (pool, rewards)
+    }
+
+    #[test]
+    fn unvested_rewards_are_claimable_right_away() {
+        let (mut pool, rewards) = pool(RewardsConfig {
+            vesting: None,
+            ..vesting(Forfeiture::All)
+        });
+        advance(&mut pool, 2);
+        pool.apply(&PoolOp::RemoveLiquidity {
+            account: Some(BOB),
+            lp_amount: 100.into(),
+        })
+        .unwrap();
+        assert_eq!(rewards.claim(BOB), 20.into());
+        assert_eq!(rewards.claim(ALICE), 60.into());
+        assert_eq!(rewards.forfeited(), 0.into());
+        // anonymous liquidity doesn't earn rewards
+        pool.apply(&PoolOp::AddLiquidity {
+            account: None,
+            amount: 100.into(),
+        })
+        .unwrap();
+        advance(&mut pool, 1);
+        assert_eq!(rewards.claim(ALICE), 40.into());
+    }
+
+    #[test]
+    fn caps_rewards_overflowing_uint() {
+        let (mut pool, rewards) = pool(RewardsConfig {
+            reward_per_epoch: TokenAmount::from_raw_amount(Uint::MAX / 2),
+            vesting: None,
+            forfeiture: Forfeiture::Keep,
+        });
+        advance(&mut pool, 3);
+        let claimable =
+            rewards.claimable_now(ALICE).raw() as u128 + rewards.claimable_now(BOB).raw() as u128;
+        assert_eq!(claimable, rewards.distributed().raw() as u128);
+        // shares are rounded down, at most a raw unit per position isn't emitted
+        assert!(rewards.distributed().raw() >= Uint::MAX - 2);
+
+        // what's left to emit is split as well, the total never exceeds `Uint::MAX`
+        advance(&mut pool, 3);
+        assert!(rewards.distributed().raw() as u128 - claimable <= 2);
+    }
+}
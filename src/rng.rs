@@ -0,0 +1,71 @@
+//! Small seeded pseudo random generator, so randomized checks and simulations are
+//! reproducible from a single `u64` seed on every platform.
+
+#[derive(Debug, Clone, PartialEq)]
+/// SplitMix64 generator, good enough statistically for test and simulation inputs but
+/// not suitable for anything security related
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns value uniformly distributed in `0..bound`, `bound` has to be positive
+    pub fn below(&mut self, bound: u64) -> u64 {
+        // rejection sampling avoids modulo bias
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+
+    /// Returns value uniformly distributed in `low..=high`
+    pub fn range(&mut self, low: u64, high: u64) -> u64 {
+        match (high - low).checked_add(1) {
+            Some(bound) => low + self.below(bound),
+            None => self.next_u64(),
+        }
+    }
+
+    /// Returns value uniformly distributed in `0.0..1.0`
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns true with the given probability
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.unit() < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_reproducible_and_bounded() {
+        let (mut rng, mut same) = (Rng::new(42), Rng::new(42));
+        let first = rng.next_u64();
+        assert_eq!(same.next_u64(), first);
+        assert_ne!(Rng::new(43).next_u64(), first);
+
+        assert!((0..1000).all(|_| (5..=7).contains(&rng.range(5, 7))));
+        assert!((0..1000).all(|_| (0.0..1.0).contains(&rng.unit())));
+        // full range can't overflow the bound
+        rng.range(0, u64::MAX);
+    }
+}
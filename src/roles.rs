@@ -0,0 +1,648 @@
+//! Permissions of pool maintainers. A pool wrapped in `PermissionedPool` checks the
+//! caller's role before mutations reserved to maintainers:
+//!
+//! * admin grants and revokes roles, changes fees and parameters, draws on the insurance
+//!   fund and resumes the pool,
+//! * operator updates the price and moves the pool clock forward,
+//! * guardian can only pause the pool, which rejects deposits, withdrawals and swaps.
+//!
+//! Fee changes, transfers of a role to another account and resuming the pool are
+//! `PrivilegedAction`s, scheduled behind a timelock and executable once it passed.
+//!
+//! Anyone may deposit, withdraw and swap while the pool isn't paused, operations attributed
+//! to an account only on behalf of the caller's own one. While it's paused LPs
+//! still exit with `PermissionedPool::emergency_withdraw`. Role changes,
+//! pausing and scheduled actions are emitted as `PoolEvent`s of the wrapped pool, so they
+//! end up in the same event stream as the mutations they authorize.
+
+use std::collections::BTreeSet;
+
+use crate::error::{RoleError, TimelockError};
+use crate::events::PoolEvent;
+use crate::governance::{PendingUpdate, PoolParams, DEFAULT_GOVERNANCE_DELAY};
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::timelock::{Queued, TimelockQueue};
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    Admin,
+    Operator,
+    Guardian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Action taking effect only after the timelock of the pool passed
+pub enum PrivilegedAction {
+    /// changes fees like `LpPool::update_fees`, scheduled by an admin
+    UpdateFees {
+        min_fee: Percentage,
+        max_fee: Percentage,
+    },
+    /// moves the role from the account scheduling the transfer to another one
+    TransferRole {
+        from: AccountId,
+        to: AccountId,
+        role: Role,
+    },
+    /// resumes paused pool, scheduled by an admin
+    Unpause,
+}
+
+#[derive(Debug)]
+/// Pool whose maintenance operations are restricted to accounts holding a role
+pub struct PermissionedPool {
+    pool: LpPool,
+    roles: BTreeSet<(AccountId, Role)>,
+    paused: bool,
+    timelock: TimelockQueue<PrivilegedAction>,
+}
+
+impl PermissionedPool {
+    /// Wraps the pool, granting admin role to `admin`. Privileged actions wait for
+    /// `DEFAULT_GOVERNANCE_DELAY` epochs.
+    pub fn new(pool: LpPool, admin: AccountId) -> Self {
+        Self::with_delay(pool, admin, DEFAULT_GOVERNANCE_DELAY)
+    }
+
+    /// Wraps the pool, granting admin role to `admin`. Privileged actions wait for `delay`
+    /// epochs.
+    pub fn with_delay(pool: LpPool, admin: AccountId, delay: Epoch) -> Self {
+        let mut permissioned = Self {
+            pool,
+            roles: BTreeSet::new(),
+            paused: false,
+            timelock: TimelockQueue::new(delay),
+        };
+        permissioned.roles.insert((admin, Role::Admin));
+        permissioned.pool.emit(PoolEvent::RoleGranted {
+            account: admin,
+            role: Role::Admin,
+            by: admin,
+        });
+        permissioned
+    }
+
+    pub fn pool(&self) -> &LpPool {
+        &self.pool
+    }
+
+    /// Returns the wrapped pool, dropping roles
+    pub fn into_inner(self) -> LpPool {
+        self.pool
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn has_role(&self, account: AccountId, role: Role) -> bool {
+        self.roles.contains(&(account, role))
+    }
+
+    /// Returns roles of the account
+    pub fn roles(&self, account: AccountId) -> Vec<Role> {
+        self.roles
+            .range((account, Role::Admin)..=(account, Role::Guardian))
+            .map(|&(_, role)| role)
+            .collect()
+    }
+
+    /// Returns accounts holding the role in ascending order
+    pub fn holders(&self, role: Role) -> Vec<AccountId> {
+        self.roles
+            .iter()
+            .filter(|&&(_, held)| held == role)
+            .map(|&(account, _)| account)
+            .collect()
+    }
+
+    fn require(&self, account: AccountId, role: Role) -> Result<(), RoleError> {
+        match self.has_role(account, role) {
+            true => Ok(()),
+            false => Err(RoleError::Unauthorized { account, role }),
+        }
+    }
+
+    /// Grants the role to the account, granting a held role does nothing
+    pub fn grant(
+        &mut self,
+        caller: AccountId,
+        account: AccountId,
+        role: Role,
+    ) -> Result<(), RoleError> {
+        self.require(caller, Role::Admin)?;
+        if self.roles.insert((account, role)) {
+            self.pool.emit(PoolEvent::RoleGranted {
+                account,
+                role,
+                by: caller,
+            });
+        }
+        Ok(())
+    }
+
+    /// Revokes the role of the account, revoking a role that isn't held does nothing. The
+    /// pool always keeps at least one admin.
+    pub fn revoke(
+        &mut self,
+        caller: AccountId,
+        account: AccountId,
+        role: Role,
+    ) -> Result<(), RoleError> {
+        self.require(caller, Role::Admin)?;
+        if role == Role::Admin && self.holders(Role::Admin) == [account] {
+            return Err(RoleError::LastAdmin(account));
+        }
+        if self.roles.remove(&(account, role)) {
+            self.pool.emit(PoolEvent::RoleRevoked {
+                account,
+                role,
+                by: caller,
+            });
+        }
+        Ok(())
+    }
+
+    /// Pauses deposits, withdrawals and swaps, callable by guardians and admins
+    pub fn pause(&mut self, caller: AccountId) -> Result<(), RoleError> {
+        if !self.has_role(caller, Role::Admin) {
+            self.require(caller, Role::Guardian)?;
+        }
+        if !self.paused {
+            self.paused = true;
+            self.pool.emit(PoolEvent::Paused { by: caller });
+        }
+        Ok(())
+    }
+
+    /// Withdraws lp tokens from the caller's position while the pool is paused, returning
+    /// the proportional share of both assets. Like every withdrawal it charges no fee and
+    /// swaps nothing, and it skips hooks of the pool so that no hook keeps LP funds
+    /// locked during an incident. Anonymous liquidity isn't attributed to anyone and
+    /// can't be withdrawn this way.
+    pub fn emergency_withdraw(
+        &mut self,
+        caller: AccountId,
+        lp_amount: LpTokenAmount,
+    ) -> Result<(TokenAmount, StakedTokenAmount), RoleError> {
+        if !self.paused {
+            return Err(RoleError::NotPaused);
+        }
+        Ok(self.pool.remove_liquidity_for(caller, lp_amount)?)
+    }
+
+    /// Covers a shortfall from the insurance fund, see `LpPool::cover_shortfall`
+    pub fn cover_shortfall(
+        &mut self,
+        caller: AccountId,
+        amount: TokenAmount,
+    ) -> Result<(), RoleError> {
+        self.require(caller, Role::Admin)?;
+        Ok(self.pool.cover_shortfall(amount)?)
+    }
+
+    /// Returns privileged actions waiting for their timelock
+    pub fn scheduled(&self) -> &[Queued<PrivilegedAction>] {
+        self.timelock.queued()
+    }
+
+    /// Queues the action behind the timelock and returns its id. Changing fees and
+    /// resuming the pool requires admin role, a role can only be transferred by an
+    /// account holding it.
+    pub fn schedule(
+        &mut self,
+        caller: AccountId,
+        action: PrivilegedAction,
+    ) -> Result<u64, RoleError> {
+        match action {
+            PrivilegedAction::UpdateFees { min_fee, max_fee } => {
+                self.require(caller, Role::Admin)?;
+                // invalid fees are reported right away instead of once the timelock passed
+                PoolParams {
+                    min_fee,
+                    max_fee,
+                    ..self.pool.params()
+                }
+                .validate()?;
+            }
+            PrivilegedAction::TransferRole { from, role, .. } => match from == caller {
+                true => self.require(caller, role)?,
+                false => {
+                    return Err(RoleError::Unauthorized {
+                        account: caller,
+                        role,
+                    })
+                }
+            },
+            PrivilegedAction::Unpause => self.require(caller, Role::Admin)?,
+        }
+        let queued = self.timelock.queue(action, self.pool.epoch()).clone();
+        self.pool.emit(PoolEvent::ActionScheduled {
+            id: queued.id,
+            action,
+            executable_at: queued.executable_at,
+        });
+        Ok(queued.id)
+    }
+
+    /// Drops scheduled action, callable by admins
+    pub fn cancel(&mut self, caller: AccountId, id: u64) -> Result<(), RoleError> {
+        self.require(caller, Role::Admin)?;
+        self.timelock.cancel(id)?;
+        self.pool
+            .emit(PoolEvent::ActionCancelled { id, by: caller });
+        Ok(())
+    }
+
+    /// Executes scheduled action whose timelock passed. The action was authorized when
+    /// it was scheduled, so anyone may execute it, only role transfers check again that
+    /// the role is still held.
+    pub fn execute(&mut self, id: u64) -> Result<(), RoleError> {
+        let action = match self.timelock.get(id) {
+            Some(queued) => queued.action,
+            None => return Err(TimelockError::UnknownAction(id).into()),
+        };
+        if let PrivilegedAction::TransferRole { from, role, .. } = action {
+            self.require(from, role)?;
+        }
+        self.timelock.execute(id, self.pool.epoch())?;
+        match action {
+            PrivilegedAction::UpdateFees { min_fee, max_fee } => {
+                self.pool.update_fees(min_fee, max_fee)?
+            }
+            PrivilegedAction::TransferRole { from, to, role } => {
+                self.roles.remove(&(from, role));
+                self.pool.emit(PoolEvent::RoleRevoked {
+                    account: from,
+                    role,
+                    by: from,
+                });
+                if self.roles.insert((to, role)) {
+                    self.pool.emit(PoolEvent::RoleGranted {
+                        account: to,
+                        role,
+                        by: from,
+                    });
+                }
+            }
+            PrivilegedAction::Unpause => {
+                if self.paused {
+                    self.paused = false;
+                    self.pool.emit(PoolEvent::Unpaused);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues parameter change behind the governance timelock
+    pub fn propose_update(
+        &mut self,
+        caller: AccountId,
+        params: PoolParams,
+    ) -> Result<PendingUpdate, RoleError> {
+        self.require(caller, Role::Admin)?;
+        Ok(self.pool.propose_update(params)?)
+    }
+
+    /// Applies queued parameter change once its timelock passed
+    pub fn apply_pending(&mut self, caller: AccountId) -> Result<PoolParams, RoleError> {
+        self.require(caller, Role::Admin)?;
+        Ok(self.pool.apply_pending()?)
+    }
+
+    pub fn set_price(&mut self, caller: AccountId, price: Price) -> Result<(), RoleError> {
+        self.require(caller, Role::Operator)?;
+        Ok(self.pool.set_price(price)?)
+    }
+
+    /// Applies the operation on behalf of the caller. Price updates and epoch changes
+    /// require operator role, other operations are rejected while the pool is paused.
+    /// Operations attributed to an account other than the caller are rejected.
+    pub fn apply(&mut self, caller: AccountId, op: &PoolOp) -> Result<OpOutcome, RoleError> {
+        match op {
+            PoolOp::SetPrice { .. } | PoolOp::AdvanceEpoch { .. } => {
+                self.require(caller, Role::Operator)?
+            }
+            _ if self.paused => return Err(RoleError::Paused),
+            _ => {}
+        }
+        if let Some(account) = op.account().filter(|&account| account != caller) {
+            return Err(RoleError::NotAccountOwner { caller, account });
+        }
+        Ok(self.pool.apply(op)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{GovernanceError, RemoveLiquidityError};
+    use crate::test_utils::fixture_config;
+
+    const ADMIN: AccountId = 1;
+    const OPERATOR: AccountId = 2;
+    const GUARDIAN: AccountId = 3;
+    const USER: AccountId = 4;
+
+    fn pool() -> PermissionedPool {
+        let mut pool = PermissionedPool::new(fixture_config().build(), ADMIN);
+        pool.grant(ADMIN, OPERATOR, Role::Operator).unwrap();
+        pool.grant(ADMIN, GUARDIAN, Role::Guardian).unwrap();
+        pool
+    }
+
+    #[test]
+    fn restricts_operations_to_roles() {
+        let mut pool = pool();
+        let fees = PrivilegedAction::UpdateFees {
+            min_fee: 0.002.into(),
+            max_fee: 0.08.into(),
+        };
+        assert!(matches!(
+            pool.schedule(OPERATOR, fees),
+            Err(RoleError::Unauthorized {
+                account: OPERATOR,
+                role: Role::Admin
+            })
+        ));
+        pool.schedule(ADMIN, fees).unwrap();
+        assert!(matches!(
+            pool.set_price(ADMIN, 2.into()),
+            Err(RoleError::Unauthorized { .. })
+        ));
+        pool.set_price(OPERATOR, 2.into()).unwrap();
+        assert!(matches!(
+            pool.apply(USER, &PoolOp::AdvanceEpoch { epochs: 1 }),
+            Err(RoleError::Unauthorized { .. })
+        ));
+        pool.apply(OPERATOR, &PoolOp::AdvanceEpoch { epochs: 1 })
+            .unwrap();
+        assert!(matches!(
+            pool.grant(GUARDIAN, USER, Role::Admin),
+            Err(RoleError::Unauthorized { .. })
+        ));
+        assert!(matches!(
+            pool.schedule(GUARDIAN, PrivilegedAction::Unpause),
+            Err(RoleError::Unauthorized { .. })
+        ));
+        assert!(matches!(
+            pool.revoke(ADMIN, ADMIN, Role::Admin),
+            Err(RoleError::LastAdmin(ADMIN))
+        ));
+        assert!(matches!(
+            pool.cover_shortfall(GUARDIAN, 1.into()),
+            Err(RoleError::Unauthorized { .. })
+        ));
+        assert!(matches!(
+            pool.cover_shortfall(ADMIN, 1.into()),
+            Err(RoleError::Insurance(_))
+        ));
+        assert_eq!(pool.pool().price(), 2.into());
+        assert_eq!(pool.roles(ADMIN), [Role::Admin]);
+    }
+
+    #[test]
+    fn pausing_rejects_user_operations() {
+        let mut pool = pool();
+        let deposit = PoolOp::AddLiquidity {
+            account: Some(USER),
+            amount: 100.into(),
+        };
+        pool.apply(USER, &deposit).unwrap();
+        pool.pause(GUARDIAN).unwrap();
+        assert!(matches!(
+            pool.apply(
+                USER,
+                &PoolOp::Swap {
+                    account: None,
+                    amount: 1.into()
+                }
+            ),
+            Err(RoleError::Paused)
+        ));
+        assert!(matches!(pool.apply(USER, &deposit), Err(RoleError::Paused)));
+        // maintenance goes on while paused
+        pool.set_price(OPERATOR, 1.6.into()).unwrap();
+        let unpause = pool.schedule(ADMIN, PrivilegedAction::Unpause).unwrap();
+        pool.apply(OPERATOR, &PoolOp::AdvanceEpoch { epochs: 2 })
+            .unwrap();
+        pool.execute(unpause).unwrap();
+        pool.apply(
+            USER,
+            &PoolOp::Swap {
+                account: None,
+                amount: 1.into(),
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn emits_role_changes() {
+        let mut pool = pool();
+        pool.revoke(ADMIN, GUARDIAN, Role::Guardian).unwrap();
+        assert!(matches!(
+            pool.pause(GUARDIAN),
+            Err(RoleError::Unauthorized { .. })
+        ));
+        pool.pause(ADMIN).unwrap();
+        let mut inner = pool.into_inner();
+        assert_eq!(
+            inner.drain_events(),
+            [
+                PoolEvent::RoleGranted {
+                    account: ADMIN,
+                    role: Role::Admin,
+                    by: ADMIN
+                },
+                PoolEvent::RoleGranted {
+                    account: OPERATOR,
+                    role: Role::Operator,
+                    by: ADMIN
+                },
+                PoolEvent::RoleGranted {
+                    account: GUARDIAN,
+                    role: Role::Guardian,
+                    by: ADMIN
+                },
+                PoolEvent::RoleRevoked {
+                    account: GUARDIAN,
+                    role: Role::Guardian,
+                    by: ADMIN
+                },
+                PoolEvent::Paused { by: ADMIN },
+            ]
+        );
+    }
+
+    #[test]
+    fn privileged_actions_wait_for_timelock() {
+        let mut pool = PermissionedPool::with_delay(fixture_config().build(), ADMIN, 3);
+        pool.grant(ADMIN, OPERATOR, Role::Operator).unwrap();
+        let fees = pool
+            .schedule(
+                ADMIN,
+                PrivilegedAction::UpdateFees {
+                    min_fee: 0.002.into(),
+                    max_fee: 0.08.into(),
+                },
+            )
+            .unwrap();
+        let transfer = PrivilegedAction::TransferRole {
+            from: ADMIN,
+            to: USER,
+            role: Role::Admin,
+        };
+        assert!(matches!(
+            pool.schedule(OPERATOR, transfer),
+            Err(RoleError::Unauthorized { .. })
+        ));
+        let transfer = pool.schedule(ADMIN, transfer).unwrap();
+        let cancelled = pool.schedule(ADMIN, PrivilegedAction::Unpause).unwrap();
+        assert!(matches!(
+            pool.schedule(
+                ADMIN,
+                PrivilegedAction::UpdateFees {
+                    min_fee: 0.1.into(),
+                    max_fee: 0.08.into(),
+                }
+            ),
+            Err(RoleError::Governance(GovernanceError::InvalidFees { .. }))
+        ));
+
+        assert!(matches!(
+            pool.execute(fees),
+            Err(RoleError::Timelock(TimelockError::NotReady {
+                executable_at: 3,
+                ..
+            }))
+        ));
+        pool.cancel(ADMIN, cancelled).unwrap();
+        pool.apply(OPERATOR, &PoolOp::AdvanceEpoch { epochs: 3 })
+            .unwrap();
+        pool.execute(fees).unwrap();
+        pool.execute(transfer).unwrap();
+        assert!(matches!(
+            pool.execute(cancelled),
+            Err(RoleError::Timelock(TimelockError::UnknownAction(_)))
+        ));
+
+        assert_eq!(pool.pool().params().max_fee, 0.08.into());
+        assert_eq!(pool.holders(Role::Admin), [USER]);
+        assert!(pool.scheduled().is_empty());
+        let mut inner = pool.into_inner();
+        let events = inner.drain_events();
+        assert!(events.contains(&PoolEvent::ActionScheduled {
+            id: transfer,
+            action: transfer_action(),
+            executable_at: 3
+        }));
+        assert!(events.contains(&PoolEvent::ActionCancelled {
+            id: cancelled,
+            by: ADMIN
+        }));
+        assert!(events.ends_with(&[
+            PoolEvent::RoleRevoked {
+                account: ADMIN,
+                role: Role::Admin,
+                by: ADMIN
+            },
+            PoolEvent::RoleGranted {
+                account: USER,
+                role: Role::Admin,
+                by: ADMIN
+            },
+        ]));
+    }
+
+    fn transfer_action() -> PrivilegedAction {
+        PrivilegedAction::TransferRole {
+            from: ADMIN,
+            to: USER,
+            role: Role::Admin,
+        }
+    }
+
+    #[test]
+    fn lps_exit_paused_pool() {
+        let mut pool = pool();
+        for (account, amount) in [(USER, 100), (OPERATOR, 50)] {
+            pool.apply(
+                account,
+                &PoolOp::AddLiquidity {
+                    account: Some(account),
+                    amount: amount.into(),
+                },
+            )
+            .unwrap();
+        }
+        pool.apply(
+            USER,
+            &PoolOp::Swap {
+                account: None,
+                amount: 30.into(),
+            },
+        )
+        .unwrap();
+        assert!(matches!(
+            pool.emergency_withdraw(USER, 1.into()),
+            Err(RoleError::NotPaused)
+        ));
+
+        pool.pause(GUARDIAN).unwrap();
+        let balances = pool.pool().balances();
+        let (tokens, staked) = pool.emergency_withdraw(USER, 100.into()).unwrap();
+        // two thirds of both assets, nothing swapped or charged
+        assert_eq!(tokens.raw(), balances.tokens.raw() * 2 / 3);
+        assert_eq!(staked, 20.into());
+        assert!(matches!(
+            pool.emergency_withdraw(USER, 1.into()),
+            Err(RoleError::RemoveLiquidity(
+                RemoveLiquidityError::PositionNotEnoughTokens { .. }
+            ))
+        ));
+        pool.emergency_withdraw(OPERATOR, 50.into()).unwrap();
+        assert_eq!(pool.pool().lp_token_amount(), 0.into());
+    }
+
+    #[test]
+    fn rejects_operations_on_behalf_of_other_accounts() {
+        let mut pool = pool();
+        let deposit = PoolOp::AddLiquidity {
+            account: Some(USER),
+            amount: 100.into(),
+        };
+        assert!(matches!(
+            pool.apply(OPERATOR, &deposit),
+            Err(RoleError::NotAccountOwner {
+                caller: OPERATOR,
+                account: USER
+            })
+        ));
+        pool.apply(USER, &deposit).unwrap();
+
+        let withdrawal = PoolOp::RemoveLiquidity {
+            account: Some(USER),
+            lp_amount: 100.into(),
+        };
+        assert!(matches!(
+            pool.apply(GUARDIAN, &withdrawal),
+            Err(RoleError::NotAccountOwner { .. })
+        ));
+        assert!(matches!(
+            pool.apply(
+                GUARDIAN,
+                &PoolOp::Swap {
+                    account: Some(USER),
+                    amount: 1.into(),
+                }
+            ),
+            Err(RoleError::NotAccountOwner { .. })
+        ));
+        assert_eq!(pool.pool().position(USER).unwrap().lp_tokens, 100.into());
+        pool.apply(USER, &withdrawal).unwrap();
+    }
+}
@@ -0,0 +1,477 @@
+//! JSON-RPC 2.0 interface following Solana RPC conventions, so existing Solana tooling
+//! can query the simulated pool. Results are wrapped in `{"context": {"slot": ...},
+//! "value": ...}` with the pool epoch used as the slot, token amounts are encoded like
+//! `UiTokenAmount` with the raw value as a string and account data as base64.
+//!
+//! | method          | params                                     |
+//! |-----------------|--------------------------------------------|
+//! | `getQuote`      | `[amount]`                                 |
+//! | `simulateSwap`  | `[amount]`                                 |
+//! | `getPoolState`  | `[{"encoding": "base64" \| "jsonParsed"}]` |
+//!
+//! Amounts are raw staked token amounts given as integers or, for values above 2^53,
+//! as decimal strings. Batch requests are supported.
+
+use std::io::{self, BufReader};
+use std::net::{TcpListener, TcpStream};
+
+use crate::account::{PoolAccount, POOL_ACCOUNT_SPACE, POOL_ACCOUNT_VERSION};
+use crate::encoding::base64_encode;
+use crate::error::SwapError;
+use crate::http::{read_request, write_response};
+use crate::json::Value;
+use crate::lp_pool::LpPool;
+use crate::shared::SharedPool;
+use crate::types::*;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// server error code used for requests rejected by the pool
+const POOL_ERROR: i64 = -32002;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Error object of a failed call
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+
+    fn invalid_params(message: impl ToString) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+
+    fn to_json(&self) -> Value {
+        let mut fields = vec![
+            ("code".to_string(), Value::number(self.code)),
+            ("message".to_string(), Value::String(self.message.clone())),
+        ];
+        fields.extend(self.data.clone().map(|data| ("data".to_string(), data)));
+        Value::Object(fields)
+    }
+}
+
+impl From<SwapError> for RpcError {
+    fn from(error: SwapError) -> Self {
+        Self {
+            data: Some(swap_error(&error)),
+            ..Self::new(POOL_ERROR, error)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// JSON-RPC server over a shared pool
+pub struct RpcServer {
+    pool: SharedPool,
+}
+
+impl RpcServer {
+    pub fn new(pool: SharedPool) -> Self {
+        Self { pool }
+    }
+
+    /// Handles single or batch request body and returns the response document
+    pub fn handle(&self, body: &str) -> Value {
+        match Value::parse(body) {
+            Ok(Value::Array(requests)) if !requests.is_empty() => {
+                Value::Array(requests.iter().map(|request| self.call(request)).collect())
+            }
+            Ok(request @ Value::Object(_)) => self.call(&request),
+            Ok(_) => response(
+                Value::Null,
+                Err(RpcError::new(INVALID_REQUEST, "invalid request")),
+            ),
+            Err(error) => response(Value::Null, Err(RpcError::new(PARSE_ERROR, error))),
+        }
+    }
+
+    /// Serves `POST` requests from the listener until accepting a connection fails.
+    /// Every connection is handled on its own thread and closed after a single request.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            let server = self.clone();
+            std::thread::spawn(move || server.handle_connection(stream));
+        }
+    }
+
+    fn handle_connection(&self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let (status, body) = match read_request(&mut reader) {
+            Ok((method, _, body)) if method == "POST" => (200, self.handle(&body)),
+            Ok((method, _, _)) => (
+                405,
+                response(
+                    Value::Null,
+                    Err(RpcError::new(
+                        INVALID_REQUEST,
+                        format!("{method} is not allowed"),
+                    )),
+                ),
+            ),
+            Err(error) => (
+                400,
+                response(Value::Null, Err(RpcError::new(PARSE_ERROR, error))),
+            ),
+        };
+        write_response(stream, status, &body)
+    }
+
+    fn call(&self, request: &Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        if request.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+            return response(
+                id,
+                Err(RpcError::new(INVALID_REQUEST, "expected jsonrpc 2.0")),
+            );
+        }
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return response(id, Err(RpcError::new(INVALID_REQUEST, "missing method")));
+        };
+        let params = match request.get("params") {
+            None => &[][..],
+            Some(Value::Array(params)) => params.as_slice(),
+            Some(_) => {
+                return response(
+                    id,
+                    Err(RpcError::invalid_params("params have to be an array")),
+                )
+            }
+        };
+
+        let result = self.pool.with(|pool| {
+            let value = match method {
+                "getQuote" => quote(pool, params),
+                "simulateSwap" => simulate_swap(pool, params),
+                "getPoolState" => pool_state(pool, params),
+                _ => Err(RpcError::new(
+                    METHOD_NOT_FOUND,
+                    format!("method `{method}` not found"),
+                )),
+            }?;
+            Ok(Value::object([
+                (
+                    "context",
+                    Value::object([("slot", Value::number(pool.epoch()))]),
+                ),
+                ("value", value),
+            ]))
+        });
+        response(id, result)
+    }
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    let (key, value) = match result {
+        Ok(result) => ("result", result),
+        Err(error) => ("error", error.to_json()),
+    };
+    Value::object([("jsonrpc", Value::from("2.0")), (key, value), ("id", id)])
+}
+
+fn quote(pool: &LpPool, params: &[Value]) -> Result<Value, RpcError> {
+    let amount = amount_param(params)?;
+    Ok(Value::object([
+        ("amountIn", ui_amount(amount.raw())),
+        ("amountOut", ui_amount(pool.amount_for_swap(amount)?.raw())),
+        ("fee", ui_string(pool.fee_for_swap(amount)?.raw())),
+    ]))
+}
+
+/// Runs swap on a copy of the pool, like `simulateTransaction` failures are reported
+/// in `err` instead of failing the call
+fn simulate_swap(pool: &LpPool, params: &[Value]) -> Result<Value, RpcError> {
+    let amount = amount_param(params)?;
+    let mut simulated = LpPool::from_snapshot(&pool.to_snapshot())
+        .expect("snapshot of a live pool can be restored");
+    let fee = simulated.fee_for_swap(amount);
+    let (err, logs, amount_out) = match simulated.swap(amount) {
+        Ok(amount_out) => (
+            Value::Null,
+            vec![format!(
+                "Program log: swapped {} staked tokens for {} tokens",
+                amount.raw(),
+                amount_out.raw()
+            )],
+            ui_amount(amount_out.raw()),
+        ),
+        Err(error) => (
+            swap_error(&error),
+            vec![format!("Program log: Error: {error}")],
+            Value::Null,
+        ),
+    };
+    Ok(Value::object([
+        ("err", err),
+        (
+            "logs",
+            Value::Array(logs.into_iter().map(Value::String).collect()),
+        ),
+        ("amountOut", amount_out),
+        (
+            "fee",
+            fee.map(|fee| ui_string(fee.raw())).unwrap_or(Value::Null),
+        ),
+        (
+            "accounts",
+            Value::Array(vec![base64_account(&simulated.to_account())]),
+        ),
+    ]))
+}
+
+fn pool_state(pool: &LpPool, params: &[Value]) -> Result<Value, RpcError> {
+    let encoding = match params.first() {
+        None => "base64",
+        Some(config) => match config.get("encoding") {
+            None => "base64",
+            Some(encoding) => encoding
+                .as_str()
+                .ok_or_else(|| RpcError::invalid_params("`encoding` has to be a string"))?,
+        },
+    };
+    let account = pool.to_account();
+    match encoding {
+        "base64" => Ok(base64_account(&account)),
+        "jsonParsed" => Ok(Value::object([
+            (
+                "data",
+                Value::object([
+                    ("parsed", parsed_account(&account)),
+                    ("space", Value::number(POOL_ACCOUNT_SPACE)),
+                ]),
+            ),
+            ("space", Value::number(POOL_ACCOUNT_SPACE)),
+        ])),
+        _ => Err(RpcError::invalid_params(format!(
+            "unsupported encoding `{encoding}`"
+        ))),
+    }
+}
+
+fn amount_param(params: &[Value]) -> Result<StakedTokenAmount, RpcError> {
+    let raw = match params.first() {
+        Some(Value::String(amount)) => amount.parse().ok(),
+        Some(amount) => amount.as_u64(),
+        None => return Err(RpcError::invalid_params("missing amount")),
+    };
+    raw.map(StakedTokenAmount::from_raw_amount).ok_or_else(|| {
+        RpcError::invalid_params("amount has to be an unsigned integer or integer string")
+    })
+}
+
+/// Encodes raw amount like Solana's `UiTokenAmount`
+fn ui_amount(raw: Uint) -> Value {
+    Value::object([
+        ("amount", Value::String(raw.to_string())),
+        ("decimals", Value::number(SCALE.ilog10())),
+        ("uiAmountString", ui_string(raw)),
+    ])
+}
+
+/// Formats raw fixed-point value as a decimal without trailing zeros
+fn ui_string(raw: Uint) -> Value {
+    let decimal = TokenAmount::from_raw_amount(raw).to_string();
+    Value::from(decimal.trim_end_matches('0').trim_end_matches('.'))
+}
+
+fn base64_account(account: &PoolAccount) -> Value {
+    Value::object([
+        (
+            "data",
+            Value::Array(vec![
+                Value::String(base64_encode(&account.to_account_data())),
+                Value::from("base64"),
+            ]),
+        ),
+        ("space", Value::number(POOL_ACCOUNT_SPACE)),
+    ])
+}
+
+/// Account fields with 64 bit integers encoded as strings
+fn parsed_account(account: &PoolAccount) -> Value {
+    let integer = |value: u64| Value::String(value.to_string());
+    let optional = |value: Option<u64>| value.map(integer).unwrap_or(Value::Null);
+    Value::object([
+        ("version", Value::number(POOL_ACCOUNT_VERSION)),
+        ("price", integer(account.price)),
+        ("priceUpdatedAt", integer(account.price_updated_at)),
+        ("tokenAmount", integer(account.token_amount)),
+        ("stTokenAmount", integer(account.st_token_amount)),
+        ("lpTokenAmount", integer(account.lp_token_amount)),
+        ("liquidityTarget", integer(account.liquidity_target)),
+        ("minFee", integer(account.min_fee)),
+        ("maxFee", integer(account.max_fee)),
+        ("treasuryCut", integer(account.treasury_cut)),
+        ("epoch", integer(account.epoch)),
+        ("maxPriceAge", optional(account.max_price_age)),
+        ("maxPriceDeviation", optional(account.max_price_deviation)),
+        ("maxFeeChange", optional(account.max_fee_change)),
+        ("monotonicPrice", Value::Bool(account.monotonic_price)),
+        ("totalFees", integer(account.total_fees)),
+        ("lpFees", integer(account.lp_fees)),
+        ("treasuryFees", integer(account.treasury_fees)),
+        (
+            "claimableTreasuryFees",
+            integer(account.claimable_treasury_fees),
+        ),
+    ])
+}
+
+fn swap_error(error: &SwapError) -> Value {
+    let kind = match error {
+        SwapError::ZeroTokensAsArgument => "ZeroAmount",
+        SwapError::AmountTooBig => "AmountTooBig",
+        SwapError::PoolNotEnoughTokens { .. } => "NotEnoughLiquidity",
+        SwapError::StalePrice { .. } => "StalePrice",
+        SwapError::Oracle(_) => "OracleUnavailable",
+    };
+    Value::object([
+        ("kind", Value::from(kind)),
+        ("message", Value::String(error.to_string())),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::*;
+    use crate::encoding::base64_decode;
+
+    fn server() -> RpcServer {
+        let mut pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        pool.add_liquidity(100.into()).unwrap();
+        RpcServer::new(SharedPool::new(pool))
+    }
+
+    fn value<'a>(response: &'a Value, path: &[&str]) -> &'a Value {
+        path.iter().fold(response, |value, key| {
+            value.get(key).unwrap_or_else(|| panic!("missing {key}"))
+        })
+    }
+
+    #[test]
+    fn answers_solana_style_calls() {
+        let server = server();
+        let quote =
+            server.handle(r#"{"jsonrpc":"2.0","id":1,"method":"getQuote","params":[6000000]}"#);
+        assert_eq!(value(&quote, &["id"]), &Value::number(1));
+        assert_eq!(
+            value(&quote, &["result", "context", "slot"]),
+            &Value::number(0)
+        );
+        assert_eq!(
+            value(&quote, &["result", "value", "amountOut"]),
+            &Value::object([
+                ("amount", Value::from("8991000")),
+                ("decimals", Value::number(6)),
+                ("uiAmountString", Value::from("8.991")),
+            ])
+        );
+
+        let simulated = server
+            .handle(r#"{"jsonrpc":"2.0","id":"a","method":"simulateSwap","params":["6000000"]}"#);
+        assert_eq!(value(&simulated, &["result", "value", "err"]), &Value::Null);
+        let data = value(&simulated, &["result", "value", "accounts"])
+            .index(0)
+            .and_then(|account| account.get("data"))
+            .and_then(|data| data.index(0))
+            .and_then(Value::as_str)
+            .unwrap();
+        let account = PoolAccount::from_borsh(&base64_decode(data).unwrap()).unwrap();
+        assert_eq!(account.st_token_amount, 6_000_000);
+
+        // simulation leaves the pool untouched
+        let state = server.handle(
+            r#"{"jsonrpc":"2.0","id":2,"method":"getPoolState","params":[{"encoding":"jsonParsed"}]}"#,
+        );
+        assert_eq!(
+            value(
+                &state,
+                &["result", "value", "data", "parsed", "stTokenAmount"]
+            ),
+            &Value::from("0")
+        );
+
+        let batch = server.handle(
+            r#"[{"jsonrpc":"2.0","id":1,"method":"getPoolState"},{"jsonrpc":"2.0","id":2,"method":"getQuote","params":[0]}]"#,
+        );
+        let batch = batch.as_array().unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(
+            value(&batch[1], &["error", "data", "kind"]),
+            &Value::from("ZeroAmount")
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let server = server();
+        let code = |body: &str| value(&server.handle(body), &["error", "code"]).as_i64();
+
+        assert_eq!(code("{"), Some(PARSE_ERROR));
+        assert_eq!(
+            code(r#"{"id":1,"method":"getQuote"}"#),
+            Some(INVALID_REQUEST)
+        );
+        assert_eq!(
+            code(r#"{"jsonrpc":"2.0","id":1,"method":"sendTransaction"}"#),
+            Some(METHOD_NOT_FOUND)
+        );
+        assert_eq!(
+            code(r#"{"jsonrpc":"2.0","id":1,"method":"getQuote","params":[-1]}"#),
+            Some(INVALID_PARAMS)
+        );
+        assert_eq!(
+            code(
+                r#"{"jsonrpc":"2.0","id":1,"method":"getPoolState","params":[{"encoding":"base58"}]}"#
+            ),
+            Some(INVALID_PARAMS)
+        );
+        assert_eq!(
+            code(r#"{"jsonrpc":"2.0","id":1,"method":"getQuote","params":[1000000000]}"#),
+            Some(POOL_ERROR)
+        );
+
+        let failed = server
+            .handle(r#"{"jsonrpc":"2.0","id":1,"method":"simulateSwap","params":[1000000000]}"#);
+        assert_eq!(
+            value(&failed, &["result", "value", "err", "kind"]),
+            &Value::from("NotEnoughLiquidity")
+        );
+    }
+
+    #[test]
+    fn serves_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = server();
+        std::thread::spawn(move || server.serve(listener));
+
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"getQuote","params":[6000000]}"#;
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(r#""uiAmountString":"8.991""#));
+    }
+}
@@ -0,0 +1,244 @@
+//! Canonical scenarios exercising every pool feature, with a serialized record of the
+//! pool state after each of their steps. The records are compared against documents
+//! checked in under `snapshots/`, so a change to rounding or to any formula shows up as
+//! an explicit diff of the affected steps in review. After an intended change the
+//! documents are regenerated by running the tests with `UPDATE_SNAPSHOTS=1`.
+
+use crate::error::OpError;
+use crate::fee_policy::FeePolicy;
+use crate::governance::PoolParams;
+use crate::json::Value;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::price_smoothing::PriceSmoothing;
+use crate::replay::PoolConfig;
+use crate::schema::ToJson;
+use crate::surcharge::{SurchargeConfig, SurchargeDecay};
+use crate::types::*;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Named sequence of operations applied to a pool built from the configuration
+pub struct Scenario {
+    pub name: &'static str,
+    pub config: PoolConfig,
+    pub ops: Vec<PoolOp>,
+}
+
+impl Scenario {
+    /// Applies operations to a fresh pool and records every step, the initial state
+    /// included, as `{"step", "op", "outcome", "state"}` objects
+    pub fn record(&self) -> Value {
+        let mut pool = self.config.build();
+        let mut steps = vec![Value::object([
+            ("step", 0usize.to_json()),
+            ("op", Value::Null),
+            ("outcome", Value::Null),
+            ("state", pool.to_json()),
+        ])];
+        for (index, op) in self.ops.iter().enumerate() {
+            let outcome = pool.apply(op);
+            steps.push(Value::object([
+                ("step", (index + 1).to_json()),
+                ("op", op_to_json(op)),
+                ("outcome", outcome_to_json(&outcome)),
+                ("state", pool.to_json()),
+            ]));
+        }
+        Value::Array(steps)
+    }
+}
+
+fn op_to_json(op: &PoolOp) -> Value {
+    Value::object([
+        ("name", Value::from(op.name())),
+        ("account", op.account().to_json()),
+        ("amount", op.raw_amount().to_json()),
+    ])
+}
+
+fn outcome_to_json(outcome: &Result<OpOutcome, OpError>) -> Value {
+    match outcome {
+        Ok(OpOutcome::LiquidityAdded(lp_amount)) => {
+            Value::object([("lp_amount", lp_amount.to_json())])
+        }
+        Ok(OpOutcome::LiquidityRemoved(amount, st_amount)) => Value::object([
+            ("amount", amount.to_json()),
+            ("st_amount", st_amount.to_json()),
+        ]),
+        Ok(OpOutcome::Swapped(amount)) => Value::object([("amount", amount.to_json())]),
+        Ok(OpOutcome::PriceSet | OpOutcome::EpochAdvanced) => Value::object::<&str>([]),
+        Err(error) => Value::object([("error", Value::from(error.to_string()))]),
+    }
+}
+
+fn params(min_fee: f64, max_fee: f64, liquidity_target: u64) -> PoolParams {
+    PoolParams {
+        min_fee: min_fee.into(),
+        max_fee: max_fee.into(),
+        liquidity_target: liquidity_target.into(),
+    }
+}
+
+fn add(account: Option<AccountId>, amount: u64) -> PoolOp {
+    PoolOp::AddLiquidity {
+        account,
+        amount: amount.into(),
+    }
+}
+
+fn remove(account: Option<AccountId>, lp_amount: LpTokenAmount) -> PoolOp {
+    PoolOp::RemoveLiquidity { account, lp_amount }
+}
+
+fn swap(amount: StakedTokenAmount) -> PoolOp {
+    PoolOp::Swap {
+        account: None,
+        amount,
+    }
+}
+
+fn set_price(price: f64) -> PoolOp {
+    PoolOp::SetPrice {
+        price: price.into(),
+    }
+}
+
+/// Returns scenarios covering deposits, swaps, withdrawals, accounts, epochs, price
+/// updates and every optional fee setting, each of them kept short enough to be reviewed
+/// step by step
+pub fn canonical_scenarios() -> Vec<Scenario> {
+    let mut fees = PoolConfig::new(1.2.into(), params(0.001, 0.09, 1_000));
+    fees.treasury_cut = 0.2.into();
+    fees.fee_policy = FeePolicy::VolatilitySensitive {
+        sensitivity: 2.0.into(),
+        max_surcharge: 0.02.into(),
+    };
+    fees.surcharge = Some(SurchargeConfig {
+        large_swap_threshold: 0.1.into(),
+        surcharge: 0.01.into(),
+        decay_rate: 0.5.into(),
+        decay: SurchargeDecay::PerOperation,
+    });
+
+    let mut prices = PoolConfig::new(1.1.into(), params(0.003, 0.05, 500));
+    prices.price_smoothing = PriceSmoothing::Ema { alpha: 0.5.into() };
+    prices.max_price_deviation = Some(0.1.into());
+    prices.monotonic_price = true;
+
+    vec![
+        Scenario {
+            name: "basic_unstake",
+            config: PoolConfig::new(1.5.into(), params(0.001, 0.09, 90)),
+            ops: vec![
+                add(None, 100),
+                swap(6.into()),
+                add(None, 10),
+                swap(30.into()),
+                remove(None, 109.9991.into()),
+            ],
+        },
+        Scenario {
+            name: "accounts",
+            config: PoolConfig::new(1.0.into(), params(0.001, 0.05, 200)),
+            ops: vec![
+                add(Some(0), 150),
+                add(Some(1), 50),
+                swap(80.into()),
+                PoolOp::AdvanceEpoch { epochs: 1 },
+                remove(Some(1), 25.into()),
+                add(Some(0), 30),
+                remove(Some(0), 100.into()),
+                remove(Some(1), 100.into()),
+            ],
+        },
+        Scenario {
+            name: "fee_settings",
+            config: fees,
+            ops: vec![
+                add(None, 1_000),
+                swap(50.into()),
+                swap(150.into()),
+                PoolOp::AdvanceEpoch { epochs: 1 },
+                set_price(1.25),
+                swap(10.into()),
+                swap(StakedTokenAmount::from_raw_amount(1)),
+            ],
+        },
+        Scenario {
+            name: "price_updates",
+            config: prices,
+            ops: vec![
+                add(None, 500),
+                set_price(1.15),
+                PoolOp::AdvanceEpoch { epochs: 3 },
+                set_price(1.5),
+                set_price(1.12),
+                swap(100.into()),
+                swap(1_000.into()),
+                remove(None, 250.into()),
+            ],
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    /// Compares recorded scenario with its checked in document. Mismatches are written
+    /// next to it with a `.new` suffix, or over it when `UPDATE_SNAPSHOTS` is set.
+    fn assert_snapshot(scenario: &Scenario) -> Result<(), String> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots");
+        let path = dir.join(format!("{}.json", scenario.name));
+        let actual = scenario.record().to_pretty_string() + "\n";
+        let expected = fs::read_to_string(&path).unwrap_or_default();
+        if actual == expected {
+            return Ok(());
+        }
+
+        let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+        let target = match update {
+            true => path.clone(),
+            false => path.with_extension("json.new"),
+        };
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&target, &actual).unwrap();
+        if update {
+            return Ok(());
+        }
+        let line = actual
+            .lines()
+            .zip(expected.lines().chain(std::iter::repeat("")))
+            .position(|(actual, expected)| actual != expected)
+            .unwrap_or(expected.lines().count());
+        Err(format!(
+            "snapshot {} differs from line {}, review {} and rerun with UPDATE_SNAPSHOTS=1",
+            path.display(),
+            line + 1,
+            target.display()
+        ))
+    }
+
+    #[test]
+    fn scenarios_match_snapshots() {
+        let failures: Vec<_> = canonical_scenarios()
+            .iter()
+            .filter_map(|scenario| assert_snapshot(scenario).err())
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn records_every_step() {
+        for scenario in canonical_scenarios() {
+            let record = scenario.record();
+            let steps = record.as_array().unwrap();
+            assert_eq!(steps.len(), scenario.ops.len() + 1, "{}", scenario.name);
+            // every scenario ends in a state which restores into an equal pool
+            let state = steps.last().unwrap().get("state").unwrap();
+            let pool = <crate::lp_pool::LpPool as crate::schema::FromJson>::from_json(state);
+            assert_eq!(&pool.unwrap().to_json(), state, "{}", scenario.name);
+        }
+    }
+}
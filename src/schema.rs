@@ -0,0 +1,226 @@
+//! Versioned serialization of pool state into `json::Value` documents. Every persisted
+//! type implements `ToJson`/`FromJson` next to its definition. Fields added in later
+//! schema versions are read with `field_or_default`, so older documents keep loading.
+
+use duplicate::duplicate_item;
+
+use crate::error::SchemaError;
+use crate::json::Value;
+use crate::types::*;
+
+/// version written into every serialized pool, bumped on incompatible layout changes
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// Converts value into a JSON document
+pub trait ToJson {
+    fn to_json(&self) -> Value;
+}
+
+/// Restores value from a JSON document produced by `ToJson`
+pub trait FromJson: Sized {
+    fn from_json(value: &Value) -> Result<Self, SchemaError>;
+}
+
+/// Reads required field of an object
+pub fn field<T: FromJson>(value: &Value, key: &str) -> Result<T, SchemaError> {
+    let field = value
+        .get(key)
+        .ok_or_else(|| SchemaError::MissingField { path: key.into() })?;
+    T::from_json(field).map_err(|error| error.in_field(key))
+}
+
+/// Reads optional field of an object, missing fields are replaced with the default value
+pub fn field_or_default<T: FromJson + Default>(value: &Value, key: &str) -> Result<T, SchemaError> {
+    match value.get(key) {
+        Some(_) => field(value, key),
+        None => Ok(T::default()),
+    }
+}
+
+fn invalid(expected: &str) -> SchemaError {
+    SchemaError::InvalidValue {
+        path: String::new(),
+        reason: format!("expected {expected}"),
+    }
+}
+
+/// Parses number literal of any integer type
+fn integer<T: std::str::FromStr>(value: &Value) -> Result<T, SchemaError> {
+    match value {
+        Value::Number(literal) => literal.parse().map_err(|_| invalid("integer")),
+        _ => Err(invalid("integer")),
+    }
+}
+
+#[duplicate_item(ImplName; [u64]; [i64]; [u128]; [i128])]
+impl ToJson for ImplName {
+    fn to_json(&self) -> Value {
+        Value::number(self)
+    }
+}
+
+#[duplicate_item(ImplName; [u64]; [i64]; [u128]; [i128])]
+impl FromJson for ImplName {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        integer(value)
+    }
+}
+
+impl ToJson for Value {
+    fn to_json(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl FromJson for Value {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        Ok(value.clone())
+    }
+}
+
+impl ToJson for usize {
+    fn to_json(&self) -> Value {
+        Value::number(self)
+    }
+}
+
+impl FromJson for usize {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        integer(value)
+    }
+}
+
+impl ToJson for bool {
+    fn to_json(&self) -> Value {
+        Value::Bool(*self)
+    }
+}
+
+impl FromJson for bool {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        value.as_bool().ok_or_else(|| invalid("bool"))
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> Value {
+        Value::String(self.clone())
+    }
+}
+
+impl FromJson for String {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        value
+            .as_str()
+            .map(Into::into)
+            .ok_or_else(|| invalid("string"))
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> Value {
+        self.as_ref().map_or(Value::Null, ToJson::to_json)
+    }
+}
+
+impl<T: FromJson> FromJson for Option<T> {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_json(value).map(Some),
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn to_json(&self) -> Value {
+        Value::Array(self.iter().map(ToJson::to_json).collect())
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> Value {
+        self.as_slice().to_json()
+    }
+}
+
+impl<T: FromJson> FromJson for Vec<T> {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        value
+            .as_array()
+            .ok_or_else(|| invalid("array"))?
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                T::from_json(item).map_err(|error| error.in_field(&index.to_string()))
+            })
+            .collect()
+    }
+}
+
+// amounts are stored as raw fixed-point integers so they round trip exactly
+#[duplicate_item(ImplName; [TokenAmount]; [StakedTokenAmount]; [LpTokenAmount]; [Price]; [Percentage])]
+impl ToJson for ImplName {
+    fn to_json(&self) -> Value {
+        self.raw().to_json()
+    }
+}
+
+#[duplicate_item(ImplName; [TokenAmount]; [StakedTokenAmount]; [LpTokenAmount]; [Price]; [Percentage])]
+impl FromJson for ImplName {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        integer(value).map(Self::from_raw_amount)
+    }
+}
+
+impl ToJson for SignedTokenAmount {
+    fn to_json(&self) -> Value {
+        self.raw().to_json()
+    }
+}
+
+impl FromJson for SignedTokenAmount {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        integer(value).map(Self::from_raw_amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primitives_round_trip() {
+        let amount = TokenAmount::from(1.5);
+        assert_eq!(TokenAmount::from_json(&amount.to_json()), Ok(amount));
+
+        let big = u128::MAX;
+        assert_eq!(u128::from_json(&big.to_json()), Ok(big));
+
+        let prices = vec![Some(Price::from(1)), None];
+        assert_eq!(
+            Vec::<Option<Price>>::from_json(&prices.to_json()),
+            Ok(prices)
+        );
+    }
+
+    #[test]
+    fn errors_point_to_the_field() {
+        let value = Value::parse(r#"{"items": [1, "x"]}"#).unwrap();
+
+        assert_eq!(
+            field::<Vec<u64>>(&value, "items"),
+            Err(SchemaError::InvalidValue {
+                path: "items.1".into(),
+                reason: "expected integer".into()
+            })
+        );
+        assert_eq!(
+            field::<u64>(&value, "missing"),
+            Err(SchemaError::MissingField {
+                path: "missing".into()
+            })
+        );
+        assert_eq!(field_or_default::<u64>(&value, "missing"), Ok(0));
+    }
+}
@@ -0,0 +1,285 @@
+//! Service layer of the gRPC API defined in `proto/lp_pool.proto`. Messages mirror the
+//! protobuf definitions field by field and pool errors are mapped to gRPC status codes,
+//! so a transport only has to decode requests and encode responses.
+
+use crate::error::{AddLiquidityError, RemoveLiquidityError, SwapError};
+use crate::shared::SharedPool;
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// gRPC status codes returned by the service
+pub enum Code {
+    InvalidArgument = 3,
+    FailedPrecondition = 9,
+    OutOfRange = 11,
+    Unavailable = 14,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Failed call, sent as gRPC status
+pub struct Status {
+    pub code: Code,
+    pub message: String,
+}
+
+impl Status {
+    fn new(code: Code, error: impl ToString) -> Self {
+        Self {
+            code,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<SwapError> for Status {
+    fn from(error: SwapError) -> Self {
+        let code = match error {
+            SwapError::ZeroTokensAsArgument => Code::InvalidArgument,
+            SwapError::AmountTooBig => Code::OutOfRange,
+            SwapError::PoolNotEnoughTokens { .. } | SwapError::StalePrice { .. } => {
+                Code::FailedPrecondition
+            }
+            SwapError::Oracle(_) => Code::Unavailable,
+        };
+        Status::new(code, error)
+    }
+}
+
+impl From<AddLiquidityError> for Status {
+    fn from(error: AddLiquidityError) -> Self {
+        let code = match error {
+            AddLiquidityError::NoTokensProvided => Code::InvalidArgument,
+            AddLiquidityError::TokenAmountTooBig => Code::OutOfRange,
+            AddLiquidityError::PoolWithoutValue => Code::FailedPrecondition,
+            AddLiquidityError::Oracle(_) => Code::Unavailable,
+        };
+        Status::new(code, error)
+    }
+}
+
+impl From<RemoveLiquidityError> for Status {
+    fn from(error: RemoveLiquidityError) -> Self {
+        let code = match error {
+            RemoveLiquidityError::NotEnoughTokens { .. }
+            | RemoveLiquidityError::PositionNotEnoughTokens { .. } => Code::FailedPrecondition,
+            RemoveLiquidityError::WithdrawCalculationOverflow => Code::OutOfRange,
+        };
+        Status::new(code, error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QuoteRequest {
+    pub staked_token_amount: Uint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QuoteResponse {
+    pub token_amount: Uint,
+    pub fee: Uint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SwapRequest {
+    pub staked_token_amount: Uint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SwapResponse {
+    pub token_amount: Uint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AddLiquidityRequest {
+    pub token_amount: Uint,
+    pub account: Option<AccountId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AddLiquidityResponse {
+    pub lp_token_amount: Uint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RemoveLiquidityRequest {
+    pub lp_token_amount: Uint,
+    pub account: Option<AccountId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RemoveLiquidityResponse {
+    pub token_amount: Uint,
+    pub staked_token_amount: Uint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StatsRequest {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StatsResponse {
+    pub price: Uint,
+    pub epoch: Epoch,
+    pub token_amount: Uint,
+    pub staked_token_amount: Uint,
+    pub lp_token_amount: Uint,
+    pub total_value: Uint,
+    pub liquidity_target: Uint,
+    pub min_fee: Uint,
+    pub max_fee: Uint,
+    pub current_fee: Uint,
+    pub total_fees: Uint,
+}
+
+#[derive(Debug, Clone)]
+/// Implementation of `LpPoolService` backed by a shared pool
+pub struct PoolService {
+    pool: SharedPool,
+}
+
+impl PoolService {
+    pub fn new(pool: SharedPool) -> Self {
+        Self { pool }
+    }
+
+    pub fn quote(&self, request: QuoteRequest) -> Result<QuoteResponse, Status> {
+        let amount = StakedTokenAmount::from_raw_amount(request.staked_token_amount);
+        self.pool.with(|pool| {
+            Ok(QuoteResponse {
+                token_amount: pool.amount_for_swap(amount)?.raw(),
+                fee: pool.fee_for_swap(amount)?.raw(),
+            })
+        })
+    }
+
+    pub fn swap(&self, request: SwapRequest) -> Result<SwapResponse, Status> {
+        let amount = StakedTokenAmount::from_raw_amount(request.staked_token_amount);
+        let token_amount = self.pool.with(|pool| pool.swap(amount))?;
+        Ok(SwapResponse {
+            token_amount: token_amount.raw(),
+        })
+    }
+
+    pub fn add_liquidity(
+        &self,
+        request: AddLiquidityRequest,
+    ) -> Result<AddLiquidityResponse, Status> {
+        let amount = TokenAmount::from_raw_amount(request.token_amount);
+        let lp_tokens = self.pool.with(|pool| match request.account {
+            Some(account) => pool.add_liquidity_for(account, amount),
+            None => pool.add_liquidity(amount),
+        })?;
+        Ok(AddLiquidityResponse {
+            lp_token_amount: lp_tokens.raw(),
+        })
+    }
+
+    pub fn remove_liquidity(
+        &self,
+        request: RemoveLiquidityRequest,
+    ) -> Result<RemoveLiquidityResponse, Status> {
+        let amount = LpTokenAmount::from_raw_amount(request.lp_token_amount);
+        let (tokens, staked_tokens) = self.pool.with(|pool| match request.account {
+            Some(account) => pool.remove_liquidity_for(account, amount),
+            None => pool.remove_liquidity(amount),
+        })?;
+        Ok(RemoveLiquidityResponse {
+            token_amount: tokens.raw(),
+            staked_token_amount: staked_tokens.raw(),
+        })
+    }
+
+    pub fn stats(&self, _request: StatsRequest) -> Result<StatsResponse, Status> {
+        Ok(self.pool.with(|pool| {
+            let params = pool.params();
+            StatsResponse {
+                price: pool.price().raw(),
+                epoch: pool.epoch(),
+                token_amount: pool.token_amount().raw(),
+                staked_token_amount: pool.st_token_amount().raw(),
+                lp_token_amount: pool.lp_token_amount().raw(),
+                total_value: pool.total_value().raw(),
+                liquidity_target: params.liquidity_target.raw(),
+                min_fee: params.min_fee.raw(),
+                max_fee: params.max_fee.raw(),
+                current_fee: pool.current_fee().raw(),
+                total_fees: pool.fee_revenue().total.raw(),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lp_pool::LpPool;
+
+    fn service() -> PoolService {
+        let pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        PoolService::new(SharedPool::new(pool))
+    }
+
+    #[test]
+    fn serves_pool_operations() {
+        let service = service();
+        let added = service
+            .add_liquidity(AddLiquidityRequest {
+                token_amount: 100_000_000,
+                account: Some(1),
+            })
+            .unwrap();
+        assert_eq!(added.lp_token_amount, 100_000_000);
+
+        let quote = service
+            .quote(QuoteRequest {
+                staked_token_amount: 6_000_000,
+            })
+            .unwrap();
+        let swapped = service
+            .swap(SwapRequest {
+                staked_token_amount: 6_000_000,
+            })
+            .unwrap();
+        assert_eq!(quote.token_amount, swapped.token_amount);
+        assert_eq!(quote.fee, 1_000);
+
+        let stats = service.stats(StatsRequest {}).unwrap();
+        assert_eq!(stats.staked_token_amount, 6_000_000);
+        assert_eq!(stats.total_fees, 9_000);
+
+        let removed = service
+            .remove_liquidity(RemoveLiquidityRequest {
+                lp_token_amount: 10_000_000,
+                account: Some(1),
+            })
+            .unwrap();
+        assert_eq!(removed.staked_token_amount, 600_000);
+    }
+
+    #[test]
+    fn maps_errors_to_status_codes() {
+        let service = service();
+        let status = |result: Result<SwapResponse, Status>| result.unwrap_err().code;
+        assert_eq!(
+            status(service.swap(SwapRequest {
+                staked_token_amount: 0
+            })),
+            Code::InvalidArgument
+        );
+        assert_eq!(
+            status(service.swap(SwapRequest {
+                staked_token_amount: 1
+            })),
+            Code::FailedPrecondition
+        );
+        assert_eq!(
+            service
+                .remove_liquidity(RemoveLiquidityRequest {
+                    lp_token_amount: 1,
+                    account: None,
+                })
+                .unwrap_err()
+                .code,
+            Code::FailedPrecondition
+        );
+    }
+}
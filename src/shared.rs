@@ -0,0 +1,595 @@
+#[cfg(all(test, loom))]
+use crate::loom::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex, RwLock,
+};
+#[cfg(not(all(test, loom)))]
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex, RwLock,
+};
+use std::sync::{Arc, MutexGuard, PoisonError, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::conservation::Balances;
+use crate::error::{AddLiquidityError, OpError, RemoveLiquidityError, SlippageError, SwapError};
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::types::*;
+
+#[derive(Debug, Clone)]
+/// Pool shared between threads. Every call locks the pool for its whole duration, so
+/// operations of different threads never interleave.
+pub struct SharedPool {
+    pool: Arc<Mutex<LpPool>>,
+}
+
+impl SharedPool {
+    pub fn new(pool: LpPool) -> Self {
+        Self {
+            pool: Arc::new(Mutex::new(pool)),
+        }
+    }
+
+    /// Runs closure with exclusive access to the pool
+    pub fn with<R>(&self, f: impl FnOnce(&mut LpPool) -> R) -> R {
+        f(&mut self.lock())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LpPool> {
+        // pool methods validate before mutating, so state is consistent even if a
+        // closure panicked while holding the lock
+        self.pool.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Pool shared between threads behind a read-write lock. Quotes take the read lock and
+/// run in parallel, mutations take the write lock. Every method holds its lock for its
+/// whole duration, so compound operations which check the pool before mutating it, like
+/// `swap_at_least`, can't be invalidated by another thread in between.
+pub struct SharedLpPool {
+    pool: Arc<RwLock<LpPool>>,
+}
+
+impl SharedLpPool {
+    pub fn new(pool: LpPool) -> Self {
+        Self {
+            pool: Arc::new(RwLock::new(pool)),
+        }
+    }
+
+    // pool methods validate before mutating, so state is consistent even if a closure
+    // panicked while holding the lock
+    fn read_lock(&self) -> RwLockReadGuard<'_, LpPool> {
+        self.pool.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_lock(&self) -> RwLockWriteGuard<'_, LpPool> {
+        self.pool.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Runs closure with shared access to the pool, concurrently with other readers
+    pub fn read<R>(&self, f: impl FnOnce(&LpPool) -> R) -> R {
+        f(&self.read_lock())
+    }
+
+    /// Runs closure with exclusive access to the pool
+    pub fn write<R>(&self, f: impl FnOnce(&mut LpPool) -> R) -> R {
+        f(&mut self.write_lock())
+    }
+
+    /// Returns balances of a single consistent state
+    pub fn balances(&self) -> Balances {
+        self.read_lock().balances()
+    }
+
+    /// Returns tokens a swap would grant and the fee it would charge, both quoted from
+    /// the same state
+    pub fn quote_swap(
+        &self,
+        swap_amount: StakedTokenAmount,
+    ) -> Result<(TokenAmount, Percentage), SwapError> {
+        let pool = self.read_lock();
+        Ok((
+            pool.amount_for_swap(swap_amount)?,
+            pool.fee_for_swap(swap_amount)?,
+        ))
+    }
+
+    pub fn current_fee(&self) -> Percentage {
+        self.read_lock().current_fee()
+    }
+
+    pub fn apply(&self, op: &PoolOp) -> Result<OpOutcome, OpError> {
+        self.write_lock().apply(op)
+    }
+
+    pub fn add_liquidity(
+        &self,
+        token_amount_in: TokenAmount,
+    ) -> Result<LpTokenAmount, AddLiquidityError> {
+        self.write_lock().add_liquidity(token_amount_in)
+    }
+
+    pub fn swap(&self, swap_amount: StakedTokenAmount) -> Result<TokenAmount, SwapError> {
+        self.write_lock().swap(swap_amount)
+    }
+
+    /// Swaps only if the swap pays out at least `min_amount_out`, the price is refreshed
+    /// and the swap quoted and executed under a single write lock
+    pub fn swap_at_least(
+        &self,
+        swap_amount: StakedTokenAmount,
+        min_amount_out: TokenAmount,
+    ) -> Result<TokenAmount, SlippageError> {
+        let mut pool = self.write_lock();
+        pool.refresh_price().map_err(SwapError::from)?;
+        let amount_out = pool.amount_for_swap(swap_amount)?;
+        if amount_out < min_amount_out {
+            return Err(SlippageError::Exceeded {
+                amount_out,
+                min_amount_out,
+            });
+        }
+        Ok(pool.swap(swap_amount)?)
+    }
+
+    /// Withdraws the account's whole position, read and withdrawn under a single write
+    /// lock so concurrent withdrawals of the account can't make it overdraw
+    pub fn withdraw_position(
+        &self,
+        account: AccountId,
+    ) -> Result<(TokenAmount, StakedTokenAmount), RemoveLiquidityError> {
+        let mut pool = self.write_lock();
+        let lp_tokens = pool
+            .position(account)
+            .map(|position| position.lp_tokens)
+            .unwrap_or_default();
+        pool.remove_liquidity_for(account, lp_tokens)
+    }
+}
+
+#[derive(Debug)]
+/// Latest published snapshot of the pool behind a mutex, numbered by a generation bumped
+/// on every publish so readers only lock the mutex once the generation moved
+struct SnapshotSlot {
+    snapshot: Mutex<Arc<LpPool>>,
+    generation: AtomicU64,
+}
+
+impl SnapshotSlot {
+    fn load(&self) -> (u64, Arc<LpPool>) {
+        // generation is read first, so a snapshot published in between is only reloaded
+        // once more and never missed
+        let generation = self.generation.load(Ordering::Acquire);
+        let snapshot = self
+            .snapshot
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        (generation, snapshot)
+    }
+
+    fn publish(&self, pool: &LpPool) {
+        // the snapshot is built and the previous one dropped outside of the lock, so readers
+        // only ever wait for the pointer store
+        let snapshot = Arc::new(pool.detached());
+        let previous = std::mem::replace(
+            &mut *self.snapshot.lock().unwrap_or_else(PoisonError::into_inner),
+            snapshot,
+        );
+        self.generation.fetch_add(1, Ordering::Release);
+        drop(previous);
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Pool shared between threads whose quotes never wait for mutations. Mutations lock the
+/// pool and publish an immutable snapshot of the resulting state, quotes are answered by
+/// `QuoteReader`s from the last published snapshot. Reads aren't lock-free: readers check
+/// a single atomic per quote and lock the mutex guarding the snapshot only when a newer
+/// one was published. The mutex is held for an `Arc` clone by readers and for a pointer
+/// store by writers, so a reader may briefly wait for a publishing writer but never for
+/// the mutation itself.
+pub struct SnapshotPool {
+    pool: Arc<Mutex<LpPool>>,
+    slot: Arc<SnapshotSlot>,
+}
+
+impl SnapshotPool {
+    pub fn new(pool: LpPool) -> Self {
+        let slot = SnapshotSlot {
+            snapshot: Mutex::new(Arc::new(pool.detached())),
+            generation: AtomicU64::new(0),
+        };
+        Self {
+            pool: Arc::new(Mutex::new(pool)),
+            slot: Arc::new(slot),
+        }
+    }
+
+    /// Runs closure with exclusive access to the pool and publishes the state it left
+    pub fn write<R>(&self, f: impl FnOnce(&mut LpPool) -> R) -> R {
+        let mut pool = self.pool.lock().unwrap_or_else(PoisonError::into_inner);
+        let result = f(&mut pool);
+        // published under the pool lock, so snapshots are published in mutation order
+        self.slot.publish(&pool);
+        result
+    }
+
+    /// Returns last published snapshot
+    pub fn snapshot(&self) -> Arc<LpPool> {
+        self.slot.load().1
+    }
+
+    /// Returns reader quoting from the last published snapshot, meant to be kept by a
+    /// single thread
+    pub fn reader(&self) -> QuoteReader {
+        let (generation, snapshot) = self.slot.load();
+        QuoteReader {
+            slot: self.slot.clone(),
+            generation,
+            snapshot,
+        }
+    }
+
+    pub fn apply(&self, op: &PoolOp) -> Result<OpOutcome, OpError> {
+        self.write(|pool| pool.apply(op))
+    }
+
+    pub fn add_liquidity(
+        &self,
+        token_amount_in: TokenAmount,
+    ) -> Result<LpTokenAmount, AddLiquidityError> {
+        self.write(|pool| pool.add_liquidity(token_amount_in))
+    }
+
+    pub fn swap(&self, swap_amount: StakedTokenAmount) -> Result<TokenAmount, SwapError> {
+        self.write(|pool| pool.swap(swap_amount))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Quotes of a `SnapshotPool`, every call answers from a single snapshot
+pub struct QuoteReader {
+    slot: Arc<SnapshotSlot>,
+    generation: u64,
+    snapshot: Arc<LpPool>,
+}
+
+impl QuoteReader {
+    /// Returns last published snapshot, reloaded only if a newer one was published since
+    /// the previous call
+    pub fn snapshot(&mut self) -> &LpPool {
+        if self.slot.generation.load(Ordering::Acquire) != self.generation {
+            (self.generation, self.snapshot) = self.slot.load();
+        }
+        &self.snapshot
+    }
+
+    pub fn balances(&mut self) -> Balances {
+        self.snapshot().balances()
+    }
+
+    /// Returns tokens a swap would grant and the fee it would charge
+    pub fn quote_swap(
+        &mut self,
+        swap_amount: StakedTokenAmount,
+    ) -> Result<(TokenAmount, Percentage), SwapError> {
+        let pool = self.snapshot();
+        Ok((
+            pool.amount_for_swap(swap_amount)?,
+            pool.fee_for_swap(swap_amount)?,
+        ))
+    }
+
+    pub fn current_fee(&mut self) -> Percentage {
+        self.snapshot().current_fee()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(loom)]
+    use std::sync::Mutex as StdMutex;
+
+    use super::*;
+    #[cfg(loom)]
+    use crate::loom;
+
+    #[test]
+    fn serializes_concurrent_operations() {
+        let mut pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        pool.add_liquidity(1_000.into()).unwrap();
+        let shared = SharedPool::new(pool);
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        shared.with(|pool| pool.swap(1.into())).unwrap();
+                    }
+                })
+            })
+            .collect();
+        threads
+            .into_iter()
+            .for_each(|thread| thread.join().unwrap());
+
+        assert_eq!(
+            shared.with(|pool| pool.st_token_amount()),
+            StakedTokenAmount::from(80)
+        );
+    }
+
+    /// Quote taken while another thread swaps and deposits
+    type Quote = (Balances, Option<TokenAmount>, Percentage);
+
+    fn quote(pool: &LpPool) -> Quote {
+        (
+            pool.balances(),
+            pool.amount_for_swap(5.into()).ok(),
+            pool.current_fee(),
+        )
+    }
+
+    fn pool() -> LpPool {
+        let mut pool = LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        pool.add_liquidity(100.into()).unwrap();
+        pool
+    }
+
+    fn writes() -> [PoolOp; 2] {
+        [
+            PoolOp::Swap {
+                account: None,
+                amount: 30.into(),
+            },
+            PoolOp::AddLiquidity {
+                account: None,
+                amount: 10.into(),
+            },
+        ]
+    }
+
+    /// Returns quotes of the states reachable by applying a prefix of the writes
+    fn consistent_quotes() -> Vec<Quote> {
+        let mut pool = pool();
+        let mut quotes = vec![quote(&pool)];
+        for op in writes() {
+            pool.apply(&op).unwrap();
+            quotes.push(quote(&pool));
+        }
+        quotes
+    }
+
+    /// Swap quote taken while another thread swaps and deposits
+    #[cfg(loom)]
+    type SwapQuote = (Option<TokenAmount>, Option<Percentage>);
+
+    #[cfg(loom)]
+    fn swap_quote(pool: &LpPool) -> SwapQuote {
+        (
+            pool.amount_for_swap(5.into()).ok(),
+            pool.fee_for_swap(5.into()).ok(),
+        )
+    }
+
+    /// Returns swap quotes of the states reachable by applying a prefix of the writes
+    #[cfg(loom)]
+    fn consistent_swap_quotes() -> Vec<SwapQuote> {
+        let mut pool = pool();
+        let mut quotes = vec![swap_quote(&pool)];
+        for op in writes() {
+            pool.apply(&op).unwrap();
+            quotes.push(swap_quote(&pool));
+        }
+        quotes
+    }
+
+    #[cfg(loom)]
+    #[test]
+    fn quotes_never_observe_torn_state() {
+        let consistent = consistent_quotes();
+        let executions = loom::model(|| {
+            let shared = SharedPool::new(pool());
+            let writer = {
+                let shared = shared.clone();
+                loom::thread::spawn(move || {
+                    for op in writes() {
+                        shared.with(|pool| pool.apply(&op)).unwrap();
+                    }
+                })
+            };
+            for _ in 0..2 {
+                let quote = shared.with(|pool| quote(pool));
+                assert!(consistent.contains(&quote), "torn quote {quote:?}");
+            }
+            writer.join().unwrap();
+        });
+        assert!(executions >= 6, "{executions}");
+    }
+
+    #[cfg(loom)]
+    #[test]
+    fn detects_quotes_split_across_locks() {
+        // reading balances and the fee under separate locks lets a write slip in between
+        let consistent = consistent_quotes();
+        let torn = StdMutex::new(0);
+        loom::model(|| {
+            let shared = SharedPool::new(pool());
+            let writer = {
+                let shared = shared.clone();
+                loom::thread::spawn(move || {
+                    shared.with(|pool| pool.apply(&writes()[0])).unwrap();
+                })
+            };
+            let balances = shared.with(|pool| pool.balances());
+            let (_, amount, fee) = shared.with(|pool| quote(pool));
+            if !consistent.contains(&(balances, amount, fee)) {
+                *torn.lock().unwrap() += 1;
+            }
+            writer.join().unwrap();
+        });
+        assert!(torn.into_inner().unwrap() > 0);
+    }
+
+    #[cfg(loom)]
+    #[test]
+    fn shared_lp_pool_quotes_never_observe_torn_state() {
+        let consistent = consistent_quotes();
+        let swap_quotes = consistent_swap_quotes();
+        let executions = loom::model(|| {
+            let shared = SharedLpPool::new(pool());
+            let writer = {
+                let shared = shared.clone();
+                loom::thread::spawn(move || {
+                    for op in writes() {
+                        shared.apply(&op).unwrap();
+                    }
+                })
+            };
+            for _ in 0..2 {
+                let quote = shared.read(quote);
+                assert!(consistent.contains(&quote), "torn quote {quote:?}");
+                let (amount, fee) = shared.quote_swap(5.into()).unwrap();
+                let quote = (Some(amount), Some(fee));
+                assert!(swap_quotes.contains(&quote), "torn swap quote {quote:?}");
+            }
+            writer.join().unwrap();
+        });
+        assert!(executions >= 6, "{executions}");
+    }
+
+    #[cfg(loom)]
+    #[test]
+    fn snapshot_readers_follow_published_states() {
+        let consistent = consistent_quotes();
+        let swap_quotes = consistent_swap_quotes();
+        let executions = loom::model(|| {
+            let shared = SnapshotPool::new(pool());
+            let mut reader = shared.reader();
+            let writer = {
+                let shared = shared.clone();
+                loom::thread::spawn(move || {
+                    for op in writes() {
+                        shared.apply(&op).unwrap();
+                    }
+                })
+            };
+            let mut last_state = 0;
+            for _ in 0..2 {
+                let quote = quote(reader.snapshot());
+                let state = consistent
+                    .iter()
+                    .position(|consistent| *consistent == quote)
+                    .unwrap_or_else(|| panic!("torn quote {quote:?}"));
+                assert!(
+                    state >= last_state,
+                    "went back from state {last_state} to {state}"
+                );
+                last_state = state;
+                let quote = swap_quote(reader.snapshot());
+                assert!(swap_quotes.contains(&quote), "torn swap quote {quote:?}");
+            }
+            writer.join().unwrap();
+            // the generation is bumped after the snapshot is stored, so the last publish
+            // is never missed
+            assert_eq!(quote(reader.snapshot()), *consistent.last().unwrap());
+        });
+        assert!(executions >= 6, "{executions}");
+    }
+
+    #[test]
+    fn shared_lp_pool_quotes_and_swaps_consistently() {
+        let pool = SharedLpPool::new(pool());
+        let (amount_out, fee) = pool.quote_swap(6.into()).unwrap();
+        assert_eq!(fee, pool.current_fee());
+        assert!(matches!(
+            pool.swap_at_least(6.into(), amount_out + TokenAmount::from(0.01)),
+            Err(SlippageError::Exceeded { .. })
+        ));
+        assert_eq!(pool.balances().staked_tokens, 0.into());
+        assert_eq!(
+            pool.swap_at_least(6.into(), amount_out).unwrap(),
+            amount_out
+        );
+
+        // racing swaps bounded by the first quote can't all pass once liquidity moves
+        let min_amount_out = pool.quote_swap(10.into()).unwrap().0;
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let pool = pool.clone();
+                std::thread::spawn(move || pool.swap_at_least(10.into(), min_amount_out))
+            })
+            .collect();
+        let swapped = threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .filter(Result::is_ok)
+            .count();
+        assert_eq!(swapped, 1);
+        assert_eq!(pool.read(|pool| pool.st_token_amount()), 16.into());
+    }
+
+    #[test]
+    fn shared_lp_pool_withdraws_positions_once() {
+        let pool = SharedLpPool::new(pool());
+        pool.write(|pool| pool.add_liquidity_for(7, 10.into()))
+            .unwrap();
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let pool = pool.clone();
+                std::thread::spawn(move || pool.withdraw_position(7).unwrap())
+            })
+            .collect();
+        let withdrawn: Vec<_> = threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap().0)
+            .filter(|tokens| tokens.raw() > 0)
+            .collect();
+        assert_eq!(withdrawn, [TokenAmount::from(10)]);
+        assert_eq!(pool.read(|pool| pool.lp_token_amount()), 100.into());
+    }
+
+    #[test]
+    fn snapshot_pool_quotes_published_states() {
+        let shared = SnapshotPool::new(pool());
+        let mut reader = shared.reader();
+        let (amount_out, fee) = reader.quote_swap(6.into()).unwrap();
+        assert_eq!(fee, reader.current_fee());
+        assert_eq!(shared.swap(6.into()).unwrap(), amount_out);
+        assert_eq!(reader.balances().staked_tokens, 6.into());
+        assert!(reader.quote_swap(6.into()).unwrap().0 < amount_out);
+
+        // quotes racing writers always come from a state some prefix of writes led to
+        let consistent = consistent_quotes();
+        let pool = SnapshotPool::new(pool());
+        let writer = {
+            let pool = pool.clone();
+            std::thread::spawn(move || {
+                for op in writes() {
+                    pool.apply(&op).unwrap();
+                }
+            })
+        };
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let mut reader = pool.reader();
+                let consistent = consistent.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        let quote = quote(reader.snapshot());
+                        assert!(consistent.contains(&quote), "torn quote {quote:?}");
+                    }
+                })
+            })
+            .chain([writer])
+            .collect();
+        readers
+            .into_iter()
+            .for_each(|thread| thread.join().unwrap());
+        assert_eq!(quote(&pool.snapshot()), *consistent.last().unwrap());
+    }
+}
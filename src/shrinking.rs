@@ -0,0 +1,425 @@
+//! Shrinking of failing operation sequences into minimal reproducers. Sequences are
+//! shrunk by removing chunks of operations, from halves of the sequence down to single
+//! operations, and by simplifying the remaining ones: accounts are dropped and amounts
+//! are moved towards zero, prices towards one, as long as the sequence keeps failing.
+//! Shrunk sequences are printed as unit tests which can be pasted into a test module.
+
+use crate::discounts::DiscountCriterion;
+use crate::ops::PoolOp;
+use crate::replay::PoolConfig;
+use crate::types::*;
+
+/// Shrinks operations as long as the sequence keeps failing, the returned sequence
+/// fails and no single removal or simplification of its operations fails anymore
+pub fn shrink(mut ops: Vec<PoolOp>, fails: impl Fn(&[PoolOp]) -> bool) -> Vec<PoolOp> {
+    loop {
+        let len = ops.len();
+        ops = remove_chunks(ops, &fails);
+        let simplified = simplify(&mut ops, &fails);
+        if ops.len() == len && !simplified {
+            return ops;
+        }
+    }
+}
+
+fn remove_chunks(mut ops: Vec<PoolOp>, fails: &impl Fn(&[PoolOp]) -> bool) -> Vec<PoolOp> {
+    let mut size = (ops.len() / 2).max(1);
+    loop {
+        let mut start = 0;
+        while start < ops.len() {
+            let mut candidate = ops.clone();
+            candidate.drain(start..(start + size).min(ops.len()));
+            match fails(&candidate) {
+                true => ops = candidate,
+                false => start += size,
+            }
+        }
+        if size == 1 {
+            return ops;
+        }
+        size /= 2;
+    }
+}
+
+/// Replaces operations with their simplest variants which still fail, returns true if
+/// any operation changed
+fn simplify(ops: &mut [PoolOp], fails: &impl Fn(&[PoolOp]) -> bool) -> bool {
+    let mut changed = false;
+    for index in 0..ops.len() {
+        while let Some(op) = simpler(&ops[index]).into_iter().find(|candidate| {
+            let mut ops = ops.to_vec();
+            ops[index] = *candidate;
+            fails(&ops)
+        }) {
+            ops[index] = op;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Returns variants of the operation which are strictly simpler, simplest first
+fn simpler(op: &PoolOp) -> Vec<PoolOp> {
+    let target = match op {
+        PoolOp::SetPrice { .. } => SCALE,
+        _ => 0,
+    };
+    let value = op.raw_amount();
+    let distance = value.abs_diff(target);
+    // target itself first, then values halving the distance to the original value
+    let values = std::iter::successors(Some(distance), |distance| Some(distance / 2))
+        .take_while(|distance| *distance > 0)
+        .map(|distance| match value > target {
+            true => value - distance,
+            false => value + distance,
+        });
+
+    let anonymous = op.account().map(|_| with_raw_amount(op, None, value));
+    anonymous
+        .into_iter()
+        .chain(values.map(|value| with_raw_amount(op, op.account(), value)))
+        .collect()
+}
+
+fn with_raw_amount(op: &PoolOp, account: Option<AccountId>, value: Uint) -> PoolOp {
+    match op {
+        PoolOp::AddLiquidity { .. } => PoolOp::AddLiquidity {
+            account,
+            amount: TokenAmount::from_raw_amount(value),
+        },
+        PoolOp::RemoveLiquidity { .. } => PoolOp::RemoveLiquidity {
+            account,
+            lp_amount: LpTokenAmount::from_raw_amount(value),
+        },
+        PoolOp::Swap { .. } => PoolOp::Swap {
+            account,
+            amount: StakedTokenAmount::from_raw_amount(value),
+        },
+        PoolOp::SetPrice { .. } => PoolOp::SetPrice {
+            price: Price::from_raw_amount(value),
+        },
+        PoolOp::AdvanceEpoch { .. } => PoolOp::AdvanceEpoch { epochs: value },
+        PoolOp::Unreplayable => PoolOp::Unreplayable,
+    }
+}
+
+/// Returns constructor call of a fixed-point value as Rust code
+pub fn raw_code(type_name: &str, value: Uint) -> String {
+    format!("{type_name}::from_raw_amount({value})")
+}
+
+/// Returns operation as Rust code
+pub fn op_code(op: &PoolOp) -> String {
+    match *op {
+        PoolOp::AddLiquidity { account, amount } => format!(
+            "PoolOp::AddLiquidity {{ account: {account:?}, amount: {} }}",
+            raw_code("TokenAmount", amount.raw())
+        ),
+        PoolOp::RemoveLiquidity { account, lp_amount } => format!(
+            "PoolOp::RemoveLiquidity {{ account: {account:?}, lp_amount: {} }}",
+            raw_code("LpTokenAmount", lp_amount.raw())
+        ),
+        PoolOp::Swap { account, amount } => format!(
+            "PoolOp::Swap {{ account: {account:?}, amount: {} }}",
+            raw_code("StakedTokenAmount", amount.raw())
+        ),
+        PoolOp::SetPrice { price } => format!(
+            "PoolOp::SetPrice {{ price: {} }}",
+            raw_code("Price", price.raw())
+        ),
+        PoolOp::AdvanceEpoch { epochs } => format!("PoolOp::AdvanceEpoch {{ epochs: {epochs} }}"),
+        PoolOp::Unreplayable => "PoolOp::Unreplayable".into(),
+    }
+}
+
+/// Returns statements creating `config` as Rust code, settings left at their defaults
+/// are omitted
+fn config_code(config: &PoolConfig) -> Vec<String> {
+    let percentage = |value: Percentage| raw_code("Percentage", value.raw());
+    let params = config.params;
+    let defaults = PoolConfig::new(config.price, params);
+    let mut settings = Vec::new();
+    if config.treasury_cut != defaults.treasury_cut {
+        settings.push(format!(
+            "config.treasury_cut = {};",
+            percentage(config.treasury_cut)
+        ));
+    }
+    if config.insurance_cut != defaults.insurance_cut {
+        settings.push(format!(
+            "config.insurance_cut = {};",
+            percentage(config.insurance_cut)
+        ));
+    }
+    if let crate::fee_policy::FeePolicy::VolatilitySensitive {
+        sensitivity,
+        max_surcharge,
+    } = config.fee_policy
+    {
+        settings.push(format!(
+            "config.fee_policy = FeePolicy::VolatilitySensitive {{ sensitivity: {}, max_surcharge: {} }};",
+            percentage(sensitivity),
+            percentage(max_surcharge)
+        ));
+    }
+    if let crate::price_smoothing::PriceSmoothing::Ema { alpha } = config.price_smoothing {
+        settings.push(format!(
+            "config.price_smoothing = PriceSmoothing::Ema {{ alpha: {} }};",
+            percentage(alpha)
+        ));
+    }
+    if let Some(surcharge) = config.surcharge {
+        settings.extend([
+            "config.surcharge = Some(SurchargeConfig {".to_string(),
+            format!(
+                "    large_swap_threshold: {},",
+                percentage(surcharge.large_swap_threshold)
+            ),
+            format!("    surcharge: {},", percentage(surcharge.surcharge)),
+            format!("    decay_rate: {},", percentage(surcharge.decay_rate)),
+            format!("    decay: SurchargeDecay::{:?},", surcharge.decay),
+            "});".to_string(),
+        ]);
+    }
+    if let Some(bootstrap) = config.bootstrap {
+        settings.extend([
+            "config.bootstrap = Some(LiquidityBootstrap::new(".to_string(),
+            "    BootstrapConfig {".to_string(),
+            format!(
+                "        surcharge: {},",
+                percentage(bootstrap.config().surcharge)
+            ),
+            format!("        epochs: {},", bootstrap.config().epochs),
+            "    },".to_string(),
+            format!("    {},", bootstrap.started_at()),
+            "));".to_string(),
+        ]);
+    }
+    if let Some(age) = config.max_price_age {
+        settings.push(format!("config.max_price_age = Some({age});"));
+    }
+    if let Some(deviation) = config.max_price_deviation {
+        settings.push(format!(
+            "config.max_price_deviation = Some({});",
+            percentage(deviation)
+        ));
+    }
+    if config.monotonic_price {
+        settings.push("config.monotonic_price = true;".to_string());
+    }
+    if !config.fee_discounts.is_empty() {
+        settings.push("config.fee_discounts = vec![".to_string());
+        for tier in &config.fee_discounts {
+            let criterion = match tier.criterion {
+                DiscountCriterion::LiquidityShare(share) => {
+                    format!("DiscountCriterion::LiquidityShare({})", percentage(share))
+                }
+                DiscountCriterion::Volume(volume) => format!(
+                    "DiscountCriterion::Volume({})",
+                    raw_code("TokenAmount", volume.raw())
+                ),
+            };
+            settings.push(format!(
+                "    DiscountTier::new({criterion}, {}),",
+                percentage(tier.discount)
+            ));
+        }
+        settings.push("];".to_string());
+    }
+
+    let binding = match settings.is_empty() {
+        true => "let config",
+        false => "let mut config",
+    };
+    let lines = vec![
+        format!("{binding} = PoolConfig::new("),
+        format!("    {},", raw_code("Price", config.price.raw())),
+        "    PoolParams {".to_string(),
+        format!("        min_fee: {},", percentage(params.min_fee)),
+        format!("        max_fee: {},", percentage(params.max_fee)),
+        format!(
+            "        liquidity_target: {},",
+            raw_code("TokenAmount", params.liquidity_target.raw())
+        ),
+        "    },".to_string(),
+        ");".to_string(),
+    ];
+    lines.into_iter().chain(settings).collect()
+}
+
+/// Prints failing sequence as a unit test named `name`. The test builds `pool` from the
+/// configuration, declares `ops` and ends with `checks`, statements which are expected
+/// to panic while the failure isn't fixed. The code uses items exported from the crate
+/// root, so it compiles with `use invariant_task::*;`, or `use crate::*;` inside it.
+pub fn unit_test(name: &str, config: &PoolConfig, ops: &[PoolOp], checks: &[String]) -> String {
+    let mut lines = vec!["#[test]".to_string(), format!("fn {name}() {{")];
+    let body = config_code(config)
+        .into_iter()
+        .chain(["let ops = [".to_string()])
+        .chain(ops.iter().map(|op| format!("    {},", op_code(op))))
+        .chain([
+            "];".to_string(),
+            "let mut pool = config.build();".to_string(),
+        ])
+        .chain(checks.iter().cloned());
+    lines.extend(body.map(|line| format!("    {line}")));
+    lines.push("}".to_string());
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::governance::PoolParams;
+    use crate::rng::Rng;
+    use crate::surcharge::{SurchargeConfig, SurchargeDecay};
+
+    #[test]
+    fn shrinks_to_minimal_reproducer() {
+        let mut rng = Rng::new(3);
+        let ops = crate::properties::random_ops(&mut rng, 60);
+        // fails once the pool saw a deposit followed by a swap of at least 1000 raw units
+        let fails = |ops: &[PoolOp]| {
+            let deposit = ops
+                .iter()
+                .position(|op| matches!(op, PoolOp::AddLiquidity { .. }));
+            deposit.is_some_and(|deposit| {
+                ops[deposit..]
+                    .iter()
+                    .any(|op| matches!(op, PoolOp::Swap { amount, .. } if amount.raw() >= 1_000))
+            })
+        };
+        assert!(fails(&ops));
+        assert_eq!(
+            shrink(ops, fails),
+            vec![
+                PoolOp::AddLiquidity {
+                    account: None,
+                    amount: TokenAmount::from_raw_amount(0),
+                },
+                PoolOp::Swap {
+                    account: None,
+                    amount: StakedTokenAmount::from_raw_amount(1_000),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn simplifies_towards_defaults() {
+        let price = PoolOp::SetPrice {
+            price: Price::from_raw_amount(SCALE + 10),
+        };
+        let candidates = simpler(&price);
+        assert_eq!(candidates[0].raw_amount(), SCALE);
+        assert_eq!(candidates.last().unwrap().raw_amount(), SCALE + 9);
+        assert!(simpler(&PoolOp::AdvanceEpoch { epochs: 0 }).is_empty());
+        assert_eq!(
+            simpler(&PoolOp::RemoveLiquidity {
+                account: Some(2),
+                lp_amount: 0.into(),
+            }),
+            vec![PoolOp::RemoveLiquidity {
+                account: None,
+                lp_amount: 0.into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn prints_unit_test() {
+        let mut config = PoolConfig::new(
+            1.5.into(),
+            PoolParams {
+                min_fee: 0.001.into(),
+                max_fee: 0.09.into(),
+                liquidity_target: 90.into(),
+            },
+        );
+        config.surcharge = Some(SurchargeConfig {
+            large_swap_threshold: 0.1.into(),
+            surcharge: 0.01.into(),
+            decay_rate: 0.5.into(),
+            decay: SurchargeDecay::PerEpoch,
+        });
+        config.monotonic_price = true;
+        let ops = [
+            PoolOp::AddLiquidity {
+                account: Some(1),
+                amount: 100.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 6.into(),
+            },
+        ];
+        let test = unit_test(
+            "reproduces_failure",
+            &config,
+            &ops,
+            &["check_ops(&mut pool, &ops).unwrap();".to_string()],
+        );
+        assert_eq!(test, printed_test_source());
+    }
+
+    fn printed_test_source() -> &'static str {
+        "#[test]
+fn reproduces_failure() {
+    let mut config = PoolConfig::new(
+        Price::from_raw_amount(1500000),
+        PoolParams {
+            min_fee: Percentage::from_raw_amount(1000),
+            max_fee: Percentage::from_raw_amount(90000),
+            liquidity_target: TokenAmount::from_raw_amount(90000000),
+        },
+    );
+    config.surcharge = Some(SurchargeConfig {
+        large_swap_threshold: Percentage::from_raw_amount(100000),
+        surcharge: Percentage::from_raw_amount(10000),
+        decay_rate: Percentage::from_raw_amount(500000),
+        decay: SurchargeDecay::PerEpoch,
+    });
+    config.monotonic_price = true;
+    let ops = [
+        PoolOp::AddLiquidity { account: Some(1), amount: TokenAmount::from_raw_amount(100000000) },
+        PoolOp::Swap { account: None, amount: StakedTokenAmount::from_raw_amount(6000000) },
+    ];
+    let mut pool = config.build();
+    check_ops(&mut pool, &ops).unwrap();
+}
+"
+    }
+
+    // printed by `prints_unit_test`, pasted to make sure the generated code compiles
+    #[test]
+    fn reproduces_failure() {
+        use crate::*;
+        let mut config = PoolConfig::new(
+            Price::from_raw_amount(1500000),
+            PoolParams {
+                min_fee: Percentage::from_raw_amount(1000),
+                max_fee: Percentage::from_raw_amount(90000),
+                liquidity_target: TokenAmount::from_raw_amount(90000000),
+            },
+        );
+        config.surcharge = Some(SurchargeConfig {
+            large_swap_threshold: Percentage::from_raw_amount(100000),
+            surcharge: Percentage::from_raw_amount(10000),
+            decay_rate: Percentage::from_raw_amount(500000),
+            decay: SurchargeDecay::PerEpoch,
+        });
+        config.monotonic_price = true;
+        let ops = [
+            PoolOp::AddLiquidity {
+                account: Some(1),
+                amount: TokenAmount::from_raw_amount(100000000),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: StakedTokenAmount::from_raw_amount(6000000),
+            },
+        ];
+        let mut pool = config.build();
+        check_ops(&mut pool, &ops).unwrap();
+    }
+}
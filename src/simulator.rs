@@ -0,0 +1,444 @@
+//! Seeded Monte Carlo simulation of pool usage. Every step either deposits liquidity or
+//! swaps, with sizes drawn from configurable distributions, and the price drifts at the
+//! end of every epoch. Operations are generated from a single seed, so a simulation is
+//! reproduced exactly, end state included, by running the same configuration again.
+//!
+//! `simulate_many` runs independent simulations of many pool configurations on all
+//! available cores, for parameter sweeps and optimizer backends.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::backtest::liquidity_ratio;
+use crate::lp_pool::LpPool;
+use crate::ops::{OpOutcome, PoolOp};
+use crate::replay::PoolConfig;
+use crate::rng::Rng;
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Distribution of raw amounts
+pub enum Distribution {
+    Fixed(Uint),
+    /// every amount in `low..=high` is equally likely
+    Uniform {
+        low: Uint,
+        high: Uint,
+    },
+    /// every magnitude in `low..=high` is equally likely, models many small and few
+    /// large amounts, `low` has to be positive
+    LogUniform {
+        low: Uint,
+        high: Uint,
+    },
+}
+
+impl Distribution {
+    pub fn sample(&self, rng: &mut Rng) -> Uint {
+        match *self {
+            Distribution::Fixed(amount) => amount,
+            Distribution::Uniform { low, high } => rng.range(low, high),
+            Distribution::LogUniform { low, high } => {
+                let (low_ln, high_ln) = ((low as f64).ln(), (high as f64).ln());
+                let amount = (low_ln + (high_ln - low_ln) * rng.unit()).exp();
+                (amount as Uint).clamp(low, high)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Random walk of the price, applied once per epoch
+pub struct PriceDrift {
+    /// expected relative price change per epoch
+    pub drift: f64,
+    /// biggest relative deviation from the expected change, drawn uniformly
+    pub volatility: f64,
+}
+
+impl PriceDrift {
+    pub(crate) fn next(&self, price: Price, rng: &mut Rng) -> Price {
+        let change = 1.0 + self.drift + self.volatility * (2.0 * rng.unit() - 1.0);
+        Price::from_raw_amount(((price.raw() as f64 * change) as Uint).max(1))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Configuration of a simulation run
+pub struct SimulationConfig {
+    pub pool: PoolConfig,
+    pub seed: u64,
+    /// number of randomized operations, the initial deposit and epoch changes excluded
+    pub steps: usize,
+    /// liquidity deposited before the first step
+    pub initial_liquidity: TokenAmount,
+    /// distribution of staked tokens swapped by a single swap
+    pub swap_size: Distribution,
+    /// probability a step deposits liquidity instead of swapping
+    pub deposit_probability: f64,
+    /// distribution of tokens deposited by a single deposit
+    pub deposit_size: Distribution,
+    /// number of steps in a single epoch
+    pub epoch_length: usize,
+    pub price_drift: PriceDrift,
+}
+
+impl SimulationConfig {
+    /// Creates simulation of a thousand steps with a pool funded up to its liquidity
+    /// target, swaps of up to a hundredth of the target and a slowly rising price
+    pub fn new(pool: PoolConfig, seed: u64) -> Self {
+        let target = pool.params.liquidity_target.raw();
+        Self {
+            initial_liquidity: pool.params.liquidity_target,
+            pool,
+            seed,
+            steps: 1_000,
+            swap_size: Distribution::LogUniform {
+                low: 1,
+                high: (target / 100).max(1),
+            },
+            deposit_probability: 0.1,
+            deposit_size: Distribution::LogUniform {
+                low: 1,
+                high: (target / 20).max(1),
+            },
+            epoch_length: 100,
+            price_drift: PriceDrift {
+                drift: 0.0002,
+                volatility: 0.0001,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Summary statistics of a simulation run
+pub struct SimulationStats {
+    pub successful_swaps: usize,
+    pub deposits: usize,
+    pub withdrawals: usize,
+    /// operations rejected by the pool
+    pub failed_ops: usize,
+    /// tokens paid out by swaps
+    pub swap_volume: TokenAmount,
+    /// all fees charged by swaps
+    pub fee_revenue: TokenAmount,
+    /// lowest observed ratio of pool tokens to the liquidity target
+    pub min_liquidity_ratio: Percentage,
+    /// ratio of pool tokens to the liquidity target after the last operation
+    pub final_liquidity_ratio: Percentage,
+    pub final_price: Price,
+    pub final_total_value: TokenAmount,
+}
+
+#[derive(Debug)]
+/// Result of a simulation run
+pub struct Simulation {
+    /// pool in its end state, with operation log of successful operations
+    pub pool: LpPool,
+    /// every generated operation in order, including rejected ones
+    pub ops: Vec<PoolOp>,
+    pub stats: SimulationStats,
+}
+
+/// Applies operations to the simulated pool and collects their statistics
+pub(crate) struct Recorder {
+    ops: Vec<PoolOp>,
+    stats: SimulationStats,
+}
+
+impl Recorder {
+    pub(crate) fn new(pool: &LpPool) -> Self {
+        // lowest ratio is tracked from the first operation on, which funds the pool
+        Self {
+            ops: Vec::new(),
+            stats: SimulationStats {
+                successful_swaps: 0,
+                deposits: 0,
+                withdrawals: 0,
+                failed_ops: 0,
+                swap_volume: 0.into(),
+                fee_revenue: 0.into(),
+                min_liquidity_ratio: Percentage::from_raw_amount(Uint::MAX),
+                final_liquidity_ratio: 0.0.into(),
+                final_price: pool.price(),
+                final_total_value: 0.into(),
+            },
+        }
+    }
+
+    /// Applies operation, returns true if the pool accepted it
+    pub(crate) fn run(&mut self, pool: &mut LpPool, op: PoolOp) -> bool {
+        let outcome = pool.apply(&op);
+        let stats = &mut self.stats;
+        match outcome {
+            Ok(OpOutcome::Swapped(amount_out)) => {
+                stats.successful_swaps += 1;
+                stats.swap_volume = stats.swap_volume + amount_out;
+            }
+            Ok(OpOutcome::LiquidityAdded(_)) => stats.deposits += 1,
+            Ok(OpOutcome::LiquidityRemoved(..)) => stats.withdrawals += 1,
+            Ok(_) => {}
+            Err(_) => stats.failed_ops += 1,
+        }
+        let ratio = liquidity_ratio(pool);
+        if ratio < stats.min_liquidity_ratio {
+            stats.min_liquidity_ratio = ratio;
+        }
+        self.ops.push(op);
+        outcome.is_ok()
+    }
+
+    /// Advances epoch and moves the price by the drift
+    pub(crate) fn end_epoch(&mut self, pool: &mut LpPool, drift: &PriceDrift, rng: &mut Rng) {
+        self.run(pool, PoolOp::AdvanceEpoch { epochs: 1 });
+        let price = drift.next(pool.price(), rng);
+        self.run(pool, PoolOp::SetPrice { price });
+    }
+
+    pub(crate) fn finish(mut self, pool: LpPool) -> Simulation {
+        self.stats.fee_revenue = pool.fee_revenue().total;
+        self.stats.final_liquidity_ratio = liquidity_ratio(&pool);
+        if self.stats.final_liquidity_ratio < self.stats.min_liquidity_ratio {
+            self.stats.min_liquidity_ratio = self.stats.final_liquidity_ratio;
+        }
+        self.stats.final_price = pool.price();
+        self.stats.final_total_value = pool.total_value();
+        Simulation {
+            pool,
+            ops: self.ops,
+            stats: self.stats,
+        }
+    }
+}
+
+/// Runs simulation, equal configurations produce equal operations and end states
+pub fn simulate(config: &SimulationConfig) -> Simulation {
+    let mut rng = Rng::new(config.seed);
+    let mut pool = config.pool.build();
+    let mut recorder = Recorder::new(&pool);
+
+    recorder.run(
+        &mut pool,
+        PoolOp::AddLiquidity {
+            account: None,
+            amount: config.initial_liquidity,
+        },
+    );
+    for step in 1..=config.steps {
+        let op = match rng.chance(config.deposit_probability) {
+            true => PoolOp::AddLiquidity {
+                account: None,
+                amount: TokenAmount::from_raw_amount(config.deposit_size.sample(&mut rng)),
+            },
+            false => PoolOp::Swap {
+                account: None,
+                amount: StakedTokenAmount::from_raw_amount(config.swap_size.sample(&mut rng)),
+            },
+        };
+        recorder.run(&mut pool, op);
+
+        if config.epoch_length > 0 && step % config.epoch_length == 0 {
+            recorder.end_epoch(&mut pool, &config.price_drift, &mut rng);
+        }
+    }
+    recorder.finish(pool)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Aggregated statistics of simulation runs
+pub struct SimulationSummary {
+    pub runs: usize,
+    pub successful_swaps: usize,
+    pub failed_ops: usize,
+    pub mean_fee_revenue: TokenAmount,
+    pub min_fee_revenue: TokenAmount,
+    pub max_fee_revenue: TokenAmount,
+    /// lowest liquidity ratio observed by any run
+    pub min_liquidity_ratio: Percentage,
+    pub mean_final_total_value: TokenAmount,
+}
+
+impl SimulationSummary {
+    fn new(runs: &[SimulationStats]) -> Self {
+        let mean = |amount: fn(&SimulationStats) -> TokenAmount| {
+            let sum: u128 = runs.iter().map(|stats| amount(stats).raw() as u128).sum();
+            TokenAmount::from_raw_amount((sum / runs.len().max(1) as u128) as Uint)
+        };
+        let fees = runs.iter().map(|stats| stats.fee_revenue);
+        Self {
+            runs: runs.len(),
+            successful_swaps: runs.iter().map(|stats| stats.successful_swaps).sum(),
+            failed_ops: runs.iter().map(|stats| stats.failed_ops).sum(),
+            mean_fee_revenue: mean(|stats| stats.fee_revenue),
+            min_fee_revenue: fees
+                .clone()
+                .min_by_key(|fees| fees.raw())
+                .unwrap_or_default(),
+            max_fee_revenue: fees.max_by_key(|fees| fees.raw()).unwrap_or_default(),
+            min_liquidity_ratio: runs
+                .iter()
+                .map(|stats| stats.min_liquidity_ratio)
+                .min_by_key(|ratio| ratio.raw())
+                .unwrap_or_default(),
+            mean_final_total_value: mean(|stats| stats.final_total_value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Results of `simulate_many`
+pub struct BulkSimulation {
+    /// statistics of every run, in the order of the configurations
+    pub runs: Vec<SimulationStats>,
+    pub summary: SimulationSummary,
+}
+
+/// Simulates `steps` operations on a pool built from every configuration, in parallel
+/// on all available cores. Run `i` uses the `i`-th seed drawn from `seed`, so results
+/// don't depend on how runs were scheduled. Only statistics are kept, end states of
+/// thousands of pools would take too much memory.
+pub fn simulate_many(configs: &[PoolConfig], steps: usize, seed: u64) -> BulkSimulation {
+    let mut rng = Rng::new(seed);
+    let configs: Vec<_> = configs
+        .iter()
+        .map(|pool| SimulationConfig {
+            steps,
+            ..SimulationConfig::new(pool.clone(), rng.next_u64())
+        })
+        .collect();
+
+    let next = AtomicUsize::new(0);
+    let runs = Mutex::new(vec![None; configs.len()]);
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |workers| workers.get())
+        .min(configs.len());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(config) = configs.get(index) else {
+                    break;
+                };
+                let stats = simulate(config).stats;
+                runs.lock().unwrap_or_else(|p| p.into_inner())[index] = Some(stats);
+            });
+        }
+    });
+
+    let runs: Vec<_> = runs
+        .into_inner()
+        .unwrap_or_else(|p| p.into_inner())
+        .into_iter()
+        .map(|stats| stats.expect("every run is simulated"))
+        .collect();
+    BulkSimulation {
+        summary: SimulationSummary::new(&runs),
+        runs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::governance::PoolParams;
+
+    fn config(seed: u64) -> SimulationConfig {
+        let pool = PoolConfig::new(
+            1.1.into(),
+            PoolParams {
+                min_fee: 0.001.into(),
+                max_fee: 0.09.into(),
+                liquidity_target: 10_000.into(),
+            },
+        );
+        SimulationConfig::new(pool, seed)
+    }
+
+    #[test]
+    fn is_reproducible_from_seed() {
+        let first = simulate(&config(7));
+        let second = simulate(&config(7));
+        assert_eq!(first.ops, second.ops);
+        assert_eq!(first.stats, second.stats);
+        assert_eq!(first.pool.state_hash(), second.pool.state_hash());
+        assert_ne!(simulate(&config(8)).ops, first.ops);
+
+        // operation log of the end state replays into the same pool
+        let replayed = LpPool::replay(&config(7).pool, first.pool.op_log()).unwrap();
+        assert_eq!(replayed.state_hash(), first.pool.state_hash());
+    }
+
+    #[test]
+    fn summarizes_run() {
+        let config = config(1);
+        let Simulation { ops, stats, .. } = simulate(&config);
+        let epochs = config.steps / config.epoch_length;
+        assert_eq!(ops.len(), 1 + config.steps + 2 * epochs);
+        assert_eq!(
+            stats.successful_swaps + stats.deposits + stats.failed_ops,
+            1 + config.steps
+        );
+        assert!(
+            stats.deposits > 50 && stats.successful_swaps > 800,
+            "{stats:?}"
+        );
+        assert!(stats.fee_revenue > 0.into());
+        assert!(stats.min_liquidity_ratio <= stats.final_liquidity_ratio);
+        assert!(stats.final_price > config.pool.price);
+    }
+
+    #[test]
+    fn samples_within_bounds() {
+        let mut rng = Rng::new(0);
+        let log = Distribution::LogUniform {
+            low: 10,
+            high: 1_000_000,
+        };
+        let samples: Vec<_> = (0..1_000).map(|_| log.sample(&mut rng)).collect();
+        assert!(samples
+            .iter()
+            .all(|sample| (10..=1_000_000).contains(sample)));
+        // two of five magnitudes are below a thousand
+        let small = samples.iter().filter(|&&sample| sample < 1_000).count();
+        assert!((330..470).contains(&small), "{small}");
+        assert_eq!(Distribution::Fixed(5).sample(&mut rng), 5);
+    }
+
+    #[test]
+    fn simulates_many_configs_deterministically() {
+        let configs: Vec<_> = [0.001, 0.005, 0.01]
+            .into_iter()
+            .flat_map(|min_fee| {
+                [0.03, 0.09].map(|max_fee| {
+                    let mut pool = config(0).pool;
+                    pool.params.min_fee = min_fee.into();
+                    pool.params.max_fee = max_fee.into();
+                    pool
+                })
+            })
+            .collect();
+        let bulk = simulate_many(&configs, 200, 5);
+        assert_eq!(bulk, simulate_many(&configs, 200, 5));
+        assert_eq!(bulk.runs.len(), 6);
+        assert_eq!(bulk.summary.runs, 6);
+
+        // every run matches its sequential simulation
+        let mut rng = Rng::new(5);
+        for (pool, stats) in configs.iter().zip(&bulk.runs) {
+            let config = SimulationConfig {
+                steps: 200,
+                ..SimulationConfig::new(pool.clone(), rng.next_u64())
+            };
+            assert_eq!(simulate(&config).stats, *stats);
+        }
+        let summary = bulk.summary;
+        assert!(summary.min_fee_revenue <= summary.mean_fee_revenue);
+        assert!(summary.mean_fee_revenue <= summary.max_fee_revenue);
+        assert_eq!(
+            summary.successful_swaps,
+            bulk.runs.iter().map(|stats| stats.successful_swaps).sum()
+        );
+        assert_eq!(simulate_many(&[], 10, 5).summary.runs, 0);
+    }
+}
@@ -0,0 +1,488 @@
+//! Compact binary snapshots of pool state and operation logs. Pool state uses the same
+//! schema as JSON documents, encoded with varint numbers and a table of object keys so
+//! repeated field names are stored once. Operation logs are encoded directly, a few bytes
+//! per operation.
+
+use crate::error::SnapshotError;
+use crate::json::Value;
+use crate::lp_pool::LpPool;
+use crate::ops::PoolOp;
+use crate::schema::{FromJson, ToJson};
+use crate::types::*;
+
+const STATE_MAGIC: &[u8; 4] = b"LPSN";
+const OPS_MAGIC: &[u8; 4] = b"LPOP";
+/// version of the binary encoding, independent from `SCHEMA_VERSION` of the state itself
+const FORMAT_VERSION: u8 = 1;
+
+const TAG_NULL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_UNSIGNED: u8 = 3;
+const TAG_NEGATIVE: u8 = 4;
+const TAG_NUMBER_LITERAL: u8 = 5;
+const TAG_STRING: u8 = 6;
+const TAG_ARRAY: u8 = 7;
+const TAG_OBJECT: u8 = 8;
+
+const OP_ADD_LIQUIDITY: u8 = 0;
+const OP_REMOVE_LIQUIDITY: u8 = 1;
+const OP_SWAP: u8 = 2;
+const OP_SET_PRICE: u8 = 3;
+const OP_ADVANCE_EPOCH: u8 = 4;
+/// swaps attributed to an account have their own tag, so logs of anonymous swaps encoded
+/// before swaps had accounts keep decoding
+const OP_ACCOUNT_SWAP: u8 = 5;
+const OP_UNREPLAYABLE: u8 = 6;
+
+impl LpPool {
+    /// Encodes pool state into a compact binary snapshot
+    pub fn to_snapshot(&self) -> Vec<u8> {
+        encode_value(&self.to_json())
+    }
+
+    /// Restores pool state from a snapshot created with `to_snapshot`
+    pub fn from_snapshot(bytes: &[u8]) -> Result<LpPool, SnapshotError> {
+        Ok(LpPool::from_json(&decode_value(bytes)?)?)
+    }
+}
+
+/// Encodes JSON value into the binary snapshot format
+pub fn encode_value(value: &Value) -> Vec<u8> {
+    let mut keys = Vec::new();
+    collect_keys(value, &mut keys);
+
+    let mut writer = Writer::new(STATE_MAGIC);
+    writer.varint(keys.len() as u128);
+    for key in &keys {
+        writer.bytes(key.as_bytes());
+    }
+    writer.value(value, &keys);
+    writer.buffer
+}
+
+/// Decodes JSON value from the binary snapshot format
+pub fn decode_value(bytes: &[u8]) -> Result<Value, SnapshotError> {
+    let mut reader = Reader::new(bytes, STATE_MAGIC)?;
+    let key_count = reader.length()?;
+    let keys = (0..key_count)
+        .map(|_| reader.string())
+        .collect::<Result<Vec<_>, _>>()?;
+    let value = reader.value(&keys, 0)?;
+    reader.finish()?;
+    Ok(value)
+}
+
+/// Encodes log of pool operations
+pub fn encode_ops(ops: &[PoolOp]) -> Vec<u8> {
+    let mut writer = Writer::new(OPS_MAGIC);
+    writer.varint(ops.len() as u128);
+    ops.iter().for_each(|op| writer.op(op));
+    writer.buffer
+}
+
+/// Decodes log of pool operations created with `encode_ops`
+pub fn decode_ops(bytes: &[u8]) -> Result<Vec<PoolOp>, SnapshotError> {
+    let mut reader = Reader::new(bytes, OPS_MAGIC)?;
+    let count = reader.length()?;
+    let ops = (0..count).map(|_| reader.op()).collect::<Result<_, _>>()?;
+    reader.finish()?;
+    Ok(ops)
+}
+
+/// Encodes single operation without any header, records can be concatenated into
+/// append-only logs
+pub(crate) fn encode_op_record(op: &PoolOp) -> Vec<u8> {
+    let mut writer = Writer { buffer: Vec::new() };
+    writer.op(op);
+    writer.buffer
+}
+
+/// Decodes concatenated records created with `encode_op_record`
+pub(crate) fn decode_op_records(bytes: &[u8]) -> Result<Vec<PoolOp>, SnapshotError> {
+    let mut reader = Reader { bytes, position: 0 };
+    let mut ops = Vec::new();
+    while reader.position < bytes.len() {
+        ops.push(reader.op()?);
+    }
+    Ok(ops)
+}
+
+fn collect_keys<'a>(value: &'a Value, keys: &mut Vec<&'a str>) {
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| collect_keys(item, keys)),
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                if !keys.contains(&key.as_str()) {
+                    keys.push(key);
+                }
+                collect_keys(value, keys);
+            }
+        }
+        _ => {}
+    }
+}
+
+struct Writer {
+    buffer: Vec<u8>,
+}
+
+impl Writer {
+    fn new(magic: &[u8; 4]) -> Self {
+        let mut buffer = magic.to_vec();
+        buffer.push(FORMAT_VERSION);
+        Self { buffer }
+    }
+
+    fn byte(&mut self, byte: u8) {
+        self.buffer.push(byte);
+    }
+
+    /// LEB128 encoding, small numbers take a single byte
+    fn varint(&mut self, mut value: u128) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.buffer.push(byte);
+                return;
+            }
+            self.buffer.push(byte | 0x80);
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.varint(bytes.len() as u128);
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    fn account(&mut self, account: Option<AccountId>) {
+        match account {
+            Some(account) => {
+                self.byte(1);
+                self.varint(account as u128);
+            }
+            None => self.byte(0),
+        }
+    }
+
+    fn op(&mut self, op: &PoolOp) {
+        match *op {
+            PoolOp::AddLiquidity { account, amount } => {
+                self.byte(OP_ADD_LIQUIDITY);
+                self.account(account);
+                self.varint(amount.raw() as u128);
+            }
+            PoolOp::RemoveLiquidity { account, lp_amount } => {
+                self.byte(OP_REMOVE_LIQUIDITY);
+                self.account(account);
+                self.varint(lp_amount.raw() as u128);
+            }
+            PoolOp::Swap {
+                account: None,
+                amount,
+            } => {
+                self.byte(OP_SWAP);
+                self.varint(amount.raw() as u128);
+            }
+            PoolOp::Swap {
+                account: Some(account),
+                amount,
+            } => {
+                self.byte(OP_ACCOUNT_SWAP);
+                self.varint(account as u128);
+                self.varint(amount.raw() as u128);
+            }
+            PoolOp::SetPrice { price } => {
+                self.byte(OP_SET_PRICE);
+                self.varint(price.raw() as u128);
+            }
+            PoolOp::AdvanceEpoch { epochs } => {
+                self.byte(OP_ADVANCE_EPOCH);
+                self.varint(epochs as u128);
+            }
+            PoolOp::Unreplayable => self.byte(OP_UNREPLAYABLE),
+        }
+    }
+
+    fn value(&mut self, value: &Value, keys: &[&str]) {
+        match value {
+            Value::Null => self.byte(TAG_NULL),
+            Value::Bool(false) => self.byte(TAG_FALSE),
+            Value::Bool(true) => self.byte(TAG_TRUE),
+            Value::Number(literal) => self.number(literal),
+            Value::String(value) => {
+                self.byte(TAG_STRING);
+                self.bytes(value.as_bytes());
+            }
+            Value::Array(items) => {
+                self.byte(TAG_ARRAY);
+                self.varint(items.len() as u128);
+                items.iter().for_each(|item| self.value(item, keys));
+            }
+            Value::Object(fields) => {
+                self.byte(TAG_OBJECT);
+                self.varint(fields.len() as u128);
+                for (key, value) in fields {
+                    let index = keys
+                        .iter()
+                        .position(|known| known == key)
+                        .expect("all keys are collected before encoding");
+                    self.varint(index as u128);
+                    self.value(value, keys);
+                }
+            }
+        }
+    }
+
+    /// Integers are stored as varints, other literals (fractions, exponents, non canonical
+    /// forms) are kept as text so they decode to the very same literal
+    fn number(&mut self, literal: &str) {
+        if let Ok(value) = literal.parse::<u128>() {
+            if value.to_string() == literal {
+                self.byte(TAG_UNSIGNED);
+                self.varint(value);
+                return;
+            }
+        }
+        if let Some(magnitude) = literal.strip_prefix('-') {
+            if let Ok(value) = magnitude.parse::<u128>() {
+                if value != 0 && value.to_string() == magnitude {
+                    self.byte(TAG_NEGATIVE);
+                    self.varint(value);
+                    return;
+                }
+            }
+        }
+        self.byte(TAG_NUMBER_LITERAL);
+        self.bytes(literal.as_bytes());
+    }
+}
+
+/// max nesting of arrays and objects, protects the recursive decoder from stack overflows
+const MAX_DEPTH: usize = 64;
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], magic: &[u8; 4]) -> Result<Self, SnapshotError> {
+        if bytes.len() < 5 || &bytes[..4] != magic {
+            return Err(SnapshotError::InvalidHeader);
+        }
+        if bytes[4] != FORMAT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(bytes[4]));
+        }
+        Ok(Self { bytes, position: 5 })
+    }
+
+    fn finish(&self) -> Result<(), SnapshotError> {
+        match self.position == self.bytes.len() {
+            true => Ok(()),
+            false => Err(SnapshotError::TrailingBytes),
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, SnapshotError> {
+        let byte = *self
+            .bytes
+            .get(self.position)
+            .ok_or(SnapshotError::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u128, SnapshotError> {
+        let mut value = 0u128;
+        for shift in (0..128).step_by(7) {
+            let byte = self.byte()?;
+            let bits = (byte & 0x7f) as u128;
+            if shift == 126 && bits > 0b11 {
+                return Err(SnapshotError::VarintOverflow);
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(SnapshotError::VarintOverflow)
+    }
+
+    fn uint(&mut self) -> Result<Uint, SnapshotError> {
+        Uint::try_from(self.varint()?).map_err(|_| SnapshotError::VarintOverflow)
+    }
+
+    fn length(&mut self) -> Result<usize, SnapshotError> {
+        let length = self.varint()?;
+        match length <= (self.bytes.len() - self.position) as u128 {
+            true => Ok(length as usize),
+            // every element takes at least one byte, longer lengths are corrupted
+            false => Err(SnapshotError::UnexpectedEnd),
+        }
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], SnapshotError> {
+        let length = self.length()?;
+        let bytes = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn string(&mut self) -> Result<String, SnapshotError> {
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| SnapshotError::InvalidUtf8)
+    }
+
+    fn account(&mut self) -> Result<Option<AccountId>, SnapshotError> {
+        match self.byte()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.uint()?)),
+            tag => Err(SnapshotError::InvalidTag(tag)),
+        }
+    }
+
+    fn op(&mut self) -> Result<PoolOp, SnapshotError> {
+        let op = match self.byte()? {
+            OP_ADD_LIQUIDITY => PoolOp::AddLiquidity {
+                account: self.account()?,
+                amount: TokenAmount::from_raw_amount(self.uint()?),
+            },
+            OP_REMOVE_LIQUIDITY => PoolOp::RemoveLiquidity {
+                account: self.account()?,
+                lp_amount: LpTokenAmount::from_raw_amount(self.uint()?),
+            },
+            OP_SWAP => PoolOp::Swap {
+                account: None,
+                amount: StakedTokenAmount::from_raw_amount(self.uint()?),
+            },
+            OP_ACCOUNT_SWAP => PoolOp::Swap {
+                account: Some(self.uint()?),
+                amount: StakedTokenAmount::from_raw_amount(self.uint()?),
+            },
+            OP_SET_PRICE => PoolOp::SetPrice {
+                price: Price::from_raw_amount(self.uint()?),
+            },
+            OP_ADVANCE_EPOCH => PoolOp::AdvanceEpoch {
+                epochs: self.uint()?,
+            },
+            OP_UNREPLAYABLE => PoolOp::Unreplayable,
+            tag => return Err(SnapshotError::InvalidTag(tag)),
+        };
+        Ok(op)
+    }
+
+    fn value(&mut self, keys: &[String], depth: usize) -> Result<Value, SnapshotError> {
+        if depth > MAX_DEPTH {
+            return Err(SnapshotError::TooDeep);
+        }
+        let value = match self.byte()? {
+            TAG_NULL => Value::Null,
+            TAG_FALSE => Value::Bool(false),
+            TAG_TRUE => Value::Bool(true),
+            TAG_UNSIGNED => Value::number(self.varint()?),
+            TAG_NEGATIVE => Value::Number(format!("-{}", self.varint()?)),
+            TAG_NUMBER_LITERAL => Value::Number(self.string()?),
+            TAG_STRING => Value::String(self.string()?),
+            TAG_ARRAY => {
+                let length = self.length()?;
+                let items = (0..length)
+                    .map(|_| self.value(keys, depth + 1))
+                    .collect::<Result<_, _>>()?;
+                Value::Array(items)
+            }
+            TAG_OBJECT => {
+                let length = self.length()?;
+                let mut fields = Vec::with_capacity(length);
+                for _ in 0..length {
+                    let index = self.varint()?;
+                    let key = usize::try_from(index)
+                        .ok()
+                        .and_then(|index| keys.get(index))
+                        .ok_or(SnapshotError::InvalidKeyIndex(index))?;
+                    fields.push((key.clone(), self.value(keys, depth + 1)?));
+                }
+                Value::Object(fields)
+            }
+            tag => return Err(SnapshotError::InvalidTag(tag)),
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pool_snapshot_is_smaller_than_json() {
+        let mut pool = LpPool::init(1.5.into(), 0.1.into(), 0.9.into(), 100.into()).unwrap();
+        pool.add_liquidity_for(1, 1000.into()).unwrap();
+        for epoch in 0..40 {
+            pool.set_price(Price::from_raw_amount(1_500_000 + epoch * 1_000))
+                .unwrap();
+            pool.swap(StakedTokenAmount::from(1)).unwrap();
+            pool.advance_epoch(1);
+        }
+
+        let snapshot = pool.to_snapshot();
+        let restored = LpPool::from_snapshot(&snapshot).unwrap();
+
+        assert_eq!(restored.to_json(), pool.to_json());
+        assert!(snapshot.len() * 2 < pool.to_json().to_string().len());
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let value = Value::parse(r#"{"a": [-5, 1.25, 1e3, 340282366920938463463374607431768211455], "b": {"a": null, "c": "x"}, "d": true}"#)
+            .unwrap();
+        assert_eq!(decode_value(&encode_value(&value)), Ok(value));
+    }
+
+    #[test]
+    fn ops_round_trip() {
+        let ops = vec![
+            PoolOp::AddLiquidity {
+                account: Some(3),
+                amount: 100.into(),
+            },
+            PoolOp::RemoveLiquidity {
+                account: None,
+                lp_amount: 1.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 6.into(),
+            },
+            PoolOp::Swap {
+                account: Some(3),
+                amount: 6.into(),
+            },
+            PoolOp::SetPrice { price: 1.1.into() },
+            PoolOp::AdvanceEpoch { epochs: 1 },
+        ];
+        let encoded = encode_ops(&ops);
+
+        assert_eq!(decode_ops(&encoded), Ok(ops));
+        assert!(encoded.len() < 50);
+    }
+
+    #[test]
+    fn rejects_corrupted_input() {
+        let encoded = encode_ops(&[PoolOp::Swap {
+            account: None,
+            amount: 6.into(),
+        }]);
+
+        assert_eq!(
+            decode_ops(&encoded[..encoded.len() - 1]),
+            Err(SnapshotError::UnexpectedEnd)
+        );
+        assert_eq!(decode_value(&encoded), Err(SnapshotError::InvalidHeader));
+        assert_eq!(
+            decode_ops(&[b'L', b'P', b'O', b'P', 1, 1, 9]),
+            Err(SnapshotError::InvalidTag(9))
+        );
+    }
+}
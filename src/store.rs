@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+use crate::error::StoreError;
+use crate::lp_pool::LpPool;
+use crate::ops::PoolOp;
+use crate::snapshot::{decode_op_records, encode_op_record};
+
+/// Persistence of many pools identified by name. Every pool has its latest saved state
+/// and a log of operations appended since that state was saved.
+pub trait PoolStore {
+    /// Saves state of the pool, clearing operations logged before
+    fn save(&mut self, id: &str, pool: &LpPool) -> Result<(), StoreError>;
+
+    /// Loads last saved state of the pool, `None` if it was never saved
+    fn load(&self, id: &str) -> Result<Option<LpPool>, StoreError>;
+
+    /// Returns ids of saved pools in ascending order
+    fn list(&self) -> Result<Vec<String>, StoreError>;
+
+    /// Appends operation to the log of the pool
+    fn append_op(&mut self, id: &str, op: &PoolOp) -> Result<(), StoreError>;
+
+    /// Returns operations appended since the pool was last saved
+    fn ops(&self, id: &str) -> Result<Vec<PoolOp>, StoreError>;
+}
+
+/// Pool ids are used as file names, so only portable characters are allowed
+pub(crate) fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '-' || char == '_')
+}
+
+fn validate_id(id: &str) -> Result<(), StoreError> {
+    match is_valid_id(id) {
+        true => Ok(()),
+        false => Err(StoreError::InvalidId(id.into())),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Store keeping pool snapshots in memory, useful for tests and short lived services
+pub struct MemoryStore {
+    pools: BTreeMap<String, (Vec<u8>, Vec<PoolOp>)>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PoolStore for MemoryStore {
+    fn save(&mut self, id: &str, pool: &LpPool) -> Result<(), StoreError> {
+        validate_id(id)?;
+        self.pools
+            .insert(id.into(), (pool.to_snapshot(), Vec::new()));
+        Ok(())
+    }
+
+    fn load(&self, id: &str) -> Result<Option<LpPool>, StoreError> {
+        validate_id(id)?;
+        self.pools
+            .get(id)
+            .map(|(snapshot, _)| LpPool::from_snapshot(snapshot))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    fn list(&self) -> Result<Vec<String>, StoreError> {
+        Ok(self.pools.keys().cloned().collect())
+    }
+
+    fn append_op(&mut self, id: &str, op: &PoolOp) -> Result<(), StoreError> {
+        validate_id(id)?;
+        let (_, ops) = self
+            .pools
+            .get_mut(id)
+            .ok_or_else(|| StoreError::UnknownPool(id.into()))?;
+        ops.push(*op);
+        Ok(())
+    }
+
+    fn ops(&self, id: &str) -> Result<Vec<PoolOp>, StoreError> {
+        validate_id(id)?;
+        self.pools
+            .get(id)
+            .map(|(_, ops)| ops.clone())
+            .ok_or_else(|| StoreError::UnknownPool(id.into()))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Store keeping every pool in a directory as a binary snapshot file (`<id>.pool`) and an
+/// append-only operation log (`<id>.ops`)
+pub struct FileStore {
+    directory: PathBuf,
+}
+
+impl FileStore {
+    /// Opens store in the directory, creating it if it doesn't exist
+    pub fn open(directory: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        Ok(Self { directory })
+    }
+
+    fn snapshot_path(&self, id: &str) -> PathBuf {
+        self.directory.join(format!("{id}.pool"))
+    }
+
+    fn ops_path(&self, id: &str) -> PathBuf {
+        self.directory.join(format!("{id}.ops"))
+    }
+}
+
+impl PoolStore for FileStore {
+    fn save(&mut self, id: &str, pool: &LpPool) -> Result<(), StoreError> {
+        validate_id(id)?;
+        // snapshot is moved in place only after it was fully written
+        let temporary = self.directory.join(format!("{id}.pool.tmp"));
+        fs::write(&temporary, pool.to_snapshot())?;
+        fs::rename(&temporary, self.snapshot_path(id))?;
+        fs::write(self.ops_path(id), [])?;
+        Ok(())
+    }
+
+    fn load(&self, id: &str) -> Result<Option<LpPool>, StoreError> {
+        validate_id(id)?;
+        match fs::read(self.snapshot_path(id)) {
+            Ok(snapshot) => Ok(Some(LpPool::from_snapshot(&snapshot)?)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<String>, StoreError> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "pool")
+            {
+                if let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn append_op(&mut self, id: &str, op: &PoolOp) -> Result<(), StoreError> {
+        validate_id(id)?;
+        if !self.snapshot_path(id).exists() {
+            return Err(StoreError::UnknownPool(id.into()));
+        }
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(self.ops_path(id))?
+            .write_all(&encode_op_record(op))?;
+        Ok(())
+    }
+
+    fn ops(&self, id: &str) -> Result<Vec<PoolOp>, StoreError> {
+        validate_id(id)?;
+        if !self.snapshot_path(id).exists() {
+            return Err(StoreError::UnknownPool(id.into()));
+        }
+        match fs::read(self.ops_path(id)) {
+            Ok(bytes) => Ok(decode_op_records(&bytes)?),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::ToJson;
+
+    fn pool() -> LpPool {
+        let mut pool = LpPool::init(1.5.into(), 0.1.into(), 0.9.into(), 100.into()).unwrap();
+        pool.add_liquidity(100.into()).unwrap();
+        pool
+    }
+
+    fn exercise(store: &mut impl PoolStore) {
+        let pool = pool();
+        let swap = PoolOp::Swap {
+            account: None,
+            amount: 6.into(),
+        };
+        assert!(matches!(
+            store.append_op("a", &swap),
+            Err(StoreError::UnknownPool(_))
+        ));
+        assert!(matches!(
+            store.save("../a", &pool),
+            Err(StoreError::InvalidId(_))
+        ));
+
+        store.save("b", &pool).unwrap();
+        store.save("a", &pool).unwrap();
+        store.append_op("a", &swap).unwrap();
+        store
+            .append_op("a", &PoolOp::AdvanceEpoch { epochs: 1 })
+            .unwrap();
+
+        assert_eq!(store.list().unwrap(), vec!["a", "b"]);
+        assert_eq!(store.load("a").unwrap().unwrap().to_json(), pool.to_json());
+        assert!(store.load("c").unwrap().is_none());
+        assert_eq!(
+            store.ops("a").unwrap(),
+            vec![swap, PoolOp::AdvanceEpoch { epochs: 1 }]
+        );
+
+        store.save("a", &pool).unwrap();
+        assert!(store.ops("a").unwrap().is_empty());
+    }
+
+    #[test]
+    fn memory_store_persists_pools() {
+        exercise(&mut MemoryStore::new());
+    }
+
+    #[test]
+    fn file_store_persists_pools() {
+        let directory = std::env::temp_dir().join(format!("lp-pool-store-{}", std::process::id()));
+        exercise(&mut FileStore::open(&directory).unwrap());
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
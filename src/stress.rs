@@ -0,0 +1,237 @@
+//! Stress checks with values at the edges of the representable range. Generated
+//! configurations and operations use amounts and prices near `Uint::MAX`, dust of a few
+//! raw units and pathological fee settings, like inverted or over 100% fees, and every
+//! operation has to either succeed with conserved balances or fail with a typed error.
+//! Panics are caught and reported together with the operation which caused them, and
+//! silent truncation shows up as a conservation violation, see `check_step`.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::conservation::check_step;
+use crate::error::StressError;
+use crate::fee_policy::FeePolicy;
+use crate::governance::PoolParams;
+use crate::lp_pool::LpPool;
+use crate::ops::PoolOp;
+use crate::price_smoothing::PriceSmoothing;
+use crate::replay::PoolConfig;
+use crate::rng::Rng;
+use crate::surcharge::{SurchargeConfig, SurchargeDecay};
+use crate::types::*;
+
+/// raw amounts at the edges of the representable range
+pub const EDGE_AMOUNTS: [Uint; 12] = [
+    0,
+    1,
+    2,
+    SCALE - 1,
+    SCALE,
+    SCALE + 1,
+    Uint::MAX / SCALE,
+    Uint::MAX / SCALE + 1,
+    Uint::MAX / 2,
+    Uint::MAX / 2 + 1,
+    Uint::MAX - 1,
+    Uint::MAX,
+];
+/// raw prices at the edges of the representable range
+pub const EDGE_PRICES: [Uint; 8] = [
+    0,
+    1,
+    SCALE - 1,
+    SCALE,
+    SCALE + 1,
+    Uint::MAX / SCALE,
+    Uint::MAX / 2,
+    Uint::MAX,
+];
+/// raw percentages around zero and 100%, including invalid ones above it
+pub const EDGE_PERCENTAGES: [Uint; 8] = [
+    0,
+    1,
+    SCALE / 2,
+    SCALE - 1,
+    SCALE,
+    SCALE + 1,
+    Uint::MAX / 2,
+    Uint::MAX,
+];
+
+fn pick(rng: &mut Rng, values: &[Uint]) -> Uint {
+    values[rng.below(values.len() as u64) as usize]
+}
+
+/// Returns edge value most of the time and a random raw value near an edge otherwise
+fn edge_amount(rng: &mut Rng) -> Uint {
+    match rng.chance(0.8) {
+        true => pick(rng, &EDGE_AMOUNTS),
+        false => {
+            let edge = pick(rng, &EDGE_AMOUNTS);
+            let offset = rng.range(0, 1_000);
+            match rng.chance(0.5) {
+                true => edge.saturating_add(offset),
+                false => edge.saturating_sub(offset),
+            }
+        }
+    }
+}
+
+fn edge_percentage(rng: &mut Rng) -> Percentage {
+    Percentage::from_raw_amount(pick(rng, &EDGE_PERCENTAGES))
+}
+
+/// Generates configuration with extreme prices and fee settings, it's not necessarily
+/// valid, e.g. min fee can be above max fee, fees can exceed 100% and the liquidity
+/// target can be zero
+pub fn extreme_config(rng: &mut Rng) -> PoolConfig {
+    let params = PoolParams {
+        min_fee: edge_percentage(rng),
+        max_fee: edge_percentage(rng),
+        liquidity_target: TokenAmount::from_raw_amount(edge_amount(rng)),
+    };
+    let price = Price::from_raw_amount(pick(rng, &EDGE_PRICES).max(1));
+    let mut config = PoolConfig::new(price, params);
+    config.treasury_cut = edge_percentage(rng);
+    if rng.chance(0.3) {
+        config.fee_policy = FeePolicy::VolatilitySensitive {
+            sensitivity: edge_percentage(rng),
+            max_surcharge: edge_percentage(rng),
+        };
+    }
+    if rng.chance(0.3) {
+        config.price_smoothing = PriceSmoothing::Ema {
+            alpha: edge_percentage(rng),
+        };
+    }
+    if rng.chance(0.3) {
+        config.surcharge = Some(SurchargeConfig {
+            large_swap_threshold: edge_percentage(rng),
+            surcharge: edge_percentage(rng),
+            decay_rate: edge_percentage(rng),
+            decay: match rng.chance(0.5) {
+                true => SurchargeDecay::PerOperation,
+                false => SurchargeDecay::PerEpoch,
+            },
+        });
+    }
+    config.max_price_age = rng.chance(0.3).then(|| pick(rng, &EDGE_AMOUNTS));
+    config.max_price_deviation = rng.chance(0.3).then(|| edge_percentage(rng));
+    config.monotonic_price = rng.chance(0.2);
+    config
+}
+
+/// Generates operations with extreme amounts, prices and epoch jumps
+pub fn extreme_ops(rng: &mut Rng, len: usize) -> Vec<PoolOp> {
+    let account = |rng: &mut Rng| rng.chance(0.5).then(|| rng.below(2));
+    (0..len)
+        .map(|_| match rng.below(9) {
+            0..=2 => PoolOp::AddLiquidity {
+                account: account(rng),
+                amount: TokenAmount::from_raw_amount(edge_amount(rng)),
+            },
+            3..=4 => PoolOp::RemoveLiquidity {
+                account: account(rng),
+                lp_amount: LpTokenAmount::from_raw_amount(edge_amount(rng)),
+            },
+            5..=6 => PoolOp::Swap {
+                account: None,
+                amount: StakedTokenAmount::from_raw_amount(edge_amount(rng)),
+            },
+            7 => PoolOp::SetPrice {
+                price: Price::from_raw_amount(pick(rng, &EDGE_PRICES)),
+            },
+            _ => PoolOp::AdvanceEpoch {
+                epochs: pick(rng, &EDGE_AMOUNTS),
+            },
+        })
+        .collect()
+}
+
+/// Runs `f`, converting a panic into `StressError::Panicked`
+fn guard<T>(step: usize, op: Option<PoolOp>, f: impl FnOnce() -> T) -> Result<T, StressError> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        StressError::Panicked { step, op, message }
+    })
+}
+
+/// Queries every read-only value of the pool, which has to be representable in any
+/// state the pool accepted
+fn query(pool: &LpPool) {
+    for amount in [1, SCALE, Uint::MAX] {
+        let _ = pool.fee_for_swap(StakedTokenAmount::from_raw_amount(amount));
+        let _ = pool.amount_for_swap(StakedTokenAmount::from_raw_amount(amount));
+    }
+    pool.current_fee();
+    pool.total_value();
+    pool.lp_tokens_value(pool.lp_token_amount());
+    pool.estimate_lp_apy(Epoch::MAX);
+    pool.fee_curve_points(3);
+    for account in 0..2 {
+        pool.position_pnl(account);
+    }
+}
+
+/// Validates configuration and applies operations to a pool built from it, checking that
+/// nothing panics and that every step conserves balances. Invalid configurations have to
+/// be rejected by validation, pools built from them anyway still mustn't panic.
+pub fn check_extremes(config: &PoolConfig, ops: &[PoolOp]) -> Result<(), StressError> {
+    let params = config.params;
+    let valid = params.min_fee <= params.max_fee
+        && params.max_fee.raw() <= SCALE
+        && params.liquidity_target.raw() > 0;
+    if guard(0, None, || params.validate())?.is_ok() != valid {
+        return Err(StressError::InvalidConfigAccepted(params));
+    }
+
+    let mut pool = guard(0, None, || config.build())?;
+    for (step, op) in ops.iter().enumerate() {
+        let before = pool.balances();
+        let outcome = guard(step, Some(*op), || pool.apply(op))?;
+        check_step(step, &before, op, &outcome, &pool.balances())?;
+        guard(step, Some(*op), || query(&pool))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extreme_values_never_panic() {
+        let results: Vec<_> = (0..300)
+            .map(|seed| {
+                let mut rng = Rng::new(seed);
+                let config = extreme_config(&mut rng);
+                let ops = extreme_ops(&mut rng, 40);
+                (seed, check_extremes(&config, &ops))
+            })
+            .collect();
+
+        for (seed, result) in results {
+            if let Err(error) = result {
+                panic!("seed {seed}: {error}");
+            }
+        }
+    }
+
+    #[test]
+    fn huge_amounts_fail_with_typed_errors() {
+        let mut pool = LpPool::init(1_000.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap();
+        pool.add_liquidity(TokenAmount::from_raw_amount(Uint::MAX))
+            .unwrap();
+        assert!(matches!(
+            pool.add_liquidity(1.into()),
+            Err(crate::error::AddLiquidityError::TokenAmountTooBig)
+        ));
+        assert!(matches!(
+            pool.swap(StakedTokenAmount::from_raw_amount(Uint::MAX)),
+            Err(crate::error::SwapError::AmountTooBig)
+        ));
+    }
+}
@@ -0,0 +1,239 @@
+//! Subscriptions to pool events for independent consumers like metrics, websockets or
+//! persistence. Every subscriber receives its own copy of every event emitted after it
+//! subscribed, regardless of the registered event sink or of `drain_events`. Queues are
+//! unbounded, so slow consumers never block the pool, and subscribers whose receiver was
+//! dropped are removed on the next event.
+//!
+//! Synchronous subscribers get a std channel receiver, crossbeam based since Rust 1.67,
+//! so it needs no extra dependency. With the `async-events` feature subscribers can await
+//! events instead, from any executor, through the receiver's `recv` future.
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::events::PoolEvent;
+use crate::lp_pool::LpPool;
+
+#[derive(Debug)]
+enum Subscriber {
+    Sync(Sender<PoolEvent>),
+    #[cfg(feature = "async-events")]
+    Async(r#async::AsyncSender),
+}
+
+#[derive(Debug, Default)]
+/// Subscribers of a pool
+pub(crate) struct Subscribers(Vec<Subscriber>);
+
+impl Subscribers {
+    /// Sends copy of the event to every subscriber, dropping ones which stopped listening
+    pub(crate) fn publish(&mut self, event: &PoolEvent) {
+        self.0.retain(|subscriber| match subscriber {
+            Subscriber::Sync(sender) => sender.send(event.clone()).is_ok(),
+            #[cfg(feature = "async-events")]
+            Subscriber::Async(sender) => sender.send(event),
+        });
+    }
+}
+
+impl LpPool {
+    /// Returns receiver of every event emitted from now on
+    pub fn subscribe(&mut self) -> Receiver<PoolEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers_mut().0.push(Subscriber::Sync(sender));
+        receiver
+    }
+
+    /// Returns receiver awaiting every event emitted from now on
+    #[cfg(feature = "async-events")]
+    pub fn subscribe_async(&mut self) -> AsyncEventReceiver {
+        let (sender, receiver) = r#async::channel();
+        self.subscribers_mut().0.push(Subscriber::Async(sender));
+        receiver
+    }
+
+    /// Returns amount of subscribers still listening as of the last event
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers().0.len()
+    }
+}
+
+#[cfg(feature = "async-events")]
+pub use r#async::AsyncEventReceiver;
+
+#[cfg(feature = "async-events")]
+mod r#async {
+    use std::collections::VecDeque;
+    use std::future::poll_fn;
+    use std::sync::{Arc, Mutex, MutexGuard};
+    use std::task::{Context, Poll, Waker};
+
+    use crate::events::PoolEvent;
+
+    #[derive(Debug, Default)]
+    struct Queue {
+        events: VecDeque<PoolEvent>,
+        waker: Option<Waker>,
+        /// set when the pool was dropped
+        closed: bool,
+    }
+
+    #[derive(Debug, Default)]
+    struct Shared(Mutex<Queue>);
+
+    impl Shared {
+        fn lock(&self) -> MutexGuard<'_, Queue> {
+            self.0.lock().unwrap_or_else(|p| p.into_inner())
+        }
+    }
+
+    pub(super) fn channel() -> (AsyncSender, AsyncEventReceiver) {
+        let shared = Arc::new(Shared::default());
+        (AsyncSender(shared.clone()), AsyncEventReceiver(shared))
+    }
+
+    #[derive(Debug)]
+    pub(super) struct AsyncSender(Arc<Shared>);
+
+    impl AsyncSender {
+        /// Queues the event and wakes the receiver, returns `false` if it was dropped
+        pub(super) fn send(&self, event: &PoolEvent) -> bool {
+            if Arc::strong_count(&self.0) == 1 {
+                return false;
+            }
+            let mut queue = self.0.lock();
+            queue.events.push_back(event.clone());
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+            true
+        }
+    }
+
+    impl Drop for AsyncSender {
+        fn drop(&mut self) {
+            let mut queue = self.0.lock();
+            queue.closed = true;
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    /// Receiver of pool events usable from any async executor
+    pub struct AsyncEventReceiver(Arc<Shared>);
+
+    impl AsyncEventReceiver {
+        /// Waits for the next event, `None` once the pool was dropped and all events were
+        /// received
+        pub async fn recv(&mut self) -> Option<PoolEvent> {
+            poll_fn(|context| self.poll_recv(context)).await
+        }
+
+        /// Polls for the next event, registering the context to be woken by the next one
+        pub fn poll_recv(&mut self, context: &mut Context<'_>) -> Poll<Option<PoolEvent>> {
+            let mut queue = self.0.lock();
+            match queue.events.pop_front() {
+                Some(event) => Poll::Ready(Some(event)),
+                None if queue.closed => Poll::Ready(None),
+                None => {
+                    queue.waker = Some(context.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+
+        /// Returns the next already emitted event without waiting
+        pub fn try_recv(&mut self) -> Option<PoolEvent> {
+            self.0.lock().events.pop_front()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::PoolOp;
+
+    fn pool() -> LpPool {
+        LpPool::init(1.5.into(), 0.001.into(), 0.09.into(), 90.into()).unwrap()
+    }
+
+    #[test]
+    fn subscribers_receive_events_independently() {
+        let mut pool = pool();
+        pool.apply(&PoolOp::AdvanceEpoch { epochs: 1 }).unwrap();
+        let (metrics, persistence) = (pool.subscribe(), pool.subscribe());
+        pool.apply(&PoolOp::AddLiquidity {
+            account: None,
+            amount: 100.into(),
+        })
+        .unwrap();
+
+        let expected = PoolEvent::LiquidityAdded {
+            account: None,
+            amount: 100.into(),
+            lp_amount: 100.into(),
+        };
+        assert_eq!(metrics.try_recv(), Ok(expected.clone()));
+        assert!(metrics.try_recv().is_err());
+        assert_eq!(persistence.try_recv(), Ok(expected));
+        // subscriptions don't consume queued events
+        assert_eq!(pool.drain_events().len(), 2);
+
+        drop(persistence);
+        assert_eq!(pool.subscriber_count(), 2);
+        pool.apply(&PoolOp::Swap {
+            account: None,
+            amount: 6.into(),
+        })
+        .unwrap();
+        assert_eq!(pool.subscriber_count(), 1);
+        assert!(metrics.try_iter().count() > 0);
+        drop(pool);
+        assert!(metrics.recv().is_err());
+    }
+
+    #[cfg(feature = "async-events")]
+    #[test]
+    fn async_subscribers_await_events() {
+        use std::future::Future;
+        use std::pin::pin;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+
+        #[derive(Default)]
+        struct Flag(AtomicBool);
+
+        impl Wake for Flag {
+            fn wake(self: Arc<Self>) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let mut pool = pool();
+        let mut receiver = pool.subscribe_async();
+        let flag = Arc::new(Flag::default());
+        let waker = Waker::from(flag.clone());
+        let mut context = Context::from_waker(&waker);
+        {
+            let mut next = pin!(receiver.recv());
+            assert!(next.as_mut().poll(&mut context).is_pending());
+            pool.apply(&PoolOp::AdvanceEpoch { epochs: 2 }).unwrap();
+            assert!(flag.0.load(Ordering::SeqCst));
+            assert_eq!(
+                next.poll(&mut context),
+                Poll::Ready(Some(PoolEvent::EpochAdvanced {
+                    epochs: 2,
+                    epoch: 2
+                }))
+            );
+        }
+
+        pool.apply(&PoolOp::AdvanceEpoch { epochs: 1 }).unwrap();
+        drop(pool);
+        assert!(receiver.try_recv().is_some());
+        assert_eq!(receiver.poll_recv(&mut context), Poll::Ready(None));
+    }
+}
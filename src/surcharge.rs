@@ -0,0 +1,202 @@
+use crate::error::SchemaError;
+use crate::json::Value;
+use crate::schema::{field, FromJson, ToJson};
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Describes when an active surcharge decays
+pub enum SurchargeDecay {
+    /// decays after every successful pool operation
+    PerOperation,
+    /// decays every time the pool advances an epoch
+    PerEpoch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Configuration of the anti-sandwich fee surcharge
+pub struct SurchargeConfig {
+    /// swaps paying out at least this share of the pool's tokens are considered large
+    pub large_swap_threshold: Percentage,
+    /// fee added on top of the regular fee right after a large swap
+    pub surcharge: Percentage,
+    /// share of the active surcharge removed on every decay step
+    pub decay_rate: Percentage,
+    pub decay: SurchargeDecay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Temporary fee raised after large swaps that decays over subsequent operations/epochs,
+/// making sandwiching a large swap more expensive
+pub struct SwapSurcharge {
+    config: SurchargeConfig,
+    active: Percentage,
+}
+
+impl SwapSurcharge {
+    pub fn new(config: SurchargeConfig) -> Self {
+        Self {
+            config,
+            active: Percentage::from_raw_amount(0),
+        }
+    }
+
+    pub fn config(&self) -> &SurchargeConfig {
+        &self.config
+    }
+
+    /// Returns surcharge currently added to swap fees
+    pub fn active(&self) -> Percentage {
+        self.active
+    }
+
+    /// Raises the surcharge if the swap was large.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount_out` - tokens paid out by the swap
+    /// * `pool_tokens` - tokens held by the pool before the swap
+    pub fn on_swap(&mut self, amount_out: TokenAmount, pool_tokens: TokenAmount) {
+        if pool_tokens.raw() == 0 {
+            return;
+        }
+        let share = amount_out.raw() as u128 * SCALE as u128 / pool_tokens.raw() as u128;
+        if share >= self.config.large_swap_threshold.raw() as u128 {
+            self.active =
+                Percentage::from_raw_amount(self.active.raw().max(self.config.surcharge.raw()));
+        }
+    }
+
+    /// Decays the surcharge once if it decays per operation
+    pub fn on_operation(&mut self) {
+        if self.config.decay == SurchargeDecay::PerOperation {
+            self.decay(1);
+        }
+    }
+
+    /// Decays the surcharge once per passed epoch if it decays per epoch
+    pub fn on_epochs(&mut self, epochs: Epoch) {
+        if self.config.decay == SurchargeDecay::PerEpoch {
+            self.decay(epochs);
+        }
+    }
+
+    fn decay(&mut self, steps: Epoch) {
+        let retained = SCALE - self.config.decay_rate.raw().min(SCALE);
+        let mut active = self.active.raw();
+        for _ in 0..steps {
+            let decayed = (active as u128 * retained as u128 / SCALE as u128) as Uint;
+            // surcharge that stopped decaying stays the same for any amount of steps
+            if decayed == active {
+                break;
+            }
+            active = decayed;
+        }
+        self.active = Percentage::from_raw_amount(active);
+    }
+}
+
+impl ToJson for SurchargeDecay {
+    fn to_json(&self) -> Value {
+        match self {
+            SurchargeDecay::PerOperation => "per_operation".into(),
+            SurchargeDecay::PerEpoch => "per_epoch".into(),
+        }
+    }
+}
+
+impl FromJson for SurchargeDecay {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        match String::from_json(value)?.as_str() {
+            "per_operation" => Ok(SurchargeDecay::PerOperation),
+            "per_epoch" => Ok(SurchargeDecay::PerEpoch),
+            other => Err(SchemaError::InvalidValue {
+                path: String::new(),
+                reason: format!("unknown variant `{other}`"),
+            }),
+        }
+    }
+}
+
+impl ToJson for SurchargeConfig {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("large_swap_threshold", self.large_swap_threshold.to_json()),
+            ("surcharge", self.surcharge.to_json()),
+            ("decay_rate", self.decay_rate.to_json()),
+            ("decay", self.decay.to_json()),
+        ])
+    }
+}
+
+impl FromJson for SurchargeConfig {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        Ok(Self {
+            large_swap_threshold: field(value, "large_swap_threshold")?,
+            surcharge: field(value, "surcharge")?,
+            decay_rate: field(value, "decay_rate")?,
+            decay: field(value, "decay")?,
+        })
+    }
+}
+
+impl ToJson for SwapSurcharge {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("config", self.config.to_json()),
+            ("active", self.active.to_json()),
+        ])
+    }
+}
+
+impl FromJson for SwapSurcharge {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        Ok(Self {
+            config: field(value, "config")?,
+            active: field(value, "active")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn surcharge(decay: SurchargeDecay) -> SwapSurcharge {
+        SwapSurcharge::new(SurchargeConfig {
+            large_swap_threshold: 0.1.into(),
+            surcharge: 0.02.into(),
+            decay_rate: 0.5.into(),
+            decay,
+        })
+    }
+
+    #[test]
+    fn small_swaps_dont_raise_surcharge() {
+        let mut surcharge = surcharge(SurchargeDecay::PerOperation);
+        surcharge.on_swap(5.into(), 100.into());
+        assert_eq!(surcharge.active(), Percentage::from_raw_amount(0));
+    }
+
+    #[test]
+    fn decays_per_operation() {
+        let mut surcharge = surcharge(SurchargeDecay::PerOperation);
+        surcharge.on_swap(10.into(), 100.into());
+        assert_eq!(surcharge.active(), Percentage::from(0.02));
+
+        surcharge.on_epochs(5);
+        assert_eq!(surcharge.active(), Percentage::from(0.02));
+        surcharge.on_operation();
+        assert_eq!(surcharge.active(), Percentage::from(0.01));
+    }
+
+    #[test]
+    fn decays_per_epoch() {
+        let mut surcharge = surcharge(SurchargeDecay::PerEpoch);
+        surcharge.on_swap(50.into(), 100.into());
+
+        surcharge.on_operation();
+        assert_eq!(surcharge.active(), Percentage::from(0.02));
+        surcharge.on_epochs(2);
+        assert_eq!(surcharge.active(), Percentage::from(0.005));
+    }
+}
@@ -0,0 +1,202 @@
+//! Assertions of pool invariants and fixture pools for tests of code embedding `LpPool`,
+//! enabled by the `test-utils` feature. Assertions panic with the violated invariant, and
+//! sequences violating one are shrunk and printed as a ready-to-paste unit test, so the
+//! same checks the crate runs on itself can guard pools driven by downstream code.
+
+use crate::conservation::check_ops;
+use crate::equivalence::check_equivalent;
+use crate::governance::PoolParams;
+use crate::lp_pool::LpPool;
+use crate::ops::PoolOp;
+use crate::properties::check_invariants;
+use crate::replay::PoolConfig;
+use crate::schema::ToJson;
+use crate::shrinking::{shrink, unit_test};
+use crate::types::*;
+
+/// Configuration of the pool from the task description, price of 1.5, fees between
+/// 0.1% and 9% and liquidity target of 90 tokens
+pub const FIXTURE_CONFIG: PoolConfig = PoolConfig::new(
+    Price::from_raw_amount(1_500_000),
+    PoolParams {
+        min_fee: Percentage::from_basis_points(10),
+        max_fee: Percentage::from_basis_points(900),
+        liquidity_target: TokenAmount::from_units(90),
+    },
+);
+
+/// Returns `FIXTURE_CONFIG`
+pub fn fixture_config() -> PoolConfig {
+    FIXTURE_CONFIG
+}
+
+/// Pool built from `fixture_config` holding 100 tokens of anonymous liquidity
+pub fn fixture_pool() -> LpPool {
+    let mut pool = fixture_config().build();
+    pool.apply(&PoolOp::AddLiquidity {
+        account: None,
+        amount: 100.into(),
+    })
+    .expect("fixture deposit succeeds");
+    pool
+}
+
+/// Pool built from `fixture_config` whose liquidity was swapped below the target, so
+/// swaps are charged more than the minimal fee
+pub fn drained_pool() -> LpPool {
+    let mut pool = fixture_pool();
+    pool.apply(&PoolOp::Swap {
+        account: None,
+        amount: 30.into(),
+    })
+    .expect("fixture swap succeeds");
+    pool
+}
+
+/// Pool built from `fixture_config` with positions of accounts `0` and `1`, holding 60
+/// and 40 tokens of liquidity, and fees earned by both of them
+pub fn accounts_pool() -> LpPool {
+    let mut pool = fixture_config().build();
+    for op in [
+        PoolOp::AddLiquidity {
+            account: Some(0),
+            amount: 60.into(),
+        },
+        PoolOp::AddLiquidity {
+            account: Some(1),
+            amount: 40.into(),
+        },
+        PoolOp::Swap {
+            account: None,
+            amount: 10.into(),
+        },
+    ] {
+        pool.apply(&op).expect("fixture operation succeeds");
+    }
+    pool
+}
+
+/// Asserts that applying `ops` to `before` conserves value in every step and ends in the
+/// state of `after`. Operations are applied to a copy of `before` restored from its
+/// snapshot, so its oracle is replaced with the last accepted price.
+pub fn assert_conservation(before: &LpPool, ops: &[PoolOp], after: &LpPool) {
+    let copy = || LpPool::from_snapshot(&before.to_snapshot()).expect("snapshot restores");
+    let mut pool = copy();
+    if let Err(error) = check_ops(&mut pool, ops) {
+        let ops = shrink(ops.to_vec(), |ops| check_ops(&mut copy(), ops).is_err());
+        panic!("{error}, shortest violating sequence is {ops:?}");
+    }
+    assert!(
+        pool.to_json() == after.to_json(),
+        "applying {ops:?} ends in state\n{}\nbut expected state is\n{}",
+        pool.to_json().to_pretty_string(),
+        after.to_json().to_pretty_string()
+    );
+}
+
+/// Asserts that `ops` applied to a pool built from `config` keep every invariant
+/// checked by `check_invariants`. Violating sequences are shrunk and printed as a unit
+/// test.
+pub fn assert_invariants(config: &PoolConfig, ops: &[PoolOp]) {
+    let check = |ops: &[PoolOp]| check_invariants(&mut config.build(), ops);
+    if check(ops).is_err() {
+        let ops = shrink(ops.to_vec(), |ops| check(ops).is_err());
+        let error = check(&ops).expect_err("shrunk sequence still fails");
+        let test = unit_test(
+            "violates_invariants",
+            config,
+            &ops,
+            &["check_invariants(&mut pool, &ops).unwrap();".to_string()],
+        );
+        panic!("{error}, reproduced by\n{test}");
+    }
+}
+
+/// Asserts that pools built from both configurations return equal outcomes and keep
+/// equal balances for every operation, see `check_equivalent`. The first divergence is
+/// printed together with JSON documents of both pools before and after it.
+pub fn assert_equivalent(config_a: &PoolConfig, config_b: &PoolConfig, ops: &[PoolOp]) {
+    if let Err(error) = check_equivalent(config_a, config_b, ops) {
+        panic!(
+            "{error}\nfirst pool before\n{}\nafter\n{}\nsecond pool before\n{}\nafter\n{}",
+            error.before_a.to_pretty_string(),
+            error.after_a.to_pretty_string(),
+            error.before_b.to_pretty_string(),
+            error.after_b.to_pretty_string()
+        );
+    }
+}
+
+/// Asserts that pool restores into an equal pool from its snapshot and from its JSON
+/// document
+pub fn assert_restores(pool: &LpPool) {
+    let restored = LpPool::from_snapshot(&pool.to_snapshot()).expect("snapshot restores");
+    assert_eq!(restored.to_json(), pool.to_json(), "snapshot round trip");
+    let restored =
+        <LpPool as crate::schema::FromJson>::from_json(&pool.to_json()).expect("JSON restores");
+    assert_eq!(restored.to_json(), pool.to_json(), "JSON round trip");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    #[test]
+    fn fixtures_keep_invariants() {
+        for pool in [fixture_pool(), drained_pool(), accounts_pool()] {
+            assert_restores(&pool);
+        }
+        assert!(drained_pool().current_fee() > fixture_pool().current_fee());
+        assert_eq!(accounts_pool().positions().iter().count(), 2);
+
+        let ops = crate::properties::random_ops(&mut Rng::new(5), 40);
+        assert_invariants(&fixture_config(), &ops);
+    }
+
+    #[test]
+    fn asserts_conservation() {
+        let before = fixture_pool();
+        let ops = [
+            PoolOp::Swap {
+                account: None,
+                amount: 30.into(),
+            },
+            PoolOp::AdvanceEpoch { epochs: 1 },
+        ];
+        let mut after = fixture_pool();
+        for op in &ops {
+            after.apply(op).unwrap();
+        }
+        assert_conservation(&before, &ops, &after);
+    }
+
+    #[test]
+    #[should_panic(expected = "pools diverged on Swap")]
+    fn rejects_diverging_pools() {
+        let mut config = fixture_config();
+        config.params.min_fee = 0.002.into();
+        let ops = [
+            PoolOp::AddLiquidity {
+                account: None,
+                amount: 100.into(),
+            },
+            PoolOp::Swap {
+                account: None,
+                amount: 6.into(),
+            },
+        ];
+        assert_equivalent(&fixture_config(), &fixture_config(), &ops);
+        assert_equivalent(&fixture_config(), &config, &ops);
+    }
+
+    #[test]
+    #[should_panic(expected = "ends in state")]
+    fn rejects_diverging_end_state() {
+        let ops = [PoolOp::Swap {
+            account: None,
+            amount: 30.into(),
+        }];
+        assert_conservation(&fixture_pool(), &ops, &fixture_pool());
+    }
+}
@@ -0,0 +1,157 @@
+//! Queue delaying privileged actions by a number of epochs, so that users see a change
+//! coming and can react before it takes effect. Queued actions are identified by ids
+//! increasing from `0` and are either executed once their delay passed or cancelled.
+
+use crate::error::TimelockError;
+use crate::types::*;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Action waiting in the queue
+pub struct Queued<A> {
+    pub id: u64,
+    pub action: A,
+    pub queued_at: Epoch,
+    /// first epoch in which the action can be executed
+    pub executable_at: Epoch,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Queue of timelocked actions in the order they were queued
+pub struct TimelockQueue<A> {
+    delay: Epoch,
+    queued: Vec<Queued<A>>,
+    next_id: u64,
+}
+
+impl<A> TimelockQueue<A> {
+    pub fn new(delay: Epoch) -> Self {
+        Self {
+            delay,
+            queued: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn delay(&self) -> Epoch {
+        self.delay
+    }
+
+    /// Changes delay of actions queued from now on
+    pub fn set_delay(&mut self, delay: Epoch) {
+        self.delay = delay;
+    }
+
+    /// Returns actions waiting in the queue
+    pub fn queued(&self) -> &[Queued<A>] {
+        &self.queued
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Queued<A>> {
+        self.queued.iter().find(|queued| queued.id == id)
+    }
+
+    /// Queues action executable after the delay passes and returns it
+    pub fn queue(&mut self, action: A, current_epoch: Epoch) -> &Queued<A> {
+        self.push(Queued {
+            id: self.next_id,
+            action,
+            queued_at: current_epoch,
+            executable_at: current_epoch.saturating_add(self.delay),
+        })
+    }
+
+    /// Re-queues action with its original id and epochs, e.g. when restoring the queue.
+    /// Ids stop growing at `u64::MAX`.
+    pub(crate) fn push(&mut self, queued: Queued<A>) -> &Queued<A> {
+        self.next_id = self.next_id.max(queued.id.saturating_add(1));
+        self.queued.push(queued);
+        self.queued.last().expect("action was just queued")
+    }
+
+    fn position(&self, id: u64) -> Result<usize, TimelockError> {
+        self.queued
+            .iter()
+            .position(|queued| queued.id == id)
+            .ok_or(TimelockError::UnknownAction(id))
+    }
+
+    /// Removes action from the queue without executing it
+    pub fn cancel(&mut self, id: u64) -> Result<Queued<A>, TimelockError> {
+        let position = self.position(id)?;
+        Ok(self.queued.remove(position))
+    }
+
+    /// Removes action from the queue for execution if its delay already passed
+    pub fn execute(&mut self, id: u64, current_epoch: Epoch) -> Result<Queued<A>, TimelockError> {
+        let position = self.position(id)?;
+        let executable_at = self.queued[position].executable_at;
+        if current_epoch < executable_at {
+            return Err(TimelockError::NotReady {
+                id,
+                current_epoch,
+                executable_at,
+            });
+        }
+        Ok(self.queued.remove(position))
+    }
+
+    /// Removes every action whose delay already passed, in the order they were queued
+    pub fn take_ready(&mut self, current_epoch: Epoch) -> Vec<Queued<A>> {
+        let (ready, waiting) = std::mem::take(&mut self.queued)
+            .into_iter()
+            .partition(|queued| queued.executable_at <= current_epoch);
+        self.queued = waiting;
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn executes_actions_after_their_delay() {
+        let mut queue = TimelockQueue::new(2);
+        assert_eq!(queue.queue("a", 10).executable_at, 12);
+        queue.set_delay(5);
+        let b = queue.queue("b", 10).id;
+        queue.queue("c", 11);
+
+        assert!(matches!(
+            queue.execute(b, 14),
+            Err(TimelockError::NotReady {
+                id: 1,
+                current_epoch: 14,
+                executable_at: 15
+            })
+        ));
+        assert_eq!(queue.execute(b, 15).unwrap().action, "b");
+        assert!(matches!(
+            queue.execute(b, 15),
+            Err(TimelockError::UnknownAction(1))
+        ));
+        assert_eq!(queue.cancel(2).unwrap().action, "c");
+        assert_eq!(queue.queued().len(), 1);
+        // ids aren't reused after actions left the queue
+        assert_eq!(queue.queue("d", 20).id, 3);
+        let ready = queue.take_ready(25);
+        assert_eq!(
+            ready.iter().map(|queued| queued.action).collect::<Vec<_>>(),
+            ["a", "d"]
+        );
+        assert!(queue.queued().is_empty());
+    }
+
+    #[test]
+    fn restores_actions_with_largest_id() {
+        let mut queue = TimelockQueue::new(2);
+        queue.push(Queued {
+            id: u64::MAX,
+            action: "a",
+            queued_at: 0,
+            executable_at: 2,
+        });
+        queue.queue("b", 0);
+        assert_eq!(queue.queued().len(), 2);
+    }
+}
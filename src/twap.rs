@@ -0,0 +1,180 @@
+use std::collections::VecDeque;
+
+use crate::error::SchemaError;
+use crate::json::Value;
+use crate::schema::{field, FromJson, ToJson};
+use crate::types::*;
+
+/// default amount of checkpoints kept by the accumulator
+pub const DEFAULT_TWAP_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Checkpoint {
+    epoch: Epoch,
+    /// sum of raw prices weighted by amount of epochs they were active, up to `epoch`
+    cumulative: u128,
+    /// price active from `epoch` onwards
+    price: Price,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Time weighted average price accumulator. Every observation stores a checkpoint with the
+/// cumulative price, so averages over any window covered by the checkpoints can be queried.
+/// Time is measured in pool epochs.
+pub struct TwapAccumulator {
+    checkpoints: VecDeque<Checkpoint>,
+    capacity: usize,
+}
+
+impl TwapAccumulator {
+    pub fn new(epoch: Epoch, price: Price, capacity: usize) -> Self {
+        let mut checkpoints = VecDeque::with_capacity(capacity);
+        checkpoints.push_back(Checkpoint {
+            epoch,
+            cumulative: 0,
+            price,
+        });
+        Self {
+            checkpoints,
+            capacity: capacity.max(2),
+        }
+    }
+
+    fn last(&self) -> Checkpoint {
+        *self.checkpoints.back().expect("accumulator is never empty")
+    }
+
+    fn cumulative_at(checkpoint: Checkpoint, epoch: Epoch) -> u128 {
+        checkpoint.cumulative + checkpoint.price.raw() as u128 * (epoch - checkpoint.epoch) as u128
+    }
+
+    /// Records that `price` is active from `epoch` onwards. Observations have to be made in
+    /// non decreasing epoch order, older ones are ignored.
+    pub fn observe(&mut self, epoch: Epoch, price: Price) {
+        let last = self.last();
+        if epoch < last.epoch {
+            return;
+        }
+        let checkpoint = Checkpoint {
+            epoch,
+            cumulative: Self::cumulative_at(last, epoch),
+            price,
+        };
+        if epoch == last.epoch {
+            *self
+                .checkpoints
+                .back_mut()
+                .expect("accumulator is never empty") = checkpoint;
+            return;
+        }
+        if self.checkpoints.len() == self.capacity {
+            self.checkpoints.pop_front();
+        }
+        self.checkpoints.push_back(checkpoint);
+    }
+
+    /// Returns time weighted average price over the last `window` epochs ending at `now`.
+    /// Zero window returns the current price, `None` is returned if the checkpoints don't
+    /// cover the whole window.
+    pub fn twap(&self, now: Epoch, window: Epoch) -> Option<Price> {
+        let last = self.last();
+        if now < last.epoch {
+            return None;
+        }
+        if window == 0 {
+            return Some(last.price);
+        }
+        let start = now.checked_sub(window)?;
+        let start_checkpoint = *self
+            .checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.epoch <= start)?;
+
+        let total = Self::cumulative_at(last, now) - Self::cumulative_at(start_checkpoint, start);
+        let average = total / window as u128;
+        Some(Price::from_raw_amount(
+            average.min(Uint::MAX as u128) as Uint
+        ))
+    }
+}
+
+impl ToJson for TwapAccumulator {
+    fn to_json(&self) -> Value {
+        let checkpoints = self
+            .checkpoints
+            .iter()
+            .map(|checkpoint| {
+                Value::object([
+                    ("epoch", checkpoint.epoch.to_json()),
+                    ("cumulative", checkpoint.cumulative.to_json()),
+                    ("price", checkpoint.price.to_json()),
+                ])
+            })
+            .collect();
+        Value::object([
+            ("capacity", self.capacity.to_json()),
+            ("checkpoints", Value::Array(checkpoints)),
+        ])
+    }
+}
+
+impl FromJson for TwapAccumulator {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        let capacity: usize = field(value, "capacity")?;
+        let checkpoints = field::<Vec<Value>>(value, "checkpoints")?
+            .iter()
+            .map(|checkpoint| {
+                Ok(Checkpoint {
+                    epoch: field(checkpoint, "epoch")?,
+                    cumulative: field(checkpoint, "cumulative")?,
+                    price: field(checkpoint, "price")?,
+                })
+            })
+            .collect::<Result<VecDeque<_>, SchemaError>>()
+            .map_err(|error| error.in_field("checkpoints"))?;
+        if checkpoints.is_empty() {
+            return Err(SchemaError::InvalidValue {
+                path: "checkpoints".into(),
+                reason: "accumulator needs at least one checkpoint".into(),
+            });
+        }
+        Ok(Self {
+            checkpoints,
+            capacity: capacity.max(2),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_price_twap_equals_price() {
+        let accumulator = TwapAccumulator::new(0, 1.5.into(), DEFAULT_TWAP_CAPACITY);
+
+        assert_eq!(accumulator.twap(10, 5), Some(Price::from(1.5)));
+        assert_eq!(accumulator.twap(10, 0), Some(Price::from(1.5)));
+    }
+
+    #[test]
+    fn weights_prices_by_time() {
+        let mut accumulator = TwapAccumulator::new(0, 1.into(), DEFAULT_TWAP_CAPACITY);
+        accumulator.observe(3, 2.into());
+
+        // 3 epochs at 1 and 1 epoch at 2
+        assert_eq!(accumulator.twap(4, 4), Some(Price::from(1.25)));
+        assert_eq!(accumulator.twap(4, 1), Some(Price::from(2)));
+    }
+
+    #[test]
+    fn window_outside_of_history_has_no_twap() {
+        let mut accumulator = TwapAccumulator::new(5, 1.into(), 2);
+        accumulator.observe(6, 2.into());
+        accumulator.observe(7, 3.into());
+
+        assert_eq!(accumulator.twap(7, 10), None);
+        assert_eq!(accumulator.twap(8, 2), Some(Price::from(2.5)));
+    }
+}
@@ -1,51 +1,162 @@
 use duplicate::duplicate_item;
-use std::ops::{Add, Div, Mul, Sub};
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::FromStr;
+
+use crate::error::ParseAmountError;
 
 /// precision selected for our fixed-point decimals
 const PRECISION: i32 = 6;
 /// alias for u64, allows for easy swapping with other types like u128
 pub type Uint = u64;
 
+/// signed counterpart of `Uint`, wide enough to hold difference of any two `Uint` values
+pub type Int = i128;
+
+/// Identifier of an account interacting with the pool
+pub type AccountId = u64;
+
+/// Epoch counter used to measure time inside the pool
+pub type Epoch = u64;
+
 /// Scale factor of fixed-point decimals
 pub const SCALE: Uint = 10u32.pow(PRECISION as u32) as Uint;
 
 #[inline(always)]
-/// floating point numbers don't support const functions right now so we need separate function to
-/// calculate correct multiplier. This should effectively be optimized by the compiler into const f64
-pub fn f64_precision_multiplier() -> f64 {
+/// multiplier converting floating point numbers into fixed-point decimals
+pub const fn f64_precision_multiplier() -> f64 {
     SCALE as f64
 }
 
-#[derive(Debug, PartialEq, Clone, Copy, PartialOrd)]
+/// Returns `a * b / c` rounded down, `None` if `c` is zero or the quotient doesn't fit
+/// into `Uint`. Branches on whether the product fits into `Uint`: such products are
+/// divided without widening, bigger ones take the outlined `u128` path. The
+/// `mul_div/*` and `mul_div_u128/*` benchmarks in `benches/pool.rs` compare both paths
+/// against always widening, run `cargo bench -- mul_div` before relying on either being
+/// cheaper.
+#[inline]
+pub const fn mul_div(a: Uint, b: Uint, c: Uint) -> Option<Uint> {
+    match a.checked_mul(b) {
+        Some(product) => product.checked_div(c),
+        None => mul_div_wide(a, b, c),
+    }
+}
+
+#[cold]
+#[inline(never)]
+const fn mul_div_wide(a: Uint, b: Uint, c: Uint) -> Option<Uint> {
+    match (a as u128 * b as u128).checked_div(c as u128) {
+        Some(quotient) if quotient <= Uint::MAX as u128 => Some(quotient as Uint),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, PartialOrd, Default)]
 /// Token Amount in fixed-point decimal format
 pub struct TokenAmount(Uint);
 
-#[derive(Debug, PartialEq, Clone, Copy, PartialOrd)]
+#[derive(Debug, PartialEq, Clone, Copy, PartialOrd, Default)]
 /// Staked Token Amount in fixed-point decimal format
 pub struct StakedTokenAmount(Uint);
 
-#[derive(Debug, PartialEq, Clone, Copy, PartialOrd)]
+#[derive(Debug, PartialEq, Clone, Copy, PartialOrd, Default)]
 /// Lp Token Amount in fixed-point decimal format
 pub struct LpTokenAmount(Uint);
 
-#[derive(Debug, PartialEq, Clone, Copy, PartialOrd)]
+#[derive(Debug, PartialEq, Clone, Copy, PartialOrd, Default)]
 /// Price of StakedToken in respect to Token in fixed-point decimal format
 pub struct Price(Uint);
 
-#[derive(Debug, PartialEq, Clone, Copy, PartialOrd)]
+#[derive(Debug, PartialEq, Clone, Copy, PartialOrd, Default)]
 /// Percentage in fixed-point decimal format
 pub struct Percentage(Uint);
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Default)]
+/// Signed Token Amount in fixed-point decimal format, used for deltas and profit/loss
+pub struct SignedTokenAmount(Int);
+
+impl SignedTokenAmount {
+    /// takes value as minimal precision units and wraps it into the struct
+    pub const fn from_raw_amount(value: Int) -> Self {
+        Self(value)
+    }
+    /// returns raw fixed point value
+    pub const fn raw(&self) -> Int {
+        self.0
+    }
+    /// returns `lhs - rhs` as signed amount
+    pub fn difference(lhs: TokenAmount, rhs: TokenAmount) -> Self {
+        Self(lhs.raw() as Int - rhs.raw() as Int)
+    }
+    pub fn is_negative(&self) -> bool {
+        self.0 < 0
+    }
+}
+
+impl From<TokenAmount> for SignedTokenAmount {
+    fn from(value: TokenAmount) -> Self {
+        Self(value.raw() as Int)
+    }
+}
+
+impl Add for SignedTokenAmount {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Sub for SignedTokenAmount {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl Neg for SignedTokenAmount {
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        Self(-self.0)
+    }
+}
+
 impl TokenAmount {
     /// Applies fee and returns remaining amount
     pub fn apply_fee(&self, fee: Percentage) -> TokenAmount {
-        TokenAmount::from_raw_amount(self.0 * (SCALE - fee.raw()) / SCALE)
+        let retained = SCALE.saturating_sub(fee.raw());
+        // retained share is at most 100%, so the result never exceeds the amount
+        TokenAmount::from_raw_amount(mul_div(self.0, retained, SCALE).unwrap_or(self.0))
     }
 }
 
 impl StakedTokenAmount {
+    /// Returns value of the staked tokens at the given price, panics if it doesn't fit into
+    /// `Uint`
     pub fn into_token_amount(self, price: Price) -> TokenAmount {
-        TokenAmount::from_raw_amount(self.raw() * price.raw() / SCALE)
+        self.checked_into_token_amount(price)
+            .expect("value of staked tokens overflows")
+    }
+
+    /// Returns value of the staked tokens at the given price, `None` if it doesn't fit into
+    /// `Uint`
+    pub fn checked_into_token_amount(self, price: Price) -> Option<TokenAmount> {
+        mul_div(self.raw(), price.raw(), SCALE).map(TokenAmount::from_raw_amount)
+    }
+}
+
+impl Price {
+    /// Converts `mantissa * 10^exponent` into fixed-point price, digits below the precision
+    /// are truncated. Returns `None` if the price doesn't fit into `Uint`.
+    pub fn from_decimal(mantissa: u64, exponent: i32) -> Option<Self> {
+        let shift = exponent + PRECISION;
+        let value = if shift >= 0 {
+            (mantissa as u128).checked_mul(10u128.checked_pow(shift as u32)?)?
+        } else {
+            10u128
+                .checked_pow(shift.unsigned_abs())
+                .map_or(0, |divisor| mantissa as u128 / divisor)
+        };
+        Uint::try_from(value).ok().map(Self)
     }
 }
 
@@ -69,13 +180,53 @@ impl LpTokenAmount {
 #[duplicate_item(ImplName; [TokenAmount]; [StakedTokenAmount]; [LpTokenAmount]; [Price]; [Percentage])]
 impl ImplName {
     /// takes value as minimal precision units (based on fixed-point decimal precision) and wraps it into appropriate struct
-    pub fn from_raw_amount(value: Uint) -> Self {
+    pub const fn from_raw_amount(value: Uint) -> Self {
         Self(value)
     }
     /// returns raw fixed point value
-    pub fn raw(&self) -> Uint {
+    pub const fn raw(&self) -> Uint {
         self.0
     }
+    /// adds amounts, saturating at `Uint::MAX` instead of overflowing
+    pub const fn saturating_add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+    /// adds amounts, `None` on overflow
+    pub const fn checked_add(self, rhs: Self) -> Option<Self> {
+        match self.0.checked_add(rhs.0) {
+            Some(value) => Some(Self(value)),
+            None => None,
+        }
+    }
+    /// subtracts amounts, `None` if `rhs` is bigger
+    pub const fn checked_sub(self, rhs: Self) -> Option<Self> {
+        match self.0.checked_sub(rhs.0) {
+            Some(value) => Some(Self(value)),
+            None => None,
+        }
+    }
+    /// multiplies fixed-point decimals like `Mul`, `None` if the product doesn't fit
+    pub const fn checked_mul(self, rhs: Self) -> Option<Self> {
+        match mul_div(self.0, rhs.0, SCALE) {
+            Some(value) => Some(Self(value)),
+            None => None,
+        }
+    }
+}
+
+#[duplicate_item(ImplName; [TokenAmount]; [StakedTokenAmount]; [LpTokenAmount]; [Price])]
+impl ImplName {
+    /// const counterpart of `From<Uint>`, takes value in whole units
+    pub const fn from_units(value: Uint) -> Self {
+        Self(value * SCALE)
+    }
+}
+
+impl Percentage {
+    /// takes value in basis points (hundredths of a percent), e.g. `9` is 0.09%
+    pub const fn from_basis_points(basis_points: Uint) -> Self {
+        Self(basis_points * (SCALE / 10_000))
+    }
 }
 
 #[duplicate_item(ImplName; [TokenAmount]; [StakedTokenAmount]; [LpTokenAmount]; [Price])]
@@ -94,6 +245,34 @@ impl From<f64> for ImplName {
     }
 }
 
+#[duplicate_item(ImplName; [TokenAmount]; [StakedTokenAmount]; [LpTokenAmount]; [Price]; [Percentage])]
+impl fmt::Display for ImplName {
+    /// formats value as a decimal with full precision, e.g. `1.500000`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = PRECISION as usize;
+        write!(f, "{}.{:0digits$}", self.0 / SCALE, self.0 % SCALE)
+    }
+}
+
+#[duplicate_item(ImplName; [TokenAmount]; [StakedTokenAmount]; [LpTokenAmount]; [Price]; [Percentage])]
+impl FromStr for ImplName {
+    type Err = ParseAmountError;
+
+    /// parses decimal literal like `12.5` exactly, digits below the precision are truncated
+    fn from_str(literal: &str) -> Result<Self, Self::Err> {
+        let error = || ParseAmountError(literal.into());
+        let (whole, fraction) = literal.split_once('.').unwrap_or((literal, ""));
+        let digits = format!("{whole}{fraction}");
+        if digits.is_empty() || !digits.chars().all(|char| char.is_ascii_digit()) {
+            return Err(error());
+        }
+        let mantissa = digits.parse().map_err(|_| error())?;
+        Price::from_decimal(mantissa, -(fraction.len() as i32))
+            .map(|price| Self(price.0))
+            .ok_or_else(error)
+    }
+}
+
 //////////////////////
 /// MATH OPERATORS ///
 //////////////////////
@@ -154,6 +333,17 @@ mod tests {
         assert_eq!(uint_token, f64_token);
     }
 
+    #[test]
+    fn parses_and_formats_decimals() {
+        assert_eq!("12.5".parse(), Ok(TokenAmount::from(12.5)));
+        assert_eq!("4.35".parse(), Ok(Price::from_raw_amount(4_350_000)));
+        assert_eq!("7".parse(), Ok(StakedTokenAmount::from(7)));
+        assert!("1.2.3".parse::<TokenAmount>().is_err());
+        assert!("-1".parse::<TokenAmount>().is_err());
+        assert_eq!(Price::from(1.5).to_string(), "1.500000");
+        assert_eq!(Percentage::from(0.003).to_string(), "0.003000");
+    }
+
     #[test]
     fn can_calculate_percentage() {
         // 10%
@@ -171,4 +361,58 @@ mod tests {
 
         assert_eq!(in_tokens.raw(), TokenAmount::from(1.5).raw());
     }
+
+    #[test]
+    fn can_calculate_signed_difference() {
+        let loss = SignedTokenAmount::difference(1.into(), 3.into());
+        assert!(loss.is_negative());
+        assert_eq!(-loss, SignedTokenAmount::from(TokenAmount::from(2)));
+    }
+
+    #[test]
+    fn can_create_price_from_decimal() {
+        assert_eq!(Price::from_decimal(15, -1), Some(Price::from(1.5)));
+        assert_eq!(Price::from_decimal(150_000_000, -8), Some(Price::from(1.5)));
+        assert_eq!(Price::from_decimal(3, 2), Some(Price::from(300)));
+        assert_eq!(Price::from_decimal(1, -7), Some(Price::from_raw_amount(0)));
+        assert_eq!(Price::from_decimal(u64::MAX, 1), None);
+    }
+
+    #[test]
+    fn mul_div_matches_widened_math() {
+        let mut rng = crate::rng::Rng::new(3);
+        for _ in 0..10_000 {
+            let bits = rng.range(1, 64) as u32;
+            let mut value = || rng.next_u64() >> (64 - bits);
+            let (a, b, c) = (value(), value(), value());
+            let widened = (a as u128 * b as u128).checked_div(c as u128);
+            assert_eq!(
+                mul_div(a, b, c),
+                widened.and_then(|quotient| Uint::try_from(quotient).ok()),
+                "{a} {b} {c}"
+            );
+        }
+        assert_eq!(mul_div(Uint::MAX, Uint::MAX, Uint::MAX), Some(Uint::MAX));
+        assert_eq!(mul_div(Uint::MAX, 2, 1), None);
+        assert_eq!(mul_div(1, 1, 0), None);
+    }
+
+    #[test]
+    fn constructs_amounts_in_const_contexts() {
+        const FEE: Percentage = Percentage::from_basis_points(9);
+        const VALUE: Option<TokenAmount> =
+            TokenAmount::from_units(3).checked_mul(TokenAmount::from_raw_amount(SCALE / 2));
+        assert_eq!(FEE, Percentage::from(0.0009));
+        assert_eq!(VALUE, Some(TokenAmount::from(1.5)));
+        assert_eq!(Price::from_units(2), Price::from(2));
+        assert_eq!(
+            TokenAmount::from_units(1).checked_sub(TokenAmount::from_units(2)),
+            None
+        );
+        assert_eq!(
+            LpTokenAmount::from_raw_amount(Uint::MAX)
+                .checked_add(LpTokenAmount::from_raw_amount(1)),
+            None
+        );
+    }
 }
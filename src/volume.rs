@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+
+use crate::error::SchemaError;
+use crate::json::Value;
+use crate::schema::{field, FromJson, ToJson};
+use crate::types::*;
+
+/// amount of epochs in a year assuming ~2 day long Solana epochs
+pub const EPOCHS_PER_YEAR: Epoch = 182;
+/// default amount of epochs for which volume is recorded
+pub const DEFAULT_VOLUME_HISTORY_EPOCHS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Swap volume recorded during a single epoch
+pub struct EpochVolume {
+    pub epoch: Epoch,
+    /// staked tokens swapped into the pool
+    pub staked_volume: StakedTokenAmount,
+    /// value of swapped staked tokens before fees were applied
+    pub token_volume: TokenAmount,
+    pub swap_count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Records swap volume per epoch for a bounded amount of most recent epochs, volumes
+/// saturate at `Uint::MAX` instead of rejecting swaps
+pub struct VolumeHistory {
+    epochs: VecDeque<EpochVolume>,
+    capacity: usize,
+}
+
+impl VolumeHistory {
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            // allocated upfront so that recording swaps never allocates
+            #[cfg(feature = "bounded-events")]
+            epochs: VecDeque::with_capacity(capacity),
+            #[cfg(not(feature = "bounded-events"))]
+            epochs: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Records swap executed in the given epoch
+    pub fn record_swap(
+        &mut self,
+        epoch: Epoch,
+        staked_amount: StakedTokenAmount,
+        token_value: TokenAmount,
+    ) {
+        if self.epochs.back().map(|volume| volume.epoch) != Some(epoch) {
+            if self.epochs.len() == self.capacity {
+                self.epochs.pop_front();
+            }
+            self.epochs.push_back(EpochVolume {
+                epoch,
+                ..Default::default()
+            });
+        }
+
+        let volume = self
+            .epochs
+            .back_mut()
+            .expect("volume entry was just ensured");
+        volume.staked_volume = volume.staked_volume.saturating_add(staked_amount);
+        volume.token_volume = volume.token_volume.saturating_add(token_value);
+        volume.swap_count = volume.swap_count.saturating_add(1);
+    }
+
+    /// Returns recorded epochs from the oldest to the newest
+    pub fn iter(&self) -> impl Iterator<Item = &EpochVolume> {
+        self.epochs.iter()
+    }
+
+    /// Returns volume summed over `window` epochs ending with `current_epoch` (inclusive)
+    pub fn in_window(&self, current_epoch: Epoch, window: Epoch) -> EpochVolume {
+        let first_epoch = current_epoch.saturating_add(1).saturating_sub(window);
+        self.epochs
+            .iter()
+            .filter(|volume| volume.epoch >= first_epoch && volume.epoch <= current_epoch)
+            .fold(
+                EpochVolume {
+                    epoch: current_epoch,
+                    ..Default::default()
+                },
+                |total, volume| EpochVolume {
+                    epoch: total.epoch,
+                    staked_volume: total.staked_volume.saturating_add(volume.staked_volume),
+                    token_volume: total.token_volume.saturating_add(volume.token_volume),
+                    swap_count: total.swap_count.saturating_add(volume.swap_count),
+                },
+            )
+    }
+}
+
+impl Default for VolumeHistory {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_VOLUME_HISTORY_EPOCHS)
+    }
+}
+
+impl ToJson for EpochVolume {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("epoch", self.epoch.to_json()),
+            ("staked_volume", self.staked_volume.to_json()),
+            ("token_volume", self.token_volume.to_json()),
+            ("swap_count", self.swap_count.to_json()),
+        ])
+    }
+}
+
+impl FromJson for EpochVolume {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        Ok(Self {
+            epoch: field(value, "epoch")?,
+            staked_volume: field(value, "staked_volume")?,
+            token_volume: field(value, "token_volume")?,
+            swap_count: field(value, "swap_count")?,
+        })
+    }
+}
+
+impl ToJson for VolumeHistory {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("capacity", self.capacity.to_json()),
+            (
+                "epochs",
+                self.epochs.iter().copied().collect::<Vec<_>>().to_json(),
+            ),
+        ])
+    }
+}
+
+impl FromJson for VolumeHistory {
+    fn from_json(value: &Value) -> Result<Self, SchemaError> {
+        let mut history = Self::with_capacity(field(value, "capacity")?);
+        history.epochs = field::<Vec<EpochVolume>>(value, "epochs")?.into();
+        while history.epochs.len() > history.capacity {
+            history.epochs.pop_front();
+        }
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_swaps_by_epoch() {
+        let mut history = VolumeHistory::default();
+        history.record_swap(1, 1.into(), 2.into());
+        history.record_swap(1, 1.into(), 2.into());
+        history.record_swap(3, 2.into(), 4.into());
+
+        let epochs: Vec<_> = history.iter().map(|v| (v.epoch, v.swap_count)).collect();
+        assert_eq!(epochs, vec![(1, 2), (3, 1)]);
+    }
+
+    #[test]
+    fn sums_volume_in_window() {
+        let mut history = VolumeHistory::default();
+        history.record_swap(1, 1.into(), 2.into());
+        history.record_swap(2, 1.into(), 2.into());
+        history.record_swap(3, 2.into(), 4.into());
+
+        let volume = history.in_window(3, 2);
+        assert_eq!(volume.token_volume, TokenAmount::from(6));
+        assert_eq!(volume.swap_count, 2);
+    }
+
+    #[test]
+    fn drops_oldest_epochs() {
+        let mut history = VolumeHistory::with_capacity(1);
+        history.record_swap(1, 1.into(), 2.into());
+        history.record_swap(2, 1.into(), 2.into());
+
+        assert_eq!(history.in_window(2, 10).swap_count, 1);
+    }
+}
@@ -0,0 +1,134 @@
+//! Pool API shaped for wasm-bindgen exports. Amounts cross the boundary as decimal
+//! strings, e.g. `"1.5"`, because JavaScript numbers can't represent every fixed-point
+//! value exactly, and errors are returned as messages JavaScript can throw.
+
+use crate::lp_pool::LpPool;
+use crate::types::*;
+
+/// Parses decimal string argument into an amount
+fn parse<T: std::str::FromStr<Err = crate::error::ParseAmountError>>(
+    amount: &str,
+) -> Result<T, String> {
+    amount
+        .parse()
+        .map_err(|error: crate::error::ParseAmountError| error.to_string())
+}
+
+#[derive(Debug)]
+/// Pool exported to JavaScript
+pub struct WasmPool {
+    pool: LpPool,
+}
+
+impl WasmPool {
+    /// Creates pool, every argument is a decimal string
+    pub fn new(
+        price: &str,
+        min_fee: &str,
+        max_fee: &str,
+        liquidity_target: &str,
+    ) -> Result<WasmPool, String> {
+        let pool = LpPool::init(
+            parse(price)?,
+            parse(min_fee)?,
+            parse(max_fee)?,
+            parse(liquidity_target)?,
+        )
+        .unwrap_or_else(|never| match never {});
+        Ok(Self { pool })
+    }
+
+    /// Returns tokens granted for swapped staked tokens without modifying the pool
+    pub fn quote_swap(&self, amount: &str) -> Result<String, String> {
+        let amount: StakedTokenAmount = parse(amount)?;
+        self.pool
+            .amount_for_swap(amount)
+            .map(|amount| amount.to_string())
+            .map_err(|error| error.to_string())
+    }
+
+    /// Returns fee charged for swapped staked tokens without modifying the pool
+    pub fn quote_fee(&self, amount: &str) -> Result<String, String> {
+        let amount: StakedTokenAmount = parse(amount)?;
+        self.pool
+            .fee_for_swap(amount)
+            .map(|fee| fee.to_string())
+            .map_err(|error| error.to_string())
+    }
+
+    pub fn swap(&mut self, amount: &str) -> Result<String, String> {
+        let amount: StakedTokenAmount = parse(amount)?;
+        self.pool
+            .swap(amount)
+            .map(|amount| amount.to_string())
+            .map_err(|error| error.to_string())
+    }
+
+    pub fn add_liquidity(&mut self, amount: &str) -> Result<String, String> {
+        let amount: TokenAmount = parse(amount)?;
+        self.pool
+            .add_liquidity(amount)
+            .map(|lp_amount| lp_amount.to_string())
+            .map_err(|error| error.to_string())
+    }
+
+    /// Returns withdrawn tokens and staked tokens
+    pub fn remove_liquidity(&mut self, lp_amount: &str) -> Result<Vec<String>, String> {
+        let lp_amount: LpTokenAmount = parse(lp_amount)?;
+        self.pool
+            .remove_liquidity(lp_amount)
+            .map(|(tokens, staked_tokens)| vec![tokens.to_string(), staked_tokens.to_string()])
+            .map_err(|error| error.to_string())
+    }
+
+    pub fn set_price(&mut self, price: &str) -> Result<(), String> {
+        self.pool
+            .set_price(parse(price)?)
+            .map_err(|error| error.to_string())
+    }
+
+    pub fn price(&self) -> String {
+        self.pool.price().to_string()
+    }
+
+    pub fn token_amount(&self) -> String {
+        self.pool.token_amount().to_string()
+    }
+
+    pub fn st_token_amount(&self) -> String {
+        self.pool.st_token_amount().to_string()
+    }
+
+    pub fn lp_token_amount(&self) -> String {
+        self.pool.lp_token_amount().to_string()
+    }
+
+    pub fn current_fee(&self) -> String {
+        self.pool.current_fee().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exposes_exact_string_amounts() {
+        let mut pool = WasmPool::new("1.5", "0.001", "0.09", "90").unwrap();
+        assert_eq!(pool.add_liquidity("100").unwrap(), "100.000000");
+        assert_eq!(pool.quote_fee("6").unwrap(), "0.001000");
+
+        let quote = pool.quote_swap("6").unwrap();
+        assert_eq!(pool.swap("6").unwrap(), quote);
+        assert_eq!(quote, "8.991000");
+        assert_eq!(pool.st_token_amount(), "6.000000");
+        assert_eq!(
+            pool.remove_liquidity("10").unwrap(),
+            vec!["9.100900", "0.600000"]
+        );
+
+        assert!(pool.swap("1e3").is_err());
+        assert!(pool.swap("0").unwrap_err().contains("Zero tokens"));
+        assert!(WasmPool::new("x", "0", "0", "1").is_err());
+    }
+}